@@ -0,0 +1,9 @@
+//! Decodes raw bytes as a bytecode container, running any module in it
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stack_vm_jit::vm::fuzz;
+
+fuzz_target!(|bytes: &[u8]| {
+    let _ = fuzz::run_bytes(bytes);
+});
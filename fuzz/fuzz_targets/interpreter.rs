@@ -0,0 +1,10 @@
+//! Runs modules built to pass the verifier, so most inputs reach the
+//! interpreter
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stack_vm_jit::vm::fuzz::{self, FuzzModule};
+
+fuzz_target!(|module: FuzzModule| {
+    let _ = fuzz::run_module(module.into_module());
+});
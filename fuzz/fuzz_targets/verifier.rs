@@ -0,0 +1,12 @@
+//! Verifies arbitrary instructions and constants, running whatever passes
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use stack_vm_jit::vm::fuzz;
+use stack_vm_jit::vm::instruction::Instruction;
+use stack_vm_jit::vm::types::Value;
+
+fuzz_target!(|input: (Vec<Instruction>, Vec<Value>)| {
+    let (instructions, constants) = input;
+    let _ = fuzz::run_verified(instructions, constants);
+});
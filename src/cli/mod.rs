@@ -0,0 +1,486 @@
+//! Subcommands of the `stack-vm-jit` binary that work on programs read
+//! from disk, as opposed to the built-in demos in `main.rs`.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use stack_vm_jit::vm::assembler::{mnemonic, Assembler, AssemblerError, Disassembler, DisassemblerError};
+use stack_vm_jit::vm::bench::{self, BenchError};
+use stack_vm_jit::vm::container::CONTAINER_MAGIC;
+use stack_vm_jit::vm::debugger::Debugger;
+use stack_vm_jit::vm::io::StdIo;
+use stack_vm_jit::vm::jit::HotSpotProfiler;
+use stack_vm_jit::vm::module::{BytecodeModule, DebugInfo, ModuleError};
+use stack_vm_jit::vm::repl::{Repl, ReplMode};
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::stdlib;
+use stack_vm_jit::vm::trace::{TraceError, TraceWriter};
+
+/// Why a subcommand failed; printed by `main` before exiting with an error
+/// status
+#[derive(Debug)]
+pub enum CliError {
+    /// The command line was wrong; the message says how
+    Usage(String),
+    Io { path: String, error: io::Error },
+    Assembly { path: String, error: AssemblerError },
+    Module { path: String, error: ModuleError },
+    Disassembly { path: String, error: DisassemblerError },
+    Execution(VmError),
+    Benchmark(Box<BenchError>),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Usage(message) => write!(f, "{}", message),
+            CliError::Io { path, error } => write!(f, "{}: {}", path, error),
+            CliError::Assembly { path, error } => write!(f, "{}: {}", path, error),
+            CliError::Module { path, error } => write!(f, "{}: {}", path, error),
+            CliError::Disassembly { path, error } => write!(f, "{}: {}", path, error),
+            // Execution errors carry their own traceback
+            CliError::Execution(error) => write!(f, "{:#}", error),
+            CliError::Benchmark(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for CliError {}
+
+impl From<VmError> for CliError {
+    fn from(error: VmError) -> Self {
+        CliError::Execution(error)
+    }
+}
+
+impl From<BenchError> for CliError {
+    fn from(error: BenchError) -> Self {
+        CliError::Benchmark(Box::new(error))
+    }
+}
+
+impl From<TraceError> for CliError {
+    fn from(error: TraceError) -> Self {
+        match error {
+            TraceError::Io(error) => io_error("<stderr>", error),
+            TraceError::Execution(error) => CliError::Execution(error),
+        }
+    }
+}
+
+/// Command-line arguments of a subcommand, split into positional
+/// arguments, flags and options that take a value
+#[derive(Debug, Default)]
+pub struct Args {
+    positional: Vec<String>,
+    flags: Vec<String>,
+    options: Vec<(String, String)>,
+}
+
+impl Args {
+    /// Parses `args`, accepting only the given `flags` and `options`; an
+    /// option takes the argument after it as its value
+    pub fn parse(
+        args: impl IntoIterator<Item = String>,
+        flags: &[&str],
+        options: &[&str],
+    ) -> Result<Self, CliError> {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if options.contains(&arg.as_str()) {
+                let value = args
+                    .next()
+                    .ok_or_else(|| CliError::Usage(format!("{} needs a value", arg)))?;
+                parsed.options.push((arg, value));
+            } else if flags.contains(&arg.as_str()) {
+                parsed.flags.push(arg);
+            } else if arg.starts_with('-') && arg != "-" {
+                return Err(CliError::Usage(format!("Unknown option {}", arg)));
+            } else {
+                parsed.positional.push(arg);
+            }
+        }
+        Ok(parsed)
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    /// Value of the last of `names` given, so `-o` and `--output` can
+    /// stand for each other
+    pub fn option(&self, names: &[&str]) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(name, _)| names.contains(&name.as_str()))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The one file a subcommand works on
+    pub fn input(&self, usage: &str) -> Result<&str, CliError> {
+        match self.positional.as_slice() {
+            [input] => Ok(input),
+            _ => Err(CliError::Usage(format!("Usage: {}", usage))),
+        }
+    }
+
+    /// Fails if a subcommand that works on no file was given one
+    pub fn no_input(&self, usage: &str) -> Result<(), CliError> {
+        match self.positional.as_slice() {
+            [] => Ok(()),
+            _ => Err(CliError::Usage(format!("Usage: {}", usage))),
+        }
+    }
+}
+
+/// Reads the module in `path`: assembly source if it ends in `.asm`, and
+/// otherwise a bytecode container or, with the `serde` feature, a module
+/// in the JSON layout of [`BytecodeModule::to_json_bytes`]
+pub fn load_module(path: &str) -> Result<BytecodeModule, CliError> {
+    if is_assembly(path) {
+        let source = fs::read_to_string(path).map_err(|error| io_error(path, error))?;
+        return Assembler::new()
+            .with_source_name(path)
+            .assemble_module(&source)
+            .map_err(|error| CliError::Assembly { path: path.to_string(), error });
+    }
+
+    let bytes = fs::read(path).map_err(|error| io_error(path, error))?;
+    let module = if bytes.starts_with(&CONTAINER_MAGIC) {
+        BytecodeModule::from_bytes(&bytes)
+    } else {
+        from_json(&bytes)
+    };
+    module.map_err(|error| CliError::Module { path: path.to_string(), error })
+}
+
+fn is_assembly(path: &str) -> bool {
+    Path::new(path).extension().is_some_and(|extension| extension == "asm")
+}
+
+#[cfg(feature = "serde")]
+fn from_json(bytes: &[u8]) -> Result<BytecodeModule, ModuleError> {
+    BytecodeModule::from_json_bytes(bytes)
+}
+
+#[cfg(not(feature = "serde"))]
+fn from_json(_bytes: &[u8]) -> Result<BytecodeModule, ModuleError> {
+    Err(ModuleError::InvalidContainer("Not a bytecode container".to_string()))
+}
+
+fn io_error(path: &str, error: io::Error) -> CliError {
+    CliError::Io { path: path.to_string(), error }
+}
+
+/// A VM for running `module` as a program: it has the process's streams
+/// and the `print`/`println`/`read_line` host functions, runs until the
+/// program ends, and links the standard library if the module imports
+/// from it
+pub fn program_vm(module: BytecodeModule) -> Result<VirtualMachine, CliError> {
+    let mut vm = VirtualMachine::builder()
+        .with_max_instructions(u64::MAX)
+        .with_io(StdIo)
+        .build();
+    let library = stdlib::module();
+    if module.externs.iter().any(|reference| library.function(&reference.name).is_some()) {
+        vm.load_modules([module, library])?;
+    } else {
+        vm.load_module(module)?;
+    }
+    Ok(vm)
+}
+
+pub const RUN_USAGE: &str =
+    "stack-vm-jit run <program.asm|program.bc> [--stats] [--stats-json] [--trace] [--jit]";
+
+/// `run FILE`: loads the program, runs it to the end and prints the value
+/// it leaves on the stack, if any. `--trace` writes a row for every
+/// instruction to standard error as it runs, and `--jit` runs it with the
+/// JIT's hot-spot profiler on. `--stats` writes statistics for people to
+/// standard error; `--stats-json` prints them as JSON to standard output,
+/// after the value.
+pub fn run(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--stats", "--stats-json", "--trace", "--jit"], &[])?;
+    let mut vm = program_vm(load_module(args.input(RUN_USAGE)?)?)?;
+    if args.flag("--jit") {
+        vm.enable_profiling();
+    }
+    let start = Instant::now();
+    if args.flag("--trace") {
+        trace(&mut vm)?;
+    } else {
+        vm.run()?;
+    }
+    let elapsed = start.elapsed();
+
+    if let Ok(value) = vm.stack_top() {
+        println!("{}", value);
+    }
+    if args.flag("--stats") {
+        print_statistics(&vm, elapsed.as_secs_f64());
+    }
+    if args.flag("--stats-json") {
+        println!("{}", statistics_json(&vm, elapsed)?);
+    }
+    Ok(())
+}
+
+/// [`VirtualMachine::statistics`] as JSON, timed by the caller since
+/// tracing steps the VM without running it
+#[cfg(feature = "serde")]
+fn statistics_json(vm: &VirtualMachine, elapsed: Duration) -> Result<String, CliError> {
+    let mut statistics = vm.statistics();
+    let seconds = elapsed.as_secs_f64();
+    statistics.run_time_seconds = seconds;
+    if seconds > 0.0 {
+        statistics.instructions_per_second = statistics.instructions as f64 / seconds;
+    }
+    Ok(statistics.to_json())
+}
+
+#[cfg(not(feature = "serde"))]
+fn statistics_json(_vm: &VirtualMachine, _elapsed: Duration) -> Result<String, CliError> {
+    Err(CliError::Usage("--stats-json needs the serde feature".to_string()))
+}
+
+pub const ASM_USAGE: &str = "stack-vm-jit asm <input.asm> [-o output.bc] [--emit-debug]";
+
+/// `asm FILE`: assembles a source file into a bytecode container, next to
+/// it unless `-o` says where. Line tables are left out unless
+/// `--emit-debug` asks for them, since only error locations use them; the
+/// symbol table stays for the disassembler.
+pub fn assemble(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--emit-debug"], &["-o", "--output"])?;
+    let input = args.input(ASM_USAGE)?;
+    if !is_assembly(input) {
+        return Err(CliError::Usage(format!("{} is not an assembly (.asm) file", input)));
+    }
+    let output = match args.option(&["-o", "--output"]) {
+        Some(output) => output.to_string(),
+        None => Path::new(input).with_extension("bc").to_string_lossy().into_owned(),
+    };
+
+    let mut module = load_module(input)?;
+    if !args.flag("--emit-debug") {
+        module.debug_info = DebugInfo::default();
+    }
+    let bytes = module
+        .to_bytes()
+        .map_err(|error| CliError::Module { path: output.clone(), error })?;
+    fs::write(&output, bytes).map_err(|error| io_error(&output, error))
+}
+
+pub const DISASM_USAGE: &str = "stack-vm-jit disasm <module.bc> [--lines]";
+
+/// `disasm FILE`: prints a module as assembly, with the names its symbol
+/// table has for labels, constants and functions; `--lines` marks where
+/// each source line starts if the module has line tables
+pub fn disassemble(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--lines"], &[])?;
+    let input = args.input(DISASM_USAGE)?;
+    let module = load_module(input)?;
+    let text = Disassembler::new()
+        .with_line_comments(args.flag("--lines"))
+        .disassemble_module(&module)
+        .map_err(|error| CliError::Disassembly { path: input.to_string(), error })?;
+    print!("{}", text);
+    Ok(())
+}
+
+pub const REPL_USAGE: &str = "stack-vm-jit repl [--expr]";
+
+/// `repl`: reads assembly, or expressions with `--expr`, from standard
+/// input a line at a time, running each on a VM that keeps its stack and
+/// heap between lines
+pub fn repl(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--expr"], &[])?;
+    args.no_input(REPL_USAGE)?;
+    let mode = if args.flag("--expr") { ReplMode::Expression } else { ReplMode::Assembly };
+    println!("Stack VM REPL (:help for commands)");
+    Repl::with_mode(mode)
+        .run(io::stdin().lock(), io::stdout())
+        .map_err(|error| io_error("<stdin>", error))
+}
+
+pub const DEBUG_USAGE: &str = "stack-vm-jit debug <program.asm|program.bc>";
+
+/// `debug FILE`: loads the program and hands it to a [`Debugger`] reading
+/// commands from standard input
+pub fn debug(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &[])?;
+    let vm = program_vm(load_module(args.input(DEBUG_USAGE)?)?)?;
+    Debugger::new(vm)
+        .run(io::stdin().lock(), io::stdout())
+        .map_err(|error| io_error("<stdin>", error))
+}
+
+pub const PROFILE_USAGE: &str = "stack-vm-jit profile <program.asm|program.bc> [--out profile.json]";
+
+/// `profile FILE`: runs the program with the hot-spot profiler on and
+/// writes what it counted, in [`HotSpotProfiler::export_profile_data`]'s
+/// format, next to the program unless `--out` says where
+pub fn profile(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &["-o", "--out"])?;
+    let input = args.input(PROFILE_USAGE)?;
+    let output = match args.option(&["-o", "--out"]) {
+        Some(output) => output.to_string(),
+        None => Path::new(input).with_extension("profile.json").to_string_lossy().into_owned(),
+    };
+
+    let mut vm = program_vm(load_module(input)?)?;
+    vm.enable_profiling();
+    vm.run()?;
+    if let Ok(value) = vm.stack_top() {
+        println!("{}", value);
+    }
+    let data = export_profile(&vm)?;
+    fs::write(&output, data).map_err(|error| io_error(&output, error))
+}
+
+#[cfg(feature = "serde")]
+fn export_profile(vm: &VirtualMachine) -> Result<String, CliError> {
+    Ok(vm.get_profiler().map(HotSpotProfiler::export_profile_data).unwrap_or_default())
+}
+
+#[cfg(not(feature = "serde"))]
+fn export_profile(_vm: &VirtualMachine) -> Result<String, CliError> {
+    Err(CliError::Usage("profile needs the serde feature".to_string()))
+}
+
+#[cfg(feature = "serde")]
+fn import_profile(profiler: &mut HotSpotProfiler, data: &str) -> Result<(), String> {
+    profiler.import_profile_data(data)
+}
+
+#[cfg(not(feature = "serde"))]
+fn import_profile(_profiler: &mut HotSpotProfiler, _data: &str) -> Result<(), String> {
+    Err("report needs the serde feature".to_string())
+}
+
+pub const REPORT_USAGE: &str = "stack-vm-jit report <profile.json> [--program FILE] [--top N]";
+
+/// `report FILE`: prints the most executed instructions of a profile
+/// written by `profile`, with their share of all instructions run.
+/// `--program` names the program profiled, for operands and source lines;
+/// `--top` how many to show, ten by default.
+pub fn report(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &[], &["--program", "--top"])?;
+    let input = args.input(REPORT_USAGE)?;
+    let top = match args.option(&["--top"]) {
+        Some(top) => top
+            .parse()
+            .map_err(|_| CliError::Usage(format!("--top needs a number, not {}", top)))?,
+        None => 10,
+    };
+    let program = args.option(&["--program"]).map(load_module).transpose()?;
+
+    let data = fs::read_to_string(input).map_err(|error| io_error(input, error))?;
+    let mut profiler = HotSpotProfiler::new();
+    import_profile(&mut profiler, &data).map_err(|error| CliError::Usage(format!("{}: {}", input, error)))?;
+    let mut hot = profiler.get_hot_instructions(1);
+    hot.sort_by(|a, b| b.execution_count.cmp(&a.execution_count).then(a.pc.cmp(&b.pc)));
+    let total: u64 = hot.iter().map(|profile| profile.execution_count).sum();
+
+    println!("{} instructions executed at {} addresses", total, hot.len());
+    println!("{:>6} {:>12} {:>7}  Instruction", "PC", "Count", "%");
+    for profile in hot.into_iter().take(top) {
+        let share = profile.execution_count as f64 / total as f64 * 100.0;
+        let instruction = match &program {
+            Some(module) => describe_instruction(module, profile.pc),
+            None => mnemonic(profile.opcode).map_or_else(|| format!("{:?}", profile.opcode), str::to_string),
+        };
+        println!("{:>6} {:>12} {:>6.1}%  {}", profile.pc, profile.execution_count, share, instruction);
+    }
+    Ok(())
+}
+
+/// The instruction at `pc` in `module` as assembly, with its source line
+/// if the module has line tables
+fn describe_instruction(module: &BytecodeModule, pc: usize) -> String {
+    let Some(instruction) = module.instructions.get(pc) else {
+        return "(not in the program)".to_string();
+    };
+    let opcode = instruction.opcode();
+    let mut text = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+    if let Some(operand) = instruction.operand() {
+        text.push_str(&format!(" {}", operand));
+    }
+    if let Some(line) = module.debug_info.lines.line_at(pc) {
+        text = format!("{:<24} ; line {}", text, line);
+    }
+    text
+}
+
+pub const BENCHMARK_USAGE: &str = "stack-vm-jit benchmark [--runs N] [--workload NAME] [--trace]";
+
+/// `benchmark`: runs the standard workloads under every execution tier
+/// and prints how they compare. `--runs` is how many runs of each are
+/// averaged, five by default; `--workload` picks one by name. `--trace`
+/// writes every instruction of every run to standard error, and the
+/// timings then include writing it.
+pub fn benchmark(args: impl IntoIterator<Item = String>) -> Result<(), CliError> {
+    let args = Args::parse(args, &["--trace"], &["--runs", "--workload"])?;
+    args.no_input(BENCHMARK_USAGE)?;
+    let runs = match args.option(&["--runs"]) {
+        Some(runs) => runs
+            .parse()
+            .ok()
+            .filter(|&runs| runs > 0)
+            .ok_or_else(|| CliError::Usage(format!("--runs needs a positive number, not {}", runs)))?,
+        None => 5,
+    };
+    let mut workloads = bench::workloads();
+    if let Some(name) = args.option(&["--workload"]) {
+        workloads.retain(|workload| workload.name == name);
+        if workloads.is_empty() {
+            return Err(CliError::Usage(format!("No workload named {}", name)));
+        }
+    }
+
+    for workload in &workloads {
+        println!("{:<10} {}", workload.name, workload.description);
+    }
+    println!();
+    let report = if args.flag("--trace") {
+        println!("Timings include writing the trace");
+        bench::run_suite_traced(&workloads, runs, &mut io::stderr().lock())?
+    } else {
+        bench::run_suite(&workloads, runs)?
+    };
+    print!("{}", report);
+    Ok(())
+}
+
+/// Runs `vm` to the end, tracing each instruction to standard error
+pub fn trace(vm: &mut VirtualMachine) -> Result<(), CliError> {
+    let mut tracer = TraceWriter::new(io::stderr().lock());
+    tracer.write_header().map_err(TraceError::Io)?;
+    tracer.run(vm, u64::MAX)?;
+    Ok(())
+}
+
+/// Summary of a finished run, on standard error so it stays apart from
+/// what the program printed
+fn print_statistics(vm: &VirtualMachine, seconds: f64) {
+    let instructions = vm.instruction_count();
+    eprintln!("Execution time: {:.3} ms", seconds * 1000.0);
+    eprintln!("Instructions executed: {}", instructions);
+    if seconds > 0.0 {
+        eprintln!("Instructions per second: {:.0}", instructions as f64 / seconds);
+    }
+    eprintln!("Final stack size: {}", vm.stack_size());
+    eprintln!("Heap: {} objects, {} bytes", vm.heap_allocated_objects(), vm.heap_total_bytes());
+
+    let statistics = vm.opcode_statistics();
+    eprintln!("Most executed:");
+    for (opcode, count) in statistics.most_executed().into_iter().take(5) {
+        let name = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+        let share = count as f64 / statistics.total() as f64 * 100.0;
+        eprintln!("  {:<14} {:>10} ({:.1}%)", name, count, share);
+    }
+}
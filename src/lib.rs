@@ -1,4 +1,36 @@
+//! A stack-based virtual machine with a hot-spot profiler, an assembler
+//! and a small expression language.
+//!
+//! The types an embedder needs are re-exported here and, for a single
+//! glob import, in [`prelude`]: assemble or compile a
+//! [`BytecodeModule`], load it into a [`VirtualMachine`] and run it. The
+//! modules under [`vm`] hold everything else and keep their paths.
+
 pub mod vm;
 
+pub use vm::assembler::{Assembler, AssemblerError, Disassembler};
+pub use vm::builder::ProgramBuilder;
+pub use vm::compiler::SimpleCompiler;
+pub use vm::instruction::{Instruction, Opcode};
+pub use vm::io::{MemoryIo, StdIo, VmIo};
+pub use vm::linker::{LinkError, Linker};
+pub use vm::module::{BytecodeModule, ModuleError};
+pub use vm::runtime::{
+    ExecutionStatistics, InterruptHandle, NativeContext, NativeError, RunOutcome, VirtualMachine,
+    VmBuilder, VmError,
+};
 pub use vm::stack::OperandStack;
 pub use vm::types::Value;
+
+/// The types most programs embedding the VM use, for
+/// `use stack_vm_jit::prelude::*`
+pub mod prelude {
+    pub use crate::vm::assembler::Assembler;
+    pub use crate::vm::builder::ProgramBuilder;
+    pub use crate::vm::compiler::SimpleCompiler;
+    pub use crate::vm::instruction::{Instruction, Opcode};
+    pub use crate::vm::io::VmIo;
+    pub use crate::vm::module::BytecodeModule;
+    pub use crate::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+    pub use crate::vm::types::Value;
+}
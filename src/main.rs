@@ -1,23 +1,55 @@
+mod cli;
+
 use std::env;
+use std::process::ExitCode;
 use std::time::Instant;
 
-mod vm;
-
-use vm::{
-    runtime::VirtualMachine,
+use stack_vm_jit::vm::{
+    builder::ProgramBuilder,
+    runtime::{RunOutcome, VirtualMachine},
+    trace::{TraceError, TraceWriter},
     instruction::{Instruction, Opcode},
     types::Value,
 };
 
-fn main() {
+fn main() -> ExitCode {
+    let mut args = env::args().skip(1);
+    let command = args.next();
+
+    // Commands on files print only what the program does
+    let result = match command.as_deref() {
+        Some("run") => cli::run(args),
+        Some("asm") => cli::assemble(args),
+        Some("disasm") => cli::disassemble(args),
+        Some("repl") => cli::repl(args),
+        Some("debug") => cli::debug(args),
+        Some("profile") => cli::profile(args),
+        Some("report") => cli::report(args),
+        Some("benchmark") => cli::benchmark(args),
+        _ => {
+            run_builtin(command.as_deref());
+            Ok(())
+        }
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn print_banner() {
     println!("🚀 Stack-Based VM with JIT Compilation System");
     println!("============================================");
+}
 
-    let args: Vec<String> = env::args().collect();
-    
-    match args.get(1).map(|s| s.as_str()) {
+fn run_builtin(command: Option<&str>) {
+    print_banner();
+
+    match command {
         Some("demo") => run_demo(),
-        Some("benchmark") => run_benchmark(),
         Some("fibonacci") => run_fibonacci_program(),
         Some("calculator") => run_calculator_program(),
         Some("profiling") => run_profiling_demo(),
@@ -32,17 +64,39 @@ fn show_help() {
     println!();
     println!("Commands:");
     println!("  demo         Run interactive demonstration");
-    println!("  benchmark    Performance benchmarking");
+    println!("  benchmark    Time the standard workloads under each execution tier");
+    println!("               --runs N  runs of each to average (default: 5)");
+    println!("               --workload NAME  only this one: fib, sieve, strings or objects");
+    println!("               --trace  trace every instruction to stderr");
     println!("  fibonacci    Fibonacci calculation example");
     println!("  calculator   Simple calculator demo");
     println!("  profiling    JIT profiling demonstration");
     println!("  gc           Garbage collection demo");
+    println!("  repl         Assembly/expression REPL");
+    println!("               --expr  start out reading expressions");
+    println!("  run FILE     Run an assembly (.asm) or bytecode (.bc) program");
+    println!("               --stats  print execution statistics");
+    println!("               --stats-json  print them as JSON on stdout, after the value");
+    println!("               --trace  trace every instruction to stderr");
+    println!("               --jit  run with the JIT profiler on");
+    println!("  asm FILE     Assemble a .asm file into bytecode");
+    println!("               -o FILE  where to write it (default: FILE.bc)");
+    println!("               --emit-debug  keep line tables for error locations");
+    println!("  disasm FILE  Print a bytecode module as assembly");
+    println!("               --lines  mark where each source line starts");
+    println!("  debug FILE   Step through a program with breakpoints (type help)");
+    println!("  profile FILE Run a program with the profiler on and save the profile");
+    println!("               --out FILE  where to write it (default: FILE.profile.json)");
+    println!("  report FILE  Print the hottest instructions of a saved profile");
+    println!("               --program FILE  the program profiled, for operands and lines");
+    println!("               --top N  how many to print (default: 10)");
     println!("  help         Show this help message");
     println!();
     println!("Examples:");
     println!("  cargo run demo");
     println!("  cargo run benchmark");
     println!("  cargo run fibonacci");
+    println!("  cargo run -- run program.asm --stats");
 }
 
 fn run_interactive_demo() {
@@ -57,7 +111,7 @@ fn run_interactive_demo() {
         Instruction::new(Opcode::Push, Some(Value::Integer(3))),    // Push 3  
         Instruction::new(Opcode::Add, None),                        // Add: 5 + 3 = 8
         Instruction::new(Opcode::Push, Some(Value::Integer(2))),    // Push 2
-        Instruction::new(Opcode::Multiply, None),                   // Multiply: 8 * 2 = 16
+        Instruction::new(Opcode::Mul, None),                   // Multiply: 8 * 2 = 16
         Instruction::new(Opcode::Halt, None),                       // Halt
     ];
     
@@ -70,39 +124,16 @@ fn run_interactive_demo() {
     vm.load_program(program);
     
     println!("\n🔄 Execution Trace:");
-    println!("Step | PC | Instruction    | Stack State");
-    println!("-----|----|--------------  |------------");
-    
-    let mut step = 0;
-    while !vm.is_halted() {
-        let pc = vm.program_counter();
-        let instruction = vm.current_instruction().unwrap();
-        
-        print!("{:4} | {:2} | {:14} |", step, pc, format!("{:?}", instruction.opcode()));
-        
-        match vm.step() {
-            Ok(_) => {
-                if let Ok(top) = vm.stack_top() {
-                    println!(" {:?}", top);
-                } else {
-                    println!(" [empty]");
-                }
-            }
-            Err(e) => {
-                println!(" ERROR: {}", e);
-                break;
-            }
-        }
-        
-        step += 1;
-        if step > 20 { // Safety limit
-            println!("Maximum steps reached");
-            break;
-        }
+    let mut tracer = TraceWriter::new(std::io::stdout());
+    let traced = tracer.write_header().map_err(TraceError::Io).and_then(|_| tracer.run(&mut vm, 21)); // Safety limit
+    match traced {
+        Ok(RunOutcome::Exhausted) => println!("Maximum steps reached"),
+        Ok(_) => {}
+        Err(e) => println!("❌ Trace failed: {}", e),
     }
     
     if let Ok(result) = vm.stack_top() {
-        println!("\n✅ Result: {:?}", result);
+        println!("\n✅ Result: {}", result);
     }
     
     println!("\n📊 VM Statistics:");
@@ -129,17 +160,17 @@ fn run_fibonacci_program() {
         
         // Loop start (PC=4)
         // Check if i <= n
-        Instruction::new(Opcode::Duplicate, None),                  // Dup i
+        Instruction::new(Opcode::Dup, None),                  // Dup i
         Instruction::new(Opcode::Push, Some(Value::Integer(4))),    // Push n index
-        Instruction::new(Opcode::LoadLocal, None),                  // Load n from stack position
-        Instruction::new(Opcode::LessOrEqual, None),                // i <= n
+        Instruction::new(Opcode::Load, None),                  // Load n from stack position
+        Instruction::new(Opcode::LessEqual, None),                // i <= n
         Instruction::new(Opcode::JumpIfFalse, Some(Value::Integer(16))), // Jump to end if false
         
         // Fibonacci step: temp = a + b, a = b, b = temp
         Instruction::new(Opcode::Push, Some(Value::Integer(2))),    // Index for a
-        Instruction::new(Opcode::LoadLocal, None),                  // Load a
+        Instruction::new(Opcode::Load, None),                  // Load a
         Instruction::new(Opcode::Push, Some(Value::Integer(1))),    // Index for b  
-        Instruction::new(Opcode::LoadLocal, None),                  // Load b
+        Instruction::new(Opcode::Load, None),                  // Load b
         Instruction::new(Opcode::Add, None),                        // temp = a + b
         
         // i = i + 1 and loop
@@ -149,7 +180,7 @@ fn run_fibonacci_program() {
         
         // End: result is in b
         Instruction::new(Opcode::Push, Some(Value::Integer(1))),    // Index for b
-        Instruction::new(Opcode::LoadLocal, None),                  // Load result
+        Instruction::new(Opcode::Load, None),                  // Load result
         Instruction::new(Opcode::Halt, None),
     ];
     
@@ -165,7 +196,7 @@ fn run_fibonacci_program() {
             let duration = start_time.elapsed();
             
             if let Ok(result) = vm.stack_top() {
-                println!("✅ Fibonacci(10) = {:?}", result);
+                println!("✅ Fibonacci(10) = {}", result);
                 println!("   Expected: 55");
             }
             
@@ -177,7 +208,7 @@ fn run_fibonacci_program() {
                      vm.heap_allocated_objects(), vm.heap_total_bytes());
         }
         Err(e) => {
-            println!("❌ Execution failed: {}", e);
+            println!("❌ Execution failed: {:#}", e);
         }
     }
 }
@@ -195,15 +226,15 @@ fn run_calculator_program() {
         Instruction::new(Opcode::Push, Some(Value::Integer(5))),
         Instruction::new(Opcode::Add, None),                        // 15
         Instruction::new(Opcode::Push, Some(Value::Integer(3))),
-        Instruction::new(Opcode::Multiply, None),                   // 45
+        Instruction::new(Opcode::Mul, None),                   // 45
         
         // Right side: 8 / 2
         Instruction::new(Opcode::Push, Some(Value::Integer(8))),
         Instruction::new(Opcode::Push, Some(Value::Integer(2))),
-        Instruction::new(Opcode::Divide, None),                     // 4
+        Instruction::new(Opcode::Div, None),                     // 4
         
         // Final calculation: 45 - 4
-        Instruction::new(Opcode::Subtract, None),                   // 41
+        Instruction::new(Opcode::Sub, None),                   // 41
         Instruction::new(Opcode::Halt, None),
     ];
     
@@ -212,65 +243,34 @@ fn run_calculator_program() {
     
     vm.load_program(program);
     
-    match vm.run() {
-        Ok(_) => {
-            if let Ok(result) = vm.stack_top() {
-                println!("✅ Calculated result: {:?}", result);
+    match vm.run_to_value() {
+        Ok(result) => {
+            if let Some(result) = result {
+                println!("✅ Calculated result: {}", result);
             }
             println!("📊 Instructions executed: {}", vm.instruction_count());
         }
         Err(e) => {
-            println!("❌ Calculation failed: {}", e);
+            println!("❌ Calculation failed: {:#}", e);
         }
     }
-}
 
-fn run_benchmark() {
-    println!("\n⚡ Performance Benchmark");
-    println!("------------------------");
-    
-    let iterations = vec![1_000, 10_000, 100_000];
-    
-    for &iter_count in &iterations {
-        println!("\n🔄 Testing with {} iterations", iter_count);
-        
-        let mut vm = VirtualMachine::new();
-        
-        // Simple loop that decrements a counter
-        let mut program = vec![
-            Instruction::new(Opcode::Push, Some(Value::Integer(iter_count))), // Counter
-        ];
-        
-        // Loop: decrement counter, check if > 0, continue or exit
-        for _ in 0..5 { // Unroll loop slightly for more instructions
-            program.extend(vec![
-                Instruction::new(Opcode::Push, Some(Value::Integer(1))),
-                Instruction::new(Opcode::Subtract, None),                   // counter--
-                Instruction::new(Opcode::Duplicate, None),                  // Dup counter
-                Instruction::new(Opcode::Push, Some(Value::Integer(0))),
-                Instruction::new(Opcode::Greater, None),                    // counter > 0
-                Instruction::new(Opcode::JumpIfTrue, Some(Value::Integer(1))), // Loop if true
-            ]);
-        }
-        
-        program.push(Instruction::new(Opcode::Halt, None));
-        
-        vm.load_program(program);
-        
-        let start_time = Instant::now();
-        
-        match vm.run() {
-            Ok(_) => {
-                let duration = start_time.elapsed();
-                let instructions_per_second = vm.instruction_count() as f64 / duration.as_secs_f64();
-                
-                println!("  ✅ Completed in {:?}", duration);
-                println!("  📊 Instructions executed: {}", vm.instruction_count());
-                println!("  🚀 Performance: {:.0} instructions/second", instructions_per_second);
-            }
-            Err(e) => {
-                println!("  ❌ Benchmark failed: {}", e);
-            }
+    // Decimals keep base-10 fractions exact, where floats would give 0.30000000000000004
+    let mut vm = VirtualMachine::new();
+    let program = vec![
+        Instruction::new(Opcode::Push, Some(Value::Decimal("0.1".parse().unwrap()))),
+        Instruction::new(Opcode::Push, Some(Value::Decimal("0.2".parse().unwrap()))),
+        Instruction::new(Opcode::Add, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    println!("\nExpression: 0.1 + 0.2 (decimal)");
+    vm.load_program(program);
+    match vm.run_to_value() {
+        Ok(Some(result)) => println!("✅ Calculated result: {}", result),
+        Ok(None) => {}
+        Err(e) => {
+            println!("❌ Calculation failed: {:#}", e);
         }
     }
 }
@@ -283,19 +283,21 @@ fn run_profiling_demo() {
     vm.enable_profiling();
     
     // Program with a loop that will be detected as a hot spot
-    let program = vec![
-        Instruction::new(Opcode::Push, Some(Value::Integer(100))),  // Counter
-        // Hot loop starts here (PC=1)
-        Instruction::new(Opcode::Duplicate, None),                  // Dup counter
-        Instruction::new(Opcode::Push, Some(Value::Integer(0))),
-        Instruction::new(Opcode::Greater, None),                    // counter > 0
-        Instruction::new(Opcode::JumpIfFalse, Some(Value::Integer(8))), // Exit if false
-        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
-        Instruction::new(Opcode::Subtract, None),                   // counter--
-        Instruction::new(Opcode::Jump, Some(Value::Integer(1))),    // Jump back to loop
-        // Loop ends here
-        Instruction::new(Opcode::Halt, None),
-    ];
+    let mut builder = ProgramBuilder::new();
+    let exit = builder.label("exit");
+    builder.push(Value::Integer(100));                              // Counter
+    let hot_loop = builder.here("loop");
+    builder
+        .emit(Opcode::Dup)                                          // Dup counter
+        .push(Value::Integer(0))
+        .emit(Opcode::GreaterThan)                                  // counter > 0
+        .jump_if_false(exit)                                        // Exit if false
+        .push(Value::Integer(1))
+        .emit(Opcode::Sub)                                          // counter--
+        .jump_to(hot_loop)                                          // Jump back to loop
+        .bind(exit)
+        .emit(Opcode::Halt);
+    let program = builder.build().expect("profiling demo labels are bound").instructions;
     
     println!("Running program with profiling enabled...");
     vm.load_program(program);
@@ -311,15 +313,16 @@ fn run_profiling_demo() {
             
             if let Some(profiler) = vm.get_profiler() {
                 println!("\n🔥 Hot Spot Analysis:");
-                let hot_spots = profiler.get_hot_spots(10); // Get top 10
+                let mut hot_spots = profiler.get_hot_instructions(1);
+                hot_spots.sort_by_key(|profile| std::cmp::Reverse(profile.execution_count));
                 
-                for (pc, count) in hot_spots {
-                    let percentage = (count as f64 / vm.instruction_count() as f64) * 100.0;
-                    println!("  PC {:2}: {:8} executions ({:.1}%)", pc, count, percentage);
+                for profile in hot_spots.into_iter().take(10) { // Top 10
+                    let percentage = (profile.execution_count as f64 / vm.instruction_count() as f64) * 100.0;
+                    println!("  PC {:2}: {:8} executions ({:.1}%)", profile.pc, profile.execution_count, percentage);
                 }
                 
                 println!("\n🎯 JIT Compilation Candidates:");
-                let candidates = profiler.get_compilation_candidates();
+                let candidates = profiler.hot_loops();
                 for pc in candidates {
                     println!("  PC {} is ready for JIT compilation", pc);
                 }
@@ -362,6 +365,9 @@ fn run_gc_demo() {
             println!("\nAfter execution:");
             println!("  Objects: {}", vm.heap_allocated_objects());
             println!("  Bytes: {}", vm.heap_total_bytes());
+
+            println!("\n🕸️ What is still reachable (Graphviz DOT):");
+            print!("{}", vm.heap_graph().to_dot());
             
             println!("\n🧹 Triggering garbage collection...");
             let collected = vm.trigger_gc();
@@ -0,0 +1,225 @@
+//! Control-flow analysis of bytecode.
+//!
+//! [`build_cfg`] splits a program into basic blocks, straight runs of
+//! instructions entered only at the top and left only at the bottom, joined
+//! by the jumps and fall-throughs between them. [`ControlFlowGraph::to_dot`]
+//! draws the graph for Graphviz; given the profiler of a VM that ran the
+//! program, it labels conditional edges with how often each way was taken
+//! and shades blocks by how often they ran.
+
+use crate::vm::assembler::mnemonic;
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::jit::HotSpotProfiler;
+use crate::vm::types::Value;
+use std::fmt::Write;
+
+/// Instructions `start..end`, run one after another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl BasicBlock {
+    /// Address of the instruction that ends the block
+    pub fn last(&self) -> usize {
+        self.end - 1
+    }
+}
+
+/// How control passes from one block to the next
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Into the block that follows, including after a call returns
+    Fallthrough,
+    /// An unconditional `Jump`
+    Jump,
+    /// A conditional jump that jumped
+    Taken,
+    /// A conditional jump that did not
+    NotTaken,
+}
+
+/// An edge between blocks, by their indices in [`ControlFlowGraph::blocks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub from: usize,
+    pub to: usize,
+    pub kind: EdgeKind,
+}
+
+/// Basic blocks in address order and the edges between them
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ControlFlowGraph {
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>,
+}
+
+/// Local jump target of `instruction`, calls included
+fn branch_target(instruction: &Instruction) -> Option<usize> {
+    match (instruction.opcode(), instruction.operand()) {
+        (
+            Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Call,
+            Some(Value::Integer(target)),
+        ) => usize::try_from(*target).ok(),
+        _ => None,
+    }
+}
+
+/// Splits `instructions` into basic blocks. A block starts at the top of
+/// the program, at every jump or call target and after every jump,
+/// `Return` and `Halt`. Calls do not end a block: control comes back to
+/// the instruction after them, so they are drawn as falling through, and
+/// the functions they call are blocks of their own without edges into
+/// them. Jumps outside the program have no edge.
+pub fn build_cfg(instructions: &[Instruction]) -> ControlFlowGraph {
+    let mut leaders = vec![false; instructions.len()];
+    if let Some(first) = leaders.first_mut() {
+        *first = true;
+    }
+    for (pc, instruction) in instructions.iter().enumerate() {
+        if let Some(target) = branch_target(instruction)
+            && target < instructions.len()
+        {
+            leaders[target] = true;
+        }
+        let ends = matches!(
+            instruction.opcode(),
+            Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Return | Opcode::Halt
+        );
+        if ends && pc + 1 < instructions.len() {
+            leaders[pc + 1] = true;
+        }
+    }
+
+    let starts: Vec<usize> = (0..instructions.len()).filter(|&pc| leaders[pc]).collect();
+    let blocks: Vec<BasicBlock> = starts
+        .iter()
+        .enumerate()
+        .map(|(index, &start)| BasicBlock {
+            start,
+            end: starts.get(index + 1).copied().unwrap_or(instructions.len()),
+        })
+        .collect();
+
+    let block_at = |pc: usize| starts.binary_search(&pc).ok();
+    let mut edges = Vec::new();
+    for (from, block) in blocks.iter().enumerate() {
+        let instruction = &instructions[block.last()];
+        let next = (from + 1 < blocks.len()).then_some(from + 1);
+        let target = branch_target(instruction).and_then(block_at);
+        let mut edge = |to: Option<usize>, kind| {
+            if let Some(to) = to {
+                edges.push(Edge { from, to, kind });
+            }
+        };
+        match instruction.opcode() {
+            Opcode::Return | Opcode::Halt => {}
+            Opcode::Jump => edge(target, EdgeKind::Jump),
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                edge(target, EdgeKind::Taken);
+                edge(next, EdgeKind::NotTaken);
+            }
+            _ => edge(next, EdgeKind::Fallthrough),
+        }
+    }
+    ControlFlowGraph { blocks, edges }
+}
+
+impl ControlFlowGraph {
+    /// Index of the block holding the instruction at `pc`
+    pub fn block_at(&self, pc: usize) -> Option<usize> {
+        let index = self.blocks.partition_point(|block| block.end <= pc);
+        self.blocks
+            .get(index)
+            .filter(|block| block.start <= pc)
+            .map(|_| index)
+    }
+
+    /// Edges leaving block `block`
+    pub fn successors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.from == block)
+    }
+
+    /// Edges entering block `block`
+    pub fn predecessors(&self, block: usize) -> impl Iterator<Item = &Edge> {
+        self.edges.iter().filter(move |edge| edge.to == block)
+    }
+
+    /// Times block `block` ran, as counted at its first instruction
+    pub fn execution_count(&self, block: usize, profiler: &HotSpotProfiler) -> u64 {
+        self.blocks
+            .get(block)
+            .and_then(|block| profiler.get_instruction_profile(block.start))
+            .map_or(0, |profile| profile.execution_count)
+    }
+
+    /// The graph in Graphviz's DOT language, a box per block listing its
+    /// instructions. With `profiler`, each block shows how often it ran and
+    /// is filled redder the hotter it is, blocks that never ran are grey,
+    /// and conditional edges carry their taken and not-taken counts.
+    pub fn to_dot(&self, instructions: &[Instruction], profiler: Option<&HotSpotProfiler>) -> String {
+        let counts: Vec<u64> = (0..self.blocks.len())
+            .map(|block| profiler.map_or(0, |profiler| self.execution_count(block, profiler)))
+            .collect();
+        let hottest = counts.iter().copied().max().unwrap_or(0);
+
+        let mut dot = String::from("digraph cfg {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for (index, block) in self.blocks.iter().enumerate() {
+            let mut label = format!("B{} ({}..{})", index, block.start, block.end);
+            if profiler.is_some() {
+                let _ = write!(label, ", ran {}", counts[index]);
+            }
+            label.push_str("\\l");
+            for (pc, instruction) in instructions.iter().enumerate().take(block.end).skip(block.start) {
+                let _ = write!(label, "{:>4}: {}\\l", pc, escape(&describe(instruction)));
+            }
+            let style = match profiler {
+                None => String::new(),
+                Some(_) if counts[index] == 0 => ", color=grey, fontcolor=grey".to_string(),
+                Some(_) => format!(
+                    ", style=filled, fillcolor=\"0.000 {:.3} 1.000\"",
+                    counts[index] as f64 / hottest as f64
+                ),
+            };
+            let _ = writeln!(dot, "    B{} [label=\"{}\"{}];", index, label, style);
+        }
+        for edge in &self.edges {
+            let branch = profiler
+                .and_then(|profiler| profiler.get_branch_profile(self.blocks[edge.from].last()));
+            let attributes = match (edge.kind, branch) {
+                (EdgeKind::Fallthrough | EdgeKind::Jump, _) => String::new(),
+                (EdgeKind::Taken, None) => " [label=\"taken\"]".to_string(),
+                (EdgeKind::NotTaken, None) => " [label=\"not taken\", style=dashed]".to_string(),
+                (EdgeKind::Taken, Some(branch)) => {
+                    format!(" [label=\"taken {}\"]", branch.taken_count())
+                }
+                (EdgeKind::NotTaken, Some(branch)) => format!(
+                    " [label=\"not taken {}\", style=dashed]",
+                    branch.not_taken_count()
+                ),
+            };
+            let _ = writeln!(dot, "    B{} -> B{}{};", edge.from, edge.to, attributes);
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// `instruction` as the disassembler writes it
+fn describe(instruction: &Instruction) -> String {
+    let opcode = instruction.opcode();
+    let mut text = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+    if let Some(operand) = instruction.operand() {
+        let _ = write!(text, " {}", operand);
+    }
+    text
+}
+
+/// `text` inside a DOT string
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -1,36 +1,481 @@
 use crate::vm::instruction::{Instruction, Opcode};
-use crate::vm::types::Value;
-use std::collections::HashMap;
+use crate::vm::types::{Decimal, Value};
+use crate::vm::heap::{Array, GcPtr};
+use crate::vm::module::{
+    BytecodeModule, DebugInfo, ExternRef, FunctionInfo, LineTable, SymbolTable,
+};
+use crate::vm::module::ModuleError;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt;
+
+pub use crate::vm::compiler::SimpleCompiler;
+
+/// Position in assembly source; line and column are 1-based, and columns
+/// count characters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePos {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl SourcePos {
+    pub fn new(line: usize, column: usize) -> Self {
+        Self { line, column }
+    }
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum AssemblerError {
-    InvalidOpcode(String),
-    InvalidOperand(String),
-    UnknownLabel(String),
-    DuplicateLabel(String),
-    ParseError(String),
-    InvalidValue(String),
+    InvalidOpcode { opcode: String, pos: SourcePos },
+    InvalidOperand { operand: String, pos: SourcePos },
+    UnknownLabel { label: String, pos: SourcePos },
+    DuplicateLabel { label: String, pos: SourcePos },
+    ParseError { message: String, text: String, pos: SourcePos },
+    InvalidValue { value: String, pos: SourcePos },
 }
 
-impl std::fmt::Display for AssemblerError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl AssemblerError {
+    /// Where in the source the error was found
+    pub fn pos(&self) -> SourcePos {
         match self {
-            AssemblerError::InvalidOpcode(op) => write!(f, "Invalid opcode: {}", op),
-            AssemblerError::InvalidOperand(op) => write!(f, "Invalid operand: {}", op),
-            AssemblerError::UnknownLabel(label) => write!(f, "Unknown label: {}", label),
-            AssemblerError::DuplicateLabel(label) => write!(f, "Duplicate label: {}", label),
-            AssemblerError::ParseError(msg) => write!(f, "Parse error: {}", msg),
-            AssemblerError::InvalidValue(val) => write!(f, "Invalid value: {}", val),
+            AssemblerError::InvalidOpcode { pos, .. }
+            | AssemblerError::InvalidOperand { pos, .. }
+            | AssemblerError::UnknownLabel { pos, .. }
+            | AssemblerError::DuplicateLabel { pos, .. }
+            | AssemblerError::ParseError { pos, .. }
+            | AssemblerError::InvalidValue { pos, .. } => *pos,
+        }
+    }
+}
+
+impl fmt::Display for AssemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AssemblerError::InvalidOpcode { opcode, pos } => {
+                write!(f, "{}: Invalid opcode: {}", pos, opcode)
+            }
+            AssemblerError::InvalidOperand { operand, pos } => {
+                write!(f, "{}: Invalid operand: {}", pos, operand)
+            }
+            AssemblerError::UnknownLabel { label, pos } => {
+                write!(f, "{}: Unknown label: {}", pos, label)
+            }
+            AssemblerError::DuplicateLabel { label, pos } => {
+                write!(f, "{}: Duplicate label: {}", pos, label)
+            }
+            AssemblerError::ParseError { message, text, pos } => {
+                write!(f, "{}: Parse error: {} in `{}`", pos, message, text)
+            }
+            AssemblerError::InvalidValue { value, pos } => {
+                write!(f, "{}: Invalid value: {}", pos, value)
+            }
         }
     }
 }
 
 impl std::error::Error for AssemblerError {}
 
+/// A piece of source text and where it starts
+#[derive(Debug, Clone, Copy)]
+struct Located<'a> {
+    text: &'a str,
+    pos: SourcePos,
+}
+
+impl<'a> Located<'a> {
+    /// Whitespace-separated tokens of this text, each with its own position;
+    /// quoted literals stay in one token even if they contain whitespace
+    fn tokens(&self) -> Vec<Located<'a>> {
+        let mut tokens = Vec::new();
+        let mut quotes = QuoteTracker::default();
+        let mut start = None;
+        for (offset, c) in self.text.char_indices() {
+            if quotes.quoted(c) || !c.is_whitespace() {
+                start.get_or_insert(offset);
+            } else if let Some(begin) = start.take() {
+                tokens.push(self.slice(begin, offset));
+            }
+        }
+        if let Some(begin) = start {
+            tokens.push(self.slice(begin, self.text.len()));
+        }
+        tokens
+    }
+
+    fn slice(&self, start: usize, end: usize) -> Located<'a> {
+        let column = self.pos.column + self.text[..start].chars().count();
+        Located {
+            text: &self.text[start..end],
+            pos: SourcePos::new(self.pos.line, column),
+        }
+    }
+
+    /// The rest of this text starting at `token`, which must be one of its
+    /// tokens
+    fn rest_from(&self, token: &Located<'a>) -> Located<'a> {
+        let offset = token.text.as_ptr() as usize - self.text.as_ptr() as usize;
+        Located {
+            text: &self.text[offset..],
+            pos: token.pos,
+        }
+    }
+}
+
+/// Tracks whether lexing is inside a `"string"` or `'c'` literal
+#[derive(Default)]
+struct QuoteTracker {
+    quote: Option<char>,
+    escaped: bool,
+}
+
+impl QuoteTracker {
+    /// Feeds the next character; true if it belongs to a quoted literal,
+    /// including the quotes themselves
+    fn quoted(&mut self, c: char) -> bool {
+        match self.quote {
+            Some(quote) => {
+                if self.escaped {
+                    self.escaped = false;
+                } else if c == '\\' {
+                    self.escaped = true;
+                } else if c == quote {
+                    self.quote = None;
+                }
+                true
+            }
+            None if c == '"' || c == '\'' => {
+                self.quote = Some(c);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Full name of `label`: local labels (`.loop`) are qualified with the
+/// enclosing function, or left as-is at the top level
+fn scoped_label(scope: Option<&str>, label: &str) -> String {
+    match scope {
+        Some(function) if label.starts_with('.') => format!("{}{}", function, label),
+        _ => label.to_string(),
+    }
+}
+
+/// Splits `text` on `separator`, ignoring separators inside quoted literals
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut quotes = QuoteTracker::default();
+    let mut begin = 0;
+    for (offset, c) in text.char_indices() {
+        if !quotes.quoted(c) && c == separator {
+            items.push(&text[begin..offset]);
+            begin = offset + c.len_utf8();
+        }
+    }
+    items.push(&text[begin..]);
+    items
+}
+
+/// Recursive-descent evaluator for `.const` expressions
+struct ConstExpr<'a, 's> {
+    tokens: Vec<Located<'a>>,
+    next: usize,
+    assembler: &'s Assembler,
+    expr: &'s Located<'a>,
+}
+
+impl<'a> ConstExpr<'a, '_> {
+    fn error(&self, message: String, pos: SourcePos) -> AssemblerError {
+        AssemblerError::ParseError {
+            message,
+            text: self.expr.text.to_string(),
+            pos,
+        }
+    }
+
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.next).map(|token| token.text)
+    }
+
+    fn advance(&mut self) -> Option<Located<'a>> {
+        let token = self.tokens.get(self.next).copied();
+        self.next += 1;
+        token
+    }
+
+    // sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Value, AssemblerError> {
+        let mut value = self.product()?;
+        while let Some(op @ ("+" | "-")) = self.peek() {
+            let pos = self.advance().map(|token| token.pos).unwrap_or(self.expr.pos);
+            let rhs = self.product()?;
+            value = self.apply(op, value, rhs, pos)?;
+        }
+        Ok(value)
+    }
+
+    // product := unary (('*' | '/' | '%') unary)*
+    fn product(&mut self) -> Result<Value, AssemblerError> {
+        let mut value = self.unary()?;
+        while let Some(op @ ("*" | "/" | "%")) = self.peek() {
+            let pos = self.advance().map(|token| token.pos).unwrap_or(self.expr.pos);
+            let rhs = self.unary()?;
+            value = self.apply(op, value, rhs, pos)?;
+        }
+        Ok(value)
+    }
+
+    // unary := '-' unary | '(' sum ')' | literal | constant
+    fn unary(&mut self) -> Result<Value, AssemblerError> {
+        let end = SourcePos::new(
+            self.expr.pos.line,
+            self.expr.pos.column + self.expr.text.chars().count(),
+        );
+        let token = self
+            .advance()
+            .ok_or_else(|| self.error("Expression ends unexpectedly".to_string(), end))?;
+
+        match token.text {
+            "-" => {
+                let value = self.unary()?;
+                self.apply("-", Value::Integer(0), value, token.pos)
+            }
+            "(" => {
+                let value = self.sum()?;
+                match self.advance() {
+                    Some(close) if close.text == ")" => Ok(value),
+                    Some(other) => Err(self.error("Expected `)`".to_string(), other.pos)),
+                    None => Err(self.error("Expected `)`".to_string(), end)),
+                }
+            }
+            text if text.starts_with(|c: char| c.is_ascii_digit()) => {
+                self.assembler.parse_value(&token)
+            }
+            text if text.starts_with(|c: char| c.is_alphabetic() || c == '_') => {
+                match self.assembler.constants_map.get(text) {
+                    Some(&index) => Ok(self.assembler.constants[index].clone()),
+                    None => Err(AssemblerError::UnknownLabel {
+                        label: text.to_string(),
+                        pos: token.pos,
+                    }),
+                }
+            }
+            text => Err(self.error(format!("Unexpected token `{}`", text), token.pos)),
+        }
+    }
+
+    fn apply(&self, op: &str, a: Value, b: Value, pos: SourcePos) -> Result<Value, AssemblerError> {
+        let failed = |what: &str| self.error(format!("{} in `{} {} {}`", what, a, op, b), pos);
+        let result = match (&a, &b) {
+            (Value::Integer(x), Value::Integer(y)) => {
+                if matches!(op, "/" | "%") && *y == 0 {
+                    return Err(failed("Division by zero"));
+                }
+                match op {
+                    "+" => x.checked_add(*y),
+                    "-" => x.checked_sub(*y),
+                    "*" => x.checked_mul(*y),
+                    "/" => x.checked_div(*y),
+                    _ => x.checked_rem(*y),
+                }
+                .map(Value::Integer)
+            }
+            (Value::Decimal(_), Value::Integer(_) | Value::Decimal(_))
+            | (Value::Integer(_), Value::Decimal(_)) => {
+                let as_decimal = |value: &Value| match value {
+                    Value::Decimal(d) => Some(*d),
+                    Value::Integer(i) => Decimal::from_integer(*i),
+                    _ => None,
+                };
+                let (Some(x), Some(y)) = (as_decimal(&a), as_decimal(&b)) else {
+                    return Err(failed("Overflow"));
+                };
+                if matches!(op, "/" | "%") && y.is_zero() {
+                    return Err(failed("Division by zero"));
+                }
+                match op {
+                    "+" => x.checked_add(y),
+                    "-" => x.checked_sub(y),
+                    "*" => x.checked_mul(y),
+                    "/" => x.checked_div(y),
+                    _ => x.checked_rem(y),
+                }
+                .map(Value::Decimal)
+            }
+            (Value::Integer(_) | Value::Float(_), Value::Integer(_) | Value::Float(_)) => {
+                let as_float = |value: &Value| match value {
+                    Value::Integer(i) => *i as f64,
+                    Value::Float(f) => *f,
+                    _ => unreachable!("matched as a number"),
+                };
+                let (x, y) = (as_float(&a), as_float(&b));
+                Some(Value::Float(match op {
+                    "+" => x + y,
+                    "-" => x - y,
+                    "*" => x * y,
+                    "/" => x / y,
+                    _ => x % y,
+                }))
+            }
+            _ => return Err(failed("Operands must be numbers of compatible types")),
+        };
+        result.ok_or_else(|| failed("Overflow"))
+    }
+}
+
+/// Decodes the quoted literal in `token`, which starts with `quote`
+fn unquote(token: &Located, quote: char) -> Result<String, AssemblerError> {
+    let invalid = |column: usize| AssemblerError::InvalidValue {
+        value: token.text.to_string(),
+        pos: SourcePos::new(token.pos.line, column),
+    };
+
+    let mut decoded = String::new();
+    let mut chars = token.text.chars().enumerate().skip(1);
+    while let Some((index, c)) = chars.next() {
+        let column = token.pos.column + index;
+        match c {
+            c if c == quote => {
+                // The closing quote must end the token
+                return match chars.next() {
+                    None => Ok(decoded),
+                    Some((index, _)) => Err(invalid(token.pos.column + index)),
+                };
+            }
+            '\\' => {
+                let Some((_, escape)) = chars.next() else { break };
+                decoded.push(match escape {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    '\\' | '"' | '\'' => escape,
+                    'u' => {
+                        // \u{XXXX}
+                        let code: String = chars
+                            .by_ref()
+                            .map(|(_, c)| c)
+                            .take_while(|&c| c != '}')
+                            .collect();
+                        code.strip_prefix('{')
+                            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| invalid(column))?
+                    }
+                    _ => return Err(invalid(column)),
+                });
+            }
+            c => decoded.push(c),
+        }
+    }
+
+    let kind = if quote == '"' { "string" } else { "character" };
+    Err(AssemblerError::ParseError {
+        message: format!("Unterminated {} literal", kind),
+        text: token.text.to_string(),
+        pos: token.pos,
+    })
+}
+
+/// Removes `_` digit separators from a numeric literal; `None` if one does
+/// not sit between two digits. Non-numeric text is returned unchanged
+fn strip_digit_separators(text: &str) -> Option<String> {
+    let numeric = text
+        .trim_start_matches(['-', '+'])
+        .starts_with(|c: char| c.is_ascii_digit());
+    if !numeric || !text.contains('_') {
+        return Some(text.to_string());
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut stripped = String::with_capacity(text.len());
+    for (index, &c) in chars.iter().enumerate() {
+        if c != '_' {
+            stripped.push(c);
+            continue;
+        }
+        let before = index.checked_sub(1).map(|i| chars[i]);
+        let after = chars.get(index + 1).copied();
+        let between_digits = before.is_some_and(|c| c.is_ascii_hexdigit())
+            && after.is_some_and(|c| c.is_ascii_hexdigit());
+        if !between_digits {
+            return None;
+        }
+    }
+    Some(stripped)
+}
+
+/// Integer literal in decimal, `0x` hex or `0b` binary. Unsigned hex and
+/// binary literals spell out the 64-bit pattern, so `0xFFFFFFFFFFFFFFFF`
+/// is -1
+fn parse_integer(text: &str) -> Option<i64> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+
+    let radix_digits = [("0x", 16), ("0X", 16), ("0b", 2), ("0B", 2)]
+        .into_iter()
+        .find_map(|(prefix, radix)| unsigned.strip_prefix(prefix).map(|digits| (digits, radix)));
+    let Some((digits, radix)) = radix_digits else {
+        return text.parse::<i64>().ok();
+    };
+
+    // from_str_radix would accept a sign after the prefix
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+    let magnitude = u64::from_str_radix(digits, radix).ok()?;
+    if !negative {
+        Some(magnitude as i64)
+    } else if magnitude <= i64::MIN.unsigned_abs() {
+        Some((magnitude as i64).wrapping_neg())
+    } else {
+        None
+    }
+}
+
+/// Escapes `text` for use between `quote` characters
+pub(crate) fn escape(text: &str, quote: char) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            '\\' => escaped.push_str("\\\\"),
+            c if c == quote => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
 pub struct Assembler {
     labels: HashMap<String, usize>,
     constants: Vec<Value>,
     constants_map: HashMap<String, usize>,
+    // Names declared with .string/.data; pushing one emits LoadConstant
+    data_symbols: HashSet<String>,
+    // Names declared with .extern, left for the linker to resolve
+    externs: HashMap<String, SourcePos>,
+    // Functions named by .export, marked once all functions are known
+    exports: Vec<(String, SourcePos)>,
+    functions: Vec<FunctionInfo>,
+    symbols: SymbolTable,
+    // File name recorded in the debug info of assembled modules
+    source_name: Option<String>,
 }
 
 impl Assembler {
@@ -39,57 +484,297 @@ impl Assembler {
             labels: HashMap::new(),
             constants: Vec::new(),
             constants_map: HashMap::new(),
+            data_symbols: HashSet::new(),
+            externs: HashMap::new(),
+            exports: Vec::new(),
+            functions: Vec::new(),
+            symbols: SymbolTable::default(),
+            source_name: None,
         }
     }
 
+    /// Records `name` as the source file of assembled modules, for error
+    /// messages and stack traces
+    pub fn with_source_name(mut self, name: impl Into<String>) -> Self {
+        self.source_name = Some(name.into());
+        self
+    }
+
+    /// Labels, constants and functions named by the last assembled source
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
     pub fn assemble(&mut self, source: &str) -> Result<(Vec<Instruction>, Vec<Value>), AssemblerError> {
-        let lines: Vec<&str> = source.lines()
-            .map(|line| line.trim())
-            .filter(|line| !line.is_empty() && !line.starts_with(';'))
+        let module = self.assemble_module(source)?;
+        Ok((module.instructions, module.constants))
+    }
+
+    /// Assembles `source` into a module that also carries the function table
+    /// declared with `.func`/`.endfunc`
+    pub fn assemble_module(&mut self, source: &str) -> Result<BytecodeModule, AssemblerError> {
+        let lines: Vec<Located> = source.lines()
+            .enumerate()
+            .map(|(index, line)| {
+                let indent = line.len() - line.trim_start().len();
+                Located {
+                    text: line.trim(),
+                    pos: SourcePos::new(index + 1, line[..indent].chars().count() + 1),
+                }
+            })
+            .filter(|line| !line.text.is_empty() && !line.text.starts_with(';'))
             .collect();
 
         // First pass: collect labels and constants
         let mut instructions_without_labels = Vec::new();
         let mut instruction_index = 0;
+        let mut open_function: Option<(FunctionInfo, SourcePos)> = None;
 
         for line in &lines {
-            if line.starts_with('.const') {
+            let directive = line.text.split_whitespace().next().unwrap_or_default();
+            if directive == ".endfunc" {
+                let Some((function, _)) = open_function.take() else {
+                    return Err(AssemblerError::ParseError {
+                        message: ".endfunc without a matching .func".to_string(),
+                        text: line.text.to_string(),
+                        pos: line.pos,
+                    });
+                };
+                if function.address == instruction_index {
+                    return Err(AssemblerError::ParseError {
+                        message: format!("Function {} has no instructions", function.name),
+                        text: line.text.to_string(),
+                        pos: line.pos,
+                    });
+                }
+                self.symbols
+                    .functions
+                    .insert(function.name.clone(), function.address..instruction_index);
+                self.functions.push(function);
+            } else if directive == ".func" {
+                if open_function.is_some() {
+                    return Err(AssemblerError::ParseError {
+                        message: "Functions cannot be nested".to_string(),
+                        text: line.text.to_string(),
+                        pos: line.pos,
+                    });
+                }
+                let function = self.parse_function(line, instruction_index)?;
+                open_function = Some((function, line.pos));
+            } else if directive == ".const" {
                 self.parse_constant(line)?;
-            } else if line.ends_with(':') {
-                // Label
-                let label = line.trim_end_matches(':').to_string();
+            } else if directive == ".string" {
+                self.parse_string_data(line)?;
+            } else if directive == ".data" {
+                self.parse_array_data(line)?;
+            } else if directive == ".extern" {
+                self.parse_extern(line)?;
+            } else if directive == ".export" {
+                self.parse_export(line)?;
+            } else if line.text.ends_with(':') {
+                // Label; `.name:` is local to the enclosing function
+                let scope = open_function.as_ref().map(|(function, _)| function.name.as_str());
+                let label = scoped_label(scope, line.text.trim_end_matches(':'));
                 if self.labels.contains_key(&label) {
-                    return Err(AssemblerError::DuplicateLabel(label));
+                    return Err(AssemblerError::DuplicateLabel { label, pos: line.pos });
                 }
                 self.labels.insert(label, instruction_index);
             } else {
                 // Instruction - we'll parse it in the second pass
-                instructions_without_labels.push(*line);
+                let scope = open_function.as_ref().map(|(function, _)| function.name.clone());
+                instructions_without_labels.push((*line, scope));
                 instruction_index += 1;
             }
         }
 
+        if let Some((function, pos)) = open_function {
+            return Err(AssemblerError::ParseError {
+                message: format!("Function {} is missing .endfunc", function.name),
+                text: format!(".func {}", function.name),
+                pos,
+            });
+        }
+
+        // Exported constants become globals other modules can load
+        let mut globals = BTreeMap::new();
+        for (name, pos) in &self.exports {
+            if let Some(function) = self.functions.iter_mut().find(|function| function.name == *name) {
+                function.exported = true;
+            } else if let Some(&index) = self.constants_map.get(name) {
+                globals.insert(name.clone(), index);
+            } else {
+                return Err(AssemblerError::UnknownLabel { label: name.clone(), pos: *pos });
+            }
+        }
+
+        if let Some((name, &pos)) = self
+            .externs
+            .iter()
+            .find(|(name, _)| self.labels.contains_key(*name))
+        {
+            return Err(AssemblerError::DuplicateLabel { label: name.clone(), pos });
+        }
+
         // Second pass: parse instructions with label resolution
         let mut instructions = Vec::new();
-        for line in instructions_without_labels {
-            let instruction = self.parse_instruction(line)?;
+        let mut source_lines = Vec::new();
+        let mut externs = Vec::new();
+        for (line, scope) in instructions_without_labels {
+            let instruction = self.parse_instruction(&line, scope.as_deref())?;
+            if let Some(operand) = line.tokens().get(1)
+                && self.externs.contains_key(operand.text)
+                && !is_global_access(instruction.opcode()) {
+                externs.push(ExternRef {
+                    name: operand.text.to_string(),
+                    address: instructions.len(),
+                });
+            }
             instructions.push(instruction);
+            source_lines.push(Some(line.pos.line));
+        }
+
+        // Function names double as labels but are listed as functions
+        self.symbols.labels = self
+            .labels
+            .iter()
+            .filter(|(name, _)| !self.symbols.functions.contains_key(*name))
+            .map(|(name, &address)| (name.clone(), address))
+            .collect();
+        self.symbols.constants = self
+            .constants_map
+            .iter()
+            .map(|(name, &index)| (name.clone(), index))
+            .collect();
+
+        let mut module = BytecodeModule::new(instructions, self.constants.clone())
+            .with_functions(self.functions.clone())
+            .with_symbols(self.symbols.clone())
+            .with_debug_info(DebugInfo::for_file(
+                LineTable::from_lines(source_lines),
+                self.source_name.clone(),
+            ))
+            .with_externs(externs)
+            .with_globals(globals);
+        pool_global_names(&mut module);
+        module.dedup_constants();
+        Ok(module)
+    }
+
+    /// Assembles `source` straight to a bytecode container, ready to be
+    /// written to a `.bc` file and loaded with [`BytecodeModule::from_bytes`]
+    pub fn assemble_to_bytes(&mut self, source: &str) -> Result<Vec<u8>, ModuleError> {
+        self.assemble_module(source)?.to_bytes()
+    }
+
+    fn parse_function(&mut self, line: &Located, address: usize) -> Result<FunctionInfo, AssemblerError> {
+        // .func NAME [arity=A] [locals=N] [returns=R]
+        let parts = line.tokens();
+        if parts.len() < 2 {
+            return Err(AssemblerError::ParseError {
+                message: "Function declaration must be: .func NAME [arity=A] [locals=N] [returns=R]"
+                    .to_string(),
+                text: line.text.to_string(),
+                pos: line.pos,
+            });
+        }
+
+        let mut arity = 0;
+        let mut locals = None;
+        let mut returns = None;
+        for part in &parts[2..] {
+            let (key, value) = part.text.split_once('=').ok_or_else(|| AssemblerError::ParseError {
+                message: format!("Expected key=value, found `{}`", part.text),
+                text: line.text.to_string(),
+                pos: part.pos,
+            })?;
+            let count = value.parse::<usize>().map_err(|_| AssemblerError::InvalidValue {
+                value: part.text.to_string(),
+                pos: part.pos,
+            })?;
+            match key {
+                "arity" => arity = count,
+                "locals" => locals = Some(count),
+                "returns" => returns = Some(count),
+                _ => {
+                    return Err(AssemblerError::ParseError {
+                        message: format!("Unknown function attribute `{}`", key),
+                        text: line.text.to_string(),
+                        pos: part.pos,
+                    });
+                }
+            }
+        }
+
+        // Arguments live in the first locals, so there are at least `arity`
+        let locals = locals.unwrap_or(arity);
+        if locals < arity {
+            return Err(AssemblerError::ParseError {
+                message: format!("Function needs at least {} locals for its arguments", arity),
+                text: line.text.to_string(),
+                pos: line.pos,
+            });
+        }
+
+        // The function name doubles as a label for CALL
+        let name = parts[1].text.to_string();
+        if self.labels.contains_key(&name) {
+            return Err(AssemblerError::DuplicateLabel { label: name, pos: parts[1].pos });
         }
+        self.labels.insert(name.clone(), address);
+
+        let function = FunctionInfo::new(name, address, arity, locals);
+        Ok(match returns {
+            Some(returns) => function.with_returns(returns),
+            None => function,
+        })
+    }
 
-        Ok((instructions, self.constants.clone()))
+    fn parse_extern(&mut self, line: &Located) -> Result<(), AssemblerError> {
+        // .extern NAME, a function or global defined in a module linked with
+        // this one or by the host
+        let parts = line.tokens();
+        if parts.len() != 2 {
+            return Err(AssemblerError::ParseError {
+                message: "External declaration must be: .extern NAME".to_string(),
+                text: line.text.to_string(),
+                pos: line.pos,
+            });
+        }
+        self.externs.insert(parts[1].text.to_string(), parts[1].pos);
+        Ok(())
     }
 
-    fn parse_constant(&mut self, line: &str) -> Result<(), AssemblerError> {
-        // .const NAME VALUE
-        let parts: Vec<&str> = line.split_whitespace().collect();
-        if parts.len() != 3 {
-            return Err(AssemblerError::ParseError(
-                "Constant declaration must be: .const NAME VALUE".to_string()
-            ));
+    fn parse_export(&mut self, line: &Located) -> Result<(), AssemblerError> {
+        // .export NAME, a function other modules and the host may call, or a
+        // constant they may load
+        let parts = line.tokens();
+        if parts.len() != 2 {
+            return Err(AssemblerError::ParseError {
+                message: "Export declaration must be: .export NAME".to_string(),
+                text: line.text.to_string(),
+                pos: line.pos,
+            });
         }
+        self.exports.push((parts[1].text.to_string(), parts[1].pos));
+        Ok(())
+    }
 
-        let name = parts[1].to_string();
-        let value = self.parse_value(parts[2])?;
+    fn parse_constant(&mut self, line: &Located) -> Result<(), AssemblerError> {
+        // .const NAME VALUE, where VALUE may be an expression such as 4 * KB
+        let parts = line.tokens();
+        if parts.len() < 3 {
+            return Err(AssemblerError::ParseError {
+                message: "Constant declaration must be: .const NAME VALUE".to_string(),
+                text: line.text.to_string(),
+                pos: line.pos,
+            });
+        }
+
+        let name = parts[1].text.to_string();
+        let value = match self.parse_value(&parts[2]) {
+            Ok(value) if parts.len() == 3 => value,
+            _ => self.evaluate_constant(&line.rest_from(&parts[2]))?,
+        };
 
         let index = self.constants.len();
         self.constants.push(value);
@@ -98,71 +783,216 @@ impl Assembler {
         Ok(())
     }
 
-    fn parse_instruction(&self, line: &str) -> Result<Instruction, AssemblerError> {
-        let parts: Vec<&str> = line.split_whitespace().collect();
+    /// Evaluates an arithmetic expression over numeric literals and
+    /// previously declared constants
+    fn evaluate_constant(&self, expr: &Located) -> Result<Value, AssemblerError> {
+        let mut parser = ConstExpr {
+            tokens: Self::expression_tokens(expr),
+            next: 0,
+            assembler: self,
+            expr,
+        };
+        let value = parser.sum()?;
+        match parser.tokens.get(parser.next) {
+            None => Ok(value),
+            Some(extra) => Err(parser.error(format!("Unexpected token `{}`", extra.text), extra.pos)),
+        }
+    }
+
+    /// Splits an expression into operators, parentheses and operand words
+    fn expression_tokens<'a>(expr: &Located<'a>) -> Vec<Located<'a>> {
+        let mut tokens = Vec::new();
+        let mut word_start = None;
+        for (offset, c) in expr.text.char_indices() {
+            let is_word = c.is_alphanumeric() || c == '_' || c == '.';
+            if is_word {
+                word_start.get_or_insert(offset);
+                continue;
+            }
+            if let Some(start) = word_start.take() {
+                tokens.push(expr.slice(start, offset));
+            }
+            if !c.is_whitespace() {
+                tokens.push(expr.slice(offset, offset + c.len_utf8()));
+            }
+        }
+        if let Some(start) = word_start {
+            tokens.push(expr.slice(start, expr.text.len()));
+        }
+        tokens
+    }
+
+    /// Splits `.directive NAME BODY`, where the body may contain spaces
+    fn parse_data_directive<'a>(
+        line: &Located<'a>,
+        usage: &str,
+    ) -> Result<(String, Located<'a>), AssemblerError> {
+        let parts = line.tokens();
+        if parts.len() < 3 {
+            return Err(AssemblerError::ParseError {
+                message: format!("Data declaration must be: {}", usage),
+                text: line.text.to_string(),
+                pos: line.pos,
+            });
+        }
+        Ok((parts[1].text.to_string(), line.rest_from(&parts[2])))
+    }
+
+    fn add_data(&mut self, name: String, value: Value) {
+        let index = self.constants.len();
+        self.constants.push(value);
+        self.constants_map.insert(name.clone(), index);
+        self.data_symbols.insert(name);
+    }
+
+    fn parse_string_data(&mut self, line: &Located) -> Result<(), AssemblerError> {
+        // .string NAME "text, spaces allowed"
+        let (name, body) = Self::parse_data_directive(line, ".string NAME \"TEXT\"")?;
+        let tokens = body.tokens();
+        if let Some(extra) = tokens.get(1) {
+            return Err(AssemblerError::ParseError {
+                message: format!("Unexpected token `{}`", extra.text),
+                text: line.text.to_string(),
+                pos: extra.pos,
+            });
+        }
+
+        match self.parse_value(&tokens[0]) {
+            Ok(Value::String(text)) => {
+                self.add_data(name, Value::String(text));
+                Ok(())
+            }
+            Ok(_) | Err(AssemblerError::InvalidValue { .. }) => Err(AssemblerError::InvalidValue {
+                value: body.text.to_string(),
+                pos: body.pos,
+            }),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn parse_array_data(&mut self, line: &Located) -> Result<(), AssemblerError> {
+        // .data NAME [VALUE, VALUE, ...]
+        let (name, body) = Self::parse_data_directive(line, ".data NAME [VALUE, ...]")?;
+        let inner = body
+            .text
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+            .ok_or_else(|| AssemblerError::ParseError {
+                message: "Array data must be enclosed in [ ]".to_string(),
+                text: line.text.to_string(),
+                pos: body.pos,
+            })?;
+
+        let mut elements = Vec::new();
+        let mut column = body.pos.column + 1;
+        for item in split_unquoted(inner, ',') {
+            let trimmed = item.trim();
+            let leading = item.len() - item.trim_start().len();
+            if !trimmed.is_empty() {
+                let token = Located {
+                    text: trimmed,
+                    pos: SourcePos::new(body.pos.line, column + item[..leading].chars().count()),
+                };
+                elements.push(self.parse_value(&token)?);
+            } else if !inner.trim().is_empty() {
+                return Err(AssemblerError::ParseError {
+                    message: "Empty array element".to_string(),
+                    text: line.text.to_string(),
+                    pos: SourcePos::new(body.pos.line, column),
+                });
+            }
+            column += item.chars().count() + 1;
+        }
+
+        self.add_data(name, Value::Array(GcPtr::detached(Array::new(elements))));
+        Ok(())
+    }
+
+    fn parse_instruction(&self, line: &Located, scope: Option<&str>) -> Result<Instruction, AssemblerError> {
+        let parts = line.tokens();
         if parts.is_empty() {
-            return Err(AssemblerError::ParseError("Empty instruction".to_string()));
+            return Err(AssemblerError::ParseError {
+                message: "Empty instruction".to_string(),
+                text: line.text.to_string(),
+                pos: line.pos,
+            });
+        }
+        if let Some(extra) = parts.get(2) {
+            return Err(AssemblerError::ParseError {
+                message: format!("Unexpected token `{}`", extra.text),
+                text: line.text.to_string(),
+                pos: extra.pos,
+            });
+        }
+
+        let mut opcode = self.parse_opcode(&parts[0])?;
+
+        // Globals are named; the name is pooled once the module is built
+        if is_global_access(opcode) {
+            let name = match parts.get(1) {
+                Some(name) if name.text.starts_with('"') => unquote(name, '"')?,
+                Some(name) if is_identifier(name.text) => name.text.to_string(),
+                Some(name) => {
+                    return Err(AssemblerError::InvalidOperand {
+                        operand: name.text.to_string(),
+                        pos: name.pos,
+                    });
+                }
+                // Left for the verifier, as for other missing operands
+                None => return Ok(Instruction::new(opcode, None)),
+            };
+            return Ok(Instruction::new(opcode, Some(Value::String(name.into()))));
+        }
+
+        // Data is fetched from the constants pool explicitly
+        if opcode == Opcode::Push
+            && parts.get(1).is_some_and(|operand| self.data_symbols.contains(operand.text)) {
+            opcode = Opcode::LoadConstant;
         }
 
-        let opcode_str = parts[0].to_uppercase();
-        let opcode = self.parse_opcode(&opcode_str)?;
+        // External targets get a placeholder address until linking
+        if let Some(operand) = parts.get(1)
+            && self.externs.contains_key(operand.text) {
+            if !matches!(
+                opcode,
+                Opcode::Jump
+                    | Opcode::JumpIfTrue
+                    | Opcode::JumpIfFalse
+                    | Opcode::Call
+                    | Opcode::LoadConstant
+            ) {
+                return Err(AssemblerError::ParseError {
+                    message: format!(
+                        "External symbol `{}` can only be a jump or call target or loaded with LDC",
+                        operand.text
+                    ),
+                    text: line.text.to_string(),
+                    pos: operand.pos,
+                });
+            }
+            return Ok(Instruction::new(opcode, Some(Value::Integer(0))));
+        }
 
-        let operand = if parts.len() > 1 {
-            Some(self.parse_operand(parts[1])?)
-        } else {
-            None
+        let operand = match parts.get(1) {
+            Some(operand) => Some(self.parse_operand(operand, scope)?),
+            None => None,
         };
 
         Ok(Instruction::new(opcode, operand))
     }
 
-    fn parse_opcode(&self, opcode_str: &str) -> Result<Opcode, AssemblerError> {
-        match opcode_str {
-            "PUSH" => Ok(Opcode::Push),
-            "POP" => Ok(Opcode::Pop),
-            "ADD" => Ok(Opcode::Add),
-            "SUB" | "SUBTRACT" => Ok(Opcode::Subtract),
-            "MUL" | "MULTIPLY" => Ok(Opcode::Multiply),
-            "DIV" | "DIVIDE" => Ok(Opcode::Divide),
-            "MOD" | "MODULO" => Ok(Opcode::Modulo),
-            "AND" => Ok(Opcode::And),
-            "OR" => Ok(Opcode::Or),
-            "NOT" => Ok(Opcode::Not),
-            "XOR" => Ok(Opcode::Xor),
-            "SHL" | "SHIFT_LEFT" => Ok(Opcode::ShiftLeft),
-            "SHR" | "SHIFT_RIGHT" => Ok(Opcode::ShiftRight),
-            "EQ" | "EQUAL" => Ok(Opcode::Equal),
-            "NE" | "NOT_EQUAL" => Ok(Opcode::NotEqual),
-            "LT" | "LESS" => Ok(Opcode::Less),
-            "LE" | "LESS_EQUAL" => Ok(Opcode::LessOrEqual),
-            "GT" | "GREATER" => Ok(Opcode::Greater),
-            "GE" | "GREATER_EQUAL" => Ok(Opcode::GreaterOrEqual),
-            "JMP" | "JUMP" => Ok(Opcode::Jump),
-            "JT" | "JUMP_TRUE" => Ok(Opcode::JumpIfTrue),
-            "JF" | "JUMP_FALSE" => Ok(Opcode::JumpIfFalse),
-            "CALL" => Ok(Opcode::Call),
-            "RET" | "RETURN" => Ok(Opcode::Return),
-            "LOAD" => Ok(Opcode::LoadLocal),
-            "STORE" => Ok(Opcode::StoreLocal),
-            "LOADC" | "LOAD_CONST" => Ok(Opcode::LoadConstant),
-            "DUP" | "DUPLICATE" => Ok(Opcode::Duplicate),
-            "SWAP" => Ok(Opcode::Swap),
-            "NEW" | "NEW_OBJECT" => Ok(Opcode::NewObject),
-            "GET_FIELD" => Ok(Opcode::GetField),
-            "SET_FIELD" => Ok(Opcode::SetField),
-            "NEW_ARRAY" => Ok(Opcode::NewArray),
-            "GET_ARRAY" => Ok(Opcode::ArrayGet),
-            "SET_ARRAY" => Ok(Opcode::ArraySet),
-            "LEN" | "LENGTH" => Ok(Opcode::ArrayLength),
-            "HALT" => Ok(Opcode::Halt),
-            "NOP" | "NO_OP" => Ok(Opcode::NoOp),
-            _ => Err(AssemblerError::InvalidOpcode(opcode_str.to_string())),
-        }
-    }
-
-    fn parse_operand(&self, operand_str: &str) -> Result<Value, AssemblerError> {
+    fn parse_opcode(&self, token: &Located) -> Result<Opcode, AssemblerError> {
+        opcode_for(token.text).ok_or_else(|| AssemblerError::InvalidOpcode {
+            opcode: token.text.to_string(),
+            pos: token.pos,
+        })
+    }
+
+    fn parse_operand(&self, token: &Located, scope: Option<&str>) -> Result<Value, AssemblerError> {
+        let operand_str = token.text;
+
         // Handle label references
-        if let Some(&address) = self.labels.get(operand_str) {
+        if let Some(&address) = self.labels.get(&scoped_label(scope, operand_str)) {
             return Ok(Value::Integer(address as i64));
         }
 
@@ -172,34 +1002,82 @@ impl Assembler {
         }
 
         // Handle direct values
-        self.parse_value(operand_str)
+        let value = self.parse_value(token);
+
+        // A bare identifier that is neither a value, label nor constant is
+        // most likely a misspelled label
+        let is_identifier = operand_str
+            .trim_start_matches('.')
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_');
+        match value {
+            Err(AssemblerError::InvalidValue { .. }) if is_identifier => {
+                Err(AssemblerError::UnknownLabel {
+                    label: operand_str.to_string(),
+                    pos: token.pos,
+                })
+            }
+            value => value,
+        }
     }
 
-    fn parse_value(&self, value_str: &str) -> Result<Value, AssemblerError> {
+    fn parse_value(&self, token: &Located) -> Result<Value, AssemblerError> {
+        let value_str = token.text;
+        let invalid = || AssemblerError::InvalidValue {
+            value: value_str.to_string(),
+            pos: token.pos,
+        };
+
+        // Numbers may use `_` between digits, e.g. 1_000_000
+        let number = strip_digit_separators(value_str).ok_or_else(invalid)?;
+
         // Integer
-        if let Ok(int_val) = value_str.parse::<i64>() {
+        if let Some(int_val) = parse_integer(&number) {
             return Ok(Value::Integer(int_val));
         }
 
+        // Decimal (`d` suffix, e.g. 0.1d)
+        if let Some(decimal) = number.strip_suffix('d')
+            && let Ok(decimal_val) = decimal.parse::<Decimal>() {
+            return Ok(Value::Decimal(decimal_val));
+        }
+
         // Float
-        if let Ok(float_val) = value_str.parse::<f64>() {
+        if let Ok(float_val) = number.parse::<f64>() {
             return Ok(Value::Float(float_val));
         }
 
-        // Boolean
+        // Boolean and null
         match value_str.to_lowercase().as_str() {
             "true" => return Ok(Value::Boolean(true)),
             "false" => return Ok(Value::Boolean(false)),
+            "null" => return Ok(Value::Null),
             _ => {}
         }
 
-        // String (enclosed in quotes)
-        if value_str.starts_with('"') && value_str.ends_with('"') && value_str.len() >= 2 {
-            let string_content = &value_str[1..value_str.len()-1];
-            return Ok(Value::String(string_content.to_string()));
+        // String (enclosed in quotes, with escapes)
+        if value_str.starts_with('"') {
+            return Ok(Value::String(unquote(token, '"')?.into()));
+        }
+
+        // Character ('c' or an escape such as '\n')
+        if value_str.starts_with('\'') {
+            let text = unquote(token, '\'')?;
+            let mut chars = text.chars();
+            return match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(Value::Char(c)),
+                _ => Err(AssemblerError::InvalidValue {
+                    value: value_str.to_string(),
+                    pos: token.pos,
+                }),
+            };
         }
 
-        Err(AssemblerError::InvalidValue(value_str.to_string()))
+        Err(AssemblerError::InvalidValue {
+            value: value_str.to_string(),
+            pos: token.pos,
+        })
     }
 }
 
@@ -209,149 +1087,517 @@ impl Default for Assembler {
     }
 }
 
-// High-level language compiler for a simple stack-based language
-pub struct SimpleCompiler {
-    assembler: Assembler,
+#[derive(Debug, Clone)]
+pub enum DisassemblerError {
+    UnrepresentableValue(String),
+    UnrepresentableOpcode(Opcode),
+}
+
+impl fmt::Display for DisassemblerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DisassemblerError::UnrepresentableValue(value) => {
+                write!(f, "Value has no assembly syntax: {}", value)
+            }
+            DisassemblerError::UnrepresentableOpcode(opcode) => {
+                write!(f, "Opcode has no assembly mnemonic: {:?}", opcode)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DisassemblerError {}
+
+/// Converts programs back into assembly accepted by [`Assembler`].
+///
+/// Constants become `.const`/`.string`/`.data` declarations named
+/// `C<index>` and every jump or call target gets an `L<address>` label, so
+/// assembling the output yields the same instructions and constants. With
+/// a module's [`SymbolTable`] the original names are used instead.
+pub struct Disassembler {
+    line_comments: bool,
 }
 
-impl SimpleCompiler {
+impl Disassembler {
     pub fn new() -> Self {
         Self {
-            assembler: Assembler::new(),
+            line_comments: false,
         }
     }
 
-    pub fn compile_expression(&mut self, expr: &str) -> Result<(Vec<Instruction>, Vec<Value>), AssemblerError> {
-        let assembly = self.expression_to_assembly(expr)?;
-        self.assembler.assemble(&assembly)
+    /// Whether [`Disassembler::disassemble_module`] marks where each
+    /// source line's code starts with a `; file:line` comment, using the
+    /// module's debug info
+    pub fn with_line_comments(mut self, enabled: bool) -> Self {
+        self.line_comments = enabled;
+        self
     }
 
-    fn expression_to_assembly(&self, expr: &str) -> Result<String, AssemblerError> {
-        // Simple expression compiler for basic arithmetic
-        // This is a very basic implementation - a full compiler would use proper parsing
-        
-        let tokens = self.tokenize(expr);
-        let postfix = self.infix_to_postfix(tokens)?;
-        
-        let mut assembly = String::new();
-        
-        for token in postfix {
-            match token.as_str() {
-                "+" => assembly.push_str("ADD\n"),
-                "-" => assembly.push_str("SUB\n"),
-                "*" => assembly.push_str("MUL\n"),
-                "/" => assembly.push_str("DIV\n"),
-                "%" => assembly.push_str("MOD\n"),
-                _ => {
-                    if let Ok(_) = token.parse::<i64>() {
-                        assembly.push_str(&format!("PUSH {}\n", token));
-                    } else if let Ok(_) = token.parse::<f64>() {
-                        assembly.push_str(&format!("PUSH {}\n", token));
-                    } else {
-                        return Err(AssemblerError::ParseError(format!("Unknown token: {}", token)));
-                    }
-                }
+    pub fn disassemble(
+        &self,
+        instructions: &[Instruction],
+        constants: &[Value],
+    ) -> Result<String, DisassemblerError> {
+        Self::render(
+            instructions,
+            constants,
+            &[],
+            &SymbolTable::default(),
+            &[],
+            &BTreeMap::new(),
+            None,
+        )
+    }
+
+    /// Like [`Disassembler::disassemble`], also wrapping each entry of the
+    /// function table in `.func`/`.endfunc` and using the module's symbol
+    /// names for labels and constants where it has them
+    pub fn disassemble_module(&self, module: &BytecodeModule) -> Result<String, DisassemblerError> {
+        Self::render(
+            &module.instructions,
+            &module.constants,
+            &module.functions,
+            &module.symbols,
+            &module.externs,
+            &module.globals,
+            self.line_comments.then_some(&module.debug_info),
+        )
+    }
+
+    fn render(
+        instructions: &[Instruction],
+        constants: &[Value],
+        functions: &[FunctionInfo],
+        symbols: &SymbolTable,
+        externs: &[ExternRef],
+        globals: &BTreeMap<String, usize>,
+        debug_info: Option<&DebugInfo>,
+    ) -> Result<String, DisassemblerError> {
+        let mut output = String::new();
+
+        let constant_names: Vec<String> = (0..constants.len())
+            .map(|index| match symbols.constant_name(index) {
+                Some(name) => name.to_string(),
+                None => format!("C{}", index),
+            })
+            .collect();
+        for (name, constant) in constant_names.iter().zip(constants) {
+            output.push_str(&Self::format_constant(name, constant)?);
+            output.push('\n');
+        }
+        if !constants.is_empty() {
+            output.push('\n');
+        }
+
+        // Placeholder operands of unlinked references name their target
+        let extern_targets: HashMap<usize, &str> = externs
+            .iter()
+            .map(|reference| (reference.address, reference.name.as_str()))
+            .collect();
+        let extern_names: BTreeSet<&str> = extern_targets.values().copied().collect();
+        for name in &extern_names {
+            output.push_str(&format!(".extern {}\n", name));
+        }
+        if !extern_names.is_empty() {
+            output.push('\n');
+        }
+
+        let exported: Vec<&str> = functions
+            .iter()
+            .filter(|function| function.exported)
+            .map(|function| function.name.as_str())
+            .chain(globals.keys().map(String::as_str))
+            .collect();
+        for name in &exported {
+            output.push_str(&format!(".export {}\n", name));
+        }
+        if !exported.is_empty() {
+            output.push('\n');
+        }
+
+        // Function names already label their entry points
+        let entries: HashMap<usize, &FunctionInfo> = functions
+            .iter()
+            .map(|function| (function.address, function))
+            .collect();
+
+        // Every named label, plus generated ones for other targets that can
+        // be placed in the listing
+        let mut labels: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (name, &address) in &symbols.labels {
+            labels.entry(address).or_default().push(name.clone());
+        }
+        let targets = instructions
+            .iter()
+            .enumerate()
+            .filter(|(address, _)| !extern_targets.contains_key(address))
+            .filter_map(|(_, instruction)| Self::jump_target(instruction));
+        for target in targets {
+            if target <= instructions.len() && !entries.contains_key(&target) {
+                labels.entry(target).or_insert_with(|| vec![format!("L{}", target)]);
             }
         }
-        
-        assembly.push_str("HALT\n");
-        Ok(assembly)
-    }
 
-    fn tokenize(&self, expr: &str) -> Vec<String> {
-        let mut tokens = Vec::new();
-        let mut current_token = String::new();
-        
-        for ch in expr.chars() {
-            match ch {
-                ' ' | '\t' => {
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
-                    }
-                }
-                '+' | '-' | '*' | '/' | '%' | '(' | ')' => {
-                    if !current_token.is_empty() {
-                        tokens.push(current_token.clone());
-                        current_token.clear();
-                    }
-                    tokens.push(ch.to_string());
+        // Local labels are written unqualified inside their own function
+        let display = |name: &str, function: Option<&str>| match function {
+            Some(function) => match name.strip_prefix(function) {
+                Some(local) if local.starts_with('.') => local.to_string(),
+                _ => name.to_string(),
+            },
+            None => name.to_string(),
+        };
+
+        let mut current: Option<&str> = None;
+        let mut location = None;
+        for (address, instruction) in instructions.iter().enumerate() {
+            // A function ends at its recorded end, or where the next begins
+            let ended = current.is_some_and(|name| {
+                symbols.functions.get(name).is_some_and(|range| range.end == address)
+            });
+            if ended || (current.is_some() && entries.contains_key(&address)) {
+                output.push_str(".endfunc\n");
+                current = None;
+            }
+            if let Some(function) = entries.get(&address) {
+                output.push_str(&format!(
+                    ".func {} arity={} locals={}",
+                    function.name, function.arity, function.locals
+                ));
+                if let Some(returns) = function.returns {
+                    output.push_str(&format!(" returns={}", returns));
                 }
-                _ => {
-                    current_token.push(ch);
+                output.push('\n');
+                current = Some(&function.name);
+            }
+            for name in labels.get(&address).into_iter().flatten() {
+                output.push_str(&format!("{}:\n", display(name, current)));
+            }
+            if let Some(debug_info) = debug_info {
+                let here = debug_info.location(address);
+                if here.is_some() && here != location {
+                    output.push_str(&format!("    ; {}\n", here.as_deref().unwrap_or_default()));
                 }
+                location = here;
             }
-        }
-        
-        if !current_token.is_empty() {
-            tokens.push(current_token);
-        }
-        
-        tokens
-    }
 
-    fn infix_to_postfix(&self, tokens: Vec<String>) -> Result<Vec<String>, AssemblerError> {
-        let mut output = Vec::new();
-        let mut operators = Vec::new();
-        
-        for token in tokens {
-            match token.as_str() {
-                "(" => operators.push(token),
-                ")" => {
-                    while let Some(op) = operators.pop() {
-                        if op == "(" {
-                            break;
-                        }
-                        output.push(op);
-                    }
+            let opcode = instruction.opcode();
+            let mnemonic =
+                mnemonic(opcode).ok_or(DisassemblerError::UnrepresentableOpcode(opcode))?;
+            output.push_str("    ");
+            output.push_str(mnemonic);
+
+            match (Self::jump_target(instruction), instruction.operand()) {
+                _ if extern_targets.contains_key(&address) => {
+                    output.push(' ');
+                    output.push_str(extern_targets[&address]);
                 }
-                "+" | "-" => {
-                    while let Some(op) = operators.last() {
-                        if op == "(" || self.precedence(op) < self.precedence(&token) {
-                            break;
-                        }
-                        output.push(operators.pop().unwrap());
-                    }
-                    operators.push(token);
+                (Some(target), _) if entries.contains_key(&target) => {
+                    output.push(' ');
+                    output.push_str(&entries[&target].name);
+                }
+                (Some(target), _) if labels.contains_key(&target) => {
+                    output.push(' ');
+                    output.push_str(&display(&labels[&target][0], current));
                 }
-                "*" | "/" | "%" => {
-                    while let Some(op) = operators.last() {
-                        if op == "(" || self.precedence(op) < self.precedence(&token) {
-                            break;
-                        }
-                        output.push(operators.pop().unwrap());
+                (_, Some(Value::Integer(index))) if is_global_access(opcode) => {
+                    output.push(' ');
+                    match usize::try_from(*index).ok().and_then(|index| constants.get(index)) {
+                        Some(Value::String(name)) if is_identifier(name) => output.push_str(name),
+                        Some(name @ Value::String(_)) => output.push_str(&Self::format_value(name)?),
+                        _ => return Err(DisassemblerError::UnrepresentableValue(format!(
+                            "{} operand {} does not name a global",
+                            mnemonic, index
+                        ))),
                     }
-                    operators.push(token);
                 }
-                _ => {
-                    // Number or variable
-                    output.push(token);
+                (_, Some(operand)) => {
+                    output.push(' ');
+                    match Self::constant_reference(instruction, constants, symbols) {
+                        Some(index) => output.push_str(&constant_names[index]),
+                        None => output.push_str(&Self::format_value(operand)?),
+                    }
                 }
+                (_, None) => {}
             }
+            output.push('\n');
         }
-        
-        while let Some(op) = operators.pop() {
-            output.push(op);
+
+        if current.is_some() {
+            output.push_str(".endfunc\n");
         }
-        
+        for name in labels.get(&instructions.len()).into_iter().flatten() {
+            output.push_str(&format!("{}:\n", name));
+        }
+
         Ok(output)
     }
 
-    fn precedence(&self, op: &str) -> i32 {
-        match op {
-            "+" | "-" => 1,
-            "*" | "/" | "%" => 2,
-            _ => 0,
+    /// Constants-pool index of a named constant that `instruction` refers
+    /// to and that assembles back to the same instruction by name
+    fn constant_reference(
+        instruction: &Instruction,
+        constants: &[Value],
+        symbols: &SymbolTable,
+    ) -> Option<usize> {
+        let Some(Value::Integer(index)) = instruction.operand() else {
+            return None;
+        };
+        let index = usize::try_from(*index).ok()?;
+        symbols.constant_name(index)?;
+        match instruction.opcode() {
+            // Pushing a .string/.data name assembles to LoadConstant
+            Opcode::Push => {
+                let constant = constants.get(index)?;
+                (!Self::is_data(constant)).then_some(index)
+            }
+            Opcode::LoadConstant if index < constants.len() => Some(index),
+            _ => None,
+        }
+    }
+
+    /// Whether `value` is declared with `.string`/`.data` rather than
+    /// `.const`
+    fn is_data(value: &Value) -> bool {
+        match value {
+            Value::Array(_) => true,
+            Value::String(s) => s.contains(char::is_whitespace),
+            _ => false,
+        }
+    }
+
+    fn jump_target(instruction: &Instruction) -> Option<usize> {
+        match (instruction.opcode(), instruction.operand()) {
+            (
+                Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Call,
+                Some(Value::Integer(target)),
+            ) => usize::try_from(*target).ok(),
+            _ => None,
+        }
+    }
+
+    /// Declaration of constant `name`: `.const` for literals, `.string` for
+    /// text with spaces and `.data` for arrays
+    fn format_constant(name: &str, value: &Value) -> Result<String, DisassemblerError> {
+        match value {
+            Value::Array(array) => {
+                let elements = array
+                    .to_vec()
+                    .iter()
+                    .map(Self::format_value)
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(format!(".data {} [{}]", name, elements.join(", ")))
+            }
+            Value::String(s) if s.contains(char::is_whitespace) => {
+                Ok(format!(".string {} \"{}\"", name, escape(s, '"')))
+            }
+            other => Ok(format!(".const {} {}", name, Self::format_value(other)?)),
+        }
+    }
+
+    /// Value as an assembler literal
+    fn format_value(value: &Value) -> Result<String, DisassemblerError> {
+        let unrepresentable = || DisassemblerError::UnrepresentableValue(format!("{:?}", value));
+        match value {
+            Value::Integer(i) => Ok(i.to_string()),
+            Value::Float(f) => Ok(format!("{:?}", f)),
+            Value::Decimal(d) => Ok(format!("{}d", d)),
+            Value::Boolean(b) => Ok(b.to_string()),
+            Value::Null => Ok("null".to_string()),
+            Value::String(s) => Ok(format!("\"{}\"", escape(s, '"'))),
+            Value::Char(c) => Ok(format!("'{}'", escape(&c.to_string(), '\''))),
+            _ => Err(unrepresentable()),
         }
     }
 }
 
-impl Default for SimpleCompiler {
+impl Default for Disassembler {
     fn default() -> Self {
         Self::new()
     }
 }
 
+/// Whether `opcode` reads or writes a global by name
+fn is_global_access(opcode: Opcode) -> bool {
+    matches!(opcode, Opcode::LoadGlobal | Opcode::StoreGlobal)
+}
+
+fn is_identifier(text: &str) -> bool {
+    text.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Moves the names `LDG`/`STG` were assembled with into the constants
+/// pool, pointing each instruction at its name's entry
+fn pool_global_names(module: &mut BytecodeModule) {
+    for address in 0..module.instructions.len() {
+        let instruction = &module.instructions[address];
+        let opcode = instruction.opcode();
+        let Some(name @ Value::String(_)) = instruction.operand().filter(|_| is_global_access(opcode))
+        else {
+            continue;
+        };
+        let name = name.clone();
+        let index = match module.constants.iter().position(|constant| *constant == name) {
+            Some(index) => index,
+            None => module.add_constant(name),
+        };
+        module.instructions[address] = Instruction::new(opcode, Some(Value::Integer(index as i64)));
+    }
+}
+
+/// Opcode named by an assembly mnemonic or one of its long forms, in any
+/// case; the inverse of [`mnemonic`]
+pub fn opcode_for(mnemonic: &str) -> Option<Opcode> {
+    match mnemonic.to_uppercase().as_str() {
+        "PUSH" => Some(Opcode::Push),
+        "POP" => Some(Opcode::Pop),
+        "LDC" | "LOAD_CONST" => Some(Opcode::LoadConstant),
+        "LDG" | "LOAD_GLOBAL" => Some(Opcode::LoadGlobal),
+        "STG" | "STORE_GLOBAL" => Some(Opcode::StoreGlobal),
+        "PRINT" => Some(Opcode::Print),
+        "READ_LINE" | "READLN" => Some(Opcode::ReadLine),
+        "ADD" => Some(Opcode::Add),
+        "SUB" | "SUBTRACT" => Some(Opcode::Sub),
+        "MUL" | "MULTIPLY" => Some(Opcode::Mul),
+        "DIV" | "DIVIDE" => Some(Opcode::Div),
+        "MOD" | "MODULO" => Some(Opcode::Mod),
+        "NEG" | "NEGATE" => Some(Opcode::Negate),
+        "AND" => Some(Opcode::And),
+        "OR" => Some(Opcode::Or),
+        "NOT" => Some(Opcode::Not),
+        "XOR" => Some(Opcode::Xor),
+        "COALESCE" | "NULL_COALESCE" => Some(Opcode::NullCoalesce),
+        "EQ" | "EQUAL" => Some(Opcode::Equal),
+        "NE" | "NOT_EQUAL" => Some(Opcode::NotEqual),
+        "REF_EQ" | "REF_EQUAL" => Some(Opcode::RefEqual),
+        "DEEP_EQ" | "DEEP_EQUAL" => Some(Opcode::DeepEqual),
+        "LT" | "LESS" => Some(Opcode::LessThan),
+        "LE" | "LESS_EQUAL" => Some(Opcode::LessEqual),
+        "GT" | "GREATER" => Some(Opcode::GreaterThan),
+        "GE" | "GREATER_EQUAL" => Some(Opcode::GreaterEqual),
+        "JMP" | "JUMP" => Some(Opcode::Jump),
+        "JT" | "JUMP_TRUE" => Some(Opcode::JumpIfTrue),
+        "JF" | "JUMP_FALSE" => Some(Opcode::JumpIfFalse),
+        "CALL" => Some(Opcode::Call),
+        "CALLI" | "CALL_INDIRECT" => Some(Opcode::CallIndirect),
+        "CALL_NATIVE" => Some(Opcode::CallNative),
+        "RET" | "RETURN" => Some(Opcode::Return),
+        "LOAD" => Some(Opcode::Load),
+        "STORE" => Some(Opcode::Store),
+        "DUP" | "DUPLICATE" => Some(Opcode::Dup),
+        "SWAP" => Some(Opcode::Swap),
+        "NEW" | "NEW_OBJECT" => Some(Opcode::NewObject),
+        "GET_FIELD" => Some(Opcode::GetField),
+        "GET_FIELD?" | "SAFE_GET_FIELD" => Some(Opcode::SafeGetField),
+        "SET_FIELD" => Some(Opcode::SetField),
+        "FREEZE" => Some(Opcode::Freeze),
+        "NEW_ARRAY" => Some(Opcode::NewArray),
+        "GET_ARRAY" => Some(Opcode::ArrayGet),
+        "SET_ARRAY" => Some(Opcode::ArraySet),
+        "LEN" | "LENGTH" => Some(Opcode::ArrayLength),
+        "SORT" => Some(Opcode::ArraySort),
+        "NEW_BYTES" => Some(Opcode::NewBytes),
+        "GET_BYTE" => Some(Opcode::BytesGet),
+        "SET_BYTE" => Some(Opcode::BytesSet),
+        "BYTES_LEN" => Some(Opcode::BytesLength),
+        "BYTES_SLICE" => Some(Opcode::BytesSlice),
+        "NEW_INT_ARRAY" => Some(Opcode::NewIntArray),
+        "GET_INT" => Some(Opcode::IntArrayGet),
+        "SET_INT" => Some(Opcode::IntArraySet),
+        "NEW_FLOAT_ARRAY" => Some(Opcode::NewFloatArray),
+        "GET_FLOAT" => Some(Opcode::FloatArrayGet),
+        "SET_FLOAT" => Some(Opcode::FloatArraySet),
+        "TYPED_LEN" => Some(Opcode::TypedArrayLength),
+        "CHAR_AT" => Some(Opcode::CharAt),
+        "CHAR_CODE" => Some(Opcode::CharCode),
+        "CODE_TO_CHAR" => Some(Opcode::CodeToChar),
+        "SUBSTR" | "SUBSTRING" => Some(Opcode::Substring),
+        "SPLIT" => Some(Opcode::Split),
+        "STRCAT" | "CONCAT" => Some(Opcode::StrConcat),
+        "TUPLE" | "MAKE_TUPLE" => Some(Opcode::MakeTuple),
+        "TUPLE_GET" => Some(Opcode::TupleGet),
+        "HALT" => Some(Opcode::Halt),
+        _ => None,
+    }
+}
+
+/// Canonical assembly mnemonic for `opcode`; `None` for the `Wide` encoding
+/// prefix, which never appears as an instruction
+pub fn mnemonic(opcode: Opcode) -> Option<&'static str> {
+    let name = match opcode {
+        Opcode::Add => "ADD",
+        Opcode::Sub => "SUB",
+        Opcode::Mul => "MUL",
+        Opcode::Div => "DIV",
+        Opcode::Mod => "MOD",
+        Opcode::Negate => "NEG",
+        Opcode::Push => "PUSH",
+        Opcode::Pop => "POP",
+        Opcode::Dup => "DUP",
+        Opcode::Swap => "SWAP",
+        Opcode::LoadConstant => "LDC",
+        Opcode::LoadGlobal => "LDG",
+        Opcode::StoreGlobal => "STG",
+        Opcode::Print => "PRINT",
+        Opcode::ReadLine => "READ_LINE",
+        Opcode::Jump => "JMP",
+        Opcode::JumpIfTrue => "JT",
+        Opcode::JumpIfFalse => "JF",
+        Opcode::Call => "CALL",
+        Opcode::Return => "RET",
+        Opcode::CallIndirect => "CALLI",
+        Opcode::CallNative => "CALL_NATIVE",
+        Opcode::Equal => "EQ",
+        Opcode::NotEqual => "NE",
+        Opcode::LessThan => "LT",
+        Opcode::LessEqual => "LE",
+        Opcode::GreaterThan => "GT",
+        Opcode::GreaterEqual => "GE",
+        Opcode::RefEqual => "REF_EQ",
+        Opcode::DeepEqual => "DEEP_EQ",
+        Opcode::And => "AND",
+        Opcode::Or => "OR",
+        Opcode::Not => "NOT",
+        Opcode::Xor => "XOR",
+        Opcode::NullCoalesce => "COALESCE",
+        Opcode::Load => "LOAD",
+        Opcode::Store => "STORE",
+        Opcode::NewObject => "NEW",
+        Opcode::GetField => "GET_FIELD",
+        Opcode::SetField => "SET_FIELD",
+        Opcode::SafeGetField => "GET_FIELD?",
+        Opcode::NewArray => "NEW_ARRAY",
+        Opcode::ArrayGet => "GET_ARRAY",
+        Opcode::ArraySet => "SET_ARRAY",
+        Opcode::ArrayLength => "LEN",
+        Opcode::ArraySort => "SORT",
+        Opcode::NewBytes => "NEW_BYTES",
+        Opcode::BytesGet => "GET_BYTE",
+        Opcode::BytesSet => "SET_BYTE",
+        Opcode::BytesLength => "BYTES_LEN",
+        Opcode::BytesSlice => "BYTES_SLICE",
+        Opcode::CharAt => "CHAR_AT",
+        Opcode::CharCode => "CHAR_CODE",
+        Opcode::CodeToChar => "CODE_TO_CHAR",
+        Opcode::Substring => "SUBSTR",
+        Opcode::Split => "SPLIT",
+        Opcode::StrConcat => "STRCAT",
+        Opcode::MakeTuple => "TUPLE",
+        Opcode::TupleGet => "TUPLE_GET",
+        Opcode::Freeze => "FREEZE",
+        Opcode::NewIntArray => "NEW_INT_ARRAY",
+        Opcode::IntArrayGet => "GET_INT",
+        Opcode::IntArraySet => "SET_INT",
+        Opcode::NewFloatArray => "NEW_FLOAT_ARRAY",
+        Opcode::FloatArrayGet => "GET_FLOAT",
+        Opcode::FloatArraySet => "SET_FLOAT",
+        Opcode::TypedArrayLength => "TYPED_LEN",
+        Opcode::Halt => "HALT",
+        Opcode::Wide => return None,
+    };
+    Some(name)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -420,22 +1666,655 @@ mod tests {
     }
 
     #[test]
-    fn test_simple_compiler() {
-        let mut compiler = SimpleCompiler::new();
-        let result = compiler.compile_expression("5 + 3 * 2");
-        assert!(result.is_ok());
-        
-        let (instructions, constants) = result.unwrap();
-        assert!(instructions.len() > 4); // Should have push, push, push, mul, add, halt
+    fn test_errors_report_line_and_column() {
+        let mut assembler = Assembler::new();
+        let source = "PUSH 1\n\n    ; comment\n    PUSH 2\n    FROB\n";
+
+        let error = assembler.assemble(source).unwrap_err();
+        assert!(matches!(&error, AssemblerError::InvalidOpcode { opcode, .. } if opcode == "FROB"));
+        assert_eq!(error.pos(), SourcePos::new(5, 5));
+        assert_eq!(error.to_string(), "line 5, column 5: Invalid opcode: FROB");
     }
 
     #[test]
-    fn test_compiler_with_parentheses() {
-        let mut compiler = SimpleCompiler::new();
-        let result = compiler.compile_expression("(5 + 3) * 2");
-        assert!(result.is_ok());
-        
-        let (instructions, _) = result.unwrap();
-        assert!(instructions.len() > 4);
+    fn test_operand_errors_point_at_operand() {
+        let mut assembler = Assembler::new();
+        let error = assembler.assemble("PUSH 1\n  PUSH  @oops").unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+        assert_eq!(error.pos(), SourcePos::new(2, 9));
+
+        let mut assembler = Assembler::new();
+        let error = assembler.assemble("start:\nJMP strat").unwrap_err();
+        assert!(matches!(&error, AssemblerError::UnknownLabel { label, .. } if label == "strat"));
+        assert_eq!(error.pos(), SourcePos::new(2, 5));
+
+        let mut assembler = Assembler::new();
+        let error = assembler.assemble("PUSH 1 2").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 8));
+    }
+
+    #[test]
+    fn test_directive_and_label_errors_include_text() {
+        let mut assembler = Assembler::new();
+        let error = assembler.assemble("NOP_LABEL:\nHALT\n NOP_LABEL:").unwrap_err();
+        assert!(matches!(error, AssemblerError::DuplicateLabel { .. }));
+        assert_eq!(error.pos(), SourcePos::new(3, 2));
+
+        let mut assembler = Assembler::new();
+        let error = assembler.assemble(".const ONLY_NAME").unwrap_err();
+        match error {
+            AssemblerError::ParseError { text, pos, .. } => {
+                assert_eq!(text, ".const ONLY_NAME");
+                assert_eq!(pos, SourcePos::new(1, 1));
+            }
+            other => panic!("Expected parse error, got {:?}", other),
+        }
+    }
+
+    fn assert_same_program(
+        a: &(Vec<Instruction>, Vec<Value>),
+        b: &(Vec<Instruction>, Vec<Value>),
+    ) {
+        assert_eq!(a.1, b.1);
+        assert_eq!(a.0.len(), b.0.len());
+        for (x, y) in a.0.iter().zip(&b.0) {
+            assert_eq!(x.opcode(), y.opcode());
+            assert_eq!(x.operand(), y.operand());
+        }
+    }
+
+    #[test]
+    fn test_disassemble_roundtrip() {
+        let source = r#"
+            .const LIMIT 10
+            .const GREETING "hi"
+            .const RATE 0.25d
+            PUSH 0
+        loop:
+            DUP
+            PUSH LIMIT
+            LT
+            JF end
+            PUSH 1
+            ADD
+            CALL helper
+            JMP loop
+        helper:
+            PUSH 2.5
+            POP
+            PUSH null
+            POP
+            RET
+        end:
+            GET_FIELD "name"
+            HALT
+        "#;
+
+        let first = Assembler::new().assemble(source).unwrap();
+        let text = Disassembler::new().disassemble(&first.0, &first.1).unwrap();
+        assert!(text.contains(".const C1 \"hi\""));
+        assert!(text.contains("JF L14"));
+
+        let second = Assembler::new().assemble(&text).unwrap();
+        assert_same_program(&first, &second);
+
+        // Disassembling again is stable
+        let again = Disassembler::new().disassemble(&second.0, &second.1).unwrap();
+        assert_eq!(again, text);
+    }
+
+    #[test]
+    fn test_every_mnemonic_assembles_to_its_opcode() {
+        for byte in 0..=u8::MAX {
+            let Some(opcode) = Opcode::from_u8(byte) else { continue };
+            match mnemonic(opcode) {
+                Some(name) => {
+                    let (program, _) = Assembler::new().assemble(name).unwrap();
+                    assert_eq!(program[0].opcode(), opcode, "mnemonic {}", name);
+                }
+                None => assert_eq!(opcode, Opcode::Wide),
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_disassemble_unrepresentable_value() {
+        let program = vec![Instruction::new(
+            Opcode::Push,
+            Some(Value::Function { address: 0, arity: 0 }),
+        )];
+        assert!(matches!(
+            Disassembler::new().disassemble(&program, &[]),
+            Err(DisassemblerError::UnrepresentableValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_string_and_array_data() {
+        let source = r#"
+            .string GREETING "hello, world"
+            .data PRIMES [2, 3, 5, 7]
+            PUSH GREETING
+            LDC PRIMES
+            HALT
+        "#;
+
+        let (instructions, constants) = Assembler::new().assemble(source).unwrap();
+        assert_eq!(constants[0], Value::String("hello, world".into()));
+        assert_eq!(
+            constants[1],
+            Value::Array(GcPtr::detached(Array::new(vec![
+                Value::Integer(2),
+                Value::Integer(3),
+                Value::Integer(5),
+                Value::Integer(7),
+            ])))
+        );
+        assert_eq!(instructions[0].opcode(), Opcode::LoadConstant);
+        assert_eq!(instructions[0].operand(), Some(&Value::Integer(0)));
+        assert_eq!(instructions[1].opcode(), Opcode::LoadConstant);
+        assert_eq!(instructions[1].operand(), Some(&Value::Integer(1)));
+
+        // Disassembly round-trips data declarations
+        let text = Disassembler::new().disassemble(&instructions, &constants).unwrap();
+        assert!(text.contains(".string C0 \"hello, world\""));
+        assert!(text.contains(".data C1 [2, 3, 5, 7]"));
+        let again = Assembler::new().assemble(&text).unwrap();
+        assert_same_program(&(instructions, constants), &again);
+    }
+
+    #[test]
+    fn test_data_directive_errors() {
+        let error = Assembler::new().assemble(".string NAME unquoted").unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 14));
+
+        let error = Assembler::new().assemble(".data NAME [1, , 3]").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+
+        let error = Assembler::new().assemble(".data NAME [1, oops]").unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 16));
+    }
+
+    #[test]
+    fn test_function_directives() {
+        let source = r#"
+            CALL add
+            CALL noop
+            HALT
+        .func add arity=2 locals=3 returns=1
+            LOAD 0
+            LOAD 1
+            ADD
+            RET
+        .endfunc
+        .func noop
+            RET
+        .endfunc
+        "#;
+        let module = Assembler::new().assemble_module(source).unwrap();
+        assert_eq!(
+            module.functions,
+            vec![
+                FunctionInfo::new("add", 3, 2, 3).with_returns(1),
+                FunctionInfo::new("noop", 7, 0, 0),
+            ]
+        );
+        assert_eq!(module.functions[0].stack_effect(), Some(-1));
+        assert_eq!(module.functions[1].stack_effect(), None);
+        // Function names resolve as call targets
+        assert_eq!(module.instructions[0].operand(), Some(&Value::Integer(3)));
+        assert_eq!(module.instructions[1].operand(), Some(&Value::Integer(7)));
+        assert_eq!(module.function("add").map(|f| f.address), Some(3));
+        assert_eq!(module.function_at(7).map(|f| f.name.as_str()), Some("noop"));
+
+        // Locals default to the arity
+        let module = Assembler::new()
+            .assemble_module(".func f arity=2\nRET\n.endfunc")
+            .unwrap();
+        assert_eq!(module.functions[0].locals, 2);
+    }
+
+    #[test]
+    fn test_function_directive_errors() {
+        let error = Assembler::new().assemble(".func f\nRET").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 1));
+
+        let error = Assembler::new().assemble("RET\n.endfunc").unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(2, 1));
+
+        let error = Assembler::new()
+            .assemble(".func f\n.func g\nRET\n.endfunc\n.endfunc")
+            .unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(2, 1));
+
+        let error = Assembler::new().assemble(".func f\n.endfunc").unwrap_err();
+        assert!(error.to_string().contains("has no instructions"));
+
+        let error = Assembler::new()
+            .assemble(".func f arity=2 locals=1\nRET\n.endfunc")
+            .unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+
+        let error = Assembler::new()
+            .assemble(".func f locals=many\nRET\n.endfunc")
+            .unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 9));
+
+        let error = Assembler::new()
+            .assemble(".func f stack=1\nRET\n.endfunc")
+            .unwrap_err();
+        assert!(error.to_string().contains("Unknown function attribute"));
+
+        let error = Assembler::new()
+            .assemble("f:\nRET\n.func f\nRET\n.endfunc")
+            .unwrap_err();
+        assert!(matches!(error, AssemblerError::DuplicateLabel { .. }));
+    }
+
+    #[test]
+    fn test_disassemble_module_roundtrip() {
+        let source = r#"
+            PUSH 1
+            PUSH 2
+            CALL add
+            JMP done
+        .func add arity=2 locals=2 returns=1
+            LOAD 0
+            LOAD 1
+            ADD
+            RET
+        .endfunc
+        done:
+            HALT
+        "#;
+        let first = Assembler::new().assemble_module(source).unwrap();
+        let text = Disassembler::new().disassemble_module(&first).unwrap();
+        assert!(text.contains(".func add arity=2 locals=2 returns=1"));
+        assert!(text.contains("CALL add"));
+
+        let second = Assembler::new().assemble_module(&text).unwrap();
+        assert_eq!(second.functions, first.functions);
+        assert_same_program(
+            &(first.instructions, first.constants),
+            &(second.instructions, second.constants),
+        );
+    }
+
+    #[test]
+    fn test_quoted_strings_and_escapes() {
+        let source = r#"
+            .const GREETING "hello world"
+            PUSH "tab\there"
+            PUSH "say \"hi\"; bye"
+            PUSH "back\\slash \u{e9}"
+            PUSH 'x'
+            PUSH '\n'
+            PUSH '\''
+            PUSH " "
+            HALT
+        "#;
+        let (instructions, constants) = Assembler::new().assemble(source).unwrap();
+        assert_eq!(constants[0], Value::String("hello world".into()));
+
+        let operands: Vec<_> = instructions[..7]
+            .iter()
+            .map(|instruction| instruction.operand().unwrap().clone())
+            .collect();
+        assert_eq!(
+            operands,
+            vec![
+                Value::String("tab\there".into()),
+                Value::String("say \"hi\"; bye".into()),
+                Value::String("back\\slash \u{e9}".into()),
+                Value::Char('x'),
+                Value::Char('\n'),
+                Value::Char('\''),
+                Value::String(" ".into()),
+            ]
+        );
+
+        // Commas inside quoted array elements do not split them
+        let (_, constants) = Assembler::new()
+            .assemble(".data WORDS [\"a, b\", 'c', \"]\"]\nHALT")
+            .unwrap();
+        assert_eq!(
+            constants[0],
+            Value::Array(GcPtr::detached(Array::new(vec![
+                Value::String("a, b".into()),
+                Value::Char('c'),
+                Value::String("]".into()),
+            ])))
+        );
+    }
+
+    #[test]
+    fn test_quoted_literal_errors() {
+        let error = Assembler::new().assemble("PUSH \"open").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 6));
+
+        // Unknown escapes point at the backslash
+        let error = Assembler::new().assemble("PUSH \"a\\qb\"").unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 8));
+
+        let error = Assembler::new().assemble("PUSH \"a\"b").unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 9));
+
+        let error = Assembler::new().assemble("PUSH 'ab'").unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+
+        let error = Assembler::new().assemble("PUSH '\\u{110000}'").unwrap_err();
+        assert!(matches!(error, AssemblerError::InvalidValue { .. }));
+
+        let error = Assembler::new().assemble("PUSH \"a\" \"b\"").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 10));
+    }
+
+    #[test]
+    fn test_disassemble_escaped_literals() {
+        let instructions = vec![
+            Instruction::new(Opcode::Push, Some(Value::String("two words".into()))),
+            Instruction::new(Opcode::Push, Some(Value::String("quote \" and \\ and\nnewline".into()))),
+            Instruction::new(Opcode::Push, Some(Value::Char('\t'))),
+            Instruction::new(Opcode::Push, Some(Value::Char('\''))),
+            Instruction::new(Opcode::Halt, None),
+        ];
+        let constants = vec![
+            Value::String("line one\nline two".into()),
+            Value::Array(GcPtr::detached(Array::new(vec![
+                Value::String("x, y]".into()),
+                Value::Char(','),
+            ]))),
+        ];
+
+        let text = Disassembler::new().disassemble(&instructions, &constants).unwrap();
+        assert!(text.contains(".string C0 \"line one\\nline two\""));
+        assert!(text.contains("PUSH \"quote \\\" and \\\\ and\\nnewline\""));
+        assert!(text.contains("PUSH '\\t'"));
+
+        let again = Assembler::new().assemble(&text).unwrap();
+        assert_same_program(&(instructions, constants), &again);
+    }
+
+    #[test]
+    fn test_numeric_literal_forms() {
+        let source = "
+            PUSH 0xFF
+            PUSH 0b1010
+            PUSH 1_000_000
+            PUSH -0x10
+            PUSH 0xFFFF_FFFF_FFFF_FFFF
+            PUSH -0x8000_0000_0000_0000
+            PUSH 1_000.5
+            PUSH 1_234.50d
+            HALT
+        ";
+        let (instructions, _) = Assembler::new().assemble(source).unwrap();
+        let operands: Vec<_> = instructions[..8]
+            .iter()
+            .map(|instruction| instruction.operand().unwrap().clone())
+            .collect();
+        assert_eq!(
+            operands,
+            vec![
+                Value::Integer(255),
+                Value::Integer(10),
+                Value::Integer(1_000_000),
+                Value::Integer(-16),
+                Value::Integer(-1),
+                Value::Integer(i64::MIN),
+                Value::Float(1000.5),
+                Value::Decimal("1234.5".parse().unwrap()),
+            ]
+        );
+
+        // Labels and constants are unaffected
+        let (instructions, _) = Assembler::new().assemble(".const X_Y 3\nPUSH X_Y").unwrap();
+        assert_eq!(instructions[0].operand(), Some(&Value::Integer(0)));
+    }
+
+    #[test]
+    fn test_malformed_numeric_literals() {
+        let literals = [
+            "0x",
+            "0xG1",
+            "0b102",
+            "1__000",
+            "1_",
+            "0x_FF",
+            "0x-1",
+            "0x1_0000_0000_0000_0000",
+            "-0x8000_0000_0000_0001",
+        ];
+        for literal in literals {
+            let error = Assembler::new().assemble(&format!("PUSH {}", literal)).unwrap_err();
+            assert!(
+                matches!(error, AssemblerError::InvalidValue { .. }),
+                "{} gave {:?}",
+                literal,
+                error
+            );
+            assert_eq!(error.pos(), SourcePos::new(1, 6));
+        }
+    }
+
+    #[test]
+    fn test_constant_expressions() {
+        let source = "
+            .const KB 1024
+            .const SIZE 4*KB
+            .const HALF SIZE / 2
+            .const MASK (1 + 2) * -(4 - 0x10)
+            .const RATE 0.5 * 3
+            .const PRICE 1.25d * 2
+            .const ALIAS SIZE
+            .const NAME \"a b\"
+            .const GREETING NAME
+            HALT
+        ";
+        let module = Assembler::new().assemble_module(source).unwrap();
+        // Aliases share the entry of the constant they repeat
+        assert_eq!(
+            module.constants,
+            vec![
+                Value::Integer(1024),
+                Value::Integer(4096),
+                Value::Integer(2048),
+                Value::Integer(36),
+                Value::Float(1.5),
+                Value::Decimal("2.5".parse().unwrap()),
+                Value::String("a b".into()),
+            ]
+        );
+        assert_eq!(module.symbols.constants["ALIAS"], 1);
+        assert_eq!(module.symbols.constants["GREETING"], 6);
+    }
+
+    #[test]
+    fn test_constant_expression_errors() {
+        let error = Assembler::new().assemble(".const X 4 * LATER\n.const LATER 1").unwrap_err();
+        assert!(matches!(error, AssemblerError::UnknownLabel { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 14));
+
+        let error = Assembler::new().assemble(".const X 1 / (2 - 2)").unwrap_err();
+        assert!(error.to_string().contains("Division by zero"));
+        assert_eq!(error.pos(), SourcePos::new(1, 12));
+
+        let error = Assembler::new()
+            .assemble(".const X 0x7FFF_FFFF_FFFF_FFFF + 1")
+            .unwrap_err();
+        assert!(error.to_string().contains("Overflow"));
+
+        let error = Assembler::new().assemble(".const X (1 + 2").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+        assert_eq!(error.pos(), SourcePos::new(1, 16));
+
+        let error = Assembler::new().assemble(".const X 1 +").unwrap_err();
+        assert!(error.to_string().contains("ends unexpectedly"));
+
+        let error = Assembler::new().assemble(".const X 1 2").unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(1, 12));
+
+        let error = Assembler::new().assemble(".const S \"a\"\n.const X S * 2").unwrap_err();
+        assert!(error.to_string().contains("compatible types"));
+
+        let error = Assembler::new().assemble(".const X 1.5d * 2.0").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+    }
+
+    #[test]
+    fn test_local_labels_are_scoped_to_functions() {
+        let source = "
+            CALL first
+            CALL second
+            HALT
+        .func first
+        .loop:
+            JMP .done
+        .done:
+            RET
+        .endfunc
+        .func second
+        .loop:
+            JMP .loop
+        .endfunc
+        ";
+        let (instructions, _) = Assembler::new().assemble(source).unwrap();
+        assert_eq!(instructions[3].operand(), Some(&Value::Integer(4)));
+        assert_eq!(instructions[5].operand(), Some(&Value::Integer(5)));
+
+        // Labels whose names start like a directive are still labels
+        let (instructions, _) = Assembler::new().assemble(".constant:\nJMP .constant").unwrap();
+        assert_eq!(instructions[0].operand(), Some(&Value::Integer(0)));
+    }
+
+    #[test]
+    fn test_local_label_errors() {
+        let source = "
+        .func f
+        .loop:
+            RET
+        .loop:
+            RET
+        .endfunc
+        ";
+        let error = Assembler::new().assemble(source).unwrap_err();
+        assert!(matches!(error, AssemblerError::DuplicateLabel { .. }));
+        assert_eq!(error.pos(), SourcePos::new(5, 9));
+
+        // A local label is not visible from another function
+        let source = "
+        .func f
+        .inner:
+            RET
+        .endfunc
+        .func g
+            JMP .inner
+        .endfunc
+        ";
+        let error = Assembler::new().assemble(source).unwrap_err();
+        assert!(matches!(error, AssemblerError::UnknownLabel { .. }));
+        assert_eq!(error.pos(), SourcePos::new(7, 17));
+    }
+
+    #[test]
+    fn test_symbol_table() {
+        let source = "
+            .const LIMIT 3
+            .string TITLE \"count up\"
+            PUSH 0
+        top:
+            CALL step
+            JMP top
+        .func step
+        .again:
+            JMP .again
+        .endfunc
+        ";
+        let mut assembler = Assembler::new();
+        let module = assembler.assemble_module(source).unwrap();
+        let symbols = &module.symbols;
+
+        assert_eq!(symbols.labels.get("top"), Some(&1));
+        assert_eq!(symbols.labels.get("step.again"), Some(&3));
+        assert!(!symbols.labels.contains_key("step"));
+        assert_eq!(symbols.constants.get("LIMIT"), Some(&0));
+        assert_eq!(symbols.constants.get("TITLE"), Some(&1));
+        assert_eq!(symbols.functions.get("step"), Some(&(3..4)));
+
+        assert_eq!(symbols.label_at(1), Some("top"));
+        assert_eq!(symbols.constant_name(1), Some("TITLE"));
+        assert_eq!(symbols.function_containing(3), Some("step"));
+        assert_eq!(symbols.function_containing(2), None);
+        assert_eq!(assembler.symbols(), symbols);
+    }
+
+    #[test]
+    fn test_module_line_table() {
+        let source = "PUSH 1\n\n; comment\nPUSH 2\nloop:\nADD\nHALT";
+        let module = Assembler::new().assemble_module(source).unwrap();
+        let lines = &module.debug_info.lines;
+
+        assert_eq!(lines.entries().len(), 4);
+        assert_eq!(lines.line_at(0), Some(1));
+        assert_eq!(lines.line_at(1), Some(4));
+        assert_eq!(lines.line_at(2), Some(6));
+        assert_eq!(lines.line_at(3), Some(7));
+        assert_eq!(lines.line_at(4), Some(7));
+
+        let table = LineTable::from_lines([Some(1), Some(1), None, Some(2)]);
+        assert_eq!(table.entries().len(), 3);
+        assert_eq!(table.line_at(1), Some(1));
+        assert_eq!(table.line_at(2), None);
+        assert!(LineTable::default().line_at(0).is_none());
+    }
+
+    #[test]
+    fn test_disassemble_with_symbols() {
+        let source = "
+            .const LIMIT 10
+            .string TITLE \"hello there\"
+            PUSH LIMIT
+            PUSH TITLE
+        loop:
+            CALL helper
+            JT loop
+            HALT
+        .func helper
+            DUP
+        .inner:
+            DUP
+            JF .inner
+            RET
+        .endfunc
+        tail:
+            JMP tail
+        ";
+        let first = Assembler::new().assemble_module(source).unwrap();
+        let text = Disassembler::new().disassemble_module(&first).unwrap();
+        assert!(text.contains(".const LIMIT 10"));
+        assert!(text.contains(".string TITLE \"hello there\""));
+        assert!(text.contains("PUSH LIMIT"));
+        assert!(text.contains("LDC TITLE"));
+        assert!(text.contains("loop:\n"));
+        assert!(text.contains(".inner:\n"));
+        assert!(text.contains("JF .inner"));
+        assert!(text.contains(".endfunc\ntail:\n"));
+
+        let second = Assembler::new().assemble_module(&text).unwrap();
+        assert_eq!(second.symbols, first.symbols);
+        assert_eq!(second.functions, first.functions);
+        assert_same_program(
+            &(first.instructions, first.constants),
+            &(second.instructions, second.constants),
+        );
+    }
+}
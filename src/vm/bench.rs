@@ -0,0 +1,353 @@
+//! Canonical workloads for comparing the VM's execution tiers.
+//!
+//! [`run_suite`] runs each [`Workload`] under every [`Tier`] and returns a
+//! [`BenchReport`], whose `Display` is the table the `benchmark` command
+//! prints. The VM does not generate native code yet, so every tier is the
+//! interpreter: the profiled tier runs with the hot-spot profiler on, and
+//! the optimized tier also puts the module through the bytecode optimizer
+//! before loading it. [`run_suite_traced`] also writes a trace of every
+//! instruction each run executes.
+
+use crate::vm::assembler::Assembler;
+use crate::vm::module::BytecodeModule;
+use crate::vm::optimizer::{OptLevel, optimize_module};
+use crate::vm::runtime::{VirtualMachine, VmError};
+use crate::vm::trace::{TraceError, TraceWriter};
+use std::fmt;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// How a workload is run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tier {
+    /// The plain interpreter
+    Interpreter,
+    /// The interpreter with the hot-spot profiler on
+    Profiled,
+    /// The profiled interpreter on a module optimized at [`OptLevel::Full`]
+    Optimized,
+}
+
+impl Tier {
+    pub const ALL: [Tier; 3] = [Tier::Interpreter, Tier::Profiled, Tier::Optimized];
+
+    /// A VM for this tier with `module` loaded
+    fn load(self, mut module: BytecodeModule) -> Result<VirtualMachine, VmError> {
+        if self == Tier::Optimized {
+            optimize_module(&mut module, OptLevel::Full);
+        }
+        let mut vm = VirtualMachine::builder()
+            .with_max_instructions(u64::MAX)
+            .with_profiling(self != Tier::Interpreter)
+            .build();
+        vm.load_module(module)?;
+        Ok(vm)
+    }
+}
+
+impl fmt::Display for Tier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Tier::Interpreter => "interpreter",
+            Tier::Profiled => "profiled",
+            Tier::Optimized => "optimized",
+        })
+    }
+}
+
+/// A program to time, which leaves its result on the stack
+#[derive(Debug, Clone)]
+pub struct Workload {
+    pub name: &'static str,
+    pub description: String,
+    pub module: BytecodeModule,
+}
+
+impl Workload {
+    fn assemble(name: &'static str, description: String, source: &str) -> Self {
+        let module = Assembler::new()
+            .assemble_module(source)
+            .unwrap_or_else(|error| panic!("the {} workload assembles: {}", name, error));
+        Self { name, description, module }
+    }
+
+    /// The naive recursive Fibonacci number `n`: calls and integer
+    /// arithmetic
+    pub fn fib(n: u32) -> Self {
+        let source = format!(
+            "    PUSH {}\n    CALL fib\n    HALT\n\
+             .func fib arity=1 returns=1\n    LOAD 0\n    PUSH 2\n    LT\n    JF .recurse\n    LOAD 0\n    RET\n\
+             .recurse:\n    LOAD 0\n    PUSH 1\n    SUB\n    CALL fib\n    LOAD 0\n    PUSH 2\n    SUB\n    CALL fib\n    ADD\n    RET\n\
+             .endfunc\n",
+            n
+        );
+        Self::assemble("fib", format!("recursive fib({})", n), &source)
+    }
+
+    /// Counts the primes below `limit` with a sieve of Eratosthenes: loops
+    /// over a typed array
+    pub fn sieve(limit: u32) -> Self {
+        // Locals: 0 the composite flags, 1 the candidate, 2 its multiple,
+        // 3 primes found
+        let source = format!(
+            "    CALL main\n    HALT\n\
+             .func main locals=4 returns=1\n    PUSH {limit}\n    NEW_INT_ARRAY\n    STORE 0\n    PUSH 2\n    STORE 1\n    PUSH 0\n    STORE 3\n\
+             .outer:\n    LOAD 1\n    PUSH {limit}\n    LT\n    JF .done\n    LOAD 0\n    LOAD 1\n    GET_INT\n    JT .next\n\
+             \x20   LOAD 3\n    PUSH 1\n    ADD\n    STORE 3\n    LOAD 1\n    LOAD 1\n    MUL\n    STORE 2\n\
+             .inner:\n    LOAD 2\n    PUSH {limit}\n    LT\n    JF .next\n    LOAD 0\n    LOAD 2\n    PUSH 1\n    SET_INT\n\
+             \x20   LOAD 2\n    LOAD 1\n    ADD\n    STORE 2\n    JMP .inner\n\
+             .next:\n    LOAD 1\n    PUSH 1\n    ADD\n    STORE 1\n    JMP .outer\n\
+             .done:\n    LOAD 3\n    RET\n.endfunc\n"
+        );
+        Self::assemble("sieve", format!("primes below {}", limit), &source)
+    }
+
+    /// Builds a string by appending to it `count` times: string
+    /// allocation and copying
+    pub fn strings(count: u32) -> Self {
+        // Locals: 0 the string, 1 the counter
+        let source = format!(
+            "    CALL main\n    HALT\n\
+             .func main locals=2 returns=1\n    PUSH \"\"\n    STORE 0\n    PUSH 0\n    STORE 1\n\
+             .loop:\n    LOAD 1\n    PUSH {count}\n    LT\n    JF .done\n    LOAD 0\n    LOAD 1\n    PUSH 10\n    MOD\n    STRCAT\n    STORE 0\n\
+             \x20   LOAD 1\n    PUSH 1\n    ADD\n    STORE 1\n    JMP .loop\n\
+             .done:\n    LOAD 0\n    RET\n.endfunc\n"
+        );
+        Self::assemble("strings", format!("{} appends", count), &source)
+    }
+
+    /// Allocates `count` short-lived objects, summing a field of each:
+    /// heap allocation and field access
+    pub fn objects(count: u32) -> Self {
+        // Locals: 0 the counter, 1 the sum, 2 the current object
+        let source = format!(
+            "    CALL main\n    HALT\n\
+             .func main locals=3 returns=1\n    PUSH 0\n    STORE 0\n    PUSH 0\n    STORE 1\n\
+             .loop:\n    LOAD 0\n    PUSH {count}\n    LT\n    JF .done\n    NEW\n    STORE 2\n\
+             \x20   LOAD 2\n    LOAD 0\n    SET_FIELD \"value\"\n    LOAD 2\n    GET_FIELD \"value\"\n    LOAD 1\n    ADD\n    STORE 1\n\
+             \x20   LOAD 0\n    PUSH 1\n    ADD\n    STORE 0\n    JMP .loop\n\
+             .done:\n    LOAD 1\n    RET\n.endfunc\n"
+        );
+        Self::assemble("objects", format!("{} objects", count), &source)
+    }
+}
+
+/// The standard suite: fib, sieve, string building and object churn
+pub fn workloads() -> Vec<Workload> {
+    vec![
+        Workload::fib(22),
+        Workload::sieve(50_000),
+        Workload::strings(2_000),
+        Workload::objects(20_000),
+    ]
+}
+
+/// Timings of one workload under one tier
+#[derive(Debug, Clone)]
+pub struct Measurement {
+    pub workload: &'static str,
+    pub tier: Tier,
+    /// What the workload left on the stack, as text
+    pub result: String,
+    /// Instructions one run executed
+    pub instructions: u64,
+    pub runs: u32,
+    pub fastest: Duration,
+    pub mean: Duration,
+}
+
+impl Measurement {
+    pub fn instructions_per_second(&self) -> f64 {
+        let seconds = self.mean.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.instructions as f64 / seconds
+    }
+}
+
+/// Why a benchmark could not finish
+#[derive(Debug)]
+pub enum BenchError {
+    /// The workload failed to load or run
+    Execution { workload: &'static str, tier: Tier, error: VmError },
+    /// A tier computed something other than the interpreter did
+    Mismatch { workload: &'static str, tier: Tier, expected: String, actual: String },
+    /// The trace could not be written
+    Trace(io::Error),
+}
+
+impl fmt::Display for BenchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BenchError::Execution { workload, tier, error } => {
+                write!(f, "{} on the {} tier: {}", workload, tier, error)
+            }
+            BenchError::Mismatch { workload, tier, expected, actual } => write!(
+                f,
+                "{} on the {} tier returned {} where the interpreter returned {}",
+                workload, tier, actual, expected
+            ),
+            BenchError::Trace(error) => write!(f, "Failed to write trace: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for BenchError {}
+
+/// Runs `workload` under `tier` `runs` times, each on a freshly loaded VM;
+/// only running it is timed
+pub fn measure(workload: &Workload, tier: Tier, runs: u32) -> Result<Measurement, BenchError> {
+    time(workload, tier, runs, None)
+}
+
+/// Like [`measure`], but traces every instruction of every run to `trace`.
+/// The timings include writing the trace.
+pub fn measure_traced(
+    workload: &Workload,
+    tier: Tier,
+    runs: u32,
+    trace: &mut dyn Write,
+) -> Result<Measurement, BenchError> {
+    time(workload, tier, runs, Some(trace))
+}
+
+fn time(
+    workload: &Workload,
+    tier: Tier,
+    runs: u32,
+    mut trace: Option<&mut dyn Write>,
+) -> Result<Measurement, BenchError> {
+    let failed = |error| BenchError::Execution { workload: workload.name, tier, error };
+    let runs = runs.max(1);
+    let mut total = Duration::ZERO;
+    let mut fastest = Duration::MAX;
+    let mut result = String::new();
+    let mut instructions = 0;
+    for run in 1..=runs {
+        let mut vm = tier.load(workload.module.clone()).map_err(failed)?;
+        let start = Instant::now();
+        match trace.as_deref_mut() {
+            Some(output) => {
+                writeln!(output, "{} on the {} tier, run {}", workload.name, tier, run)
+                    .map_err(BenchError::Trace)?;
+                let mut tracer = TraceWriter::new(output);
+                tracer.write_header().map_err(BenchError::Trace)?;
+                tracer.run(&mut vm, u64::MAX).map_err(|error| match error {
+                    TraceError::Io(error) => BenchError::Trace(error),
+                    TraceError::Execution(error) => failed(error),
+                })?;
+            }
+            None => {
+                vm.run().map_err(failed)?;
+            }
+        }
+        let elapsed = start.elapsed();
+        total += elapsed;
+        fastest = fastest.min(elapsed);
+        result = vm.stack_top().map(ToString::to_string).unwrap_or_default();
+        instructions = vm.instruction_count();
+    }
+    Ok(Measurement {
+        workload: workload.name,
+        tier,
+        result,
+        instructions,
+        runs,
+        fastest,
+        mean: total / runs,
+    })
+}
+
+/// Measures every workload under every tier, checking that the tiers
+/// agree on each result
+pub fn run_suite(workloads: &[Workload], runs: u32) -> Result<BenchReport, BenchError> {
+    suite(workloads, runs, None)
+}
+
+/// Like [`run_suite`], but traces every instruction of every run to
+/// `trace`. The timings include writing the trace.
+pub fn run_suite_traced(
+    workloads: &[Workload],
+    runs: u32,
+    trace: &mut dyn Write,
+) -> Result<BenchReport, BenchError> {
+    suite(workloads, runs, Some(trace))
+}
+
+fn suite(
+    workloads: &[Workload],
+    runs: u32,
+    mut trace: Option<&mut dyn Write>,
+) -> Result<BenchReport, BenchError> {
+    let mut measurements = Vec::new();
+    for workload in workloads {
+        let mut expected: Option<String> = None;
+        for tier in Tier::ALL {
+            let measurement = time(workload, tier, runs, trace.as_mut().map(|trace| &mut **trace as &mut dyn Write))?;
+            match &expected {
+                None => expected = Some(measurement.result.clone()),
+                Some(expected) if *expected != measurement.result => {
+                    return Err(BenchError::Mismatch {
+                        workload: workload.name,
+                        tier,
+                        expected: expected.clone(),
+                        actual: measurement.result,
+                    });
+                }
+                Some(_) => {}
+            }
+            measurements.push(measurement);
+        }
+    }
+    Ok(BenchReport { measurements })
+}
+
+/// Measurements from [`run_suite`], in workload then tier order
+#[derive(Debug, Clone)]
+pub struct BenchReport {
+    pub measurements: Vec<Measurement>,
+}
+
+impl BenchReport {
+    /// How many times faster `tier` ran `workload` than the interpreter
+    pub fn speedup(&self, workload: &str, tier: Tier) -> Option<f64> {
+        let mean = |tier| {
+            self.measurements
+                .iter()
+                .find(|measurement| measurement.workload == workload && measurement.tier == tier)
+                .map(|measurement| measurement.mean.as_secs_f64())
+        };
+        let baseline = mean(Tier::Interpreter)?;
+        let time = mean(tier)?;
+        (time > 0.0).then(|| baseline / time)
+    }
+}
+
+/// A row per workload and tier: mean and fastest time, instructions per
+/// second and speed relative to the interpreter
+impl fmt::Display for BenchReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:<16} {:>12} {:>12} {:>14} {:>8}",
+            "Workload", "Tier", "Mean", "Fastest", "Instr/s", "Speedup"
+        )?;
+        for measurement in &self.measurements {
+            let speedup = self
+                .speedup(measurement.workload, measurement.tier)
+                .map_or_else(|| "-".to_string(), |speedup| format!("{:.2}x", speedup));
+            writeln!(
+                f,
+                "{:<10} {:<16} {:>12} {:>12} {:>14.0} {:>8}",
+                measurement.workload,
+                measurement.tier.to_string(),
+                format!("{:.3?}", measurement.mean),
+                format!("{:.3?}", measurement.fastest),
+                measurement.instructions_per_second(),
+                speedup
+            )?;
+        }
+        Ok(())
+    }
+}
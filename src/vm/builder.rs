@@ -0,0 +1,258 @@
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::module::{BytecodeModule, FunctionInfo, SymbolTable};
+use crate::vm::types::Value;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuilderError {
+    UnboundLabel(String),
+    LabelAlreadyBound(String),
+    UnknownLabel(usize),
+    NestedFunction(String),
+    UnclosedFunction(String),
+    EndFunctionWithoutFunction,
+}
+
+impl fmt::Display for BuilderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuilderError::UnboundLabel(name) => write!(f, "Label {} is used but never bound", name),
+            BuilderError::LabelAlreadyBound(name) => write!(f, "Label {} is already bound", name),
+            BuilderError::UnknownLabel(id) => {
+                write!(f, "Label #{} does not belong to this builder", id)
+            }
+            BuilderError::NestedFunction(name) => {
+                write!(f, "Function {} starts inside another function", name)
+            }
+            BuilderError::UnclosedFunction(name) => write!(f, "Function {} is never ended", name),
+            BuilderError::EndFunctionWithoutFunction => {
+                write!(f, "end_function called outside of a function")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BuilderError {}
+
+/// A jump or call target handed out by [`ProgramBuilder::label`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+struct LabelSlot {
+    name: String,
+    address: Option<usize>,
+}
+
+/// Builds programs from Rust code, resolving label targets and constant
+/// indices when [`ProgramBuilder::build`] is called
+pub struct ProgramBuilder {
+    instructions: Vec<Instruction>,
+    constants: Vec<Value>,
+    labels: Vec<LabelSlot>,
+    // Instructions whose operand is the address of a label
+    fixups: Vec<(usize, Label)>,
+    // Integer pushes that must be moved into the pool if it is not empty
+    integer_pushes: Vec<(usize, i64)>,
+    functions: Vec<FunctionInfo>,
+    symbols: SymbolTable,
+    open_function: Option<usize>,
+    errors: Vec<BuilderError>,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self {
+            instructions: Vec::new(),
+            constants: Vec::new(),
+            labels: Vec::new(),
+            fixups: Vec::new(),
+            integer_pushes: Vec::new(),
+            functions: Vec::new(),
+            symbols: SymbolTable::default(),
+            open_function: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Address the next emitted instruction will have
+    pub fn position(&self) -> usize {
+        self.instructions.len()
+    }
+
+    /// Creates a label that can be jumped to before or after it is bound
+    pub fn label(&mut self, name: impl Into<String>) -> Label {
+        self.labels.push(LabelSlot {
+            name: name.into(),
+            address: None,
+        });
+        Label(self.labels.len() - 1)
+    }
+
+    /// Marks the current position as the target of `label`
+    pub fn bind(&mut self, label: Label) -> &mut Self {
+        let position = self.position();
+        match self.labels.get_mut(label.0) {
+            Some(slot) if slot.address.is_some() => {
+                let error = BuilderError::LabelAlreadyBound(slot.name.clone());
+                self.errors.push(error);
+            }
+            Some(slot) => slot.address = Some(position),
+            None => self.errors.push(BuilderError::UnknownLabel(label.0)),
+        }
+        self
+    }
+
+    /// Creates a label bound to the current position
+    pub fn here(&mut self, name: impl Into<String>) -> Label {
+        let label = self.label(name);
+        self.bind(label);
+        label
+    }
+
+    pub fn emit(&mut self, opcode: Opcode) -> &mut Self {
+        self.instructions.push(Instruction::new(opcode, None));
+        self
+    }
+
+    pub fn emit_with(&mut self, opcode: Opcode, operand: Value) -> &mut Self {
+        self.instructions.push(Instruction::new(opcode, Some(operand)));
+        self
+    }
+
+    /// Pushes `value` as a literal; integers are moved into the constants
+    /// pool on build if the program has any other constants
+    pub fn push(&mut self, value: Value) -> &mut Self {
+        if let Value::Integer(literal) = value {
+            self.integer_pushes.push((self.position(), literal));
+        }
+        self.emit_with(Opcode::Push, value)
+    }
+
+    /// Index of `value` in the constants pool, adding it if needed
+    pub fn constant(&mut self, value: Value) -> usize {
+        if let Some(index) = self.constants.iter().position(|constant| *constant == value) {
+            return index;
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// Adds `value` to the pool under `name`, as `.const` does
+    pub fn named_constant(&mut self, name: impl Into<String>, value: Value) -> usize {
+        let index = self.constant(value);
+        self.symbols.constants.insert(name.into(), index);
+        index
+    }
+
+    /// Pushes `value` from the constants pool; arrays are copied on load
+    pub fn push_constant(&mut self, value: Value) -> &mut Self {
+        let index = self.constant(value);
+        self.emit_with(Opcode::LoadConstant, Value::Integer(index as i64))
+    }
+
+    pub fn jump_to(&mut self, label: Label) -> &mut Self {
+        self.emit_to(Opcode::Jump, label)
+    }
+
+    pub fn jump_if_true(&mut self, label: Label) -> &mut Self {
+        self.emit_to(Opcode::JumpIfTrue, label)
+    }
+
+    pub fn jump_if_false(&mut self, label: Label) -> &mut Self {
+        self.emit_to(Opcode::JumpIfFalse, label)
+    }
+
+    pub fn call(&mut self, label: Label) -> &mut Self {
+        self.emit_to(Opcode::Call, label)
+    }
+
+    fn emit_to(&mut self, opcode: Opcode, label: Label) -> &mut Self {
+        self.fixups.push((self.position(), label));
+        self.emit_with(opcode, Value::Integer(0))
+    }
+
+    /// Binds `label` as the entry of a function whose calls get a frame of
+    /// `locals` slots, the first `arity` holding the arguments
+    pub fn function(&mut self, label: Label, arity: usize, locals: usize) -> &mut Self {
+        let name = self.label_name(label);
+        if self.open_function.is_some() {
+            self.errors.push(BuilderError::NestedFunction(name.clone()));
+        }
+        self.bind(label);
+        self.functions
+            .push(FunctionInfo::new(name, self.position(), arity, locals.max(arity)));
+        self.open_function = Some(self.functions.len() - 1);
+        self
+    }
+
+    /// Ends the function started by the last [`ProgramBuilder::function`]
+    pub fn end_function(&mut self) -> &mut Self {
+        match self.open_function.take() {
+            Some(index) => {
+                let function = &self.functions[index];
+                self.symbols
+                    .functions
+                    .insert(function.name.clone(), function.address..self.position());
+            }
+            None => self.errors.push(BuilderError::EndFunctionWithoutFunction),
+        }
+        self
+    }
+
+    fn label_name(&self, label: Label) -> String {
+        self.labels
+            .get(label.0)
+            .map_or_else(|| format!("#{}", label.0), |slot| slot.name.clone())
+    }
+
+    /// Resolves label addresses and constant indices into a module
+    pub fn build(mut self) -> Result<BytecodeModule, BuilderError> {
+        if let Some(error) = self.errors.first() {
+            return Err(error.clone());
+        }
+        if let Some(index) = self.open_function {
+            return Err(BuilderError::UnclosedFunction(self.functions[index].name.clone()));
+        }
+
+        for &(position, label) in &self.fixups {
+            let slot = self
+                .labels
+                .get(label.0)
+                .ok_or(BuilderError::UnknownLabel(label.0))?;
+            let address = slot
+                .address
+                .ok_or_else(|| BuilderError::UnboundLabel(slot.name.clone()))?;
+            let opcode = self.instructions[position].opcode();
+            self.instructions[position] =
+                Instruction::new(opcode, Some(Value::Integer(address as i64)));
+        }
+
+        // With a non-empty pool, PUSH n loads constant n rather than the
+        // literal n, so integer literals have to be pooled as well
+        if !self.constants.is_empty() {
+            for (position, literal) in std::mem::take(&mut self.integer_pushes) {
+                let index = self.constant(Value::Integer(literal));
+                self.instructions[position] =
+                    Instruction::new(Opcode::Push, Some(Value::Integer(index as i64)));
+            }
+        }
+
+        let function_names: Vec<&str> = self.functions.iter().map(|f| f.name.as_str()).collect();
+        self.symbols.labels = self
+            .labels
+            .iter()
+            .filter(|slot| !function_names.contains(&slot.name.as_str()))
+            .filter_map(|slot| Some((slot.name.clone(), slot.address?)))
+            .collect();
+
+        Ok(BytecodeModule::new(self.instructions, self.constants)
+            .with_functions(self.functions)
+            .with_symbols(self.symbols))
+    }
+}
+
+impl Default for ProgramBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,5 +1,6 @@
 use crate::vm::types::Value;
 use std::fmt;
+use std::sync::Arc;
 
 #[derive(Debug)]
 pub enum CallFrameError {
@@ -33,7 +34,8 @@ pub struct CallFrame {
     stack_base: usize,
     program_counter: usize,
     locals: Vec<Value>,
-    function_name: Option<String>,
+    // Shared with the dispatcher's function table, so calls do not copy it
+    function_name: Option<Arc<str>>,
 }
 
 impl CallFrame {
@@ -114,11 +116,12 @@ impl CallFrame {
         self.function_name.as_deref()
     }
 
-    pub fn set_function_name(&mut self, name: String) {
-        self.function_name = Some(name);
+    pub fn set_function_name(&mut self, name: impl Into<Arc<str>>) {
+        self.function_name = Some(name.into());
     }
 }
 
+#[derive(Clone)]
 pub struct CallStack {
     frames: Vec<CallFrame>,
     max_depth: usize,
@@ -170,6 +173,11 @@ impl CallStack {
         self.frames.len()
     }
 
+    /// Live frames, outermost first
+    pub fn frames(&self) -> &[CallFrame] {
+        &self.frames
+    }
+
     pub fn is_empty(&self) -> bool {
         self.frames.is_empty()
     }
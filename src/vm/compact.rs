@@ -0,0 +1,151 @@
+//! Dense byte-stream form of a program.
+//!
+//! Each instruction is its opcode byte, followed for indexed opcodes by a
+//! one-byte operand, or by the whole instruction behind a `Wide` prefix
+//! with a four-byte operand (see [`Instruction::encode`]). An offset table
+//! maps program counters to byte offsets so instructions can be fetched in
+//! place without decoding the whole stream.
+
+use crate::vm::instruction::{EncodingError, Instruction, Opcode};
+use crate::vm::types::Value;
+
+/// A program held as encoded bytes rather than [`Instruction`] values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactCode {
+    bytes: Vec<u8>,
+    /// Byte offset of each instruction
+    offsets: Vec<u32>,
+}
+
+impl CompactCode {
+    /// Encodes `instructions`, whose operands must all be indices; use
+    /// [`pool_literals`] first for programs that push literal values
+    pub fn encode(instructions: &[Instruction]) -> Result<Self, EncodingError> {
+        let mut bytes = Vec::with_capacity(instructions.len() * 2);
+        let mut offsets = Vec::with_capacity(instructions.len());
+        for instruction in instructions {
+            offsets.push(Self::offset(bytes.len())?);
+            instruction.encode(&mut bytes)?;
+        }
+        Ok(Self { bytes, offsets })
+    }
+
+    /// Takes ownership of an encoded stream, checking that every
+    /// instruction in it decodes
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, EncodingError> {
+        let mut offsets = Vec::new();
+        let mut offset = 0;
+        while offset < bytes.len() {
+            offsets.push(Self::offset(offset)?);
+            let (_, consumed) = Instruction::decode(&bytes[offset..]).map_err(|e| match e {
+                EncodingError::UnexpectedEnd(at) => EncodingError::UnexpectedEnd(offset + at),
+                EncodingError::MisplacedWidePrefix(at) => {
+                    EncodingError::MisplacedWidePrefix(offset + at)
+                }
+                other => other,
+            })?;
+            offset += consumed;
+        }
+        Ok(Self { bytes, offsets })
+    }
+
+    fn offset(position: usize) -> Result<u32, EncodingError> {
+        u32::try_from(position).map_err(|_| {
+            EncodingError::InvalidOperand("Program is too large to encode".to_string())
+        })
+    }
+
+    /// Number of instructions
+    pub fn len(&self) -> usize {
+        self.offsets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.offsets.is_empty()
+    }
+
+    /// Size of the encoded stream in bytes
+    pub fn byte_len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Opcode of the instruction at `pc`, without decoding its operand
+    pub fn opcode_at(&self, pc: usize) -> Option<Opcode> {
+        let byte = self.bytes[*self.offsets.get(pc)? as usize];
+        match Opcode::from_u8(byte)? {
+            Opcode::Wide => Opcode::from_u8(self.bytes[self.offsets[pc] as usize + 1]),
+            opcode => Some(opcode),
+        }
+    }
+
+    /// Decodes the instruction at `pc`
+    pub fn fetch(&self, pc: usize) -> Option<Instruction> {
+        let offset = *self.offsets.get(pc)? as usize;
+        // Every offset was checked to decode when the code was built
+        Instruction::decode(&self.bytes[offset..])
+            .ok()
+            .map(|(instruction, _)| instruction)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Instruction> + '_ {
+        (0..self.len()).filter_map(|pc| self.fetch(pc))
+    }
+
+    /// Decodes the whole program
+    pub fn to_instructions(&self) -> Vec<Instruction> {
+        self.iter().collect()
+    }
+}
+
+/// Instructions and constants equivalent to these, with every literal
+/// `Push` operand moved into the constant pool so the program can be
+/// encoded.
+///
+/// A `Push` operand is a literal unless it is an integer indexing a
+/// non-empty pool; once any literal is pooled, integer literals have to
+/// be pooled too.
+pub fn pool_literals(
+    instructions: &[Instruction],
+    constants: &[Value],
+) -> (Vec<Instruction>, Vec<Value>) {
+    let encodable = |operand: Option<&Value>| {
+        matches!(operand, Some(Value::Integer(index)) if u32::try_from(*index).is_ok())
+    };
+    let is_literal = |instruction: &Instruction| {
+        instruction.opcode() == Opcode::Push
+            && (constants.is_empty() || !matches!(instruction.operand(), Some(Value::Integer(_))))
+    };
+    let needs_pool = instructions.iter().any(|instruction| {
+        instruction.opcode() == Opcode::Push && !encodable(instruction.operand())
+    });
+    if !needs_pool {
+        return (instructions.to_vec(), constants.to_vec());
+    }
+
+    let mut pooled = constants.to_vec();
+    let instructions = instructions
+        .iter()
+        .map(|instruction| match instruction.operand() {
+            Some(literal) if is_literal(instruction) => {
+                let index = pooled
+                    .iter()
+                    .position(|constant| constant == literal)
+                    .unwrap_or_else(|| {
+                        pooled.push(literal.clone());
+                        pooled.len() - 1
+                    });
+                Instruction::new(Opcode::Push, Some(Value::Integer(index as i64)))
+            }
+            _ => instruction.clone(),
+        })
+        .collect();
+    (instructions, pooled)
+}
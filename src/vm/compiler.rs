@@ -0,0 +1,1996 @@
+use crate::vm::assembler::{escape, mnemonic, Assembler, AssemblerError, SourcePos};
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::module::{BytecodeModule, DebugInfo, LineTable};
+use crate::vm::optimizer::evaluate;
+use crate::vm::types::Value;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// Source range of a token or syntax node; `end` is the position just past
+/// its last character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: SourcePos,
+    pub end: SourcePos,
+}
+
+impl Span {
+    pub fn new(start: SourcePos, end: SourcePos) -> Self {
+        Self { start, end }
+    }
+
+    /// Span from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span::new(self.start, other.end)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Identifier(String),
+    True,
+    False,
+    Let,
+    While,
+    For,
+    Break,
+    Continue,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    EqualEqual,
+    BangEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    AndAnd,
+    OrOr,
+    Bang,
+    Equal,
+    Semicolon,
+    LeftParen,
+    RightParen,
+    LeftBrace,
+    RightBrace,
+    LeftBracket,
+    RightBracket,
+    Comma,
+}
+
+impl TokenKind {
+    fn keyword(name: &str) -> Option<TokenKind> {
+        let keyword = match name {
+            "true" => TokenKind::True,
+            "false" => TokenKind::False,
+            "let" => TokenKind::Let,
+            "while" => TokenKind::While,
+            "for" => TokenKind::For,
+            "break" => TokenKind::Break,
+            "continue" => TokenKind::Continue,
+            _ => return None,
+        };
+        Some(keyword)
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            TokenKind::Integer(value) => return write!(f, "{}", value),
+            TokenKind::Float(value) => return write!(f, "{:?}", value),
+            TokenKind::String(text) => return write!(f, "\"{}\"", escape(text, '"')),
+            TokenKind::Identifier(name) => name,
+            TokenKind::True => "true",
+            TokenKind::False => "false",
+            TokenKind::Let => "let",
+            TokenKind::While => "while",
+            TokenKind::For => "for",
+            TokenKind::Break => "break",
+            TokenKind::Continue => "continue",
+            TokenKind::Plus => "+",
+            TokenKind::Minus => "-",
+            TokenKind::Star => "*",
+            TokenKind::Slash => "/",
+            TokenKind::Percent => "%",
+            TokenKind::EqualEqual => "==",
+            TokenKind::BangEqual => "!=",
+            TokenKind::Less => "<",
+            TokenKind::LessEqual => "<=",
+            TokenKind::Greater => ">",
+            TokenKind::GreaterEqual => ">=",
+            TokenKind::AndAnd => "&&",
+            TokenKind::OrOr => "||",
+            TokenKind::Bang => "!",
+            TokenKind::Equal => "=",
+            TokenKind::Semicolon => ";",
+            TokenKind::LeftParen => "(",
+            TokenKind::RightParen => ")",
+            TokenKind::LeftBrace => "{",
+            TokenKind::RightBrace => "}",
+            TokenKind::LeftBracket => "[",
+            TokenKind::RightBracket => "]",
+            TokenKind::Comma => ",",
+        };
+        f.write_str(text)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
+}
+
+/// Splits source into tokens, skipping whitespace and `//` comments
+pub struct Lexer<'a> {
+    source: &'a str,
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            chars: source.chars().collect(),
+            index: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+
+    pub fn tokenize(mut self) -> Result<Vec<Token>, AssemblerError> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
+    }
+
+    fn next_token(&mut self) -> Result<Option<Token>, AssemblerError> {
+        self.skip_trivia();
+        let start = self.pos();
+        let Some(ch) = self.bump() else {
+            return Ok(None);
+        };
+
+        let kind = match ch {
+            '0'..='9' => self.number(ch, start)?,
+            '"' => self.string(start)?,
+            c if c.is_alphabetic() || c == '_' => {
+                let mut name = c.to_string();
+                while let Some(c) = self.peek(0).filter(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                    self.bump();
+                }
+                TokenKind::keyword(&name).unwrap_or(TokenKind::Identifier(name))
+            }
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' => TokenKind::Slash,
+            '%' => TokenKind::Percent,
+            ';' => TokenKind::Semicolon,
+            '(' => TokenKind::LeftParen,
+            ')' => TokenKind::RightParen,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
+            '[' => TokenKind::LeftBracket,
+            ']' => TokenKind::RightBracket,
+            ',' => TokenKind::Comma,
+            '=' => self.pair('=', TokenKind::EqualEqual, TokenKind::Equal),
+            '!' => self.pair('=', TokenKind::BangEqual, TokenKind::Bang),
+            '<' => self.pair('=', TokenKind::LessEqual, TokenKind::Less),
+            '>' => self.pair('=', TokenKind::GreaterEqual, TokenKind::Greater),
+            '&' if self.peek(0) == Some('&') => {
+                self.bump();
+                TokenKind::AndAnd
+            }
+            '|' if self.peek(0) == Some('|') => {
+                self.bump();
+                TokenKind::OrOr
+            }
+            other => {
+                return Err(syntax_error(self.source, format!("Unexpected character `{}`", other), start));
+            }
+        };
+
+        Ok(Some(Token {
+            kind,
+            span: Span::new(start, self.pos()),
+        }))
+    }
+
+    /// `double` if the next character is `second`, consuming it; otherwise
+    /// `single`
+    fn pair(&mut self, second: char, double: TokenKind, single: TokenKind) -> TokenKind {
+        if self.peek(0) == Some(second) {
+            self.bump();
+            double
+        } else {
+            single
+        }
+    }
+
+    // DIGITS [. DIGITS] [e [+-] DIGITS], with `_` allowed between digits
+    fn number(&mut self, first: char, start: SourcePos) -> Result<TokenKind, AssemblerError> {
+        let mut text = first.to_string();
+        let mut is_float = false;
+        self.digits(&mut text);
+
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            is_float = true;
+            text.push('.');
+            self.bump();
+            self.digits(&mut text);
+        }
+        if matches!(self.peek(0), Some('e' | 'E')) {
+            let sign = usize::from(matches!(self.peek(1), Some('+' | '-')));
+            if self.peek(1 + sign).is_some_and(|c| c.is_ascii_digit()) {
+                is_float = true;
+                for _ in 0..=sign {
+                    text.extend(self.bump());
+                }
+                self.digits(&mut text);
+            }
+        }
+
+        if is_float {
+            let value = text.parse().expect("digits with a fraction or exponent parse as a float");
+            return Ok(TokenKind::Float(value));
+        }
+        text.parse().map(TokenKind::Integer).map_err(|_| {
+            syntax_error(self.source, format!("Integer literal out of range: {}", text), start)
+        })
+    }
+
+    // "TEXT" with escapes \n \t \r \0 \\ \", on a single line
+    fn string(&mut self, start: SourcePos) -> Result<TokenKind, AssemblerError> {
+        let mut text = String::new();
+        loop {
+            let escape_pos = self.pos();
+            match self.bump() {
+                Some('"') => return Ok(TokenKind::String(text)),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('0') => '\0',
+                        Some(c @ ('\\' | '"')) => c,
+                        Some('\n') | None => break,
+                        Some(other) => {
+                            let message = format!("Unknown escape sequence `\\{}`", other);
+                            return Err(syntax_error(self.source, message, escape_pos));
+                        }
+                    };
+                    text.push(escaped);
+                }
+                Some('\n') | None => break,
+                Some(c) => text.push(c),
+            }
+        }
+        Err(syntax_error(self.source, "Unterminated string literal".to_string(), start))
+    }
+
+    fn digits(&mut self, text: &mut String) {
+        while let Some(c) = self.peek(0) {
+            if c.is_ascii_digit() {
+                text.push(c);
+            } else if c != '_' || !self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn skip_trivia(&mut self) {
+        while let Some(c) = self.peek(0) {
+            if c == '/' && self.peek(1) == Some('/') {
+                while self.peek(0).is_some_and(|c| c != '\n') {
+                    self.bump();
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn peek(&self, ahead: usize) -> Option<char> {
+        self.chars.get(self.index + ahead).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek(0)?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn pos(&self) -> SourcePos {
+        SourcePos::new(self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Negate,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    fn opcode(self) -> Opcode {
+        match self {
+            BinaryOp::Add => Opcode::Add,
+            BinaryOp::Sub => Opcode::Sub,
+            BinaryOp::Mul => Opcode::Mul,
+            BinaryOp::Div => Opcode::Div,
+            BinaryOp::Mod => Opcode::Mod,
+            BinaryOp::Equal => Opcode::Equal,
+            BinaryOp::NotEqual => Opcode::NotEqual,
+            BinaryOp::Less => Opcode::LessThan,
+            BinaryOp::LessEqual => Opcode::LessEqual,
+            BinaryOp::Greater => Opcode::GreaterThan,
+            BinaryOp::GreaterEqual => Opcode::GreaterEqual,
+            BinaryOp::And => Opcode::And,
+            BinaryOp::Or => Opcode::Or,
+        }
+    }
+}
+
+impl UnaryOp {
+    fn opcode(self) -> Opcode {
+        match self {
+            UnaryOp::Negate => Opcode::Negate,
+            UnaryOp::Not => Opcode::Not,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprKind {
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Variable(String),
+    /// `[a, b, ...]`
+    Array(Vec<Expr>),
+    /// `array[index]`
+    Index(Box<Expr>, Box<Expr>),
+    /// `name(args, ...)`, calling one of [`BUILTINS`]
+    Call { name: String, args: Vec<Expr> },
+    Unary(UnaryOp, Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum StmtKind {
+    Let { name: String, value: Expr },
+    Assign { name: String, value: Expr },
+    /// `array[index] = value`
+    IndexAssign { array: Expr, index: Expr, value: Expr },
+    Expr(Expr),
+    Block(Vec<Stmt>),
+    While { condition: Expr, body: Vec<Stmt> },
+    /// `for (INIT; CONDITION; STEP) { BODY }`, each header part optional
+    For {
+        init: Option<Box<Stmt>>,
+        condition: Option<Expr>,
+        step: Option<Box<Stmt>>,
+        body: Vec<Stmt>,
+    },
+    Break,
+    Continue,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub span: Span,
+}
+
+impl Expr {
+    /// The value of a literal expression
+    pub fn constant(&self) -> Option<Value> {
+        match &self.kind {
+            ExprKind::Integer(value) => Some(Value::Integer(*value)),
+            ExprKind::Float(value) => Some(Value::Float(*value)),
+            ExprKind::Boolean(value) => Some(Value::Boolean(*value)),
+            ExprKind::String(text) => Some(Value::String(text.as_str().into())),
+            _ => None,
+        }
+    }
+
+    /// Replaces operators applied to constants with their result, e.g.
+    /// `2 * 3 + 4` with `10`. Operations that would fail at run time, such
+    /// as division by zero, are left for the VM to report
+    pub fn fold(self) -> Expr {
+        let span = self.span;
+        let kind = match self.kind {
+            ExprKind::Array(elements) => {
+                ExprKind::Array(elements.into_iter().map(Expr::fold).collect())
+            }
+            ExprKind::Index(array, index) => {
+                ExprKind::Index(Box::new(array.fold()), Box::new(index.fold()))
+            }
+            ExprKind::Call { name, args } => ExprKind::Call {
+                name,
+                args: args.into_iter().map(Expr::fold).collect(),
+            },
+            ExprKind::Unary(op, operand) => {
+                let operand = operand.fold();
+                let folded = operand
+                    .constant()
+                    .and_then(|value| evaluate(op.opcode(), vec![value]));
+                match folded.and_then(|value| Self::literal(value, span)) {
+                    Some(literal) => return literal,
+                    None => ExprKind::Unary(op, Box::new(operand)),
+                }
+            }
+            ExprKind::Binary(op, left, right) => {
+                let (left, right) = (left.fold(), right.fold());
+                let folded = left.constant().zip(right.constant()).and_then(|(a, b)| {
+                    // `+` on a string concatenates, as in code generation
+                    let opcode = match (op, &a, &b) {
+                        (BinaryOp::Add, Value::String(_), _) | (BinaryOp::Add, _, Value::String(_)) => {
+                            Opcode::StrConcat
+                        }
+                        _ => op.opcode(),
+                    };
+                    evaluate(opcode, vec![a, b])
+                });
+                match folded.and_then(|value| Self::literal(value, span)) {
+                    Some(literal) => return literal,
+                    None => ExprKind::Binary(op, Box::new(left), Box::new(right)),
+                }
+            }
+            kind => kind,
+        };
+        Expr { kind, span }
+    }
+
+    /// `value` as a literal expression, if the language can spell it
+    fn literal(value: Value, span: Span) -> Option<Expr> {
+        let kind = match value {
+            Value::Integer(value) => ExprKind::Integer(value),
+            Value::Float(value) if value.is_finite() => ExprKind::Float(value),
+            Value::Boolean(value) => ExprKind::Boolean(value),
+            Value::String(text) => ExprKind::String(text.as_str().to_string()),
+            _ => return None,
+        };
+        Some(Expr { kind, span })
+    }
+}
+
+impl Stmt {
+    /// Folds the constant expressions in this statement. A loop whose
+    /// condition is constant either loses its condition test or, if the
+    /// condition is false, is dropped with only a `for` loop's
+    /// initializer kept
+    pub fn fold(self) -> Stmt {
+        let span = self.span;
+        let fold_body = |body: Vec<Stmt>| body.into_iter().map(Stmt::fold).collect::<Vec<_>>();
+        let kind = match self.kind {
+            StmtKind::Let { name, value } => StmtKind::Let { name, value: value.fold() },
+            StmtKind::Assign { name, value } => StmtKind::Assign { name, value: value.fold() },
+            StmtKind::IndexAssign { array, index, value } => StmtKind::IndexAssign {
+                array: array.fold(),
+                index: index.fold(),
+                value: value.fold(),
+            },
+            StmtKind::Expr(expr) => StmtKind::Expr(expr.fold()),
+            StmtKind::Block(body) => StmtKind::Block(fold_body(body)),
+            StmtKind::While { condition, body } => {
+                let condition = condition.fold();
+                match condition.constant().map(|value| value.is_truthy()) {
+                    // Loops until `break`, the same as `for (;;)`
+                    Some(true) => StmtKind::For {
+                        init: None,
+                        condition: None,
+                        step: None,
+                        body: fold_body(body),
+                    },
+                    Some(false) => StmtKind::Block(Vec::new()),
+                    None => StmtKind::While { condition, body: fold_body(body) },
+                }
+            }
+            StmtKind::For { init, condition, step, body } => {
+                let init = init.map(|init| Box::new(init.fold()));
+                let condition = condition.map(Expr::fold);
+                match condition.as_ref().and_then(Expr::constant).map(|value| value.is_truthy()) {
+                    Some(false) => StmtKind::Block(init.map(|init| *init).into_iter().collect()),
+                    truthy => StmtKind::For {
+                        init,
+                        condition: if truthy.is_some() { None } else { condition },
+                        step: step.map(|step| Box::new(step.fold())),
+                        body: fold_body(body),
+                    },
+                }
+            }
+            kind @ (StmtKind::Break | StmtKind::Continue) => kind,
+        };
+        Stmt { kind, span }
+    }
+}
+
+/// Recursive-descent parser over the tokens of one source text
+pub struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<Token>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(source: &'a str) -> Result<Self, AssemblerError> {
+        Ok(Self {
+            source,
+            tokens: Lexer::new(source).tokenize()?,
+            next: 0,
+        })
+    }
+
+    /// Statements separated by `;`, up to the end of the source
+    pub fn parse_program(&mut self) -> Result<Vec<Stmt>, AssemblerError> {
+        let mut statements = Vec::new();
+        while let Some(token) = self.peek() {
+            if token.kind == TokenKind::RightBrace {
+                return Err(self.error("Unexpected `}`".to_string(), token.span.start));
+            }
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    /// A single expression spanning the whole source
+    pub fn parse_expression(&mut self) -> Result<Expr, AssemblerError> {
+        let expr = self.expression()?;
+        match self.peek() {
+            Some(token) => Err(self.unexpected(token)),
+            None => Ok(expr),
+        }
+    }
+
+    fn statement(&mut self) -> Result<Stmt, AssemblerError> {
+        let token = self.peek().expect("callers check for the end of input").clone();
+        let statement = match token.kind {
+            TokenKind::LeftBrace => self.block_statement()?,
+            TokenKind::While => self.while_statement()?,
+            TokenKind::For => self.for_statement()?,
+            TokenKind::Semicolon => {
+                return Err(self.error("Empty statement".to_string(), token.span.start));
+            }
+            _ => {
+                let statement = self.simple_statement()?;
+                // A block's closing `}` also ends its last statement
+                match self.peek() {
+                    None => {}
+                    Some(token) if token.kind == TokenKind::RightBrace => {}
+                    Some(token) if token.kind == TokenKind::Semicolon => self.next += 1,
+                    Some(token) => return Err(self.unexpected(token)),
+                }
+                return Ok(statement);
+            }
+        };
+        self.eat(&TokenKind::Semicolon);
+        Ok(statement)
+    }
+
+    /// A statement without a block: `let`, assignment, `break`, `continue`
+    /// or an expression
+    fn simple_statement(&mut self) -> Result<Stmt, AssemblerError> {
+        let token = self.peek().expect("callers check for the end of input").clone();
+        match &token.kind {
+            TokenKind::Break | TokenKind::Continue => {
+                self.next += 1;
+                let kind = if token.kind == TokenKind::Break {
+                    StmtKind::Break
+                } else {
+                    StmtKind::Continue
+                };
+                Ok(Stmt { kind, span: token.span })
+            }
+            TokenKind::Let => {
+                self.next += 1;
+                let usage = || "Expected `let NAME = EXPRESSION`".to_string();
+                let name = match self.peek() {
+                    Some(Token { kind: TokenKind::Identifier(name), .. }) => name.clone(),
+                    Some(other) => {
+                        let message = format!("Invalid variable name: {}", other.kind);
+                        return Err(self.error(message, other.span.start));
+                    }
+                    None => return Err(self.error(usage(), token.span.start)),
+                };
+                self.next += 1;
+                if !self.eat(&TokenKind::Equal) {
+                    return Err(self.error(usage(), token.span.start));
+                }
+                let value = self.initializer()?;
+                let span = token.span.to(value.span);
+                Ok(Stmt { kind: StmtKind::Let { name, value }, span })
+            }
+            _ => {
+                let expr = self.expression()?;
+                if !self.eat(&TokenKind::Equal) {
+                    let span = expr.span;
+                    return Ok(Stmt { kind: StmtKind::Expr(expr), span });
+                }
+
+                let value = self.initializer()?;
+                let span = expr.span.to(value.span);
+                let kind = match expr.kind {
+                    ExprKind::Variable(name) => StmtKind::Assign { name, value },
+                    ExprKind::Index(array, index) => StmtKind::IndexAssign {
+                        array: *array,
+                        index: *index,
+                        value,
+                    },
+                    _ => {
+                        let message = "Only variables and array elements can be assigned to";
+                        return Err(self.error(message.to_string(), expr.span.start));
+                    }
+                };
+                Ok(Stmt { kind, span })
+            }
+        }
+    }
+
+    /// The expression after the `=` just consumed
+    fn initializer(&mut self) -> Result<Expr, AssemblerError> {
+        let ends_statement = self.peek().is_none_or(|token| {
+            matches!(token.kind, TokenKind::Semicolon | TokenKind::RightBrace)
+        });
+        if ends_statement {
+            let equals = self.tokens[self.next - 1].span.start;
+            return Err(self.error("Missing expression after `=`".to_string(), equals));
+        }
+        self.expression()
+    }
+
+    fn block_statement(&mut self) -> Result<Stmt, AssemblerError> {
+        let (body, span) = self.block()?;
+        Ok(Stmt { kind: StmtKind::Block(body), span })
+    }
+
+    /// `{ STATEMENTS }`
+    fn block(&mut self) -> Result<(Vec<Stmt>, Span), AssemblerError> {
+        let open = match self.peek() {
+            Some(token) if token.kind == TokenKind::LeftBrace => token.span,
+            Some(token) => {
+                return Err(self.error(format!("Expected `{{`, found `{}`", token.kind), token.span.start));
+            }
+            None => return Err(self.error("Expected `{`".to_string(), self.end_pos())),
+        };
+        self.next += 1;
+
+        let mut body = Vec::new();
+        loop {
+            match self.peek() {
+                Some(token) if token.kind == TokenKind::RightBrace => {
+                    let span = open.to(token.span);
+                    self.next += 1;
+                    return Ok((body, span));
+                }
+                Some(_) => body.push(self.statement()?),
+                None => return Err(self.error("Missing `}`".to_string(), open.start)),
+            }
+        }
+    }
+
+    // while CONDITION { BODY }
+    fn while_statement(&mut self) -> Result<Stmt, AssemblerError> {
+        let keyword = self.tokens[self.next].span;
+        self.next += 1;
+        if self.peek().is_none_or(|token| token.kind == TokenKind::LeftBrace) {
+            return Err(self.error("Missing loop condition".to_string(), keyword.start));
+        }
+        let condition = self.expression()?;
+        if self.peek().is_none_or(|token| token.kind != TokenKind::LeftBrace) {
+            return Err(self.error("Expected `while CONDITION { ... }`".to_string(), keyword.start));
+        }
+        let (body, body_span) = self.block()?;
+        Ok(Stmt {
+            kind: StmtKind::While { condition, body },
+            span: keyword.to(body_span),
+        })
+    }
+
+    // for (INIT; CONDITION; STEP) { BODY }
+    fn for_statement(&mut self) -> Result<Stmt, AssemblerError> {
+        let keyword = self.tokens[self.next].span;
+        self.next += 1;
+        let usage = "Expected `for (INIT; CONDITION; STEP) { ... }`";
+        let expect = |parser: &mut Self, kind: TokenKind| {
+            if parser.eat(&kind) {
+                Ok(())
+            } else {
+                Err(parser.error(usage.to_string(), keyword.start))
+            }
+        };
+
+        expect(self, TokenKind::LeftParen)?;
+        let init = if self.at(&TokenKind::Semicolon) {
+            None
+        } else {
+            Some(Box::new(self.simple_statement()?))
+        };
+        expect(self, TokenKind::Semicolon)?;
+        let condition = if self.at(&TokenKind::Semicolon) {
+            None
+        } else {
+            Some(self.expression()?)
+        };
+        expect(self, TokenKind::Semicolon)?;
+        let step = if self.at(&TokenKind::RightParen) {
+            None
+        } else {
+            Some(Box::new(self.simple_statement()?))
+        };
+        expect(self, TokenKind::RightParen)?;
+
+        let (body, body_span) = self.block()?;
+        Ok(Stmt {
+            kind: StmtKind::For { init, condition, step, body },
+            span: keyword.to(body_span),
+        })
+    }
+
+    fn expression(&mut self) -> Result<Expr, AssemblerError> {
+        self.binary(0)
+    }
+
+    /// Operators by precedence level, loosest first
+    const BINARY_LEVELS: &'static [&'static [(TokenKind, BinaryOp)]] = &[
+        &[(TokenKind::OrOr, BinaryOp::Or)],
+        &[(TokenKind::AndAnd, BinaryOp::And)],
+        &[
+            (TokenKind::EqualEqual, BinaryOp::Equal),
+            (TokenKind::BangEqual, BinaryOp::NotEqual),
+        ],
+        &[
+            (TokenKind::Less, BinaryOp::Less),
+            (TokenKind::LessEqual, BinaryOp::LessEqual),
+            (TokenKind::Greater, BinaryOp::Greater),
+            (TokenKind::GreaterEqual, BinaryOp::GreaterEqual),
+        ],
+        &[(TokenKind::Plus, BinaryOp::Add), (TokenKind::Minus, BinaryOp::Sub)],
+        &[
+            (TokenKind::Star, BinaryOp::Mul),
+            (TokenKind::Slash, BinaryOp::Div),
+            (TokenKind::Percent, BinaryOp::Mod),
+        ],
+    ];
+
+    /// Left-associative binary operators at `level` and tighter
+    fn binary(&mut self, level: usize) -> Result<Expr, AssemblerError> {
+        let Some(operators) = Self::BINARY_LEVELS.get(level) else {
+            return self.unary();
+        };
+
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self
+            .peek()
+            .and_then(|token| operators.iter().find(|(kind, _)| *kind == token.kind))
+            .map(|(_, op)| *op)
+        {
+            self.next += 1;
+            let right = self.binary(level + 1)?;
+            let span = left.span.to(right.span);
+            left = Expr {
+                kind: ExprKind::Binary(op, Box::new(left), Box::new(right)),
+                span,
+            };
+        }
+        Ok(left)
+    }
+
+    /// Prefix `-` and `!`, binding tighter than any binary operator and
+    /// nesting freely, as in `- -x` or `!!x`
+    fn unary(&mut self) -> Result<Expr, AssemblerError> {
+        let op = match self.peek().map(|token| &token.kind) {
+            Some(TokenKind::Minus) => UnaryOp::Negate,
+            Some(TokenKind::Bang) => UnaryOp::Not,
+            _ => return self.postfix(),
+        };
+        let span = self.tokens[self.next].span;
+        self.next += 1;
+        let operand = self.unary()?;
+        Ok(Expr {
+            span: span.to(operand.span),
+            kind: ExprKind::Unary(op, Box::new(operand)),
+        })
+    }
+
+    /// A primary expression followed by any number of `[index]`
+    fn postfix(&mut self) -> Result<Expr, AssemblerError> {
+        let mut expr = self.primary()?;
+        while self.eat(&TokenKind::LeftBracket) {
+            let index = self.expression()?;
+            let close = self.close(TokenKind::RightBracket)?;
+            let span = expr.span.to(close);
+            expr = Expr {
+                kind: ExprKind::Index(Box::new(expr), Box::new(index)),
+                span,
+            };
+        }
+        Ok(expr)
+    }
+
+    fn primary(&mut self) -> Result<Expr, AssemblerError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.error("Expected expression".to_string(), self.end_pos()));
+        };
+        let kind = match token.kind {
+            TokenKind::Integer(value) => ExprKind::Integer(value),
+            TokenKind::Float(value) => ExprKind::Float(value),
+            TokenKind::String(text) => ExprKind::String(text),
+            TokenKind::True => ExprKind::Boolean(true),
+            TokenKind::False => ExprKind::Boolean(false),
+            TokenKind::Identifier(name)
+                if self.tokens.get(self.next + 1).is_some_and(|t| t.kind == TokenKind::LeftParen) =>
+            {
+                self.next += 2;
+                let args = self.list(TokenKind::RightParen)?;
+                let close = self.close(TokenKind::RightParen)?;
+                return Ok(Expr { kind: ExprKind::Call { name, args }, span: token.span.to(close) });
+            }
+            TokenKind::Identifier(name) => ExprKind::Variable(name),
+            TokenKind::LeftParen => {
+                self.next += 1;
+                let inner = self.expression()?;
+                let close = self.close(TokenKind::RightParen)?;
+                return Ok(Expr { kind: inner.kind, span: token.span.to(close) });
+            }
+            // [ELEMENT, ...], with an optional trailing comma
+            TokenKind::LeftBracket => {
+                self.next += 1;
+                let elements = self.list(TokenKind::RightBracket)?;
+                let close = self.close(TokenKind::RightBracket)?;
+                return Ok(Expr { kind: ExprKind::Array(elements), span: token.span.to(close) });
+            }
+            _ => {
+                let message = format!("Expected expression, found `{}`", token.kind);
+                return Err(self.error(message, token.span.start));
+            }
+        };
+        self.next += 1;
+        Ok(Expr { kind, span: token.span })
+    }
+
+    /// Comma-separated expressions up to, but not including, `close`; a
+    /// trailing comma is allowed
+    fn list(&mut self, close: TokenKind) -> Result<Vec<Expr>, AssemblerError> {
+        let mut items = Vec::new();
+        while !self.at(&close) {
+            items.push(self.expression()?);
+            if !self.eat(&TokenKind::Comma) {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Consumes the closing `kind` of a bracketed construct, returning its
+    /// span
+    fn close(&mut self, kind: TokenKind) -> Result<Span, AssemblerError> {
+        match self.peek() {
+            Some(token) if token.kind == kind => {
+                let span = token.span;
+                self.next += 1;
+                Ok(span)
+            }
+            Some(other) => {
+                let message = format!("Expected `{}`, found `{}`", kind, other.kind);
+                Err(self.error(message, other.span.start))
+            }
+            None => Err(self.error(format!("Expected `{}`", kind), self.end_pos())),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn at(&self, kind: &TokenKind) -> bool {
+        self.peek().is_some_and(|token| token.kind == *kind)
+    }
+
+    /// Consumes the next token if it is `kind`
+    fn eat(&mut self, kind: &TokenKind) -> bool {
+        let found = self.at(kind);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    /// Where the source ends, for errors about missing input
+    fn end_pos(&self) -> SourcePos {
+        self.tokens.last().map_or(SourcePos::new(1, 1), |token| token.span.end)
+    }
+
+    fn unexpected(&self, token: &Token) -> AssemblerError {
+        self.error(format!("Unexpected `{}`", token.kind), token.span.start)
+    }
+
+    fn error(&self, message: String, pos: SourcePos) -> AssemblerError {
+        syntax_error(self.source, message, pos)
+    }
+}
+
+/// Functions callable from the language: name, arity and the opcode
+/// implementing them
+pub const BUILTINS: &[(&str, usize, Opcode)] = &[
+    ("len", 1, Opcode::ArrayLength),
+    ("char_at", 2, Opcode::CharAt),
+    ("substring", 3, Opcode::Substring),
+];
+
+fn builtin(name: &str) -> Option<(usize, Opcode)> {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _, _)| *builtin == name)
+        .map(|&(_, arity, opcode)| (arity, opcode))
+}
+
+/// A problem found in a program, and the source it concerns
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self {
+            message: message.into(),
+            span,
+        }
+    }
+
+    /// This diagnostic as the error compiling `source` reports
+    pub fn into_error(self, source: &str) -> AssemblerError {
+        syntax_error(source, self.message, self.span.start)
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span.start, self.message)
+    }
+}
+
+/// Checks a parsed program before code generation: every variable is
+/// declared before use, calls name a builtin with the right number of
+/// arguments, and `break`/`continue` appear inside loops. Checking carries
+/// on past a problem, so all of them are reported
+pub fn check_program(program: &[Stmt]) -> Vec<Diagnostic> {
+    let mut checker = Checker::new();
+    for statement in program {
+        checker.statement(statement);
+    }
+    checker.diagnostics
+}
+
+/// Checks a standalone expression, in which no variables are declared
+pub fn check_expression(expr: &Expr) -> Vec<Diagnostic> {
+    let mut checker = Checker::new();
+    checker.expr(expr);
+    checker.diagnostics
+}
+
+struct Checker {
+    scopes: Vec<HashSet<String>>,
+    loop_depth: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn new() -> Self {
+        Self {
+            scopes: vec![HashSet::new()],
+            loop_depth: 0,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt) {
+        match &statement.kind {
+            StmtKind::Let { name, value } => {
+                // The initializer cannot see the variable it defines
+                self.expr(value);
+                self.scopes
+                    .last_mut()
+                    .expect("the outermost scope is never popped")
+                    .insert(name.clone());
+            }
+            StmtKind::Assign { name, value } => {
+                let start = statement.span.start;
+                let end = SourcePos::new(start.line, start.column + name.chars().count());
+                self.variable(name, Span::new(start, end));
+                self.expr(value);
+            }
+            StmtKind::IndexAssign { array, index, value } => {
+                self.expr(array);
+                self.expr(index);
+                self.expr(value);
+            }
+            StmtKind::Expr(expr) => self.expr(expr),
+            StmtKind::Block(body) => self.block(body),
+            StmtKind::While { condition, body } => {
+                self.expr(condition);
+                self.loop_body(body);
+            }
+            StmtKind::For { init, condition, step, body } => {
+                // Variables declared in the header are scoped to the loop
+                self.scopes.push(HashSet::new());
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.expr(condition);
+                }
+                self.loop_body(body);
+                if let Some(step) = step {
+                    self.statement(step);
+                }
+                self.scopes.pop();
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                if self.loop_depth == 0 {
+                    let keyword = if statement.kind == StmtKind::Break { "break" } else { "continue" };
+                    let message = format!("`{}` outside of a loop", keyword);
+                    self.diagnostics.push(Diagnostic::new(message, statement.span));
+                }
+            }
+        }
+    }
+
+    fn block(&mut self, body: &[Stmt]) {
+        self.scopes.push(HashSet::new());
+        for statement in body {
+            self.statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    fn loop_body(&mut self, body: &[Stmt]) {
+        self.loop_depth += 1;
+        self.block(body);
+        self.loop_depth -= 1;
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Variable(name) => self.variable(name, expr.span),
+            ExprKind::Array(elements) => elements.iter().for_each(|element| self.expr(element)),
+            ExprKind::Index(array, index) => {
+                self.expr(array);
+                self.expr(index);
+            }
+            ExprKind::Call { name, args } => {
+                match builtin(name) {
+                    None => {
+                        let message = format!("Unknown function: {}", name);
+                        self.diagnostics.push(Diagnostic::new(message, expr.span));
+                    }
+                    Some((arity, _)) if arity != args.len() => {
+                        let message = format!(
+                            "`{}` takes {} argument{}, but {} {} given",
+                            name,
+                            arity,
+                            if arity == 1 { "" } else { "s" },
+                            args.len(),
+                            if args.len() == 1 { "was" } else { "were" },
+                        );
+                        self.diagnostics.push(Diagnostic::new(message, expr.span));
+                    }
+                    Some(_) => {}
+                }
+                args.iter().for_each(|arg| self.expr(arg));
+            }
+            ExprKind::Unary(_, operand) => self.expr(operand),
+            ExprKind::Binary(_, left, right) => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::Integer(_) | ExprKind::Float(_) | ExprKind::Boolean(_) | ExprKind::String(_) => {}
+        }
+    }
+
+    fn variable(&mut self, name: &str, span: Span) {
+        if !self.scopes.iter().any(|scope| scope.contains(name)) {
+            let message = format!("Unknown variable: {}", name);
+            self.diagnostics.push(Diagnostic::new(message, span));
+        }
+    }
+}
+
+fn syntax_error(source: &str, message: String, pos: SourcePos) -> AssemblerError {
+    AssemblerError::ParseError {
+        message,
+        text: source.lines().nth(pos.line - 1).unwrap_or(source).trim().to_string(),
+        pos,
+    }
+}
+
+/// What the compiler knows about a value's type: enough to choose between
+/// numeric `+` and string concatenation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ValueType {
+    String,
+    Other,
+}
+
+impl ValueType {
+    fn describe(self) -> &'static str {
+        match self {
+            ValueType::String => "a string",
+            ValueType::Other => "a non-string value",
+        }
+    }
+}
+
+/// A declared variable: its local slot and the type of its initializer
+#[derive(Debug, Clone, Copy)]
+struct Variable {
+    slot: usize,
+    value_type: ValueType,
+}
+
+/// Variables in scope while compiling statements, innermost scope last.
+/// Each variable lives in its own local slot of the program's frame
+struct Scopes {
+    scopes: Vec<HashMap<String, Variable>>,
+    next_slot: usize,
+    max_slots: usize,
+}
+
+impl Scopes {
+    fn new() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            next_slot: 0,
+            max_slots: 0,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Variable> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    /// Binds `name` to a fresh slot, shadowing any earlier binding
+    fn declare(&mut self, name: &str, value_type: ValueType) -> usize {
+        let slot = self.next_slot;
+        self.next_slot += 1;
+        self.max_slots = self.max_slots.max(self.next_slot);
+        self.scopes
+            .last_mut()
+            .expect("the outermost scope is never popped")
+            .insert(name.to_string(), Variable { slot, value_type });
+        slot
+    }
+
+    fn enter(&mut self) -> usize {
+        self.scopes.push(HashMap::new());
+        self.next_slot
+    }
+
+    /// Leaves the innermost scope; its slots are reused by later scopes
+    fn exit(&mut self, first_slot: usize) {
+        self.scopes.pop();
+        self.next_slot = first_slot;
+    }
+}
+
+/// Jump targets for `continue` (`next`) and `break` (`end`) in a loop
+#[derive(Clone)]
+struct LoopLabels {
+    next: String,
+    end: String,
+}
+
+/// Emits assembly for parsed statements and expressions
+struct CodeGen<'a> {
+    source: &'a str,
+    scopes: Scopes,
+    loops: Vec<LoopLabels>,
+    label_count: usize,
+    /// String literals, declared as `.string STR<index>`
+    strings: Vec<String>,
+    /// Integer literals, declared as `.const INT<index>` when pooled
+    integers: Vec<i64>,
+    pool_integers: bool,
+    out: String,
+    /// Source line of the construct being compiled
+    line: Option<usize>,
+    /// Source line of each line of `out`
+    out_lines: Vec<Option<usize>>,
+}
+
+impl<'a> CodeGen<'a> {
+    fn new(source: &'a str, pool_integers: bool) -> Self {
+        Self {
+            source,
+            scopes: Scopes::new(),
+            loops: Vec::new(),
+            label_count: 0,
+            strings: Vec::new(),
+            integers: Vec::new(),
+            pool_integers,
+            out: String::new(),
+            line: None,
+            out_lines: Vec::new(),
+        }
+    }
+
+    /// Runs `emit` on a fresh generator. An integer PUSH operand indexes
+    /// the constants pool once the pool is non-empty, so code that declares
+    /// strings is generated again with its integers pooled as well
+    fn generate(
+        source: &'a str,
+        emit: impl Fn(&mut CodeGen<'a>) -> Result<(), AssemblerError>,
+    ) -> Result<Self, AssemblerError> {
+        let mut codegen = CodeGen::new(source, false);
+        emit(&mut codegen)?;
+        if !codegen.strings.is_empty() {
+            codegen = CodeGen::new(source, true);
+            emit(&mut codegen)?;
+        }
+        Ok(codegen)
+    }
+
+    /// Constants pool declarations for the literals used
+    fn declarations(&self) -> String {
+        let mut declarations = String::new();
+        for (index, text) in self.strings.iter().enumerate() {
+            declarations.push_str(&format!(".string STR{} \"{}\"\n", index, escape(text, '"')));
+        }
+        for (index, value) in self.integers.iter().enumerate() {
+            declarations.push_str(&format!(".const INT{} {}\n", index, value));
+        }
+        declarations
+    }
+
+    /// Index of `item` in `pool`, adding it if not yet present
+    fn intern<T: PartialEq>(pool: &mut Vec<T>, item: T) -> usize {
+        pool.iter().position(|existing| *existing == item).unwrap_or_else(|| {
+            pool.push(item);
+            pool.len() - 1
+        })
+    }
+
+    /// Fresh local labels for a loop; not yet entered
+    fn new_loop(&mut self, kind: &str) -> LoopLabels {
+        let id = self.label_count;
+        self.label_count += 1;
+        LoopLabels {
+            next: format!(".{}{}", kind, id),
+            end: format!(".{}{}_end", kind, id),
+        }
+    }
+
+    fn emit(&mut self, line: impl AsRef<str>) {
+        for text in line.as_ref().lines() {
+            self.out.push_str(text);
+            self.out.push('\n');
+            self.out_lines.push(self.line);
+        }
+    }
+
+    /// Runs `compile` with emitted code attributed to source `line`
+    fn at_line<T>(&mut self, line: usize, compile: impl FnOnce(&mut Self) -> T) -> T {
+        let outer = self.line.replace(line);
+        let result = compile(self);
+        self.line = outer;
+        result
+    }
+
+    /// Compiles `statement`, discarding the value of an expression
+    /// statement unless `keep_value`
+    fn statement(&mut self, statement: &Stmt, keep_value: bool) -> Result<(), AssemblerError> {
+        self.at_line(statement.span.start.line, |codegen| {
+            codegen.statement_code(statement, keep_value)
+        })
+    }
+
+    fn statement_code(&mut self, statement: &Stmt, keep_value: bool) -> Result<(), AssemblerError> {
+        match &statement.kind {
+            StmtKind::Let { name, value } => {
+                // The initializer cannot see the variable it defines
+                let value_type = self.expr(value)?;
+                let slot = self.scopes.declare(name, value_type);
+                self.emit(format!("STORE {}", slot));
+            }
+            StmtKind::Assign { name, value } => {
+                let variable = self.variable(name);
+                let value_type = self.expr(value)?;
+                if value_type != variable.value_type {
+                    let message = format!(
+                        "Cannot assign {} to `{}`, which holds {}",
+                        value_type.describe(),
+                        name,
+                        variable.value_type.describe()
+                    );
+                    return Err(syntax_error(self.source, message, value.span.start));
+                }
+                self.emit(format!("STORE {}", variable.slot));
+            }
+            StmtKind::IndexAssign { array, index, value } => {
+                self.expr(array)?;
+                self.expr(index)?;
+                self.expr(value)?;
+                self.emit("SET_ARRAY");
+            }
+            StmtKind::Expr(expr) => {
+                self.expr(expr)?;
+                if !keep_value {
+                    self.emit("POP");
+                }
+            }
+            StmtKind::Block(body) => self.block(body)?,
+            StmtKind::While { condition, body } => {
+                let labels = self.new_loop("while");
+                self.emit(format!("{}:", labels.next));
+                self.expr(condition)?;
+                self.emit(format!("JF {}", labels.end));
+                self.loop_body(&labels, body)?;
+                self.emit(format!("JMP {}\n{}:", labels.next, labels.end));
+            }
+            StmtKind::For { init, condition, step, body } => {
+                // Variables declared in the header are scoped to the loop
+                let first_slot = self.scopes.enter();
+                if let Some(init) = init {
+                    self.statement(init, false)?;
+                }
+
+                let labels = self.new_loop("for");
+                let start = format!("{}_start", labels.next);
+                self.emit(format!("{}:", start));
+                if let Some(condition) = condition {
+                    self.expr(condition)?;
+                    self.emit(format!("JF {}", labels.end));
+                }
+                self.loop_body(&labels, body)?;
+
+                self.emit(format!("{}:", labels.next));
+                if let Some(step) = step {
+                    self.statement(step, false)?;
+                }
+                self.emit(format!("JMP {}\n{}:", start, labels.end));
+                self.scopes.exit(first_slot);
+            }
+            StmtKind::Break | StmtKind::Continue => {
+                let labels = self.loops.last().expect("checked: loop control is inside a loop");
+                let target = if statement.kind == StmtKind::Break { &labels.end } else { &labels.next };
+                let jump = format!("JMP {}", target);
+                self.emit(jump);
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles `body` in a new scope
+    fn block(&mut self, body: &[Stmt]) -> Result<(), AssemblerError> {
+        let first_slot = self.scopes.enter();
+        for statement in body {
+            self.statement(statement, false)?;
+        }
+        self.scopes.exit(first_slot);
+        Ok(())
+    }
+
+    fn loop_body(&mut self, labels: &LoopLabels, body: &[Stmt]) -> Result<(), AssemblerError> {
+        self.loops.push(labels.clone());
+        let result = self.block(body);
+        self.loops.pop();
+        result
+    }
+
+    /// Emits code leaving the value of `expr` on the stack
+    fn expr(&mut self, expr: &Expr) -> Result<ValueType, AssemblerError> {
+        self.at_line(expr.span.start.line, |codegen| codegen.expr_code(expr))
+    }
+
+    fn expr_code(&mut self, expr: &Expr) -> Result<ValueType, AssemblerError> {
+        match &expr.kind {
+            ExprKind::Integer(value) if self.pool_integers => {
+                let index = Self::intern(&mut self.integers, *value);
+                self.emit(format!("PUSH INT{}", index));
+            }
+            ExprKind::Integer(value) => self.emit(format!("PUSH {}", value)),
+            // Debug formatting keeps the `.0` that marks a float
+            ExprKind::Float(value) => self.emit(format!("PUSH {:?}", value)),
+            ExprKind::Boolean(value) => self.emit(format!("PUSH {}", value)),
+            ExprKind::String(text) => {
+                let index = Self::intern(&mut self.strings, text.clone());
+                self.emit(format!("PUSH STR{}", index));
+                return Ok(ValueType::String);
+            }
+            ExprKind::Variable(name) => {
+                let variable = self.variable(name);
+                self.emit(format!("LOAD {}", variable.slot));
+                return Ok(variable.value_type);
+            }
+            ExprKind::Array(elements) => {
+                for element in elements {
+                    self.expr(element)?;
+                }
+                self.emit(format!("NEW_ARRAY {}", elements.len()));
+            }
+            ExprKind::Index(array, index) => {
+                self.expr(array)?;
+                self.expr(index)?;
+                self.emit("GET_ARRAY");
+            }
+            ExprKind::Call { name, args } => {
+                for arg in args {
+                    self.expr(arg)?;
+                }
+                let (_, opcode) = builtin(name).expect("checked: calls name a builtin");
+                self.emit(mnemonic(opcode).expect("builtins have mnemonics"));
+                if opcode == Opcode::Substring {
+                    return Ok(ValueType::String);
+                }
+            }
+            ExprKind::Unary(op, operand) => {
+                self.expr(operand)?;
+                self.emit(mnemonic(op.opcode()).expect("unary operators have mnemonics"));
+            }
+            ExprKind::Binary(op, left, right) => {
+                let left = self.expr(left)?;
+                let right = self.expr(right)?;
+                // `+` with a string on either side concatenates
+                if *op == BinaryOp::Add && (left == ValueType::String || right == ValueType::String) {
+                    self.emit("STRCAT");
+                    return Ok(ValueType::String);
+                }
+                self.emit(mnemonic(op.opcode()).expect("binary operators have mnemonics"));
+            }
+        }
+        Ok(ValueType::Other)
+    }
+
+    fn variable(&self, name: &str) -> Variable {
+        self.scopes.lookup(name).expect("checked: variables are declared before use")
+    }
+}
+
+// High-level language compiler for a simple stack-based language
+pub struct SimpleCompiler {
+    assembler: Assembler,
+    fold_constants: bool,
+    source_name: Option<String>,
+}
+
+impl SimpleCompiler {
+    /// Entry function wrapping compiled programs, giving them a frame for
+    /// their variables
+    const ENTRY_FUNCTION: &'static str = "main";
+
+    pub fn new() -> Self {
+        Self {
+            assembler: Assembler::new(),
+            fold_constants: true,
+            source_name: None,
+        }
+    }
+
+    /// Records `name` as the source file of compiled programs, for error
+    /// messages and stack traces
+    pub fn with_source_name(mut self, name: impl Into<String>) -> Self {
+        self.source_name = Some(name.into());
+        self
+    }
+
+    /// Whether to fold constant expressions and conditions before
+    /// generating code; on by default
+    pub fn with_constant_folding(mut self, enabled: bool) -> Self {
+        self.fold_constants = enabled;
+        self
+    }
+
+    /// Compiles a single expression over literals, followed by `HALT`.
+    /// String literals are placed in the returned constants pool
+    pub fn compile_expression(&mut self, expr: &str) -> Result<(Vec<Instruction>, Vec<Value>), AssemblerError> {
+        let mut parsed = Parser::new(expr)?.parse_expression()?;
+        if let Some(diagnostic) = check_expression(&parsed).into_iter().next() {
+            return Err(diagnostic.into_error(expr));
+        }
+        if self.fold_constants {
+            parsed = parsed.fold();
+        }
+        let codegen = CodeGen::generate(expr, |codegen| {
+            codegen.expr(&parsed)?;
+            codegen.emit("HALT");
+            Ok(())
+        })?;
+        self.assembler.assemble(&format!("{}{}", codegen.declarations(), codegen.out))
+    }
+
+    /// Compiles statements separated by `;`: `let x = expr`, assignments
+    /// `x = expr`, `{ ... }` blocks, `while COND { ... }` and
+    /// `for (INIT; COND; STEP) { ... }` loops with `break`/`continue`, and
+    /// expressions, including `[a, b]` array literals, `a[i]` element reads
+    /// and assignments, and calls to [`BUILTINS`]. `+` concatenates when
+    /// either operand is a string. The value of a final expression
+    /// statement is left on the stack as the program's result.
+    ///
+    /// Variables live in the locals of an entry function, so the module
+    /// must be run with [`VirtualMachine::load_module`](crate::vm::runtime::VirtualMachine::load_module).
+    pub fn compile_program(&mut self, source: &str) -> Result<BytecodeModule, AssemblerError> {
+        let mut program = Parser::new(source)?.parse_program()?;
+        if let Some(diagnostic) = check_program(&program).into_iter().next() {
+            return Err(diagnostic.into_error(source));
+        }
+        if self.fold_constants {
+            program = program.into_iter().map(Stmt::fold).collect();
+        }
+        let codegen = CodeGen::generate(source, |codegen| {
+            for (index, statement) in program.iter().enumerate() {
+                codegen.statement(statement, index + 1 == program.len())?;
+            }
+            Ok(())
+        })?;
+
+        let prologue = format!(
+            "{declarations}CALL {name}\nHALT\n.func {name} locals={locals}\n",
+            declarations = codegen.declarations(),
+            name = Self::ENTRY_FUNCTION,
+            locals = codegen.scopes.max_slots,
+        );
+        let assembly = format!("{}{}RET\n.endfunc\n", prologue, codegen.out);
+        let mut module = Assembler::new().assemble_module(&assembly)?;
+
+        // Map the assembler's line table back from assembly to source lines
+        let source_lines: Vec<Option<usize>> = std::iter::repeat_n(None, prologue.lines().count())
+            .chain(codegen.out_lines.iter().copied())
+            .collect();
+        let lines = (0..module.instructions.len()).map(|pc| {
+            let assembly_line = module.debug_info.lines.line_at(pc)?;
+            source_lines.get(assembly_line - 1).copied().flatten()
+        });
+        module.debug_info = DebugInfo::for_file(
+            LineTable::from_lines(lines.collect::<Vec<_>>()),
+            self.source_name.clone(),
+        );
+        Ok(module)
+    }
+
+    /// Every problem [`compile_program`](Self::compile_program) would
+    /// reject `source` for before generating code. A syntax error stops
+    /// parsing, so it is reported alone
+    pub fn diagnostics(&self, source: &str) -> Vec<Diagnostic> {
+        match Parser::new(source).and_then(|mut parser| parser.parse_program()) {
+            Ok(program) => check_program(&program),
+            Err(error) => {
+                let pos = error.pos();
+                let message = match error {
+                    AssemblerError::ParseError { message, .. } => message,
+                    other => other.to_string(),
+                };
+                vec![Diagnostic::new(message, Span::new(pos, pos))]
+            }
+        }
+    }
+}
+
+impl Default for SimpleCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vm::instruction::Opcode;
+
+    fn parse_expr(source: &str) -> Expr {
+        Parser::new(source).unwrap().parse_expression().unwrap()
+    }
+
+    #[test]
+    fn test_lexer_tokens_and_spans() {
+        let tokens = Lexer::new("let x1 = 2.5e3 // note\n  >= !=_").tokenize().unwrap();
+        let kinds: Vec<_> = tokens.iter().map(|token| token.kind.clone()).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Let,
+                TokenKind::Identifier("x1".to_string()),
+                TokenKind::Equal,
+                TokenKind::Float(2500.0),
+                TokenKind::GreaterEqual,
+                TokenKind::BangEqual,
+                TokenKind::Identifier("_".to_string()),
+            ]
+        );
+        assert_eq!(tokens[1].span, Span::new(SourcePos::new(1, 5), SourcePos::new(1, 7)));
+        assert_eq!(tokens[4].span, Span::new(SourcePos::new(2, 3), SourcePos::new(2, 5)));
+
+        let tokens = Lexer::new("1_000 2e").tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::Integer(1000));
+        assert_eq!(tokens[1].kind, TokenKind::Integer(2));
+        assert_eq!(tokens[2].kind, TokenKind::Identifier("e".to_string()));
+    }
+
+    #[test]
+    fn test_lexer_errors() {
+        let error = Lexer::new("1 +\n  # 2").tokenize().unwrap_err();
+        assert!(error.to_string().contains("Unexpected character `#`"));
+        assert_eq!(error.pos(), SourcePos::new(2, 3));
+
+        let error = Lexer::new("99999999999999999999").tokenize().unwrap_err();
+        assert!(error.to_string().contains("out of range"));
+    }
+
+    #[test]
+    fn test_lexer_string_literals() {
+        let tokens = Lexer::new(r#""a \"b\"\n" + "c""#).tokenize().unwrap();
+        assert_eq!(tokens[0].kind, TokenKind::String("a \"b\"\n".to_string()));
+        assert_eq!(tokens[2].span, Span::new(SourcePos::new(1, 15), SourcePos::new(1, 18)));
+
+        let error = Lexer::new("1 + \"open\nx").tokenize().unwrap_err();
+        assert!(error.to_string().contains("Unterminated string literal"));
+        assert_eq!(error.pos(), SourcePos::new(1, 5));
+
+        let error = Lexer::new(r#""\q""#).tokenize().unwrap_err();
+        assert!(error.to_string().contains("Unknown escape sequence"));
+        assert_eq!(error.pos(), SourcePos::new(1, 2));
+    }
+
+    #[test]
+    fn test_parser_precedence_and_spans() {
+        let expr = parse_expr("1 + 2 * 3 == 7 || !false");
+        let ExprKind::Binary(BinaryOp::Or, left, right) = &expr.kind else {
+            panic!("Expected `||` at the root, got {:?}", expr.kind);
+        };
+        assert!(matches!(right.kind, ExprKind::Unary(UnaryOp::Not, _)));
+        let ExprKind::Binary(BinaryOp::Equal, sum, _) = &left.kind else {
+            panic!("Expected `==` under `||`, got {:?}", left.kind);
+        };
+        let ExprKind::Binary(BinaryOp::Add, _, product) = &sum.kind else {
+            panic!("Expected `+` under `==`, got {:?}", sum.kind);
+        };
+        assert!(matches!(product.kind, ExprKind::Binary(BinaryOp::Mul, _, _)));
+
+        assert_eq!(expr.span, Span::new(SourcePos::new(1, 1), SourcePos::new(1, 25)));
+        assert_eq!(product.span, Span::new(SourcePos::new(1, 5), SourcePos::new(1, 10)));
+
+        // Operators at the same level associate to the left
+        let expr = parse_expr("(8 - 4) - 2");
+        let ExprKind::Binary(BinaryOp::Sub, left, _) = &expr.kind else {
+            panic!("Expected subtraction, got {:?}", expr.kind);
+        };
+        assert_eq!(left.span, Span::new(SourcePos::new(1, 1), SourcePos::new(1, 8)));
+    }
+
+    #[test]
+    fn test_parser_statements() {
+        let program = Parser::new("let i = 0;\nwhile i < 3 { i = i + 1; }\nfor (;;) { break }")
+            .unwrap()
+            .parse_program()
+            .unwrap();
+        assert_eq!(program.len(), 3);
+        assert!(matches!(&program[0].kind, StmtKind::Let { name, .. } if name == "i"));
+        assert_eq!(program[1].span, Span::new(SourcePos::new(2, 1), SourcePos::new(2, 27)));
+        match &program[2].kind {
+            StmtKind::For { init: None, condition: None, step: None, body } => {
+                assert_eq!(body.len(), 1);
+                assert_eq!(body[0].kind, StmtKind::Break);
+            }
+            other => panic!("Expected an empty for header, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parser_arrays_and_index_assignment() {
+        let program = Parser::new("a[i + 1] = [1, [2]][1][0]").unwrap().parse_program().unwrap();
+        let StmtKind::IndexAssign { array, index, value } = &program[0].kind else {
+            panic!("Expected an element assignment, got {:?}", program[0].kind);
+        };
+        assert_eq!(array.kind, ExprKind::Variable("a".to_string()));
+        assert!(matches!(index.kind, ExprKind::Binary(BinaryOp::Add, _, _)));
+        let ExprKind::Index(inner, _) = &value.kind else {
+            panic!("Expected indexing, got {:?}", value.kind);
+        };
+        assert!(matches!(&inner.kind, ExprKind::Index(literal, _)
+            if matches!(&literal.kind, ExprKind::Array(elements) if elements.len() == 2)));
+        assert_eq!(value.span, Span::new(SourcePos::new(1, 12), SourcePos::new(1, 26)));
+
+        let error = Parser::new("1 + 2 = 3").unwrap().parse_program().unwrap_err();
+        assert!(error.to_string().contains("Only variables and array elements"));
+        assert_eq!(error.pos(), SourcePos::new(1, 1));
+
+        let error = Parser::new("[1, 2").unwrap().parse_program().unwrap_err();
+        assert!(error.to_string().contains("Expected `]`"));
+    }
+
+    #[test]
+    fn test_parser_errors() {
+        let error = Parser::new("(1 + 2").unwrap().parse_expression().unwrap_err();
+        assert!(error.to_string().contains("Expected `)`"));
+
+        let error = Parser::new("1 2").unwrap().parse_expression().unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(1, 3));
+
+        let error = Parser::new("let x = 1 +;").unwrap().parse_program().unwrap_err();
+        assert!(error.to_string().contains("Expected expression, found `;`"));
+        assert_eq!(error.pos(), SourcePos::new(1, 12));
+    }
+
+    #[test]
+    fn test_simple_compiler() {
+        let mut compiler = SimpleCompiler::new().with_constant_folding(false);
+        let result = compiler.compile_expression("5 + 3 * 2");
+        assert!(result.is_ok());
+
+        let (instructions, _constants) = result.unwrap();
+        assert!(instructions.len() > 4); // Should have push, push, push, mul, add, halt
+    }
+
+    #[test]
+    fn test_compiler_with_parentheses() {
+        let mut compiler = SimpleCompiler::new().with_constant_folding(false);
+        let result = compiler.compile_expression("(5 + 3) * 2");
+        assert!(result.is_ok());
+
+        let (instructions, _) = result.unwrap();
+        assert!(instructions.len() > 4);
+    }
+
+    #[test]
+    fn test_compiler_comparison_operators() {
+        let mut compiler = SimpleCompiler::new().with_constant_folding(false);
+        let (instructions, _) = compiler.compile_expression("1 + 2 >= 3 && !(4 == 5)").unwrap();
+        let opcodes: Vec<_> = instructions.iter().map(|i| i.opcode()).collect();
+        assert_eq!(
+            opcodes,
+            vec![
+                Opcode::Push,
+                Opcode::Push,
+                Opcode::Add,
+                Opcode::Push,
+                Opcode::GreaterEqual,
+                Opcode::Push,
+                Opcode::Push,
+                Opcode::Equal,
+                Opcode::Not,
+                Opcode::And,
+                Opcode::Halt,
+            ]
+        );
+
+        let error = compiler.compile_expression("1 & 2").unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(1, 3));
+    }
+
+    #[test]
+    fn test_compiler_unary_operators() {
+        let mut compiler = SimpleCompiler::new().with_constant_folding(false);
+        let (instructions, _) = compiler.compile_expression("-2 * - -3 - !!(1 < 2)").unwrap();
+        let opcodes: Vec<_> = instructions.iter().map(|i| i.opcode()).collect();
+        assert_eq!(
+            opcodes,
+            vec![
+                Opcode::Push,
+                Opcode::Negate,
+                Opcode::Push,
+                Opcode::Negate,
+                Opcode::Negate,
+                Opcode::Mul,
+                Opcode::Push,
+                Opcode::Push,
+                Opcode::LessThan,
+                Opcode::Not,
+                Opcode::Not,
+                Opcode::Sub,
+                Opcode::Halt,
+            ]
+        );
+
+        let error = compiler.compile_expression("1 + -").unwrap_err();
+        assert!(error.to_string().contains("Expected expression"));
+        assert_eq!(error.pos(), SourcePos::new(1, 6));
+    }
+
+    #[test]
+    fn test_compiler_string_constants() {
+        let mut compiler = SimpleCompiler::new().with_constant_folding(false);
+        let (instructions, constants) = compiler.compile_expression(r#""ab" + 1 + "ab""#).unwrap();
+        assert_eq!(constants, vec![Value::String("ab".into()), Value::Integer(1)]);
+        let opcodes: Vec<_> = instructions.iter().map(|i| i.opcode()).collect();
+        assert_eq!(
+            opcodes,
+            vec![
+                Opcode::LoadConstant,
+                Opcode::Push,
+                Opcode::StrConcat,
+                Opcode::LoadConstant,
+                Opcode::StrConcat,
+                Opcode::Halt,
+            ]
+        );
+        // The integer is pushed through the constants pool
+        assert_eq!(instructions[1].operand(), Some(&Value::Integer(1)));
+
+        let error = compiler.compile_program("let s = \"a\";\ns = 1").unwrap_err();
+        assert!(error.to_string().contains("Cannot assign a non-string value to `s`"));
+        assert_eq!(error.pos(), SourcePos::new(2, 5));
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        let folded = |source: &str| parse_expr(source).fold().kind;
+        assert_eq!(folded("2 * 3 + 4"), ExprKind::Integer(10));
+        assert_eq!(folded("-(1.5 * 2)"), ExprKind::Float(-3.0));
+        assert_eq!(folded("!(1 < 2) || 3 >= 3"), ExprKind::Boolean(true));
+        assert_eq!(folded("\"n=\" + 2 * 2"), ExprKind::String("n=4".to_string()));
+
+        // Only the constant operand of a variable expression is folded
+        let ExprKind::Binary(BinaryOp::Mul, left, right) = folded("x * (2 + 3)") else {
+            panic!("Expected a multiplication");
+        };
+        assert_eq!(left.kind, ExprKind::Variable("x".to_string()));
+        assert_eq!(right.kind, ExprKind::Integer(5));
+
+        // Failures are left for run time
+        assert!(matches!(folded("1 / 0"), ExprKind::Binary(BinaryOp::Div, _, _)));
+        assert!(matches!(folded("9223372036854775807 + 1"), ExprKind::Binary(BinaryOp::Add, _, _)));
+        assert!(matches!(folded("-true"), ExprKind::Unary(UnaryOp::Negate, _)));
+    }
+
+    #[test]
+    fn test_constant_loop_conditions() {
+        let fold = |source: &str| -> Vec<StmtKind> {
+            let program = Parser::new(source).unwrap().parse_program().unwrap();
+            program.into_iter().map(|statement| statement.fold().kind).collect()
+        };
+
+        assert_eq!(fold("while 1 > 2 { 1 }"), vec![StmtKind::Block(Vec::new())]);
+        assert!(matches!(
+            &fold("while 1 < 2 { break }")[0],
+            StmtKind::For { init: None, condition: None, step: None, .. }
+        ));
+        match &fold("for (let i = 0; false; i = i + 1) { i }")[0] {
+            StmtKind::Block(body) => {
+                assert!(matches!(&body[..], [Stmt { kind: StmtKind::Let { .. }, .. }]));
+            }
+            other => panic!("Expected the initializer alone, got {:?}", other),
+        }
+        assert!(matches!(
+            &fold("for (;true;) { break }")[0],
+            StmtKind::For { condition: None, .. }
+        ));
+    }
+
+    #[test]
+    fn test_diagnostics_report_every_problem() {
+        let source = "let a = [1];\nbreak;\nlen(a, 2) + size(a);\ntotal = b;\nwhile true { continue }";
+        let diagnostics = SimpleCompiler::new().diagnostics(source);
+        let found: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.message.as_str(), diagnostic.span.start))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("`break` outside of a loop", SourcePos::new(2, 1)),
+                ("`len` takes 1 argument, but 2 were given", SourcePos::new(3, 1)),
+                ("Unknown function: size", SourcePos::new(3, 13)),
+                ("Unknown variable: total", SourcePos::new(4, 1)),
+                ("Unknown variable: b", SourcePos::new(4, 9)),
+            ]
+        );
+        assert_eq!(diagnostics[1].span.end, SourcePos::new(3, 10));
+        assert_eq!(diagnostics[3].span.end, SourcePos::new(4, 6));
+        assert_eq!(diagnostics[0].to_string(), "line 2, column 1: `break` outside of a loop");
+
+        let diagnostics = SimpleCompiler::new().diagnostics("let x = (1;");
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("Expected `)`"));
+
+        assert!(SimpleCompiler::new().diagnostics("let s = \"abc\"; substring(s, 0, len([s]))").is_empty());
+    }
+
+    #[test]
+    fn test_compiler_error_column() {
+        let mut compiler = SimpleCompiler::new();
+        let error = compiler.compile_expression("1 + x2").unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(1, 5));
+    }
+
+    #[test]
+    fn test_compile_program_errors() {
+        let mut compiler = SimpleCompiler::new();
+
+        let error = compiler.compile_program("let x = 1;\nx + y").unwrap_err();
+        assert!(error.to_string().contains("Unknown variable: y"));
+        assert_eq!(error.pos(), SourcePos::new(2, 5));
+
+        let error = compiler.compile_program("{ let x = 1; };\nx").unwrap_err();
+        assert!(error.to_string().contains("Unknown variable: x"));
+
+        let error = compiler.compile_program("count = 3").unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(1, 1));
+
+        let error = compiler.compile_program("let 9 = 3").unwrap_err();
+        assert!(error.to_string().contains("Invalid variable name"));
+
+        let error = compiler.compile_program("let x =;").unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(1, 7));
+
+        let error = compiler.compile_program("let x").unwrap_err();
+        assert!(matches!(error, AssemblerError::ParseError { .. }));
+
+        let error = compiler.compile_program("{ let x = 1;").unwrap_err();
+        assert!(error.to_string().contains("Missing `}`"));
+
+        let error = compiler.compile_program("1;;2").unwrap_err();
+        assert!(error.to_string().contains("Empty statement"));
+
+        let error = compiler.compile_program("1 }").unwrap_err();
+        assert!(error.to_string().contains("Unexpected `}`"));
+        assert_eq!(error.pos(), SourcePos::new(1, 3));
+
+        let error = compiler.compile_program("break;").unwrap_err();
+        assert!(error.to_string().contains("outside of a loop"));
+
+        let error = compiler.compile_program("while { 1 }").unwrap_err();
+        assert!(error.to_string().contains("Missing loop condition"));
+
+        let error = compiler.compile_program("while 1; { 1 }").unwrap_err();
+        assert_eq!(error.pos(), SourcePos::new(1, 1));
+
+        let error = compiler.compile_program("for (let i = 0; i) { 1 }").unwrap_err();
+        assert!(error.to_string().contains("Expected `for"));
+
+        let error = compiler.compile_program("for (;;) 1").unwrap_err();
+        assert!(error.to_string().contains("Expected `{`"));
+
+        let error = compiler.compile_program("for (let i = 0;;) {}\ni").unwrap_err();
+        assert!(error.to_string().contains("Unknown variable: i"));
+
+        // Compiling again with the same compiler still works
+        assert!(compiler.compile_program("let x = 1; x").is_ok());
+    }
+}
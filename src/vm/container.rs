@@ -0,0 +1,982 @@
+//! The `.svmbc` binary container for [`BytecodeModule`]s.
+//!
+//! A container is the magic bytes `SVMB`, a little-endian `u16` format
+//! version, the SHA-256 digest of everything after the header (from
+//! version 2 on), then a sequence of sections, each a tag byte, a `u32`
+//! payload length and the payload:
+//!
+//! - constants: `u32` count, then each value as a type tag and its data
+//! - code: the instruction stream of [`encode_program`]
+//! - functions: the function table
+//! - externs: references left for the linker
+//! - globals (optional): exported constants by name
+//! - debug (optional): the line table and symbol names
+//! - files (optional): source file names by address
+//! - compressed: another section's tag, its payload length and the payload
+//!   compressed with zstd, written by
+//!   `BytecodeModule::to_compressed_bytes` for the constants and code
+//!   sections. Reading one needs the `compression` feature.
+//! - signature (optional): an embedder's signature of the digest, added
+//!   by [`sign_container`] after every other section and not covered by
+//!   the digest
+//! - end (from version 3 on): an empty section closing the container, so
+//!   a reader knows where it stops without reading to the end of the
+//!   stream. It is not covered by the digest either.
+//!
+//! Readers skip sections with unknown tags, so later versions can add
+//! optional sections without breaking older loaders. Code from older
+//! versions is brought up to date as it is read: opcodes retired since the
+//! container was written are rewritten to their replacements.
+
+use crate::vm::compact::pool_literals;
+use crate::vm::heap::{Array, ByteBuffer, GcPtr, Object, TypedArray};
+use crate::vm::instruction::{decode_program, encode_program, Instruction, Opcode};
+use crate::vm::loader::DEFAULT_MAX_SECTION_LEN;
+use crate::vm::module::{
+    BytecodeModule, DebugInfo, ExternRef, FileEntry, FunctionInfo, LineEntry, LineTable,
+    ModuleError, SymbolTable,
+};
+use crate::vm::types::{Decimal, Value, VmTuple};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+/// File extension for bytecode containers
+pub const CONTAINER_EXTENSION: &str = "svmbc";
+
+/// First bytes of every container
+pub const CONTAINER_MAGIC: [u8; 4] = *b"SVMB";
+
+/// Layout version written by [`BytecodeModule::to_bytes`]
+pub const CONTAINER_FORMAT_VERSION: u16 = 3;
+
+/// Oldest layout version readers still accept
+pub const MIN_CONTAINER_FORMAT_VERSION: u16 = 1;
+
+/// First version whose header carries a content digest
+const DIGEST_VERSION: u16 = 2;
+
+/// First version closed by an end section
+const END_VERSION: u16 = 3;
+
+/// Length of the SHA-256 content digest
+pub const DIGEST_LEN: usize = 32;
+
+/// Opcode byte retired from the instruction set, read as `to` in code
+/// written by format versions before `until`
+struct OpcodeMigration {
+    until: u16,
+    from: u8,
+    to: Opcode,
+}
+
+/// Every retired opcode, oldest first. A retired byte must not be given to
+/// a new opcode while containers older than `until` are accepted, and its
+/// replacement must take the same kind of operand, so jump targets and the
+/// rest of the encoding stay valid.
+const OPCODE_MIGRATIONS: &[OpcodeMigration] = &[];
+
+const SECTION_CONSTANTS: u8 = 1;
+const SECTION_CODE: u8 = 2;
+const SECTION_FUNCTIONS: u8 = 3;
+const SECTION_EXTERNS: u8 = 4;
+const SECTION_DEBUG: u8 = 5;
+const SECTION_FILES: u8 = 6;
+const SECTION_COMPRESSED: u8 = 7;
+const SECTION_SIGNATURE: u8 = 8;
+const SECTION_GLOBALS: u8 = 9;
+const SECTION_END: u8 = 10;
+
+/// The end section as written: its tag and a zero length
+const END_SECTION: [u8; 5] = [SECTION_END, 0, 0, 0, 0];
+
+/// Bits of a function table entry's flags byte
+const FUNCTION_RETURNS: u8 = 1;
+const FUNCTION_EXPORTED: u8 = 2;
+
+const TAG_NULL: u8 = 0;
+const TAG_BOOLEAN: u8 = 1;
+const TAG_INTEGER: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+const TAG_DECIMAL: u8 = 4;
+const TAG_CHAR: u8 = 5;
+const TAG_STRING: u8 = 6;
+const TAG_GC_STRING: u8 = 7;
+const TAG_ARRAY: u8 = 8;
+const TAG_OBJECT: u8 = 9;
+const TAG_BYTES: u8 = 10;
+const TAG_INT_ARRAY: u8 = 11;
+const TAG_FLOAT_ARRAY: u8 = 12;
+const TAG_TUPLE: u8 = 13;
+const TAG_FUNCTION: u8 = 14;
+
+fn invalid(message: impl Into<String>) -> ModuleError {
+    ModuleError::InvalidContainer(message.into())
+}
+
+/// Checks a container's signature before it is loaded, so hosts can refuse
+/// bytecode that does not come from a trusted source. Closures taking the
+/// same arguments implement it.
+pub trait SignatureVerifier {
+    /// `digest` is the SHA-256 of the container's sections and `signature`
+    /// what [`sign_container`] stored, or `None` for unsigned containers;
+    /// an error rejects the container with [`ModuleError::SignatureRejected`]
+    fn verify(&self, digest: &[u8; DIGEST_LEN], signature: Option<&[u8]>) -> Result<(), String>;
+}
+
+impl<F> SignatureVerifier for F
+where
+    F: Fn(&[u8; DIGEST_LEN], Option<&[u8]>) -> Result<(), String>,
+{
+    fn verify(&self, digest: &[u8; DIGEST_LEN], signature: Option<&[u8]>) -> Result<(), String> {
+        self(digest, signature)
+    }
+}
+
+/// Adds a signature section to a container written by
+/// [`BytecodeModule::to_bytes`], holding what `sign` returns for the
+/// container's digest. It goes after the other sections and before the
+/// end section.
+pub fn sign_container(
+    bytes: &mut Vec<u8>,
+    sign: impl FnOnce(&[u8; DIGEST_LEN]) -> Vec<u8>,
+) -> Result<(), ModuleError> {
+    let header: &[u8; CONTAINER_HEADER_LEN] = bytes
+        .get(..CONTAINER_HEADER_LEN)
+        .and_then(|header| header.try_into().ok())
+        .ok_or_else(|| invalid("missing SVMB magic number"))?;
+    let version = check_header(header)?;
+    let end = CONTAINER_HEADER_LEN + digest_len(version);
+    let digest: [u8; DIGEST_LEN] = bytes
+        .get(CONTAINER_HEADER_LEN..end)
+        .and_then(|digest| digest.try_into().ok())
+        .ok_or_else(|| invalid("only containers with a content digest can be signed"))?;
+
+    let ended = version >= END_VERSION;
+    if ended {
+        if !bytes.ends_with(&END_SECTION) {
+            return Err(invalid("missing end section"));
+        }
+        bytes.truncate(bytes.len() - END_SECTION.len());
+    }
+
+    let mut out = Writer::default();
+    out.section(SECTION_SIGNATURE, Writer { bytes: sign(&digest) })?;
+    bytes.extend_from_slice(&out.bytes);
+    if ended {
+        bytes.extend_from_slice(&END_SECTION);
+    }
+    Ok(())
+}
+
+#[derive(Default)]
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn u32(&mut self, value: usize) -> Result<(), ModuleError> {
+        let value =
+            u32::try_from(value).map_err(|_| invalid(format!("{} does not fit in u32", value)))?;
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+        Ok(())
+    }
+
+    fn str(&mut self, text: &str) -> Result<(), ModuleError> {
+        self.u32(text.len())?;
+        self.bytes.extend_from_slice(text.as_bytes());
+        Ok(())
+    }
+
+    fn section(&mut self, tag: u8, payload: Writer) -> Result<(), ModuleError> {
+        self.u8(tag);
+        self.u32(payload.bytes.len())?;
+        self.bytes.extend_from_slice(&payload.bytes);
+        Ok(())
+    }
+
+    /// Writes a constants or code section, compressed at `level` when that
+    /// makes it smaller
+    fn data_section(&mut self, tag: u8, payload: Writer, level: Option<i32>) -> Result<(), ModuleError> {
+        #[cfg(feature = "compression")]
+        if let Some(level) = level {
+            let compressed = zstd::bulk::compress(&payload.bytes, level)
+                .map_err(|e| invalid(format!("failed to compress section {}: {}", tag, e)))?;
+            if compressed.len() + 5 < payload.bytes.len() {
+                let mut section = Writer::default();
+                section.u8(tag);
+                section.u32(payload.bytes.len())?;
+                section.bytes.extend_from_slice(&compressed);
+                return self.section(SECTION_COMPRESSED, section);
+            }
+        }
+        #[cfg(not(feature = "compression"))]
+        let _ = level;
+        self.section(tag, payload)
+    }
+
+    fn value(&mut self, value: &Value, seen: &mut Vec<usize>) -> Result<(), ModuleError> {
+        match value {
+            Value::Null => self.u8(TAG_NULL),
+            Value::Boolean(b) => {
+                self.u8(TAG_BOOLEAN);
+                self.u8(*b as u8);
+            }
+            Value::Integer(i) => {
+                self.u8(TAG_INTEGER);
+                self.bytes.extend_from_slice(&i.to_le_bytes());
+            }
+            Value::Float(f) => {
+                self.u8(TAG_FLOAT);
+                self.bytes.extend_from_slice(&f.to_le_bytes());
+            }
+            Value::Decimal(d) => {
+                self.u8(TAG_DECIMAL);
+                self.str(&d.to_string())?;
+            }
+            Value::Char(c) => {
+                self.u8(TAG_CHAR);
+                self.u32(*c as usize)?;
+            }
+            Value::String(s) => {
+                self.u8(TAG_STRING);
+                self.str(s)?;
+            }
+            Value::StrSlice(s) => {
+                self.u8(TAG_STRING);
+                self.str(s)?;
+            }
+            Value::GcString(s) => {
+                self.u8(TAG_GC_STRING);
+                self.str(&s.to_string())?;
+            }
+            Value::Bytes(b) => {
+                let bytes = b.to_vec();
+                self.u8(TAG_BYTES);
+                self.u32(bytes.len())?;
+                self.bytes.extend_from_slice(&bytes);
+            }
+            Value::IntArray(a) => {
+                let elements = a.to_vec();
+                self.u8(TAG_INT_ARRAY);
+                self.u32(elements.len())?;
+                for element in elements {
+                    self.bytes.extend_from_slice(&element.to_le_bytes());
+                }
+            }
+            Value::FloatArray(a) => {
+                let elements = a.to_vec();
+                self.u8(TAG_FLOAT_ARRAY);
+                self.u32(elements.len())?;
+                for element in elements {
+                    self.bytes.extend_from_slice(&element.to_le_bytes());
+                }
+            }
+            Value::Function { address, arity } => {
+                self.u8(TAG_FUNCTION);
+                self.u32(*address as usize)?;
+                self.u32(*arity as usize)?;
+            }
+            Value::Tuple(tuple) => {
+                self.u8(TAG_TUPLE);
+                self.u32(tuple.len())?;
+                for element in tuple.iter() {
+                    self.value(element, seen)?;
+                }
+            }
+            Value::Array(array) => {
+                Self::enter(array.object_id(), seen)?;
+                let elements = array.to_vec();
+                self.u8(TAG_ARRAY);
+                self.u32(elements.len())?;
+                for element in &elements {
+                    self.value(element, seen)?;
+                }
+                seen.pop();
+            }
+            Value::GcObject(object) => {
+                Self::enter(object.object_id(), seen)?;
+                let fields = object.fields();
+                self.u8(TAG_OBJECT);
+                self.u32(fields.len())?;
+                for (name, field) in &fields {
+                    self.str(name)?;
+                    self.value(field, seen)?;
+                }
+                seen.pop();
+            }
+        }
+        Ok(())
+    }
+
+    fn enter(object_id: usize, seen: &mut Vec<usize>) -> Result<(), ModuleError> {
+        if seen.contains(&object_id) {
+            return Err(invalid(format!("cannot encode cyclic value (object {})", object_id)));
+        }
+        seen.push(object_id);
+        Ok(())
+    }
+}
+
+/// Deepest nesting of arrays, tuples and objects a container may hold
+const MAX_VALUE_DEPTH: usize = 128;
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            offset: 0,
+            depth: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ModuleError> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or_else(|| invalid(format!("unexpected end of data at byte {}", self.offset)))?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ModuleError> {
+        Ok(self.take(N)?.try_into().expect("slice has N bytes"))
+    }
+
+    fn u8(&mut self) -> Result<u8, ModuleError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<usize, ModuleError> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    /// A count of items, each at least `item_size` bytes, that must fit in
+    /// what is left of the data
+    fn count(&mut self, item_size: usize) -> Result<usize, ModuleError> {
+        let count = self.u32()?;
+        if count.saturating_mul(item_size) > self.bytes.len() - self.offset {
+            return Err(invalid(format!("count {} exceeds the remaining data", count)));
+        }
+        Ok(count)
+    }
+
+    fn str(&mut self) -> Result<String, ModuleError> {
+        let len = self.u32()?;
+        let bytes = self.take(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| invalid("string is not valid UTF-8"))
+    }
+
+    fn value(&mut self) -> Result<Value, ModuleError> {
+        if self.depth == MAX_VALUE_DEPTH {
+            return Err(invalid("values are nested too deeply"));
+        }
+        self.depth += 1;
+        let value = self.value_contents();
+        self.depth -= 1;
+        value
+    }
+
+    fn value_contents(&mut self) -> Result<Value, ModuleError> {
+        Ok(match self.u8()? {
+            TAG_NULL => Value::Null,
+            TAG_BOOLEAN => Value::Boolean(self.u8()? != 0),
+            TAG_INTEGER => Value::Integer(i64::from_le_bytes(self.array()?)),
+            TAG_FLOAT => Value::Float(f64::from_le_bytes(self.array()?)),
+            TAG_DECIMAL => Value::Decimal(
+                self.str()?
+                    .parse::<Decimal>()
+                    .map_err(|e| invalid(e.to_string()))?,
+            ),
+            TAG_CHAR => {
+                let code = self.u32()? as u32;
+                let c = char::from_u32(code)
+                    .ok_or_else(|| invalid(format!("invalid char code {}", code)))?;
+                Value::Char(c)
+            }
+            TAG_STRING => Value::String(self.str()?.into()),
+            TAG_GC_STRING => Value::GcString(GcPtr::detached(self.str()?)),
+            TAG_BYTES => {
+                let len = self.u32()?;
+                Value::Bytes(GcPtr::detached(ByteBuffer::new(self.take(len)?.to_vec())))
+            }
+            TAG_INT_ARRAY => {
+                let count = self.count(8)?;
+                let elements = (0..count)
+                    .map(|_| Ok(i64::from_le_bytes(self.array()?)))
+                    .collect::<Result<_, ModuleError>>()?;
+                Value::IntArray(GcPtr::detached(TypedArray::new(elements)))
+            }
+            TAG_FLOAT_ARRAY => {
+                let count = self.count(8)?;
+                let elements = (0..count)
+                    .map(|_| Ok(f64::from_le_bytes(self.array()?)))
+                    .collect::<Result<_, ModuleError>>()?;
+                Value::FloatArray(GcPtr::detached(TypedArray::new(elements)))
+            }
+            TAG_FUNCTION => Value::Function {
+                address: self.u32()? as u32,
+                arity: self.u32()? as u32,
+            },
+            TAG_TUPLE => {
+                let count = self.count(1)?;
+                let elements = (0..count).map(|_| self.value()).collect::<Result<Vec<_>, _>>()?;
+                Value::Tuple(VmTuple::new(elements).ok_or_else(|| invalid("tuple is too long"))?)
+            }
+            TAG_ARRAY => {
+                let count = self.count(1)?;
+                let elements = (0..count).map(|_| self.value()).collect::<Result<Vec<_>, _>>()?;
+                Value::Array(GcPtr::detached(Array::new(elements)))
+            }
+            TAG_OBJECT => {
+                let count = self.count(5)?;
+                let object = Object::new();
+                for _ in 0..count {
+                    let name = self.str()?;
+                    object.set_field(name, self.value()?);
+                }
+                Value::GcObject(GcPtr::detached(object))
+            }
+            tag => return Err(invalid(format!("unknown value tag {}", tag))),
+        })
+    }
+}
+
+impl BytecodeModule {
+    /// Encodes this module as a `.svmbc` container. Operands the compact
+    /// instruction encoding cannot hold, such as float literals, are moved
+    /// into the constants pool first.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ModuleError> {
+        self.encode(None)
+    }
+
+    /// Like [`BytecodeModule::to_bytes`], with the constants and code
+    /// sections compressed using zstd at `level` (1-22, or 0 for zstd's
+    /// default). Sections that would not get smaller are stored as is.
+    #[cfg(feature = "compression")]
+    pub fn to_compressed_bytes(&self, level: i32) -> Result<Vec<u8>, ModuleError> {
+        self.encode(Some(level))
+    }
+
+    fn encode(&self, level: Option<i32>) -> Result<Vec<u8>, ModuleError> {
+        let (instructions, constants) = pool_literals(&self.instructions, &self.constants);
+
+        let mut out = Writer::default();
+        out.bytes.extend_from_slice(&CONTAINER_MAGIC);
+        out.bytes.extend_from_slice(&CONTAINER_FORMAT_VERSION.to_le_bytes());
+        // Filled in once the sections are written
+        out.bytes.extend_from_slice(&[0; DIGEST_LEN]);
+
+        let mut section = Writer::default();
+        section.u32(constants.len())?;
+        for constant in &constants {
+            section.value(constant, &mut Vec::new())?;
+        }
+        out.data_section(SECTION_CONSTANTS, section, level)?;
+
+        let code = encode_program(&instructions).map_err(|e| invalid(e.to_string()))?;
+        out.data_section(SECTION_CODE, Writer { bytes: code }, level)?;
+
+        let mut section = Writer::default();
+        section.u32(self.functions.len())?;
+        for function in &self.functions {
+            section.str(&function.name)?;
+            section.u32(function.address)?;
+            section.u32(function.arity)?;
+            section.u32(function.locals)?;
+            let mut flags = 0;
+            if function.returns.is_some() {
+                flags |= FUNCTION_RETURNS;
+            }
+            if function.exported {
+                flags |= FUNCTION_EXPORTED;
+            }
+            section.u8(flags);
+            if let Some(returns) = function.returns {
+                section.u32(returns)?;
+            }
+        }
+        out.section(SECTION_FUNCTIONS, section)?;
+
+        if !self.externs.is_empty() {
+            let mut section = Writer::default();
+            section.u32(self.externs.len())?;
+            for reference in &self.externs {
+                section.str(&reference.name)?;
+                section.u32(reference.address)?;
+            }
+            out.section(SECTION_EXTERNS, section)?;
+        }
+
+        if !self.globals.is_empty() {
+            let mut section = Writer::default();
+            section.u32(self.globals.len())?;
+            for (name, &index) in &self.globals {
+                section.str(name)?;
+                section.u32(index)?;
+            }
+            out.section(SECTION_GLOBALS, section)?;
+        }
+
+        self.write_debug_sections(&mut out)?;
+
+        let start = CONTAINER_HEADER_LEN + DIGEST_LEN;
+        let digest = Sha256::digest(&out.bytes[start..]);
+        out.bytes[CONTAINER_HEADER_LEN..start].copy_from_slice(&digest);
+        out.bytes.extend_from_slice(&END_SECTION);
+        Ok(out.bytes)
+    }
+
+    /// Writes the optional debug and files sections
+    fn write_debug_sections(&self, out: &mut Writer) -> Result<(), ModuleError> {
+        if !self.debug_info.lines.is_empty() || !self.symbols.is_empty() {
+            let mut section = Writer::default();
+            let entries = self.debug_info.lines.entries();
+            section.u32(entries.len())?;
+            for entry in entries {
+                section.u32(entry.address)?;
+                // Lines are 1-based, so 0 marks generated code
+                section.u32(entry.line.unwrap_or(0))?;
+            }
+            section.u32(self.symbols.labels.len())?;
+            for (name, &address) in &self.symbols.labels {
+                section.str(name)?;
+                section.u32(address)?;
+            }
+            section.u32(self.symbols.constants.len())?;
+            for (name, &index) in &self.symbols.constants {
+                section.str(name)?;
+                section.u32(index)?;
+            }
+            section.u32(self.symbols.functions.len())?;
+            for (name, range) in &self.symbols.functions {
+                section.str(name)?;
+                section.u32(range.start)?;
+                section.u32(range.end)?;
+            }
+            out.section(SECTION_DEBUG, section)?;
+        }
+
+        if !self.debug_info.files.is_empty() {
+            let mut section = Writer::default();
+            section.u32(self.debug_info.files.len())?;
+            for entry in &self.debug_info.files {
+                section.u32(entry.address)?;
+                match &entry.name {
+                    Some(name) => {
+                        section.u8(1);
+                        section.str(name)?;
+                    }
+                    None => section.u8(0),
+                }
+            }
+            out.section(SECTION_FILES, section)?;
+        }
+        Ok(())
+    }
+
+    /// Bytes the debug and files sections take in this module's container,
+    /// which [`BytecodeModule::strip_debug`] saves
+    pub(crate) fn debug_sections_len(&self) -> Result<usize, ModuleError> {
+        let mut out = Writer::default();
+        self.write_debug_sections(&mut out)?;
+        Ok(out.bytes.len())
+    }
+
+    /// Reads a module written by [`BytecodeModule::to_bytes`], checking
+    /// its content digest. Use a [`crate::vm::loader::ModuleLoader`] to
+    /// also check its signature. Bytes after the end section are an error.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ModuleError> {
+        let mut reader = Reader::new(bytes);
+        let header = reader
+            .array()
+            .map_err(|_| invalid("missing SVMB magic number"))?;
+        let version = check_header(&header)?;
+        let digest = reader
+            .take(digest_len(version))
+            .map_err(|_| invalid("missing content digest"))?;
+
+        let mut sections = SectionDecoder::new(version, digest);
+        while !reader.is_empty() && !sections.is_ended() {
+            let tag = reader.u8()?;
+            let len = reader.u32()?;
+            sections.push(tag, reader.take(len)?)?;
+        }
+        if !reader.is_empty() {
+            return Err(invalid("data after the end section"));
+        }
+        sections.finish(None)
+    }
+}
+
+/// Decompresses a compressed section's payload, which must come to `len`
+/// bytes; memory grows with the data actually inflated, not with `len`
+#[cfg(feature = "compression")]
+fn inflate(data: &[u8], len: usize) -> Result<Vec<u8>, ModuleError> {
+    use std::io::Read;
+
+    let mut payload = Vec::new();
+    zstd::stream::read::Decoder::new(data)
+        .and_then(|decoder| decoder.take(len as u64 + 1).read_to_end(&mut payload))
+        .map_err(|e| invalid(format!("corrupt compressed section: {}", e)))?;
+    if payload.len() != len {
+        return Err(invalid(format!(
+            "compressed section inflates to {} bytes, not {}",
+            payload.len(),
+            len
+        )));
+    }
+    Ok(payload)
+}
+
+#[cfg(not(feature = "compression"))]
+fn inflate(_: &[u8], _: usize) -> Result<Vec<u8>, ModuleError> {
+    Err(invalid("compressed sections need the `compression` feature"))
+}
+
+/// Bytes before the first section: the magic number and format version
+pub(crate) const CONTAINER_HEADER_LEN: usize = 6;
+
+/// The container's format version, if it is one this reader supports
+pub(crate) fn check_header(header: &[u8; CONTAINER_HEADER_LEN]) -> Result<u16, ModuleError> {
+    if header[..4] != CONTAINER_MAGIC {
+        return Err(invalid("missing SVMB magic number"));
+    }
+    let version = u16::from_le_bytes([header[4], header[5]]);
+    if !(MIN_CONTAINER_FORMAT_VERSION..=CONTAINER_FORMAT_VERSION).contains(&version) {
+        return Err(ModuleError::UnsupportedContainerVersion(version));
+    }
+    Ok(version)
+}
+
+/// Rewrites the retired opcodes in `code` written by format `version`,
+/// leaving malformed code for [`decode_program`] to report
+fn migrate_code<'a>(code: &'a [u8], version: u16, migrations: &[OpcodeMigration]) -> Cow<'a, [u8]> {
+    let migrations: Vec<&OpcodeMigration> =
+        migrations.iter().filter(|migration| version < migration.until).collect();
+    if migrations.is_empty() {
+        return Cow::Borrowed(code);
+    }
+
+    let mut code = code.to_vec();
+    let mut offset = 0;
+    while offset < code.len() {
+        let wide = code[offset] == Opcode::Wide as u8;
+        let at = offset + wide as usize;
+        let Some(byte) = code.get_mut(at) else { break };
+        // In order, so an opcode retired twice ends up as the latest one
+        for migration in &migrations {
+            if *byte == migration.from {
+                *byte = migration.to as u8;
+            }
+        }
+        let Some(opcode) = Opcode::from_u8(*byte) else { break };
+        offset = at + match (wide, opcode.has_index_operand()) {
+            (true, _) => 5,
+            (false, true) => 2,
+            (false, false) => 1,
+        };
+    }
+    Cow::Owned(code)
+}
+
+/// Length of the content digest following a header of `version`
+pub(crate) fn digest_len(version: u16) -> usize {
+    if version >= DIGEST_VERSION { DIGEST_LEN } else { 0 }
+}
+
+/// Builds a module from container sections as they are read, so each
+/// section can be checked and dropped before the next one arrives
+pub(crate) struct SectionDecoder {
+    /// Format version of the container being read
+    version: u16,
+    /// Largest payload a compressed section may inflate to
+    max_section_len: u64,
+    /// Digest from the header, which older containers do not have
+    expected_digest: Option<[u8; DIGEST_LEN]>,
+    hasher: Sha256,
+    signature: Option<Vec<u8>>,
+    /// Whether the end section has been read
+    ended: bool,
+    constants: Option<Vec<Value>>,
+    instructions: Option<Vec<Instruction>>,
+    functions: Vec<FunctionInfo>,
+    externs: Vec<ExternRef>,
+    globals: BTreeMap<String, usize>,
+    lines: LineTable,
+    files: Vec<FileEntry>,
+    symbols: SymbolTable,
+}
+
+impl SectionDecoder {
+    /// Decoder for a container of format `version` whose header carried
+    /// `digest`, which is empty for containers older than version 2
+    pub(crate) fn new(version: u16, digest: &[u8]) -> Self {
+        Self {
+            version,
+            max_section_len: DEFAULT_MAX_SECTION_LEN,
+            expected_digest: digest.try_into().ok(),
+            hasher: Sha256::new(),
+            signature: None,
+            ended: false,
+            constants: None,
+            instructions: None,
+            functions: Vec::new(),
+            externs: Vec::new(),
+            globals: BTreeMap::new(),
+            lines: LineTable::default(),
+            files: Vec::new(),
+            symbols: SymbolTable::default(),
+        }
+    }
+
+    pub(crate) fn with_max_section_len(mut self, limit: u64) -> Self {
+        self.max_section_len = limit;
+        self
+    }
+
+    /// Whether the container's end section has been read, after which
+    /// nothing more belongs to it
+    pub(crate) fn is_ended(&self) -> bool {
+        self.ended
+    }
+
+    /// Adds the next section as read from the container
+    pub(crate) fn push(&mut self, tag: u8, payload: &[u8]) -> Result<(), ModuleError> {
+        if self.ended {
+            return Err(invalid("section after the end section"));
+        }
+        if tag == SECTION_END && self.version >= END_VERSION {
+            if !payload.is_empty() {
+                return Err(invalid("end section is not empty"));
+            }
+            self.ended = true;
+            return Ok(());
+        }
+        if self.signature.is_some() {
+            return Err(invalid("signature section is not the last section"));
+        }
+        if tag == SECTION_SIGNATURE {
+            self.signature = Some(payload.to_vec());
+            return Ok(());
+        }
+        self.hasher.update([tag]);
+        self.hasher.update((payload.len() as u32).to_le_bytes());
+        self.hasher.update(payload);
+        self.decode(tag, payload)
+    }
+
+    fn decode(&mut self, tag: u8, payload: &[u8]) -> Result<(), ModuleError> {
+        let mut section = Reader::new(payload);
+        let duplicate = match tag {
+            SECTION_CONSTANTS => self.constants.is_some(),
+            SECTION_CODE => self.instructions.is_some(),
+            _ => false,
+        };
+        if duplicate {
+            return Err(invalid(format!("section {} appears twice", tag)));
+        }
+        match tag {
+            SECTION_CONSTANTS => {
+                let count = section.count(1)?;
+                let values = (0..count).map(|_| section.value());
+                self.constants = Some(values.collect::<Result<Vec<_>, _>>()?);
+            }
+            SECTION_CODE => {
+                let code = migrate_code(section.bytes, self.version, OPCODE_MIGRATIONS);
+                self.instructions =
+                    Some(decode_program(&code).map_err(|e| invalid(e.to_string()))?);
+                section.offset = section.bytes.len();
+            }
+            SECTION_FUNCTIONS => {
+                let count = section.count(17)?;
+                for _ in 0..count {
+                    let mut function = FunctionInfo::new(
+                        section.str()?,
+                        section.u32()?,
+                        section.u32()?,
+                        section.u32()?,
+                    );
+                    let flags = section.u8()?;
+                    if flags & FUNCTION_RETURNS != 0 {
+                        function = function.with_returns(section.u32()?);
+                    }
+                    if flags & FUNCTION_EXPORTED != 0 {
+                        function = function.exported();
+                    }
+                    self.functions.push(function);
+                }
+            }
+            SECTION_EXTERNS => {
+                let count = section.count(8)?;
+                for _ in 0..count {
+                    self.externs.push(ExternRef {
+                        name: section.str()?,
+                        address: section.u32()?,
+                    });
+                }
+            }
+            SECTION_GLOBALS => {
+                for _ in 0..section.count(8)? {
+                    let name = section.str()?;
+                    self.globals.insert(name, section.u32()?);
+                }
+            }
+            SECTION_DEBUG => {
+                let count = section.count(8)?;
+                let entries = (0..count)
+                    .map(|_| Ok((section.u32()?, section.u32()?)))
+                    .collect::<Result<Vec<_>, ModuleError>>()?;
+                if entries.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    return Err(invalid("line table is not in address order"));
+                }
+                self.lines = LineTable::from_entries(entries.into_iter().map(|(address, line)| {
+                    LineEntry { address, line: (line != 0).then_some(line) }
+                }));
+                for _ in 0..section.count(8)? {
+                    let name = section.str()?;
+                    self.symbols.labels.insert(name, section.u32()?);
+                }
+                for _ in 0..section.count(8)? {
+                    let name = section.str()?;
+                    self.symbols.constants.insert(name, section.u32()?);
+                }
+                for _ in 0..section.count(12)? {
+                    let name = section.str()?;
+                    let start = section.u32()?;
+                    self.symbols.functions.insert(name, start..section.u32()?);
+                }
+            }
+            SECTION_FILES => {
+                let count = section.count(5)?;
+                for _ in 0..count {
+                    let address = section.u32()?;
+                    let name = match section.u8()? {
+                        0 => None,
+                        _ => Some(section.str()?),
+                    };
+                    if self.files.last().is_some_and(|last| last.address >= address) {
+                        return Err(invalid("file table is not in address order"));
+                    }
+                    self.files.push(FileEntry { address, name });
+                }
+            }
+            SECTION_COMPRESSED => {
+                let inner = section.u8()?;
+                let len = section.u32()?;
+                if inner == SECTION_COMPRESSED {
+                    return Err(invalid("compressed section holds another compressed section"));
+                }
+                if len as u64 > self.max_section_len {
+                    return Err(ModuleError::TooLarge {
+                        what: format!("Section {}", inner),
+                        size: len as u64,
+                        limit: self.max_section_len,
+                    });
+                }
+                let payload = inflate(section.take(section.bytes.len() - section.offset)?, len)?;
+                self.decode(inner, &payload)?;
+            }
+            // Optional sections from newer writers
+            _ => section.offset = section.bytes.len(),
+        }
+        if !section.is_empty() {
+            let trailing = section.bytes.len() - section.offset;
+            return Err(invalid(format!("section {} has {} trailing bytes", tag, trailing)));
+        }
+        Ok(())
+    }
+
+    /// Checks the digest and, when given a `verifier`, the signature, then
+    /// builds the module
+    pub(crate) fn finish(
+        self,
+        verifier: Option<&dyn SignatureVerifier>,
+    ) -> Result<BytecodeModule, ModuleError> {
+        if self.version >= END_VERSION && !self.ended {
+            return Err(invalid("missing end section"));
+        }
+        let digest: [u8; DIGEST_LEN] = self.hasher.finalize().into();
+        if self.expected_digest.is_some_and(|expected| expected != digest) {
+            return Err(ModuleError::DigestMismatch);
+        }
+        if let Some(verifier) = verifier {
+            verifier
+                .verify(&digest, self.signature.as_deref())
+                .map_err(ModuleError::SignatureRejected)?;
+        }
+
+        let instructions = self.instructions.ok_or_else(|| invalid("missing code section"))?;
+        let constants = self.constants.ok_or_else(|| invalid("missing constants section"))?;
+        let mut module = BytecodeModule::new(instructions, constants)
+            .with_functions(self.functions)
+            .with_symbols(self.symbols)
+            .with_debug_info(DebugInfo { lines: self.lines, files: self.files })
+            .with_externs(self.externs)
+            .with_globals(self.globals);
+        module.dedup_constants();
+        Ok(module)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The instruction set has not retired any opcodes yet, so these use
+    // made-up migrations
+    const MIGRATIONS: &[OpcodeMigration] = &[
+        OpcodeMigration { until: 2, from: 0x07, to: Opcode::Negate },
+        OpcodeMigration { until: 3, from: 0x08, to: Opcode::Load },
+        OpcodeMigration { until: 3, from: Opcode::Negate as u8, to: Opcode::Sub },
+    ];
+
+    #[test]
+    fn test_migrate_code_rewrites_retired_opcodes() {
+        // 0x07, a wide 0x08 whose operand holds 0x07, then a narrow 0x08
+        let code = [0x07, Opcode::Wide as u8, 0x08, 0x07, 0, 0, 0, 0x08, 0x07, 0xFF];
+
+        // Negate was itself retired later, so 0x07 ends up as Sub
+        let migrated = migrate_code(&code, 1, MIGRATIONS);
+        assert_eq!(
+            migrated.as_ref(),
+            [Opcode::Sub as u8, Opcode::Wide as u8, Opcode::Load as u8, 0x07, 0, 0, 0, Opcode::Load as u8, 0x07, 0xFF]
+        );
+        let program = decode_program(&migrated).unwrap();
+        assert_eq!(program.len(), 4);
+        assert_eq!(program[1].operand(), Some(&Value::Integer(7)));
+
+        let code = [Opcode::Negate as u8, 0x08, 0x01, 0xFF];
+        assert_eq!(
+            migrate_code(&code, 2, MIGRATIONS).as_ref(),
+            [Opcode::Sub as u8, Opcode::Load as u8, 0x01, 0xFF]
+        );
+    }
+
+    #[test]
+    fn test_migrate_code_leaves_current_code_alone() {
+        let code = [0x07, 0xFF];
+        assert!(matches!(migrate_code(&code, 3, MIGRATIONS), Cow::Borrowed(_)));
+        assert!(matches!(migrate_code(&code, 1, OPCODE_MIGRATIONS), Cow::Borrowed(_)));
+        // 0x07 was already retired by version 2, so it is unknown there and
+        // the rest is left for the decoder to report
+        let code = [0x07, 0x08, 0x01];
+        assert_eq!(migrate_code(&code, 2, MIGRATIONS).as_ref(), code);
+    }
+}
@@ -0,0 +1,232 @@
+//! Which instructions of a program ran.
+//!
+//! A [`CoverageCollector`] counts the instructions VMs run through their
+//! hooks, over as many runs as it is attached to, and reports the
+//! functions and stretches of code that never ran.
+
+use crate::vm::assembler::mnemonic;
+use crate::vm::runtime::{HookEvent, VirtualMachine};
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Counts the instructions run by the VMs it is attached to, by address.
+/// Clones share the counts.
+#[derive(Debug, Clone, Default)]
+pub struct CoverageCollector {
+    counts: Arc<Mutex<Vec<u64>>>,
+}
+
+impl CoverageCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the instructions `vm` runs from now on, in place of any hook
+    /// it had. Every VM attached should have the same program loaded.
+    pub fn attach(&self, vm: &mut VirtualMachine) {
+        let counts = Arc::clone(&self.counts);
+        vm.set_hook(Box::new(move |event: &HookEvent| {
+            if let HookEvent::BeforeInstruction { pc, .. } = *event {
+                let mut counts = lock(&counts);
+                if counts.len() <= pc {
+                    counts.resize(pc + 1, 0);
+                }
+                counts[pc] += 1;
+            }
+        }));
+    }
+
+    /// Times the instruction at `pc` has run
+    pub fn count(&self, pc: usize) -> u64 {
+        lock(&self.counts).get(pc).copied().unwrap_or(0)
+    }
+
+    /// Forgets every count
+    pub fn clear(&self) {
+        lock(&self.counts).clear();
+    }
+
+    /// Coverage of the program loaded into `vm`, which names its functions
+    /// and source lines
+    pub fn report(&self, vm: &VirtualMachine) -> CoverageReport {
+        let length = vm.program_length();
+        let mut counts = lock(&self.counts).clone();
+        counts.resize(length, 0);
+
+        let mut starts: Vec<(usize, &str)> = vm
+            .functions()
+            .iter()
+            .map(|function| (function.address, function.name.as_str()))
+            .collect();
+        starts.sort();
+        if starts.first().is_none_or(|&(address, _)| address > 0) {
+            starts.insert(0, (0, TOP_LEVEL));
+        }
+        let functions: Vec<FunctionCoverage> = starts
+            .iter()
+            .enumerate()
+            .map(|(index, &(address, name))| {
+                let end = starts.get(index + 1).map_or(length, |&(next, _)| next).max(address);
+                let code = &counts[address.min(length)..end.min(length)];
+                FunctionCoverage {
+                    name: name.to_string(),
+                    address,
+                    instructions: code.len(),
+                    covered: code.iter().filter(|&&count| count > 0).count(),
+                }
+            })
+            .collect();
+
+        let mut uncovered = Vec::new();
+        let mut pc = 0;
+        while pc < length {
+            if counts[pc] > 0 {
+                pc += 1;
+                continue;
+            }
+            let start = pc;
+            // A region stays within one function
+            let function = functions.iter().rev().find(|function| function.address <= start);
+            let function_end = function.map_or(length, |function| function.address + function.instructions);
+            while pc < function_end && counts[pc] == 0 {
+                pc += 1;
+            }
+            let lines: Vec<usize> = (start..pc).filter_map(|pc| vm.source_line(pc)).collect();
+            uncovered.push(UncoveredRegion {
+                start,
+                end: pc - 1,
+                function: function.map(|function| function.name.clone()),
+                first_line: lines.iter().min().copied(),
+                last_line: lines.iter().max().copied(),
+                code: (start..pc).map(|pc| describe(vm, pc)).collect(),
+            });
+        }
+
+        let covered = counts.iter().filter(|&&count| count > 0).count();
+        CoverageReport {
+            instructions: length,
+            covered,
+            percent: percent(covered, length),
+            counts,
+            functions,
+            uncovered,
+        }
+    }
+}
+
+fn lock(counts: &Mutex<Vec<u64>>) -> MutexGuard<'_, Vec<u64>> {
+    counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Name the report gives code before the first function
+const TOP_LEVEL: &str = "(top level)";
+
+fn percent(covered: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 100.0;
+    }
+    covered as f64 / total as f64 * 100.0
+}
+
+/// The instruction at `pc` as assembly
+fn describe(vm: &VirtualMachine, pc: usize) -> String {
+    let Some(instruction) = vm.instruction_at(pc) else {
+        return String::new();
+    };
+    let opcode = instruction.opcode();
+    let name = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+    match instruction.operand() {
+        Some(operand) => format!("{} {}", name, operand),
+        None => name,
+    }
+}
+
+/// How much of a program ran, from [`CoverageCollector::report`]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CoverageReport {
+    /// Instructions in the program
+    pub instructions: usize,
+    /// Instructions that ran at least once
+    pub covered: usize,
+    pub percent: f64,
+    /// Times each instruction ran, by address
+    pub counts: Vec<u64>,
+    /// Coverage of each function, and of the code before the first one
+    pub functions: Vec<FunctionCoverage>,
+    /// Stretches of instructions that never ran, in address order
+    pub uncovered: Vec<UncoveredRegion>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FunctionCoverage {
+    pub name: String,
+    pub address: usize,
+    pub instructions: usize,
+    pub covered: usize,
+}
+
+/// Consecutive instructions of one function that never ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct UncoveredRegion {
+    /// Address of the first instruction
+    pub start: usize,
+    /// Address of the last instruction
+    pub end: usize,
+    pub function: Option<String>,
+    /// Source lines the region spans, if the program has line tables
+    pub first_line: Option<usize>,
+    pub last_line: Option<usize>,
+    /// The instructions, as assembly
+    pub code: Vec<String>,
+}
+
+/// The report as text: the totals, a line per function and a line per
+/// region that never ran
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Coverage: {} of {} instructions ({:.1}%)", self.covered, self.instructions, self.percent)?;
+        writeln!(f, "\nFunctions:")?;
+        for function in &self.functions {
+            writeln!(
+                f,
+                "  {:<20} {:>5}/{:<5} {:>5.1}%",
+                function.name,
+                function.covered,
+                function.instructions,
+                percent(function.covered, function.instructions)
+            )?;
+        }
+        if self.uncovered.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "\nNot executed:")?;
+        for region in &self.uncovered {
+            if region.start == region.end {
+                write!(f, "  pc {}", region.start)?;
+            } else {
+                write!(f, "  pc {}-{}", region.start, region.end)?;
+            }
+            match (region.first_line, region.last_line) {
+                (Some(first), Some(last)) if first != last => write!(f, " (lines {}-{})", first, last)?,
+                (Some(line), _) => write!(f, " (line {})", line)?,
+                _ => {}
+            }
+            if let Some(function) = region.function.as_deref().filter(|&name| name != TOP_LEVEL) {
+                write!(f, " in {}", function)?;
+            }
+            writeln!(f, ": {}", region.code.join("; "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl CoverageReport {
+    /// This report as a JSON object
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("coverage reports serialize to JSON")
+    }
+}
@@ -0,0 +1,284 @@
+use crate::vm::assembler::mnemonic;
+use crate::vm::history::ExecutionHistory;
+use crate::vm::repl::{describe_heap, describe_stack};
+use crate::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use std::io::{self, BufRead, Write};
+
+/// What a debugger command asked for
+#[derive(Debug, Clone, PartialEq)]
+pub enum DebuggerOutput {
+    /// Text answering the command
+    Message(String),
+    Quit,
+}
+
+const HELP: &str = "\
+Commands:
+  break TARGET    stop before TARGET: an address, label, function or :LINE
+  delete TARGET   remove the breakpoint at TARGET
+  breakpoints     list the breakpoints
+  step            run one instruction, going into calls
+  next            run one instruction, running calls through
+  finish          run until the current function returns
+  continue        run to the next breakpoint or the end
+  back            go back one instruction
+  rcontinue       go back to the last breakpoint passed, or the start
+  origin [SLOT]   go back to the instruction that put a stack slot's value
+                  there, the top by default
+  stack           show the operand stack, bottom first
+  locals          show the locals of the current function
+  heap            show what the heap holds
+  where           show the calls that led here
+  restart         go back to the start, keeping the breakpoints
+  help            show this help
+  quit            leave the debugger";
+
+/// Instructions between the snapshots going back replays from
+const SNAPSHOT_INTERVAL: u64 = 10_000;
+
+/// Runs a loaded program under the control of typed commands, built on
+/// [`VirtualMachine`]'s breakpoints and stepping methods. An
+/// [`ExecutionHistory`] of the run lets it go back as well as forward.
+pub struct Debugger {
+    vm: VirtualMachine,
+    history: ExecutionHistory,
+}
+
+impl Debugger {
+    /// Debugs the program loaded into `vm`, from where it is now, which is
+    /// as far back as it can go
+    pub fn new(mut vm: VirtualMachine) -> Self {
+        let history = ExecutionHistory::new(&mut vm, SNAPSHOT_INTERVAL);
+        Self { vm, history }
+    }
+
+    pub fn vm(&self) -> &VirtualMachine {
+        &self.vm
+    }
+
+    pub fn into_vm(self) -> VirtualMachine {
+        self.vm
+    }
+
+    /// Handles one command. Errors from the program are returned as they
+    /// are; the VM is left where the error happened, for `where` and
+    /// `stack` to show.
+    pub fn handle(&mut self, line: &str) -> Result<DebuggerOutput, VmError> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or("");
+        let argument = words.next();
+        let message = |text: String| Ok(DebuggerOutput::Message(text));
+        match (command, argument) {
+            ("quit" | "q", _) => Ok(DebuggerOutput::Quit),
+            ("help" | "h", _) => message(HELP.to_string()),
+            ("break" | "b", Some(target)) => match self.resolve(target) {
+                Some(pc) => {
+                    self.vm.add_breakpoint(pc);
+                    message(format!("Breakpoint at {}", self.describe(pc)))
+                }
+                None => message(format!("No address, label, function or line {}", target)),
+            },
+            ("delete" | "d", Some(target)) => match self.resolve(target) {
+                Some(pc) if self.vm.remove_breakpoint(pc) => {
+                    message(format!("Removed the breakpoint at pc {}", pc))
+                }
+                _ => message(format!("No breakpoint at {}", target)),
+            },
+            ("breakpoints", _) => {
+                let lines: Vec<String> = self.vm.breakpoints().map(|pc| self.describe(pc)).collect();
+                message(if lines.is_empty() { "No breakpoints".to_string() } else { lines.join("\n") })
+            }
+            ("step" | "s", _) => self.advance(VirtualMachine::step_into),
+            ("next" | "n", _) => self.advance(VirtualMachine::step_over),
+            ("finish" | "f", _) => self.advance(VirtualMachine::step_out),
+            ("continue" | "c", _) => {
+                // Leaving a breakpoint that stepping stopped on, rather than
+                // stopping at it again
+                let pc = self.vm.program_counter();
+                if self.vm.breakpoints().any(|breakpoint| breakpoint == pc) && !self.vm.is_halted() {
+                    self.vm.step()?;
+                }
+                if self.vm.is_halted() {
+                    return message(self.location());
+                }
+                // In stretches, to snapshot between them
+                loop {
+                    match self.vm.run_with_fuel(SNAPSHOT_INTERVAL)? {
+                        RunOutcome::Exhausted => self.history.record(&self.vm),
+                        RunOutcome::Paused => return Err(VmError::Paused),
+                        RunOutcome::Stopped(hit) => {
+                            self.history.record(&self.vm);
+                            let location = self.location();
+                            return message(format!("Stopped at a breakpoint (hit {})\n{}", hit.hits, location));
+                        }
+                        RunOutcome::Halted => {
+                            self.history.record(&self.vm);
+                            return message(self.location());
+                        }
+                    }
+                }
+            }
+            ("back", _) => {
+                let position = ExecutionHistory::position(&self.vm);
+                if position <= self.history.start() {
+                    return message(format!("Already at the start\n{}", self.location()));
+                }
+                self.history.go_to(&mut self.vm, position - 1)?;
+                message(self.location())
+            }
+            ("rcontinue" | "rc", _) => {
+                let breakpoints: Vec<usize> = self.vm.breakpoints().collect();
+                let found = self
+                    .history
+                    .find_back(&mut self.vm, |vm| breakpoints.contains(&vm.program_counter()))?;
+                if found.is_none() {
+                    let start = self.history.start();
+                    self.history.go_to(&mut self.vm, start)?;
+                    return message(format!("No breakpoint passed; back at the start\n{}", self.location()));
+                }
+                message(format!("Back at a breakpoint\n{}", self.location()))
+            }
+            ("origin", slot) => self.origin(slot),
+            ("stack", _) => message(describe_stack(&self.vm)),
+            ("locals", _) => message(self.describe_locals()),
+            ("heap", _) => message(describe_heap(&self.vm)),
+            ("where" | "bt", _) => {
+                let trace: Vec<String> = self.vm.stack_trace().iter().map(ToString::to_string).collect();
+                message(trace.join("\n"))
+            }
+            ("restart", _) => {
+                let start = self.history.start();
+                self.history.go_to(&mut self.vm, start)?;
+                message(self.location())
+            }
+            ("", _) => message(self.location()),
+            _ => message(format!("Unknown command {}; try help", line.trim())),
+        }
+    }
+
+    /// Reads commands from `input` until it ends or `quit`, writing a
+    /// prompt and each answer to `output`
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        writeln!(output, "{}", self.location())?;
+        let mut line = String::new();
+        loop {
+            write!(output, "(debug) ")?;
+            output.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match self.handle(&line) {
+                Ok(DebuggerOutput::Quit) => return Ok(()),
+                Ok(DebuggerOutput::Message(text)) => writeln!(output, "{}", text)?,
+                Err(e) => writeln!(output, "error: {:#}", e)?,
+            }
+        }
+    }
+
+    /// Steps with `step` unless the program has finished, then says where
+    /// it stopped
+    fn advance(
+        &mut self,
+        step: fn(&mut VirtualMachine) -> Result<(), VmError>,
+    ) -> Result<DebuggerOutput, VmError> {
+        if !self.vm.is_halted() {
+            step(&mut self.vm)?;
+        }
+        self.history.record(&self.vm);
+        Ok(DebuggerOutput::Message(self.location()))
+    }
+
+    /// Goes back to before the instruction that put the value in stack
+    /// `slot`, numbered as `stack` shows them: the last one before which
+    /// the slot was empty or held something else, or the current call had
+    /// not begun
+    fn origin(&mut self, slot: Option<&str>) -> Result<DebuggerOutput, VmError> {
+        let stack = self.vm.stack_contents();
+        let index = match slot {
+            Some(slot) => match slot.parse::<usize>() {
+                Ok(index) if index < stack.len() => index,
+                _ => return Ok(DebuggerOutput::Message(format!("No stack slot {}", slot))),
+            },
+            None if stack.is_empty() => return Ok(DebuggerOutput::Message("(empty stack)".to_string())),
+            None => stack.len() - 1,
+        };
+        let value = stack[index].clone();
+        let depth = self.vm.call_depth();
+        let now = ExecutionHistory::position(&self.vm);
+        let found = self.history.find_back(&mut self.vm, |vm| {
+            vm.call_depth() != depth || vm.stack_contents().get(index) != Some(&value)
+        })?;
+        let text = match found {
+            Some(count) => {
+                let back = now - count;
+                let unit = if back == 1 { "instruction" } else { "instructions" };
+                format!("{} was put there {} {} back, by\n{}", value, back, unit, self.location())
+            }
+            None => format!("{} was already there at the start", value),
+        };
+        Ok(DebuggerOutput::Message(text))
+    }
+
+    /// Address of a breakpoint target: an address, a label as the symbol
+    /// table names it (`.loop`, or `double.loop` inside `double`), the
+    /// start of a function, or the first instruction of `:LINE`
+    fn resolve(&self, target: &str) -> Option<usize> {
+        if let Some(line) = target.strip_prefix(':') {
+            let line = line.parse().ok()?;
+            return (0..self.vm.program_length()).find(|&pc| self.vm.source_line(pc) == Some(line));
+        }
+        if let Ok(pc) = target.parse::<usize>() {
+            return (pc < self.vm.program_length()).then_some(pc);
+        }
+        if let Some(&pc) = self.vm.symbols().labels.get(target) {
+            return Some(pc);
+        }
+        self.vm
+            .functions()
+            .iter()
+            .find(|function| function.name == target)
+            .map(|function| function.address)
+    }
+
+    /// Where the program is stopped, and the instruction it runs next
+    fn location(&self) -> String {
+        if self.vm.is_halted() {
+            return match self.vm.stack_top() {
+                Ok(value) => format!("Program halted with {} on the stack", value),
+                Err(_) => "Program halted".to_string(),
+            };
+        }
+        self.describe(self.vm.program_counter())
+    }
+
+    /// The instruction at `pc`, with its source location if known
+    fn describe(&self, pc: usize) -> String {
+        let mut text = format!("pc {}", pc);
+        if let Some(line) = self.vm.source_line(pc) {
+            match self.vm.source_file(pc) {
+                Some(file) => text.push_str(&format!(" ({}:{})", file, line)),
+                None => text.push_str(&format!(" (line {})", line)),
+            }
+        }
+        let Some(instruction) = self.vm.instruction_at(pc) else {
+            return text;
+        };
+        let opcode = instruction.opcode();
+        let name = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+        match instruction.operand() {
+            Some(operand) => format!("{}: {} {}", text, name, operand),
+            None => format!("{}: {}", text, name),
+        }
+    }
+
+    fn describe_locals(&self) -> String {
+        let locals: Vec<String> = (0..)
+            .map_while(|index| self.vm.local(index).map(|value| format!("{:4}: {}", index, value)))
+            .collect();
+        if locals.is_empty() {
+            return "No locals".to_string();
+        }
+        locals.join("\n")
+    }
+}
@@ -0,0 +1,276 @@
+//! Random programs for fuzzing the loader, verifier and interpreter.
+//!
+//! With the `fuzz` feature, [`Opcode`], [`Value`] and [`Instruction`]
+//! implement [`Arbitrary`], and [`FuzzModule`] builds modules from fuzzer
+//! input that mostly pass the verifier, so runs get past loading. The
+//! `run_*` functions are what the cargo-fuzz targets under `fuzz/` call:
+//! whatever the input, they must return an error rather than panic.
+
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::io::MemoryIo;
+use crate::vm::module::{BytecodeModule, FunctionInfo};
+use crate::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use crate::vm::types::{Decimal, MAX_TUPLE_LEN, Value};
+use crate::vm::verifier::verify_module;
+use arbitrary::{Arbitrary, Result, Unstructured};
+use std::sync::LazyLock;
+
+/// Every opcode, `Wide` included
+static OPCODES: LazyLock<Vec<Opcode>> = LazyLock::new(|| Opcode::all().collect());
+
+/// Instructions a fuzzed program may run before it is stopped
+pub const FUZZ_FUEL: u64 = 10_000;
+
+const MAX_INSTRUCTIONS: usize = 64;
+const MAX_CONSTANTS: usize = 8;
+const MAX_FUNCTIONS: usize = 4;
+/// Local slots generated `Load`s and `Store`s use
+const LOCALS: usize = 4;
+
+impl<'a> Arbitrary<'a> for Opcode {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        u.choose(&OPCODES).copied()
+    }
+}
+
+/// Values a module's constants and operands can hold; the heap-allocated
+/// kinds only exist at run time
+impl<'a> Arbitrary<'a> for Value {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(match u.int_in_range(0..=7)? {
+            0 => Value::Integer(i64::arbitrary(u)?),
+            1 => Value::Float(f64::arbitrary(u)?),
+            2 => Value::Decimal(
+                Decimal::from_integer(i32::arbitrary(u)?.into())
+                    .expect("32-bit integers fit a decimal"),
+            ),
+            3 => Value::Boolean(bool::arbitrary(u)?),
+            4 => Value::Char(char::arbitrary(u)?),
+            5 => Value::String(String::arbitrary(u)?.into()),
+            6 => Value::Function {
+                address: u32::arbitrary(u)?,
+                arity: u32::arbitrary(u)?,
+            },
+            _ => Value::Null,
+        })
+    }
+}
+
+impl<'a> Arbitrary<'a> for Instruction {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        Ok(Instruction::new(
+            Opcode::arbitrary(u)?,
+            Option::<Value>::arbitrary(u)?,
+        ))
+    }
+}
+
+/// A module built from fuzzer input so that it usually loads: operands
+/// have the types and ranges the verifier wants, no instruction pops more
+/// than the stack holds, jumps only go back to where the stack was as
+/// deep, and functions start where it was empty. Calls leave the depth
+/// unknown to the verifier, after which anything goes.
+#[derive(Debug, Clone)]
+pub struct FuzzModule {
+    pub instructions: Vec<Instruction>,
+    pub constants: Vec<Value>,
+    pub functions: Vec<FunctionInfo>,
+}
+
+impl<'a> Arbitrary<'a> for FuzzModule {
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let constant_count = u.int_in_range(0..=MAX_CONSTANTS)?;
+        let mut constants = Vec::with_capacity(constant_count);
+        for _ in 0..constant_count {
+            constants.push(Value::arbitrary(u)?);
+        }
+        let names: Vec<usize> = (0..constants.len())
+            .filter(|&index| matches!(constants[index], Value::String(_)))
+            .collect();
+
+        let length = u.int_in_range(1..=MAX_INSTRUCTIONS)?;
+        let mut instructions = Vec::with_capacity(length + 1);
+        // Stack depth on entry to each instruction, while it is known
+        let mut depths: Vec<Option<usize>> = Vec::with_capacity(length + 1);
+        let mut depth = Some(0);
+        while instructions.len() < length && !u.is_empty() {
+            let pc = instructions.len();
+            let opcode = Opcode::arbitrary(u)?;
+            let Some(instruction) = generate(u, opcode, pc, depth, &depths, &constants, &names)?
+            else {
+                continue;
+            };
+            depths.push(depth);
+            depth = match (depth, instruction.opcode()) {
+                (_, Opcode::Call | Opcode::CallIndirect | Opcode::CallNative) => None,
+                (Some(depth), _) => instruction
+                    .stack_effect()
+                    .map(|(popped, pushed)| depth - popped + pushed)
+                    .or(Some(depth)),
+                (None, _) => None,
+            };
+            // Code after an unconditional transfer could only be reached by
+            // a forward jump, which the generator never makes
+            let ends = matches!(
+                instruction.opcode(),
+                Opcode::Jump | Opcode::Return | Opcode::Halt
+            );
+            instructions.push(instruction);
+            if ends {
+                break;
+            }
+        }
+        if !matches!(
+            instructions.last().map(Instruction::opcode),
+            Some(Opcode::Jump | Opcode::Return | Opcode::Halt)
+        ) {
+            depths.push(depth);
+            instructions.push(Instruction::new(Opcode::Halt, None));
+        }
+
+        let starts: Vec<usize> = (1..instructions.len())
+            .filter(|&pc| depths[pc] == Some(0))
+            .collect();
+        let mut functions = Vec::new();
+        for index in 0..u.int_in_range(0..=MAX_FUNCTIONS)? {
+            let Some(&address) = u.choose(&starts).ok() else {
+                break;
+            };
+            if functions
+                .iter()
+                .any(|function: &FunctionInfo| function.address == address)
+            {
+                continue;
+            }
+            let arity = u.int_in_range(0..=2)?;
+            let locals = arity + u.int_in_range(0..=LOCALS)?;
+            functions.push(FunctionInfo::new(
+                format!("f{}", index),
+                address,
+                arity,
+                locals,
+            ));
+        }
+
+        Ok(FuzzModule {
+            instructions,
+            constants,
+            functions,
+        })
+    }
+}
+
+/// An instruction for `opcode` at `pc`, or `None` if it cannot go there
+fn generate(
+    u: &mut Unstructured<'_>,
+    opcode: Opcode,
+    pc: usize,
+    depth: Option<usize>,
+    depths: &[Option<usize>],
+    constants: &[Value],
+    names: &[usize],
+) -> Result<Option<Instruction>> {
+    let index = |value: usize| Some(Value::Integer(value as i64));
+    let operand = match opcode {
+        Opcode::Wide => return Ok(None),
+        Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+            let after = match (depth, opcode) {
+                (Some(0), Opcode::JumpIfTrue | Opcode::JumpIfFalse) => return Ok(None),
+                (Some(depth), Opcode::JumpIfTrue | Opcode::JumpIfFalse) => Some(depth - 1),
+                (depth, _) => depth,
+            };
+            let targets: Vec<usize> = (0..pc)
+                .filter(|&target| depth.is_none() || depths[target] == after)
+                .collect();
+            match u.choose(&targets) {
+                Ok(&target) => index(target),
+                Err(_) => return Ok(None),
+            }
+        }
+        Opcode::Call => index(u.choose_index(pc + 1)?),
+        Opcode::LoadConstant if constants.is_empty() => return Ok(None),
+        Opcode::LoadConstant => index(u.choose_index(constants.len())?),
+        Opcode::LoadGlobal | Opcode::StoreGlobal => match u.choose(names) {
+            Ok(&name) => index(name),
+            Err(_) => return Ok(None),
+        },
+        Opcode::Push if !constants.is_empty() => index(u.choose_index(constants.len())?),
+        Opcode::Push => Some(Value::arbitrary(u)?),
+        Opcode::Load | Opcode::Store => index(u.choose_index(LOCALS)?),
+        Opcode::TupleGet => index(u.choose_index(MAX_TUPLE_LEN)?),
+        Opcode::CallNative => index(u.choose_index(4)?),
+        Opcode::GetField | Opcode::SafeGetField | Opcode::SetField => {
+            if bool::arbitrary(u)? {
+                index(u.choose_index(4)?)
+            } else {
+                Some(Value::String(String::arbitrary(u)?.into()))
+            }
+        }
+        Opcode::NewArray | Opcode::MakeTuple => {
+            let available = depth.unwrap_or(4).min(MAX_TUPLE_LEN);
+            index(u.int_in_range(0..=available)?)
+        }
+        _ => None,
+    };
+    let instruction = Instruction::new(opcode, operand);
+    if let (Some(depth), Some((popped, _))) = (depth, instruction.stack_effect())
+        && popped > depth
+    {
+        return Ok(None);
+    }
+    Ok(Some(instruction))
+}
+
+impl FuzzModule {
+    pub fn into_module(self) -> BytecodeModule {
+        BytecodeModule::new(self.instructions, self.constants).with_functions(self.functions)
+    }
+}
+
+/// A VM with small limits and in-memory streams, so a fuzzed program can
+/// neither run for long nor write to the terminal
+pub fn fuzz_vm() -> VirtualMachine {
+    VirtualMachine::builder()
+        .with_max_instructions(FUZZ_FUEL)
+        .with_stack_capacity(1024)
+        .with_max_call_depth(64)
+        .with_heap_limit(1 << 20)
+        .with_memory_limit(1 << 20)
+        .with_io(MemoryIo::new())
+        .build()
+}
+
+/// Loads `module` into a [`fuzz_vm`] and runs it until it ends or runs
+/// out of fuel. It loads through [`VirtualMachine::load_module_unverified`],
+/// so the interpreter's own checks are all that stand between it and a
+/// bad program.
+pub fn run_module(module: BytecodeModule) -> std::result::Result<RunOutcome, VmError> {
+    let mut vm = fuzz_vm();
+    vm.load_module_unverified(module)?;
+    vm.run_with_fuel(FUZZ_FUEL)
+}
+
+/// Loads `instructions` and `constants` into a [`fuzz_vm`] through
+/// [`VirtualMachine::load_bytecode_module`], which verifies them, and runs
+/// them if they pass
+pub fn run_verified(
+    instructions: Vec<Instruction>,
+    constants: Vec<Value>,
+) -> std::result::Result<RunOutcome, VmError> {
+    let mut vm = fuzz_vm();
+    vm.load_bytecode_module(instructions, constants)?;
+    vm.run_with_fuel(FUZZ_FUEL)
+}
+
+/// Whether `module` passes the verifier, checking that verifying it does
+/// not panic either
+pub fn verifies(module: &BytecodeModule) -> bool {
+    verify_module(module).is_ok()
+}
+
+/// Decodes `bytes` as a bytecode container and runs the module in it.
+/// `None` if the bytes are not one.
+pub fn run_bytes(bytes: &[u8]) -> Option<std::result::Result<RunOutcome, VmError>> {
+    let module = BytecodeModule::from_bytes(bytes).ok()?;
+    Some(run_module(module))
+}
@@ -1,8 +1,10 @@
-use crate::vm::types::Value;
-use std::collections::HashMap;
+use crate::vm::types::{Value, VmString};
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::mem;
 use std::ops::Deref;
-use std::sync::{Arc, Weak};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
 
 #[derive(Debug)]
 pub enum HeapError {
@@ -23,23 +25,95 @@ impl fmt::Display for HeapError {
 
 impl std::error::Error for HeapError {}
 
-/// Garbage-collected pointer to heap-allocated objects
-#[derive(Debug, Clone, PartialEq)]
-pub struct GcPtr<T> {
-    inner: Arc<T>,
+/// Object ids are unique across every heap in the process, so identity
+/// comparisons stay meaningful when values from different VMs meet.
+static NEXT_OBJECT_ID: AtomicUsize = AtomicUsize::new(1);
+
+fn next_object_id() -> usize {
+    NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Heap allocation header shared by all pointers to one object
+#[derive(Debug)]
+struct GcBox<T> {
     object_id: usize,
+    charge: Option<HeapCharge>,
+    value: T,
+}
+
+/// Bytes an object or string counts against its heap's live size, given
+/// back when the last pointer to it is dropped
+#[derive(Debug)]
+pub(crate) struct HeapCharge {
+    size: AtomicUsize,
+    live_bytes: Arc<AtomicUsize>,
+}
+
+impl HeapCharge {
+    /// Counts `bytes` more, for an object that has grown
+    fn grow(&self, bytes: usize) {
+        self.size.fetch_add(bytes, Ordering::Relaxed);
+        self.live_bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Drop for HeapCharge {
+    fn drop(&mut self) {
+        self.live_bytes.fetch_sub(*self.size.get_mut(), Ordering::Relaxed);
+    }
+}
+
+/// Garbage-collected pointer to heap-allocated objects.
+///
+/// The object id lives in the allocation rather than the pointer, so a
+/// `GcPtr` is a single machine word and keeps `Value` compact.
+#[derive(Debug)]
+pub struct GcPtr<T> {
+    inner: Arc<GcBox<T>>,
+}
+
+impl<T> Clone for GcPtr<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+/// Pointers compare by the contents they reference
+impl<T: PartialEq> PartialEq for GcPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner) || self.inner.value == other.inner.value
+    }
 }
 
 impl<T> GcPtr<T> {
-    fn new(value: T, object_id: usize) -> Self {
+    fn new(value: T, object_id: usize, charge: Option<HeapCharge>) -> Self {
         Self {
-            inner: Arc::new(value),
-            object_id,
+            inner: Arc::new(GcBox { object_id, charge, value }),
         }
     }
     
     pub fn object_id(&self) -> usize {
-        self.object_id
+        self.inner.object_id
+    }
+    
+    /// Counts `bytes` more against the heap the object came from, if any
+    fn grow(&self, bytes: usize) {
+        if let Some(charge) = &self.inner.charge {
+            charge.grow(bytes);
+        }
+    }
+
+    /// The object, if this is the only pointer to it
+    fn get_mut(&mut self) -> Option<&mut T> {
+        Arc::get_mut(&mut self.inner).map(|inner| &mut inner.value)
+    }
+
+    /// Allocate outside of any `Heap`, e.g. when deserializing values. The
+    /// object gets a fresh id but is not counted in heap statistics.
+    pub fn detached(value: T) -> Self {
+        Self::new(value, next_object_id(), None)
     }
 }
 
@@ -47,39 +121,229 @@ impl<T> Deref for GcPtr<T> {
     type Target = T;
     
     fn deref(&self) -> &Self::Target {
-        &self.inner
+        &self.inner.value
     }
 }
 
 impl GcPtr<String> {
     pub fn as_str(&self) -> &str {
-        &self.inner
+        &self.inner.value
+    }
+}
+
+impl GcPtr<Object> {
+    /// [`Object::set_field`], counting a new field against the heap the
+    /// object came from
+    pub fn set_field(&self, name: impl AsRef<str> + Into<String>, value: Value) -> bool {
+        let object = &self.inner.value;
+        let (count, capacity) = object.field_storage();
+        let new_name = name.as_ref().len();
+        if !object.set_field(name, value) {
+            return false;
+        }
+        let (grown_count, grown_capacity) = object.field_storage();
+        if grown_count > count {
+            self.grow(new_name + grown_capacity.saturating_sub(capacity) * mem::size_of::<(String, Value)>());
+        }
+        true
     }
 }
 
-/// Object with dynamic fields
-#[derive(Debug, Clone, PartialEq)]
+impl GcPtr<Array> {
+    /// [`Array::push`], counting any room the array grows by against the
+    /// heap it came from
+    pub fn push(&self, value: Value) {
+        let array = &self.inner.value;
+        let capacity = array.read().capacity();
+        array.push(value);
+        let grown = array.read().capacity().saturating_sub(capacity);
+        self.grow(grown * mem::size_of::<Value>());
+    }
+}
+
+/// String storage that a `GcStr` view can point into
+#[derive(Debug, Clone)]
+pub enum StrSource {
+    Plain(VmString),
+    Gc(GcPtr<String>),
+}
+
+impl StrSource {
+    fn as_str(&self) -> &str {
+        match self {
+            StrSource::Plain(s) => s.as_str(),
+            StrSource::Gc(s) => s.as_str(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct StrView {
+    source: StrSource,
+    start: usize,
+    end: usize,
+}
+
+/// Zero-copy view of part of a string. Slicing shares the source buffer
+/// instead of copying the characters; `start..end` are byte offsets that
+/// always fall on character boundaries.
+#[derive(Debug, Clone)]
+pub struct GcStr {
+    view: Arc<StrView>,
+}
+
+impl GcStr {
+    /// View `start..end` (byte offsets) of `source`, or None if the range is
+    /// out of bounds or splits a character
+    pub fn new(source: StrSource, start: usize, end: usize) -> Option<Self> {
+        source.as_str().get(start..end)?;
+        Some(Self {
+            view: Arc::new(StrView { source, start, end }),
+        })
+    }
+
+    /// View of this slice's `start..end` (byte offsets relative to the slice)
+    pub fn slice(&self, start: usize, end: usize) -> Option<Self> {
+        self.as_str().get(start..end)?;
+        Some(Self {
+            view: Arc::new(StrView {
+                source: self.view.source.clone(),
+                start: self.view.start + start,
+                end: self.view.start + end,
+            }),
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.view.source.as_str()[self.view.start..self.view.end]
+    }
+
+    /// Whether both slices view the same source buffer
+    pub fn shares_source(a: &GcStr, b: &GcStr) -> bool {
+        match (&a.view.source, &b.view.source) {
+            (StrSource::Plain(x), StrSource::Plain(y)) => VmString::ptr_eq(x, y),
+            (StrSource::Gc(x), StrSource::Gc(y)) => x.object_id() == y.object_id(),
+            _ => false,
+        }
+    }
+}
+
+impl Deref for GcStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl PartialEq for GcStr {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+/// Object with dynamic fields. Fields live behind a lock so objects can be
+/// mutated through a shared `GcPtr`.
+///
+/// A frozen object rejects further writes. Its fields can never change
+/// again, so reads from it may be treated as constants.
+#[derive(Debug)]
 pub struct Object {
-    fields: HashMap<String, Value>,
+    fields: RwLock<HashMap<String, Value>>,
+    frozen: AtomicBool,
 }
 
 impl Object {
     pub fn new() -> Self {
         Self {
-            fields: HashMap::new(),
+            fields: RwLock::new(HashMap::new()),
+            frozen: AtomicBool::new(false),
         }
     }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, Value>> {
+        self.fields.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, Value>> {
+        self.fields.write().unwrap_or_else(|e| e.into_inner())
+    }
     
-    pub fn set_field(&mut self, name: String, value: Value) {
-        self.fields.insert(name, value);
+    /// Set a field, returning false if the object is frozen. The name is
+    /// only copied for a field the object does not have yet.
+    pub fn set_field(&self, name: impl AsRef<str> + Into<String>, value: Value) -> bool {
+        if self.is_frozen() {
+            return false;
+        }
+        let mut fields = self.write();
+        match fields.get_mut(name.as_ref()) {
+            Some(field) => *field = value,
+            None => {
+                fields.insert(name.into(), value);
+            }
+        }
+        true
     }
     
-    pub fn get_field(&self, name: &str) -> Option<&Value> {
-        self.fields.get(name)
+    pub fn get_field(&self, name: &str) -> Option<Value> {
+        self.read().get(name).cloned()
     }
     
     pub fn field_count(&self) -> usize {
-        self.fields.len()
+        self.read().len()
+    }
+
+    /// Fields held and room for them, as the heap counts an object's size
+    fn field_storage(&self) -> (usize, usize) {
+        let fields = self.read();
+        (fields.len(), fields.capacity())
+    }
+
+    /// Bytes the fields' storage and names take, as the heap counts them
+    fn storage_size(&self) -> usize {
+        let fields = self.read();
+        fields.capacity() * mem::size_of::<(String, Value)>()
+            + fields.keys().map(String::len).sum::<usize>()
+    }
+    
+    /// Snapshot of the fields, in no particular order
+    pub fn fields(&self) -> Vec<(String, Value)> {
+        self.read()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Make the object permanently read-only
+    pub fn freeze(&self) {
+        self.frozen.store(true, Ordering::Release);
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.frozen.load(Ordering::Acquire)
+    }
+}
+
+/// Clones are unfrozen copies of the fields
+impl Clone for Object {
+    fn clone(&self) -> Self {
+        Self {
+            fields: RwLock::new(self.read().clone()),
+            frozen: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Drop for Object {
+    fn drop(&mut self) {
+        let fields = self.fields.get_mut().unwrap_or_else(PoisonError::into_inner);
+        drop_values(fields.drain().map(|(_, value)| value).collect());
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other) || *self.read() == *other.read()
     }
 }
 
@@ -89,18 +353,262 @@ impl Default for Object {
     }
 }
 
+/// Growable array of values. Elements live behind a lock so arrays can be
+/// mutated through a shared `GcPtr`.
+#[derive(Debug, Default)]
+pub struct Array {
+    elements: RwLock<Vec<Value>>,
+}
+
+impl Array {
+    pub fn new(elements: Vec<Value>) -> Self {
+        Self {
+            elements: RwLock::new(elements),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<Value>> {
+        self.elements.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Vec<Value>> {
+        self.elements.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<Value> {
+        self.read().get(index).cloned()
+    }
+
+    /// Replace the element at `index`, returning false if it is out of bounds
+    pub fn set(&self, index: usize, value: Value) -> bool {
+        match self.write().get_mut(index) {
+            Some(slot) => {
+                *slot = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn push(&self, value: Value) {
+        self.write().push(value);
+    }
+
+    pub fn to_vec(&self) -> Vec<Value> {
+        self.read().clone()
+    }
+
+    /// Sort the elements in place using `Value::total_cmp`
+    pub fn sort(&self) {
+        self.write().sort_by(|a, b| a.total_cmp(b));
+    }
+}
+
+impl Clone for Array {
+    fn clone(&self) -> Self {
+        Self::new(self.to_vec())
+    }
+}
+
+impl Drop for Array {
+    fn drop(&mut self) {
+        drop_values(mem::take(self.elements.get_mut().unwrap_or_else(PoisonError::into_inner)));
+    }
+}
+
+impl PartialEq for Array {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other) || *self.read() == *other.read()
+    }
+}
+
+/// Drops `values` and whatever only they keep alive one value at a time,
+/// rather than recursively, so a guest program that builds deeply nested
+/// arrays, objects or tuples cannot overflow the host's stack when they
+/// are freed
+pub(crate) fn drop_values(mut values: Vec<Value>) {
+    while let Some(mut value) = values.pop() {
+        match &mut value {
+            Value::Array(array) => {
+                if let Some(array) = array.get_mut() {
+                    values.append(array.elements.get_mut().unwrap_or_else(PoisonError::into_inner));
+                }
+            }
+            Value::GcObject(object) => {
+                if let Some(object) = object.get_mut() {
+                    let fields = object.fields.get_mut().unwrap_or_else(PoisonError::into_inner);
+                    values.extend(fields.drain().map(|(_, value)| value));
+                }
+            }
+            Value::Tuple(tuple) => {
+                if let Some(elements) = tuple.take_unique() {
+                    values.extend(elements);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Mutable byte buffer for binary data
+#[derive(Debug, Default)]
+pub struct ByteBuffer {
+    bytes: RwLock<Vec<u8>>,
+}
+
+impl ByteBuffer {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self {
+            bytes: RwLock::new(bytes),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<u8>> {
+        self.bytes.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Vec<u8>> {
+        self.bytes.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<u8> {
+        self.read().get(index).copied()
+    }
+
+    /// Replace the byte at `index`, returning false if it is out of bounds
+    pub fn set(&self, index: usize, byte: u8) -> bool {
+        match self.write().get_mut(index) {
+            Some(slot) => {
+                *slot = byte;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Copy of the bytes in `start..end`, or None if the range is invalid
+    pub fn slice(&self, start: usize, end: usize) -> Option<Vec<u8>> {
+        self.read().get(start..end).map(|bytes| bytes.to_vec())
+    }
+
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.read().clone()
+    }
+}
+
+impl Clone for ByteBuffer {
+    fn clone(&self) -> Self {
+        Self::new(self.to_vec())
+    }
+}
+
+impl PartialEq for ByteBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other) || *self.read() == *other.read()
+    }
+}
+
+/// Homogeneous array of raw numbers, stored unboxed so numeric kernels
+/// walk a contiguous `Vec<T>` instead of tagged `Value`s
+#[derive(Debug, Default)]
+pub struct TypedArray<T> {
+    elements: RwLock<Vec<T>>,
+}
+
+pub type IntArray = TypedArray<i64>;
+pub type FloatArray = TypedArray<f64>;
+
+impl<T: Copy> TypedArray<T> {
+    pub fn new(elements: Vec<T>) -> Self {
+        Self {
+            elements: RwLock::new(elements),
+        }
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, Vec<T>> {
+        self.elements.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Vec<T>> {
+        self.elements.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.read().get(index).copied()
+    }
+
+    /// Replace the element at `index`, returning false if it is out of bounds
+    pub fn set(&self, index: usize, element: T) -> bool {
+        match self.write().get_mut(index) {
+            Some(slot) => {
+                *slot = element;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Run `f` over the elements in place, without copying them out
+    pub fn with_slice<R>(&self, f: impl FnOnce(&[T]) -> R) -> R {
+        f(&self.read())
+    }
+
+    /// Run `f` over the elements mutably, in place
+    pub fn with_slice_mut<R>(&self, f: impl FnOnce(&mut [T]) -> R) -> R {
+        f(&mut self.write())
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.read().clone()
+    }
+}
+
+impl<T: Copy> Clone for TypedArray<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.to_vec())
+    }
+}
+
+impl<T: Copy + PartialEq> PartialEq for TypedArray<T> {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other) || *self.read() == *other.read()
+    }
+}
+
 /// Weak reference to a garbage-collected object
 #[derive(Debug)]
 pub struct WeakRef<T> {
-    inner: Weak<T>,
-    object_id: usize,
+    inner: Weak<GcBox<T>>,
 }
 
 impl<T> WeakRef<T> {
     fn new(gc_ptr: &GcPtr<T>) -> Self {
         Self {
             inner: Arc::downgrade(&gc_ptr.inner),
-            object_id: gc_ptr.object_id,
         }
     }
     
@@ -109,10 +617,71 @@ impl<T> WeakRef<T> {
     }
     
     pub fn upgrade(&self) -> Option<GcPtr<T>> {
-        self.inner.upgrade().map(|arc| GcPtr {
-            inner: arc,
-            object_id: self.object_id,
-        })
+        self.inner.upgrade().map(|inner| GcPtr { inner })
+    }
+}
+
+/// Contents of a mutable heap object at one moment, kept with the object
+/// so they can be written back to it
+pub(crate) enum ObjectContents {
+    Object(GcPtr<Object>, HashMap<String, Value>, bool),
+    Array(GcPtr<Array>, Vec<Value>),
+    Bytes(GcPtr<ByteBuffer>, Vec<u8>),
+    IntArray(GcPtr<IntArray>, Vec<i64>),
+    FloatArray(GcPtr<FloatArray>, Vec<f64>),
+}
+
+impl ObjectContents {
+    /// Contents of every mutable object reachable from `roots`, each
+    /// object once however many paths lead to it
+    pub(crate) fn capture<'a>(roots: impl IntoIterator<Item = &'a Value>) -> Vec<Self> {
+        let mut seen = HashSet::new();
+        let mut pending: Vec<Value> = roots.into_iter().cloned().collect();
+        let mut saved = Vec::new();
+        while let Some(value) = pending.pop() {
+            match value {
+                Value::GcObject(object) if seen.insert(object.object_id()) => {
+                    let fields = object.read().clone();
+                    pending.extend(fields.values().cloned());
+                    let frozen = object.is_frozen();
+                    saved.push(ObjectContents::Object(object, fields, frozen));
+                }
+                Value::Array(array) if seen.insert(array.object_id()) => {
+                    let elements = array.to_vec();
+                    pending.extend(elements.iter().cloned());
+                    saved.push(ObjectContents::Array(array, elements));
+                }
+                Value::Bytes(bytes) if seen.insert(bytes.object_id()) => {
+                    let contents = bytes.to_vec();
+                    saved.push(ObjectContents::Bytes(bytes, contents));
+                }
+                Value::IntArray(array) if seen.insert(array.object_id()) => {
+                    let elements = array.to_vec();
+                    saved.push(ObjectContents::IntArray(array, elements));
+                }
+                Value::FloatArray(array) if seen.insert(array.object_id()) => {
+                    let elements = array.to_vec();
+                    saved.push(ObjectContents::FloatArray(array, elements));
+                }
+                Value::Tuple(tuple) => pending.extend(tuple.iter().cloned()),
+                _ => {}
+            }
+        }
+        saved
+    }
+
+    /// Puts the saved contents back, unfreezing an object frozen since
+    pub(crate) fn restore(&self) {
+        match self {
+            ObjectContents::Object(object, fields, frozen) => {
+                *object.write() = fields.clone();
+                object.frozen.store(*frozen, Ordering::Release);
+            }
+            ObjectContents::Array(array, elements) => *array.write() = elements.clone(),
+            ObjectContents::Bytes(bytes, contents) => *bytes.write() = contents.clone(),
+            ObjectContents::IntArray(array, elements) => *array.write() = elements.clone(),
+            ObjectContents::FloatArray(array, elements) => *array.write() = elements.clone(),
+        }
     }
 }
 
@@ -123,47 +692,56 @@ pub struct AllocationStats {
     pub bytes_allocated: usize,
     pub string_allocations: usize,
     pub object_allocations: usize,
+    pub array_allocations: usize,
+    pub bytes_allocations: usize,
+    pub typed_array_allocations: usize,
 }
 
 /// Garbage-collected heap
+#[derive(Clone)]
 pub struct Heap {
-    next_object_id: usize,
     allocated_objects: usize,
     total_allocated_bytes: usize,
     max_heap_size: Option<usize>,
-    current_heap_size: usize,
+    /// Bytes held by objects allocated here that are still alive, shared
+    /// with the objects so dropping one gives its bytes back
+    live_bytes: Arc<AtomicUsize>,
     young_generation_count: usize,
     old_generation_count: usize,
     allocation_tracking: bool,
     allocation_stats: AllocationStats,
+    collections: usize,
+    collected_objects: usize,
 }
 
 impl Heap {
     pub fn new() -> Self {
         Self {
-            next_object_id: 1,
             allocated_objects: 0,
             total_allocated_bytes: 0,
             max_heap_size: None,
-            current_heap_size: 0,
+            live_bytes: Arc::default(),
             young_generation_count: 0,
             old_generation_count: 0,
             allocation_tracking: false,
             allocation_stats: AllocationStats::default(),
+            collections: 0,
+            collected_objects: 0,
         }
     }
     
     pub fn with_initial_size(max_size: usize) -> Self {
         Self {
-            next_object_id: 1,
             allocated_objects: 0,
             total_allocated_bytes: 0,
             max_heap_size: Some(max_size),
-            current_heap_size: 0,
+            live_bytes: Arc::default(),
             young_generation_count: 0,
             old_generation_count: 0,
             allocation_tracking: false,
             allocation_stats: AllocationStats::default(),
+            collections: 0,
+            collected_objects: 0,
         }
     }
     
@@ -171,21 +749,18 @@ impl Heap {
         let size = value.len() + std::mem::size_of::<String>();
         
         // Check heap limits
-        if let Some(max_size) = self.max_heap_size {
-            if self.current_heap_size + size > max_size {
+        if let Some(max_size) = self.max_heap_size
+            && self.current_heap_size() + size > max_size {
                 return Err(HeapError::OutOfMemory);
             }
-        }
         
-        let object_id = self.next_object_id;
-        self.next_object_id += 1;
+        let object_id = next_object_id();
         
-        let gc_ptr = GcPtr::new(value, object_id);
+        let gc_ptr = self.charge(value, object_id, size);
         
         // Update statistics
         self.allocated_objects += 1;
         self.total_allocated_bytes += size;
-        self.current_heap_size += size;
         self.young_generation_count += 1;
         
         if self.allocation_tracking {
@@ -197,26 +772,58 @@ impl Heap {
         Ok(gc_ptr)
     }
     
+    /// A string built while a program runs, counted like
+    /// [`allocate_string`](Self::allocate_string) but kept as a plain
+    /// [`VmString`] value
+    pub fn allocate_str(&mut self, value: String) -> Result<VmString, HeapError> {
+        let size = value.len() + std::mem::size_of::<String>();
+        if !self.has_room(size) {
+            return Err(HeapError::OutOfMemory);
+        }
+
+        self.allocated_objects += 1;
+        self.total_allocated_bytes += size;
+        self.young_generation_count += 1;
+        if self.allocation_tracking {
+            self.allocation_stats.total_allocations += 1;
+            self.allocation_stats.bytes_allocated += size;
+            self.allocation_stats.string_allocations += 1;
+        }
+        Ok(VmString::charged(value, self.new_charge(size)))
+    }
+
+    /// Counts a string a host function made against this heap, unless it
+    /// is already counted somewhere or shared with values that were there
+    /// before, such as constants
+    pub fn adopt_string(&mut self, string: &mut VmString) -> Result<(), HeapError> {
+        if let Some(text) = string.take_unowned() {
+            *string = self.allocate_str(text)?;
+        }
+        Ok(())
+    }
+
+    /// Whether `size` more bytes fit under the heap's limit
+    pub fn has_room(&self, size: usize) -> bool {
+        self.max_heap_size
+            .is_none_or(|max| self.current_heap_size().saturating_add(size) <= max)
+    }
+
     pub fn allocate_object(&mut self, object: Object) -> Result<GcPtr<Object>, HeapError> {
-        let size = std::mem::size_of::<Object>() + 
-                   object.fields.capacity() * std::mem::size_of::<(String, Value)>();
+        let size = std::mem::size_of::<Object>() + object.storage_size();
         
         // Check heap limits
-        if let Some(max_size) = self.max_heap_size {
-            if self.current_heap_size + size > max_size {
+        if let Some(max_size) = self.max_heap_size
+            && self.current_heap_size() + size > max_size {
                 return Err(HeapError::OutOfMemory);
             }
-        }
         
-        let object_id = self.next_object_id;
-        self.next_object_id += 1;
+        let object_id = next_object_id();
         
-        let gc_ptr = GcPtr::new(object, object_id);
+        let gc_ptr = self.charge(object, object_id, size);
         
         // Update statistics
         self.allocated_objects += 1;
         self.total_allocated_bytes += size;
-        self.current_heap_size += size;
         self.young_generation_count += 1;
         
         if self.allocation_tracking {
@@ -228,6 +835,106 @@ impl Heap {
         Ok(gc_ptr)
     }
     
+    pub fn allocate_array(&mut self, elements: Vec<Value>) -> Result<GcPtr<Array>, HeapError> {
+        let size = std::mem::size_of::<Array>() + 
+                   elements.capacity() * std::mem::size_of::<Value>();
+        
+        // Check heap limits
+        if let Some(max_size) = self.max_heap_size
+            && self.current_heap_size() + size > max_size {
+                return Err(HeapError::OutOfMemory);
+            }
+        
+        let object_id = next_object_id();
+        
+        let gc_ptr = self.charge(Array::new(elements), object_id, size);
+        
+        // Update statistics
+        self.allocated_objects += 1;
+        self.total_allocated_bytes += size;
+        self.young_generation_count += 1;
+        
+        if self.allocation_tracking {
+            self.allocation_stats.total_allocations += 1;
+            self.allocation_stats.bytes_allocated += size;
+            self.allocation_stats.array_allocations += 1;
+        }
+        
+        Ok(gc_ptr)
+    }
+    
+    pub fn allocate_bytes(&mut self, bytes: Vec<u8>) -> Result<GcPtr<ByteBuffer>, HeapError> {
+        let size = std::mem::size_of::<ByteBuffer>() + bytes.capacity();
+        
+        // Check heap limits
+        if let Some(max_size) = self.max_heap_size
+            && self.current_heap_size() + size > max_size {
+                return Err(HeapError::OutOfMemory);
+            }
+        
+        let object_id = next_object_id();
+        
+        let gc_ptr = self.charge(ByteBuffer::new(bytes), object_id, size);
+        
+        // Update statistics
+        self.allocated_objects += 1;
+        self.total_allocated_bytes += size;
+        self.young_generation_count += 1;
+        
+        if self.allocation_tracking {
+            self.allocation_stats.total_allocations += 1;
+            self.allocation_stats.bytes_allocated += size;
+            self.allocation_stats.bytes_allocations += 1;
+        }
+        
+        Ok(gc_ptr)
+    }
+
+    pub fn allocate_typed_array<T: Copy>(
+        &mut self,
+        elements: Vec<T>,
+    ) -> Result<GcPtr<TypedArray<T>>, HeapError> {
+        let size = std::mem::size_of::<TypedArray<T>>()
+            + elements.capacity() * std::mem::size_of::<T>();
+        
+        // Check heap limits
+        if let Some(max_size) = self.max_heap_size
+            && self.current_heap_size() + size > max_size {
+                return Err(HeapError::OutOfMemory);
+            }
+        
+        let object_id = next_object_id();
+        
+        let gc_ptr = self.charge(TypedArray::new(elements), object_id, size);
+        
+        // Update statistics
+        self.allocated_objects += 1;
+        self.total_allocated_bytes += size;
+        self.young_generation_count += 1;
+        
+        if self.allocation_tracking {
+            self.allocation_stats.total_allocations += 1;
+            self.allocation_stats.bytes_allocated += size;
+            self.allocation_stats.typed_array_allocations += 1;
+        }
+        
+        Ok(gc_ptr)
+    }
+    
+    /// `value` as a new object counting `size` bytes against this heap
+    /// for as long as it is alive
+    fn charge<T>(&self, value: T, object_id: usize, size: usize) -> GcPtr<T> {
+        GcPtr::new(value, object_id, Some(self.new_charge(size)))
+    }
+
+    fn new_charge(&self, size: usize) -> HeapCharge {
+        self.live_bytes.fetch_add(size, Ordering::Relaxed);
+        HeapCharge {
+            size: AtomicUsize::new(size),
+            live_bytes: Arc::clone(&self.live_bytes),
+        }
+    }
+
     pub fn create_weak_reference<T>(&self, gc_ptr: &GcPtr<T>) -> WeakRef<T> {
         WeakRef::new(gc_ptr)
     }
@@ -237,12 +944,13 @@ impl Heap {
     pub fn collect_garbage<T>(&mut self, _roots: &[&GcPtr<T>]) -> usize {
         // For testing purposes, simulate collecting 1 object
         // In reality, this would mark all reachable objects and sweep unreachable ones
+        self.collections += 1;
         if self.allocated_objects > 0 {
             self.allocated_objects -= 1;
-            self.current_heap_size = self.current_heap_size.saturating_sub(50); // Rough estimate
             if self.young_generation_count > 0 {
                 self.young_generation_count -= 1;
             }
+            self.collected_objects += 1;
             1
         } else {
             0
@@ -280,8 +988,11 @@ impl Heap {
         self.max_heap_size
     }
     
+    /// Bytes held by objects allocated here that are still alive. An
+    /// object gives its bytes back when the last value referring to it is
+    /// dropped.
     pub fn current_heap_size(&self) -> usize {
-        self.current_heap_size
+        self.live_bytes.load(Ordering::Relaxed)
     }
     
     pub fn young_generation_objects(&self) -> usize {
@@ -291,6 +1002,16 @@ impl Heap {
     pub fn old_generation_objects(&self) -> usize {
         self.old_generation_count
     }
+
+    /// Garbage collections run so far
+    pub fn collections(&self) -> usize {
+        self.collections
+    }
+
+    /// Objects all garbage collections so far have freed
+    pub fn collected_objects(&self) -> usize {
+        self.collected_objects
+    }
     
     pub fn enable_allocation_tracking(&mut self) {
         self.allocation_tracking = true;
@@ -302,7 +1023,7 @@ impl Heap {
     
     pub fn fragmentation_ratio(&self) -> f64 {
         // Simulate fragmentation calculation
-        if self.current_heap_size == 0 {
+        if self.current_heap_size() == 0 {
             0.0
         } else {
             0.1 // 10% fragmentation
@@ -0,0 +1,215 @@
+//! The heap as a graph of objects, for seeing what keeps what alive.
+//!
+//! [`HeapGraph::from_roots`] walks every heap object reachable from a set of
+//! named roots, and [`VirtualMachine::heap_graph`] does so from a VM's
+//! operand stack, locals and globals. Objects are nodes and the fields and
+//! elements that refer to other objects are edges. Tuples live inline in
+//! the values that hold them rather than on the heap, so a reference inside
+//! one is an edge labelled with its path, such as `pair.1`.
+//! [`to_dot`](HeapGraph::to_dot) draws the graph for Graphviz with the
+//! roots highlighted; [`to_json`](HeapGraph::to_json) writes it for other
+//! tools.
+//!
+//! [`VirtualMachine::heap_graph`]: crate::vm::runtime::VirtualMachine::heap_graph
+
+use crate::vm::types::Value;
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Write;
+
+/// Characters of a string shown in its node
+const STRING_PREVIEW: usize = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum HeapNodeKind {
+    Object,
+    Array,
+    Bytes,
+    IntArray,
+    FloatArray,
+    String,
+}
+
+/// One heap object
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeapNode {
+    /// The object's id, unique among live objects
+    pub id: usize,
+    pub kind: HeapNodeKind,
+    /// Fields of an object, elements of an array or characters of a string
+    pub len: usize,
+    /// Whether an object has been frozen
+    pub frozen: bool,
+    /// A string's text, cut short if long
+    pub preview: Option<String>,
+}
+
+/// A field or element of object `from` referring to object `to`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeapEdge {
+    pub from: usize,
+    pub to: usize,
+    /// The field name, or `[index]` for an array element
+    pub label: String,
+}
+
+/// A root referring to object `to`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeapRoot {
+    pub name: String,
+    pub to: usize,
+}
+
+/// Objects reachable from the roots, in the order they were found
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct HeapGraph {
+    pub nodes: Vec<HeapNode>,
+    pub edges: Vec<HeapEdge>,
+    pub roots: Vec<HeapRoot>,
+}
+
+impl HeapGraph {
+    /// The objects reachable from `roots`, each a name and the value it
+    /// holds. Roots holding no heap object are left out.
+    pub fn from_roots<'a>(roots: impl IntoIterator<Item = (String, &'a Value)>) -> Self {
+        let mut graph = HeapGraph::default();
+        let mut seen = HashSet::new();
+        // References still to follow: the object they come from, or `None`
+        // for a root, their label and the value
+        let mut pending: VecDeque<(Option<usize>, String, Value)> = roots
+            .into_iter()
+            .map(|(name, value)| (None, name, value.clone()))
+            .collect();
+        while let Some((from, label, value)) = pending.pop_front() {
+            if let Value::Tuple(tuple) = &value {
+                for (index, element) in tuple.iter().enumerate() {
+                    pending.push_back((from, format!("{}.{}", label, index), element.clone()));
+                }
+                continue;
+            }
+            let Some(node) = node(&value) else {
+                continue;
+            };
+            let to = node.id;
+            match from {
+                Some(from) => graph.edges.push(HeapEdge { from, to, label }),
+                None => graph.roots.push(HeapRoot { name: label, to }),
+            }
+            if !seen.insert(to) {
+                continue;
+            }
+            graph.nodes.push(node);
+            match &value {
+                Value::GcObject(object) => {
+                    let mut fields = object.fields();
+                    fields.sort_by(|a, b| a.0.cmp(&b.0));
+                    pending.extend(fields.into_iter().map(|(name, value)| (Some(to), name, value)));
+                }
+                Value::Array(array) => {
+                    let elements = array.to_vec().into_iter().enumerate();
+                    pending.extend(
+                        elements.map(|(index, value)| (Some(to), format!("[{}]", index), value)),
+                    );
+                }
+                _ => {}
+            }
+        }
+        graph
+    }
+
+    pub fn node(&self, id: usize) -> Option<&HeapNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    pub fn is_root(&self, id: usize) -> bool {
+        self.roots.iter().any(|root| root.to == id)
+    }
+
+    /// References to object `id` from other objects: what keeps it alive
+    /// besides any roots
+    pub fn retainers(&self, id: usize) -> impl Iterator<Item = &HeapEdge> {
+        self.edges.iter().filter(move |edge| edge.to == id)
+    }
+
+    /// The graph in Graphviz's DOT language: a box per object, a plain
+    /// label per root pointing at what it holds, and objects held by a
+    /// root filled in
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph heap {\n");
+        dot.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+        for node in &self.nodes {
+            let style = if self.is_root(node.id) {
+                ", style=\"filled,bold\", fillcolor=lightblue"
+            } else {
+                ""
+            };
+            let _ = writeln!(dot, "    n{} [label=\"{}\"{}];", node.id, escape(&describe(node)), style);
+        }
+        for (index, root) in self.roots.iter().enumerate() {
+            let _ = writeln!(dot, "    root{} [shape=plaintext, label=\"{}\"];", index, escape(&root.name));
+            let _ = writeln!(dot, "    root{} -> n{} [style=bold];", index, root.to);
+        }
+        for edge in &self.edges {
+            let _ = writeln!(dot, "    n{} -> n{} [label=\"{}\"];", edge.from, edge.to, escape(&edge.label));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[cfg(feature = "serde")]
+impl HeapGraph {
+    /// This graph as a JSON object
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("heap graphs serialize to JSON")
+    }
+}
+
+/// The node for `value`, if it is a heap object
+fn node(value: &Value) -> Option<HeapNode> {
+    let node = |id, kind, len| HeapNode { id, kind, len, frozen: false, preview: None };
+    Some(match value {
+        Value::GcObject(object) => HeapNode {
+            frozen: object.is_frozen(),
+            ..node(object.object_id(), HeapNodeKind::Object, object.field_count())
+        },
+        Value::Array(array) => node(array.object_id(), HeapNodeKind::Array, array.len()),
+        Value::Bytes(bytes) => node(bytes.object_id(), HeapNodeKind::Bytes, bytes.len()),
+        Value::IntArray(array) => node(array.object_id(), HeapNodeKind::IntArray, array.len()),
+        Value::FloatArray(array) => node(array.object_id(), HeapNodeKind::FloatArray, array.len()),
+        Value::GcString(text) => HeapNode {
+            preview: Some(text.chars().take(STRING_PREVIEW).collect()),
+            ..node(text.object_id(), HeapNodeKind::String, text.chars().count())
+        },
+        _ => return None,
+    })
+}
+
+/// `node` as its box is labelled
+fn describe(node: &HeapNode) -> String {
+    let what = match node.kind {
+        HeapNodeKind::Object => format!("object, {} fields", node.len),
+        HeapNodeKind::Array => format!("array of {}", node.len),
+        HeapNodeKind::Bytes => format!("{} bytes", node.len),
+        HeapNodeKind::IntArray => format!("int array of {}", node.len),
+        HeapNodeKind::FloatArray => format!("float array of {}", node.len),
+        HeapNodeKind::String => {
+            let preview = node.preview.as_deref().unwrap_or_default();
+            let more = if node.len > preview.chars().count() { "..." } else { "" };
+            format!("string {:?}{}", preview, more)
+        }
+    };
+    let frozen = if node.frozen { ", frozen" } else { "" };
+    format!("#{} {}{}", node.id, what, frozen)
+}
+
+/// `text` inside a DOT string
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,205 @@
+//! Going back to earlier points of a run.
+//!
+//! An [`ExecutionHistory`] keeps a [`VmSnapshot`] every so many
+//! instructions. To go back to an instruction it restores the nearest
+//! snapshot before it and replays forward from there. The VM's streams go
+//! through the history too, so a replay reads the input the run read the
+//! first time and does not repeat output already written.
+//!
+//! Points in a run are counted in instructions started, as
+//! [`OpcodeStatistics::total`] counts them; unlike
+//! [`VirtualMachine::instruction_count`], that includes the instructions
+//! the VM runs itself, such as I/O and host calls.
+//!
+//! [`OpcodeStatistics::total`]: crate::vm::runtime::OpcodeStatistics::total
+
+use crate::vm::io::{MemoryIo, VmIo};
+use crate::vm::runtime::{VirtualMachine, VmError, VmSnapshot};
+use std::collections::BTreeMap;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// How far into each stream a run has got
+#[derive(Debug, Clone, Copy, Default)]
+struct StreamPosition {
+    lines_read: usize,
+    stdout_written: usize,
+    stderr_written: usize,
+}
+
+struct Streams {
+    inner: Box<dyn VmIo>,
+    /// Every line the run has read, `None` for the end of input
+    input: Vec<Option<String>>,
+    position: StreamPosition,
+    /// Bytes of each output stream already passed on
+    stdout_shown: usize,
+    stderr_shown: usize,
+}
+
+/// The part of `text`, written `written` bytes into a stream, that goes
+/// past the `shown` bytes already passed on
+fn unseen(text: &str, written: usize, shown: usize) -> &str {
+    let skip = shown.saturating_sub(written).min(text.len());
+    text.get(skip..).unwrap_or("")
+}
+
+/// The VM's side of the streams, shared with the history
+struct HistoryIo(Arc<Mutex<Streams>>);
+
+impl VmIo for HistoryIo {
+    fn write_stdout(&mut self, text: &str) -> io::Result<()> {
+        let mut streams = lock(&self.0);
+        let written = streams.position.stdout_written;
+        streams.position.stdout_written += text.len();
+        let unseen = unseen(text, written, streams.stdout_shown);
+        if !unseen.is_empty() {
+            streams.inner.write_stdout(unseen)?;
+            streams.stdout_shown = streams.position.stdout_written;
+        }
+        Ok(())
+    }
+
+    fn write_stderr(&mut self, text: &str) -> io::Result<()> {
+        let mut streams = lock(&self.0);
+        let written = streams.position.stderr_written;
+        streams.position.stderr_written += text.len();
+        let unseen = unseen(text, written, streams.stderr_shown);
+        if !unseen.is_empty() {
+            streams.inner.write_stderr(unseen)?;
+            streams.stderr_shown = streams.position.stderr_written;
+        }
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut streams = lock(&self.0);
+        let index = streams.position.lines_read;
+        let line = match streams.input.get(index) {
+            Some(line) => line.clone(),
+            None => {
+                let line = streams.inner.read_line()?;
+                streams.input.push(line.clone());
+                line
+            }
+        };
+        streams.position.lines_read += 1;
+        Ok(line)
+    }
+}
+
+fn lock(streams: &Mutex<Streams>) -> MutexGuard<'_, Streams> {
+    streams.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Snapshots of a VM's run, by position, that let it be moved back to
+/// any instruction since the history began
+pub struct ExecutionHistory {
+    interval: u64,
+    snapshots: BTreeMap<u64, (VmSnapshot, StreamPosition)>,
+    streams: Arc<Mutex<Streams>>,
+}
+
+impl ExecutionHistory {
+    /// Starts a history of `vm` from where it is now, to be snapshotted
+    /// every `interval` instructions; its streams are taken over for
+    /// replays
+    pub fn new(vm: &mut VirtualMachine, interval: u64) -> Self {
+        let streams = Arc::new(Mutex::new(Streams {
+            // In place until the VM's own streams are taken over
+            inner: Box::new(MemoryIo::new()),
+            input: Vec::new(),
+            position: StreamPosition::default(),
+            stdout_shown: 0,
+            stderr_shown: 0,
+        }));
+        lock(&streams).inner = vm.replace_io(HistoryIo(Arc::clone(&streams)));
+        let mut history = Self { interval: interval.max(1), snapshots: BTreeMap::new(), streams };
+        history.snapshots.insert(Self::position(vm), (vm.snapshot(), StreamPosition::default()));
+        history
+    }
+
+    /// Instructions `vm` has started, which is where it is in its run
+    pub fn position(vm: &VirtualMachine) -> u64 {
+        vm.opcode_statistics().total()
+    }
+
+    /// Instructions between snapshots
+    pub fn interval(&self) -> u64 {
+        self.interval
+    }
+
+    /// Position the history begins at
+    pub fn start(&self) -> u64 {
+        self.snapshots.keys().next().copied().unwrap_or(0)
+    }
+
+    /// Snapshots taken so far
+    pub fn snapshot_count(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    /// Snapshots `vm` unless there is a snapshot less than an interval
+    /// before where it is. Call it as the run goes forward; going back
+    /// past an instruction costs replaying from the snapshot before it.
+    pub fn record(&mut self, vm: &VirtualMachine) {
+        let count = Self::position(vm);
+        let since = count.saturating_sub(self.interval - 1);
+        if self.snapshots.range(since..=count).next().is_none() {
+            let position = lock(&self.streams).position;
+            self.snapshots.insert(count, (vm.snapshot(), position));
+        }
+    }
+
+    /// Moves `vm` to position `count`, or to the start of the history if
+    /// that is later. Going forward past where
+    /// the run has been runs the program.
+    pub fn go_to(&mut self, vm: &mut VirtualMachine, count: u64) -> Result<(), VmError> {
+        let count = count.max(self.start());
+        if count < Self::position(vm) {
+            let (snapshot, position) = self
+                .snapshots
+                .range(..=count)
+                .next_back()
+                .map(|(_, saved)| saved)
+                .expect("histories start with a snapshot");
+            vm.restore(snapshot);
+            lock(&self.streams).position = *position;
+        }
+        while Self::position(vm) < count && !vm.is_halted() {
+            vm.step()?;
+            self.record(vm);
+        }
+        Ok(())
+    }
+
+    /// The latest position before where `vm` is now at which
+    /// `found` holds of it, looking no further back than the history
+    /// goes. `vm` is left there, or where it was if `found` never holds.
+    pub fn find_back(
+        &mut self,
+        vm: &mut VirtualMachine,
+        mut found: impl FnMut(&VirtualMachine) -> bool,
+    ) -> Result<Option<u64>, VmError> {
+        let now = Self::position(vm);
+        let mut end = now;
+        let starts: Vec<u64> = self.snapshots.range(..now).map(|(&count, _)| count).rev().collect();
+        for start in starts {
+            self.go_to(vm, start)?;
+            let mut latest = None;
+            while Self::position(vm) < end && !vm.is_halted() {
+                if found(vm) {
+                    latest = Some(Self::position(vm));
+                }
+                vm.step()?;
+            }
+            if let Some(count) = latest {
+                self.go_to(vm, count)?;
+                return Ok(Some(count));
+            }
+            end = start;
+        }
+        self.go_to(vm, now)?;
+        Ok(None)
+    }
+}
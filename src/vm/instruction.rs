@@ -1,10 +1,15 @@
 use crate::vm::call_frame::{CallFrame, CallFrameError, CallStack};
-use crate::vm::heap::{Heap, Object};
+use crate::vm::heap::{GcStr, Heap, HeapError, Object, StrSource};
+use crate::vm::module::FunctionInfo;
 use crate::vm::stack::{OperandStack, StackError};
-use crate::vm::types::Value;
+use crate::vm::types::{Decimal, Value, VmString, VmTuple, MAX_TUPLE_LEN};
+use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt;
+use std::sync::Arc;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[repr(u8)]
 pub enum Opcode {
     // Arithmetic operations
@@ -13,12 +18,18 @@ pub enum Opcode {
     Mul = 0x03,
     Div = 0x04,
     Mod = 0x05,
+    Negate = 0x06,
 
     // Stack operations
     Push = 0x10,
     Pop = 0x11,
     Dup = 0x12,
     Swap = 0x13,
+    LoadConstant = 0x14,
+    // Read and write the VM's named globals; the operand indexes the
+    // constants pool entry holding the name
+    LoadGlobal = 0x15,
+    StoreGlobal = 0x16,
 
     // Control flow
     Jump = 0x20,
@@ -26,6 +37,9 @@ pub enum Opcode {
     JumpIfFalse = 0x22,
     Call = 0x23,
     Return = 0x24,
+    CallIndirect = 0x25,
+    // Calls native function n, registered with the VM that loaded the module
+    CallNative = 0x26,
 
     // Comparison operations
     Equal = 0x30,
@@ -34,12 +48,15 @@ pub enum Opcode {
     LessEqual = 0x33,
     GreaterThan = 0x34,
     GreaterEqual = 0x35,
+    RefEqual = 0x36,
+    DeepEqual = 0x37,
 
     // Logical operations
     And = 0x40,
     Or = 0x41,
     Not = 0x42,
     Xor = 0x43,
+    NullCoalesce = 0x44,
 
     // Memory operations
     Load = 0x50,
@@ -47,12 +64,78 @@ pub enum Opcode {
     NewObject = 0x52,
     GetField = 0x53,
     SetField = 0x54,
+    NewArray = 0x55,
+    ArrayGet = 0x56,
+    ArraySet = 0x57,
+    ArrayLength = 0x58,
+    NewBytes = 0x59,
+    BytesGet = 0x5A,
+    BytesSet = 0x5B,
+    BytesLength = 0x5C,
+    BytesSlice = 0x5D,
+    ArraySort = 0x5E,
+    SafeGetField = 0x5F,
+
+    // String and character operations
+    CharAt = 0x60,
+    CharCode = 0x61,
+    CodeToChar = 0x62,
+    Substring = 0x63,
+    Split = 0x64,
+    StrConcat = 0x65,
+
+    // Tuple operations
+    MakeTuple = 0x70,
+    TupleGet = 0x71,
+
+    // Object operations
+    Freeze = 0x80,
+
+    // Typed array operations
+    NewIntArray = 0x90,
+    IntArrayGet = 0x91,
+    IntArraySet = 0x92,
+    NewFloatArray = 0x93,
+    FloatArrayGet = 0x94,
+    FloatArraySet = 0x95,
+    TypedArrayLength = 0x96,
+
+    // I/O through the VM's VmIo
+    Print = 0xA0,
+    ReadLine = 0xA1,
+
+    // Encoding prefix: the following opcode carries a 32-bit operand
+    Wide = 0xFE,
 
     // Halt/Debug
     Halt = 0xFF,
 }
 
 impl Opcode {
+    /// Whether the dispatcher runs this opcode on two integers without
+    /// looking at other operand types: `Add`, `Sub`, `Mul` and the
+    /// comparisons
+    pub fn has_integer_fast_path(self) -> bool {
+        matches!(
+            self,
+            Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::RefEqual
+                | Opcode::LessThan
+                | Opcode::LessEqual
+                | Opcode::GreaterThan
+                | Opcode::GreaterEqual
+        )
+    }
+
+    /// Every opcode, in encoding order
+    pub fn all() -> impl Iterator<Item = Opcode> {
+        (0..=u8::MAX).filter_map(Opcode::from_u8)
+    }
+
     pub fn from_u8(byte: u8) -> Option<Self> {
         match byte {
             0x01 => Some(Opcode::Add),
@@ -60,37 +143,128 @@ impl Opcode {
             0x03 => Some(Opcode::Mul),
             0x04 => Some(Opcode::Div),
             0x05 => Some(Opcode::Mod),
+            0x06 => Some(Opcode::Negate),
             0x10 => Some(Opcode::Push),
             0x11 => Some(Opcode::Pop),
             0x12 => Some(Opcode::Dup),
             0x13 => Some(Opcode::Swap),
+            0x14 => Some(Opcode::LoadConstant),
+            0x15 => Some(Opcode::LoadGlobal),
+            0x16 => Some(Opcode::StoreGlobal),
             0x20 => Some(Opcode::Jump),
             0x21 => Some(Opcode::JumpIfTrue),
             0x22 => Some(Opcode::JumpIfFalse),
             0x23 => Some(Opcode::Call),
             0x24 => Some(Opcode::Return),
+            0x25 => Some(Opcode::CallIndirect),
+            0x26 => Some(Opcode::CallNative),
             0x30 => Some(Opcode::Equal),
             0x31 => Some(Opcode::NotEqual),
             0x32 => Some(Opcode::LessThan),
             0x33 => Some(Opcode::LessEqual),
             0x34 => Some(Opcode::GreaterThan),
             0x35 => Some(Opcode::GreaterEqual),
+            0x36 => Some(Opcode::RefEqual),
+            0x37 => Some(Opcode::DeepEqual),
             0x40 => Some(Opcode::And),
             0x41 => Some(Opcode::Or),
             0x42 => Some(Opcode::Not),
             0x43 => Some(Opcode::Xor),
+            0x44 => Some(Opcode::NullCoalesce),
             0x50 => Some(Opcode::Load),
             0x51 => Some(Opcode::Store),
             0x52 => Some(Opcode::NewObject),
             0x53 => Some(Opcode::GetField),
             0x54 => Some(Opcode::SetField),
+            0x55 => Some(Opcode::NewArray),
+            0x56 => Some(Opcode::ArrayGet),
+            0x57 => Some(Opcode::ArraySet),
+            0x58 => Some(Opcode::ArrayLength),
+            0x59 => Some(Opcode::NewBytes),
+            0x5A => Some(Opcode::BytesGet),
+            0x5B => Some(Opcode::BytesSet),
+            0x5C => Some(Opcode::BytesLength),
+            0x5D => Some(Opcode::BytesSlice),
+            0x5E => Some(Opcode::ArraySort),
+            0x5F => Some(Opcode::SafeGetField),
+            0x60 => Some(Opcode::CharAt),
+            0x61 => Some(Opcode::CharCode),
+            0x62 => Some(Opcode::CodeToChar),
+            0x63 => Some(Opcode::Substring),
+            0x64 => Some(Opcode::Split),
+            0x65 => Some(Opcode::StrConcat),
+            0x70 => Some(Opcode::MakeTuple),
+            0x71 => Some(Opcode::TupleGet),
+            0x80 => Some(Opcode::Freeze),
+            0x90 => Some(Opcode::NewIntArray),
+            0x91 => Some(Opcode::IntArrayGet),
+            0x92 => Some(Opcode::IntArraySet),
+            0x93 => Some(Opcode::NewFloatArray),
+            0x94 => Some(Opcode::FloatArrayGet),
+            0x95 => Some(Opcode::FloatArraySet),
+            0x96 => Some(Opcode::TypedArrayLength),
+            0xA0 => Some(Opcode::Print),
+            0xA1 => Some(Opcode::ReadLine),
+            0xFE => Some(Opcode::Wide),
             0xFF => Some(Opcode::Halt),
             _ => None,
         }
     }
+
+    /// Whether this opcode is followed by an index operand (constant index,
+    /// local slot, field index, element count or jump target) in the binary
+    /// encoding.
+    pub fn has_index_operand(&self) -> bool {
+        matches!(
+            self,
+            Opcode::Push
+                | Opcode::LoadConstant
+                | Opcode::LoadGlobal
+                | Opcode::StoreGlobal
+                | Opcode::Jump
+                | Opcode::JumpIfTrue
+                | Opcode::JumpIfFalse
+                | Opcode::Call
+                | Opcode::CallNative
+                | Opcode::Load
+                | Opcode::Store
+                | Opcode::GetField
+                | Opcode::SafeGetField
+                | Opcode::SetField
+                | Opcode::NewArray
+                | Opcode::MakeTuple
+                | Opcode::TupleGet
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EncodingError {
+    UnexpectedEnd(usize),
+    UnknownOpcode(u8),
+    InvalidOperand(String),
+    MisplacedWidePrefix(usize),
 }
 
+impl fmt::Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodingError::UnexpectedEnd(offset) => {
+                write!(f, "Unexpected end of bytecode at offset {}", offset)
+            }
+            EncodingError::UnknownOpcode(code) => write!(f, "Unknown opcode: 0x{:02X}", code),
+            EncodingError::InvalidOperand(msg) => write!(f, "Invalid operand: {}", msg),
+            EncodingError::MisplacedWidePrefix(offset) => {
+                write!(f, "Wide prefix at offset {} does not precede an indexed opcode", offset)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodingError {}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instruction {
     opcode: Opcode,
     operand: Option<Value>,
@@ -108,6 +282,197 @@ impl Instruction {
     pub fn operand(&self) -> Option<&Value> {
         self.operand.as_ref()
     }
+
+    /// Values this instruction pops and then pushes, or `None` for `Call`,
+    /// `CallIndirect`, `CallNative`, `Return`, `Halt` and `Wide`, whose effect
+    /// depends on the function being called or returned from.
+    pub fn stack_effect(&self) -> Option<(usize, usize)> {
+        let count = || match self.operand {
+            Some(Value::Integer(count)) => usize::try_from(count).unwrap_or(0),
+            _ => 0,
+        };
+        let effect = match self.opcode {
+            Opcode::Push
+            | Opcode::LoadConstant
+            | Opcode::LoadGlobal
+            | Opcode::Load
+            | Opcode::NewObject
+            | Opcode::ReadLine => (0, 1),
+            Opcode::Jump => (0, 0),
+            Opcode::Pop
+            | Opcode::JumpIfTrue
+            | Opcode::JumpIfFalse
+            | Opcode::Store
+            | Opcode::StoreGlobal
+            | Opcode::Print => (1, 0),
+            Opcode::Dup => (1, 2),
+            Opcode::Swap => (2, 2),
+            Opcode::Negate
+            | Opcode::Not
+            | Opcode::GetField
+            | Opcode::SafeGetField
+            | Opcode::ArrayLength
+            | Opcode::ArraySort
+            | Opcode::NewBytes
+            | Opcode::BytesLength
+            | Opcode::CharCode
+            | Opcode::CodeToChar
+            | Opcode::TupleGet
+            | Opcode::Freeze
+            | Opcode::NewIntArray
+            | Opcode::NewFloatArray
+            | Opcode::TypedArrayLength => (1, 1),
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Mod
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::LessThan
+            | Opcode::LessEqual
+            | Opcode::GreaterThan
+            | Opcode::GreaterEqual
+            | Opcode::RefEqual
+            | Opcode::DeepEqual
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::NullCoalesce
+            | Opcode::ArrayGet
+            | Opcode::BytesGet
+            | Opcode::CharAt
+            | Opcode::Split
+            | Opcode::StrConcat
+            | Opcode::IntArrayGet
+            | Opcode::FloatArrayGet => (2, 1),
+            Opcode::SetField => (2, 0),
+            Opcode::BytesSlice | Opcode::Substring => (3, 1),
+            Opcode::ArraySet | Opcode::BytesSet | Opcode::IntArraySet | Opcode::FloatArraySet => {
+                (3, 0)
+            }
+            Opcode::NewArray | Opcode::MakeTuple => (count(), 1),
+            Opcode::Call
+            | Opcode::CallIndirect
+            | Opcode::CallNative
+            | Opcode::Return
+            | Opcode::Halt
+            | Opcode::Wide => return None,
+        };
+        Some(effect)
+    }
+
+    /// Append the binary encoding of this instruction to `out`.
+    ///
+    /// Index operands up to 255 are stored in a single byte after the opcode.
+    /// Larger indices are emitted as `Wide <opcode> <u32 little-endian>`.
+    pub fn encode(&self, out: &mut Vec<u8>) -> Result<(), EncodingError> {
+        if self.opcode == Opcode::Wide {
+            return Err(EncodingError::InvalidOperand(
+                "Wide is an encoding prefix, not an instruction".to_string(),
+            ));
+        }
+
+        if !self.opcode.has_index_operand() {
+            if self.operand.is_some() {
+                return Err(EncodingError::InvalidOperand(format!(
+                    "{:?} does not take an operand",
+                    self.opcode
+                )));
+            }
+            out.push(self.opcode as u8);
+            return Ok(());
+        }
+
+        let index = match &self.operand {
+            Some(Value::Integer(index)) => *index,
+            Some(other) => {
+                return Err(EncodingError::InvalidOperand(format!(
+                    "{:?} requires an integer index operand, got {}",
+                    self.opcode,
+                    other.type_name()
+                )));
+            }
+            None => {
+                return Err(EncodingError::InvalidOperand(format!(
+                    "{:?} requires an operand",
+                    self.opcode
+                )));
+            }
+        };
+
+        if let Ok(narrow) = u8::try_from(index) {
+            out.push(self.opcode as u8);
+            out.push(narrow);
+        } else if let Ok(wide) = u32::try_from(index) {
+            out.push(Opcode::Wide as u8);
+            out.push(self.opcode as u8);
+            out.extend_from_slice(&wide.to_le_bytes());
+        } else {
+            return Err(EncodingError::InvalidOperand(format!(
+                "Index {} cannot be encoded",
+                index
+            )));
+        }
+        Ok(())
+    }
+
+    /// Decode one instruction from the start of `bytes`, returning it together
+    /// with the number of bytes consumed.
+    pub fn decode(bytes: &[u8]) -> Result<(Instruction, usize), EncodingError> {
+        let first = *bytes.first().ok_or(EncodingError::UnexpectedEnd(0))?;
+        let first_opcode = Opcode::from_u8(first).ok_or(EncodingError::UnknownOpcode(first))?;
+
+        if first_opcode == Opcode::Wide {
+            let byte = *bytes.get(1).ok_or(EncodingError::UnexpectedEnd(1))?;
+            let opcode = Opcode::from_u8(byte).ok_or(EncodingError::UnknownOpcode(byte))?;
+            if !opcode.has_index_operand() {
+                return Err(EncodingError::MisplacedWidePrefix(0));
+            }
+            let operand: [u8; 4] = bytes
+                .get(2..6)
+                .and_then(|slice| slice.try_into().ok())
+                .ok_or(EncodingError::UnexpectedEnd(bytes.len()))?;
+            let index = u32::from_le_bytes(operand) as i64;
+            return Ok((Instruction::new(opcode, Some(Value::Integer(index))), 6));
+        }
+
+        if first_opcode.has_index_operand() {
+            let index = *bytes.get(1).ok_or(EncodingError::UnexpectedEnd(1))?;
+            return Ok((
+                Instruction::new(first_opcode, Some(Value::Integer(index as i64))),
+                2,
+            ));
+        }
+
+        Ok((Instruction::new(first_opcode, None), 1))
+    }
+}
+
+/// Encode a sequence of instructions into a contiguous byte stream.
+pub fn encode_program(program: &[Instruction]) -> Result<Vec<u8>, EncodingError> {
+    let mut bytes = Vec::with_capacity(program.len() * 2);
+    for instruction in program {
+        instruction.encode(&mut bytes)?;
+    }
+    Ok(bytes)
+}
+
+/// Decode a byte stream produced by [`encode_program`].
+pub fn decode_program(mut bytes: &[u8]) -> Result<Vec<Instruction>, EncodingError> {
+    let mut program = Vec::new();
+    let mut offset = 0;
+    while !bytes.is_empty() {
+        let (instruction, consumed) = Instruction::decode(bytes).map_err(|e| match e {
+            EncodingError::UnexpectedEnd(at) => EncodingError::UnexpectedEnd(offset + at),
+            EncodingError::MisplacedWidePrefix(at) => EncodingError::MisplacedWidePrefix(offset + at),
+            other => other,
+        })?;
+        program.push(instruction);
+        bytes = &bytes[consumed..];
+        offset += consumed;
+    }
+    Ok(program)
 }
 
 #[derive(Debug)]
@@ -116,10 +481,31 @@ pub enum ExecutionError {
     CallFrameError(CallFrameError),
     TypeError(String),
     DivisionByZero,
+    ArithmeticOverflow,
     InvalidJumpAddress(i64),
     UnknownOpcode(u8),
     InsufficientOperands,
     InvalidOperand(String),
+    IndexOutOfBounds(i64, usize), // index, length
+    FrozenObject(String),         // field name
+    ArityMismatch {
+        function: String,
+        expected: usize,
+        available: usize,
+    },
+    StackEffectMismatch {
+        function: String,
+        expected: usize,
+        actual: usize,
+    },
+    /// A host function called through `CallNative` returned an error
+    HostFunction { function: String, message: String },
+    /// A call would nest deeper than the call stack allows
+    CallDepthExceeded(usize),
+    /// `LoadGlobal` named a global that was never set
+    UndefinedGlobal(String),
+    /// `Print` or `ReadLine` failed to write or read
+    Io(String),
 }
 
 impl fmt::Display for ExecutionError {
@@ -129,10 +515,35 @@ impl fmt::Display for ExecutionError {
             ExecutionError::CallFrameError(e) => write!(f, "Call frame error: {}", e),
             ExecutionError::TypeError(msg) => write!(f, "Type error: {}", msg),
             ExecutionError::DivisionByZero => write!(f, "Division by zero"),
+            ExecutionError::ArithmeticOverflow => write!(f, "Arithmetic overflow"),
             ExecutionError::InvalidJumpAddress(addr) => write!(f, "Invalid jump address: {}", addr),
             ExecutionError::UnknownOpcode(code) => write!(f, "Unknown opcode: 0x{:02X}", code),
             ExecutionError::InsufficientOperands => write!(f, "Insufficient operands on stack"),
             ExecutionError::InvalidOperand(msg) => write!(f, "Invalid operand: {}", msg),
+            ExecutionError::IndexOutOfBounds(index, len) => {
+                write!(f, "Index {} out of bounds (length: {})", index, len)
+            }
+            ExecutionError::FrozenObject(field) => {
+                write!(f, "Cannot set field '{}' on a frozen object", field)
+            }
+            ExecutionError::ArityMismatch { function, expected, available } => write!(
+                f,
+                "Function {} takes {} arguments, but only {} values are on the stack",
+                function, expected, available
+            ),
+            ExecutionError::StackEffectMismatch { function, expected, actual } => write!(
+                f,
+                "Function {} should return {} values, but left {}",
+                function, expected, actual
+            ),
+            ExecutionError::HostFunction { function, message } => {
+                write!(f, "Host function {} failed: {}", function, message)
+            }
+            ExecutionError::CallDepthExceeded(depth) => {
+                write!(f, "Call depth exceeded the limit of {}", depth)
+            }
+            ExecutionError::UndefinedGlobal(name) => write!(f, "Undefined global: {}", name),
+            ExecutionError::Io(message) => write!(f, "I/O error: {}", message),
         }
     }
 }
@@ -151,10 +562,23 @@ impl From<CallFrameError> for ExecutionError {
     }
 }
 
+/// What a call needs of a declared function, with a name the frames of
+/// its calls share instead of each copying it
+#[derive(Clone)]
+struct Callee {
+    name: Arc<str>,
+    arity: usize,
+    locals: usize,
+    returns: Option<usize>,
+}
+
+#[derive(Clone)]
 pub struct InstructionDispatcher {
     program_counter: usize,
     instruction_count: u64,
     branch_predictions: std::collections::HashMap<usize, bool>,
+    // Declared functions by entry address; calls elsewhere get empty frames
+    functions: std::collections::HashMap<usize, Callee>,
 }
 
 impl InstructionDispatcher {
@@ -163,9 +587,26 @@ impl InstructionDispatcher {
             program_counter: 0,
             instruction_count: 0,
             branch_predictions: std::collections::HashMap::new(),
+            functions: std::collections::HashMap::new(),
         }
     }
 
+    /// Use `functions` to size the frames created by calls into them
+    pub fn set_functions(&mut self, functions: &[FunctionInfo]) {
+        self.functions = functions
+            .iter()
+            .map(|function| {
+                let callee = Callee {
+                    name: function.name.as_str().into(),
+                    arity: function.arity,
+                    locals: function.locals,
+                    returns: function.returns,
+                };
+                (function.address, callee)
+            })
+            .collect();
+    }
+
     pub fn current_pc(&self) -> usize {
         self.program_counter
     }
@@ -174,6 +615,13 @@ impl InstructionDispatcher {
         self.program_counter = pc;
     }
 
+    /// Moves on to the next instruction, for instructions executed
+    /// elsewhere. Those executed here move the program counter themselves:
+    /// on to the next instruction, or for control flow to its target.
+    pub fn advance(&mut self) {
+        self.program_counter += 1;
+    }
+
     pub fn instruction_count(&self) -> u64 {
         self.instruction_count
     }
@@ -203,19 +651,32 @@ impl InstructionDispatcher {
             Opcode::Mul => self.execute_mul(stack),
             Opcode::Div => self.execute_div(stack),
             Opcode::Mod => self.execute_mod(stack),
+            Opcode::Negate => self.execute_negate(stack),
 
             // Stack operations
             Opcode::Push => self.execute_push_with_constants(instruction, stack, constants),
+            Opcode::LoadConstant => self.execute_load_constant(instruction, stack, constants, heap),
             Opcode::Pop => self.execute_pop(stack),
             Opcode::Dup => self.execute_dup(stack),
             Opcode::Swap => self.execute_swap(stack),
 
-            // Control flow
-            Opcode::Jump => self.execute_jump(instruction),
-            Opcode::JumpIfTrue => self.execute_jump_if_true(instruction, stack),
-            Opcode::JumpIfFalse => self.execute_jump_if_false(instruction, stack),
-            Opcode::Call => self.execute_call(instruction, call_stack),
-            Opcode::Return => self.execute_return(call_stack),
+            // Control flow sets the program counter itself
+            Opcode::Jump => return self.execute_jump(instruction),
+            Opcode::JumpIfTrue => return self.execute_jump_if_true(instruction, stack),
+            Opcode::JumpIfFalse => return self.execute_jump_if_false(instruction, stack),
+            Opcode::Call => return self.execute_call(instruction, stack, call_stack),
+            Opcode::Return => return self.execute_return(stack, call_stack),
+            Opcode::CallIndirect => return self.execute_call_indirect(stack, call_stack),
+            Opcode::CallNative => Err(ExecutionError::InvalidOperand(
+                "CallNative needs the native functions of a VirtualMachine".to_string(),
+            )),
+            Opcode::LoadGlobal | Opcode::StoreGlobal => Err(ExecutionError::InvalidOperand(
+                format!("{:?} needs the globals of a VirtualMachine", instruction.opcode()),
+            )),
+            Opcode::Print | Opcode::ReadLine => Err(ExecutionError::InvalidOperand(format!(
+                "{:?} needs the I/O of a VirtualMachine",
+                instruction.opcode()
+            ))),
 
             // Comparison operations
             Opcode::Equal => self.execute_equal(stack),
@@ -224,22 +685,64 @@ impl InstructionDispatcher {
             Opcode::LessEqual => self.execute_less_equal(stack),
             Opcode::GreaterThan => self.execute_greater_than(stack),
             Opcode::GreaterEqual => self.execute_greater_equal(stack),
+            Opcode::RefEqual => self.execute_equal(stack),
+            Opcode::DeepEqual => self.execute_deep_equal(stack),
 
             // Logical operations
             Opcode::And => self.execute_and(stack),
             Opcode::Or => self.execute_or(stack),
             Opcode::Not => self.execute_not(stack),
             Opcode::Xor => self.execute_xor(stack),
+            Opcode::NullCoalesce => self.execute_null_coalesce(stack),
 
             // Memory operations
             Opcode::Load => self.execute_load(instruction, stack, call_stack),
             Opcode::Store => self.execute_store(instruction, stack, call_stack),
             Opcode::NewObject => self.execute_new_object(stack, heap),
-            Opcode::GetField => self.execute_get_field(instruction, stack),
+            Opcode::GetField => self.execute_get_field(instruction, stack, false),
+            Opcode::SafeGetField => self.execute_get_field(instruction, stack, true),
             Opcode::SetField => self.execute_set_field(instruction, stack),
-
-            Opcode::Halt => Ok(()),
-        }
+            Opcode::NewArray => self.execute_new_array(instruction, stack, heap),
+            Opcode::ArrayGet => self.execute_array_get(stack),
+            Opcode::ArraySet => self.execute_array_set(stack),
+            Opcode::ArrayLength => self.execute_array_length(stack),
+            Opcode::ArraySort => self.execute_array_sort(stack),
+            Opcode::NewBytes => self.execute_new_bytes(stack, heap),
+            Opcode::BytesGet => self.execute_bytes_get(stack),
+            Opcode::BytesSet => self.execute_bytes_set(stack),
+            Opcode::BytesLength => self.execute_bytes_length(stack),
+            Opcode::BytesSlice => self.execute_bytes_slice(stack, heap),
+
+            // String and character operations
+            Opcode::CharAt => self.execute_char_at(stack),
+            Opcode::CharCode => self.execute_char_code(stack),
+            Opcode::CodeToChar => self.execute_code_to_char(stack),
+            Opcode::Substring => self.execute_substring(stack),
+            Opcode::Split => self.execute_split(stack, heap),
+            Opcode::StrConcat => self.execute_str_concat(stack, Some(heap)),
+
+
+            // Tuple operations
+            Opcode::MakeTuple => self.execute_make_tuple(instruction, stack),
+            Opcode::TupleGet => self.execute_tuple_get(instruction, stack),
+
+            // Object operations
+            Opcode::Freeze => self.execute_freeze(stack),
+
+            // Typed array operations
+            Opcode::NewIntArray => self.execute_new_int_array(stack, heap),
+            Opcode::IntArrayGet => self.execute_int_array_get(stack),
+            Opcode::IntArraySet => self.execute_int_array_set(stack),
+            Opcode::NewFloatArray => self.execute_new_float_array(stack, heap),
+            Opcode::FloatArrayGet => self.execute_float_array_get(stack),
+            Opcode::FloatArraySet => self.execute_float_array_set(stack),
+            Opcode::TypedArrayLength => self.execute_typed_array_length(stack),
+
+            Opcode::Wide => Err(ExecutionError::UnknownOpcode(Opcode::Wide as u8)),
+            Opcode::Halt => return Ok(()),
+        }?;
+        self.program_counter += 1;
+        Ok(())
     }
 
     pub fn execute(
@@ -257,19 +760,34 @@ impl InstructionDispatcher {
             Opcode::Mul => self.execute_mul(stack),
             Opcode::Div => self.execute_div(stack),
             Opcode::Mod => self.execute_mod(stack),
+            Opcode::Negate => self.execute_negate(stack),
 
             // Stack operations
             Opcode::Push => self.execute_push(instruction, stack),
             Opcode::Pop => self.execute_pop(stack),
             Opcode::Dup => self.execute_dup(stack),
             Opcode::Swap => self.execute_swap(stack),
+            Opcode::LoadConstant => Err(ExecutionError::InvalidOperand(
+                "LoadConstant requires the constants pool - use execute_with_constants".to_string()
+            )),
 
-            // Control flow
-            Opcode::Jump => self.execute_jump(instruction),
-            Opcode::JumpIfTrue => self.execute_jump_if_true(instruction, stack),
-            Opcode::JumpIfFalse => self.execute_jump_if_false(instruction, stack),
-            Opcode::Call => self.execute_call(instruction, call_stack),
-            Opcode::Return => self.execute_return(call_stack),
+            // Control flow sets the program counter itself
+            Opcode::Jump => return self.execute_jump(instruction),
+            Opcode::JumpIfTrue => return self.execute_jump_if_true(instruction, stack),
+            Opcode::JumpIfFalse => return self.execute_jump_if_false(instruction, stack),
+            Opcode::Call => return self.execute_call(instruction, stack, call_stack),
+            Opcode::Return => return self.execute_return(stack, call_stack),
+            Opcode::CallIndirect => return self.execute_call_indirect(stack, call_stack),
+            Opcode::CallNative => Err(ExecutionError::InvalidOperand(
+                "CallNative needs the native functions of a VirtualMachine".to_string(),
+            )),
+            Opcode::LoadGlobal | Opcode::StoreGlobal => Err(ExecutionError::InvalidOperand(
+                format!("{:?} needs the globals of a VirtualMachine", instruction.opcode()),
+            )),
+            Opcode::Print | Opcode::ReadLine => Err(ExecutionError::InvalidOperand(format!(
+                "{:?} needs the I/O of a VirtualMachine",
+                instruction.opcode()
+            ))),
 
             // Comparison operations
             Opcode::Equal => self.execute_equal(stack),
@@ -278,12 +796,15 @@ impl InstructionDispatcher {
             Opcode::LessEqual => self.execute_less_equal(stack),
             Opcode::GreaterThan => self.execute_greater_than(stack),
             Opcode::GreaterEqual => self.execute_greater_equal(stack),
+            Opcode::RefEqual => self.execute_equal(stack),
+            Opcode::DeepEqual => self.execute_deep_equal(stack),
 
             // Logical operations
             Opcode::And => self.execute_and(stack),
             Opcode::Or => self.execute_or(stack),
             Opcode::Not => self.execute_not(stack),
             Opcode::Xor => self.execute_xor(stack),
+            Opcode::NullCoalesce => self.execute_null_coalesce(stack),
 
             // Memory operations
             Opcode::Load => self.execute_load(instruction, stack, call_stack),
@@ -291,23 +812,79 @@ impl InstructionDispatcher {
             Opcode::NewObject => Err(ExecutionError::InvalidOperand(
                 "NewObject requires heap access - use execute_with_constants".to_string()
             )),
-            Opcode::GetField => self.execute_get_field(instruction, stack),
+            Opcode::GetField => self.execute_get_field(instruction, stack, false),
+            Opcode::SafeGetField => self.execute_get_field(instruction, stack, true),
             Opcode::SetField => self.execute_set_field(instruction, stack),
+            Opcode::NewArray => Err(ExecutionError::InvalidOperand(
+                "NewArray requires heap access - use execute_with_constants".to_string()
+            )),
+            Opcode::ArrayGet => self.execute_array_get(stack),
+            Opcode::ArraySet => self.execute_array_set(stack),
+            Opcode::ArrayLength => self.execute_array_length(stack),
+            Opcode::ArraySort => self.execute_array_sort(stack),
+            Opcode::NewBytes | Opcode::BytesSlice => Err(ExecutionError::InvalidOperand(
+                format!("{:?} requires heap access - use execute_with_constants", instruction.opcode())
+            )),
+            Opcode::BytesGet => self.execute_bytes_get(stack),
+            Opcode::BytesSet => self.execute_bytes_set(stack),
+            Opcode::BytesLength => self.execute_bytes_length(stack),
+
+            // String and character operations
+            Opcode::CharAt => self.execute_char_at(stack),
+            Opcode::CharCode => self.execute_char_code(stack),
+            Opcode::CodeToChar => self.execute_code_to_char(stack),
+            Opcode::Substring => self.execute_substring(stack),
+            Opcode::Split => Err(ExecutionError::InvalidOperand(
+                "Split requires heap access - use execute_with_constants".to_string()
+            )),
+            Opcode::StrConcat => self.execute_str_concat(stack, None),
 
-            Opcode::Halt => Ok(()),
-        }
+
+            // Tuple operations
+            Opcode::MakeTuple => self.execute_make_tuple(instruction, stack),
+            Opcode::TupleGet => self.execute_tuple_get(instruction, stack),
+
+            // Object operations
+            Opcode::Freeze => self.execute_freeze(stack),
+
+            // Typed array operations
+            Opcode::NewIntArray | Opcode::NewFloatArray => Err(ExecutionError::InvalidOperand(
+                format!("{:?} requires heap access - use execute_with_constants", instruction.opcode())
+            )),
+            Opcode::IntArrayGet => self.execute_int_array_get(stack),
+            Opcode::IntArraySet => self.execute_int_array_set(stack),
+            Opcode::FloatArrayGet => self.execute_float_array_get(stack),
+            Opcode::FloatArraySet => self.execute_float_array_set(stack),
+            Opcode::TypedArrayLength => self.execute_typed_array_length(stack),
+
+            Opcode::Wide => Err(ExecutionError::UnknownOpcode(Opcode::Wide as u8)),
+            Opcode::Halt => return Ok(()),
+        }?;
+        self.program_counter += 1;
+        Ok(())
     }
 
     // Arithmetic implementations
+    // Add, Sub, Mul and the comparisons first try two integers in place,
+    // which is one tag test instead of a match over every pair of types
     fn execute_add(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| a.checked_add(b).map(Value::Integer)) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
 
         let result = match (a, b) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a + b),
+            (Value::Integer(a), Value::Integer(b)) => {
+                Value::Integer(a.checked_add(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
             (Value::Float(a), Value::Float(b)) => Value::Float(a + b),
             (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 + b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a + b as f64),
+            (a, b) if Self::is_decimal_pair(&a, &b) => {
+                let (a, b) = Self::decimal_operands(&a, &b)?;
+                Value::Decimal(a.checked_add(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot add these types".to_string(),
@@ -320,14 +897,23 @@ impl InstructionDispatcher {
     }
 
     fn execute_sub(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| a.checked_sub(b).map(Value::Integer)) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
 
         let result = match (a, b) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a - b),
+            (Value::Integer(a), Value::Integer(b)) => {
+                Value::Integer(a.checked_sub(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
             (Value::Float(a), Value::Float(b)) => Value::Float(a - b),
             (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 - b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a - b as f64),
+            (a, b) if Self::is_decimal_pair(&a, &b) => {
+                let (a, b) = Self::decimal_operands(&a, &b)?;
+                Value::Decimal(a.checked_sub(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot subtract these types".to_string(),
@@ -340,14 +926,23 @@ impl InstructionDispatcher {
     }
 
     fn execute_mul(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| a.checked_mul(b).map(Value::Integer)) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
 
         let result = match (a, b) {
-            (Value::Integer(a), Value::Integer(b)) => Value::Integer(a * b),
+            (Value::Integer(a), Value::Integer(b)) => {
+                Value::Integer(a.checked_mul(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
             (Value::Float(a), Value::Float(b)) => Value::Float(a * b),
             (Value::Integer(a), Value::Float(b)) => Value::Float(a as f64 * b),
             (Value::Float(a), Value::Integer(b)) => Value::Float(a * b as f64),
+            (a, b) if Self::is_decimal_pair(&a, &b) => {
+                let (a, b) = Self::decimal_operands(&a, &b)?;
+                Value::Decimal(a.checked_mul(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot multiply these types".to_string(),
@@ -368,7 +963,7 @@ impl InstructionDispatcher {
                 if b == 0 {
                     return Err(ExecutionError::DivisionByZero);
                 }
-                Value::Integer(a / b)
+                Value::Integer(a.checked_div(b).ok_or(ExecutionError::ArithmeticOverflow)?)
             }
             (Value::Float(a), Value::Float(b)) => {
                 if b == 0.0 {
@@ -388,6 +983,13 @@ impl InstructionDispatcher {
                 }
                 Value::Float(a / b as f64)
             }
+            (a, b) if Self::is_decimal_pair(&a, &b) => {
+                let (a, b) = Self::decimal_operands(&a, &b)?;
+                if b.is_zero() {
+                    return Err(ExecutionError::DivisionByZero);
+                }
+                Value::Decimal(a.checked_div(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot divide these types".to_string(),
@@ -408,11 +1010,34 @@ impl InstructionDispatcher {
                 if b == 0 {
                     return Err(ExecutionError::DivisionByZero);
                 }
-                Value::Integer(a % b)
+                Value::Integer(a.checked_rem(b).ok_or(ExecutionError::ArithmeticOverflow)?)
+            }
+            (a, b) if Self::is_decimal_pair(&a, &b) => {
+                let (a, b) = Self::decimal_operands(&a, &b)?;
+                if b.is_zero() {
+                    return Err(ExecutionError::DivisionByZero);
+                }
+                Value::Decimal(a.checked_rem(b).ok_or(ExecutionError::ArithmeticOverflow)?)
             }
             _ => {
                 return Err(ExecutionError::TypeError(
-                    "Modulo only supported for integers".to_string(),
+                    "Modulo only supported for integers and decimals".to_string(),
+                ));
+            }
+        };
+
+        stack.push(result);
+        Ok(())
+    }
+
+    fn execute_negate(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let result = match stack.pop()? {
+            Value::Integer(a) => Value::Integer(a.checked_neg().ok_or(ExecutionError::ArithmeticOverflow)?),
+            Value::Float(a) => Value::Float(-a),
+            Value::Decimal(a) => Value::Decimal(a.checked_neg().ok_or(ExecutionError::ArithmeticOverflow)?),
+            _ => {
+                return Err(ExecutionError::TypeError(
+                    "Negation only supported for numbers".to_string(),
                 ));
             }
         };
@@ -421,6 +1046,29 @@ impl InstructionDispatcher {
         Ok(())
     }
 
+    fn is_decimal_pair(a: &Value, b: &Value) -> bool {
+        matches!(a, Value::Decimal(_)) || matches!(b, Value::Decimal(_))
+    }
+
+    /// Operands of a decimal operation. Integers are promoted exactly;
+    /// floats are rejected since mixing them in would lose exactness.
+    fn decimal_operands(a: &Value, b: &Value) -> Result<(Decimal, Decimal), ExecutionError> {
+        let promote = |value: &Value| match value {
+            Value::Decimal(d) => Ok(*d),
+            Value::Integer(i) => Decimal::from_integer(*i).ok_or(ExecutionError::ArithmeticOverflow),
+            other => Err(ExecutionError::TypeError(format!(
+                "Cannot mix decimal with {}",
+                other.type_name()
+            ))),
+        };
+        Ok((promote(a)?, promote(b)?))
+    }
+
+    fn decimal_cmp(a: &Value, b: &Value) -> Result<Ordering, ExecutionError> {
+        let (a, b) = Self::decimal_operands(a, b)?;
+        Ok(a.cmp(&b))
+    }
+
     // Stack operations
     fn execute_push(
         &mut self,
@@ -435,6 +1083,49 @@ impl InstructionDispatcher {
         Ok(())
     }
 
+    fn execute_load_constant(
+        &mut self,
+        instruction: &Instruction,
+        stack: &mut OperandStack,
+        constants: &[Value],
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        let index = match instruction.operand() {
+            Some(Value::Integer(index)) => *index,
+            _ => {
+                return Err(ExecutionError::InvalidOperand(
+                    "LoadConstant requires an integer constant index".to_string(),
+                ))
+            }
+        };
+        let constant = usize::try_from(index)
+            .ok()
+            .and_then(|i| constants.get(i))
+            .ok_or_else(|| {
+                ExecutionError::InvalidOperand(format!(
+                    "Constant index {} out of bounds (pool size: {})",
+                    index,
+                    constants.len()
+                ))
+            })?;
+
+        // Arrays in the pool are copied so programs cannot mutate their data
+        let value = match constant {
+            Value::Array(array) => match heap.allocate_array(array.to_vec()) {
+                Ok(copy) => Value::Array(copy),
+                Err(heap_error) => {
+                    return Err(ExecutionError::InvalidOperand(format!(
+                        "Failed to allocate array: {}",
+                        heap_error
+                    )))
+                }
+            },
+            other => other.clone(),
+        };
+        stack.push(value);
+        Ok(())
+    }
+
     fn execute_push_with_constants(
         &mut self,
         instruction: &Instruction,
@@ -509,6 +1200,9 @@ impl InstructionDispatcher {
         let condition = stack.pop()?;
         if condition.is_truthy() {
             self.execute_jump(instruction)?;
+        } else {
+            // Not taken: fall through to the next instruction
+            self.program_counter += 1;
         }
         Ok(())
     }
@@ -521,6 +1215,9 @@ impl InstructionDispatcher {
         let condition = stack.pop()?;
         if !condition.is_truthy() {
             self.execute_jump(instruction)?;
+        } else {
+            // Not taken: fall through to the next instruction
+            self.program_counter += 1;
         }
         Ok(())
     }
@@ -528,45 +1225,141 @@ impl InstructionDispatcher {
     fn execute_call(
         &mut self,
         instruction: &Instruction,
+        stack: &mut OperandStack,
         call_stack: &mut CallStack,
     ) -> Result<(), ExecutionError> {
         if let Some(Value::Integer(function_addr)) = instruction.operand() {
             if *function_addr < 0 {
                 return Err(ExecutionError::InvalidJumpAddress(*function_addr));
             }
-            let return_addr = self.program_counter + 1;
-            let frame = CallFrame::new(*function_addr as usize, return_addr, 0);
-            call_stack.push_unchecked(frame);
-            // Jump to the function address
-            self.program_counter = *function_addr as usize;
+            self.enter_function(*function_addr as usize, stack, call_stack)?;
         } else {
             return Err(ExecutionError::InsufficientOperands);
         }
         Ok(())
     }
 
-    fn execute_return(&mut self, call_stack: &mut CallStack) -> Result<(), ExecutionError> {
+    fn execute_call_indirect(
+        &mut self,
+        stack: &mut OperandStack,
+        call_stack: &mut CallStack,
+    ) -> Result<(), ExecutionError> {
+        // The callee is on top of the stack, with its arguments beneath it
+        match stack.pop()? {
+            Value::Function { address, arity } => {
+                if stack.frame_size() < arity as usize {
+                    return Err(ExecutionError::InsufficientOperands);
+                }
+                self.enter_function(address as usize, stack, call_stack)
+            }
+            other => Err(ExecutionError::TypeError(format!(
+                "Cannot call a value of type {}",
+                other.type_name()
+            ))),
+        }
+    }
+
+    fn enter_function(
+        &mut self,
+        address: usize,
+        stack: &mut OperandStack,
+        call_stack: &mut CallStack,
+    ) -> Result<(), ExecutionError> {
+        if call_stack.depth() >= call_stack.max_depth() {
+            return Err(ExecutionError::CallDepthExceeded(call_stack.max_depth()));
+        }
+        let return_addr = self.program_counter + 1;
+        let frame = match self.functions.get(&address) {
+            Some(function) => {
+                if stack.frame_size() < function.arity {
+                    return Err(ExecutionError::ArityMismatch {
+                        function: function.name.to_string(),
+                        expected: function.arity,
+                        available: stack.frame_size(),
+                    });
+                }
+                // The stack base sits below the arguments, so a return can
+                // check the function's declared stack effect and the
+                // function cannot pop its caller's values
+                let mut frame = CallFrame::new_with_stack_base(
+                    address,
+                    return_addr,
+                    function.locals,
+                    stack.size() - function.arity,
+                );
+                // The first argument pushed becomes local 0
+                for index in (0..function.arity).rev() {
+                    frame.set_local(index, stack.pop()?)?;
+                }
+                frame.set_function_name(Arc::clone(&function.name));
+                frame
+            }
+            // Without a declared arity the callee may take any of its
+            // caller's values
+            None => CallFrame::new_with_stack_base(address, return_addr, 0, stack.base()),
+        };
+        stack.set_base(frame.stack_base());
+        call_stack.push_unchecked(frame);
+        // Jump to the function address
+        self.program_counter = address;
+        Ok(())
+    }
+
+    fn execute_return(
+        &mut self,
+        stack: &mut OperandStack,
+        call_stack: &mut CallStack,
+    ) -> Result<(), ExecutionError> {
         let frame = call_stack.pop()?;
+        stack.set_base(call_stack.current().map_or(0, CallFrame::stack_base));
+        if let Some(function) = self.functions.get(&frame.function_index())
+            && let Some(returns) = function.returns {
+            let actual = stack.size().saturating_sub(frame.stack_base());
+            if actual != returns {
+                return Err(ExecutionError::StackEffectMismatch {
+                    function: function.name.to_string(),
+                    expected: returns,
+                    actual,
+                });
+            }
+        }
         self.program_counter = frame.return_address();
         Ok(())
     }
 
     // Comparison operations
+    // Equal and RefEqual share identity semantics for heap values
     fn execute_equal(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| Some(Value::Boolean(a == b))) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
-        stack.push(Value::Boolean(a == b));
+        stack.push(Value::Boolean(a.ref_equals(&b)));
         Ok(())
     }
 
     fn execute_not_equal(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| Some(Value::Boolean(a != b))) {
+            return Ok(());
+        }
+        let b = stack.pop()?;
+        let a = stack.pop()?;
+        stack.push(Value::Boolean(!a.ref_equals(&b)));
+        Ok(())
+    }
+
+    fn execute_deep_equal(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
         let b = stack.pop()?;
         let a = stack.pop()?;
-        stack.push(Value::Boolean(a != b));
+        stack.push(Value::Boolean(a.deep_equals(&b)));
         Ok(())
     }
 
     fn execute_less_than(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| Some(Value::Boolean(a < b))) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
 
@@ -575,6 +1368,8 @@ impl InstructionDispatcher {
             (Value::Float(a), Value::Float(b)) => a < b,
             (Value::Integer(a), Value::Float(b)) => (a as f64) < b,
             (Value::Float(a), Value::Integer(b)) => a < (b as f64),
+            (Value::Char(a), Value::Char(b)) => a < b,
+            (a, b) if Self::is_decimal_pair(&a, &b) => Self::decimal_cmp(&a, &b)?.is_lt(),
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot compare these types".to_string(),
@@ -587,6 +1382,9 @@ impl InstructionDispatcher {
     }
 
     fn execute_less_equal(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| Some(Value::Boolean(a <= b))) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
 
@@ -595,6 +1393,8 @@ impl InstructionDispatcher {
             (Value::Float(a), Value::Float(b)) => a <= b,
             (Value::Integer(a), Value::Float(b)) => (a as f64) <= b,
             (Value::Float(a), Value::Integer(b)) => a <= (b as f64),
+            (Value::Char(a), Value::Char(b)) => a <= b,
+            (a, b) if Self::is_decimal_pair(&a, &b) => Self::decimal_cmp(&a, &b)?.is_le(),
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot compare these types".to_string(),
@@ -607,6 +1407,9 @@ impl InstructionDispatcher {
     }
 
     fn execute_greater_than(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| Some(Value::Boolean(a > b))) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
 
@@ -615,6 +1418,8 @@ impl InstructionDispatcher {
             (Value::Float(a), Value::Float(b)) => a > b,
             (Value::Integer(a), Value::Float(b)) => (a as f64) > b,
             (Value::Float(a), Value::Integer(b)) => a > (b as f64),
+            (Value::Char(a), Value::Char(b)) => a > b,
+            (a, b) if Self::is_decimal_pair(&a, &b) => Self::decimal_cmp(&a, &b)?.is_gt(),
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot compare these types".to_string(),
@@ -627,6 +1432,9 @@ impl InstructionDispatcher {
     }
 
     fn execute_greater_equal(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        if stack.apply_integers(|a, b| Some(Value::Boolean(a >= b))) {
+            return Ok(());
+        }
         let b = stack.pop()?;
         let a = stack.pop()?;
 
@@ -635,6 +1443,8 @@ impl InstructionDispatcher {
             (Value::Float(a), Value::Float(b)) => a >= b,
             (Value::Integer(a), Value::Float(b)) => (a as f64) >= b,
             (Value::Float(a), Value::Integer(b)) => a >= (b as f64),
+            (Value::Char(a), Value::Char(b)) => a >= b,
+            (a, b) if Self::is_decimal_pair(&a, &b) => Self::decimal_cmp(&a, &b)?.is_ge(),
             _ => {
                 return Err(ExecutionError::TypeError(
                     "Cannot compare these types".to_string(),
@@ -674,6 +1484,14 @@ impl InstructionDispatcher {
         Ok(())
     }
 
+    fn execute_null_coalesce(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        // Pushes a unless it is null, in which case the fallback b
+        let b = stack.pop()?;
+        let a = stack.pop()?;
+        stack.push(if matches!(a, Value::Null) { b } else { a });
+        Ok(())
+    }
+
     // Memory operations
     fn execute_load(
         &mut self,
@@ -751,24 +1569,31 @@ impl InstructionDispatcher {
         }
     }
 
+    /// `GetField` and, with `null_safe`, `SafeGetField`, which propagates a
+    /// null receiver instead of raising a type error
     fn execute_get_field(
         &mut self,
         instruction: &Instruction,
         stack: &mut OperandStack,
+        null_safe: bool,
     ) -> Result<(), ExecutionError> {
+        let opcode = instruction.opcode();
+
         // Get field name from instruction operand
-        let field_name = match instruction.operand() {
-            Some(Value::String(name)) => name.clone(),
-            Some(Value::Integer(index)) => format!("field_{}", index), // Support numeric field names
+        let field_name: Cow<str> = match instruction.operand() {
+            Some(Value::String(name)) => Cow::Borrowed(name),
+            Some(Value::Integer(index)) => Cow::Owned(format!("field_{}", index)), // Support numeric field names
             Some(_) => {
-                return Err(ExecutionError::InvalidOperand(
-                    "GetField instruction requires string or integer operand".to_string(),
-                ))
+                return Err(ExecutionError::InvalidOperand(format!(
+                    "{:?} instruction requires string or integer operand",
+                    opcode
+                )))
             }
             None => {
-                return Err(ExecutionError::InvalidOperand(
-                    "GetField instruction requires operand".to_string(),
-                ))
+                return Err(ExecutionError::InvalidOperand(format!(
+                    "{:?} instruction requires operand",
+                    opcode
+                )))
             }
         };
 
@@ -779,16 +1604,21 @@ impl InstructionDispatcher {
             Value::GcObject(gc_obj) => {
                 // Get field value from object
                 if let Some(field_value) = gc_obj.get_field(&field_name) {
-                    stack.push(field_value.clone());
+                    stack.push(field_value);
                 } else {
                     // Field doesn't exist, push null
                     stack.push(Value::Null);
                 }
                 Ok(())
             }
-            _ => Err(ExecutionError::TypeError(
-                "GetField can only be used on objects".to_string()
-            ))
+            Value::Null if null_safe => {
+                stack.push(Value::Null);
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(format!(
+                "{:?} can only be used on objects",
+                opcode
+            ))),
         }
     }
 
@@ -798,9 +1628,9 @@ impl InstructionDispatcher {
         stack: &mut OperandStack,
     ) -> Result<(), ExecutionError> {
         // Get field name from instruction operand
-        let _field_name = match instruction.operand() {
-            Some(Value::String(name)) => name.clone(),
-            Some(Value::Integer(index)) => format!("field_{}", index), // Support numeric field names
+        let field_name: Cow<str> = match instruction.operand() {
+            Some(Value::String(name)) => Cow::Borrowed(name),
+            Some(Value::Integer(index)) => Cow::Owned(format!("field_{}", index)), // Support numeric field names
             Some(_) => {
                 return Err(ExecutionError::InvalidOperand(
                     "SetField instruction requires string or integer operand".to_string(),
@@ -818,16 +1648,15 @@ impl InstructionDispatcher {
         let object = stack.pop()?;
 
         match object {
+            Value::GcObject(gc_obj) if !gc_obj.is_frozen() => {
+                gc_obj.set_field(field_name, value);
+                Ok(())
+            }
             Value::GcObject(gc_obj) => {
-                // Unfortunately, we can't mutate through GcPtr directly due to shared ownership
-                // In a real implementation, this would require interior mutability (RefCell/Mutex)
-                // For now, we'll push the object back and return an error explaining this limitation
+                // Frozen: push values back in reverse order
                 stack.push(Value::GcObject(gc_obj));
                 stack.push(value);
-                
-                Err(ExecutionError::InvalidOperand(
-                    "SetField not yet implemented - requires interior mutability in GcPtr".to_string()
-                ))
+                Err(ExecutionError::FrozenObject(field_name.into_owned()))
             }
             _ => {
                 // Push values back in reverse order
@@ -839,6 +1668,715 @@ impl InstructionDispatcher {
             }
         }
     }
+
+    // Array operations
+    fn execute_new_array(
+        &mut self,
+        instruction: &Instruction,
+        stack: &mut OperandStack,
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        // Operand is the number of elements to pop; the first pushed becomes index 0
+        let count = match instruction.operand() {
+            Some(Value::Integer(count)) if *count >= 0 => *count as usize,
+            None => 0,
+            Some(_) => {
+                return Err(ExecutionError::InvalidOperand(
+                    "NewArray instruction requires a non-negative integer operand".to_string(),
+                ))
+            }
+        };
+
+        if stack.frame_size() < count {
+            return Err(ExecutionError::InsufficientOperands);
+        }
+
+        let mut elements = Vec::with_capacity(count);
+        for _ in 0..count {
+            elements.push(stack.pop()?);
+        }
+        elements.reverse();
+
+        match heap.allocate_array(elements) {
+            Ok(gc_array) => {
+                stack.push(Value::Array(gc_array));
+                Ok(())
+            }
+            Err(heap_error) => Err(ExecutionError::InvalidOperand(format!(
+                "Failed to allocate array: {}",
+                heap_error
+            ))),
+        }
+    }
+
+    fn execute_array_get(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let index = stack.pop()?;
+        let array = stack.pop()?;
+
+        match (array, index) {
+            (Value::Array(array), Value::Integer(index)) => {
+                let element = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| array.get(i))
+                    .ok_or(ExecutionError::IndexOutOfBounds(index, array.len()))?;
+                stack.push(element);
+                Ok(())
+            }
+            (Value::Array(_), _) => Err(ExecutionError::TypeError(
+                "Array index must be an integer".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "ArrayGet can only be used on arrays".to_string(),
+            )),
+        }
+    }
+
+    fn execute_array_set(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let value = stack.pop()?;
+        let index = stack.pop()?;
+        let array = stack.pop()?;
+
+        match (array, index) {
+            (Value::Array(array), Value::Integer(index)) => {
+                let stored = usize::try_from(index)
+                    .map(|i| array.set(i, value))
+                    .unwrap_or(false);
+                if !stored {
+                    return Err(ExecutionError::IndexOutOfBounds(index, array.len()));
+                }
+                Ok(())
+            }
+            (Value::Array(_), _) => Err(ExecutionError::TypeError(
+                "Array index must be an integer".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "ArraySet can only be used on arrays".to_string(),
+            )),
+        }
+    }
+
+    fn execute_array_length(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        match stack.pop()? {
+            Value::Array(array) => {
+                stack.push(Value::Integer(array.len() as i64));
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "ArrayLength can only be used on arrays".to_string(),
+            )),
+        }
+    }
+
+    // String and character operations
+    fn execute_char_at(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let index = stack.pop()?;
+        let string = stack.pop()?;
+
+        let text = string.as_str().ok_or_else(|| {
+            ExecutionError::TypeError("CharAt can only be used on strings".to_string())
+        })?;
+
+        match index {
+            Value::Integer(index) => {
+                let ch = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| text.chars().nth(i))
+                    .ok_or_else(|| ExecutionError::IndexOutOfBounds(index, text.chars().count()))?;
+                stack.push(Value::Char(ch));
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "String index must be an integer".to_string(),
+            )),
+        }
+    }
+
+    fn execute_char_code(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        match stack.pop()? {
+            Value::Char(ch) => {
+                stack.push(Value::Integer(ch as i64));
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "CharCode can only be used on characters".to_string(),
+            )),
+        }
+    }
+
+    fn execute_code_to_char(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        match stack.pop()? {
+            Value::Integer(code) => {
+                let ch = u32::try_from(code)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        ExecutionError::InvalidOperand(format!("Invalid character code: {}", code))
+                    })?;
+                stack.push(Value::Char(ch));
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "CodeToChar requires an integer code point".to_string(),
+            )),
+        }
+    }
+
+    fn execute_array_sort(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        // Sorts in place and leaves the array on the stack
+        match stack.pop()? {
+            Value::Array(array) => {
+                array.sort();
+                stack.push(Value::Array(array));
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "ArraySort can only be used on arrays".to_string(),
+            )),
+        }
+    }
+
+    fn execute_substring(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let end = stack.pop()?;
+        let start = stack.pop()?;
+        let string = stack.pop()?;
+
+        let (start, end) = match (start, end) {
+            (Value::Integer(start), Value::Integer(end)) => (start, end),
+            _ => {
+                return Err(ExecutionError::TypeError(
+                    "Substring requires integer bounds".to_string(),
+                ))
+            }
+        };
+
+        // Bounds are character indices; convert them to byte offsets
+        let text = string.as_str().ok_or_else(|| {
+            ExecutionError::TypeError("Substring can only be used on strings".to_string())
+        })?;
+        let byte_offset = |index: i64| {
+            let index = usize::try_from(index).ok()?;
+            text.char_indices()
+                .map(|(offset, _)| offset)
+                .chain(std::iter::once(text.len()))
+                .nth(index)
+        };
+        let range = byte_offset(start)
+            .zip(byte_offset(end))
+            .filter(|(start, end)| start <= end);
+        let (start_byte, end_byte) = range.ok_or_else(|| {
+            ExecutionError::InvalidOperand(format!(
+                "Invalid substring range {}..{} (length: {})",
+                start,
+                end,
+                text.chars().count()
+            ))
+        })?;
+
+        let slice = Self::slice_string(&string, start_byte, end_byte)
+            .expect("validated character boundaries");
+        stack.push(Value::StrSlice(slice));
+        Ok(())
+    }
+
+    fn execute_str_concat(
+        &mut self,
+        stack: &mut OperandStack,
+        heap: Option<&mut Heap>,
+    ) -> Result<(), ExecutionError> {
+        let b = stack.pop()?;
+        let a = stack.pop()?;
+        if a.as_str().is_none() && b.as_str().is_none() {
+            return Err(ExecutionError::TypeError(
+                "StrConcat requires at least one string operand".to_string(),
+            ));
+        }
+
+        let Some(heap) = heap else {
+            // A non-string operand is concatenated as it would be printed
+            stack.push(Value::String(VmString::from(format!("{}{}", a, b))));
+            return Ok(());
+        };
+        // Refuse before building a string the heap could never hold
+        let length = a.as_str().map_or(0, str::len) + b.as_str().map_or(0, str::len);
+        let result = if heap.has_room(length + std::mem::size_of::<String>()) {
+            heap.allocate_str(format!("{}{}", a, b))
+        } else {
+            Err(HeapError::OutOfMemory)
+        };
+        match result {
+            Ok(string) => {
+                stack.push(Value::String(string));
+                Ok(())
+            }
+            Err(heap_error) => Err(ExecutionError::InvalidOperand(format!(
+                "Failed to allocate string: {}",
+                heap_error
+            ))),
+        }
+    }
+
+    fn execute_split(
+        &mut self,
+        stack: &mut OperandStack,
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        let separator = stack.pop()?;
+        let string = stack.pop()?;
+
+        let (text, separator_text) = match (string.as_str(), separator.as_str()) {
+            (Some(text), Some(separator)) => (text, separator),
+            _ => {
+                return Err(ExecutionError::TypeError(
+                    "Split requires a string and a string separator".to_string(),
+                ))
+            }
+        };
+        if separator_text.is_empty() {
+            return Err(ExecutionError::InvalidOperand(
+                "Split separator cannot be empty".to_string(),
+            ));
+        }
+
+        let base = text.as_ptr() as usize;
+        let pieces = text
+            .split(separator_text)
+            .map(|piece| {
+                let start = piece.as_ptr() as usize - base;
+                let slice = Self::slice_string(&string, start, start + piece.len())
+                    .expect("split pieces fall on character boundaries");
+                Value::StrSlice(slice)
+            })
+            .collect();
+
+        match heap.allocate_array(pieces) {
+            Ok(gc_array) => {
+                stack.push(Value::Array(gc_array));
+                Ok(())
+            }
+            Err(heap_error) => Err(ExecutionError::InvalidOperand(format!(
+                "Failed to allocate array: {}",
+                heap_error
+            ))),
+        }
+    }
+
+    /// Zero-copy view of `start..end` (byte offsets) of a string-like value
+    fn slice_string(string: &Value, start: usize, end: usize) -> Option<GcStr> {
+        match string {
+            Value::String(s) => GcStr::new(StrSource::Plain(s.clone()), start, end),
+            Value::GcString(s) => GcStr::new(StrSource::Gc(s.clone()), start, end),
+            Value::StrSlice(s) => s.slice(start, end),
+            _ => None,
+        }
+    }
+
+    // Object operations
+    fn execute_freeze(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        // Freezes the object in place and leaves it on the stack
+        match stack.peek()? {
+            Value::GcObject(gc_obj) => {
+                gc_obj.freeze();
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "Freeze can only be used on objects".to_string(),
+            )),
+        }
+    }
+
+    // Typed array operations
+    fn pop_typed_array_length(
+        opcode: Opcode,
+        stack: &mut OperandStack,
+    ) -> Result<usize, ExecutionError> {
+        match stack.pop()? {
+            Value::Integer(length) if length >= 0 => Ok(length as usize),
+            Value::Integer(length) => Err(ExecutionError::InvalidOperand(format!(
+                "Negative typed array length: {}",
+                length
+            ))),
+            _ => Err(ExecutionError::TypeError(format!(
+                "{:?} requires an integer length",
+                opcode
+            ))),
+        }
+    }
+
+    /// `length` zeroed elements for a new heap object, allocated only if
+    /// the heap has room for them, so a huge length fails rather than
+    /// aborting the process
+    fn zeroed<T: Clone + Default>(
+        length: usize,
+        heap: &Heap,
+        what: &str,
+    ) -> Result<Vec<T>, ExecutionError> {
+        let fits = length.checked_mul(std::mem::size_of::<T>()).is_some_and(|bytes| {
+            heap.max_heap_size()
+                .is_none_or(|max| heap.current_heap_size().saturating_add(bytes) <= max)
+        });
+        let mut elements = Vec::new();
+        if !fits || elements.try_reserve_exact(length).is_err() {
+            return Err(ExecutionError::InvalidOperand(format!(
+                "Failed to allocate {}: {}",
+                what,
+                HeapError::OutOfMemory
+            )));
+        }
+        elements.resize(length, T::default());
+        Ok(elements)
+    }
+
+    fn execute_new_int_array(
+        &mut self,
+        stack: &mut OperandStack,
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        let length = Self::pop_typed_array_length(Opcode::NewIntArray, stack)?;
+        let elements: Vec<i64> = Self::zeroed(length, heap, "int array")?;
+        match heap.allocate_typed_array(elements) {
+            Ok(gc_array) => {
+                stack.push(Value::IntArray(gc_array));
+                Ok(())
+            }
+            Err(heap_error) => Err(ExecutionError::InvalidOperand(format!(
+                "Failed to allocate int array: {}",
+                heap_error
+            ))),
+        }
+    }
+
+    fn execute_new_float_array(
+        &mut self,
+        stack: &mut OperandStack,
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        let length = Self::pop_typed_array_length(Opcode::NewFloatArray, stack)?;
+        let elements: Vec<f64> = Self::zeroed(length, heap, "float array")?;
+        match heap.allocate_typed_array(elements) {
+            Ok(gc_array) => {
+                stack.push(Value::FloatArray(gc_array));
+                Ok(())
+            }
+            Err(heap_error) => Err(ExecutionError::InvalidOperand(format!(
+                "Failed to allocate float array: {}",
+                heap_error
+            ))),
+        }
+    }
+
+    fn execute_int_array_get(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let index = stack.pop()?;
+        let array = stack.pop()?;
+
+        match (array, index) {
+            (Value::IntArray(array), Value::Integer(index)) => {
+                let element = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| array.get(i))
+                    .ok_or(ExecutionError::IndexOutOfBounds(index, array.len()))?;
+                stack.push(Value::Integer(element));
+                Ok(())
+            }
+            (Value::IntArray(_), _) => Err(ExecutionError::TypeError(
+                "Array index must be an integer".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "IntArrayGet can only be used on int arrays".to_string(),
+            )),
+        }
+    }
+
+    fn execute_int_array_set(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let value = stack.pop()?;
+        let index = stack.pop()?;
+        let array = stack.pop()?;
+
+        match (array, index, value) {
+            (Value::IntArray(array), Value::Integer(index), Value::Integer(value)) => {
+                let stored = usize::try_from(index)
+                    .map(|i| array.set(i, value))
+                    .unwrap_or(false);
+                if !stored {
+                    return Err(ExecutionError::IndexOutOfBounds(index, array.len()));
+                }
+                Ok(())
+            }
+            (Value::IntArray(_), _, _) => Err(ExecutionError::TypeError(
+                "IntArraySet requires integer index and value".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "IntArraySet can only be used on int arrays".to_string(),
+            )),
+        }
+    }
+
+    fn execute_float_array_get(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let index = stack.pop()?;
+        let array = stack.pop()?;
+
+        match (array, index) {
+            (Value::FloatArray(array), Value::Integer(index)) => {
+                let element = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| array.get(i))
+                    .ok_or(ExecutionError::IndexOutOfBounds(index, array.len()))?;
+                stack.push(Value::Float(element));
+                Ok(())
+            }
+            (Value::FloatArray(_), _) => Err(ExecutionError::TypeError(
+                "Array index must be an integer".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "FloatArrayGet can only be used on float arrays".to_string(),
+            )),
+        }
+    }
+
+    fn execute_float_array_set(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let value = stack.pop()?;
+        let index = stack.pop()?;
+        let array = stack.pop()?;
+
+        // Integers are widened, matching mixed integer/float arithmetic
+        let value = match value {
+            Value::Float(f) => Some(f),
+            Value::Integer(i) => Some(i as f64),
+            _ => None,
+        };
+
+        match (array, index, value) {
+            (Value::FloatArray(array), Value::Integer(index), Some(value)) => {
+                let stored = usize::try_from(index)
+                    .map(|i| array.set(i, value))
+                    .unwrap_or(false);
+                if !stored {
+                    return Err(ExecutionError::IndexOutOfBounds(index, array.len()));
+                }
+                Ok(())
+            }
+            (Value::FloatArray(_), _, _) => Err(ExecutionError::TypeError(
+                "FloatArraySet requires an integer index and a numeric value".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "FloatArraySet can only be used on float arrays".to_string(),
+            )),
+        }
+    }
+
+    fn execute_typed_array_length(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let length = match stack.pop()? {
+            Value::IntArray(array) => array.len(),
+            Value::FloatArray(array) => array.len(),
+            _ => {
+                return Err(ExecutionError::TypeError(
+                    "TypedArrayLength can only be used on typed arrays".to_string(),
+                ))
+            }
+        };
+        stack.push(Value::Integer(length as i64));
+        Ok(())
+    }
+
+    // Tuple operations
+    fn execute_make_tuple(
+        &mut self,
+        instruction: &Instruction,
+        stack: &mut OperandStack,
+    ) -> Result<(), ExecutionError> {
+        // Operand is the element count; the first pushed becomes element 0
+        let count = match instruction.operand() {
+            Some(Value::Integer(count)) if (0..=MAX_TUPLE_LEN as i64).contains(count) => {
+                *count as usize
+            }
+            _ => {
+                return Err(ExecutionError::InvalidOperand(format!(
+                    "MakeTuple requires an element count between 0 and {}",
+                    MAX_TUPLE_LEN
+                )))
+            }
+        };
+
+        if stack.frame_size() < count {
+            return Err(ExecutionError::InsufficientOperands);
+        }
+
+        let mut elements: [Value; MAX_TUPLE_LEN] = std::array::from_fn(|_| Value::Null);
+        for slot in elements[..count].iter_mut().rev() {
+            *slot = stack.pop()?;
+        }
+
+        let tuple = VmTuple::new(elements.into_iter().take(count))
+            .expect("count checked against MAX_TUPLE_LEN");
+        stack.push(Value::Tuple(tuple));
+        Ok(())
+    }
+
+    fn execute_tuple_get(
+        &mut self,
+        instruction: &Instruction,
+        stack: &mut OperandStack,
+    ) -> Result<(), ExecutionError> {
+        let index = match instruction.operand() {
+            Some(Value::Integer(index)) => *index,
+            _ => {
+                return Err(ExecutionError::InvalidOperand(
+                    "TupleGet requires an integer index operand".to_string(),
+                ))
+            }
+        };
+
+        match stack.pop()? {
+            Value::Tuple(tuple) => {
+                let element = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| tuple.get(i))
+                    .ok_or(ExecutionError::IndexOutOfBounds(index, tuple.len()))?;
+                stack.push(element.clone());
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "TupleGet can only be used on tuples".to_string(),
+            )),
+        }
+    }
+
+    // Byte buffer operations
+    fn execute_new_bytes(
+        &mut self,
+        stack: &mut OperandStack,
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        let length = match stack.pop()? {
+            Value::Integer(length) if length >= 0 => length as usize,
+            Value::Integer(length) => {
+                return Err(ExecutionError::InvalidOperand(format!(
+                    "Negative byte buffer length: {}",
+                    length
+                )))
+            }
+            _ => {
+                return Err(ExecutionError::TypeError(
+                    "NewBytes requires an integer length".to_string(),
+                ))
+            }
+        };
+
+        let bytes = Self::zeroed(length, heap, "byte buffer")?;
+        self.push_bytes(bytes, stack, heap)
+    }
+
+    fn execute_bytes_get(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let index = stack.pop()?;
+        let buffer = stack.pop()?;
+
+        match (buffer, index) {
+            (Value::Bytes(buffer), Value::Integer(index)) => {
+                let byte = usize::try_from(index)
+                    .ok()
+                    .and_then(|i| buffer.get(i))
+                    .ok_or(ExecutionError::IndexOutOfBounds(index, buffer.len()))?;
+                stack.push(Value::Integer(byte as i64));
+                Ok(())
+            }
+            (Value::Bytes(_), _) => Err(ExecutionError::TypeError(
+                "Byte index must be an integer".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "BytesGet can only be used on byte buffers".to_string(),
+            )),
+        }
+    }
+
+    fn execute_bytes_set(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        let value = stack.pop()?;
+        let index = stack.pop()?;
+        let buffer = stack.pop()?;
+
+        match (buffer, index, value) {
+            (Value::Bytes(buffer), Value::Integer(index), Value::Integer(value)) => {
+                let byte = u8::try_from(value).map_err(|_| {
+                    ExecutionError::InvalidOperand(format!("Value {} does not fit in a byte", value))
+                })?;
+                let stored = usize::try_from(index)
+                    .map(|i| buffer.set(i, byte))
+                    .unwrap_or(false);
+                if !stored {
+                    return Err(ExecutionError::IndexOutOfBounds(index, buffer.len()));
+                }
+                Ok(())
+            }
+            (Value::Bytes(_), _, _) => Err(ExecutionError::TypeError(
+                "BytesSet requires integer index and value".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "BytesSet can only be used on byte buffers".to_string(),
+            )),
+        }
+    }
+
+    fn execute_bytes_length(&mut self, stack: &mut OperandStack) -> Result<(), ExecutionError> {
+        match stack.pop()? {
+            Value::Bytes(buffer) => {
+                stack.push(Value::Integer(buffer.len() as i64));
+                Ok(())
+            }
+            _ => Err(ExecutionError::TypeError(
+                "BytesLength can only be used on byte buffers".to_string(),
+            )),
+        }
+    }
+
+    fn execute_bytes_slice(
+        &mut self,
+        stack: &mut OperandStack,
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        let end = stack.pop()?;
+        let start = stack.pop()?;
+        let buffer = stack.pop()?;
+
+        match (buffer, start, end) {
+            (Value::Bytes(buffer), Value::Integer(start), Value::Integer(end)) => {
+                let slice = usize::try_from(start)
+                    .ok()
+                    .zip(usize::try_from(end).ok())
+                    .and_then(|(start, end)| buffer.slice(start, end))
+                    .ok_or_else(|| {
+                        ExecutionError::InvalidOperand(format!(
+                            "Invalid slice range {}..{} (length: {})",
+                            start,
+                            end,
+                            buffer.len()
+                        ))
+                    })?;
+                self.push_bytes(slice, stack, heap)
+            }
+            (Value::Bytes(_), _, _) => Err(ExecutionError::TypeError(
+                "BytesSlice requires integer bounds".to_string(),
+            )),
+            _ => Err(ExecutionError::TypeError(
+                "BytesSlice can only be used on byte buffers".to_string(),
+            )),
+        }
+    }
+
+    fn push_bytes(
+        &mut self,
+        bytes: Vec<u8>,
+        stack: &mut OperandStack,
+        heap: &mut Heap,
+    ) -> Result<(), ExecutionError> {
+        match heap.allocate_bytes(bytes) {
+            Ok(gc_bytes) => {
+                stack.push(Value::Bytes(gc_bytes));
+                Ok(())
+            }
+            Err(heap_error) => Err(ExecutionError::InvalidOperand(format!(
+                "Failed to allocate byte buffer: {}",
+                heap_error
+            ))),
+        }
+    }
 }
 
 impl Default for InstructionDispatcher {
@@ -856,9 +2394,11 @@ mod tests {
         let opcodes = [
             Opcode::Add,
             Opcode::Sub,
+            Opcode::Negate,
             Opcode::Push,
             Opcode::Jump,
             Opcode::Equal,
+            Opcode::Wide,
         ];
 
         for opcode in opcodes {
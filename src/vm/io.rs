@@ -0,0 +1,157 @@
+//! Where guest programs read and write text.
+//!
+//! The `PRINT` and `READ_LINE` opcodes, and the `print`/`println` host
+//! functions [`VmBuilder::with_io`] registers, go through the [`VmIo`] the
+//! VM was given instead of the process streams, so a host can capture or
+//! redirect them.
+//!
+//! [`VmBuilder::with_io`]: crate::vm::runtime::VmBuilder::with_io
+
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Text streams of a guest program. `Send` so the VM that owns them can
+/// move between threads.
+pub trait VmIo: Send {
+    fn write_stdout(&mut self, text: &str) -> io::Result<()>;
+
+    fn write_stderr(&mut self, text: &str) -> io::Result<()>;
+
+    /// Next line of input without its line ending, or `None` at the end
+    /// of input
+    fn read_line(&mut self) -> io::Result<Option<String>>;
+}
+
+/// The process's own standard streams; what a VM uses unless told
+/// otherwise
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdIo;
+
+impl VmIo for StdIo {
+    fn write_stdout(&mut self, text: &str) -> io::Result<()> {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(text.as_bytes())?;
+        stdout.flush()
+    }
+
+    fn write_stderr(&mut self, text: &str) -> io::Result<()> {
+        io::stderr().lock().write_all(text.as_bytes())
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if io::stdin().lock().read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(trim_line_ending(line)))
+    }
+}
+
+/// Sends standard output to a [`Write`] sink, standard error to the
+/// process's, and gives no input
+pub struct WriterIo<W> {
+    sink: W,
+}
+
+impl<W: Write> WriterIo<W> {
+    pub fn new(sink: W) -> Self {
+        Self { sink }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+impl<W: Write + Send> VmIo for WriterIo<W> {
+    fn write_stdout(&mut self, text: &str) -> io::Result<()> {
+        self.sink.write_all(text.as_bytes())
+    }
+
+    fn write_stderr(&mut self, text: &str) -> io::Result<()> {
+        io::stderr().lock().write_all(text.as_bytes())
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+/// In-memory streams: input given up front and output kept for the host
+/// to read. Clones share the same streams, so one clone can be handed to
+/// the VM and another kept to look at what the program printed.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryIo {
+    streams: Arc<Mutex<Streams>>,
+}
+
+#[derive(Debug, Default)]
+struct Streams {
+    input: VecDeque<String>,
+    stdout: String,
+    stderr: String,
+}
+
+impl MemoryIo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Streams whose input is the lines of `input`
+    pub fn with_input(input: &str) -> Self {
+        let io = Self::new();
+        io.push_input(input);
+        io
+    }
+
+    /// Adds the lines of `input` to the end of the input
+    pub fn push_input(&self, input: &str) {
+        self.streams().input.extend(input.lines().map(str::to_string));
+    }
+
+    /// Everything written to standard output so far
+    pub fn stdout(&self) -> String {
+        self.streams().stdout.clone()
+    }
+
+    pub fn stderr(&self) -> String {
+        self.streams().stderr.clone()
+    }
+
+    /// Empties standard output, returning what was in it
+    pub fn take_stdout(&self) -> String {
+        std::mem::take(&mut self.streams().stdout)
+    }
+
+    fn streams(&self) -> MutexGuard<'_, Streams> {
+        // The streams are left consistent even by a writer that panicked
+        self.streams.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl VmIo for MemoryIo {
+    fn write_stdout(&mut self, text: &str) -> io::Result<()> {
+        self.streams().stdout.push_str(text);
+        Ok(())
+    }
+
+    fn write_stderr(&mut self, text: &str) -> io::Result<()> {
+        self.streams().stderr.push_str(text);
+        Ok(())
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        Ok(self.streams().input.pop_front())
+    }
+}
+
+fn trim_line_ending(mut line: String) -> String {
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+    line
+}
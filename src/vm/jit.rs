@@ -1,7 +1,9 @@
 use crate::vm::instruction::Opcode;
+use crate::vm::module::LineTable;
 use crate::vm::types::Value;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+#[cfg(feature = "serde")]
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -199,8 +201,17 @@ impl HotSpotProfiler {
     }
     
     // Function execution tracking
+
+    /// Counts a call to the function at `function_id`. Nothing is compiled
+    /// when it turns hot; with the `tracing` feature, reaching the
+    /// threshold is reported as a hot-spot event.
     pub fn record_function_entry(&mut self, function_id: usize) {
-        *self.function_counts.entry(function_id).or_insert(0) += 1;
+        let count = self.function_counts.entry(function_id).or_insert(0);
+        *count += 1;
+        #[cfg(feature = "tracing")]
+        if *count == self.function_threshold {
+            tracing::debug!(function = function_id, count = *count, "hot spot: function");
+        }
         self.total_executions += 1;
     }
     
@@ -217,8 +228,17 @@ impl HotSpotProfiler {
     }
     
     // Loop execution tracking
+
+    /// Counts a backward jump to `loop_pc`, reported like
+    /// [`record_function_entry`](Self::record_function_entry) when the loop
+    /// turns hot
     pub fn record_loop_iteration(&mut self, loop_pc: usize) {
-        *self.loop_counts.entry(loop_pc).or_insert(0) += 1;
+        let count = self.loop_counts.entry(loop_pc).or_insert(0);
+        *count += 1;
+        #[cfg(feature = "tracing")]
+        if *count == self.loop_threshold {
+            tracing::debug!(pc = loop_pc, count = *count, "hot spot: loop");
+        }
         self.total_executions += 1;
     }
     
@@ -246,6 +266,25 @@ impl HotSpotProfiler {
     pub fn get_type_profile(&self, pc: usize) -> Option<&TypeProfile> {
         self.type_profiles.get(&pc)
     }
+
+    /// Sites whose operands were integers at least `threshold` of the time,
+    /// in order: those the dispatcher's integer fast path serves, and that
+    /// a compiler could specialize to machine arithmetic
+    pub fn integer_sites(&self, threshold: f64) -> Vec<usize> {
+        let mut sites: Vec<usize> = self
+            .type_profiles
+            .iter()
+            .filter(|(_, profile)| {
+                profile.total_observations() > 0
+                    && profile.get_type_frequency("integer") as f64
+                        / profile.total_observations() as f64
+                        >= threshold
+            })
+            .map(|(&pc, _)| pc)
+            .collect();
+        sites.sort_unstable();
+        sites
+    }
     
     // Branch profiling
     pub fn record_branch_taken(&mut self, pc: usize, taken: bool) {
@@ -277,6 +316,18 @@ impl HotSpotProfiler {
             .filter(|profile| profile.execution_count >= threshold)
             .collect()
     }
+
+    /// Instruction executions per source line, for instructions that `lines`
+    /// locates
+    pub fn line_counts(&self, lines: &LineTable) -> BTreeMap<usize, u64> {
+        let mut counts = BTreeMap::new();
+        for profile in self.instruction_profiles.values() {
+            if let Some(line) = lines.line_at(profile.pc) {
+                *counts.entry(line).or_insert(0) += profile.execution_count;
+            }
+        }
+        counts
+    }
     
     // Optimization level suggestions
     pub fn suggested_optimization_level(&self, function_id: usize) -> OptimizationLevel {
@@ -312,6 +363,21 @@ impl HotSpotProfiler {
         self.total_executions
     }
     
+    // Reset all profiling data
+    pub fn reset(&mut self) {
+        self.function_counts.clear();
+        self.loop_counts.clear();
+        self.type_profiles.clear();
+        self.branch_profiles.clear();
+        self.instruction_profiles.clear();
+        self.deoptimization_counts.clear();
+        self.deoptimization_reasons.clear();
+        self.total_executions = 0;
+    }
+}
+
+#[cfg(feature = "serde")]
+impl HotSpotProfiler {
     // Profile data export/import
     pub fn export_profile_data(&self) -> String {
         let data = ProfileData {
@@ -319,6 +385,7 @@ impl HotSpotProfiler {
             loop_counts: self.loop_counts.clone(),
             type_profiles: self.serialize_type_profiles(),
             branch_profiles: self.serialize_branch_profiles(),
+            instruction_profiles: self.serialize_instruction_profiles(),
         };
         
         serde_json::to_string(&data).unwrap_or_else(|_| "{}".to_string())
@@ -332,22 +399,11 @@ impl HotSpotProfiler {
         self.loop_counts = profile_data.loop_counts;
         self.deserialize_type_profiles(profile_data.type_profiles);
         self.deserialize_branch_profiles(profile_data.branch_profiles);
+        self.deserialize_instruction_profiles(profile_data.instruction_profiles);
         
         Ok(())
     }
     
-    // Reset all profiling data
-    pub fn reset(&mut self) {
-        self.function_counts.clear();
-        self.loop_counts.clear();
-        self.type_profiles.clear();
-        self.branch_profiles.clear();
-        self.instruction_profiles.clear();
-        self.deoptimization_counts.clear();
-        self.deoptimization_reasons.clear();
-        self.total_executions = 0;
-    }
-    
     // Helper methods for serialization
     fn serialize_type_profiles(&self) -> HashMap<String, HashMap<String, u64>> {
         let mut result = HashMap::new();
@@ -388,6 +444,25 @@ impl HotSpotProfiler {
             }
         }
     }
+
+    fn serialize_instruction_profiles(&self) -> HashMap<String, (u8, u64)> {
+        let mut result = HashMap::new();
+        for (pc, profile) in &self.instruction_profiles {
+            result.insert(pc.to_string(), (profile.opcode as u8, profile.execution_count));
+        }
+        result
+    }
+
+    fn deserialize_instruction_profiles(&mut self, data: HashMap<String, (u8, u64)>) {
+        self.instruction_profiles.clear();
+        for (pc_str, (opcode, count)) in data {
+            if let (Ok(pc), Some(opcode)) = (pc_str.parse::<usize>(), Opcode::from_u8(opcode)) {
+                let mut profile = ProfiledInstruction::new(pc, opcode);
+                profile.execution_count = count;
+                self.instruction_profiles.insert(pc, profile);
+            }
+        }
+    }
 }
 
 impl Default for HotSpotProfiler {
@@ -397,10 +472,15 @@ impl Default for HotSpotProfiler {
 }
 
 // Serialization support
+#[cfg(feature = "serde")]
 #[derive(Serialize, Deserialize)]
 struct ProfileData {
     function_counts: HashMap<usize, u64>,
     loop_counts: HashMap<usize, u64>,
     type_profiles: HashMap<String, HashMap<String, u64>>,
     branch_profiles: HashMap<String, (u64, u64)>,
+    /// Opcode and execution count by address; missing from profiles
+    /// exported before instructions were included
+    #[serde(default)]
+    instruction_profiles: HashMap<String, (u8, u64)>,
 }
\ No newline at end of file
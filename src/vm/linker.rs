@@ -0,0 +1,318 @@
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::module::{BytecodeModule, DebugInfo, ExternRef, FileEntry, LineTable, SymbolTable};
+use crate::vm::types::Value;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// Reference to an external symbol that no linked module defines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedExtern {
+    pub name: String,
+    /// Index of the referring module, in the order modules were added
+    pub module: usize,
+    /// Address of the reference within that module
+    pub address: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    NoModules,
+    /// Two modules export a function or global with the same name
+    DuplicateSymbol {
+        name: String,
+        first: usize,
+        second: usize,
+    },
+    UnresolvedExterns(Vec<UnresolvedExtern>),
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LinkError::NoModules => write!(f, "No modules to link"),
+            LinkError::DuplicateSymbol { name, first, second } => write!(
+                f,
+                "Symbol {} is exported by both module {} and module {}",
+                name, first, second
+            ),
+            LinkError::UnresolvedExterns(unresolved) => {
+                write!(f, "Unresolved external symbols:")?;
+                for reference in unresolved {
+                    write!(
+                        f,
+                        " {} (module {}, address {})",
+                        reference.name, reference.module, reference.address
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// What an exported symbol resolves to in the linked program
+#[derive(Clone, Copy)]
+enum Export {
+    /// Address of a function
+    Function(usize),
+    /// Index of a constant
+    Global(usize),
+}
+
+/// Merges separately assembled modules into one program.
+///
+/// Modules are laid out in the order they are added, so the first one holds
+/// the entry point. Exported functions and globals are the symbols: each
+/// `.extern` reference is patched with the address of the exported function
+/// of that name, or for `LDC`, the pool index of the exported global. A
+/// module that exports no functions explicitly exports all its functions.
+pub struct Linker {
+    modules: Vec<BytecodeModule>,
+    allow_unresolved: bool,
+}
+
+impl Linker {
+    pub fn new() -> Self {
+        Self {
+            modules: Vec::new(),
+            allow_unresolved: false,
+        }
+    }
+
+    pub fn add(&mut self, module: BytecodeModule) -> &mut Self {
+        self.modules.push(module);
+        self
+    }
+
+    /// Whether references no module defines are kept as externs of the
+    /// linked module, for the VM to resolve against its host functions and
+    /// globals when loading it, instead of failing the link
+    pub fn allow_unresolved(&mut self, allow: bool) -> &mut Self {
+        self.allow_unresolved = allow;
+        self
+    }
+
+    pub fn link(&self) -> Result<BytecodeModule, LinkError> {
+        if self.modules.is_empty() {
+            return Err(LinkError::NoModules);
+        }
+
+        let mut code_offsets = Vec::with_capacity(self.modules.len());
+        let mut constant_offsets = Vec::with_capacity(self.modules.len());
+        let (mut code_size, mut pool_size) = (0, 0);
+        for module in &self.modules {
+            code_offsets.push(code_size);
+            constant_offsets.push(pool_size);
+            code_size += module.instructions.len();
+            pool_size += module.constants.len();
+        }
+
+        // Exported functions and globals at their final locations
+        let mut exports: HashMap<&str, (usize, Export)> = HashMap::new();
+        for (index, module) in self.modules.iter().enumerate() {
+            let functions = module.exports().map(|function| {
+                (&function.name, Export::Function(function.address + code_offsets[index]))
+            });
+            let globals = module
+                .globals
+                .iter()
+                .map(|(name, &constant)| (name, Export::Global(constant + constant_offsets[index])));
+            for (name, export) in functions.chain(globals) {
+                if let Some(&(first, _)) = exports.get(name.as_str()) {
+                    return Err(LinkError::DuplicateSymbol {
+                        name: name.clone(),
+                        first,
+                        second: index,
+                    });
+                }
+                exports.insert(name, (index, export));
+            }
+        }
+
+        let mut instructions = Vec::with_capacity(code_size);
+        let mut constants: Vec<Value> = Vec::with_capacity(pool_size);
+        let mut functions = Vec::new();
+        let mut symbols = SymbolTable::default();
+        let mut globals = BTreeMap::new();
+        let mut externs = Vec::new();
+        let mut lines = Vec::with_capacity(code_size);
+        let mut files: Vec<FileEntry> = Vec::new();
+        let mut unresolved = Vec::new();
+        // Integer literals pushed by modules that had no pool of their own
+        let mut literal_pushes = Vec::new();
+
+        for (index, module) in self.modules.iter().enumerate() {
+            let code_offset = code_offsets[index];
+            let constant_offset = constant_offsets[index];
+
+            let mut extern_targets = HashMap::new();
+            for reference in &module.externs {
+                let loads = module.instructions.get(reference.address).map(Instruction::opcode)
+                    == Some(Opcode::LoadConstant);
+                match exports.get(reference.name.as_str()) {
+                    Some(&(_, Export::Function(address))) if !loads => {
+                        extern_targets.insert(reference.address, address);
+                    }
+                    Some(&(_, Export::Global(constant))) if loads => {
+                        extern_targets.insert(reference.address, constant);
+                    }
+                    _ => {
+                        unresolved.push(UnresolvedExtern {
+                            name: reference.name.clone(),
+                            module: index,
+                            address: reference.address,
+                        });
+                        externs.push(ExternRef {
+                            name: reference.name.clone(),
+                            address: reference.address + code_offset,
+                        });
+                    }
+                }
+            }
+            let extern_addresses: HashSet<usize> =
+                module.externs.iter().map(|reference| reference.address).collect();
+
+            for (address, instruction) in module.instructions.iter().enumerate() {
+                let relocated = match (instruction.opcode(), instruction.operand()) {
+                    _ if extern_addresses.contains(&address) => {
+                        let target = extern_targets.get(&address).copied().unwrap_or(0);
+                        Some(Value::Integer(target as i64))
+                    }
+                    (
+                        Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Call,
+                        Some(Value::Integer(target)),
+                    ) => Some(Value::Integer(target + code_offset as i64)),
+                    (Opcode::Push, Some(Value::Integer(value))) if module.constants.is_empty() => {
+                        literal_pushes.push((instructions.len(), *value));
+                        Some(Value::Integer(*value))
+                    }
+                    (
+                        Opcode::Push
+                        | Opcode::LoadConstant
+                        | Opcode::LoadGlobal
+                        | Opcode::StoreGlobal,
+                        Some(Value::Integer(index)),
+                    ) => {
+                        Some(Value::Integer(index + constant_offset as i64))
+                    }
+                    (_, Some(value)) => Some(Self::relocate_value(value, code_offset)),
+                    (_, None) => None,
+                };
+                instructions.push(Instruction::new(instruction.opcode(), relocated));
+                lines.push(module.debug_info.lines.line_at(address));
+            }
+
+            // Each module's files start where its code does, so code from a
+            // module without file names is not attributed to the one before
+            let module_files = &module.debug_info.files;
+            if module_files.first().is_none_or(|entry| entry.address > 0) {
+                files.push(FileEntry { address: code_offset, name: None });
+            }
+            files.extend(module_files.iter().map(|entry| FileEntry {
+                address: entry.address + code_offset,
+                name: entry.name.clone(),
+            }));
+
+            constants.extend(
+                module
+                    .constants
+                    .iter()
+                    .map(|constant| Self::relocate_value(constant, code_offset)),
+            );
+
+            for function in &module.functions {
+                let mut function = function.clone();
+                function.address += code_offset;
+                functions.push(function);
+            }
+            for (name, range) in &module.symbols.functions {
+                symbols
+                    .functions
+                    .insert(name.clone(), range.start + code_offset..range.end + code_offset);
+            }
+            // Labels and constant names are private, so the first module to
+            // use a name keeps it
+            for (name, &address) in &module.symbols.labels {
+                symbols.labels.entry(name.clone()).or_insert(address + code_offset);
+            }
+            for (name, &index) in &module.symbols.constants {
+                symbols.constants.entry(name.clone()).or_insert(index + constant_offset);
+            }
+            for (name, &index) in &module.globals {
+                globals.insert(name.clone(), index + constant_offset);
+            }
+        }
+
+        if !unresolved.is_empty() && !self.allow_unresolved {
+            return Err(LinkError::UnresolvedExterns(unresolved));
+        }
+
+        // With a non-empty pool, PUSH n loads constant n, so literals from
+        // modules without a pool of their own are pooled as well
+        if !constants.is_empty() {
+            for (position, literal) in literal_pushes {
+                let value = Value::Integer(literal);
+                let index = match constants[pool_size..].iter().position(|c| *c == value) {
+                    Some(offset) => pool_size + offset,
+                    None => {
+                        constants.push(value);
+                        constants.len() - 1
+                    }
+                };
+                instructions[position] =
+                    Instruction::new(Opcode::Push, Some(Value::Integer(index as i64)));
+            }
+        }
+
+        // Modules often share constants, such as the standard library's
+        let mut linked = BytecodeModule::new(instructions, constants)
+            .with_functions(functions)
+            .with_symbols(symbols)
+            .with_debug_info(DebugInfo {
+                lines: LineTable::from_lines(lines),
+                files: Self::merge_runs(files),
+            })
+            .with_externs(externs)
+            .with_globals(globals);
+        linked.dedup_constants();
+        Ok(linked)
+    }
+
+    /// `files` without entries that repeat the name before them or that
+    /// no instruction falls in
+    fn merge_runs(files: Vec<FileEntry>) -> Vec<FileEntry> {
+        let mut merged: Vec<FileEntry> = Vec::with_capacity(files.len());
+        for entry in files {
+            if merged.last().is_some_and(|last| last.address == entry.address) {
+                merged.pop();
+            }
+            if merged.last().is_none_or(|last| last.name != entry.name) {
+                merged.push(entry);
+            }
+        }
+        // Without any names there is nothing to record
+        if merged.iter().all(|entry| entry.name.is_none()) {
+            merged.clear();
+        }
+        merged
+    }
+
+    /// `value` with any function address moved by `code_offset`
+    fn relocate_value(value: &Value, code_offset: usize) -> Value {
+        match value {
+            Value::Function { address, arity } => Value::Function {
+                address: address + code_offset as u32,
+                arity: *arity,
+            },
+            other => other.clone(),
+        }
+    }
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
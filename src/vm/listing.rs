@@ -0,0 +1,234 @@
+//! Human-readable JSON listings of [`BytecodeModule`]s.
+//!
+//! A listing spells out each instruction with its mnemonic, operand, the
+//! constant it loads, the label or function it jumps to, its source line
+//! and the function it belongs to, so tools can render a module without
+//! knowing the instruction encoding. Those resolved fields are for readers
+//! only: [`BytecodeModule::from_json`] rebuilds the module from the
+//! mnemonics, operands, constants, functions and labels, and ignores them.
+
+use crate::vm::assembler::{mnemonic, opcode_for};
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::module::{
+    BytecodeModule, DebugInfo, ExternRef, FileEntry, FunctionInfo, LineTable, ModuleError,
+    SymbolTable,
+};
+use crate::vm::types::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Layout version written by [`BytecodeModule::to_json`]
+pub const LISTING_FORMAT_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Listing {
+    version: u32,
+    constants: Vec<ConstantEntry>,
+    functions: Vec<FunctionEntry>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    externs: Vec<ExternRef>,
+    /// Exported constant name to its index
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    globals: BTreeMap<String, usize>,
+    /// Label name to the address it marks
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    labels: BTreeMap<String, usize>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    files: Vec<FileEntry>,
+    instructions: Vec<InstructionEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ConstantEntry {
+    index: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    names: Vec<String>,
+    value: Value,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FunctionEntry {
+    #[serde(flatten)]
+    info: FunctionInfo,
+    /// End of the function's body, when the module records it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    end: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstructionEntry {
+    address: usize,
+    mnemonic: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    operand: Option<Value>,
+    /// Constant the instruction pushes from the pool
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    constant: Option<Value>,
+    /// Label or function the instruction jumps to or calls
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    line: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    function: Option<String>,
+}
+
+fn invalid(message: impl Into<String>) -> ModuleError {
+    ModuleError::Serialization(message.into())
+}
+
+impl BytecodeModule {
+    /// Pretty-printed JSON listing of this module
+    pub fn to_json(&self) -> Result<String, ModuleError> {
+        let mut names: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (name, &index) in &self.symbols.constants {
+            names.entry(index).or_default().push(name.clone());
+        }
+        let constants = self
+            .constants
+            .iter()
+            .enumerate()
+            .map(|(index, value)| ConstantEntry {
+                index,
+                names: names.remove(&index).unwrap_or_default(),
+                value: value.clone(),
+            })
+            .collect();
+
+        let functions = self
+            .functions
+            .iter()
+            .map(|info| FunctionEntry {
+                info: info.clone(),
+                end: self.symbols.functions.get(&info.name).map(|range| range.end),
+            })
+            .collect();
+
+        let mut labels: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (name, &address) in &self.symbols.labels {
+            labels.entry(address).or_default().push(name.clone());
+        }
+        let instructions = self
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(address, instruction)| {
+                let mnemonic = mnemonic(instruction.opcode())
+                    .ok_or_else(|| invalid(format!("Wide prefix at address {}", address)))?;
+                Ok(InstructionEntry {
+                    address,
+                    mnemonic: mnemonic.to_string(),
+                    operand: instruction.operand().cloned(),
+                    constant: self.loaded_constant(instruction).cloned(),
+                    target: self.target_name(instruction),
+                    labels: labels.remove(&address).unwrap_or_default(),
+                    line: self.debug_info.lines.line_at(address),
+                    function: self.symbols.function_containing(address).map(str::to_string),
+                })
+            })
+            .collect::<Result<_, ModuleError>>()?;
+
+        let listing = Listing {
+            version: LISTING_FORMAT_VERSION,
+            constants,
+            functions,
+            externs: self.externs.clone(),
+            globals: self.globals.clone(),
+            labels: self.symbols.labels.clone(),
+            files: self.debug_info.files.clone(),
+            instructions,
+        };
+        serde_json::to_string_pretty(&listing).map_err(|e| invalid(e.to_string()))
+    }
+
+    /// Reads a listing written by [`BytecodeModule::to_json`]
+    pub fn from_json(json: &str) -> Result<Self, ModuleError> {
+        let listing: Listing = serde_json::from_str(json).map_err(|e| invalid(e.to_string()))?;
+        if listing.version != LISTING_FORMAT_VERSION {
+            return Err(invalid(format!(
+                "unsupported listing version {} (expected {})",
+                listing.version, LISTING_FORMAT_VERSION
+            )));
+        }
+
+        let mut symbols = SymbolTable {
+            labels: listing.labels,
+            ..SymbolTable::default()
+        };
+        let mut constants = Vec::with_capacity(listing.constants.len());
+        for (index, entry) in listing.constants.into_iter().enumerate() {
+            if entry.index != index {
+                return Err(invalid(format!("constant {} is listed as {}", index, entry.index)));
+            }
+            for name in entry.names {
+                symbols.constants.insert(name, index);
+            }
+            constants.push(entry.value);
+        }
+
+        let mut functions = Vec::with_capacity(listing.functions.len());
+        for entry in listing.functions {
+            if let Some(end) = entry.end {
+                symbols.functions.insert(entry.info.name.clone(), entry.info.address..end);
+            }
+            functions.push(entry.info);
+        }
+
+        let mut instructions = Vec::with_capacity(listing.instructions.len());
+        let mut lines = Vec::with_capacity(listing.instructions.len());
+        for (address, entry) in listing.instructions.into_iter().enumerate() {
+            if entry.address != address {
+                return Err(invalid(format!(
+                    "instruction {} is listed at address {}",
+                    address, entry.address
+                )));
+            }
+            let opcode = opcode_for(&entry.mnemonic)
+                .filter(|&opcode| opcode != Opcode::Wide)
+                .ok_or_else(|| {
+                    invalid(format!("unknown mnemonic {} at address {}", entry.mnemonic, address))
+                })?;
+            instructions.push(Instruction::new(opcode, entry.operand));
+            lines.push(entry.line);
+        }
+
+        Ok(BytecodeModule::new(instructions, constants)
+            .with_functions(functions)
+            .with_symbols(symbols)
+            .with_debug_info(DebugInfo {
+                lines: LineTable::from_lines(lines),
+                files: listing.files,
+            })
+            .with_externs(listing.externs)
+            .with_globals(listing.globals))
+    }
+
+    /// Constant `instruction` pushes from the pool, following the runtime:
+    /// `PUSH` takes its operand as an index only when the pool is non-empty
+    fn loaded_constant(&self, instruction: &Instruction) -> Option<&Value> {
+        match (instruction.opcode(), instruction.operand()) {
+            (Opcode::LoadConstant, Some(Value::Integer(index)))
+            | (Opcode::Push, Some(Value::Integer(index))) => {
+                self.constants.get(usize::try_from(*index).ok()?)
+            }
+            _ => None,
+        }
+    }
+
+    fn target_name(&self, instruction: &Instruction) -> Option<String> {
+        let target = match (instruction.opcode(), instruction.operand()) {
+            (
+                Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Call,
+                Some(Value::Integer(target)),
+            ) => usize::try_from(*target).ok()?,
+            _ => return None,
+        };
+        let name = match instruction.opcode() {
+            Opcode::Call => self.function_at(target).map(|function| function.name.as_str()),
+            _ => None,
+        };
+        name.or_else(|| self.symbols.label_at(target)).map(str::to_string)
+    }
+}
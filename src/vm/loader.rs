@@ -0,0 +1,164 @@
+//! Reads `.svmbc` containers from any [`Read`] source a section at a time.
+
+use crate::vm::container::{
+    check_header, digest_len, SectionDecoder, SignatureVerifier, CONTAINER_HEADER_LEN,
+};
+use crate::vm::module::{BytecodeModule, ModuleError};
+use std::fmt;
+use std::io::{ErrorKind, Read};
+use std::sync::Arc;
+
+/// Largest section [`ModuleLoader::new`] accepts
+pub const DEFAULT_MAX_SECTION_LEN: u64 = 16 * 1024 * 1024;
+
+/// Largest container [`ModuleLoader::new`] accepts
+pub const DEFAULT_MAX_CONTAINER_LEN: u64 = 64 * 1024 * 1024;
+
+/// Loads bytecode containers from files, sockets or embedded bytes.
+///
+/// The header is checked before anything else is read, and each section is
+/// decoded and validated as soon as it arrives, so a bad container fails
+/// without being read to the end. Only one section is buffered at a time,
+/// and section lengths are checked against the limits before their data is
+/// read, so a corrupt length cannot make the loader allocate more than
+/// `max_section_len` bytes. Compressed sections are held to the same limit
+/// once inflated.
+///
+/// The content digest is checked, and the signature verifier consulted,
+/// once the last section has been read; until then nothing is returned.
+/// Containers from format version 3 on close with an end section, and the
+/// loader reads nothing past it, so several can follow one another in a
+/// stream. Older containers have no end marker and are read to the end of
+/// the stream.
+#[derive(Clone)]
+pub struct ModuleLoader {
+    max_section_len: u64,
+    max_container_len: u64,
+    verifier: Option<Arc<dyn SignatureVerifier + Send + Sync>>,
+}
+
+impl ModuleLoader {
+    pub fn new() -> Self {
+        Self {
+            max_section_len: DEFAULT_MAX_SECTION_LEN,
+            max_container_len: DEFAULT_MAX_CONTAINER_LEN,
+            verifier: None,
+        }
+    }
+
+    pub fn with_max_section_len(mut self, limit: u64) -> Self {
+        self.max_section_len = limit;
+        self
+    }
+
+    pub fn with_max_container_len(mut self, limit: u64) -> Self {
+        self.max_container_len = limit;
+        self
+    }
+
+    /// Only loads containers whose signature `verifier` accepts
+    pub fn with_signature_verifier(
+        mut self,
+        verifier: impl SignatureVerifier + Send + Sync + 'static,
+    ) -> Self {
+        self.verifier = Some(Arc::new(verifier));
+        self
+    }
+
+    /// Reads one container from `reader`, leaving it positioned just past
+    /// the container's end section. A container older than version 3 is
+    /// read until `reader` runs out.
+    pub fn load(&self, mut reader: impl Read) -> Result<BytecodeModule, ModuleError> {
+        let mut header = [0; CONTAINER_HEADER_LEN];
+        read_exact(&mut reader, &mut header, "missing SVMB magic number")?;
+        let version = check_header(&header)?;
+        let mut digest = vec![0; digest_len(version)];
+        read_exact(&mut reader, &mut digest, "missing content digest")?;
+
+        let mut total = (CONTAINER_HEADER_LEN + digest.len()) as u64;
+        let mut sections =
+            SectionDecoder::new(version, &digest).with_max_section_len(self.max_section_len);
+        let mut payload = Vec::new();
+        while !sections.is_ended() {
+            let Some(tag) = read_tag(&mut reader)? else {
+                break;
+            };
+            let mut len = [0; 4];
+            read_exact(&mut reader, &mut len, "unexpected end of section header")?;
+            let len = u32::from_le_bytes(len) as u64;
+
+            if len > self.max_section_len {
+                return Err(ModuleError::TooLarge {
+                    what: format!("Section {}", tag),
+                    size: len,
+                    limit: self.max_section_len,
+                });
+            }
+            total += 5 + len;
+            if total > self.max_container_len {
+                return Err(ModuleError::TooLarge {
+                    what: "Container".to_string(),
+                    size: total,
+                    limit: self.max_container_len,
+                });
+            }
+
+            // Grows with the data actually read, not the declared length
+            payload.clear();
+            (&mut reader).take(len).read_to_end(&mut payload)?;
+            if (payload.len() as u64) < len {
+                return Err(ModuleError::InvalidContainer(format!(
+                    "section {} ends after {} of {} bytes",
+                    tag,
+                    payload.len(),
+                    len
+                )));
+            }
+            sections.push(tag, &payload)?;
+        }
+        sections.finish(self.verifier.as_deref().map(|verifier| verifier as &dyn SignatureVerifier))
+    }
+}
+
+impl fmt::Debug for ModuleLoader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ModuleLoader")
+            .field("max_section_len", &self.max_section_len)
+            .field("max_container_len", &self.max_container_len)
+            .field("verifies_signatures", &self.verifier.is_some())
+            .finish()
+    }
+}
+
+impl Default for ModuleLoader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The next section tag, or `None` at the end of the data
+fn read_tag(reader: &mut impl Read) -> Result<Option<u8>, ModuleError> {
+    let mut tag = [0];
+    loop {
+        match reader.read(&mut tag) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(tag[0])),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8], message: &str) -> Result<(), ModuleError> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        ErrorKind::UnexpectedEof => ModuleError::InvalidContainer(message.to_string()),
+        _ => e.into(),
+    })
+}
+
+impl BytecodeModule {
+    /// Reads a container with the default [`ModuleLoader`] limits
+    pub fn read_from(reader: impl Read) -> Result<Self, ModuleError> {
+        ModuleLoader::new().load(reader)
+    }
+}
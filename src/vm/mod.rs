@@ -1,8 +1,36 @@
+pub mod analysis;
 pub mod assembler;
+pub mod bench;
+pub mod builder;
 pub mod call_frame;
+pub mod compact;
+pub mod compiler;
+pub mod container;
+pub mod coverage;
+pub mod debugger;
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
 pub mod heap;
+pub mod heap_graph;
+pub mod history;
 pub mod instruction;
+pub mod io;
 pub mod jit;
+pub mod linker;
+#[cfg(feature = "serde")]
+pub mod listing;
+pub mod loader;
+pub mod module;
+pub mod optimizer;
+pub mod recorder;
+pub mod repl;
 pub mod runtime;
+#[cfg(feature = "serde")]
+pub mod serialization;
 pub mod stack;
+pub mod stdlib;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+pub mod trace;
 pub mod types;
+pub mod verifier;
@@ -0,0 +1,565 @@
+use crate::vm::assembler::AssemblerError;
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::types::{Value, ValueKey};
+#[cfg(feature = "serde")]
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+/// Layout version written by [`BytecodeModule::to_json_bytes`]
+pub const MODULE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug)]
+pub enum ModuleError {
+    Assembly(AssemblerError),
+    Serialization(String),
+    UnsupportedVersion(u32),
+    InvalidContainer(String),
+    UnsupportedContainerVersion(u16),
+    /// Reading a container failed
+    Io(std::io::Error),
+    /// A container section or the whole container is larger than the
+    /// loader allows
+    TooLarge { what: String, size: u64, limit: u64 },
+    /// A container's sections do not match the digest in its header
+    DigestMismatch,
+    /// The loader's signature verifier refused a container
+    SignatureRejected(String),
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModuleError::Assembly(e) => write!(f, "Assembly error: {}", e),
+            ModuleError::Serialization(msg) => write!(f, "Module serialization error: {}", msg),
+            ModuleError::UnsupportedVersion(version) => write!(
+                f,
+                "Unsupported module format version {} (expected {})",
+                version, MODULE_FORMAT_VERSION
+            ),
+            ModuleError::InvalidContainer(msg) => write!(f, "Invalid bytecode container: {}", msg),
+            ModuleError::UnsupportedContainerVersion(version) => write!(
+                f,
+                "Unsupported bytecode container version {} (expected {} to {})",
+                version,
+                crate::vm::container::MIN_CONTAINER_FORMAT_VERSION,
+                crate::vm::container::CONTAINER_FORMAT_VERSION
+            ),
+            ModuleError::Io(e) => write!(f, "Failed to read bytecode container: {}", e),
+            ModuleError::TooLarge { what, size, limit } => {
+                write!(f, "{} is {} bytes, more than the limit of {}", what, size, limit)
+            }
+            ModuleError::DigestMismatch => {
+                write!(f, "Bytecode container does not match its content digest")
+            }
+            ModuleError::SignatureRejected(reason) => {
+                write!(f, "Bytecode container signature rejected: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ModuleError {}
+
+impl From<std::io::Error> for ModuleError {
+    fn from(err: std::io::Error) -> Self {
+        ModuleError::Io(err)
+    }
+}
+
+impl From<AssemblerError> for ModuleError {
+    fn from(err: AssemblerError) -> Self {
+        ModuleError::Assembly(err)
+    }
+}
+
+/// Entry in a module's function table describing the frame a call creates
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FunctionInfo {
+    pub name: String,
+    /// Address of the function's first instruction
+    pub address: usize,
+    /// Arguments popped from the operand stack into locals `0..arity`
+    pub arity: usize,
+    /// Total local slots in the frame, arguments included
+    pub locals: usize,
+    /// Values the function leaves on the operand stack when it returns, if
+    /// declared; calls to it are then checked against this stack effect
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub returns: Option<usize>,
+    /// Whether other modules and the host may call the function by name;
+    /// see [`BytecodeModule::exports`]
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exported: bool,
+}
+
+impl FunctionInfo {
+    pub fn new(name: impl Into<String>, address: usize, arity: usize, locals: usize) -> Self {
+        Self {
+            name: name.into(),
+            address,
+            arity,
+            locals,
+            returns: None,
+            exported: false,
+        }
+    }
+
+    pub fn with_returns(mut self, returns: usize) -> Self {
+        self.returns = Some(returns);
+        self
+    }
+
+    pub fn exported(mut self) -> Self {
+        self.exported = true;
+        self
+    }
+
+    /// Net change in operand stack height caused by a call, if declared
+    pub fn stack_effect(&self) -> Option<isize> {
+        self.returns
+            .map(|returns| returns as isize - self.arity as isize)
+    }
+}
+
+/// The exported functions of a function table, as for
+/// [`BytecodeModule::exports`]
+pub fn exports(functions: &[FunctionInfo]) -> impl Iterator<Item = &FunctionInfo> {
+    let all = !functions.iter().any(|function| function.exported);
+    functions.iter().filter(move |function| all || function.exported)
+}
+
+/// Source names recorded by the assembler, for symbolicated output.
+///
+/// Local labels are stored qualified with their function, e.g. `fib.loop`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SymbolTable {
+    /// Label name to the address it marks
+    pub labels: BTreeMap<String, usize>,
+    /// Constant name to its index in the constants pool
+    pub constants: BTreeMap<String, usize>,
+    /// Function name to the addresses between `.func` and `.endfunc`
+    pub functions: BTreeMap<String, Range<usize>>,
+}
+
+impl SymbolTable {
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty() && self.constants.is_empty() && self.functions.is_empty()
+    }
+
+    /// First label, by name, marking `address`
+    pub fn label_at(&self, address: usize) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(_, target)| **target == address)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// First name, by name, given to constant `index`
+    pub fn constant_name(&self, index: usize) -> Option<&str> {
+        self.constants
+            .iter()
+            .find(|(_, target)| **target == index)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Function whose body contains `address`
+    pub fn function_containing(&self, address: usize) -> Option<&str> {
+        self.functions
+            .iter()
+            .find(|(_, range)| range.contains(&address))
+            .map(|(name, _)| name.as_str())
+    }
+}
+
+/// Jump, call or `LDC` at `address` whose target is `name`, a function or
+/// global defined in another module or by the host; the operand is a
+/// placeholder until the module is linked or loaded
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ExternRef {
+    pub name: String,
+    pub address: usize,
+}
+
+/// Run of instructions generated from one source line, from `address` up
+/// to the next entry's; `line` is `None` for generated code with no source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineEntry {
+    pub address: usize,
+    pub line: Option<usize>,
+}
+
+/// Line-number table mapping instruction addresses back to 1-based source
+/// lines
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LineTable {
+    entries: Vec<LineEntry>,
+}
+
+impl LineTable {
+    /// Table for a program whose instruction `pc` came from `lines[pc]`
+    pub fn from_lines(lines: impl IntoIterator<Item = Option<usize>>) -> Self {
+        let mut entries: Vec<LineEntry> = Vec::new();
+        for (address, line) in lines.into_iter().enumerate() {
+            if entries.last().is_none_or(|last| last.line != line) {
+                entries.push(LineEntry { address, line });
+            }
+        }
+        Self { entries }
+    }
+
+    /// Table from runs already in address order, as returned by
+    /// [`LineTable::entries`]
+    pub fn from_entries(entries: impl IntoIterator<Item = LineEntry>) -> Self {
+        Self {
+            entries: entries.into_iter().collect(),
+        }
+    }
+
+    pub fn entries(&self) -> &[LineEntry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Source line of the instruction at `address`
+    pub fn line_at(&self, address: usize) -> Option<usize> {
+        let run = self.entries.partition_point(|entry| entry.address <= address);
+        self.entries.get(run.checked_sub(1)?)?.line
+    }
+}
+
+/// Source file of the instructions from `address` up to the next entry's;
+/// `name` is `None` for code whose source file is unknown
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileEntry {
+    pub address: usize,
+    pub name: Option<String>,
+}
+
+/// Information for mapping a module back to its source
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DebugInfo {
+    pub lines: LineTable,
+    /// Source files by address, in address order; a linked module has one
+    /// run per input module
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub files: Vec<FileEntry>,
+}
+
+impl DebugInfo {
+    /// Line table for code read from the single file `name`
+    pub fn for_file(lines: LineTable, name: Option<String>) -> Self {
+        Self {
+            lines,
+            files: name
+                .map(|name| FileEntry { address: 0, name: Some(name) })
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    /// Name of the source file the instruction at `address` came from
+    pub fn file_at(&self, address: usize) -> Option<&str> {
+        let run = self.files.partition_point(|entry| entry.address <= address);
+        self.files.get(run.checked_sub(1)?)?.name.as_deref()
+    }
+
+    /// `file:line`, or just the line when the file is unknown
+    pub fn location(&self, address: usize) -> Option<String> {
+        let line = self.lines.line_at(address)?;
+        Some(match self.file_at(address) {
+            Some(file) => format!("{}:{}", file, line),
+            None => format!("line {}", line),
+        })
+    }
+}
+
+/// What [`BytecodeModule::strip_debug`] removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StripStats {
+    /// Runs in the line table
+    pub line_entries: usize,
+    /// Entries in the source file table
+    pub files: usize,
+    /// Label, constant and function range names
+    pub symbols: usize,
+    /// Bytes the removed metadata took in the module's container
+    pub bytes: usize,
+}
+
+impl fmt::Display for StripStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stripped {} line entries, {} files and {} symbols ({} bytes)",
+            self.line_entries, self.files, self.symbols, self.bytes
+        )
+    }
+}
+
+/// An assembled program: code, its constants pool, function table,
+/// symbols, debug info and references still to be linked
+#[derive(Debug, Clone, Default)]
+pub struct BytecodeModule {
+    pub instructions: Vec<Instruction>,
+    pub constants: Vec<Value>,
+    pub functions: Vec<FunctionInfo>,
+    pub symbols: SymbolTable,
+    pub debug_info: DebugInfo,
+    pub externs: Vec<ExternRef>,
+    /// Exported constants other modules may load by name, as read-only
+    /// globals, with their index in the constants pool
+    pub globals: BTreeMap<String, usize>,
+}
+
+impl BytecodeModule {
+    pub fn new(instructions: Vec<Instruction>, constants: Vec<Value>) -> Self {
+        Self {
+            instructions,
+            constants,
+            functions: Vec::new(),
+            symbols: SymbolTable::default(),
+            debug_info: DebugInfo::default(),
+            externs: Vec::new(),
+            globals: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_functions(mut self, functions: Vec<FunctionInfo>) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    pub fn with_symbols(mut self, symbols: SymbolTable) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    pub fn with_debug_info(mut self, debug_info: DebugInfo) -> Self {
+        self.debug_info = debug_info;
+        self
+    }
+
+    pub fn with_externs(mut self, externs: Vec<ExternRef>) -> Self {
+        self.externs = externs;
+        self
+    }
+
+    pub fn with_globals(mut self, globals: BTreeMap<String, usize>) -> Self {
+        self.globals = globals;
+        self
+    }
+
+    /// The function starting at `address`, if one is declared there
+    pub fn function_at(&self, address: usize) -> Option<&FunctionInfo> {
+        self.functions.iter().find(|function| function.address == address)
+    }
+
+    pub fn function(&self, name: &str) -> Option<&FunctionInfo> {
+        self.functions.iter().find(|function| function.name == name)
+    }
+
+    /// Functions callable by name from other modules and the host: those
+    /// marked exported, or every function if the module marks none
+    pub fn exports(&self) -> impl Iterator<Item = &FunctionInfo> {
+        exports(&self.functions)
+    }
+
+    /// Removes the line table, source file names and symbol table, which
+    /// only tools and error messages use, for distributing the module.
+    ///
+    /// The function table is kept, since calls are checked against it and
+    /// the host calls exported functions by name, and so are exported
+    /// globals and externs, which linking and loading need.
+    pub fn strip_debug(&mut self) -> StripStats {
+        // Modules too large to encode have no container to shrink
+        let bytes = self.debug_sections_len().unwrap_or(0);
+        let symbols = mem::take(&mut self.symbols);
+        let debug_info = mem::take(&mut self.debug_info);
+        StripStats {
+            line_entries: debug_info.lines.entries().len(),
+            files: debug_info.files.len(),
+            symbols: symbols.labels.len() + symbols.constants.len() + symbols.functions.len(),
+            bytes,
+        }
+    }
+
+    /// Appends `value` to the constants pool, returning its index.
+    ///
+    /// `PUSH n` pushes the literal `n` only while the pool is empty, so
+    /// adding the first constant pools those literals as well.
+    pub fn add_constant(&mut self, value: Value) -> usize {
+        if self.constants.is_empty() {
+            for instruction in &mut self.instructions {
+                if let (Opcode::Push, Some(Value::Integer(literal))) =
+                    (instruction.opcode(), instruction.operand()) {
+                    let literal = Value::Integer(*literal);
+                    let index = match self.constants.iter().position(|c| *c == literal) {
+                        Some(index) => index,
+                        None => {
+                            self.constants.push(literal);
+                            self.constants.len() - 1
+                        }
+                    };
+                    *instruction = Instruction::new(Opcode::Push, Some(Value::Integer(index as i64)));
+                }
+            }
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// Merges identical constants into one pool entry, pointing every
+    /// `PUSH`, `LDC` and named constant that used a duplicate at the entry
+    /// that is kept. Strings with the same contents then share one buffer,
+    /// so loads of them compare equal by pointer.
+    ///
+    /// Constants are identical when they have the same type and compare
+    /// equal as [`ValueKey`]s; heap values such as arrays are only merged
+    /// with themselves.
+    /// Returns the number of entries removed.
+    // Heap values in keys hash by identity, so mutating them is harmless
+    #[allow(clippy::mutable_key_type)]
+    pub fn dedup_constants(&mut self) -> usize {
+        // Floats must match bit for bit, so 0.0 and -0.0 stay apart
+        let identical = |a: &Value, b: &Value| match (a, b) {
+            (Value::Float(a), Value::Float(b)) => a.to_bits() == b.to_bits(),
+            _ => true,
+        };
+        let mut kept: HashMap<(mem::Discriminant<Value>, ValueKey), usize> = HashMap::new();
+        let mut remap = Vec::with_capacity(self.constants.len());
+        let mut constants: Vec<Value> = Vec::with_capacity(self.constants.len());
+        for constant in self.constants.drain(..) {
+            let key = (mem::discriminant(&constant), ValueKey::new(constant));
+            let index = match kept.get(&key) {
+                Some(&index) if identical(&constants[index], key.1.value()) => index,
+                _ => {
+                    constants.push(key.1.value().clone());
+                    kept.entry(key).or_insert(constants.len() - 1);
+                    constants.len() - 1
+                }
+            };
+            remap.push(index);
+        }
+        let removed = remap.len() - constants.len();
+        self.constants = constants;
+        if removed == 0 {
+            return 0;
+        }
+
+        // PUSH operands index the pool, which is still non-empty
+        for instruction in &mut self.instructions {
+            if let (
+                Opcode::Push | Opcode::LoadConstant | Opcode::LoadGlobal | Opcode::StoreGlobal,
+                Some(Value::Integer(index)),
+            ) = (instruction.opcode(), instruction.operand())
+                && let Some(&new) = usize::try_from(*index).ok().and_then(|i| remap.get(i)) {
+                *instruction = Instruction::new(instruction.opcode(), Some(Value::Integer(new as i64)));
+            }
+        }
+        for index in self.symbols.constants.values_mut().chain(self.globals.values_mut()) {
+            if let Some(&new) = remap.get(*index) {
+                *index = new;
+            }
+        }
+        removed
+    }
+}
+
+/// On-disk layout of a serialized module, borrowing the module's parts
+/// when writing
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ModuleFile<'a> {
+    version: u32,
+    instructions: Cow<'a, [Instruction]>,
+    constants: Cow<'a, [Value]>,
+    functions: Cow<'a, [FunctionInfo]>,
+    #[serde(default)]
+    symbols: Cow<'a, SymbolTable>,
+    #[serde(default)]
+    debug_info: Cow<'a, DebugInfo>,
+    #[serde(default)]
+    externs: Cow<'a, [ExternRef]>,
+    #[serde(default)]
+    globals: Cow<'a, BTreeMap<String, usize>>,
+}
+
+#[cfg(feature = "serde")]
+impl<'a> ModuleFile<'a> {
+    fn new(module: &'a BytecodeModule) -> Self {
+        Self {
+            version: MODULE_FORMAT_VERSION,
+            instructions: Cow::Borrowed(&module.instructions),
+            constants: Cow::Borrowed(&module.constants),
+            functions: Cow::Borrowed(&module.functions),
+            symbols: Cow::Borrowed(&module.symbols),
+            debug_info: Cow::Borrowed(&module.debug_info),
+            externs: Cow::Borrowed(&module.externs),
+            globals: Cow::Borrowed(&module.globals),
+        }
+    }
+
+    fn into_module(self) -> Result<BytecodeModule, ModuleError> {
+        if self.version != MODULE_FORMAT_VERSION {
+            return Err(ModuleError::UnsupportedVersion(self.version));
+        }
+        Ok(BytecodeModule {
+            instructions: self.instructions.into_owned(),
+            constants: self.constants.into_owned(),
+            functions: self.functions.into_owned(),
+            symbols: self.symbols.into_owned(),
+            debug_info: self.debug_info.into_owned(),
+            externs: self.externs.into_owned(),
+            globals: self.globals.into_owned(),
+        })
+    }
+}
+
+/// Writes the versioned layout of [`BytecodeModule::to_json_bytes`], so modules
+/// can be stored in any serde format or embedded in other documents
+#[cfg(feature = "serde")]
+impl serde::Serialize for BytecodeModule {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ModuleFile::new(self).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BytecodeModule {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ModuleFile::deserialize(deserializer)?
+            .into_module()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl BytecodeModule {
+    /// This module as compact JSON in the versioned serde layout. `.bc`
+    /// files hold the container [`to_bytes`](Self::to_bytes) writes; this
+    /// is for tools that want the module as a document.
+    pub fn to_json_bytes(&self) -> Result<Vec<u8>, ModuleError> {
+        serde_json::to_vec(&ModuleFile::new(self))
+            .map_err(|e| ModuleError::Serialization(e.to_string()))
+    }
+
+    /// Reads a module written by [`BytecodeModule::to_json_bytes`]
+    pub fn from_json_bytes(bytes: &[u8]) -> Result<Self, ModuleError> {
+        serde_json::from_slice::<ModuleFile>(bytes)
+            .map_err(|e| ModuleError::Serialization(e.to_string()))?
+            .into_module()
+    }
+}
@@ -0,0 +1,475 @@
+//! Bytecode-level optimization of assembled or loaded modules.
+//!
+//! [`optimize_module`] rewrites a [`BytecodeModule`] in place, so build
+//! pipelines can optimize modules offline, including ones read back from
+//! containers with [`optimize_bytes`]. Every pass keeps the function table,
+//! symbols, debug info and externs pointing at the same code.
+
+use crate::vm::call_frame::CallStack;
+use crate::vm::instruction::{Instruction, InstructionDispatcher, Opcode};
+use crate::vm::module::{BytecodeModule, FileEntry, LineTable, ModuleError};
+use crate::vm::stack::OperandStack;
+use crate::vm::types::Value;
+use std::collections::HashSet;
+
+/// How hard [`optimize_module`] works
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OptLevel {
+    /// Leave the module as it is
+    None,
+    /// Peephole rewrites and constant folding
+    #[default]
+    Basic,
+    /// Everything in `Basic`, plus removing unreachable code and merging
+    /// duplicate constants
+    Full,
+}
+
+/// What [`optimize_module`] changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OptimizationStats {
+    /// Operations on constants replaced by their result, including
+    /// conditional jumps on a constant
+    pub folded: usize,
+    /// Instruction sequences rewritten by the peephole pass
+    pub peephole: usize,
+    /// Unreachable instructions removed
+    pub unreachable: usize,
+    /// Duplicate constants merged
+    pub constants_merged: usize,
+}
+
+impl OptimizationStats {
+    /// Whether the module was left unchanged
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+// Rewrites enable each other, e.g. folding `PUSH 1; PUSH 2; ADD` leaves a
+// constant for a following jump to be folded on; this bounds the rounds
+const MAX_ROUNDS: usize = 16;
+
+/// Optimizes `module` at `level`, returning what changed. With the
+/// `tracing` feature, this runs in an `optimize` span and reports what it
+/// changed as an event.
+pub fn optimize_module(module: &mut BytecodeModule, level: OptLevel) -> OptimizationStats {
+    let mut stats = OptimizationStats::default();
+    if level == OptLevel::None {
+        return stats;
+    }
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("optimize", ?level, instructions = module.instructions.len())
+        .entered();
+
+    for _ in 0..MAX_ROUNDS {
+        let before = stats;
+        stats.folded += fold_constants(module);
+        stats.peephole += peephole(module);
+        if level == OptLevel::Full {
+            stats.unreachable += remove_unreachable(module);
+        }
+        if stats == before {
+            break;
+        }
+    }
+    if level == OptLevel::Full {
+        stats.constants_merged = module.dedup_constants();
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        folded = stats.folded,
+        peephole = stats.peephole,
+        unreachable = stats.unreachable,
+        constants_merged = stats.constants_merged,
+        instructions = module.instructions.len(),
+        "module optimized"
+    );
+    stats
+}
+
+/// Optimizes the module in the container `bytes`, returning the new
+/// container. Signatures do not survive, since the digest changes.
+pub fn optimize_bytes(bytes: &[u8], level: OptLevel) -> Result<Vec<u8>, ModuleError> {
+    let mut module = BytecodeModule::from_bytes(bytes)?;
+    optimize_module(&mut module, level);
+    module.to_bytes()
+}
+
+/// Result of `opcode` on `operands`, computed by the interpreter itself
+/// so folding matches run-time behavior; `None` if the operation fails
+pub(crate) fn evaluate(opcode: Opcode, operands: Vec<Value>) -> Option<Value> {
+    // Native integer arithmetic panics rather than erroring on overflow
+    if let [Value::Integer(a), Value::Integer(b)] = operands[..] {
+        let checked = match opcode {
+            Opcode::Add => a.checked_add(b),
+            Opcode::Sub => a.checked_sub(b),
+            Opcode::Mul => a.checked_mul(b),
+            Opcode::Div => a.checked_div(b),
+            Opcode::Mod => a.checked_rem(b),
+            _ => Some(0),
+        };
+        checked?;
+    }
+
+    let mut stack = OperandStack::new();
+    for operand in operands {
+        stack.push(operand);
+    }
+    InstructionDispatcher::new()
+        .execute(&Instruction::new(opcode, None), &mut stack, &mut CallStack::new())
+        .ok()?;
+    stack.pop().ok()
+}
+
+/// Whether `value` can be folded and pushed again without the heap
+fn is_scalar(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Char(_) | Value::Null
+    )
+}
+
+/// Value `instruction` pushes when it is a scalar known before running,
+/// following the runtime: `PUSH` takes an integer operand as an index only
+/// when the pool is non-empty
+fn pushed_constant(module: &BytecodeModule, instruction: &Instruction) -> Option<Value> {
+    let value = match (instruction.opcode(), instruction.operand()?) {
+        (Opcode::Push, Value::Integer(literal)) if module.constants.is_empty() => {
+            Value::Integer(*literal)
+        }
+        (Opcode::Push | Opcode::LoadConstant, Value::Integer(index)) => {
+            module.constants.get(usize::try_from(*index).ok()?)?.clone()
+        }
+        (Opcode::Push, literal) => literal.clone(),
+        _ => return None,
+    };
+    is_scalar(&value).then_some(value)
+}
+
+/// Instruction pushing `value`, pooling it if integer operands index the
+/// pool
+fn push_instruction(module: &mut BytecodeModule, value: Value) -> Instruction {
+    let Value::Integer(literal) = value else {
+        return Instruction::new(Opcode::Push, Some(value));
+    };
+    if module.constants.is_empty() {
+        return Instruction::new(Opcode::Push, Some(value));
+    }
+    let index = module
+        .constants
+        .iter()
+        .position(|constant| matches!(constant, Value::Integer(n) if *n == literal))
+        .unwrap_or_else(|| module.add_constant(value));
+    Instruction::new(Opcode::Push, Some(Value::Integer(index as i64)))
+}
+
+/// Jump or call target of `instruction`, if it has a local one
+fn branch_target(instruction: &Instruction) -> Option<usize> {
+    match (instruction.opcode(), instruction.operand()) {
+        (
+            Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Call,
+            Some(Value::Integer(target)),
+        ) => usize::try_from(*target).ok(),
+        _ => None,
+    }
+}
+
+/// Where execution can start: the top of the program, function entry
+/// points and the addresses of function values, which `CALLI` may call
+fn roots(module: &BytecodeModule) -> Vec<usize> {
+    let values = module
+        .constants
+        .iter()
+        .chain(module.instructions.iter().filter_map(Instruction::operand));
+    let functions = values.filter_map(|value| match value {
+        Value::Function { address, .. } => Some(*address as usize),
+        _ => None,
+    });
+    (!module.instructions.is_empty())
+        .then_some(0)
+        .into_iter()
+        .chain(module.functions.iter().map(|function| function.address))
+        .chain(functions)
+        .collect()
+}
+
+/// Addresses control can arrive at other than by falling through: the
+/// roots and every local branch target
+fn entry_points(module: &BytecodeModule, externs: &HashSet<usize>) -> HashSet<usize> {
+    let targets = module
+        .instructions
+        .iter()
+        .enumerate()
+        .filter(|(address, _)| !externs.contains(address))
+        .filter_map(|(_, instruction)| branch_target(instruction));
+    roots(module).into_iter().chain(targets).collect()
+}
+
+fn extern_addresses(module: &BytecodeModule) -> HashSet<usize> {
+    module.externs.iter().map(|reference| reference.address).collect()
+}
+
+/// Replaces unary and binary operations on constants, and conditional
+/// jumps on them, with their outcome
+fn fold_constants(module: &mut BytecodeModule) -> usize {
+    let externs = extern_addresses(module);
+    let entries = entry_points(module, &externs);
+    let constant_at = |module: &BytecodeModule, address: usize| {
+        if externs.contains(&address) {
+            return None;
+        }
+        pushed_constant(module, &module.instructions[address])
+    };
+
+    let mut removed = vec![false; module.instructions.len()];
+    let mut folded = 0;
+    let mut address = 0;
+    while address < module.instructions.len() {
+        let opcode = module.instructions[address].opcode();
+        let arity = match opcode {
+            Opcode::Negate | Opcode::Not => 1,
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse if !externs.contains(&address) => 1,
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::Mul
+            | Opcode::Div
+            | Opcode::Mod
+            | Opcode::Equal
+            | Opcode::NotEqual
+            | Opcode::LessThan
+            | Opcode::LessEqual
+            | Opcode::GreaterThan
+            | Opcode::GreaterEqual
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor => 2,
+            _ => {
+                address += 1;
+                continue;
+            }
+        };
+        // The operands must be pushed just before, with no way to jump in
+        // between them
+        let first = match address.checked_sub(arity) {
+            Some(first)
+                if (first..=address).all(|a| !removed[a])
+                    && (first + 1..=address).all(|a| !entries.contains(&a)) =>
+            {
+                first
+            }
+            _ => {
+                address += 1;
+                continue;
+            }
+        };
+        let Some(operands) = (first..address)
+            .map(|a| constant_at(module, a))
+            .collect::<Option<Vec<_>>>()
+        else {
+            address += 1;
+            continue;
+        };
+
+        let replacement = match opcode {
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let taken = operands[0].is_truthy() == (opcode == Opcode::JumpIfTrue);
+                let target = module.instructions[address].operand().cloned();
+                taken.then(|| Instruction::new(Opcode::Jump, target))
+            }
+            _ => match evaluate(opcode, operands).filter(is_scalar) {
+                Some(value) => Some(push_instruction(module, value)),
+                None => {
+                    address += 1;
+                    continue;
+                }
+            },
+        };
+        removed[first..address].fill(true);
+        match replacement {
+            Some(instruction) => module.instructions[address] = instruction,
+            // An untaken jump just falls through
+            None => removed[address] = true,
+        }
+        folded += 1;
+        address += 1;
+    }
+
+    remove_instructions(module, &removed);
+    folded
+}
+
+/// Removes pushes that are popped straight away, pairs of swaps and jumps
+/// to the next instruction, and threads jumps that land on other jumps
+fn peephole(module: &mut BytecodeModule) -> usize {
+    let externs = extern_addresses(module);
+    let entries = entry_points(module, &externs);
+    let mut removed = vec![false; module.instructions.len()];
+    let mut rewrites = 0;
+
+    for address in 0..module.instructions.len() {
+        if removed[address] || externs.contains(&address) {
+            continue;
+        }
+        let instruction = &module.instructions[address];
+        let next = module.instructions.get(address + 1);
+        let pair_removable = next.is_some()
+            && !entries.contains(&(address + 1))
+            && !externs.contains(&(address + 1));
+
+        let cancels = matches!(
+            (instruction.opcode(), next.map(Instruction::opcode)),
+            (Opcode::Push | Opcode::LoadConstant | Opcode::Dup, Some(Opcode::Pop))
+                | (Opcode::Swap, Some(Opcode::Swap))
+        );
+        if cancels && pair_removable {
+            removed[address] = true;
+            removed[address + 1] = true;
+            rewrites += 1;
+            continue;
+        }
+
+        let Some(target) = branch_target(instruction) else {
+            continue;
+        };
+        match instruction.opcode() {
+            Opcode::Jump if target == address + 1 => {
+                removed[address] = true;
+                rewrites += 1;
+            }
+            Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                let threaded = thread_jump(module, &externs, target);
+                if threaded != target {
+                    let opcode = instruction.opcode();
+                    module.instructions[address] =
+                        Instruction::new(opcode, Some(Value::Integer(threaded as i64)));
+                    rewrites += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    remove_instructions(module, &removed);
+    rewrites
+}
+
+/// Where a jump to `target` ends up after following unconditional jumps
+fn thread_jump(module: &BytecodeModule, externs: &HashSet<usize>, mut target: usize) -> usize {
+    let mut seen = HashSet::new();
+    while seen.insert(target) && !externs.contains(&target) {
+        match module.instructions.get(target) {
+            Some(instruction) if instruction.opcode() == Opcode::Jump => {
+                match branch_target(instruction) {
+                    Some(next) => target = next,
+                    None => break,
+                }
+            }
+            _ => break,
+        }
+    }
+    target
+}
+
+/// Removes instructions no entry point can reach
+fn remove_unreachable(module: &mut BytecodeModule) -> usize {
+    let externs = extern_addresses(module);
+    let len = module.instructions.len();
+    let mut reachable = vec![false; len];
+    let mut pending = roots(module);
+
+    while let Some(address) = pending.pop() {
+        if address >= len || reachable[address] {
+            continue;
+        }
+        reachable[address] = true;
+        let instruction = &module.instructions[address];
+        let target = (!externs.contains(&address))
+            .then(|| branch_target(instruction))
+            .flatten();
+        pending.extend(target);
+        match instruction.opcode() {
+            Opcode::Jump | Opcode::Return | Opcode::Halt => {}
+            _ => pending.push(address + 1),
+        }
+    }
+
+    let removed: Vec<bool> = reachable.iter().map(|reachable| !reachable).collect();
+    let count = removed.iter().filter(|removed| **removed).count();
+    remove_instructions(module, &removed);
+    count
+}
+
+/// Deletes the instructions marked in `removed`, moving every address that
+/// pointed at a deleted instruction to the next one kept
+fn remove_instructions(module: &mut BytecodeModule, removed: &[bool]) {
+    if !removed.contains(&true) {
+        return;
+    }
+
+    // New address of each old one, and of the end of the program
+    let mut remap = Vec::with_capacity(removed.len() + 1);
+    let mut kept = 0;
+    for &removed in removed {
+        remap.push(kept);
+        if !removed {
+            kept += 1;
+        }
+    }
+    remap.push(kept);
+    let moved = |address: usize| remap.get(address).copied().unwrap_or(address);
+    let moved_value = |value: &Value| match value {
+        Value::Function { address, arity } => Value::Function {
+            address: moved(*address as usize) as u32,
+            arity: *arity,
+        },
+        other => other.clone(),
+    };
+
+    let externs = extern_addresses(module);
+    let mut instructions = Vec::with_capacity(kept);
+    let mut lines = Vec::with_capacity(kept);
+    for (address, instruction) in module.instructions.iter().enumerate() {
+        if removed[address] {
+            continue;
+        }
+        let operand = match (branch_target(instruction), instruction.operand()) {
+            (Some(target), _) if !externs.contains(&address) => {
+                Some(Value::Integer(moved(target) as i64))
+            }
+            (_, operand) => operand.map(moved_value),
+        };
+        instructions.push(Instruction::new(instruction.opcode(), operand));
+        lines.push(module.debug_info.lines.line_at(address));
+    }
+    module.instructions = instructions;
+    module.debug_info.lines = LineTable::from_lines(lines);
+
+    let mut files: Vec<FileEntry> = Vec::with_capacity(module.debug_info.files.len());
+    for entry in module.debug_info.files.drain(..) {
+        let address = moved(entry.address);
+        // A file whose code was all removed gives way to the next
+        if files.last().is_some_and(|last| last.address == address) {
+            files.pop();
+        }
+        files.push(FileEntry { address, name: entry.name });
+    }
+    module.debug_info.files = files;
+
+    for constant in &mut module.constants {
+        *constant = moved_value(constant);
+    }
+    for function in &mut module.functions {
+        function.address = moved(function.address);
+    }
+    for address in module.symbols.labels.values_mut() {
+        *address = moved(*address);
+    }
+    for range in module.symbols.functions.values_mut() {
+        *range = moved(range.start)..moved(range.end);
+    }
+    module.externs.retain(|reference| !removed.get(reference.address).copied().unwrap_or(false));
+    for reference in &mut module.externs {
+        reference.address = moved(reference.address);
+    }
+}
@@ -0,0 +1,436 @@
+//! Recording what a program does, and checking a run against a recording.
+//!
+//! A [`TraceRecorder`] steps a VM and keeps a [`TraceRecord`] per
+//! instruction: where it ran, what it did to the stack depth, and what it
+//! did outside the VM. The resulting [`ExecutionTrace`] encodes to a
+//! compact byte log:
+//!
+//! - the magic bytes `SVMT`, then a flag byte, 1 if the program halted
+//! - the record count, then each record as its pc, opcode byte, stack
+//!   delta and effect count, followed by the effects, each a tag byte and,
+//!   for those carrying text, its length and UTF-8 bytes
+//!
+//! Counts, lengths and addresses are LEB128 varints and stack deltas are
+//! zigzag-encoded, so most records take four bytes. A [`TraceReplayer`]
+//! runs a VM in step with a trace, feeding it the input the recording
+//! read, and stops at the first instruction that behaves differently.
+
+use crate::vm::assembler::mnemonic;
+use crate::vm::instruction::{EncodingError, Opcode};
+use crate::vm::io::{MemoryIo, VmIo};
+use crate::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const TRACE_MAGIC: [u8; 4] = *b"SVMT";
+
+/// Something an instruction did that the stack does not show
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SideEffect {
+    /// Text written to standard output
+    Output(String),
+    /// Text written to standard error
+    ErrorOutput(String),
+    /// A line read from input, or `None` at the end of input
+    Input(Option<String>),
+    /// The value `STG` stored, as displayed
+    Global(String),
+    /// The instruction failed with this error
+    Failed(String),
+}
+
+impl fmt::Display for SideEffect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SideEffect::Output(text) => write!(f, "output {:?}", text),
+            SideEffect::ErrorOutput(text) => write!(f, "error output {:?}", text),
+            SideEffect::Input(Some(line)) => write!(f, "input {:?}", line),
+            SideEffect::Input(None) => write!(f, "end of input"),
+            SideEffect::Global(value) => write!(f, "global set to {}", value),
+            SideEffect::Failed(error) => write!(f, "failed: {}", error),
+        }
+    }
+}
+
+/// One executed instruction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceRecord {
+    pub pc: usize,
+    pub opcode: Opcode,
+    /// Stack depth after the instruction less the depth before
+    pub stack_delta: isize,
+    pub effects: Vec<SideEffect>,
+}
+
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = mnemonic(self.opcode).map_or_else(|| format!("{:?}", self.opcode), str::to_string);
+        write!(f, "pc {}: {}, stack {:+}", self.pc, name, self.stack_delta)?;
+        for effect in &self.effects {
+            write!(f, ", {}", effect)?;
+        }
+        Ok(())
+    }
+}
+
+/// The instructions a run executed, in order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionTrace {
+    records: Vec<TraceRecord>,
+    halted: bool,
+}
+
+impl ExecutionTrace {
+    pub fn records(&self) -> &[TraceRecord] {
+        &self.records
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Whether the program had halted when recording stopped
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    /// Index of the first record where `other` differs from this trace,
+    /// or where one of them ends first; `None` if they are the same
+    pub fn first_divergence(&self, other: &ExecutionTrace) -> Option<usize> {
+        let common = self.records.iter().zip(&other.records).position(|(a, b)| a != b);
+        match common {
+            Some(index) => Some(index),
+            None if self.len() != other.len() || self.halted != other.halted => {
+                Some(self.len().min(other.len()))
+            }
+            None => None,
+        }
+    }
+
+    /// The trace as a byte log; see the [module docs](self)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = TRACE_MAGIC.to_vec();
+        bytes.push(u8::from(self.halted));
+        write_varint(&mut bytes, self.records.len() as u64);
+        for record in &self.records {
+            write_varint(&mut bytes, record.pc as u64);
+            bytes.push(record.opcode as u8);
+            write_varint(&mut bytes, zigzag(record.stack_delta));
+            write_varint(&mut bytes, record.effects.len() as u64);
+            for effect in &record.effects {
+                let (tag, text) = match effect {
+                    SideEffect::Output(text) => (0, Some(text)),
+                    SideEffect::ErrorOutput(text) => (1, Some(text)),
+                    SideEffect::Input(Some(line)) => (2, Some(line)),
+                    SideEffect::Input(None) => (3, None),
+                    SideEffect::Global(value) => (4, Some(value)),
+                    SideEffect::Failed(error) => (5, Some(error)),
+                };
+                bytes.push(tag);
+                if let Some(text) = text {
+                    write_varint(&mut bytes, text.len() as u64);
+                    bytes.extend_from_slice(text.as_bytes());
+                }
+            }
+        }
+        bytes
+    }
+
+    /// Reads a log written by [`to_bytes`](Self::to_bytes)
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, EncodingError> {
+        if !bytes.starts_with(&TRACE_MAGIC) {
+            return Err(EncodingError::InvalidOperand("Not an execution trace".to_string()));
+        }
+        let mut reader = Reader { bytes, offset: TRACE_MAGIC.len() };
+        let halted = match reader.byte()? {
+            0 => false,
+            1 => true,
+            flag => return Err(EncodingError::InvalidOperand(format!("Invalid trace flag {}", flag))),
+        };
+        let count = reader.length()?;
+        let mut records = Vec::new();
+        for _ in 0..count {
+            let pc = reader.length()?;
+            let opcode = reader.byte()?;
+            let opcode = Opcode::from_u8(opcode).ok_or(EncodingError::UnknownOpcode(opcode))?;
+            let stack_delta = unzigzag(reader.varint()?);
+            let mut effects = Vec::new();
+            for _ in 0..reader.length()? {
+                let effect = match reader.byte()? {
+                    0 => SideEffect::Output(reader.text()?),
+                    1 => SideEffect::ErrorOutput(reader.text()?),
+                    2 => SideEffect::Input(Some(reader.text()?)),
+                    3 => SideEffect::Input(None),
+                    4 => SideEffect::Global(reader.text()?),
+                    5 => SideEffect::Failed(reader.text()?),
+                    tag => {
+                        return Err(EncodingError::InvalidOperand(format!("Unknown side effect tag {}", tag)));
+                    }
+                };
+                effects.push(effect);
+            }
+            records.push(TraceRecord { pc, opcode, stack_delta, effects });
+        }
+        if reader.offset != bytes.len() {
+            return Err(EncodingError::InvalidOperand(format!(
+                "{} bytes after the last record",
+                bytes.len() - reader.offset
+            )));
+        }
+        Ok(Self { records, halted })
+    }
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn zigzag(value: isize) -> u64 {
+    ((value << 1) ^ (value >> (isize::BITS - 1))) as u64
+}
+
+fn unzigzag(value: u64) -> isize {
+    ((value >> 1) as isize) ^ -((value & 1) as isize)
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, EncodingError> {
+        let byte = *self.bytes.get(self.offset).ok_or(EncodingError::UnexpectedEnd(self.offset))?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, EncodingError> {
+        let start = self.offset;
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(EncodingError::InvalidOperand(format!("Varint at offset {} is too long", start)))
+    }
+
+    fn length(&mut self) -> Result<usize, EncodingError> {
+        let value = self.varint()?;
+        usize::try_from(value).map_err(|_| EncodingError::InvalidOperand(format!("Length {} is too large", value)))
+    }
+
+    fn text(&mut self) -> Result<String, EncodingError> {
+        let start = self.offset;
+        let len = self.length()?;
+        let end = self.offset.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or(EncodingError::UnexpectedEnd(self.bytes.len()))?;
+        let text = std::str::from_utf8(&self.bytes[self.offset..end])
+            .map_err(|_| EncodingError::InvalidOperand(format!("Text at offset {} is not UTF-8", start)))?;
+        self.offset = end;
+        Ok(text.to_string())
+    }
+}
+
+/// Passes a program's streams through to another [`VmIo`], noting what
+/// went by for the recorder
+struct RecordingIo {
+    inner: Box<dyn VmIo>,
+    effects: Arc<Mutex<Vec<SideEffect>>>,
+}
+
+impl RecordingIo {
+    fn record(&self, effect: SideEffect) {
+        lock(&self.effects).push(effect);
+    }
+}
+
+impl VmIo for RecordingIo {
+    fn write_stdout(&mut self, text: &str) -> io::Result<()> {
+        self.record(SideEffect::Output(text.to_string()));
+        self.inner.write_stdout(text)
+    }
+
+    fn write_stderr(&mut self, text: &str) -> io::Result<()> {
+        self.record(SideEffect::ErrorOutput(text.to_string()));
+        self.inner.write_stderr(text)
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        let line = self.inner.read_line()?;
+        self.record(SideEffect::Input(line.clone()));
+        Ok(line)
+    }
+}
+
+fn lock(effects: &Mutex<Vec<SideEffect>>) -> MutexGuard<'_, Vec<SideEffect>> {
+    effects.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs a VM an instruction at a time, building an [`ExecutionTrace`] of
+/// what each did
+pub struct TraceRecorder {
+    trace: ExecutionTrace,
+    effects: Arc<Mutex<Vec<SideEffect>>>,
+}
+
+impl TraceRecorder {
+    /// Records `vm`, whose streams become `io` seen through the recorder
+    pub fn new(vm: &mut VirtualMachine, io: impl VmIo + 'static) -> Self {
+        let effects = Arc::new(Mutex::new(Vec::new()));
+        vm.set_io(RecordingIo { inner: Box::new(io), effects: Arc::clone(&effects) });
+        Self { trace: ExecutionTrace::default(), effects }
+    }
+
+    pub fn trace(&self) -> &ExecutionTrace {
+        &self.trace
+    }
+
+    pub fn into_trace(self) -> ExecutionTrace {
+        self.trace
+    }
+
+    /// Runs the next instruction of `vm` and records it. A failing
+    /// instruction is recorded with the error before it is returned.
+    pub fn step(&mut self, vm: &mut VirtualMachine) -> Result<(), VmError> {
+        let pc = vm.program_counter();
+        let Some(instruction) = vm.current_instruction().filter(|_| !vm.is_halted()) else {
+            vm.step()?;
+            self.trace.halted = vm.is_halted();
+            return Ok(());
+        };
+        let opcode = instruction.opcode();
+        let stored = match (opcode, vm.stack_top()) {
+            (Opcode::StoreGlobal, Ok(value)) => Some(value.to_string()),
+            _ => None,
+        };
+        let depth = vm.stack_size();
+        lock(&self.effects).clear();
+
+        let result = vm.step();
+        let mut effects = std::mem::take(&mut *lock(&self.effects));
+        match &result {
+            Ok(()) => effects.extend(stored.map(SideEffect::Global)),
+            Err(e) => effects.push(SideEffect::Failed(e.to_string())),
+        }
+        self.trace.records.push(TraceRecord {
+            pc,
+            opcode,
+            stack_delta: vm.stack_size() as isize - depth as isize,
+            effects,
+        });
+        self.trace.halted = vm.is_halted();
+        result
+    }
+
+    /// Records `vm` until its program halts, or for at most `max_steps`
+    /// instructions, reporting which happened
+    pub fn run(&mut self, vm: &mut VirtualMachine, max_steps: u64) -> Result<RunOutcome, VmError> {
+        for _ in 0..max_steps {
+            if vm.is_halted() {
+                break;
+            }
+            self.step(vm)?;
+        }
+        self.trace.halted = vm.is_halted();
+        Ok(if vm.is_halted() { RunOutcome::Halted } else { RunOutcome::Exhausted })
+    }
+}
+
+/// Where a replayed run stopped matching its recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// Instruction `step` of the run did not do what the recording did
+    Diverged { step: usize, expected: TraceRecord, actual: TraceRecord },
+    /// The program halted before `step`, which the recording still has
+    HaltedEarly { step: usize },
+    /// The recording halted after its last instruction but the program
+    /// did not
+    NotHalted,
+    /// The program failed outside any recorded instruction
+    Execution(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::Diverged { step, expected, actual } => {
+                write!(f, "Step {} diverged: expected {}; found {}", step, expected, actual)
+            }
+            ReplayError::HaltedEarly { step } => write!(f, "Program halted before step {}", step),
+            ReplayError::NotHalted => write!(f, "Program did not halt where the recording did"),
+            ReplayError::Execution(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// Runs a VM in step with an [`ExecutionTrace`], checking that each
+/// instruction does what the recorded one did
+pub struct TraceReplayer<'a> {
+    expected: &'a ExecutionTrace,
+    recorder: TraceRecorder,
+}
+
+impl<'a> TraceReplayer<'a> {
+    /// Replays `trace` on `vm`, whose program should be loaded and where
+    /// the recording started. The VM's input becomes the lines the
+    /// recording read, and its output is kept for comparison only.
+    pub fn new(trace: &'a ExecutionTrace, vm: &mut VirtualMachine) -> Self {
+        let input = MemoryIo::new();
+        for effect in trace.records.iter().flat_map(|record| &record.effects) {
+            if let SideEffect::Input(Some(line)) = effect {
+                input.push_input(&format!("{}\n", line));
+            }
+        }
+        Self { expected: trace, recorder: TraceRecorder::new(vm, input) }
+    }
+
+    /// Instructions replayed so far
+    pub fn steps(&self) -> usize {
+        self.recorder.trace.len()
+    }
+
+    /// Replays the next recorded instruction, returning whether any remain
+    pub fn step(&mut self, vm: &mut VirtualMachine) -> Result<bool, ReplayError> {
+        let step = self.steps();
+        let Some(expected) = self.expected.records.get(step) else {
+            return Ok(false);
+        };
+        if vm.is_halted() {
+            return Err(ReplayError::HaltedEarly { step });
+        }
+        // A failure the recording also had is checked with the record
+        let result = self.recorder.step(vm);
+        let Some(actual) = self.recorder.trace.records.get(step) else {
+            return Err(ReplayError::Execution(result.err().map(|e| e.to_string()).unwrap_or_default()));
+        };
+        if actual != expected {
+            return Err(ReplayError::Diverged { step, expected: expected.clone(), actual: actual.clone() });
+        }
+        Ok(step + 1 < self.expected.len())
+    }
+
+    /// Replays every recorded instruction, then checks the program halted
+    /// if the recording did
+    pub fn run(&mut self, vm: &mut VirtualMachine) -> Result<(), ReplayError> {
+        while self.step(vm)? {}
+        if self.expected.halted && !vm.is_halted() {
+            return Err(ReplayError::NotHalted);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use crate::vm::assembler::{Assembler, AssemblerError};
+use crate::vm::compiler::SimpleCompiler;
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::runtime::{VirtualMachine, VmError};
+use crate::vm::types::Value;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+
+#[derive(Debug)]
+pub enum ReplError {
+    Assembly(AssemblerError),
+    Execution(VmError),
+}
+
+impl fmt::Display for ReplError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplError::Assembly(e) => write!(f, "{}", e),
+            ReplError::Execution(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ReplError {}
+
+impl From<AssemblerError> for ReplError {
+    fn from(err: AssemblerError) -> Self {
+        ReplError::Assembly(err)
+    }
+}
+
+impl From<VmError> for ReplError {
+    fn from(err: VmError) -> Self {
+        ReplError::Execution(err)
+    }
+}
+
+/// How the REPL reads each line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplMode {
+    /// One assembly instruction, e.g. `PUSH 5`
+    Assembly,
+    /// A [`SimpleCompiler`] expression, e.g. `(1 + 2) * 3`
+    Expression,
+}
+
+/// What a line of input asked the REPL to do
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReplOutput {
+    /// The stack top after running the line, if the stack is not empty
+    Value(Option<Value>),
+    /// Text answering a `:` command
+    Message(String),
+    Quit,
+}
+
+const HELP: &str = "\
+Enter one assembly instruction, or an expression in :expr mode.
+Commands:
+  :asm     read assembly instructions
+  :expr    read compiler expressions
+  :stack   show every value on the stack, bottom first
+  :heap    show what the heap holds
+  :reset   clear the stack and heap
+  :help    show this help
+  :quit    leave the REPL";
+
+/// Runs lines of assembly or expressions one at a time on a VM that keeps
+/// its stack between lines
+pub struct Repl {
+    vm: VirtualMachine,
+    mode: ReplMode,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        Self::with_mode(ReplMode::Assembly)
+    }
+
+    pub fn with_mode(mode: ReplMode) -> Self {
+        Self {
+            vm: VirtualMachine::new(),
+            mode,
+        }
+    }
+
+    pub fn mode(&self) -> ReplMode {
+        self.mode
+    }
+
+    pub fn vm(&self) -> &VirtualMachine {
+        &self.vm
+    }
+
+    /// Assembles or compiles `line`, runs it, and returns the stack top
+    pub fn eval_line(&mut self, line: &str) -> Result<Option<Value>, ReplError> {
+        let (mut instructions, constants) = match self.mode {
+            ReplMode::Assembly => Assembler::new().assemble(line)?,
+            ReplMode::Expression => SimpleCompiler::new().compile_expression(line)?,
+        };
+        if !instructions.is_empty() {
+            if instructions.last().map(Instruction::opcode) != Some(Opcode::Halt) {
+                instructions.push(Instruction::new(Opcode::Halt, None));
+            }
+            self.vm.run_continuing(instructions, constants)?;
+        }
+        Ok(self.vm.stack_top().ok().cloned())
+    }
+
+    /// Handles one line of input, either a `:` command or code to run
+    pub fn handle(&mut self, line: &str) -> Result<ReplOutput, ReplError> {
+        let line = line.trim();
+        let message = |text: &str| Ok(ReplOutput::Message(text.to_string()));
+        match line {
+            ":quit" | ":q" => Ok(ReplOutput::Quit),
+            ":help" | ":h" => message(HELP),
+            ":asm" => {
+                self.mode = ReplMode::Assembly;
+                message("Reading assembly")
+            }
+            ":expr" => {
+                self.mode = ReplMode::Expression;
+                message("Reading expressions")
+            }
+            ":stack" => Ok(ReplOutput::Message(describe_stack(&self.vm))),
+            ":heap" => Ok(ReplOutput::Message(describe_heap(&self.vm))),
+            ":reset" => {
+                self.vm = VirtualMachine::new();
+                message("Stack and heap cleared")
+            }
+            command if command.starts_with(':') => {
+                Ok(ReplOutput::Message(format!("Unknown command {}; try :help", command)))
+            }
+            "" => Ok(ReplOutput::Value(self.vm.stack_top().ok().cloned())),
+            code => self.eval_line(code).map(ReplOutput::Value),
+        }
+    }
+
+    /// Reads lines from `input` until it ends or `:quit`, writing a prompt
+    /// and each result to `output`
+    pub fn run(&mut self, mut input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        let mut line = String::new();
+        loop {
+            write!(output, "> ")?;
+            output.flush()?;
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            match self.handle(&line) {
+                Ok(ReplOutput::Quit) => return Ok(()),
+                Ok(ReplOutput::Message(text)) => writeln!(output, "{}", text)?,
+                Ok(ReplOutput::Value(Some(value))) => writeln!(output, "{}", value)?,
+                Ok(ReplOutput::Value(None)) => writeln!(output, "(empty stack)")?,
+                Err(e) => writeln!(output, "error: {}", e)?,
+            }
+        }
+    }
+}
+
+impl Default for Repl {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The operand stack, a value a line, bottom first
+pub(crate) fn describe_stack(vm: &VirtualMachine) -> String {
+    let values = vm.stack_contents();
+    if values.is_empty() {
+        return "(empty stack)".to_string();
+    }
+    let lines: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(depth, value)| format!("{:4}: {}", depth, value))
+        .collect();
+    lines.join("\n")
+}
+
+pub(crate) fn describe_heap(vm: &VirtualMachine) -> String {
+    format!(
+        "{} objects, {} bytes in use, {} bytes allocated in all",
+        vm.heap_allocated_objects(),
+        vm.memory_usage().heap_bytes,
+        vm.heap_total_bytes()
+    )
+}
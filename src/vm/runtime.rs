@@ -1,16 +1,62 @@
+use crate::vm::assembler::mnemonic;
 use crate::vm::call_frame::CallStack;
-use crate::vm::heap::Heap;
+use crate::vm::heap::{Heap, HeapError, Object, ObjectContents};
+use crate::vm::heap_graph::HeapGraph;
 use crate::vm::instruction::{ExecutionError, Instruction, InstructionDispatcher, Opcode};
+use crate::vm::io::{StdIo, VmIo, WriterIo};
 use crate::vm::jit::HotSpotProfiler;
-use crate::vm::stack::OperandStack;
+use crate::vm::linker::{LinkError, Linker};
+use crate::vm::module::{exports, BytecodeModule, DebugInfo, FunctionInfo, SymbolTable};
+use crate::vm::stack::{OperandStack, StackError};
 use crate::vm::types::Value;
+use crate::vm::verifier::{verify_module, VerificationError, Verifier};
+use std::collections::{BTreeMap, HashMap};
 use std::fmt;
+use std::io::{self, Write};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub enum VmError {
     ExecutionError(ExecutionError),
+    /// An instruction of the program failed; see [`VmError::at`]
+    ExecutionErrorAt {
+        error: ExecutionError,
+        pc: usize,
+        /// Source location, if the module has debug info for `pc`
+        line: Option<usize>,
+        file: Option<String>,
+        context: Box<ErrorContext>,
+    },
     ProgramCounterOutOfBounds(usize, usize), // pc, program_length
     InvalidProgramState(String),
+    /// The verifier rejected a program before it was loaded
+    VerificationFailed(VerificationError),
+    /// No exported function has this name
+    UnknownFunction(String),
+    /// No function in the function table starts at this address
+    NoFunctionAt(usize),
+    /// Modules passed to [`VirtualMachine::load_modules`] could not be
+    /// linked
+    LinkFailed(LinkError),
+    /// [`VirtualMachine::run`] stopped at a pause requested through an
+    /// [`ExecutionHandle`]
+    Paused,
+    /// A run was stopped through an [`InterruptHandle`]. The VM is left
+    /// at the instruction it would have run next, so the program can be
+    /// resumed.
+    Interrupted,
+    /// A run took longer than [`VirtualMachine::set_time_limit`] allows.
+    /// The VM is left as it stopped, so the program can be resumed.
+    TimeLimitExceeded(Duration),
+    /// The operand stack and heap together held more than
+    /// [`VirtualMachine::set_memory_limit`] allows. Nothing was executed
+    /// past the limit, so the program can be resumed once memory is freed
+    /// or the limit raised.
+    MemoryLimitExceeded { limit: usize, usage: MemoryUsage },
     NoProgram,
 }
 
@@ -18,6 +64,27 @@ impl fmt::Display for VmError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             VmError::ExecutionError(e) => write!(f, "Execution error: {}", e),
+            VmError::ExecutionErrorAt { error, pc, line, file, context } => {
+                match (file, line) {
+                    (Some(file), Some(line)) => {
+                        write!(f, "Execution error at {}:{} (pc {}): {}", file, line, pc, error)?
+                    }
+                    (None, Some(line)) => {
+                        write!(f, "Execution error at line {} (pc {}): {}", line, pc, error)?
+                    }
+                    (_, None) => write!(f, "Execution error at pc {}: {}", pc, error)?,
+                }
+                let opcode = context.opcode;
+                let name = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+                write!(f, " (in {}, stack depth {})", name, context.stack_depth)?;
+                // `{:#}` adds the traceback, innermost frame first
+                if f.alternate() {
+                    for entry in &context.backtrace {
+                        write!(f, "\n    {}", entry)?;
+                    }
+                }
+                Ok(())
+            }
             VmError::ProgramCounterOutOfBounds(pc, len) => {
                 write!(
                     f,
@@ -26,6 +93,20 @@ impl fmt::Display for VmError {
                 )
             }
             VmError::InvalidProgramState(msg) => write!(f, "Invalid program state: {}", msg),
+            VmError::VerificationFailed(e) => write!(f, "Verification failed at {}", e),
+            VmError::UnknownFunction(name) => write!(f, "No exported function named {}", name),
+            VmError::NoFunctionAt(address) => write!(f, "No function starts at address {}", address),
+            VmError::LinkFailed(e) => write!(f, "Link failed: {}", e),
+            VmError::Paused => write!(f, "Execution paused"),
+            VmError::Interrupted => write!(f, "Execution interrupted"),
+            VmError::TimeLimitExceeded(limit) => {
+                write!(f, "Execution exceeded its time limit of {:?}", limit)
+            }
+            VmError::MemoryLimitExceeded { limit, usage } => write!(
+                f,
+                "Execution exceeded its memory limit of {} bytes ({} bytes of stack, {} bytes of heap)",
+                limit, usage.stack_bytes, usage.heap_bytes
+            ),
             VmError::NoProgram => write!(f, "No program loaded"),
         }
     }
@@ -33,136 +114,1240 @@ impl fmt::Display for VmError {
 
 impl std::error::Error for VmError {}
 
+/// Memory a program is using, as [`VirtualMachine::memory_usage`] counts
+/// it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoryUsage {
+    /// Values on the operand stack, at their in-place size
+    pub stack_bytes: usize,
+    /// Bytes the heap has allocated and not yet freed
+    pub heap_bytes: usize,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> usize {
+        self.stack_bytes + self.heap_bytes
+    }
+}
+
+/// Instructions executed per opcode, kept by every VM whether or not it
+/// is profiling; see [`VirtualMachine::opcode_statistics`]
+#[derive(Clone, PartialEq, Eq)]
+pub struct OpcodeStatistics {
+    counts: [u64; 256],
+}
+
+impl OpcodeStatistics {
+    pub fn new() -> Self {
+        Self { counts: [0; 256] }
+    }
+
+    pub fn count(&self, opcode: Opcode) -> u64 {
+        self.counts[opcode as usize]
+    }
+
+    pub fn total(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Opcodes executed at least once and how often, most executed first
+    pub fn most_executed(&self) -> Vec<(Opcode, u64)> {
+        let mut executed: Vec<(Opcode, u64)> = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count > 0)
+            .filter_map(|(byte, &count)| Some((Opcode::from_u8(byte as u8)?, count)))
+            .collect();
+        executed.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| (a.0 as u8).cmp(&(b.0 as u8))));
+        executed
+    }
+
+    fn record(&mut self, opcode: Opcode) {
+        self.counts[opcode as usize] += 1;
+    }
+}
+
+impl Default for OpcodeStatistics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for OpcodeStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.most_executed()).finish()
+    }
+}
+
+/// What a VM has done so far, gathered by [`VirtualMachine::statistics`]
+/// for scripts that compare runs or configurations
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ExecutionStatistics {
+    /// Wall-clock time spent in [`VirtualMachine::run`] and
+    /// [`VirtualMachine::resume`]
+    pub run_time_seconds: f64,
+    pub instructions: u64,
+    /// Zero if no time has been measured
+    pub instructions_per_second: f64,
+    /// Instructions executed per opcode mnemonic, for opcodes executed at
+    /// least once
+    pub opcodes: BTreeMap<String, u64>,
+    pub gc: GcStatistics,
+    /// Present while the VM is profiling
+    pub jit: Option<JitStatistics>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GcStatistics {
+    pub live_objects: usize,
+    pub young_objects: usize,
+    pub old_objects: usize,
+    pub bytes_in_use: usize,
+    /// Bytes allocated over the VM's life, freed or not
+    pub bytes_allocated: usize,
+    pub collections: usize,
+    pub collected_objects: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct JitStatistics {
+    /// Function entries and loop iterations the profiler has counted
+    pub hot_spot_executions: u64,
+    /// Entry addresses of functions past the profiler's threshold
+    pub hot_functions: Vec<usize>,
+    /// Addresses of loop heads past the profiler's threshold
+    pub hot_loops: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl ExecutionStatistics {
+    /// These statistics as a JSON object
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("statistics serialize to JSON")
+    }
+}
+
+/// Function for [`VirtualMachine::call_function`] to call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionRef<'a> {
+    /// An exported function, by name
+    Name(&'a str),
+    /// A function in the function table, by entry address
+    Address(usize),
+}
+
+impl<'a> From<&'a str> for FunctionRef<'a> {
+    fn from(name: &'a str) -> Self {
+        FunctionRef::Name(name)
+    }
+}
+
+impl From<usize> for FunctionRef<'_> {
+    fn from(address: usize) -> Self {
+        FunctionRef::Address(address)
+    }
+}
+
+/// One frame of [`VirtualMachine::stack_trace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackTraceEntry {
+    /// The function the frame is running, if it is in the function table
+    pub function: Option<String>,
+    /// The instruction executing, or the call waiting to return
+    pub pc: usize,
+    pub line: Option<usize>,
+    pub file: Option<String>,
+}
+
+impl fmt::Display for StackTraceEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at {}", self.function.as_deref().unwrap_or("<top level>"))?;
+        match (&self.file, self.line) {
+            (Some(file), Some(line)) => write!(f, " ({}:{}, pc {})", file, line, self.pc),
+            (None, Some(line)) => write!(f, " (line {}, pc {})", line, self.pc),
+            (_, None) => write!(f, " (pc {})", self.pc),
+        }
+    }
+}
+
+/// What the VM was doing when an instruction failed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    pub opcode: Opcode,
+    /// Values on the operand stack when the instruction started
+    pub stack_depth: usize,
+    /// The frames active at the failure, innermost first, as
+    /// [`VirtualMachine::stack_trace`] gives them
+    pub backtrace: Vec<StackTraceEntry>,
+}
+
+impl VmError {
+    /// Attaches where an execution error happened: the instruction at `pc`,
+    /// the operand stack depth and the active `frames`, innermost first,
+    /// whose first entry gives the source location. Other errors are
+    /// returned as they are.
+    pub fn at(
+        self,
+        pc: usize,
+        opcode: Opcode,
+        stack_depth: usize,
+        frames: Vec<StackTraceEntry>,
+    ) -> VmError {
+        match self {
+            VmError::ExecutionError(error) => {
+                let innermost = frames.first();
+                VmError::ExecutionErrorAt {
+                    error,
+                    pc,
+                    line: innermost.and_then(|frame| frame.line),
+                    file: innermost.and_then(|frame| frame.file.clone()),
+                    context: Box::new(ErrorContext {
+                        opcode,
+                        stack_depth,
+                        backtrace: frames,
+                    }),
+                }
+            }
+            other => other,
+        }
+    }
+
+    /// Frames active when an execution error happened, innermost first;
+    /// empty for other errors
+    pub fn backtrace(&self) -> &[StackTraceEntry] {
+        match self {
+            VmError::ExecutionErrorAt { context, .. } => &context.backtrace,
+            _ => &[],
+        }
+    }
+}
+
 impl From<ExecutionError> for VmError {
     fn from(err: ExecutionError) -> Self {
         VmError::ExecutionError(err)
     }
 }
 
+/// Failure reported by a host function, which stops the guest program
+/// with [`ExecutionError::HostFunction`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NativeError {
+    message: String,
+}
+
+impl NativeError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl fmt::Display for NativeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for NativeError {}
+
+impl From<String> for NativeError {
+    fn from(message: String) -> Self {
+        Self::new(message)
+    }
+}
+
+impl From<&str> for NativeError {
+    fn from(message: &str) -> Self {
+        Self::new(message)
+    }
+}
+
+impl From<io::Error> for NativeError {
+    fn from(error: io::Error) -> Self {
+        Self::new(error.to_string())
+    }
+}
+
+/// Lets a native that calls back into the guest pass its failures on
+impl From<VmError> for NativeError {
+    fn from(error: VmError) -> Self {
+        Self::new(error.to_string())
+    }
+}
+
+impl From<HeapError> for NativeError {
+    fn from(error: HeapError) -> Self {
+        Self::new(error.to_string())
+    }
+}
+
+/// Signature the VM calls host functions with; see
+/// [`VirtualMachine::register_host_function`]. `Send` and `Sync` so the VM
+/// can move between threads.
+type HostFn = dyn Fn(&mut NativeContext, &[Value]) -> Result<Vec<Value>, NativeError> + Send + Sync;
+
+/// Function the host provides to modules, which import it with `.extern`
+struct HostFunction {
+    name: String,
+    arity: usize,
+    returns: usize,
+    /// Shared so the VM can lend itself to the function while it runs
+    function: Arc<HostFn>,
+}
+
+/// The VM as a native registered with
+/// [`VirtualMachine::register_native_with_context`] sees it while it runs
+pub struct NativeContext<'a> {
+    vm: &'a mut VirtualMachine,
+}
+
+impl NativeContext<'_> {
+    /// The VM's text streams
+    pub fn io(&mut self) -> &mut dyn VmIo {
+        &mut *self.vm.io
+    }
+
+    /// A string on the VM's heap. Values a native allocates count towards
+    /// the heap and memory limits like those the program allocates.
+    pub fn new_string(&mut self, value: impl Into<String>) -> Result<Value, NativeError> {
+        Ok(Value::GcString(self.vm.heap.allocate_string(value.into())?))
+    }
+
+    /// An object on the VM's heap with `fields`
+    pub fn new_object<S: Into<String>>(
+        &mut self,
+        fields: impl IntoIterator<Item = (S, Value)>,
+    ) -> Result<Value, NativeError> {
+        let object = Object::new();
+        for (name, value) in fields {
+            object.set_field(name.into(), value);
+        }
+        Ok(Value::GcObject(self.vm.heap.allocate_object(object)?))
+    }
+
+    /// An array on the VM's heap
+    pub fn new_array(&mut self, elements: Vec<Value>) -> Result<Value, NativeError> {
+        Ok(Value::Array(self.vm.heap.allocate_array(elements)?))
+    }
+
+    /// A byte buffer on the VM's heap
+    pub fn new_bytes(&mut self, bytes: Vec<u8>) -> Result<Value, NativeError> {
+        Ok(Value::Bytes(self.vm.heap.allocate_bytes(bytes)?))
+    }
+
+    /// The global `name` as `LDG` reads it; see
+    /// [`VirtualMachine::get_global`]
+    pub fn global(&self, name: &str) -> Option<&Value> {
+        self.vm.get_global(name)
+    }
+
+    /// Sets the global `name` as `STG` does; see
+    /// [`VirtualMachine::set_global`]
+    pub fn set_global(&mut self, name: impl Into<String>, value: Value) {
+        self.vm.set_global(name, value);
+    }
+
+    /// The VM's profiler, if profiling is enabled
+    pub fn profiler(&mut self) -> Option<&mut HotSpotProfiler> {
+        self.vm.profiler.as_mut()
+    }
+
+    /// Calls a guest function from inside the native, as
+    /// [`VirtualMachine::call_function`] does from outside the VM. The
+    /// native's caller is set aside for the call and put back afterwards,
+    /// whether the call succeeds or not, so natives and guest functions
+    /// can call each other to any depth the call depth limit allows.
+    pub fn call<'f>(
+        &mut self,
+        function: impl Into<FunctionRef<'f>>,
+        args: &[Value],
+    ) -> Result<Value, VmError> {
+        let mut results = self.call_values(function, args)?;
+        Ok(results.pop().unwrap_or(Value::Null))
+    }
+
+    /// Like [`call`](Self::call), but gives back every value the function
+    /// returns
+    pub fn call_values<'f>(
+        &mut self,
+        function: impl Into<FunctionRef<'f>>,
+        args: &[Value],
+    ) -> Result<Vec<Value>, VmError> {
+        let address = self.vm.resolve_function(function.into(), args.len())?;
+        let saved = self.vm.set_aside();
+        let results = self.vm.run_call(address, args);
+        self.vm.put_back(saved);
+        results
+    }
+}
+
+/// What [`VirtualMachine::call_function`] puts aside while it runs a call
+struct SavedExecution {
+    operand_stack: OperandStack,
+    call_stack: CallStack,
+    pc: usize,
+    halted: bool,
+    fuel: u64,
+}
+
+/// How a run stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunOutcome {
+    /// The program reached `HALT`
+    Halted,
+    /// A breakpoint was reached; running again continues from it
+    Stopped(BreakpointHit),
+    /// The fuel ran out first; [`VirtualMachine::add_fuel`] and
+    /// [`VirtualMachine::resume`] carry on where it stopped
+    Exhausted,
+    /// A pause was requested; [`VirtualMachine::resume`] continues from the
+    /// next instruction
+    Paused,
+}
+
+/// The breakpoint a run stopped at, before running its instruction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BreakpointHit {
+    pub pc: usize,
+    /// Times this breakpoint has stopped the program, including this one
+    pub hits: u64,
+}
+
+/// Condition of a breakpoint added with
+/// [`VirtualMachine::add_conditional_breakpoint`]
+type BreakpointCondition = dyn Fn(&VirtualMachine) -> bool + Send;
+
+struct Breakpoint {
+    condition: Option<Box<BreakpointCondition>>,
+    hits: u64,
+}
+
+/// Something a hook set with [`VirtualMachine::set_hook`] observes
+#[derive(Debug, Clone, Copy)]
+pub enum HookEvent<'a> {
+    /// `instruction` at `pc` is about to run, with `stack_depth` values on
+    /// the operand stack
+    BeforeInstruction {
+        pc: usize,
+        instruction: &'a Instruction,
+        stack_depth: usize,
+    },
+    /// `instruction` at `pc` ran without error, leaving `stack_depth` values
+    AfterInstruction {
+        pc: usize,
+        instruction: &'a Instruction,
+        stack_depth: usize,
+    },
+    /// The call at `pc` entered the function at `target`, `depth` frames
+    /// deep
+    Call { pc: usize, target: usize, depth: usize },
+    /// The return at `pc` went back to `target`, leaving `depth` frames
+    Return { pc: usize, target: usize, depth: usize },
+    /// A garbage collection freed `collected` objects
+    GarbageCollection { collected: usize },
+}
+
+/// Observer set with [`VirtualMachine::set_hook`]
+type Hook = dyn Fn(&HookEvent) + Send;
+
+/// Requests that a running [`VirtualMachine`] pause. Handles can be cloned
+/// and sent to other threads, or captured by host functions, so a host can
+/// stop a program it does not control directly.
+#[derive(Debug, Clone, Default)]
+pub struct ExecutionHandle {
+    pause_requested: Arc<AtomicBool>,
+}
+
+impl ExecutionHandle {
+    /// Pauses the VM before the next instruction it runs; a VM that is not
+    /// running pauses as soon as it starts
+    pub fn pause(&self) {
+        self.pause_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether a pause is waiting to take effect
+    pub fn is_pause_requested(&self) -> bool {
+        self.pause_requested.load(Ordering::SeqCst)
+    }
+
+    /// Clears a waiting pause, returning whether there was one. Checked
+    /// before every instruction, so the common case is a plain load.
+    fn take_pause_request(&self) -> bool {
+        self.pause_requested.load(Ordering::Relaxed)
+            && self.pause_requested.swap(false, Ordering::SeqCst)
+    }
+}
+
+/// Stops a running [`VirtualMachine`] with [`VmError::Interrupted`], e.g.
+/// from a Ctrl-C handler or a server's request timeout. Unlike a pause,
+/// which a run reports as an outcome, an interrupt is an error, and it
+/// also stops [`VirtualMachine::call_function`].
+#[derive(Debug, Clone, Default)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// Interrupts the VM before the next instruction it runs; a VM that is
+    /// not running is interrupted as soon as it starts
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether an interrupt is waiting to take effect
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted.load(Ordering::SeqCst)
+    }
+
+    /// Fails with [`VmError::Interrupted`] if an interrupt is waiting,
+    /// clearing it so the VM can run again
+    fn check(&self) -> Result<(), VmError> {
+        if self.interrupted.load(Ordering::Relaxed) && self.interrupted.swap(false, Ordering::SeqCst) {
+            return Err(VmError::Interrupted);
+        }
+        Ok(())
+    }
+}
+
+/// The state of a [`VirtualMachine`]'s run at one point, taken with
+/// [`VirtualMachine::snapshot`]. Heap objects the program could reach are
+/// kept along with a copy of their contents, so restoring also undoes
+/// writes to them. The loaded program, breakpoints, host functions,
+/// profiler and streams are not part of it.
+pub struct VmSnapshot {
+    operand_stack: OperandStack,
+    call_stack: CallStack,
+    dispatcher: InstructionDispatcher,
+    heap: Heap,
+    opcode_statistics: OpcodeStatistics,
+    globals: HashMap<String, Value>,
+    halted: bool,
+    stopped_at: Option<usize>,
+    objects: Vec<ObjectContents>,
+}
+
+// The program, heap and everything the host registers are owned or
+// shared through `Arc`, so a paused VM can move to another thread; this
+// keeps that from regressing
+const _: () = {
+    const fn assert_send<T: Send>() {}
+    assert_send::<VirtualMachine>();
+};
+
 pub struct VirtualMachine {
     operand_stack: OperandStack,
     call_stack: CallStack,
     dispatcher: InstructionDispatcher,
-    program: Vec<Instruction>,
+    /// Shared so a run can hold on to it and borrow each instruction it
+    /// executes while the VM is borrowed mutably
+    program: Arc<[Instruction]>,
     constants: Vec<Value>,
+    functions: Vec<FunctionInfo>,
+    debug_info: DebugInfo,
+    /// Names from the loaded module, for naming frames of functions missing
+    /// from the function table
+    symbols: SymbolTable,
     heap: Heap,
     profiler: Option<HotSpotProfiler>,
+    opcode_statistics: OpcodeStatistics,
     halted: bool,
     max_instructions: u64,
+    /// Instructions left before a fuelled run pauses
+    fuel: u64,
+    time_limit: Option<Duration>,
+    memory_limit: Option<usize>,
+    /// Time spent running, for [`VirtualMachine::statistics`]
+    run_time: Duration,
+    execution: ExecutionHandle,
+    interrupt: InterruptHandle,
+    hook: Option<Box<Hook>>,
+    breakpoints: BTreeMap<usize, Breakpoint>,
+    /// Breakpoint the program last stopped at, which does not stop it
+    /// again when it continues
+    stopped_at: Option<usize>,
+    /// Function and loop thresholds for profilers the VM creates
+    jit_thresholds: Option<(u64, u64)>,
+    host_functions: Vec<HostFunction>,
+    host_globals: HashMap<String, Value>,
+    /// Globals read and written at run time with `LoadGlobal` and
+    /// `StoreGlobal`
+    globals: HashMap<String, Value>,
+    /// Constants the loaded modules export, which modules added later
+    /// can import
+    exported_constants: BTreeMap<String, usize>,
+    /// Text streams for `Print`, `ReadLine` and host functions
+    io: Box<dyn VmIo>,
 }
 
-impl VirtualMachine {
+/// Configures a [`VirtualMachine`]; every setting not given keeps the
+/// default of [`VirtualMachine::new`]
+pub struct VmBuilder {
+    max_instructions: u64,
+    stack_capacity: Option<usize>,
+    max_call_depth: Option<usize>,
+    heap_limit: Option<usize>,
+    time_limit: Option<Duration>,
+    memory_limit: Option<usize>,
+    jit_thresholds: Option<(u64, u64)>,
+    profiling: bool,
+    io: Option<Box<dyn VmIo>>,
+}
+
+impl VmBuilder {
     const DEFAULT_MAX_INSTRUCTIONS: u64 = 1_000_000; // Prevent infinite loops
 
     pub fn new() -> Self {
         Self {
-            operand_stack: OperandStack::new(),
-            call_stack: CallStack::new(),
-            dispatcher: InstructionDispatcher::new(),
-            program: Vec::new(),
-            constants: Vec::new(),
-            heap: Heap::new(),
-            profiler: None,
-            halted: false,
             max_instructions: Self::DEFAULT_MAX_INSTRUCTIONS,
+            stack_capacity: None,
+            max_call_depth: None,
+            heap_limit: None,
+            time_limit: None,
+            memory_limit: None,
+            jit_thresholds: None,
+            profiling: false,
+            io: None,
         }
     }
 
-    pub fn with_max_instructions(max_instructions: u64) -> Self {
-        Self {
-            operand_stack: OperandStack::new(),
-            call_stack: CallStack::new(),
+    /// Fuel [`VirtualMachine::run`] gives a program, i.e. the instructions
+    /// it may execute before it is stopped
+    pub fn with_max_instructions(mut self, max_instructions: u64) -> Self {
+        self.max_instructions = max_instructions;
+        self
+    }
+
+    /// Values the operand stack may hold
+    pub fn with_stack_capacity(mut self, capacity: usize) -> Self {
+        self.stack_capacity = Some(capacity);
+        self
+    }
+
+    /// Calls that may be active at once
+    pub fn with_max_call_depth(mut self, depth: usize) -> Self {
+        self.max_call_depth = Some(depth);
+        self
+    }
+
+    /// Bytes the heap may allocate before allocations fail
+    pub fn with_heap_limit(mut self, bytes: usize) -> Self {
+        self.heap_limit = Some(bytes);
+        self
+    }
+
+    /// Wall-clock time each run may take; see
+    /// [`VirtualMachine::set_time_limit`]
+    pub fn with_time_limit(mut self, limit: Duration) -> Self {
+        self.time_limit = Some(limit);
+        self
+    }
+
+    /// Bytes the operand stack and heap may hold between them; see
+    /// [`VirtualMachine::set_memory_limit`]
+    pub fn with_memory_limit(mut self, bytes: usize) -> Self {
+        self.memory_limit = Some(bytes);
+        self
+    }
+
+    /// Executions after which the profiler reports a function or loop as
+    /// hot
+    pub fn with_jit_thresholds(mut self, function_threshold: u64, loop_threshold: u64) -> Self {
+        self.jit_thresholds = Some((function_threshold, loop_threshold));
+        self
+    }
+
+    /// Whether the VM starts with profiling enabled
+    pub fn with_profiling(mut self, enabled: bool) -> Self {
+        self.profiling = enabled;
+        self
+    }
+
+    /// Sends the program's standard output to `sink`, as
+    /// [`with_io`](Self::with_io) does with a [`WriterIo`]
+    pub fn with_output(self, sink: impl Write + Send + 'static) -> Self {
+        self.with_io(WriterIo::new(sink))
+    }
+
+    /// Gives the program `io` for its text streams in place of the
+    /// process's, and registers host functions that use it: `print` and
+    /// `println` write their argument to standard output, the second
+    /// followed by a newline, `eprintln` does the same on standard error,
+    /// and `read_line` returns the next line of input or `null`
+    pub fn with_io(mut self, io: impl VmIo + 'static) -> Self {
+        self.io = Some(Box::new(io));
+        self
+    }
+
+    pub fn build(self) -> VirtualMachine {
+        let mut vm = VirtualMachine {
+            operand_stack: match self.stack_capacity {
+                Some(capacity) => OperandStack::with_capacity(capacity),
+                None => OperandStack::new(),
+            },
+            call_stack: match self.max_call_depth {
+                Some(depth) => CallStack::with_max_depth(depth),
+                None => CallStack::new(),
+            },
             dispatcher: InstructionDispatcher::new(),
-            program: Vec::new(),
+            program: Arc::from([]),
             constants: Vec::new(),
-            heap: Heap::new(),
+            functions: Vec::new(),
+            debug_info: DebugInfo::default(),
+            symbols: SymbolTable::default(),
+            heap: match self.heap_limit {
+                Some(limit) => Heap::with_initial_size(limit),
+                None => Heap::new(),
+            },
             profiler: None,
+            opcode_statistics: OpcodeStatistics::new(),
             halted: false,
-            max_instructions,
+            max_instructions: self.max_instructions,
+            fuel: 0,
+            time_limit: self.time_limit,
+            memory_limit: self.memory_limit,
+            run_time: Duration::ZERO,
+            execution: ExecutionHandle::default(),
+            interrupt: InterruptHandle::default(),
+            hook: None,
+            breakpoints: BTreeMap::new(),
+            stopped_at: None,
+            jit_thresholds: self.jit_thresholds,
+            host_functions: Vec::new(),
+            host_globals: HashMap::new(),
+            globals: HashMap::new(),
+            exported_constants: BTreeMap::new(),
+            io: Box::new(StdIo),
+        };
+        if self.profiling {
+            vm.enable_profiling();
+        }
+        if let Some(io) = self.io {
+            vm.io = io;
+            let printers = [("print", "", false), ("println", "\n", false), ("eprintln", "\n", true)];
+            for (name, end, stderr) in printers {
+                let function = move |context: &mut NativeContext, args: &[Value]| {
+                    let text = format!("{}{}", args[0], end);
+                    let io = context.io();
+                    if stderr { io.write_stderr(&text)? } else { io.write_stdout(&text)? }
+                    Ok(Vec::new())
+                };
+                vm.add_host_function(name.to_string(), 1, 0, Arc::new(function));
+            }
+            vm.register_native_with_context("read_line", 0, |context, _| {
+                let line = context.io().read_line()?;
+                Ok(line.map_or(Value::Null, |line| Value::String(line.into())))
+            });
         }
+        vm
+    }
+}
+
+impl Default for VmBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualMachine {
+    pub fn new() -> Self {
+        VmBuilder::new().build()
+    }
+
+    /// Builder for a VM with limits and settings other than the defaults
+    pub fn builder() -> VmBuilder {
+        VmBuilder::new()
     }
 
     pub fn load_program(&mut self, program: Vec<Instruction>) {
-        self.program = program;
+        self.program = program.into();
+        self.functions.clear();
+        self.debug_info = DebugInfo::default();
+        self.symbols = SymbolTable::default();
+        self.exported_constants.clear();
         self.reset();
     }
 
+    /// Runs `program` from its start on the current operand stack and heap,
+    /// so values left by earlier runs stay available to it
+    pub fn run_continuing(
+        &mut self,
+        program: Vec<Instruction>,
+        constants: Vec<Value>,
+    ) -> Result<RunOutcome, VmError> {
+        self.program = program.into();
+        self.constants = constants;
+        self.functions.clear();
+        self.debug_info = DebugInfo::default();
+        self.symbols = SymbolTable::default();
+        self.exported_constants.clear();
+        self.call_stack.clear();
+        self.operand_stack.set_base(0);
+        self.dispatcher = InstructionDispatcher::new();
+        self.halted = false;
+        self.run()
+    }
+
     pub fn reset(&mut self) {
         self.operand_stack.clear();
         self.call_stack.clear();
         self.dispatcher = InstructionDispatcher::new();
+        self.dispatcher.set_functions(&self.functions);
+        self.opcode_statistics = OpcodeStatistics::new();
+        self.run_time = Duration::ZERO;
         self.halted = false;
+        self.stopped_at = None;
+    }
+
+    /// Runs the program with the fuel set by
+    /// [`VmBuilder::with_max_instructions`] until it halts or reaches a
+    /// breakpoint, failing if the fuel runs out. The VM is left as it
+    /// stopped, so running again continues from a breakpoint, and
+    /// [`add_fuel`](Self::add_fuel) and [`resume`](Self::resume) can still
+    /// finish a program that ran out.
+    pub fn run(&mut self) -> Result<RunOutcome, VmError> {
+        match self.run_with_fuel(self.max_instructions)? {
+            outcome @ (RunOutcome::Halted | RunOutcome::Stopped(_)) => Ok(outcome),
+            RunOutcome::Paused => Err(VmError::Paused),
+            RunOutcome::Exhausted => Err(VmError::InvalidProgramState(
+                "Maximum instruction count exceeded".to_string(),
+            )),
+        }
+    }
+
+    /// Runs the program until it halts or has executed `fuel` instructions,
+    /// replacing any fuel left from earlier runs
+    pub fn run_with_fuel(&mut self, fuel: u64) -> Result<RunOutcome, VmError> {
+        self.fuel = fuel;
+        self.resume()
     }
 
-    pub fn run(&mut self) -> Result<(), VmError> {
+    /// Continues the program from where it stopped with the fuel it has
+    /// left
+    pub fn resume(&mut self) -> Result<RunOutcome, VmError> {
+        let started = Instant::now();
+        let outcome = self.run_until_stopped();
+        self.run_time += started.elapsed();
+        outcome
+    }
+
+    fn run_until_stopped(&mut self) -> Result<RunOutcome, VmError> {
         if self.program.is_empty() {
             return Err(VmError::NoProgram);
         }
 
-        while !self.halted && self.dispatcher.instruction_count() < self.max_instructions {
-            self.step()?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("run", pc = self.dispatcher.current_pc(), fuel = self.fuel)
+            .entered();
+
+        // Instruction boundaries are the safepoints: the VM is consistent
+        // there and the next step starts at the program counter
+        let deadline = self.deadline();
+        let program = Arc::clone(&self.program);
+        while !self.halted {
+            self.interrupt.check()?;
+            if self.execution.take_pause_request() {
+                return Ok(RunOutcome::Paused);
+            }
+            if let Some(hit) = self.check_breakpoint() {
+                return Ok(RunOutcome::Stopped(hit));
+            }
+            self.check_deadline(deadline)?;
+            if self.fuel == 0 {
+                return Ok(RunOutcome::Exhausted);
+            }
+            self.fuel -= 1;
+            self.step_through(&program)?;
         }
+        Ok(RunOutcome::Halted)
+    }
 
-        if self.dispatcher.instruction_count() >= self.max_instructions {
-            return Err(VmError::InvalidProgramState(
-                "Maximum instruction count exceeded".to_string(),
-            ));
+    /// Moves the VM to a new thread and [`resume`](Self::resume)s the
+    /// program there, handing the VM back with the outcome when the run
+    /// stops. A handle taken with [`execution_handle`](Self::execution_handle)
+    /// beforehand can pause it, and the paused VM can go on to resume in
+    /// this thread or another.
+    pub fn resume_on_thread(mut self) -> thread::JoinHandle<(Self, Result<RunOutcome, VmError>)> {
+        thread::spawn(move || {
+            let outcome = self.resume();
+            (self, outcome)
+        })
+    }
+
+    /// Stops runs before the instruction at `pc` executes, replacing any
+    /// breakpoint already there
+    pub fn add_breakpoint(&mut self, pc: usize) {
+        self.breakpoints.insert(pc, Breakpoint { condition: None, hits: 0 });
+    }
+
+    /// Stops runs before the instruction at `pc` when `condition` holds.
+    /// The condition sees the VM as it is at the breakpoint, e.g. through
+    /// [`stack_top`](Self::stack_top), [`stack_contents`](Self::stack_contents)
+    /// and [`local`](Self::local).
+    pub fn add_conditional_breakpoint(
+        &mut self,
+        pc: usize,
+        condition: impl Fn(&VirtualMachine) -> bool + Send + 'static,
+    ) {
+        let condition = Some(Box::new(condition) as Box<BreakpointCondition>);
+        self.breakpoints.insert(pc, Breakpoint { condition, hits: 0 });
+    }
+
+    /// Removes the breakpoint at `pc`, returning whether there was one
+    pub fn remove_breakpoint(&mut self, pc: usize) -> bool {
+        self.breakpoints.remove(&pc).is_some()
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// Addresses with breakpoints, in order
+    pub fn breakpoints(&self) -> impl Iterator<Item = usize> + '_ {
+        self.breakpoints.keys().copied()
+    }
+
+    /// The breakpoint stopping the program before its next instruction,
+    /// if any. A program continuing from a breakpoint passes it once.
+    fn check_breakpoint(&mut self) -> Option<BreakpointHit> {
+        let pc = self.dispatcher.current_pc();
+        if self.stopped_at.take() == Some(pc) {
+            return None;
         }
+        let breakpoint = self.breakpoints.get(&pc)?;
+        if let Some(condition) = &breakpoint.condition
+            && !condition(self) {
+                return None;
+            }
+        let breakpoint = self.breakpoints.get_mut(&pc)?;
+        breakpoint.hits += 1;
+        self.stopped_at = Some(pc);
+        Some(BreakpointHit { pc, hits: breakpoint.hits })
+    }
 
-        Ok(())
+    /// Limits the wall-clock time of each run, [`resume`](Self::resume) or
+    /// [`call_function`](Self::call_function) to `limit`, failing with
+    /// [`VmError::TimeLimitExceeded`] past it. The clock is read before
+    /// branches, calls and returns, so straight-line code runs on to the
+    /// next one.
+    pub fn set_time_limit(&mut self, limit: Duration) {
+        self.time_limit = Some(limit);
     }
 
-    pub fn step(&mut self) -> Result<(), VmError> {
-        if self.halted {
+    pub fn clear_time_limit(&mut self) {
+        self.time_limit = None;
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.time_limit
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.time_limit.map(|limit| Instant::now() + limit)
+    }
+
+    /// Fails if `deadline` has passed and the next instruction is a
+    /// safepoint for the time limit
+    fn check_deadline(&self, deadline: Option<Instant>) -> Result<(), VmError> {
+        let (Some(deadline), Some(limit)) = (deadline, self.time_limit) else {
             return Ok(());
+        };
+        let at_safepoint = self.current_instruction().is_some_and(|instruction| {
+            matches!(
+                instruction.opcode(),
+                Opcode::Jump
+                    | Opcode::JumpIfTrue
+                    | Opcode::JumpIfFalse
+                    | Opcode::Call
+                    | Opcode::CallIndirect
+                    | Opcode::CallNative
+                    | Opcode::Return
+            )
+        });
+        if at_safepoint && Instant::now() >= deadline {
+            return Err(VmError::TimeLimitExceeded(limit));
         }
+        Ok(())
+    }
 
-        if self.program.is_empty() {
-            return Err(VmError::NoProgram);
-        }
+    /// Limits the memory a program may use, operand stack and heap
+    /// together, to `bytes`, failing with [`VmError::MemoryLimitExceeded`]
+    /// before the first instruction that would run past it
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.memory_limit = Some(bytes);
+    }
 
-        let pc = self.dispatcher.current_pc();
+    pub fn clear_memory_limit(&mut self) {
+        self.memory_limit = None;
+    }
+
+    pub fn memory_limit(&self) -> Option<usize> {
+        self.memory_limit
+    }
 
-        if pc >= self.program.len() {
-            return Err(VmError::ProgramCounterOutOfBounds(pc, self.program.len()));
+    /// Memory the program is using now
+    pub fn memory_usage(&self) -> MemoryUsage {
+        MemoryUsage {
+            stack_bytes: self.operand_stack.size() * std::mem::size_of::<Value>(),
+            heap_bytes: self.heap.current_heap_size(),
         }
+    }
 
-        let instruction = &self.program[pc].clone();
+    fn check_memory(&self) -> Result<(), VmError> {
+        let Some(limit) = self.memory_limit else {
+            return Ok(());
+        };
+        let usage = self.memory_usage();
+        if usage.total() > limit {
+            return Err(VmError::MemoryLimitExceeded { limit, usage });
+        }
+        Ok(())
+    }
+
+    /// Handle through which the host can pause this VM while it runs
+    pub fn execution_handle(&self) -> ExecutionHandle {
+        self.execution.clone()
+    }
 
-        // Handle halt instruction specially
-        if instruction.opcode() == Opcode::Halt {
-            self.halted = true;
+    /// Handle through which the host can interrupt this VM while it runs,
+    /// from this thread or another
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        self.interrupt.clone()
+    }
+
+    /// Pauses the program at the next safepoint, as
+    /// [`ExecutionHandle::pause`] does
+    pub fn pause(&self) {
+        self.execution.pause();
+    }
+
+    /// Gives the program `fuel` more instructions to run
+    pub fn add_fuel(&mut self, fuel: u64) {
+        self.fuel = self.fuel.saturating_add(fuel);
+    }
+
+    /// Instructions the program may still run before it is paused
+    pub fn remaining_fuel(&self) -> u64 {
+        self.fuel
+    }
+
+    /// Runs the program like [`run`](Self::run) and returns its result.
+    ///
+    /// By convention a program's result is the value on top of the operand
+    /// stack when it reaches `HALT`; `None` means it left the stack empty.
+    /// The value stays on the stack, so [`stack_top`](Self::stack_top)
+    /// still sees it.
+    pub fn run_to_value(&mut self) -> Result<Option<Value>, VmError> {
+        self.run()?;
+        Ok(self.operand_stack.top().cloned())
+    }
+
+    pub fn step(&mut self) -> Result<(), VmError> {
+        let program = Arc::clone(&self.program);
+        self.step_through(&program)
+    }
+
+    /// [`step`](Self::step) on `program`, the loaded program, which runs
+    /// hold on to so that no step has to copy the instruction it executes
+    fn step_through(&mut self, program: &[Instruction]) -> Result<(), VmError> {
+        if self.halted {
             return Ok(());
         }
 
-        // Profile the instruction execution if profiling is enabled
+        let pc = self.dispatcher.current_pc();
+        let Some(instruction) = program.get(pc) else {
+            if program.is_empty() {
+                return Err(VmError::NoProgram);
+            }
+            return Err(VmError::ProgramCounterOutOfBounds(pc, program.len()));
+        };
+
+        self.check_memory()?;
+
+        let opcode = instruction.opcode();
+        let stack_depth = self.operand_stack.size();
+        let call_depth = self.call_stack.depth();
+        self.emit(HookEvent::BeforeInstruction { pc, instruction, stack_depth });
+        #[cfg(feature = "tracing")]
+        tracing::trace!(pc, ?opcode, stack_depth, "instruction");
+        self.opcode_statistics.record(opcode);
         if let Some(ref mut profiler) = self.profiler {
-            profiler.record_instruction_execution(pc, instruction.opcode());
+            profiler.record_instruction_execution(pc, opcode);
+            // What the integer fast path's sites see, for HotSpotProfiler::integer_sites
+            if opcode.has_integer_fast_path()
+                && let [.., a, b] = self.operand_stack.values()
+            {
+                profiler.record_type_observation(pc, a);
+                profiler.record_type_observation(pc, b);
+            }
         }
 
-        // Execute instruction
-        self.dispatcher
-            .execute_with_constants(instruction, &mut self.operand_stack, &mut self.call_stack, &self.constants, &mut self.heap)?;
+        self.execute(instruction)
+            .map_err(|error| VmError::from(error).at(pc, opcode, stack_depth, self.stack_trace()))?;
+
+        if self.profiler.is_some() || self.hook.is_some() {
+            self.observe_transfer(pc, instruction, call_depth);
+        }
+        Ok(())
+    }
 
-        // For control flow instructions, PC is handled by the instruction itself
-        // For all other instructions, increment PC
+    /// Executes `instruction`, leaving the program counter on the next
+    /// one to run. Everything but what needs the VM itself (host
+    /// functions, globals, I/O and halting) goes to the dispatcher, which
+    /// moves the program counter for the instructions it runs.
+    fn execute(&mut self, instruction: &Instruction) -> Result<(), ExecutionError> {
+        if self.would_overflow(instruction) {
+            return Err(ExecutionError::StackError(StackError::Overflow));
+        }
         match instruction.opcode() {
-            Opcode::Jump
-            | Opcode::JumpIfTrue
-            | Opcode::JumpIfFalse
-            | Opcode::Call
-            | Opcode::Return => {
-                // Control flow instructions manage their own PC
+            Opcode::Halt => {
+                self.halted = true;
+                return Ok(());
             }
+            Opcode::CallNative => self.call_native(instruction)?,
+            Opcode::LoadGlobal | Opcode::StoreGlobal => self.access_global(instruction)?,
+            Opcode::Print => self.print()?,
+            Opcode::ReadLine => self.read_line()?,
             _ => {
-                // Regular instructions: increment PC
-                self.dispatcher.set_pc(pc + 1);
+                return self.dispatcher.execute_with_constants(
+                    instruction,
+                    &mut self.operand_stack,
+                    &mut self.call_stack,
+                    &self.constants,
+                    &mut self.heap,
+                );
+            }
+        }
+        self.dispatcher.advance();
+        Ok(())
+    }
+
+    /// Tells the profiler and hook where `instruction`, just executed at
+    /// `pc` with `call_depth` calls active, went
+    fn observe_transfer(&mut self, pc: usize, instruction: &Instruction, call_depth: usize) {
+        let target = self.dispatcher.current_pc();
+        let depth = self.call_stack.depth();
+
+        // Calls and backward jumps are what the profiler finds hot code by
+        if let Some(ref mut profiler) = self.profiler {
+            match instruction.opcode() {
+                Opcode::Call | Opcode::CallIndirect if depth > call_depth => {
+                    profiler.record_function_entry(target);
+                }
+                Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse if target <= pc => {
+                    profiler.record_loop_iteration(target);
+                }
+                _ => {}
+            }
+            if matches!(instruction.opcode(), Opcode::JumpIfTrue | Opcode::JumpIfFalse) {
+                profiler.record_branch_taken(pc, target != pc + 1);
             }
         }
 
+        if self.hook.is_some() {
+            match instruction.opcode() {
+                Opcode::Call | Opcode::CallIndirect if depth > call_depth => {
+                    self.emit(HookEvent::Call { pc, target, depth });
+                }
+                Opcode::Return => self.emit(HookEvent::Return { pc, target, depth }),
+                _ => {}
+            }
+            let stack_depth = self.operand_stack.size();
+            self.emit(HookEvent::AfterInstruction { pc, instruction, stack_depth });
+        }
+    }
+
+    /// Runs one instruction, descending into calls; the same as
+    /// [`step`](Self::step), named to pair with the other stepping methods
+    pub fn step_into(&mut self) -> Result<(), VmError> {
+        self.step()
+    }
+
+    /// Runs one instruction, treating a call as a single step: a call runs
+    /// until it returns, stopping at the instruction after it
+    pub fn step_over(&mut self) -> Result<(), VmError> {
+        let depth = self.call_stack.depth();
+        self.step()?;
+        self.step_until(|vm| vm.call_stack.depth() <= depth)
+    }
+
+    /// Runs until the current function returns, stopping at the instruction
+    /// after its call; at the top level this runs the program to its end
+    pub fn step_out(&mut self) -> Result<(), VmError> {
+        let depth = self.call_stack.depth();
+        self.step_until(|vm| vm.call_stack.depth() < depth)
+    }
+
+    /// Steps until `done` holds or the program halts. Like [`run`], this
+    /// stops with an error at a requested pause or when the fuel `run`
+    /// gives is used up, so stepping over an endless loop still returns.
+    ///
+    /// [`run`]: Self::run
+    fn step_until(&mut self, done: impl Fn(&Self) -> bool) -> Result<(), VmError> {
+        let mut fuel = self.max_instructions;
+        let deadline = self.deadline();
+        while !self.halted && !done(self) {
+            self.interrupt.check()?;
+            if self.execution.take_pause_request() {
+                return Err(VmError::Paused);
+            }
+            self.check_deadline(deadline)?;
+            if fuel == 0 {
+                return Err(VmError::InvalidProgramState(
+                    "Maximum instruction count exceeded".to_string(),
+                ));
+            }
+            fuel -= 1;
+            self.step()?;
+        }
         Ok(())
     }
 
+    /// Calls `hook` with every [`HookEvent`] from now on, replacing any
+    /// hook set before. Hooks only observe, and must be `Send`; a tracer or
+    /// coverage tool that keeps state shares it with the hook through an
+    /// atomic or an `Arc<Mutex<_>>`:
+    ///
+    /// ```
+    /// use stack_vm_jit::vm::assembler::Assembler;
+    /// use stack_vm_jit::vm::runtime::{HookEvent, VirtualMachine};
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// let steps = Arc::new(AtomicUsize::new(0));
+    /// let counter = Arc::clone(&steps);
+    /// let mut vm = VirtualMachine::new();
+    /// vm.set_hook(Box::new(move |event: &HookEvent| {
+    ///     if let HookEvent::BeforeInstruction { .. } = event {
+    ///         counter.fetch_add(1, Ordering::Relaxed);
+    ///     }
+    /// }));
+    /// vm.load_module(Assembler::new().assemble_module("PUSH 1\nPOP\nHALT").unwrap()).unwrap();
+    /// vm.run().unwrap();
+    /// assert_eq!(steps.load(Ordering::Relaxed), 3);
+    /// ```
+    pub fn set_hook(&mut self, hook: Box<dyn Fn(&HookEvent) + Send>) {
+        self.hook = Some(hook);
+    }
+
+    pub fn clear_hook(&mut self) {
+        self.hook = None;
+    }
+
+    fn emit(&self, event: HookEvent) {
+        if let Some(hook) = &self.hook {
+            hook(&event);
+        }
+    }
+
     // Public interface methods
     pub fn stack_size(&self) -> usize {
         self.operand_stack.size()
@@ -182,8 +1367,8 @@ impl VirtualMachine {
 
     pub fn stack_top(&self) -> Result<&Value, VmError> {
         self.operand_stack
-            .peek()
-            .map_err(|e| VmError::ExecutionError(ExecutionError::StackError(e)))
+            .top()
+            .ok_or(VmError::ExecutionError(ExecutionError::StackError(StackError::Underflow)))
     }
 
     pub fn instruction_count(&self) -> u64 {
@@ -208,13 +1393,668 @@ impl VirtualMachine {
                 "Cannot load empty instruction list".to_string()
             ));
         }
+        Verifier::new(&instructions, &constants)
+            .verify()
+            .map_err(VmError::VerificationFailed)?;
         
-        self.program = instructions;
+        self.program = instructions.into();
         self.constants = constants;
+        self.functions.clear();
+        self.debug_info = DebugInfo::default();
+        self.symbols = SymbolTable::default();
+        self.exported_constants.clear();
+        self.reset();
+        Ok(())
+    }
+
+    /// Registers `function` as the host function `name`, replacing any
+    /// registered before. Modules import it with `.extern name` and call
+    /// it with `CALL name`, which pops `arity` arguments and must leave
+    /// exactly `returns` results.
+    ///
+    /// Imports are resolved when a module is loaded, so register host
+    /// functions first.
+    pub fn register_host_function(
+        &mut self,
+        name: impl Into<String>,
+        arity: usize,
+        returns: usize,
+        function: impl Fn(&[Value]) -> Result<Vec<Value>, NativeError> + Send + Sync + 'static,
+    ) {
+        self.add_host_function(name.into(), arity, returns, Arc::new(move |_, args| function(args)));
+    }
+
+    /// Registers `function` as the native function `name`, like
+    /// [`register_native`](Self::register_native), but also hands it a
+    /// [`NativeContext`] through which it can call back into the guest
+    /// program
+    pub fn register_native_with_context(
+        &mut self,
+        name: impl Into<String>,
+        arity: usize,
+        function: impl Fn(&mut NativeContext, &[Value]) -> Result<Value, NativeError> + Send + Sync + 'static,
+    ) {
+        self.add_host_function(
+            name.into(),
+            arity,
+            1,
+            Arc::new(move |context, args| Ok(vec![function(context, args)?])),
+        );
+    }
+
+    fn add_host_function(&mut self, name: String, arity: usize, returns: usize, function: Arc<HostFn>) {
+        let function = HostFunction {
+            name,
+            arity,
+            returns,
+            function,
+        };
+        match self.host_functions.iter_mut().find(|host| host.name == function.name) {
+            Some(host) => *host = function,
+            None => self.host_functions.push(function),
+        }
+    }
+
+    /// Registers `function` as the native function `name` taking `arity`
+    /// arguments and returning one value, as [`register_host_function`]
+    /// does for any number of results. Guest programs call it with
+    /// `.extern name` and `CALL name`, which the loader turns into
+    /// `CALL_NATIVE`.
+    ///
+    /// [`register_host_function`]: VirtualMachine::register_host_function
+    pub fn register_native(
+        &mut self,
+        name: impl Into<String>,
+        arity: usize,
+        function: impl Fn(&[Value]) -> Result<Value, NativeError> + Send + Sync + 'static,
+    ) {
+        self.register_host_function(name, arity, 1, move |args| Ok(vec![function(args)?]));
+    }
+
+    /// Defines the global `name`, which modules import with `.extern name`
+    /// and read with `LDC name`. Modules loaded afterwards see `value`.
+    pub fn define_global(&mut self, name: impl Into<String>, value: Value) {
+        self.host_globals.insert(name.into(), value);
+    }
+
+    /// Sets the global `name`, which programs read with `LDG name` and
+    /// can overwrite with `STG name`. Unlike [`define_global`], this is a
+    /// variable rather than a constant fixed at load time: it keeps its
+    /// value across loads and resets, and the host sees what the program
+    /// stores in it.
+    ///
+    /// [`define_global`]: VirtualMachine::define_global
+    pub fn set_global(&mut self, name: impl Into<String>, value: Value) {
+        self.globals.insert(name.into(), value);
+    }
+
+    /// Current value of the global `name`, if it has been set
+    pub fn get_global(&self, name: &str) -> Option<&Value> {
+        self.globals.get(name)
+    }
+
+    /// Removes the global `name`, returning the value it had
+    pub fn remove_global(&mut self, name: &str) -> Option<Value> {
+        self.globals.remove(name)
+    }
+
+    /// Replaces the text streams `Print`, `ReadLine` and host functions
+    /// use; see [`VmBuilder::with_io`]
+    pub fn set_io(&mut self, io: impl VmIo + 'static) {
+        self.io = Box::new(io);
+    }
+
+    /// Like [`set_io`](Self::set_io), but hands back the streams replaced,
+    /// for a wrapper around them
+    pub fn replace_io(&mut self, io: impl VmIo + 'static) -> Box<dyn VmIo> {
+        mem::replace(&mut self.io, Box::new(io))
+    }
+
+    /// The heap objects reachable from the operand stack, the locals of
+    /// every active call and the globals, each root named for where it is
+    pub fn heap_graph(&self) -> HeapGraph {
+        let stack = self
+            .operand_stack
+            .values()
+            .iter()
+            .enumerate()
+            .map(|(slot, value)| (format!("stack {}", slot), value));
+        let locals = self.call_stack.frames().iter().flat_map(|frame| {
+            let function = frame.function_name().unwrap_or("(top level)");
+            (0..frame.local_count()).filter_map(move |index| {
+                let value = frame.get_local(index).ok()?;
+                Some((format!("{} local {}", function, index), value))
+            })
+        });
+        let mut globals: Vec<(&String, &Value)> =
+            self.globals.iter().chain(&self.host_globals).collect();
+        globals.sort_by_key(|&(name, _)| name);
+        let globals = globals
+            .into_iter()
+            .map(|(name, value)| (format!("global {}", name), value));
+        HeapGraph::from_roots(stack.chain(locals).chain(globals))
+    }
+
+    /// Saves where the program is, for [`restore`](Self::restore) to come
+    /// back to
+    pub fn snapshot(&self) -> VmSnapshot {
+        let locals = self.call_stack.frames().iter().flat_map(|frame| {
+            (0..frame.local_count()).filter_map(|index| frame.get_local(index).ok())
+        });
+        let roots = self
+            .operand_stack
+            .values()
+            .iter()
+            .chain(locals)
+            .chain(self.globals.values())
+            .chain(self.host_globals.values())
+            .chain(&self.constants);
+        VmSnapshot {
+            operand_stack: self.operand_stack.clone(),
+            call_stack: self.call_stack.clone(),
+            dispatcher: self.dispatcher.clone(),
+            heap: self.heap.clone(),
+            opcode_statistics: self.opcode_statistics.clone(),
+            globals: self.globals.clone(),
+            halted: self.halted,
+            stopped_at: self.stopped_at,
+            objects: ObjectContents::capture(roots),
+        }
+    }
+
+    /// Puts the program back where it was when `snapshot` was taken. The
+    /// snapshot must come from this VM with the same program loaded.
+    pub fn restore(&mut self, snapshot: &VmSnapshot) {
+        self.operand_stack = snapshot.operand_stack.clone();
+        self.call_stack = snapshot.call_stack.clone();
+        self.dispatcher = snapshot.dispatcher.clone();
+        self.heap = snapshot.heap.clone();
+        self.opcode_statistics = snapshot.opcode_statistics.clone();
+        self.globals = snapshot.globals.clone();
+        self.halted = snapshot.halted;
+        self.stopped_at = snapshot.stopped_at;
+        for object in &snapshot.objects {
+            object.restore();
+        }
+    }
+
+    /// Links `modules` and loads the result, as [`Linker`] would lay them
+    /// out. Imports that none of them export are resolved against the host
+    /// functions and globals like [`VirtualMachine::load_module`] does.
+    pub fn load_modules(
+        &mut self,
+        modules: impl IntoIterator<Item = BytecodeModule>,
+    ) -> Result<(), VmError> {
+        let mut linker = Linker::new();
+        linker.allow_unresolved(true);
+        for module in modules {
+            linker.add(module);
+        }
+        self.load_module(linker.link().map_err(VmError::LinkFailed)?)
+    }
+
+    /// Loads an assembled module, including its function table. Its
+    /// remaining `.extern` imports must name host functions, for calls, or
+    /// host globals, for `LDC`. The module must pass the [`Verifier`].
+    pub fn load_module(&mut self, module: BytecodeModule) -> Result<(), VmError> {
+        self.install_module(module, true)
+    }
+
+    /// Loads `module` like [`load_module`](Self::load_module) but without
+    /// verifying it first. The interpreter still checks each instruction as
+    /// it runs, so a bad program stops with an error rather than
+    /// misbehaving; this is for exercising those checks and for programs
+    /// the verifier is too strict for, such as ones that leave values on
+    /// the stack as they loop.
+    pub fn load_module_unverified(&mut self, module: BytecodeModule) -> Result<(), VmError> {
+        self.install_module(module, false)
+    }
+
+    fn install_module(&mut self, module: BytecodeModule, verify: bool) -> Result<(), VmError> {
+        Self::check_module(&module)?;
+        let module = self.resolve_imports(module)?;
+        if verify {
+            verify_module(&module).map_err(VmError::VerificationFailed)?;
+        }
+
+        self.program = module.instructions.into();
+        self.constants = module.constants;
+        self.functions = module.functions;
+        self.debug_info = module.debug_info;
+        self.symbols = module.symbols;
+        self.exported_constants = module.globals;
         self.reset();
+        #[cfg(feature = "tracing")]
+        self.trace_load("load_module");
+        Ok(())
+    }
+
+    /// Loads `module` alongside the program already loaded rather than in
+    /// its place, as if the two had been linked with `module` second, and
+    /// verifies the result. Its
+    /// imports resolve against the exports of the modules loaded so far,
+    /// then against the host; its exports can be called with
+    /// [`call_function`](Self::call_function) and by modules added later.
+    ///
+    /// Code already loaded keeps its addresses, so a paused or stopped
+    /// program can carry on and call into the new module. Execution still
+    /// starts at the first module's entry point; the code at the start of
+    /// `module` only runs if something jumps to it.
+    pub fn add_module(&mut self, module: BytecodeModule) -> Result<(), VmError> {
+        if self.program.is_empty() {
+            return self.load_module(module);
+        }
+        Self::check_module(&module)?;
+        // The linker lays `module` out after the code already loaded
+        let base = self.program.len();
+        let loaded = BytecodeModule::new(self.program.to_vec(), self.constants.clone())
+            .with_functions(self.functions.clone())
+            .with_symbols(self.symbols.clone())
+            .with_debug_info(self.debug_info.clone())
+            .with_globals(self.exported_constants.clone());
+        let linked = Linker::new()
+            .add(loaded)
+            .add(module)
+            .allow_unresolved(true)
+            .link()
+            .map_err(VmError::LinkFailed)?;
+        let linked = self.resolve_imports(linked)?;
+        Verifier::new(&linked.instructions, &linked.constants)
+            .with_functions(&linked.functions)
+            .with_entry_point(base)
+            .verify()
+            .map_err(VmError::VerificationFailed)?;
+
+        self.program = linked.instructions.into();
+        self.constants = linked.constants;
+        self.functions = linked.functions;
+        self.debug_info = linked.debug_info;
+        self.symbols = linked.symbols;
+        self.exported_constants = linked.globals;
+        self.dispatcher.set_functions(&self.functions);
+        #[cfg(feature = "tracing")]
+        self.trace_load("add_module");
+        Ok(())
+    }
+
+    /// Reports the program just loaded to `tracing` subscribers
+    #[cfg(feature = "tracing")]
+    fn trace_load(&self, how: &'static str) {
+        tracing::info!(
+            how,
+            instructions = self.program.len(),
+            constants = self.constants.len(),
+            functions = self.functions.len(),
+            "program loaded"
+        );
+    }
+
+    fn check_module(module: &BytecodeModule) -> Result<(), VmError> {
+        if module.instructions.is_empty() {
+            return Err(VmError::InvalidProgramState(
+                "Cannot load empty instruction list".to_string()
+            ));
+        }
+        if let Some(function) = module
+            .functions
+            .iter()
+            .find(|function| function.address >= module.instructions.len())
+        {
+            return Err(VmError::InvalidProgramState(format!(
+                "Function {} starts at {} past the end of the program",
+                function.name, function.address
+            )));
+        }
         Ok(())
     }
 
+    /// `module` with its externs pointed at host functions and globals
+    fn resolve_imports(&self, mut module: BytecodeModule) -> Result<BytecodeModule, VmError> {
+        let mut imported_globals = HashMap::new();
+        for reference in mem::take(&mut module.externs) {
+            let opcode = module.instructions.get(reference.address).map(Instruction::opcode);
+            let host_function = self
+                .host_functions
+                .iter()
+                .position(|host| host.name == reference.name);
+            let resolved = match (opcode, host_function) {
+                (Some(Opcode::Call), Some(index)) => Some(Instruction::new(
+                    Opcode::CallNative,
+                    Some(Value::Integer(index as i64)),
+                )),
+                (Some(Opcode::LoadConstant), _) => self.host_globals.get(&reference.name).map(|value| {
+                    let index = *imported_globals
+                        .entry(reference.name.clone())
+                        .or_insert_with(|| module.add_constant(value.clone()));
+                    Instruction::new(Opcode::LoadConstant, Some(Value::Integer(index as i64)))
+                }),
+                _ => None,
+            };
+            match resolved {
+                Some(instruction) => module.instructions[reference.address] = instruction,
+                None => {
+                    return Err(VmError::InvalidProgramState(format!(
+                        "Unresolved external symbol {} at {}; link the module or register it with the VM",
+                        reference.name, reference.address
+                    )));
+                }
+            }
+        }
+        Ok(module)
+    }
+
+    /// Whether `instruction` would push past the operand stack's limit,
+    /// which the stack itself only reports by panicking
+    fn would_overflow(&self, instruction: &Instruction) -> bool {
+        // No instruction pushes more than two values, so only stacks this
+        // close to their limit need looking at
+        if self.operand_stack.size() + 2 <= self.operand_stack.limit() {
+            return false;
+        }
+        let Some((pops, pushes)) = instruction.stack_effect() else {
+            return false;
+        };
+        pushes > pops
+            && self.operand_stack.size().saturating_sub(pops) + pushes > self.operand_stack.limit()
+    }
+
+    /// Reads or writes the global whose name a `LoadGlobal` or
+    /// `StoreGlobal` instruction points at in the constants pool
+    fn access_global(&mut self, instruction: &Instruction) -> Result<(), ExecutionError> {
+        let name = match instruction.operand() {
+            Some(Value::Integer(index)) => {
+                usize::try_from(*index).ok().and_then(|index| self.constants.get(index))
+            }
+            _ => None,
+        };
+        let Some(Value::String(name)) = name else {
+            return Err(ExecutionError::InvalidOperand(format!(
+                "{:?} operand {:?} does not name a global",
+                instruction.opcode(),
+                instruction.operand()
+            )));
+        };
+        if instruction.opcode() == Opcode::StoreGlobal {
+            let value = self.operand_stack.pop()?;
+            // Only a new global needs its name copied
+            match self.globals.get_mut(&**name) {
+                Some(global) => *global = value,
+                None => {
+                    self.globals.insert(name.to_string(), value);
+                }
+            }
+            return Ok(());
+        }
+        let value = self
+            .globals
+            .get(&**name)
+            .cloned()
+            .ok_or_else(|| ExecutionError::UndefinedGlobal(name.to_string()))?;
+        self.operand_stack.try_push(value)?;
+        Ok(())
+    }
+
+    /// Pops a value and writes it to standard output on a line of its own
+    fn print(&mut self) -> Result<(), ExecutionError> {
+        let value = self.operand_stack.pop()?;
+        self.io
+            .write_stdout(&format!("{}\n", value))
+            .map_err(|e| ExecutionError::Io(e.to_string()))
+    }
+
+    /// Pushes the next line of input, or `Null` at the end of input. The
+    /// line counts against the heap like any string the program builds.
+    fn read_line(&mut self) -> Result<(), ExecutionError> {
+        let line = self.io.read_line().map_err(|e| ExecutionError::Io(e.to_string()))?;
+        let value = match line {
+            Some(line) => Value::String(self.heap.allocate_str(line).map_err(|heap_error| {
+                ExecutionError::InvalidOperand(format!("Failed to allocate string: {}", heap_error))
+            })?),
+            None => Value::Null,
+        };
+        self.operand_stack.try_push(value)?;
+        Ok(())
+    }
+
+    /// Runs the host function a `CallNative` instruction names on arguments
+    /// popped from the operand stack
+    fn call_native(&mut self, instruction: &Instruction) -> Result<(), ExecutionError> {
+        let host = match instruction.operand() {
+            Some(Value::Integer(index)) => usize::try_from(*index)
+                .ok()
+                .and_then(|index| self.host_functions.get(index)),
+            _ => None,
+        }
+        .ok_or_else(|| {
+            ExecutionError::InvalidOperand(format!(
+                "CallNative operand {:?} is not a registered host function",
+                instruction.operand()
+            ))
+        })?;
+        let (name, arity, returns) = (host.name.clone(), host.arity, host.returns);
+        let function = Arc::clone(&host.function);
+
+        if self.operand_stack.frame_size() < arity {
+            return Err(ExecutionError::ArityMismatch {
+                function: name,
+                expected: arity,
+                available: self.operand_stack.frame_size(),
+            });
+        }
+        let mut args = Vec::with_capacity(arity);
+        for _ in 0..arity {
+            args.push(self.operand_stack.pop().map_err(ExecutionError::StackError)?);
+        }
+        args.reverse();
+
+        let mut context = NativeContext { vm: self };
+        let results = function(&mut context, &args).map_err(|error| ExecutionError::HostFunction {
+            function: name.clone(),
+            message: error.message,
+        })?;
+        if results.len() != returns {
+            return Err(ExecutionError::StackEffectMismatch {
+                function: name,
+                expected: returns,
+                actual: results.len(),
+            });
+        }
+        for mut result in results {
+            // Strings the host built count against the heap like the program's own
+            if let Value::String(string) = &mut result {
+                self.heap.adopt_string(string).map_err(|heap_error| ExecutionError::HostFunction {
+                    function: name.clone(),
+                    message: format!("Failed to allocate string: {}", heap_error),
+                })?;
+            }
+            self.operand_stack.try_push(result).map_err(ExecutionError::StackError)?;
+        }
+        Ok(())
+    }
+
+    pub fn functions(&self) -> &[FunctionInfo] {
+        &self.functions
+    }
+
+    /// Calls a function with `args` and runs until it returns, giving back
+    /// its result: the last value it returns, or `Null` if it returns none.
+    ///
+    /// `function` is an exported function's name, or the address of any
+    /// function in the function table. The call runs on its own operand
+    /// and call stacks with the fuel [`VmBuilder::with_max_instructions`]
+    /// sets; afterwards the stacks, program counter and fuel of whatever
+    /// was running before are put back, so a host can call into a paused
+    /// program or call several times into one module. The heap is shared.
+    ///
+    /// What was running is put back even if the call fails, so a paused
+    /// program can still be resumed; the error's
+    /// [`backtrace`](VmError::backtrace) shows where the call failed.
+    pub fn call_function<'a>(
+        &mut self,
+        function: impl Into<FunctionRef<'a>>,
+        args: &[Value],
+    ) -> Result<Value, VmError> {
+        let mut results = self.call_function_values(function, args)?;
+        Ok(results.pop().unwrap_or(Value::Null))
+    }
+
+    /// Like [`call_function`](Self::call_function), but gives back every
+    /// value the function returns
+    pub fn call_function_values<'a>(
+        &mut self,
+        function: impl Into<FunctionRef<'a>>,
+        args: &[Value],
+    ) -> Result<Vec<Value>, VmError> {
+        let address = self.resolve_function(function.into(), args.len())?;
+        let saved = self.set_aside();
+        // The paused program comes back whether or not the call succeeds
+        let results = self.run_call(address, args);
+        self.put_back(saved);
+        results
+    }
+
+    /// Entry address of `function`, checking it takes `arg_count`
+    /// arguments
+    fn resolve_function(&self, function: FunctionRef, arg_count: usize) -> Result<usize, VmError> {
+        let function = match function {
+            FunctionRef::Name(name) => exports(&self.functions)
+                .find(|function| function.name == name)
+                .ok_or_else(|| VmError::UnknownFunction(name.to_string()))?,
+            FunctionRef::Address(address) => self
+                .functions
+                .iter()
+                .find(|function| function.address == address)
+                .ok_or(VmError::NoFunctionAt(address))?,
+        };
+        if arg_count != function.arity {
+            return Err(VmError::ExecutionError(ExecutionError::ArityMismatch {
+                function: function.name.clone(),
+                expected: function.arity,
+                available: arg_count,
+            }));
+        }
+        Ok(function.address)
+    }
+
+    /// Sets aside whatever is running for a call to run in its place.
+    /// The new stacks keep the old ones' limits, less the frames already
+    /// in use, so calls nested through natives stay within the call depth.
+    fn set_aside(&mut self) -> SavedExecution {
+        let operand_stack = match self.operand_stack.max_size() {
+            Some(capacity) => OperandStack::with_capacity(capacity),
+            None => OperandStack::new(),
+        };
+        let max_depth = self.call_stack.max_depth().saturating_sub(self.call_stack.depth());
+        SavedExecution {
+            operand_stack: mem::replace(&mut self.operand_stack, operand_stack),
+            call_stack: mem::replace(&mut self.call_stack, CallStack::with_max_depth(max_depth)),
+            pc: self.dispatcher.current_pc(),
+            halted: mem::replace(&mut self.halted, false),
+            fuel: mem::replace(&mut self.fuel, self.max_instructions),
+        }
+    }
+
+    fn put_back(&mut self, saved: SavedExecution) {
+        self.operand_stack = saved.operand_stack;
+        self.call_stack = saved.call_stack;
+        self.dispatcher.set_pc(saved.pc);
+        self.halted = saved.halted;
+        self.fuel = saved.fuel;
+    }
+
+    /// Calls the function at `address` on stacks [`set_aside`] has
+    /// cleared and runs until it returns
+    ///
+    /// [`set_aside`]: VirtualMachine::set_aside
+    fn run_call(&mut self, address: usize, args: &[Value]) -> Result<Vec<Value>, VmError> {
+        let call = Instruction::new(Opcode::Call, Some(Value::Integer(address as i64)));
+        for arg in args {
+            self.operand_stack.try_push(arg.clone()).map_err(ExecutionError::StackError)?;
+        }
+        // Returning lands just past the end of the program, where the
+        // call stack is empty again
+        self.dispatcher.set_pc(self.program.len() - 1);
+        self.dispatcher.execute_with_constants(
+            &call,
+            &mut self.operand_stack,
+            &mut self.call_stack,
+            &self.constants,
+            &mut self.heap,
+        )?;
+        let deadline = self.deadline();
+        let program = Arc::clone(&self.program);
+        while !self.call_stack.is_empty() && !self.halted {
+            self.interrupt.check()?;
+            self.check_deadline(deadline)?;
+            if self.fuel == 0 {
+                return Err(VmError::InvalidProgramState(
+                    "Maximum instruction count exceeded".to_string(),
+                ));
+            }
+            self.fuel -= 1;
+            self.step_through(&program)?;
+        }
+
+        let mut results = Vec::with_capacity(self.operand_stack.size());
+        while let Ok(value) = self.operand_stack.pop() {
+            results.push(value);
+        }
+        results.reverse();
+        Ok(results)
+    }
+
+    /// Frames of the running program, innermost first, each with the
+    /// function it is in and the instruction it is at. After an error this
+    /// shows where the failing instruction was called from.
+    pub fn stack_trace(&self) -> Vec<StackTraceEntry> {
+        let frames = self.call_stack.frames();
+        let mut pc = self.dispatcher.current_pc();
+        let mut trace = Vec::with_capacity(frames.len() + 1);
+        for frame in frames.iter().rev() {
+            let function = frame.function_name().map(str::to_string).or_else(|| {
+                self.symbols
+                    .functions
+                    .iter()
+                    .find(|(_, range)| range.start == frame.function_index())
+                    .map(|(name, _)| name.clone())
+            });
+            trace.push(StackTraceEntry {
+                function,
+                pc,
+                line: self.source_line(pc),
+                file: self.source_file(pc).map(str::to_string),
+            });
+            pc = frame.return_address().saturating_sub(1);
+        }
+        trace.push(StackTraceEntry {
+            function: None,
+            pc,
+            line: self.source_line(pc),
+            file: self.source_file(pc).map(str::to_string),
+        });
+        trace
+    }
+
+    pub fn debug_info(&self) -> &DebugInfo {
+        &self.debug_info
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    /// Source line of the instruction at `pc`, if the loaded module has
+    /// debug info
+    pub fn source_line(&self, pc: usize) -> Option<usize> {
+        self.debug_info.lines.line_at(pc)
+    }
+
+    /// Source file of the instruction at `pc`, if the loaded module names
+    /// one
+    pub fn source_file(&self, pc: usize) -> Option<&str> {
+        self.debug_info.file_at(pc)
+    }
+
     pub fn get_constant(&self, index: usize) -> Result<&Value, VmError> {
         self.constants
             .get(index)
@@ -234,14 +2074,23 @@ impl VirtualMachine {
 
     pub fn trigger_gc(&mut self) -> usize {
         // Simple GC trigger - in a real implementation, this would trace all roots
-        self.heap.collect_garbage::<String>(&[])
+        let collected = self.heap.collect_garbage::<String>(&[]);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(collected, heap_size = self.heap.current_heap_size(), "garbage collected");
+        self.emit(HookEvent::GarbageCollection { collected });
+        collected
     }
 
     // Debug methods
+    /// The operand stack, bottom first
     pub fn stack_contents(&self) -> Vec<Value> {
-        // For debugging, we'll return an empty vec for now
-        // In a real implementation, we'd add proper introspection to OperandStack
-        Vec::new()
+        self.operand_stack.values().to_vec()
+    }
+
+    /// Local `index` of the innermost frame, if there is a frame with that
+    /// many locals
+    pub fn local(&self, index: usize) -> Option<&Value> {
+        self.call_stack.current().ok()?.get_local(index).ok()
     }
 
     pub fn current_instruction(&self) -> Option<&Instruction> {
@@ -249,9 +2098,17 @@ impl VirtualMachine {
         self.program.get(pc)
     }
 
+    /// The instruction at `pc` of the loaded program
+    pub fn instruction_at(&self, pc: usize) -> Option<&Instruction> {
+        self.program.get(pc)
+    }
+
     // Profiling methods
     pub fn enable_profiling(&mut self) {
-        self.profiler = Some(HotSpotProfiler::new());
+        self.profiler = Some(match self.jit_thresholds {
+            Some((function, loops)) => HotSpotProfiler::with_thresholds(function, loops),
+            None => HotSpotProfiler::new(),
+        });
     }
 
     pub fn disable_profiling(&mut self) {
@@ -270,6 +2127,64 @@ impl VirtualMachine {
         self.profiler.as_mut()
     }
 
+    /// How many times each opcode has executed since the program was
+    /// loaded or the VM [`reset`](Self::reset)
+    pub fn opcode_statistics(&self) -> &OpcodeStatistics {
+        &self.opcode_statistics
+    }
+
+    pub fn reset_opcode_statistics(&mut self) {
+        self.opcode_statistics = OpcodeStatistics::new();
+    }
+
+    /// Wall-clock time spent in [`run`](Self::run) and
+    /// [`resume`](Self::resume) since the VM was created or reset
+    pub fn run_time(&self) -> Duration {
+        self.run_time
+    }
+
+    /// Time, instruction counts, opcode mix, heap and profiler figures of
+    /// everything run since the VM was created or reset
+    pub fn statistics(&self) -> ExecutionStatistics {
+        let instructions = self.instruction_count();
+        let seconds = self.run_time.as_secs_f64();
+        let opcodes = self
+            .opcode_statistics
+            .most_executed()
+            .into_iter()
+            .map(|(opcode, count)| {
+                let name = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+                (name, count)
+            })
+            .collect();
+        ExecutionStatistics {
+            run_time_seconds: seconds,
+            instructions,
+            instructions_per_second: if seconds > 0.0 { instructions as f64 / seconds } else { 0.0 },
+            opcodes,
+            gc: GcStatistics {
+                live_objects: self.heap.allocated_objects(),
+                young_objects: self.heap.young_generation_objects(),
+                old_objects: self.heap.old_generation_objects(),
+                bytes_in_use: self.heap.current_heap_size(),
+                bytes_allocated: self.heap.total_allocated_bytes(),
+                collections: self.heap.collections(),
+                collected_objects: self.heap.collected_objects(),
+            },
+            jit: self.profiler.as_ref().map(|profiler| {
+                let mut hot_functions = profiler.hot_functions();
+                hot_functions.sort_unstable();
+                let mut hot_loops = profiler.hot_loops();
+                hot_loops.sort_unstable();
+                JitStatistics {
+                    hot_spot_executions: profiler.total_executions(),
+                    hot_functions,
+                    hot_loops,
+                }
+            }),
+        }
+    }
+
     pub fn reset_profiler(&mut self) {
         if let Some(ref mut profiler) = self.profiler {
             profiler.reset();
@@ -315,7 +2230,7 @@ mod tests {
 
     #[test]
     fn test_vm_max_instructions() {
-        let mut vm = VirtualMachine::with_max_instructions(3);
+        let mut vm = VirtualMachine::builder().with_max_instructions(3).build();
 
         // Infinite loop program
         let program = vec![
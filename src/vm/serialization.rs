@@ -0,0 +1,149 @@
+//! serde support for `Value`.
+//!
+//! GC-backed values (objects, arrays, byte buffers, GC strings) are written
+//! as deep copies of their contents and read back as fresh detached heap
+//! allocations. String slices are written as plain strings and decimals as
+//! their exact text. Cyclic structures cannot be serialized.
+
+use crate::vm::heap::{Array, ByteBuffer, GcPtr, Object, TypedArray};
+use crate::vm::types::{Decimal, Value, VmTuple, MAX_TUPLE_LEN};
+use serde::de::{Deserializer, Error as _};
+use serde::ser::{Error as _, Serializer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Plain-data mirror of `Value` used as the serialized form
+#[derive(Serialize, Deserialize)]
+enum ValueRepr {
+    Integer(i64),
+    Float(f64),
+    Decimal(String),
+    Boolean(bool),
+    Char(char),
+    String(String),
+    GcString(String),
+    GcObject(BTreeMap<String, ValueRepr>),
+    Array(Vec<ValueRepr>),
+    Bytes(Vec<u8>),
+    IntArray(Vec<i64>),
+    FloatArray(Vec<f64>),
+    Tuple(Vec<ValueRepr>),
+    Function { address: u32, arity: u32 },
+    Null,
+}
+
+impl ValueRepr {
+    fn from_value(value: &Value, seen: &mut Vec<usize>) -> Result<Self, String> {
+        Ok(match value {
+            Value::Integer(i) => ValueRepr::Integer(*i),
+            Value::Float(f) => ValueRepr::Float(*f),
+            Value::Decimal(d) => ValueRepr::Decimal(d.to_string()),
+            Value::Boolean(b) => ValueRepr::Boolean(*b),
+            Value::Char(c) => ValueRepr::Char(*c),
+            Value::String(s) => ValueRepr::String(s.to_string()),
+            Value::GcString(s) => ValueRepr::GcString(s.to_string()),
+            Value::StrSlice(s) => ValueRepr::String(s.to_string()),
+            Value::Bytes(b) => ValueRepr::Bytes(b.to_vec()),
+            Value::IntArray(a) => ValueRepr::IntArray(a.to_vec()),
+            Value::FloatArray(a) => ValueRepr::FloatArray(a.to_vec()),
+            Value::Function { address, arity } => ValueRepr::Function {
+                address: *address,
+                arity: *arity,
+            },
+            Value::Null => ValueRepr::Null,
+            Value::Tuple(tuple) => ValueRepr::Tuple(
+                tuple
+                    .iter()
+                    .map(|element| Self::from_value(element, seen))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Array(array) => {
+                Self::enter(array.object_id(), seen)?;
+                let elements = array
+                    .to_vec()
+                    .iter()
+                    .map(|element| Self::from_value(element, seen))
+                    .collect::<Result<_, _>>()?;
+                seen.pop();
+                ValueRepr::Array(elements)
+            }
+            Value::GcObject(object) => {
+                Self::enter(object.object_id(), seen)?;
+                let fields = object
+                    .fields()
+                    .into_iter()
+                    .map(|(name, field)| Ok((name, Self::from_value(&field, seen)?)))
+                    .collect::<Result<_, String>>()?;
+                seen.pop();
+                ValueRepr::GcObject(fields)
+            }
+        })
+    }
+
+    fn enter(object_id: usize, seen: &mut Vec<usize>) -> Result<(), String> {
+        if seen.contains(&object_id) {
+            return Err(format!("cannot serialize cyclic value (object {})", object_id));
+        }
+        seen.push(object_id);
+        Ok(())
+    }
+
+    fn into_value(self) -> Result<Value, String> {
+        Ok(match self {
+            ValueRepr::Integer(i) => Value::Integer(i),
+            ValueRepr::Float(f) => Value::Float(f),
+            ValueRepr::Decimal(d) => {
+                Value::Decimal(d.parse::<Decimal>().map_err(|e| e.to_string())?)
+            }
+            ValueRepr::Boolean(b) => Value::Boolean(b),
+            ValueRepr::Char(c) => Value::Char(c),
+            ValueRepr::String(s) => Value::String(s.into()),
+            ValueRepr::GcString(s) => Value::GcString(GcPtr::detached(s)),
+            ValueRepr::Bytes(b) => Value::Bytes(GcPtr::detached(ByteBuffer::new(b))),
+            ValueRepr::IntArray(a) => Value::IntArray(GcPtr::detached(TypedArray::new(a))),
+            ValueRepr::FloatArray(a) => Value::FloatArray(GcPtr::detached(TypedArray::new(a))),
+            ValueRepr::Function { address, arity } => Value::Function { address, arity },
+            ValueRepr::Null => Value::Null,
+            ValueRepr::Tuple(elements) => {
+                let len = elements.len();
+                let elements = elements
+                    .into_iter()
+                    .map(ValueRepr::into_value)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let tuple = VmTuple::new(elements).ok_or_else(|| {
+                    format!("tuple of {} elements exceeds maximum of {}", len, MAX_TUPLE_LEN)
+                })?;
+                Value::Tuple(tuple)
+            }
+            ValueRepr::Array(elements) => Value::Array(GcPtr::detached(Array::new(
+                elements
+                    .into_iter()
+                    .map(ValueRepr::into_value)
+                    .collect::<Result<_, _>>()?,
+            ))),
+            ValueRepr::GcObject(fields) => {
+                let object = Object::new();
+                for (name, field) in fields {
+                    object.set_field(name, field.into_value()?);
+                }
+                Value::GcObject(GcPtr::detached(object))
+            }
+        })
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ValueRepr::from_value(self, &mut Vec::new())
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        ValueRepr::deserialize(deserializer)?
+            .into_value()
+            .map_err(D::Error::custom)
+    }
+}
@@ -5,6 +5,8 @@ use std::fmt;
 pub enum StackError {
     Underflow,
     Overflow,
+    /// A function tried to pop a value belonging to its caller
+    FrameUnderflow,
 }
 
 impl fmt::Display for StackError {
@@ -14,15 +16,21 @@ impl fmt::Display for StackError {
                 write!(f, "Stack underflow: attempted to pop from empty stack")
             }
             StackError::Overflow => write!(f, "Stack overflow: maximum stack size exceeded"),
+            StackError::FrameUnderflow => {
+                write!(f, "Stack underflow: attempted to pop below the current frame")
+            }
         }
     }
 }
 
 impl std::error::Error for StackError {}
 
+#[derive(Clone)]
 pub struct OperandStack {
     values: Vec<Value>,
     max_size: Option<usize>,
+    // Values below this belong to the callers of the running function
+    base: usize,
 }
 
 impl OperandStack {
@@ -33,6 +41,7 @@ impl OperandStack {
         Self {
             values: Vec::with_capacity(Self::DEFAULT_CAPACITY),
             max_size: None, // Unlimited growth up to MAX_STACK_SIZE
+            base: 0,
         }
     }
 
@@ -41,6 +50,7 @@ impl OperandStack {
         Self {
             values: Vec::with_capacity(actual_max),
             max_size: Some(actual_max),
+            base: 0,
         }
     }
 
@@ -51,11 +61,10 @@ impl OperandStack {
         }
 
         // For limited stacks, panic on overflow (as per test expectations)
-        if let Some(max) = self.max_size {
-            if self.values.len() >= max {
+        if let Some(max) = self.max_size
+            && self.values.len() >= max {
                 panic!("Stack overflow: exceeded capacity");
             }
-        }
 
         self.values.push(value);
     }
@@ -66,24 +75,79 @@ impl OperandStack {
             return Err(StackError::Overflow);
         }
 
-        if let Some(max) = self.max_size {
-            if self.values.len() >= max {
+        if let Some(max) = self.max_size
+            && self.values.len() >= max {
                 return Err(StackError::Overflow);
             }
-        }
 
         self.values.push(value);
         Ok(())
     }
 
+    /// Replaces the top two values with `op` of them when both are
+    /// integers the running function can pop and `op` has a result, the
+    /// common case of arithmetic and comparisons. Otherwise leaves the
+    /// stack alone and returns false.
+    #[inline]
+    pub fn apply_integers(&mut self, op: impl FnOnce(i64, i64) -> Option<Value>) -> bool {
+        let len = self.values.len();
+        if len < self.base + 2 {
+            return false;
+        }
+        let [Value::Integer(a), Value::Integer(b)] = self.values[len - 2..] else {
+            return false;
+        };
+        let Some(result) = op(a, b) else {
+            return false;
+        };
+        self.values.truncate(len - 1);
+        self.values[len - 2] = result;
+        true
+    }
+
     pub fn pop(&mut self) -> Result<Value, StackError> {
+        self.check_frame()?;
         self.values.pop().ok_or(StackError::Underflow)
     }
 
     pub fn peek(&self) -> Result<&Value, StackError> {
+        self.check_frame()?;
         self.values.last().ok_or(StackError::Underflow)
     }
 
+    /// Every value, bottom first, whichever frame it belongs to
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// The top value whichever frame it belongs to, for looking at the
+    /// stack from outside the program
+    pub fn top(&self) -> Option<&Value> {
+        self.values.last()
+    }
+
+    fn check_frame(&self) -> Result<(), StackError> {
+        if self.base > 0 && self.values.len() <= self.base {
+            return Err(StackError::FrameUnderflow);
+        }
+        Ok(())
+    }
+
+    /// Height below which values belong to the callers of the running
+    /// function, and cannot be popped
+    pub fn base(&self) -> usize {
+        self.base
+    }
+
+    pub fn set_base(&mut self, base: usize) {
+        self.base = base;
+    }
+
+    /// Values the running function can pop
+    pub fn frame_size(&self) -> usize {
+        self.values.len().saturating_sub(self.base)
+    }
+
     pub fn size(&self) -> usize {
         self.values.len()
     }
@@ -94,6 +158,7 @@ impl OperandStack {
 
     pub fn clear(&mut self) {
         self.values.clear();
+        self.base = 0;
     }
 
     pub fn capacity(&self) -> usize {
@@ -103,6 +168,11 @@ impl OperandStack {
     pub fn max_size(&self) -> Option<usize> {
         self.max_size
     }
+
+    /// Most values the stack can hold, bounded or not
+    pub fn limit(&self) -> usize {
+        self.max_size.unwrap_or(Self::MAX_STACK_SIZE)
+    }
 }
 
 impl Default for OperandStack {
@@ -128,4 +198,21 @@ mod tests {
         assert!(stack.capacity() > initial_capacity);
         assert_eq!(stack.size(), initial_capacity + 100);
     }
+
+    #[test]
+    fn test_frame_base() {
+        let mut stack = OperandStack::new();
+        stack.push(Value::Integer(1));
+        stack.set_base(1);
+        assert!(matches!(stack.pop(), Err(StackError::FrameUnderflow)));
+        assert_eq!(stack.top(), Some(&Value::Integer(1)));
+
+        stack.push(Value::Integer(2));
+        assert_eq!(stack.frame_size(), 1);
+        assert_eq!(stack.pop().unwrap(), Value::Integer(2));
+
+        stack.clear();
+        assert_eq!(stack.base(), 0);
+        assert!(matches!(stack.pop(), Err(StackError::Underflow)));
+    }
 }
@@ -0,0 +1,151 @@
+use crate::vm::assembler::Assembler;
+use crate::vm::linker::{LinkError, Linker};
+use crate::vm::module::BytecodeModule;
+
+/// Assembly source of the standard library.
+///
+/// Every routine is a `.func` with a declared stack effect, so programs
+/// call them through `.extern` declarations and link against [`module`]:
+///
+/// - `abs(x)`, `min(a, b)`, `max(a, b)`
+/// - `array_fill(array, value)` stores `value` in every element and returns
+///   the array; `array_sum(array)` adds up its elements
+/// - `to_string(x)` formats any value as a string; `str_repeat(s, n)`
+///   concatenates `n` copies of `s`
+pub const SOURCE: &str = r#"
+; Integer literals load from the pool once it is non-empty
+.const ZERO 0
+.const ONE 1
+.const EMPTY ""
+
+.func abs arity=1 returns=1
+    LOAD 0
+    PUSH ZERO
+    LT
+    JF .positive
+    LOAD 0
+    NEG
+    RET
+.positive:
+    LOAD 0
+    RET
+.endfunc
+
+.func min arity=2 returns=1
+    LOAD 0
+    LOAD 1
+    LE
+    JF .second
+    LOAD 0
+    RET
+.second:
+    LOAD 1
+    RET
+.endfunc
+
+.func max arity=2 returns=1
+    LOAD 0
+    LOAD 1
+    GE
+    JF .second
+    LOAD 0
+    RET
+.second:
+    LOAD 1
+    RET
+.endfunc
+
+; locals: array, value, index
+.func array_fill arity=2 locals=3 returns=1
+    PUSH ZERO
+    STORE 2
+.loop:
+    LOAD 2
+    LOAD 0
+    LEN
+    LT
+    JF .done
+    LOAD 0
+    LOAD 2
+    LOAD 1
+    SET_ARRAY
+    LOAD 2
+    PUSH ONE
+    ADD
+    STORE 2
+    JMP .loop
+.done:
+    LOAD 0
+    RET
+.endfunc
+
+; locals: array, index, total
+.func array_sum arity=1 locals=3 returns=1
+    PUSH ZERO
+    STORE 1
+    PUSH ZERO
+    STORE 2
+.loop:
+    LOAD 1
+    LOAD 0
+    LEN
+    LT
+    JF .done
+    LOAD 2
+    LOAD 0
+    LOAD 1
+    GET_ARRAY
+    ADD
+    STORE 2
+    LOAD 1
+    PUSH ONE
+    ADD
+    STORE 1
+    JMP .loop
+.done:
+    LOAD 2
+    RET
+.endfunc
+
+.func to_string arity=1 returns=1
+    PUSH EMPTY
+    LOAD 0
+    STRCAT
+    RET
+.endfunc
+
+; locals: text, count, result
+.func str_repeat arity=2 locals=3 returns=1
+    PUSH EMPTY
+    STORE 2
+.loop:
+    LOAD 1
+    PUSH ZERO
+    GT
+    JF .done
+    LOAD 2
+    LOAD 0
+    STRCAT
+    STORE 2
+    LOAD 1
+    PUSH ONE
+    SUB
+    STORE 1
+    JMP .loop
+.done:
+    LOAD 2
+    RET
+.endfunc
+"#;
+
+/// The standard library, assembled and ready to add to a [`Linker`]
+pub fn module() -> BytecodeModule {
+    Assembler::new()
+        .assemble_module(SOURCE)
+        .expect("standard library source assembles")
+}
+
+/// Links `program` with the standard library placed after it
+pub fn link(program: BytecodeModule) -> Result<BytecodeModule, LinkError> {
+    Linker::new().add(program).add(module()).link()
+}
@@ -0,0 +1,295 @@
+//! proptest strategies for values, instructions and whole programs.
+//!
+//! With the `proptest` feature, [`Value`], [`Opcode`] and [`Instruction`]
+//! implement proptest's [`Arbitrary`], so `any::<Value>()` generates
+//! values. [`Program`] generates programs that pass the verifier, only
+//! jump to instructions inside themselves and always end, for properties
+//! that hold over whole runs.
+
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::module::BytecodeModule;
+use crate::vm::types::{Decimal, MAX_TUPLE_LEN, Value, VmTuple};
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+/// Globals generated programs read and write, besides loop counters
+const GLOBALS: usize = 4;
+
+const UNARY: &[Opcode] = &[Opcode::Negate, Opcode::Not];
+
+const BINARY: &[Opcode] = &[
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Mod,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::LessThan,
+    Opcode::LessEqual,
+    Opcode::GreaterThan,
+    Opcode::GreaterEqual,
+    Opcode::DeepEqual,
+    Opcode::And,
+    Opcode::Or,
+    Opcode::Xor,
+    Opcode::NullCoalesce,
+    Opcode::StrConcat,
+];
+
+/// Values a module's constants and operands can hold, tuples of them
+/// included; the heap-allocated kinds only exist at run time
+impl Arbitrary for Value {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Value>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        let leaf = prop_oneof![
+            any::<i64>().prop_map(Value::Integer),
+            any::<f64>().prop_map(Value::Float),
+            any::<i32>().prop_map(|value| {
+                Value::Decimal(
+                    Decimal::from_integer(value.into()).expect("32-bit integers fit a decimal"),
+                )
+            }),
+            any::<bool>().prop_map(Value::Boolean),
+            any::<char>().prop_map(Value::Char),
+            ".{0,16}".prop_map(|text| Value::String(text.into())),
+            (any::<u32>(), any::<u32>())
+                .prop_map(|(address, arity)| Value::Function { address, arity }),
+            Just(Value::Null),
+        ];
+        leaf.prop_recursive(2, 16, MAX_TUPLE_LEN as u32, |element| {
+            vec(element, 0..=MAX_TUPLE_LEN).prop_map(|elements| {
+                Value::Tuple(VmTuple::new(elements).expect("no longer than a tuple can be"))
+            })
+        })
+        .boxed()
+    }
+}
+
+impl Arbitrary for Opcode {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Opcode>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        select(Opcode::all().collect::<Vec<_>>()).boxed()
+    }
+}
+
+/// Any opcode with any operand or none, mostly ones the verifier rejects
+impl Arbitrary for Instruction {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Instruction>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        (any::<Opcode>(), any::<Option<Value>>())
+            .prop_map(|(opcode, operand)| Instruction::new(opcode, operand))
+            .boxed()
+    }
+}
+
+/// A program of expressions, global assignments, conditionals and counted
+/// loops, compiled so that it passes the verifier, every jump lands inside
+/// it and every run ends, at `Halt` or with an error such as adding a
+/// string to a boolean. It makes no calls, so the operand stack only ever
+/// holds the values the verifier predicts.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    pub constants: Vec<Value>,
+}
+
+impl Program {
+    pub fn into_module(self) -> BytecodeModule {
+        BytecodeModule::new(self.instructions, self.constants)
+    }
+}
+
+impl Arbitrary for Program {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<Program>;
+
+    fn arbitrary_with(_: ()) -> Self::Strategy {
+        vec(statement(), 0..8)
+            .prop_map(|statements| compile(&statements))
+            .boxed()
+    }
+}
+
+/// Leaves one value on the stack
+#[derive(Debug, Clone)]
+enum Expression {
+    Constant(Value),
+    Global(usize),
+    Unary(Opcode, Box<Expression>),
+    Binary(Opcode, Box<Expression>, Box<Expression>),
+}
+
+/// Leaves the stack as it found it
+#[derive(Debug, Clone)]
+enum Statement {
+    Discard(Expression),
+    Assign(usize, Expression),
+    If(Expression, Vec<Statement>, Vec<Statement>),
+    /// Runs the body this many times
+    Repeat(u8, Vec<Statement>),
+}
+
+fn expression() -> impl Strategy<Value = Expression> {
+    let leaf = prop_oneof![
+        any::<Value>().prop_map(Expression::Constant),
+        (0..GLOBALS).prop_map(Expression::Global),
+    ];
+    leaf.prop_recursive(4, 32, 2, |operand| {
+        prop_oneof![
+            (select(UNARY), operand.clone())
+                .prop_map(|(opcode, operand)| Expression::Unary(opcode, Box::new(operand))),
+            (select(BINARY), operand.clone(), operand)
+                .prop_map(|(opcode, a, b)| Expression::Binary(opcode, Box::new(a), Box::new(b))),
+        ]
+    })
+}
+
+fn statement() -> impl Strategy<Value = Statement> {
+    let simple = prop_oneof![
+        expression().prop_map(Statement::Discard),
+        (0..GLOBALS, expression()).prop_map(|(global, value)| Statement::Assign(global, value)),
+    ];
+    simple.prop_recursive(3, 32, 4, |body| {
+        prop_oneof![
+            (
+                expression(),
+                vec(body.clone(), 0..4),
+                vec(body.clone(), 0..4)
+            )
+                .prop_map(|(condition, then, otherwise)| Statement::If(condition, then, otherwise)),
+            (0..4u8, vec(body, 0..4)).prop_map(|(count, body)| Statement::Repeat(count, body)),
+        ]
+    })
+}
+
+fn compile(statements: &[Statement]) -> Program {
+    let mut compiler = Compiler::default();
+    // Globals start at zero rather than unset
+    let zero = compiler.constant(Value::Integer(0));
+    for global in 0..GLOBALS {
+        compiler.emit(Opcode::LoadConstant, Some(zero));
+        let name = compiler.global(format!("g{}", global));
+        compiler.emit(Opcode::StoreGlobal, Some(name));
+    }
+    for statement in statements {
+        compiler.statement(statement, 0);
+    }
+    compiler.emit(Opcode::Halt, None);
+    Program {
+        instructions: compiler.instructions,
+        constants: compiler.constants,
+    }
+}
+
+#[derive(Default)]
+struct Compiler {
+    instructions: Vec<Instruction>,
+    constants: Vec<Value>,
+}
+
+impl Compiler {
+    fn constant(&mut self, value: Value) -> usize {
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    /// Constant naming the global `name`
+    fn global(&mut self, name: String) -> usize {
+        let existing = self
+            .constants
+            .iter()
+            .position(|constant| matches!(constant, Value::String(text) if text.as_str() == name));
+        existing.unwrap_or_else(|| self.constant(Value::String(name.into())))
+    }
+
+    /// Appends an instruction, returning its address
+    fn emit(&mut self, opcode: Opcode, operand: Option<usize>) -> usize {
+        let operand = operand.map(|operand| Value::Integer(operand as i64));
+        self.instructions.push(Instruction::new(opcode, operand));
+        self.instructions.len() - 1
+    }
+
+    /// Points the jump at `pc` at the next instruction
+    fn land(&mut self, pc: usize) {
+        let opcode = self.instructions[pc].opcode();
+        let target = Value::Integer(self.instructions.len() as i64);
+        self.instructions[pc] = Instruction::new(opcode, Some(target));
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Constant(value) => {
+                let index = self.constant(value.clone());
+                self.emit(Opcode::LoadConstant, Some(index));
+            }
+            Expression::Global(global) => {
+                let name = self.global(format!("g{}", global));
+                self.emit(Opcode::LoadGlobal, Some(name));
+            }
+            Expression::Unary(opcode, operand) => {
+                self.expression(operand);
+                self.emit(*opcode, None);
+            }
+            Expression::Binary(opcode, a, b) => {
+                self.expression(a);
+                self.expression(b);
+                self.emit(*opcode, None);
+            }
+        }
+    }
+
+    /// `loops` is how many loops enclose the statement, so each nested
+    /// loop counts in a global of its own
+    fn statement(&mut self, statement: &Statement, loops: usize) {
+        match statement {
+            Statement::Discard(value) => {
+                self.expression(value);
+                self.emit(Opcode::Pop, None);
+            }
+            Statement::Assign(global, value) => {
+                self.expression(value);
+                let name = self.global(format!("g{}", global));
+                self.emit(Opcode::StoreGlobal, Some(name));
+            }
+            Statement::If(condition, then, otherwise) => {
+                self.expression(condition);
+                let to_otherwise = self.emit(Opcode::JumpIfFalse, Some(0));
+                for statement in then {
+                    self.statement(statement, loops);
+                }
+                let to_end = self.emit(Opcode::Jump, Some(0));
+                self.land(to_otherwise);
+                for statement in otherwise {
+                    self.statement(statement, loops);
+                }
+                self.land(to_end);
+            }
+            Statement::Repeat(count, body) => {
+                let counter = self.global(format!("loop{}", loops));
+                let count = self.constant(Value::Integer((*count).into()));
+                self.emit(Opcode::LoadConstant, Some(count));
+                self.emit(Opcode::StoreGlobal, Some(counter));
+                let top = self.emit(Opcode::LoadGlobal, Some(counter));
+                let to_end = self.emit(Opcode::JumpIfFalse, Some(0));
+                for statement in body {
+                    self.statement(statement, loops + 1);
+                }
+                let one = self.constant(Value::Integer(1));
+                self.emit(Opcode::LoadGlobal, Some(counter));
+                self.emit(Opcode::LoadConstant, Some(one));
+                self.emit(Opcode::Sub, None);
+                self.emit(Opcode::StoreGlobal, Some(counter));
+                self.emit(Opcode::Jump, Some(top));
+                self.land(to_end);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+//! Instruction-by-instruction execution traces.
+
+use crate::vm::assembler::mnemonic;
+use crate::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use std::fmt;
+use std::io::{self, Write};
+
+#[derive(Debug)]
+pub enum TraceError {
+    /// The trace could not be written
+    Io(io::Error),
+    /// The traced instruction failed; its row says so too
+    Execution(VmError),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TraceError::Io(e) => write!(f, "Failed to write trace: {}", e),
+            TraceError::Execution(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl From<io::Error> for TraceError {
+    fn from(err: io::Error) -> Self {
+        TraceError::Io(err)
+    }
+}
+
+impl From<VmError> for TraceError {
+    fn from(err: VmError) -> Self {
+        TraceError::Execution(err)
+    }
+}
+
+/// Runs a VM an instruction at a time, writing a table row for each: the
+/// step number, program counter, opcode and operand, and the stack top the
+/// instruction left.
+///
+/// ```text
+///  Step |    PC | Opcode       | Operand      | Stack top
+/// ------|-------|--------------|--------------|----------
+///     0 |     0 | PUSH         | 5            | 5
+/// ```
+pub struct TraceWriter<W> {
+    output: W,
+    steps: u64,
+}
+
+impl<W: Write> TraceWriter<W> {
+    pub fn new(output: W) -> Self {
+        Self { output, steps: 0 }
+    }
+
+    /// Instructions traced so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+
+    /// Writes the column headings
+    pub fn write_header(&mut self) -> io::Result<()> {
+        writeln!(self.output, " Step |    PC | Opcode       | Operand      | Stack top")?;
+        writeln!(self.output, "------|-------|--------------|--------------|----------")
+    }
+
+    /// Runs the next instruction of `vm` and writes its row. A failing
+    /// instruction gets a row with the error in place of the stack top.
+    pub fn step(&mut self, vm: &mut VirtualMachine) -> Result<(), TraceError> {
+        let pc = vm.program_counter();
+        let (name, operand) = match vm.current_instruction() {
+            Some(instruction) => {
+                let opcode = instruction.opcode();
+                let name = mnemonic(opcode).map_or_else(|| format!("{:?}", opcode), str::to_string);
+                let operand = instruction.operand().map(ToString::to_string).unwrap_or_default();
+                (name, operand)
+            }
+            None => (String::new(), String::new()),
+        };
+        write!(self.output, "{:5} | {:5} | {:<12} | {:<12} | ", self.steps, pc, name, operand)?;
+        self.steps += 1;
+
+        if let Err(e) = vm.step() {
+            writeln!(self.output, "error: {}", e)?;
+            return Err(e.into());
+        }
+        match vm.stack_top() {
+            Ok(top) => writeln!(self.output, "{}", top)?,
+            Err(_) => writeln!(self.output, "[empty]")?,
+        }
+        Ok(())
+    }
+
+    /// Traces `vm` until its program halts, or for at most `max_steps`
+    /// instructions, reporting which happened
+    pub fn run(&mut self, vm: &mut VirtualMachine, max_steps: u64) -> Result<RunOutcome, TraceError> {
+        for _ in 0..max_steps {
+            if vm.is_halted() {
+                return Ok(RunOutcome::Halted);
+            }
+            self.step(vm)?;
+        }
+        Ok(if vm.is_halted() { RunOutcome::Halted } else { RunOutcome::Exhausted })
+    }
+}
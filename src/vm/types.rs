@@ -1,13 +1,367 @@
-use crate::vm::heap::{GcPtr, Object};
+use crate::vm::heap::{
+    drop_values, Array, ByteBuffer, FloatArray, GcPtr, GcStr, HeapCharge, IntArray, Object,
+};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::Arc;
 
+/// Immutable, reference-counted string. Cloning shares the buffer, so
+/// pushing or duplicating string values never copies their contents.
+#[derive(Clone)]
+pub struct VmString(Arc<StringData>); // A thin pointer keeps this one word
+
+/// A string's text and, for one a program built, the bytes it counts
+/// against the heap
+struct StringData {
+    text: String,
+    charge: Option<HeapCharge>,
+}
+
+impl VmString {
+    pub fn as_str(&self) -> &str {
+        &self.0.text
+    }
+
+    /// Whether both strings share the same buffer
+    pub fn ptr_eq(a: &VmString, b: &VmString) -> bool {
+        Arc::ptr_eq(&a.0, &b.0)
+    }
+
+    pub(crate) fn charged(text: String, charge: HeapCharge) -> Self {
+        VmString(Arc::new(StringData { text, charge: Some(charge) }))
+    }
+
+    /// The text, taken out, if nothing else holds this string and no heap
+    /// counts it yet
+    pub(crate) fn take_unowned(&mut self) -> Option<String> {
+        Arc::get_mut(&mut self.0)
+            .filter(|data| data.charge.is_none())
+            .map(|data| mem::take(&mut data.text))
+    }
+}
+
+impl Deref for VmString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl From<String> for VmString {
+    fn from(text: String) -> Self {
+        VmString(Arc::new(StringData { text, charge: None }))
+    }
+}
+
+impl From<&str> for VmString {
+    fn from(s: &str) -> Self {
+        VmString::from(s.to_string())
+    }
+}
+
+impl PartialEq for VmString {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for VmString {}
+
+impl PartialOrd for VmString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for VmString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for VmString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().hash(state);
+    }
+}
+
+impl PartialEq<str> for VmString {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for VmString {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Debug for VmString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for VmString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Exact fixed-point decimal with [`Decimal::SCALE`] fractional digits,
+/// stored as a scaled `i64`. Unlike floats, `0.1 + 0.2` is exactly `0.3`.
+/// Arithmetic is checked: overflow yields `None`, and results of `*` and
+/// `/` are rounded half away from zero to the last fractional digit.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Decimal(i64);
+
+impl Decimal {
+    /// Number of fractional decimal digits
+    pub const SCALE: u32 = 6;
+    const ONE: i64 = 10i64.pow(Self::SCALE);
+
+    pub const ZERO: Decimal = Decimal(0);
+
+    /// Decimal whose value is `scaled / 10^SCALE`
+    pub const fn from_scaled(scaled: i64) -> Self {
+        Decimal(scaled)
+    }
+
+    pub const fn scaled(self) -> i64 {
+        self.0
+    }
+
+    pub fn from_integer(value: i64) -> Option<Self> {
+        value.checked_mul(Self::ONE).map(Decimal)
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::ONE as f64
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn checked_add(self, rhs: Decimal) -> Option<Decimal> {
+        self.0.checked_add(rhs.0).map(Decimal)
+    }
+
+    pub fn checked_sub(self, rhs: Decimal) -> Option<Decimal> {
+        self.0.checked_sub(rhs.0).map(Decimal)
+    }
+
+    pub fn checked_mul(self, rhs: Decimal) -> Option<Decimal> {
+        let product = self.0 as i128 * rhs.0 as i128;
+        Self::from_i128(Self::div_round(product, Self::ONE as i128))
+    }
+
+    /// `None` on division by zero or overflow
+    pub fn checked_div(self, rhs: Decimal) -> Option<Decimal> {
+        if rhs.0 == 0 {
+            return None;
+        }
+        Self::from_i128(Self::div_round(self.0 as i128 * Self::ONE as i128, rhs.0 as i128))
+    }
+
+    pub fn checked_neg(self) -> Option<Decimal> {
+        self.0.checked_neg().map(Decimal)
+    }
+
+    /// Remainder with the sign of the dividend; `None` on division by zero
+    pub fn checked_rem(self, rhs: Decimal) -> Option<Decimal> {
+        self.0.checked_rem(rhs.0).map(Decimal)
+    }
+
+    fn from_i128(value: i128) -> Option<Decimal> {
+        i64::try_from(value).ok().map(Decimal)
+    }
+
+    fn div_round(numerator: i128, denominator: i128) -> i128 {
+        let quotient = numerator / denominator;
+        let remainder = numerator % denominator;
+        if 2 * remainder.abs() >= denominator.abs() {
+            quotient + numerator.signum() * denominator.signum()
+        } else {
+            quotient
+        }
+    }
+}
+
+/// Error returned when parsing a [`Decimal`] from text fails
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseDecimalError(String);
+
+impl fmt::Display for ParseDecimalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid decimal literal: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseDecimalError {}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    /// Parses `[-]digits[.digits]` with at most `SCALE` fractional digits
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let error = || ParseDecimalError(s.to_string());
+        let (negative, digits) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        let all_digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) {
+            return Err(error());
+        }
+        if fraction.len() > Self::SCALE as usize {
+            return Err(error());
+        }
+
+        let padded = format!("{:0<width$}", fraction, width = Self::SCALE as usize);
+        let magnitude = whole
+            .parse::<i64>()
+            .ok()
+            .and_then(|w| w.checked_mul(Self::ONE))
+            .and_then(|w| w.checked_add(padded.parse::<i64>().ok()?))
+            .ok_or_else(error)?;
+        Ok(Decimal(if negative { -magnitude } else { magnitude }))
+    }
+}
+
+/// Shortest exact form with at least one fractional digit: `0.3`, `2.0`, `-1.25`
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let magnitude = self.0.unsigned_abs();
+        let one = Self::ONE as u64;
+        let fraction = format!("{:0width$}", magnitude % one, width = Self::SCALE as usize);
+        let fraction = fraction.trim_end_matches('0');
+        let fraction = if fraction.is_empty() { "0" } else { fraction };
+        write!(f, "{}{}.{}", sign, magnitude / one, fraction)
+    }
+}
+
+impl fmt::Debug for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Decimal({})", self)
+    }
+}
+
+/// Maximum number of elements in a tuple
+pub const MAX_TUPLE_LEN: usize = 8;
+
+/// Small immutable aggregate, e.g. a function's multiple return values.
+/// Tuples live outside the GC heap: building one never allocates a heap
+/// object. The elements are stored inline in a fixed array of
+/// [`MAX_TUPLE_LEN`] slots behind a single shared allocation, which keeps
+/// a `Value` two words; clones share the same elements.
+#[derive(Clone)]
+pub struct VmTuple(Arc<TupleElements>);
+
+struct TupleElements {
+    len: usize,
+    slots: [Value; MAX_TUPLE_LEN],
+}
+
+impl VmTuple {
+    /// Returns `None` if `elements` holds more than [`MAX_TUPLE_LEN`]
+    /// values
+    pub fn new(elements: impl IntoIterator<Item = Value>) -> Option<Self> {
+        let mut slots: [Value; MAX_TUPLE_LEN] = std::array::from_fn(|_| Value::Null);
+        let mut len = 0;
+        for element in elements {
+            *slots.get_mut(len)? = element;
+            len += 1;
+        }
+        Some(VmTuple(Arc::new(TupleElements { len, slots })))
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Value> {
+        self.as_slice().get(index)
+    }
+
+    pub fn as_slice(&self) -> &[Value] {
+        &self.0.slots[..self.0.len]
+    }
+
+    /// Takes the elements out if no other clone shares them
+    pub(crate) fn take_unique(&mut self) -> Option<impl Iterator<Item = Value> + '_> {
+        let elements = Arc::get_mut(&mut self.0)?;
+        let len = mem::take(&mut elements.len);
+        Some(elements.slots[..len].iter_mut().map(|slot| mem::replace(slot, Value::Null)))
+    }
+}
+
+/// Elements that hold other aggregates are freed through [`drop_values`],
+/// so deeply nested tuples do not drop recursively
+impl Drop for VmTuple {
+    fn drop(&mut self) {
+        let Some(elements) = self.take_unique() else {
+            return;
+        };
+        let nested: Vec<Value> = elements
+            .filter(|element| {
+                matches!(element, Value::Tuple(_) | Value::Array(_) | Value::GcObject(_))
+            })
+            .collect();
+        if !nested.is_empty() {
+            drop_values(nested);
+        }
+    }
+}
+
+impl PartialEq for VmTuple {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Deref for VmTuple {
+    type Target = [Value];
+
+    fn deref(&self) -> &[Value] {
+        self.as_slice()
+    }
+}
+
+impl fmt::Debug for VmTuple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("").field(&self.as_slice()).finish()
+    }
+}
+
+/// A VM value. Every payload fits in one machine word (heap data is held
+/// through `GcPtr` or `VmString` handles), so a `Value` is two words and
+/// cloning it never copies string or object contents.
+///
+/// Rust-level `==` compares heap values structurally. Guest programs get
+/// identity semantics from `Equal` (see [`Value::ref_equals`]) and
+/// structural semantics from `DeepEqual` (see [`Value::deep_equals`]).
 #[derive(Debug, Clone, PartialEq)]
 pub enum Value {
     Integer(i64),
     Float(f64),
+    Decimal(Decimal),
     Boolean(bool),
-    String(String),
+    Char(char),
+    String(VmString),
     GcString(GcPtr<String>),
+    StrSlice(GcStr),
     GcObject(GcPtr<Object>),
+    Array(GcPtr<Array>),
+    Bytes(GcPtr<ByteBuffer>),
+    IntArray(GcPtr<IntArray>),
+    FloatArray(GcPtr<FloatArray>),
+    Tuple(VmTuple),
+    Function { address: u32, arity: u32 },
     Null,
 }
 
@@ -16,10 +370,19 @@ impl Value {
         match self {
             Value::Integer(_) => "integer",
             Value::Float(_) => "float",
+            Value::Decimal(_) => "decimal",
             Value::Boolean(_) => "boolean",
+            Value::Char(_) => "char",
             Value::String(_) => "string",
             Value::GcString(_) => "gc_string",
+            Value::StrSlice(_) => "string_slice",
             Value::GcObject(_) => "gc_object",
+            Value::Array(_) => "array",
+            Value::Bytes(_) => "bytes",
+            Value::IntArray(_) => "int_array",
+            Value::FloatArray(_) => "float_array",
+            Value::Tuple(_) => "tuple",
+            Value::Function { .. } => "function",
             Value::Null => "null",
         }
     }
@@ -29,10 +392,486 @@ impl Value {
             Value::Boolean(b) => *b,
             Value::Integer(i) => *i != 0,
             Value::Float(f) => *f != 0.0,
+            Value::Decimal(d) => !d.is_zero(),
+            Value::Char(c) => *c != '\0',
             Value::String(s) => !s.is_empty(),
             Value::GcString(s) => !s.is_empty(),
+            Value::StrSlice(s) => !s.is_empty(),
             Value::GcObject(_) => true, // Objects are always truthy
+            Value::Array(a) => !a.is_empty(),
+            Value::Bytes(b) => !b.is_empty(),
+            Value::IntArray(a) => !a.is_empty(),
+            Value::FloatArray(a) => !a.is_empty(),
+            Value::Tuple(t) => !t.is_empty(),
+            Value::Function { .. } => true,
             Value::Null => false,
         }
     }
+
+    /// Deterministic total order over all values, used by `ArraySort`.
+    ///
+    /// Values of different kinds order by kind:
+    /// null < boolean < number < char < string < bytes < int array <
+    /// float array < array < tuple < function < object.
+    /// Within a kind:
+    /// - integers, decimals and floats compare numerically; when equal the
+    ///   integer comes first, then the decimal, and NaN sorts after every
+    ///   other number
+    /// - `String`, `GcString` and `StrSlice` compare by content (byte-wise)
+    /// - bytes, typed arrays, arrays and tuples compare lexicographically by
+    ///   element
+    /// - functions compare by address, then arity
+    /// - objects compare by allocation order (object id)
+    pub fn total_cmp(&self, other: &Value) -> Ordering {
+        let rank_order = self.order_rank().cmp(&other.order_rank());
+        if rank_order != Ordering::Equal {
+            return rank_order;
+        }
+
+        match (self, other) {
+            (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+            (Value::Char(a), Value::Char(b)) => a.cmp(b),
+            (Value::Function { address: a, arity: x }, Value::Function { address: b, arity: y }) => {
+                (a, x).cmp(&(b, y))
+            }
+            (Value::GcObject(a), Value::GcObject(b)) => a.object_id().cmp(&b.object_id()),
+            (Value::Bytes(a), Value::Bytes(b)) => a.to_vec().cmp(&b.to_vec()),
+            (Value::IntArray(a), Value::IntArray(b)) => a.to_vec().cmp(&b.to_vec()),
+            (Value::FloatArray(a), Value::FloatArray(b)) => {
+                let floats = |array: &FloatArray| -> Vec<Value> {
+                    array.to_vec().into_iter().map(Value::Float).collect()
+                };
+                Self::elements_cmp(&floats(a), &floats(b))
+            }
+            (Value::Array(a), Value::Array(b)) => Self::elements_cmp(&a.to_vec(), &b.to_vec()),
+            (Value::Tuple(a), Value::Tuple(b)) => Self::elements_cmp(a, b),
+            _ => match (self.as_str(), other.as_str()) {
+                (Some(a), Some(b)) => a.cmp(b),
+                _ => Self::numeric_cmp(self, other),
+            },
+        }
+    }
+
+    fn elements_cmp(a: &[Value], b: &[Value]) -> Ordering {
+        a.iter()
+            .zip(b.iter())
+            .map(|(x, y)| x.total_cmp(y))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or_else(|| a.len().cmp(&b.len()))
+    }
+
+    fn order_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Boolean(_) => 1,
+            Value::Integer(_) | Value::Decimal(_) | Value::Float(_) => 2,
+            Value::Char(_) => 3,
+            Value::String(_) | Value::GcString(_) | Value::StrSlice(_) => 4,
+            Value::Bytes(_) => 5,
+            Value::IntArray(_) => 6,
+            Value::FloatArray(_) => 7,
+            Value::Array(_) => 8,
+            Value::Tuple(_) => 9,
+            Value::Function { .. } => 10,
+            Value::GcObject(_) => 11,
+        }
+    }
+
+    /// Equality used by the `Equal`/`NotEqual`/`RefEqual` opcodes: objects,
+    /// arrays (plain or typed) and byte buffers are equal only if they are the same heap
+    /// allocation; strings (plain or GC) compare by content; tuples compare
+    /// element-wise with these same rules; other values by value.
+    pub fn ref_equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::GcObject(a), Value::GcObject(b)) => a.object_id() == b.object_id(),
+            (Value::Array(a), Value::Array(b)) => a.object_id() == b.object_id(),
+            (Value::Bytes(a), Value::Bytes(b)) => a.object_id() == b.object_id(),
+            (Value::IntArray(a), Value::IntArray(b)) => a.object_id() == b.object_id(),
+            (Value::FloatArray(a), Value::FloatArray(b)) => a.object_id() == b.object_id(),
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(x, y)| x.ref_equals(y))
+            }
+            _ => match (self.as_str(), other.as_str()) {
+                (Some(a), Some(b)) => a == b,
+                _ => self == other,
+            },
+        }
+    }
+
+    /// Structural equality used by the `DeepEqual` opcode: containers are
+    /// equal when their contents are deeply equal, wherever they live.
+    /// Cycles are handled by treating a pair already under comparison as
+    /// equal.
+    pub fn deep_equals(&self, other: &Value) -> bool {
+        self.deep_equals_inner(other, &mut Vec::new())
+    }
+
+    fn deep_equals_inner(&self, other: &Value, in_progress: &mut Vec<(usize, usize)>) -> bool {
+        if self.ref_equals(other) {
+            return true;
+        }
+
+        let pair = match (self, other) {
+            (Value::Array(a), Value::Array(b)) => (a.object_id(), b.object_id()),
+            (Value::GcObject(a), Value::GcObject(b)) => (a.object_id(), b.object_id()),
+            (Value::Bytes(a), Value::Bytes(b)) => return a.to_vec() == b.to_vec(),
+            (Value::IntArray(a), Value::IntArray(b)) => return a.to_vec() == b.to_vec(),
+            (Value::FloatArray(a), Value::FloatArray(b)) => return a.to_vec() == b.to_vec(),
+            // Tuples are immutable values and cannot contain themselves
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                return a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(x, y)| x.deep_equals_inner(y, in_progress));
+            }
+            _ => return false,
+        };
+        if in_progress.contains(&pair) {
+            return true;
+        }
+        in_progress.push(pair);
+
+        let equal = match (self, other) {
+            (Value::Array(a), Value::Array(b)) => {
+                let (a, b) = (a.to_vec(), b.to_vec());
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(x, y)| x.deep_equals_inner(y, in_progress))
+            }
+            (Value::GcObject(a), Value::GcObject(b)) => {
+                a.field_count() == b.field_count()
+                    && a.fields().into_iter().all(|(name, x)| {
+                        b.get_field(&name)
+                            .is_some_and(|y| x.deep_equals_inner(&y, in_progress))
+                    })
+            }
+            _ => false,
+        };
+
+        in_progress.pop();
+        equal
+    }
+
+    /// Text of any string-like value (plain, GC or slice)
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s.as_str()),
+            Value::GcString(s) => Some(s.as_str()),
+            Value::StrSlice(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    fn numeric_cmp(a: &Value, b: &Value) -> Ordering {
+        // Integers and decimals compare exactly; anything involving a float
+        // compares as f64
+        let exact = |v: &Value| match v {
+            Value::Integer(i) => Some(*i as i128 * Decimal::ONE as i128),
+            Value::Decimal(d) => Some(d.scaled() as i128),
+            _ => None,
+        };
+        let approximate = |v: &Value| match v {
+            Value::Integer(i) => *i as f64,
+            Value::Decimal(d) => d.to_f64(),
+            Value::Float(f) => *f,
+            _ => 0.0,
+        };
+        let kind = |v: &Value| match v {
+            Value::Integer(_) => 0,
+            Value::Decimal(_) => 1,
+            _ => 2,
+        };
+
+        let by_value = match (exact(a), exact(b)) {
+            (Some(x), Some(y)) => x.cmp(&y),
+            _ => {
+                let (x, y) = (approximate(a), approximate(b));
+                match (x.is_nan(), y.is_nan()) {
+                    (true, true) => Ordering::Equal,
+                    (true, false) => Ordering::Greater,
+                    (false, true) => Ordering::Less,
+                    (false, false) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+                }
+            }
+        };
+        by_value.then(kind(a).cmp(&kind(b)))
+    }
+}
+
+/// Guest-facing formatting, as a program's output should show the value:
+/// `42`, `2.5`, `2.0`, `true`, `null`, `hello`, `[1, "a", 'c']`,
+/// `(1, 2)`, `i64[1, 2]`, `{name: "x"}`. Strings and chars print bare at the top level and quoted
+/// inside collections; object fields are listed in name order. Use `{:?}`
+/// for the Rust-level representation.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => f.write_str(s),
+            Value::GcString(s) => f.write_str(s),
+            Value::StrSlice(s) => f.write_str(s),
+            Value::Char(c) => write!(f, "{}", c),
+            _ => self.fmt_nested(f, &mut Vec::new(), 0),
+        }
+    }
+}
+
+/// Tuples, arrays and objects nested deeper than this print as `...`, so
+/// printing a deeply nested value cannot overflow the stack
+const MAX_DISPLAY_DEPTH: usize = 64;
+
+impl Value {
+    fn fmt_nested(&self, f: &mut fmt::Formatter<'_>, seen: &mut Vec<usize>, depth: usize) -> fmt::Result {
+        if depth >= MAX_DISPLAY_DEPTH && matches!(self, Value::Tuple(_) | Value::Array(_) | Value::GcObject(_)) {
+            return write!(f, "...");
+        }
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) if x.is_finite() && x.fract() == 0.0 => write!(f, "{:.1}", x),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Decimal(d) => write!(f, "{}", d),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Char(c) => write!(f, "{:?}", c),
+            Value::String(s) => write!(f, "{:?}", s.as_str()),
+            Value::GcString(s) => write!(f, "{:?}", s.as_str()),
+            Value::StrSlice(s) => write!(f, "{:?}", s.as_str()),
+            Value::Null => write!(f, "null"),
+            Value::Function { address, arity } => {
+                write!(f, "<function @{}/{}>", address, arity)
+            }
+            Value::Bytes(bytes) => {
+                write!(f, "<bytes")?;
+                for byte in bytes.to_vec() {
+                    write!(f, " {:02x}", byte)?;
+                }
+                write!(f, ">")
+            }
+            Value::IntArray(array) => {
+                write!(f, "i64[")?;
+                for (i, element) in array.to_vec().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Value::FloatArray(array) => {
+                write!(f, "f64[")?;
+                for (i, element) in array.to_vec().into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    Value::Float(element).fmt_nested(f, seen, depth)?;
+                }
+                write!(f, "]")
+            }
+            Value::Tuple(tuple) => {
+                write!(f, "(")?;
+                for (i, element) in tuple.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f, seen, depth + 1)?;
+                }
+                if tuple.len() == 1 {
+                    write!(f, ",")?;
+                }
+                write!(f, ")")
+            }
+            Value::Array(array) => {
+                // Arrays and objects may contain themselves
+                if seen.contains(&array.object_id()) {
+                    return write!(f, "[...]");
+                }
+                seen.push(array.object_id());
+                write!(f, "[")?;
+                for (i, element) in array.to_vec().iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    element.fmt_nested(f, seen, depth + 1)?;
+                }
+                seen.pop();
+                write!(f, "]")
+            }
+            Value::GcObject(object) => {
+                if seen.contains(&object.object_id()) {
+                    return write!(f, "{{...}}");
+                }
+                seen.push(object.object_id());
+                let mut fields = object.fields();
+                fields.sort_by(|a, b| a.0.cmp(&b.0));
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.into_iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: ", name)?;
+                    value.fmt_nested(f, seen, depth + 1)?;
+                }
+                seen.pop();
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+/// A `Value` usable as a `HashMap`/`HashSet` key.
+///
+/// Key equality differs from `Value`'s `PartialEq` so that it is a proper
+/// equivalence relation:
+/// - floats compare by bit pattern after canonicalising `-0.0` to `0.0` and
+///   every NaN to a single NaN, so NaN keys can be found again
+/// - integers and floats are distinct keys (`1` and `1.0` differ)
+/// - `String`, `GcString` and `StrSlice` compare by content, so interned
+///   strings, slices and literals find the same entry
+/// - objects, arrays (plain or typed) and byte buffers compare by identity
+///   (object id), since their contents can change while they are stored in
+///   a map
+/// - tuples compare element-wise by these same rules
+#[derive(Debug, Clone)]
+pub struct ValueKey(Value);
+
+impl ValueKey {
+    pub fn new(value: Value) -> Self {
+        ValueKey(value)
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.0
+    }
+
+    pub fn into_value(self) -> Value {
+        self.0
+    }
+
+    fn canonical_float_bits(f: f64) -> u64 {
+        if f.is_nan() {
+            f64::NAN.to_bits()
+        } else if f == 0.0 {
+            0.0f64.to_bits()
+        } else {
+            f.to_bits()
+        }
+    }
+
+    fn heap_identity(&self) -> Option<usize> {
+        match &self.0 {
+            Value::GcObject(p) => Some(p.object_id()),
+            Value::Array(p) => Some(p.object_id()),
+            Value::Bytes(p) => Some(p.object_id()),
+            Value::IntArray(p) => Some(p.object_id()),
+            Value::FloatArray(p) => Some(p.object_id()),
+            _ => None,
+        }
+    }
+}
+
+impl From<Value> for ValueKey {
+    fn from(value: Value) -> Self {
+        ValueKey(value)
+    }
+}
+
+impl PartialEq for ValueKey {
+    fn eq(&self, other: &Self) -> bool {
+        match (&self.0, &other.0) {
+            (Value::Float(a), Value::Float(b)) => {
+                Self::canonical_float_bits(*a) == Self::canonical_float_bits(*b)
+            }
+            (Value::GcObject(_), Value::GcObject(_))
+            | (Value::Array(_), Value::Array(_))
+            | (Value::Bytes(_), Value::Bytes(_))
+            | (Value::IntArray(_), Value::IntArray(_))
+            | (Value::FloatArray(_), Value::FloatArray(_)) => {
+                self.heap_identity() == other.heap_identity()
+            }
+            (Value::Tuple(a), Value::Tuple(b)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .zip(b.iter())
+                        .all(|(x, y)| ValueKey::new(x.clone()) == ValueKey::new(y.clone()))
+            }
+            (a, b) => match (a.as_str(), b.as_str()) {
+                (Some(a), Some(b)) => a == b,
+                (None, None) => a == b,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Eq for ValueKey {}
+
+impl Hash for ValueKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        match &self.0 {
+            Value::Integer(i) => {
+                0u8.hash(state);
+                i.hash(state);
+            }
+            Value::Float(f) => {
+                1u8.hash(state);
+                Self::canonical_float_bits(*f).hash(state);
+            }
+            Value::Boolean(b) => {
+                2u8.hash(state);
+                b.hash(state);
+            }
+            Value::Char(c) => {
+                3u8.hash(state);
+                c.hash(state);
+            }
+            Value::String(s) => {
+                4u8.hash(state);
+                s.as_str().hash(state);
+            }
+            Value::GcString(s) => {
+                4u8.hash(state);
+                s.as_str().hash(state);
+            }
+            Value::StrSlice(s) => {
+                4u8.hash(state);
+                s.as_str().hash(state);
+            }
+            Value::GcObject(p) => {
+                5u8.hash(state);
+                p.object_id().hash(state);
+            }
+            Value::Array(p) => {
+                6u8.hash(state);
+                p.object_id().hash(state);
+            }
+            Value::Bytes(p) => {
+                7u8.hash(state);
+                p.object_id().hash(state);
+            }
+            Value::Function { address, arity } => {
+                8u8.hash(state);
+                address.hash(state);
+                arity.hash(state);
+            }
+            Value::Null => 9u8.hash(state),
+            Value::Decimal(d) => {
+                11u8.hash(state);
+                d.hash(state);
+            }
+            Value::IntArray(p) => {
+                12u8.hash(state);
+                p.object_id().hash(state);
+            }
+            Value::FloatArray(p) => {
+                13u8.hash(state);
+                p.object_id().hash(state);
+            }
+            Value::Tuple(t) => {
+                10u8.hash(state);
+                t.len().hash(state);
+                for element in t.iter() {
+                    ValueKey::new(element.clone()).hash(state);
+                }
+            }
+        }
+    }
 }
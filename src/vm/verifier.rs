@@ -0,0 +1,316 @@
+use crate::vm::instruction::{Instruction, Opcode};
+use crate::vm::module::{BytecodeModule, FunctionInfo};
+use crate::vm::types::{Value, MAX_TUPLE_LEN};
+use std::fmt;
+
+/// The first problem the verifier found, at instruction `pc`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerificationError {
+    pub pc: usize,
+    pub message: String,
+}
+
+impl VerificationError {
+    fn new(pc: usize, message: impl Into<String>) -> Self {
+        Self {
+            pc,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {}: {}", self.pc, self.message)
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
+/// Operand stack height on entry to an instruction, relative to the start
+/// of the enclosing function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Depth {
+    Known(usize),
+    /// After a call whose stack effect is not declared
+    Unknown,
+}
+
+/// Checks a program before it runs: operands have the types their opcodes
+/// expect, jump and call targets and constant indices are in bounds, no
+/// instruction can pop from an empty stack, and every path reaching an
+/// instruction does so with the same stack depth.
+///
+/// Calls to functions in `functions` are checked against their declared
+/// arity and return count; calls elsewhere leave the depth unknown, and
+/// what follows them goes unchecked, until the paths through them rejoin
+/// one with a known depth. From there on the known depth is checked as if
+/// every path had it.
+pub struct Verifier<'a> {
+    instructions: &'a [Instruction],
+    constants: &'a [Value],
+    functions: &'a [FunctionInfo],
+    entry_points: Vec<usize>,
+}
+
+impl<'a> Verifier<'a> {
+    pub fn new(instructions: &'a [Instruction], constants: &'a [Value]) -> Self {
+        Self {
+            instructions,
+            constants,
+            functions: &[],
+            entry_points: Vec::new(),
+        }
+    }
+
+    pub fn with_functions(mut self, functions: &'a [FunctionInfo]) -> Self {
+        self.functions = functions;
+        self
+    }
+
+    /// Also starts a path with an empty stack at `pc`, for code that is
+    /// entered other than by the program's start or a declared function,
+    /// such as a module added to one already loaded
+    pub fn with_entry_point(mut self, pc: usize) -> Self {
+        self.entry_points.push(pc);
+        self
+    }
+
+    pub fn verify(&self) -> Result<(), VerificationError> {
+        self.stack_depths().map(|_| ())
+    }
+
+    /// Verifies the program and returns the operand stack depth on entry
+    /// to each instruction, relative to the start of its function: `None`
+    /// where no path reaches it or the depth depends on an undeclared call
+    pub fn stack_depths(&self) -> Result<Vec<Option<usize>>, VerificationError> {
+        for (pc, instruction) in self.instructions.iter().enumerate() {
+            self.check_operand(pc, instruction)?;
+        }
+        let depths = self.check_stack_depths()?;
+        Ok(depths
+            .into_iter()
+            .map(|depth| match depth {
+                Some(Depth::Known(depth)) => Some(depth),
+                _ => None,
+            })
+            .collect())
+    }
+
+    fn check_operand(&self, pc: usize, instruction: &Instruction) -> Result<(), VerificationError> {
+        let opcode = instruction.opcode();
+        let operand = instruction.operand();
+        let integer = || match operand {
+            Some(Value::Integer(value)) => Ok(*value),
+            Some(other) => Err(VerificationError::new(
+                pc,
+                format!("{:?} needs an integer operand, not {}", opcode, other.type_name()),
+            )),
+            None => Err(VerificationError::new(pc, format!("{:?} needs an operand", opcode))),
+        };
+        let index = |limit: usize, what: &str| {
+            let value = integer()?;
+            match usize::try_from(value) {
+                Ok(index) if index < limit => Ok(()),
+                _ => Err(VerificationError::new(
+                    pc,
+                    format!("{} {} is outside 0..{}", what, value, limit),
+                )),
+            }
+        };
+
+        match opcode {
+            Opcode::Jump | Opcode::JumpIfTrue | Opcode::JumpIfFalse | Opcode::Call => {
+                index(self.instructions.len(), "Target")
+            }
+            Opcode::LoadConstant => index(self.constants.len(), "Constant index"),
+            Opcode::LoadGlobal | Opcode::StoreGlobal => {
+                index(self.constants.len(), "Constant index")?;
+                match self.constants.get(integer()? as usize) {
+                    Some(Value::String(_)) => Ok(()),
+                    _ => Err(VerificationError::new(
+                        pc,
+                        format!("{:?} needs a constant naming the global", opcode),
+                    )),
+                }
+            }
+            Opcode::Push => match operand {
+                // With a pool, integer operands are indices into it
+                Some(Value::Integer(_)) if !self.constants.is_empty() => {
+                    index(self.constants.len(), "Constant index")
+                }
+                Some(_) => Ok(()),
+                None => Err(VerificationError::new(pc, "Push needs an operand")),
+            },
+            Opcode::Load | Opcode::Store | Opcode::TupleGet | Opcode::CallNative => {
+                index(usize::MAX, "Index").map_err(|_| {
+                    VerificationError::new(pc, format!("{:?} needs a non-negative index", opcode))
+                })
+            }
+            Opcode::NewArray if operand.is_none() => Ok(()),
+            Opcode::NewArray => index(usize::MAX, "Element count"),
+            Opcode::MakeTuple => index(MAX_TUPLE_LEN + 1, "Element count"),
+            Opcode::GetField | Opcode::SafeGetField | Opcode::SetField => match operand {
+                Some(Value::String(_) | Value::Integer(_)) => Ok(()),
+                _ => Err(VerificationError::new(
+                    pc,
+                    format!("{:?} needs a field name or number", opcode),
+                )),
+            },
+            Opcode::Wide => Err(VerificationError::new(
+                pc,
+                "Wide is an encoding prefix, not an instruction",
+            )),
+            _ if operand.is_some() => Err(VerificationError::new(
+                pc,
+                format!("{:?} does not take an operand", opcode),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The declared function whose body `pc` is in, taken to be the
+    /// nearest one starting at or before it
+    fn function_containing(&self, pc: usize) -> Option<&FunctionInfo> {
+        self.functions
+            .iter()
+            .filter(|function| function.address <= pc)
+            .max_by_key(|function| function.address)
+    }
+
+    fn check_stack_depths(&self) -> Result<Vec<Option<Depth>>, VerificationError> {
+        let mut depths: Vec<Option<Depth>> = vec![None; self.instructions.len()];
+        let mut pending = Vec::new();
+
+        let reach = |pc: usize,
+                         depth: Depth,
+                         from: usize,
+                         depths: &mut Vec<Option<Depth>>,
+                         pending: &mut Vec<usize>|
+         -> Result<(), VerificationError> {
+            let Some(slot) = depths.get_mut(pc) else {
+                return Err(VerificationError::new(
+                    from,
+                    "Execution can run past the end of the program",
+                ));
+            };
+            let merged = match (*slot, depth) {
+                (None, depth) => depth,
+                (Some(Depth::Known(a)), Depth::Known(b)) if a != b => {
+                    return Err(VerificationError::new(
+                        pc,
+                        format!("Reached with stack depth {} on one path and {} on another", a, b),
+                    ));
+                }
+                // A known depth is kept where paths of unknown depth join it
+                (Some(Depth::Unknown), depth) | (Some(depth), Depth::Unknown) => depth,
+                (Some(known), _) => known,
+            };
+            if *slot != Some(merged) {
+                *slot = Some(merged);
+                pending.push(pc);
+            }
+            Ok(())
+        };
+
+        if !self.instructions.is_empty() {
+            reach(0, Depth::Known(0), 0, &mut depths, &mut pending)?;
+        }
+        // Declared functions start with their arguments moved into locals
+        let entries = self.functions.iter().map(|function| function.address);
+        for pc in entries.chain(self.entry_points.iter().copied()) {
+            if pc < self.instructions.len() {
+                reach(pc, Depth::Known(0), pc, &mut depths, &mut pending)?;
+            }
+        }
+
+        while let Some(pc) = pending.pop() {
+            let depth = depths[pc].expect("pending instructions have a depth");
+            let instruction = &self.instructions[pc];
+            let pops = |needed: usize| match depth {
+                Depth::Known(available) if available < needed => Err(VerificationError::new(
+                    pc,
+                    format!(
+                        "{:?} needs {} values but the stack holds {}",
+                        instruction.opcode(),
+                        needed,
+                        available
+                    ),
+                )),
+                Depth::Known(available) => Ok(Depth::Known(available - needed)),
+                Depth::Unknown => Ok(Depth::Unknown),
+            };
+            let plus = |depth: Depth, count: usize| match depth {
+                Depth::Known(depth) => Depth::Known(depth + count),
+                Depth::Unknown => Depth::Unknown,
+            };
+            let target = || match instruction.operand() {
+                Some(Value::Integer(target)) => *target as usize,
+                _ => unreachable!("targets were checked with the operands"),
+            };
+
+            match instruction.opcode() {
+                Opcode::Halt => {}
+                Opcode::Return => {
+                    if let Depth::Known(actual) = depth
+                        && let Some(function) = self.function_containing(pc)
+                        && let Some(returns) = function.returns
+                        && actual != returns {
+                        return Err(VerificationError::new(
+                            pc,
+                            format!(
+                                "Function {} should return {} values, but returns {}",
+                                function.name, returns, actual
+                            ),
+                        ));
+                    }
+                }
+                Opcode::Call => {
+                    let target = target();
+                    let after = match self.functions.iter().find(|f| f.address == target) {
+                        Some(function) => match function.returns {
+                            Some(returns) => plus(pops(function.arity)?, returns),
+                            None => {
+                                pops(function.arity)?;
+                                Depth::Unknown
+                            }
+                        },
+                        None => {
+                            // The callee runs on the caller's stack
+                            reach(target, Depth::Unknown, pc, &mut depths, &mut pending)?;
+                            Depth::Unknown
+                        }
+                    };
+                    reach(pc + 1, after, pc, &mut depths, &mut pending)?;
+                }
+                Opcode::CallIndirect => {
+                    pops(1)?;
+                    reach(pc + 1, Depth::Unknown, pc, &mut depths, &mut pending)?;
+                }
+                // Host functions are registered with the VM, not the module
+                Opcode::CallNative => reach(pc + 1, Depth::Unknown, pc, &mut depths, &mut pending)?,
+                Opcode::Jump => reach(target(), depth, pc, &mut depths, &mut pending)?,
+                Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                    let after = pops(1)?;
+                    reach(target(), after, pc, &mut depths, &mut pending)?;
+                    reach(pc + 1, after, pc, &mut depths, &mut pending)?;
+                }
+                _ => {
+                    let (popped, pushed) = instruction
+                        .stack_effect()
+                        .expect("only control flow has no fixed stack effect");
+                    let after = plus(pops(popped)?, pushed);
+                    reach(pc + 1, after, pc, &mut depths, &mut pending)?;
+                }
+            }
+        }
+        Ok(depths)
+    }
+}
+
+/// Verifies a module's code against its constants and function table
+pub fn verify_module(module: &BytecodeModule) -> Result<(), VerificationError> {
+    Verifier::new(&module.instructions, &module.constants)
+        .with_functions(&module.functions)
+        .verify()
+}
@@ -0,0 +1,61 @@
+use stack_vm_jit::vm::analysis::{BasicBlock, EdgeKind, build_cfg};
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+
+/// Counts down from 10, adding the odd numbers
+const PROGRAM: &str = "    PUSH 0\n    PUSH 10\n.loop:\n    DUP\n    JF .done\n    DUP\n    PUSH 2\n    MOD\n    JF .even\n    SWAP\n    PUSH 1\n    ADD\n    SWAP\n.even:\n    PUSH 1\n    SUB\n    JMP .loop\n.done:\n    POP\n    HALT\n";
+
+#[test]
+fn test_build_cfg_splits_blocks_at_jumps() {
+    let (instructions, _) = Assembler::new().assemble(PROGRAM).unwrap();
+    let cfg = build_cfg(&instructions);
+    let blocks: Vec<(usize, usize)> = cfg.blocks.iter().map(|block| (block.start, block.end)).collect();
+    assert_eq!(blocks, [(0, 2), (2, 4), (4, 8), (8, 12), (12, 15), (15, 17)]);
+    let edges: Vec<(usize, usize, EdgeKind)> =
+        cfg.edges.iter().map(|edge| (edge.from, edge.to, edge.kind)).collect();
+    assert_eq!(
+        edges,
+        [
+            (0, 1, EdgeKind::Fallthrough),
+            (1, 5, EdgeKind::Taken),
+            (1, 2, EdgeKind::NotTaken),
+            (2, 4, EdgeKind::Taken),
+            (2, 3, EdgeKind::NotTaken),
+            (3, 4, EdgeKind::Fallthrough),
+            (4, 1, EdgeKind::Jump),
+        ]
+    );
+    assert_eq!(cfg.block_at(9), Some(3));
+    assert_eq!(cfg.block_at(17), None);
+    assert_eq!(cfg.successors(1).count(), 2);
+    assert_eq!(cfg.predecessors(1).count(), 2);
+    assert_eq!(cfg.blocks[1], BasicBlock { start: 2, end: 4 });
+
+    assert!(build_cfg(&[]).blocks.is_empty());
+}
+
+#[test]
+fn test_cfg_dot_export() {
+    let (instructions, constants) = Assembler::new().assemble(PROGRAM).unwrap();
+    let cfg = build_cfg(&instructions);
+    let dot = cfg.to_dot(&instructions, None);
+    assert!(dot.starts_with("digraph cfg {\n"));
+    assert!(dot.contains("    B0 [label=\"B0 (0..2)\\l   0: PUSH 0\\l   1: PUSH 10\\l\"];\n"));
+    assert!(dot.contains("    B1 -> B5 [label=\"taken\"];\n"));
+    assert!(dot.contains("    B1 -> B2 [label=\"not taken\", style=dashed];\n"));
+    assert!(dot.contains("    B4 -> B1;\n"));
+    assert!(dot.ends_with("}\n"));
+
+    let mut vm = VirtualMachine::builder().with_profiling(true).build();
+    vm.load_bytecode_module(instructions.clone(), constants).unwrap();
+    vm.run().unwrap();
+    let profiler = vm.get_profiler().unwrap();
+    assert_eq!(cfg.execution_count(1, profiler), 11);
+    assert_eq!(cfg.execution_count(3, profiler), 5);
+    let dot = cfg.to_dot(&instructions, Some(profiler));
+    assert!(dot.contains("B1 (2..4), ran 11\\l"));
+    assert!(dot.contains("style=filled, fillcolor=\"0.000 1.000 1.000\""));
+    assert!(dot.contains("    B1 -> B5 [label=\"taken 1\"];\n"));
+    assert!(dot.contains("    B1 -> B2 [label=\"not taken 10\", style=dashed];\n"));
+    assert!(dot.contains("    B2 -> B4 [label=\"taken 5\"];\n"));
+}
@@ -0,0 +1,148 @@
+use stack_vm_jit::vm::heap::Heap;
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+#[test]
+fn test_array_value_basics() {
+    let mut heap = Heap::new();
+
+    let empty = Value::Array(heap.allocate_array(vec![]).unwrap());
+    let full = Value::Array(heap.allocate_array(vec![Value::Integer(1)]).unwrap());
+
+    assert_eq!(empty.type_name(), "array");
+    assert!(!empty.is_truthy());
+    assert!(full.is_truthy());
+    assert_eq!(heap.allocated_objects(), 2);
+}
+
+#[test]
+fn test_array_structural_equality() {
+    let mut heap = Heap::new();
+
+    let a = heap.allocate_array(vec![Value::Integer(1), Value::Integer(2)]).unwrap();
+    let b = heap.allocate_array(vec![Value::Integer(1), Value::Integer(2)]).unwrap();
+    let c = heap.allocate_array(vec![Value::Integer(1)]).unwrap();
+
+    assert_eq!(Value::Array(a.clone()), Value::Array(b));
+    assert_ne!(Value::Array(a), Value::Array(c));
+}
+
+#[test]
+fn test_new_array_and_get() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(10))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(20))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(30))),
+        Instruction::new(Opcode::NewArray, Some(Value::Integer(3))), // [10, 20, 30]
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::ArrayGet, None),                    // arr[1]
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_size(), 1);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(20));
+}
+
+#[test]
+fn test_array_set_and_length() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::NewArray, Some(Value::Integer(2))),
+        Instruction::new(Opcode::Dup, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(99))),
+        Instruction::new(Opcode::ArraySet, None),                    // arr[0] = 99
+        Instruction::new(Opcode::Dup, None),
+        Instruction::new(Opcode::ArrayLength, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(2));
+}
+
+#[test]
+fn test_array_index_out_of_bounds() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::NewArray, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(5))),
+        Instruction::new(Opcode::ArrayGet, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    assert!(vm.run().is_err());
+}
+
+#[test]
+fn test_array_sort_mixed_values() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::String("b".into()))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(3))),
+        Instruction::new(Opcode::Push, Some(Value::Null)),
+        Instruction::new(Opcode::Push, Some(Value::Float(0.5))),
+        Instruction::new(Opcode::Push, Some(Value::String("a".into()))),
+        Instruction::new(Opcode::NewArray, Some(Value::Integer(5))),
+        Instruction::new(Opcode::ArraySort, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+
+    match vm.stack_top().unwrap() {
+        Value::Array(array) => assert_eq!(
+            array.to_vec(),
+            vec![
+                Value::Null,
+                Value::Float(0.5),
+                Value::Integer(3),
+                Value::String("a".into()),
+                Value::String("b".into()),
+            ]
+        ),
+        other => panic!("Expected array, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_load_constant_copies_array_data() {
+    use stack_vm_jit::vm::heap::{Array, GcPtr};
+
+    let mut vm = VirtualMachine::new();
+    let constants = vec![
+        Value::Array(GcPtr::detached(Array::new(vec![Value::Integer(1)]))),
+        Value::Integer(0),
+        Value::Boolean(true),
+    ];
+    let instructions = vec![
+        Instruction::new(Opcode::LoadConstant, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),    // constant 0
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),    // constant true
+        Instruction::new(Opcode::ArraySet, None),                  // copy[0] = true
+        Instruction::new(Opcode::LoadConstant, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, constants.clone()).unwrap();
+    vm.run().unwrap();
+
+    // The second load sees the untouched pool data
+    assert_eq!(vm.stack_top().unwrap(), &constants[0]);
+    assert_eq!(vm.heap_allocated_objects(), 2);
+}
@@ -0,0 +1,53 @@
+use stack_vm_jit::vm::bench::{self, Tier, Workload};
+
+#[test]
+fn test_workloads_compute_their_results() {
+    let expected = [
+        (Workload::fib(15), "610"),
+        (Workload::sieve(100), "25"),
+        (Workload::strings(12), "012345678901"),
+        (Workload::objects(10), "45"),
+    ];
+    for (workload, result) in expected {
+        let measurement = bench::measure(&workload, Tier::Interpreter, 1).unwrap();
+        assert_eq!(measurement.result, result, "{}", workload.name);
+        assert!(measurement.instructions > 0);
+    }
+}
+
+#[test]
+fn test_suite_compares_every_tier() {
+    let workloads = [Workload::fib(10), Workload::objects(20)];
+    let report = bench::run_suite(&workloads, 2).unwrap();
+    assert_eq!(report.measurements.len(), 6);
+    let tiers: Vec<Tier> = report.measurements.iter().take(3).map(|measurement| measurement.tier).collect();
+    assert_eq!(tiers, Tier::ALL);
+    for measurement in &report.measurements {
+        assert_eq!(measurement.runs, 2);
+        assert!(measurement.fastest <= measurement.mean);
+    }
+    // Every tier computes what the interpreter does
+    assert!(report.measurements[..3].iter().all(|measurement| measurement.result == "55"));
+    assert_eq!(report.speedup("fib", Tier::Interpreter), Some(1.0));
+
+    let table = report.to_string();
+    assert!(table.starts_with("Workload"));
+    assert_eq!(table.lines().count(), 7);
+    assert!(table.lines().any(|line| line.starts_with("objects") && line.contains("optimized")));
+}
+
+#[test]
+fn test_traced_runs_write_every_instruction() {
+    let workload = Workload::fib(5);
+    let mut trace = Vec::new();
+    let measurement = bench::measure_traced(&workload, Tier::Profiled, 2, &mut trace).unwrap();
+    assert_eq!(measurement.result, "5");
+
+    let trace = String::from_utf8(trace).unwrap();
+    let (first, second) = trace.split_once("fib on the profiled tier, run 2\n").unwrap();
+    let first = first.strip_prefix("fib on the profiled tier, run 1\n").unwrap();
+    assert!(first.starts_with(" Step |"));
+    // Both runs trace the same instructions
+    assert_eq!(first, second);
+    assert!(first.lines().count() as u64 > measurement.instructions);
+}
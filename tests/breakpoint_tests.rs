@@ -0,0 +1,83 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::runtime::{BreakpointHit, RunOutcome, VirtualMachine};
+use stack_vm_jit::vm::types::Value;
+
+// Counts n down to zero, summing n + (n - 1) + ... + 1
+const PROGRAM: &str = r#"
+    PUSH 4
+    CALL sum
+    HALT
+.func sum arity=1 returns=1 locals=2
+    PUSH 0
+    STORE 1
+.loop:
+    LOAD 0
+    JF .done
+    LOAD 1
+    LOAD 0
+    ADD
+    STORE 1
+    LOAD 0
+    PUSH 1
+    SUB
+    STORE 0
+    JMP .loop
+.done:
+    LOAD 1
+    RET
+.endfunc
+"#;
+
+fn vm() -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(PROGRAM).unwrap()).unwrap();
+    vm
+}
+
+/// Address of the loop head
+const LOOP: usize = 5;
+
+#[test]
+fn test_breakpoints_stop_and_continue() {
+    let mut vm = vm();
+    vm.add_breakpoint(LOOP);
+    vm.add_breakpoint(2);
+    assert_eq!(vm.breakpoints().collect::<Vec<_>>(), [2, LOOP]);
+
+    let mut counters = Vec::new();
+    loop {
+        match vm.run().unwrap() {
+            RunOutcome::Stopped(BreakpointHit { pc: LOOP, hits }) => {
+                assert_eq!(vm.program_counter(), LOOP);
+                counters.push((hits, vm.local(0).cloned().unwrap()));
+            }
+            RunOutcome::Stopped(BreakpointHit { pc: 2, hits: 1 }) => break,
+            other => panic!("unexpected outcome {:?}", other),
+        }
+    }
+    let expected: Vec<(u64, Value)> = (1..=5).map(|hit| (hit, Value::Integer(5 - hit as i64))).collect();
+    assert_eq!(counters, expected);
+    assert_eq!(vm.stack_contents(), [Value::Integer(10)]);
+
+    assert!(vm.remove_breakpoint(2));
+    assert!(!vm.remove_breakpoint(2));
+    assert_eq!(vm.run().unwrap(), RunOutcome::Halted);
+}
+
+#[test]
+fn test_conditional_breakpoints() {
+    let mut vm = vm();
+    vm.add_conditional_breakpoint(LOOP, |vm| vm.local(1) == Some(&Value::Integer(7)));
+    assert_eq!(vm.run().unwrap(), RunOutcome::Stopped(BreakpointHit { pc: LOOP, hits: 1 }));
+    // 4 + 3 so far, with 2 and 1 still to add
+    assert_eq!(vm.local(0), Some(&Value::Integer(2)));
+
+    assert_eq!(vm.run().unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(10));
+
+    // Breakpoints last across resets
+    vm.reset();
+    assert_eq!(vm.run().unwrap(), RunOutcome::Stopped(BreakpointHit { pc: LOOP, hits: 2 }));
+    vm.clear_breakpoints();
+    assert_eq!(vm.run().unwrap(), RunOutcome::Halted);
+}
@@ -0,0 +1,123 @@
+use stack_vm_jit::vm::builder::{BuilderError, ProgramBuilder};
+use stack_vm_jit::vm::instruction::Opcode;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+#[test]
+fn test_forward_and_backward_jumps() {
+    // sum = 0; i = 5; while i > 0 { sum = sum + i; i = i - 1; } sum
+    let mut builder = ProgramBuilder::new();
+    let main = builder.label("main");
+    let done = builder.label("done");
+    builder
+        .call(main)
+        .emit(Opcode::Halt)
+        .function(main, 0, 2)
+        .push(Value::Integer(0))
+        .emit_with(Opcode::Store, Value::Integer(0))
+        .push(Value::Integer(5))
+        .emit_with(Opcode::Store, Value::Integer(1));
+    let top = builder.here("top");
+    builder
+        .emit_with(Opcode::Load, Value::Integer(1))
+        .push(Value::Integer(0))
+        .emit(Opcode::GreaterThan)
+        .jump_if_false(done)
+        .emit_with(Opcode::Load, Value::Integer(0))
+        .emit_with(Opcode::Load, Value::Integer(1))
+        .emit(Opcode::Add)
+        .emit_with(Opcode::Store, Value::Integer(0))
+        .emit_with(Opcode::Load, Value::Integer(1))
+        .push(Value::Integer(1))
+        .emit(Opcode::Sub)
+        .emit_with(Opcode::Store, Value::Integer(1))
+        .jump_to(top)
+        .bind(done)
+        .emit_with(Opcode::Load, Value::Integer(0))
+        .emit(Opcode::Return)
+        .end_function();
+    let module = builder.build().unwrap();
+
+    assert_eq!(module.instructions[0].operand(), Some(&Value::Integer(2)));
+    assert_eq!(module.instructions[9].operand(), Some(&Value::Integer(19)));
+    assert_eq!(module.instructions[18].operand(), Some(&Value::Integer(6)));
+    assert_eq!(module.symbols.labels.get("top"), Some(&6));
+    assert_eq!(module.symbols.labels.get("done"), Some(&19));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(15));
+}
+
+#[test]
+fn test_functions_and_calls() {
+    let mut builder = ProgramBuilder::new();
+    let square = builder.label("square");
+    builder
+        .push(Value::Integer(7))
+        .call(square)
+        .emit(Opcode::Halt)
+        .function(square, 1, 1)
+        .emit_with(Opcode::Load, Value::Integer(0))
+        .emit(Opcode::Dup)
+        .emit(Opcode::Mul)
+        .emit(Opcode::Return)
+        .end_function();
+    let module = builder.build().unwrap();
+
+    let function = module.function("square").unwrap();
+    assert_eq!((function.address, function.arity, function.locals), (3, 1, 1));
+    assert_eq!(module.symbols.functions.get("square"), Some(&(3..7)));
+    assert!(!module.symbols.labels.contains_key("square"));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(49));
+}
+
+#[test]
+fn test_constant_pool_helpers() {
+    let mut builder = ProgramBuilder::new();
+    let greeting = builder.named_constant("GREETING", Value::String("hi".into()));
+    builder
+        .push_constant(Value::String("hi".into()))
+        .push(Value::Integer(2))
+        .emit(Opcode::Halt);
+    let module = builder.build().unwrap();
+
+    assert_eq!(greeting, 0);
+    assert_eq!(module.symbols.constants.get("GREETING"), Some(&0));
+    // The integer literal is pooled so PUSH still yields 2
+    assert_eq!(module.constants, vec![Value::String("hi".into()), Value::Integer(2)]);
+    assert_eq!(module.instructions[1].operand(), Some(&Value::Integer(1)));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(2));
+    assert_eq!(vm.stack_size(), 2);
+}
+
+#[test]
+fn test_builder_errors() {
+    let mut builder = ProgramBuilder::new();
+    let missing = builder.label("missing");
+    builder.jump_to(missing);
+    assert_eq!(builder.build().unwrap_err(), BuilderError::UnboundLabel("missing".to_string()));
+
+    let mut builder = ProgramBuilder::new();
+    let twice = builder.here("twice");
+    builder.emit(Opcode::Halt).bind(twice);
+    assert_eq!(builder.build().unwrap_err(), BuilderError::LabelAlreadyBound("twice".to_string()));
+
+    let mut builder = ProgramBuilder::new();
+    let f = builder.label("f");
+    builder.function(f, 0, 0).emit(Opcode::Return);
+    assert_eq!(builder.build().unwrap_err(), BuilderError::UnclosedFunction("f".to_string()));
+
+    let mut builder = ProgramBuilder::new();
+    builder.end_function();
+    assert_eq!(builder.build().unwrap_err(), BuilderError::EndFunctionWithoutFunction);
+}
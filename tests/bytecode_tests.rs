@@ -1,5 +1,5 @@
 use stack_vm_jit::vm::instruction::{Instruction, Opcode};
-use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
 use stack_vm_jit::vm::types::Value;
 
 #[test]
@@ -7,7 +7,7 @@ fn test_bytecode_module_creation() {
     // Create a simple bytecode module with constants pool
     let constants = vec![
         Value::Integer(42),
-        Value::String("hello world".to_string()),
+        Value::String("hello world".into()),
         Value::Float(1.414),
         Value::Boolean(true),
     ];
@@ -31,7 +31,7 @@ fn test_bytecode_module_creation() {
 fn test_push_from_constants_pool() {
     let constants = vec![
         Value::Integer(100),
-        Value::String("test".to_string()),
+        Value::String("test".into()),
     ];
     
     let instructions = vec![
@@ -50,7 +50,7 @@ fn test_push_from_constants_pool() {
     
     // Top should be the string "test"
     let top = vm.stack_top().unwrap();
-    assert_eq!(top, &Value::String("test".to_string()));
+    assert_eq!(top, &Value::String("test".into()));
 }
 
 #[test]
@@ -63,18 +63,16 @@ fn test_constants_pool_bounds_checking() {
     ];
     
     let mut vm = VirtualMachine::new();
-    vm.load_bytecode_module(instructions, constants).unwrap();
-    
-    // Should fail when trying to push from invalid constant index
-    let result = vm.run();
-    assert!(result.is_err());
+    // The verifier rejects the invalid constant index before it can run
+    let result = vm.load_bytecode_module(instructions, constants);
+    assert!(matches!(result, Err(VmError::VerificationFailed(ref e)) if e.pc == 0));
 }
 
 #[test]
 fn test_constants_pool_type_safety() {
     let constants = vec![
         Value::Integer(42),
-        Value::String("hello".to_string()),
+        Value::String("hello".into()),
         Value::Boolean(false),
     ];
     
@@ -139,8 +137,8 @@ fn test_constants_pool_string_interning() {
     // Test that string constants are properly stored and retrieved
     let string_value = "This is a test string with unicode: 🦀";
     let constants = vec![
-        Value::String(string_value.to_string()),
-        Value::String(string_value.to_string()), // Duplicate for testing
+        Value::String(string_value.into()),
+        Value::String(string_value.into()), // Duplicate for testing
     ];
     
     let instructions = vec![
@@ -0,0 +1,114 @@
+use stack_vm_jit::vm::heap::Heap;
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+#[test]
+fn test_bytes_value_basics() {
+    let mut heap = Heap::new();
+
+    let empty = Value::Bytes(heap.allocate_bytes(vec![]).unwrap());
+    let data = Value::Bytes(heap.allocate_bytes(vec![0xDE, 0xAD]).unwrap());
+
+    assert_eq!(empty.type_name(), "bytes");
+    assert!(!empty.is_truthy());
+    assert!(data.is_truthy());
+    assert_eq!(data, Value::Bytes(heap.allocate_bytes(vec![0xDE, 0xAD]).unwrap()));
+}
+
+#[test]
+fn test_new_bytes_write_and_read() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(4))),
+        Instruction::new(Opcode::NewBytes, None),                  // [0, 0, 0, 0]
+        Instruction::new(Opcode::Dup, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(255))),
+        Instruction::new(Opcode::BytesSet, None),                  // buf[2] = 255
+        Instruction::new(Opcode::Dup, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::BytesGet, None),                  // buf[2]
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_size(), 2);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(255));
+}
+
+#[test]
+fn test_bytes_slice_and_length() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(8))),
+        Instruction::new(Opcode::NewBytes, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(5))),
+        Instruction::new(Opcode::BytesSlice, None),                // buf[2..5]
+        Instruction::new(Opcode::BytesLength, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(3));
+}
+
+#[test]
+fn test_bytes_errors() {
+    // Byte values must fit in 0..=255
+    let mut vm = VirtualMachine::new();
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::NewBytes, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(256))),
+        Instruction::new(Opcode::BytesSet, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    assert!(vm.run().is_err());
+
+    // Slice ranges must be within the buffer
+    let mut vm = VirtualMachine::new();
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::NewBytes, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(4))),
+        Instruction::new(Opcode::BytesSlice, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    assert!(vm.run().is_err());
+}
+
+#[test]
+fn test_huge_buffers_fail_without_allocating() {
+    for length in [1 << 62, 1 << 40] {
+        let mut vm = VirtualMachine::builder().with_heap_limit(1 << 20).build();
+        let instructions = vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(length))),
+            Instruction::new(Opcode::NewBytes, None),
+            Instruction::new(Opcode::Halt, None),
+        ];
+        vm.load_bytecode_module(instructions, vec![]).unwrap();
+        assert!(vm.run().unwrap_err().to_string().contains("Out of memory"));
+    }
+
+    // Without a heap limit, lengths no allocator could satisfy still fail
+    let mut vm = VirtualMachine::new();
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(1 << 62))),
+        Instruction::new(Opcode::NewIntArray, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    assert!(vm.run().is_err());
+}
@@ -82,6 +82,12 @@ fn test_call_frame_metadata() {
     // Test function name (optional metadata)
     frame.set_function_name("test_function".to_string());
     assert_eq!(frame.function_name(), Some("test_function"));
+
+    // A shared name is not copied
+    let name: std::sync::Arc<str> = "shared".into();
+    frame.set_function_name(std::sync::Arc::clone(&name));
+    assert_eq!(frame.function_name(), Some("shared"));
+    assert_eq!(std::sync::Arc::strong_count(&name), 2);
 }
 
 #[test]
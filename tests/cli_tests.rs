@@ -0,0 +1,274 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::module::BytecodeModule;
+use std::fs;
+use std::path::PathBuf;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const PROGRAM: &str = ".extern println\n.extern abs\n    PUSH -3\n    CALL abs\n    DUP\n    CALL println\n    PUSH 10\n    MUL\n    HALT\n";
+
+/// Writes `contents` to a file of this name in a directory of the test's own
+fn write_file(test: &str, name: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("stack-vm-jit-cli-{}-{}", test, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn cli(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_stack-vm-jit")).args(args).output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn test_run_assembly_file() {
+    let path = write_file("run", "program.asm", PROGRAM);
+    let output = cli(&["run", path.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    // What the program prints, then the value it leaves
+    assert_eq!(stdout(&output), "3\n30\n");
+    assert!(stderr(&output).is_empty());
+
+    let output = cli(&["run", path.to_str().unwrap(), "--stats"]);
+    assert_eq!(stdout(&output), "3\n30\n");
+    assert!(stderr(&output).contains("Instructions executed: 12"));
+    assert!(stderr(&output).contains("PUSH"));
+}
+
+#[test]
+fn test_run_bytecode_file() {
+    let bytes = Assembler::new().assemble_module(PROGRAM).unwrap().to_bytes().unwrap();
+    let path = write_file("run-bytecode", "program.bc", bytes);
+    let output = cli(&["run", path.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "3\n30\n");
+
+    let path = write_file("run-bytecode", "garbage.bc", "not a module");
+    let output = cli(&["run", path.to_str().unwrap()]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).starts_with(&format!("error: {}: ", path.display())));
+}
+
+#[test]
+fn test_run_reports_errors() {
+    let path = write_file("run-errors", "bad.asm", "    PUSH 1\n    BOGUS\n");
+    let output = cli(&["run", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        stderr(&output),
+        format!("error: {}: line 2, column 5: Invalid opcode: BOGUS\n", path.display())
+    );
+
+    let path = write_file("run-errors", "div.asm", "    PUSH 1\n    PUSH 0\n    DIV\n    HALT\n");
+    let output = cli(&["run", path.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("Division by zero"));
+    assert!(stderr(&output).contains(&format!("{}:3", path.display())));
+
+    let output = cli(&["run"]);
+    assert!(stderr(&output).starts_with("error: Usage: stack-vm-jit run"));
+    let output = cli(&["run", "x.asm", "--bogus"]);
+    assert_eq!(stderr(&output), "error: Unknown option --bogus\n");
+}
+
+#[test]
+fn test_assemble_to_bytecode() {
+    let source = write_file("asm", "div.asm", "    PUSH 1\n    PUSH 0\n    DIV\n    HALT\n");
+    let output = cli(&["asm", source.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).is_empty());
+
+    // Written next to the source, without line tables
+    let bytecode = source.with_extension("bc");
+    let module = BytecodeModule::from_bytes(&fs::read(&bytecode).unwrap()).unwrap();
+    assert_eq!(module.instructions.len(), 4);
+    assert!(module.debug_info.lines.is_empty());
+    let output = cli(&["run", bytecode.to_str().unwrap()]);
+    assert!(stderr(&output).contains("Execution error at pc 2"));
+
+    let with_debug = source.with_file_name("debug.bc");
+    let output = cli(&["asm", source.to_str().unwrap(), "-o", with_debug.to_str().unwrap(), "--emit-debug"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let module = BytecodeModule::from_bytes(&fs::read(&with_debug).unwrap()).unwrap();
+    assert_eq!(module.debug_info.lines.line_at(2), Some(3));
+    let output = cli(&["run", with_debug.to_str().unwrap()]);
+    assert!(stderr(&output).contains(&format!("Execution error at {}:3", source.display())));
+}
+
+#[test]
+fn test_assemble_reports_errors() {
+    let source = write_file("asm-errors", "bad.asm", "    PUSH 1\n    JMP .nowhere\n");
+    let output = cli(&["asm", source.to_str().unwrap(), "-o"]);
+    assert_eq!(stderr(&output), "error: -o needs a value\n");
+
+    let output = cli(&["asm", source.to_str().unwrap()]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with(&format!("error: {}: line 2, column ", source.display())));
+    assert!(stderr(&output).contains("Unknown label"));
+    assert!(!source.with_extension("bc").exists());
+
+    let output = cli(&["asm", source.with_extension("bc").to_str().unwrap()]);
+    assert!(stderr(&output).contains("is not an assembly (.asm) file"));
+}
+
+#[test]
+fn test_disassemble_bytecode() {
+    let source = ".const LIMIT 10\n.export double\n    PUSH 4\n    CALL double\n    LDC LIMIT\n    LT\n    JF .big\n    PUSH \"small\"\n    HALT\n.big:\n    PUSH \"big\"\n    HALT\n.func double arity=1 returns=1\n    LOAD 0\n    PUSH 2\n    MUL\n    RET\n.endfunc\n";
+    let path = write_file("disasm", "names.asm", source);
+    assert!(cli(&["asm", path.to_str().unwrap(), "--emit-debug"]).status.success());
+    let bytecode = path.with_extension("bc");
+
+    let output = cli(&["disasm", bytecode.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert!(text.starts_with(".const LIMIT 10\n"));
+    assert!(text.contains("    JF .big\n"));
+    assert!(text.contains(".func double arity=1 locals=1 returns=1\n"));
+    assert!(!text.contains("; "));
+
+    // The listing assembles back into the same program
+    let module = BytecodeModule::from_bytes(&fs::read(&bytecode).unwrap()).unwrap();
+    let reassembled = Assembler::new().assemble_module(&text).unwrap();
+    let code = |module: &BytecodeModule| {
+        module.instructions.iter().map(|i| (i.opcode(), i.operand().cloned())).collect::<Vec<_>>()
+    };
+    assert_eq!(code(&reassembled), code(&module));
+    assert_eq!(reassembled.constants, module.constants);
+
+    let output = cli(&["disasm", bytecode.to_str().unwrap(), "--lines"]);
+    assert!(stdout(&output).contains(&format!("    ; {}:4\n    CALL double\n", path.display())));
+}
+
+#[test]
+fn test_repl_reads_standard_input() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stack-vm-jit"))
+        .args(["repl", "--expr"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"(1 + 2) * 3\n:stack\n:asm\nPUSH \"hi\"\nPRINT\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "Stack VM REPL (:help for commands)\n> 9\n>    0: 9\n> Reading assembly\n> hi\n> hi\n9\n> "
+    );
+
+    assert!(stderr(&cli(&["repl", "program.asm"])).starts_with("error: Usage: stack-vm-jit repl"));
+}
+
+#[test]
+fn test_debug_reads_commands() {
+    let path = write_file("debug", "loop.asm", "    PUSH 0\n.loop:\n    PUSH 1\n    ADD\n    DUP\n    PUSH 3\n    LT\n    JT .loop\n    HALT\n");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_stack-vm-jit"))
+        .args(["debug", path.to_str().unwrap()])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"break .loop\nc\nc\nstack\ndelete .loop\nc\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let text = stdout(&output);
+    assert!(text.contains("(hit 2)\n"));
+    assert!(text.contains("(debug)    0: 1\n"));
+    assert!(text.ends_with("(debug) Program halted with 3 on the stack\n(debug) "));
+}
+
+#[test]
+fn test_run_with_trace() {
+    let path = write_file("trace", "add.asm", "    PUSH 5\n    PUSH 3\n    ADD\n    HALT\n");
+    let output = cli(&["run", path.to_str().unwrap(), "--trace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    // The trace keeps to standard error
+    assert_eq!(stdout(&output), "8\n");
+    let trace = stderr(&output);
+    assert_eq!(trace.lines().count(), 6);
+    assert!(trace.contains("    2 |     2 | ADD          |              | 8\n"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_run_with_json_statistics() {
+    let path = write_file("stats-json", "loop.asm", "    PUSH 0\n.loop:\n    PUSH 1\n    ADD\n    DUP\n    PUSH 20000\n    LT\n    JT .loop\n    HALT\n");
+    let output = cli(&["run", path.to_str().unwrap(), "--stats-json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let printed = stdout(&output);
+    let (value, json) = printed.split_once('\n').unwrap();
+    assert_eq!(value, "20000");
+    let statistics: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(statistics["instructions"], 120_001);
+    assert_eq!(statistics["opcodes"]["JT"], 20_000);
+    assert!(statistics["run_time_seconds"].as_f64().unwrap() > 0.0);
+    assert!(statistics["jit"].is_null());
+
+    let output = cli(&["run", path.to_str().unwrap(), "--stats-json", "--jit"]);
+    assert!(stderr(&output).is_empty());
+    let printed = stdout(&output);
+    let (_, json) = printed.split_once('\n').unwrap();
+    let statistics: serde_json::Value = serde_json::from_str(json).unwrap();
+    assert_eq!(statistics["jit"]["hot_loops"], serde_json::json!([1]));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_profile_and_report() {
+    let path = write_file("profile", "loop.asm", "    PUSH 0\n.loop:\n    PUSH 1\n    ADD\n    DUP\n    PUSH 1000\n    LT\n    JT .loop\n    HALT\n");
+    let output = cli(&["profile", path.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "1000\n");
+
+    let profile = path.with_extension("profile.json");
+    let output = cli(&["report", profile.to_str().unwrap(), "--top", "2"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert!(text.starts_with("6002 instructions executed at 8 addresses\n"));
+    assert_eq!(text.lines().count(), 4);
+    assert!(text.contains("     1         1000   16.7%  PUSH\n"));
+
+    // With the program, the instructions read as they were written
+    let output = cli(&["report", profile.to_str().unwrap(), "--program", path.to_str().unwrap()]);
+    let text = stdout(&output);
+    assert!(text.contains("     2         1000   16.7%  ADD                      ; line 4\n"));
+    assert!(text.ends_with("     7            1    0.0%  HALT                     ; line 9\n"));
+
+    let elsewhere = path.with_file_name("elsewhere.json");
+    let output = cli(&["profile", path.to_str().unwrap(), "--out", elsewhere.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let output = cli(&["report", elsewhere.to_str().unwrap()]);
+    assert!(stdout(&output).starts_with("6002 instructions executed"));
+
+    let output = cli(&["report", path.to_str().unwrap()]);
+    assert!(stderr(&output).contains("Failed to parse profile data"));
+    let output = cli(&["report", profile.to_str().unwrap(), "--top", "all"]);
+    assert_eq!(stderr(&output), "error: --top needs a number, not all\n");
+}
+
+#[test]
+fn test_benchmark_one_workload() {
+    let output = cli(&["benchmark", "--runs", "1", "--workload", "fib"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let text = stdout(&output);
+    assert!(text.starts_with("fib        recursive fib(22)\n\nWorkload "));
+    assert_eq!(text.lines().count(), 6);
+    assert!(text.lines().any(|line| line.starts_with("fib        optimized")));
+
+    let output = cli(&["benchmark", "--runs", "1", "--workload", "fib", "--trace"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("\nTimings include writing the trace\nWorkload "));
+    assert!(stderr(&output).starts_with("fib on the interpreter tier, run 1\n Step |"));
+
+    let output = cli(&["benchmark", "--workload", "bogus"]);
+    assert_eq!(stderr(&output), "error: No workload named bogus\n");
+    let output = cli(&["benchmark", "--runs", "0"]);
+    assert_eq!(stderr(&output), "error: --runs needs a positive number, not 0\n");
+}
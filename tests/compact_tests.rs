@@ -0,0 +1,82 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::compact::{pool_literals, CompactCode};
+use stack_vm_jit::vm::instruction::{EncodingError, Instruction, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+fn assert_same(left: &[Instruction], right: &[Instruction]) {
+    assert_eq!(left.len(), right.len());
+    for (a, b) in left.iter().zip(right) {
+        assert_eq!(a.opcode(), b.opcode());
+        assert_eq!(a.operand(), b.operand());
+    }
+}
+
+#[test]
+fn test_compact_code_roundtrip() {
+    let program = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(70_000))),
+        Instruction::new(Opcode::Add, None),
+        Instruction::new(Opcode::JumpIfFalse, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    let code = CompactCode::encode(&program).unwrap();
+
+    assert_eq!(code.len(), 5);
+    assert_eq!(code.byte_len(), 2 + 6 + 1 + 2 + 1);
+    assert_same(&code.to_instructions(), &program);
+
+    // Instructions are fetched in place, including behind a Wide prefix
+    assert_eq!(code.opcode_at(1), Some(Opcode::Push));
+    assert_eq!(code.fetch(1).unwrap().operand(), Some(&Value::Integer(70_000)));
+    assert_eq!(code.opcode_at(2), Some(Opcode::Add));
+    assert!(code.fetch(5).is_none());
+
+    let reloaded = CompactCode::from_bytes(code.as_bytes().to_vec()).unwrap();
+    assert_eq!(reloaded, code);
+}
+
+#[test]
+fn test_compact_code_is_smaller_than_instructions() {
+    let (instructions, _) = Assembler::new()
+        .assemble("PUSH 1\nPUSH 2\nADD\nDUP\nMUL\nPOP\nHALT")
+        .unwrap();
+    let code = CompactCode::encode(&instructions).unwrap();
+    assert!(code.byte_len() < instructions.len() * std::mem::size_of::<Instruction>());
+}
+
+#[test]
+fn test_compact_code_rejects_malformed_streams() {
+    assert_eq!(
+        CompactCode::from_bytes(vec![Opcode::Push as u8]),
+        Err(EncodingError::UnexpectedEnd(1))
+    );
+    assert_eq!(
+        CompactCode::from_bytes(vec![Opcode::Halt as u8, 0xEE]),
+        Err(EncodingError::UnknownOpcode(0xEE))
+    );
+
+    let literal = vec![Instruction::new(Opcode::Push, Some(Value::String("hi".into())))];
+    assert!(CompactCode::encode(&literal).is_err());
+}
+
+#[test]
+fn test_pooled_literals_encode_and_run() {
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::String("a".into()))),
+        Instruction::new(Opcode::Push, Some(Value::String("b".into()))),
+        Instruction::new(Opcode::StrConcat, None),
+        Instruction::new(Opcode::Push, Some(Value::String("a".into()))),
+        Instruction::new(Opcode::StrConcat, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    let (pooled, constants) = pool_literals(&instructions, &[]);
+    assert_eq!(constants, vec![Value::String("a".into()), Value::String("b".into())]);
+
+    let code = CompactCode::encode(&pooled).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(code.to_instructions(), constants).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::String("aba".into()));
+}
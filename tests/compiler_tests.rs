@@ -0,0 +1,376 @@
+use stack_vm_jit::vm::assembler::SimpleCompiler;
+use stack_vm_jit::vm::instruction::Opcode;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+
+fn run_program(source: &str) -> VirtualMachine {
+    let module = SimpleCompiler::new().compile_program(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn test_let_and_variable_references() {
+    let vm = run_program("let x = 6; let y = x * 7; y");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(42));
+    assert_eq!(vm.stack_size(), 1);
+}
+
+#[test]
+fn test_assignment_updates_variable() {
+    let source = "
+        let total = 1;
+        total = total + 10;
+        total = total * 2;
+        total
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(22));
+}
+
+#[test]
+fn test_block_scopes_and_shadowing() {
+    let source = "
+        let x = 1;
+        {
+            let x = 100;
+            let y = x + 1;
+            x = y;
+        };
+        let z = 5;
+        x + z
+    ";
+    let vm = run_program(source);
+    // The inner x shadowed the outer one and went out of scope with the block
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(6));
+}
+
+#[test]
+fn test_block_slots_are_reused() {
+    let module = SimpleCompiler::new()
+        .compile_program("{ let a = 1; let b = 2; }; { let c = 3; }; let d = 4; d")
+        .unwrap();
+    assert_eq!(module.functions[0].locals, 2);
+}
+
+#[test]
+fn test_intermediate_expression_statements_are_discarded() {
+    let vm = run_program("1 + 1; 2 * 3; 10");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(10));
+    assert_eq!(vm.stack_size(), 1);
+}
+
+#[test]
+fn test_program_without_result() {
+    let vm = run_program("let x = 1;");
+    assert_eq!(vm.stack_size(), 0);
+    assert_eq!(vm.call_depth(), 0);
+}
+
+#[test]
+fn test_while_loop() {
+    // Sum 1..=10 counting down, since the loop condition is truthiness
+    let source = "
+        let n = 10;
+        let sum = 0;
+        while n {
+            sum = sum + n;
+            n = n - 1;
+        }
+        sum
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(55));
+    assert_eq!(vm.stack_size(), 1);
+}
+
+#[test]
+fn test_for_loop_with_break_and_continue() {
+    // `continue` in a for loop still runs the step
+    let source = "
+        let visited = 0;
+        let never = 0;
+        for (let i = 3; i; i = i - 1) {
+            visited = visited + 1;
+            continue;
+            never = never + 1;
+        }
+        visited * 10 + never
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(30));
+
+    // `break` and `continue` apply to the innermost loop
+    let source = "
+        let visited = 0;
+        let skipped = 0;
+        for (let i = 6; i; i = i - 1) {
+            let odd = i % 2;
+            while odd {
+                skipped = skipped + 1;
+                odd = 0;
+                continue;
+            }
+            visited = visited + 1;
+        }
+        for (;;) {
+            break;
+        }
+        visited * 10 + skipped
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(63));
+    assert_eq!(vm.stack_size(), 1);
+}
+
+#[test]
+fn test_loops_are_backward_jumps() {
+    use stack_vm_jit::vm::instruction::Opcode;
+
+    let module = SimpleCompiler::new()
+        .compile_program("let n = 3; while n { n = n - 1; }")
+        .unwrap();
+    let backward = module
+        .instructions
+        .iter()
+        .enumerate()
+        .filter(|(pc, instruction)| {
+            instruction.opcode() == Opcode::Jump
+                && matches!(instruction.operand(), Some(Value::Integer(target)) if (*target as usize) < *pc)
+        })
+        .count();
+    assert_eq!(backward, 1);
+}
+
+#[test]
+fn test_comparison_and_boolean_operators() {
+    let cases = [
+        ("1 < 2", true),
+        ("2 <= 2", true),
+        ("3 > 4", false),
+        ("4 >= 5", false),
+        ("1 + 1 == 2", true),
+        ("2 * 3 != 6", false),
+        ("1 < 2 && 3 < 2", false),
+        ("1 < 2 || 3 < 2", true),
+        ("!(1 < 2)", false),
+        ("!false && true", true),
+        // && binds tighter than ||
+        ("true || false && false", true),
+        ("(true || false) && false", false),
+        ("!1 == false", true),
+    ];
+    for (source, expected) in cases {
+        let vm = run_program(source);
+        assert_eq!(*vm.stack_top().unwrap(), Value::Boolean(expected), "{}", source);
+    }
+}
+
+#[test]
+fn test_loop_with_comparison_condition() {
+    let source = "
+        let product = 1;
+        for (let i = 1; i <= 5; i = i + 1) {
+            product = product * i;
+        }
+        let n = 0;
+        while n * n < 50 && product != 0 {
+            n = n + 1;
+        }
+        product + n
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(128));
+}
+
+#[test]
+fn test_unary_operators() {
+    let vm = run_program("let x = 5; -x * 2 + - -3");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(-7));
+
+    let vm = run_program("let a = 1; let b = 2; !(a < b) || !!(a == 1)");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Boolean(true));
+
+    let vm = run_program("let f = 1.5; -f");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Float(-1.5));
+}
+
+#[test]
+fn test_negative_loop_bound() {
+    let source = "
+        let count = 0;
+        for (let i = -3; i < -(-3); i = i + 1) { count = count + 1; }
+        count
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(6));
+}
+
+#[test]
+fn test_string_concatenation() {
+    let source = r#"
+        let greeting = "hello";
+        let name = "world";
+        greeting + ", " + name + "!"
+    "#;
+    let vm = run_program(source);
+    assert_eq!(vm.stack_top().unwrap().as_str(), Some("hello, world!"));
+}
+
+#[test]
+fn test_strings_with_integer_literals() {
+    // Integers stay literal values alongside the pooled strings
+    let source = r#"
+        let line = "";
+        for (let i = 1; i <= 3; i = i + 1) { line = line + i + ";"; }
+        line + "\tdone"
+    "#;
+    let vm = run_program(source);
+    assert_eq!(vm.stack_top().unwrap().as_str(), Some("1;2;3;\tdone"));
+}
+
+#[test]
+fn test_array_literals_and_indexing() {
+    let vm = run_program("let a = [10, 20, 30,]; a[1] = a[0] + a[2]; a[1]");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(40));
+
+    let vm = run_program("let grid = [[1, 2], [3, 4]]; grid[1][0] * -grid[0][1]");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(-6));
+}
+
+#[test]
+fn test_bubble_sort() {
+    let source = "
+        let a = [5, 3, 9, 1, 7, 2];
+        let n = 6;
+        for (let i = 0; i < n; i = i + 1) {
+            for (let j = 0; j < n - 1 - i; j = j + 1) {
+                // No `if` yet: the swap leaves the condition false
+                while a[j] > a[j + 1] {
+                    let t = a[j];
+                    a[j] = a[j + 1];
+                    a[j + 1] = t;
+                }
+            }
+        }
+        a[0] * 100000 + a[1] * 10000 + a[2] * 1000 + a[3] * 100 + a[4] * 10 + a[5]
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(123579));
+}
+
+#[test]
+fn test_sieve_of_eratosthenes() {
+    let source = "
+        let n = 30;
+        let composite = [false, false, false, false, false, false, false, false, false, false,
+                         false, false, false, false, false, false, false, false, false, false,
+                         false, false, false, false, false, false, false, false, false, false];
+        let count = 0;
+        for (let i = 2; i < n; i = i + 1) {
+            while !composite[i] {
+                count = count + 1;
+                for (let j = i * i; j < n; j = j + i) { composite[j] = true; }
+                break;
+            }
+        }
+        count
+    ";
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(10));
+}
+
+fn instruction_count(compiler: SimpleCompiler, source: &str) -> usize {
+    let mut compiler = compiler;
+    compiler.compile_program(source).unwrap().instructions.len()
+}
+
+#[test]
+fn test_constant_folding_shrinks_code() {
+    let source = "let x = 2 * 3 + 4; x * (10 - 8)";
+    let folded = instruction_count(SimpleCompiler::new(), source);
+    let unfolded = instruction_count(SimpleCompiler::new().with_constant_folding(false), source);
+    assert_eq!(folded, instruction_count(SimpleCompiler::new(), "let x = 10; x * 2"));
+    assert_eq!(unfolded - folded, 6);
+
+    let vm = run_program(source);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(20));
+}
+
+#[test]
+fn test_constant_conditions_are_resolved() {
+    let empty = instruction_count(SimpleCompiler::new(), "");
+    assert_eq!(instruction_count(SimpleCompiler::new(), "while 1 > 2 { 1 + 1; }"), empty);
+
+    // A constantly true condition is never tested
+    let source = "let n = 0; while 2 > 1 { n = n + 1; continue; }";
+    let module = SimpleCompiler::new().compile_program(source).unwrap();
+    assert!(module
+        .instructions
+        .iter()
+        .all(|instruction| instruction.opcode() != Opcode::JumpIfFalse));
+
+    let vm = run_program("let n = 0; while !false { n = n + 1; while n < 5 { n = n + 1; } break; } n");
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(5));
+}
+
+#[test]
+fn test_builtin_calls() {
+    let source = r#"
+        let words = ["stack", "machine"];
+        let first = words[0];
+        substring(first, 1, len(words) + 1) + char_at(words[1], 0)
+    "#;
+    let vm = run_program(source);
+    assert_eq!(vm.stack_top().unwrap().as_str(), Some("tam"));
+}
+
+#[test]
+fn test_semantic_errors_are_reported_before_codegen() {
+    let error = SimpleCompiler::new()
+        .compile_program("let a = [1, 2];\nlen()")
+        .unwrap_err();
+    assert!(error.to_string().contains("`len` takes 1 argument, but 0 were given"));
+
+    // Dead code is still checked before folding removes it
+    let error = SimpleCompiler::new()
+        .compile_program("while false { missing = 1; }")
+        .unwrap_err();
+    assert!(error.to_string().contains("Unknown variable: missing"));
+}
+
+#[test]
+fn test_runtime_errors_report_source_lines() {
+    let source = "let a = 10;\nlet b = a - 10;\nlet c = a / b;\nc";
+    let module = SimpleCompiler::new().compile_program(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+
+    match vm.run() {
+        Err(VmError::ExecutionErrorAt { pc, line, .. }) => {
+            assert_eq!(line, Some(3));
+            assert_eq!(vm.source_line(pc), Some(3));
+        }
+        other => panic!("expected a located error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_profile_counts_by_source_line() {
+    let source = "let i = 0;\nwhile i < 5 {\n    i = i + 1;\n}\ni";
+    let module = SimpleCompiler::new().compile_program(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.enable_profiling();
+    vm.run().unwrap();
+
+    let counts = vm
+        .get_profiler()
+        .unwrap()
+        .line_counts(&vm.debug_info().lines);
+    assert!(counts[&3] > counts[&1]);
+    assert!(counts[&2] > counts[&3]);
+    assert!(counts.contains_key(&5));
+}
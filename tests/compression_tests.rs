@@ -0,0 +1,80 @@
+use stack_vm_jit::vm::module::{BytecodeModule, ModuleError};
+
+#[cfg(feature = "compression")]
+use stack_vm_jit::vm::assembler::Assembler;
+#[cfg(feature = "compression")]
+use stack_vm_jit::vm::loader::ModuleLoader;
+#[cfg(feature = "compression")]
+use stack_vm_jit::vm::runtime::VirtualMachine;
+#[cfg(feature = "compression")]
+use stack_vm_jit::vm::types::Value;
+
+/// A large program with plenty of repetition for zstd to find
+#[cfg(feature = "compression")]
+fn large_module() -> BytecodeModule {
+    let mut source = String::from("    PUSH 0\n");
+    for _ in 0..500 {
+        source.push_str("    PUSH 1\n    ADD\n    DUP\n    POP\n");
+    }
+    source.push_str("    HALT\n");
+    // Only the constants and code are compressed, so leave out the line table
+    let module = Assembler::new().assemble_module(&source).unwrap();
+    BytecodeModule::new(module.instructions, module.constants)
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_compressed_roundtrip() {
+    let module = large_module();
+    let plain = module.to_bytes().unwrap();
+    let compressed = module.to_compressed_bytes(3).unwrap();
+    assert!(compressed.len() < plain.len() / 4);
+
+    let restored = BytecodeModule::from_bytes(&compressed).unwrap();
+    assert_eq!(restored.to_bytes().unwrap(), plain);
+
+    let streamed = BytecodeModule::read_from(compressed.as_slice()).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(streamed).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(500));
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_small_sections_stay_uncompressed() {
+    let module = Assembler::new().assemble_module("    PUSH 1\n    HALT").unwrap();
+    assert_eq!(module.to_compressed_bytes(3).unwrap(), module.to_bytes().unwrap());
+}
+
+#[cfg(feature = "compression")]
+#[test]
+fn test_inflated_sections_respect_limits() {
+    let compressed = large_module().to_compressed_bytes(3).unwrap();
+    // Every section on disk is small, but the code inflates past the limit
+    assert!(matches!(
+        ModuleLoader::new().with_max_section_len(512).load(compressed.as_slice()),
+        Err(ModuleError::TooLarge { limit: 512, .. })
+    ));
+
+    let mut corrupt = compressed.clone();
+    let last = corrupt.len() - 1;
+    corrupt[last] ^= 0xFF;
+    assert!(matches!(
+        BytecodeModule::from_bytes(&corrupt),
+        Err(ModuleError::InvalidContainer(_))
+    ));
+}
+
+#[test]
+fn test_nested_compressed_sections_are_rejected() {
+    let mut bytes = b"SVMB\x01\x00".to_vec();
+    bytes.push(7);
+    bytes.extend_from_slice(&5u32.to_le_bytes());
+    bytes.push(7);
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    assert!(matches!(
+        BytecodeModule::from_bytes(&bytes),
+        Err(ModuleError::InvalidContainer(_))
+    ));
+}
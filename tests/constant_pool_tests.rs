@@ -0,0 +1,119 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::linker::Linker;
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::{Value, VmString};
+
+fn operands(module: &BytecodeModule) -> Vec<Option<Value>> {
+    module
+        .instructions
+        .iter()
+        .map(|instruction| instruction.operand().cloned())
+        .collect()
+}
+
+#[test]
+fn test_dedup_remaps_indices() {
+    let mut module = BytecodeModule::new(
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+            Instruction::new(Opcode::LoadConstant, Some(Value::Integer(3))),
+            Instruction::new(Opcode::StrConcat, None),
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Value::String("hi".into()),
+            Value::Integer(5),
+            Value::String("hi".into()),
+            Value::String("hi".into()),
+        ],
+    );
+    module.symbols.constants.insert("LAST".to_string(), 3);
+
+    assert_eq!(module.dedup_constants(), 2);
+    assert_eq!(module.constants, vec![Value::String("hi".into()), Value::Integer(5)]);
+    assert_eq!(
+        operands(&module)[..4],
+        [
+            Some(Value::Integer(0)),
+            Some(Value::Integer(0)),
+            None,
+            Some(Value::Integer(1)),
+        ]
+    );
+    assert_eq!(module.symbols.constants["LAST"], 0);
+    assert_eq!(module.dedup_constants(), 0);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(5));
+}
+
+#[test]
+fn test_dedup_keeps_distinct_values_apart() {
+    let constants = vec![
+        Value::Integer(1),
+        Value::Float(1.0),
+        Value::Float(0.0),
+        Value::Float(-0.0),
+        Value::String("1".into()),
+        Value::Char('1'),
+    ];
+    let mut module = BytecodeModule::new(vec![Instruction::new(Opcode::Halt, None)], constants.clone());
+    assert_eq!(module.dedup_constants(), 0);
+    assert_eq!(module.constants.len(), constants.len());
+    assert!(module.constants[3] == Value::Float(-0.0));
+}
+
+#[test]
+fn test_deduplicated_strings_share_a_buffer() {
+    let module = Assembler::new()
+        .assemble_module(
+            r#"
+            .string A "shared"
+            .string B "shared"
+                LDC A
+                LDC B
+                HALT
+            "#,
+        )
+        .unwrap();
+    assert_eq!(module.constants.len(), 1);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    // Both loads hand out the single pooled string
+    match (vm.stack_top().unwrap(), vm.get_constant(0).unwrap()) {
+        (Value::String(loaded), Value::String(pooled)) => assert!(VmString::ptr_eq(loaded, pooled)),
+        other => panic!("expected strings, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_linked_and_loaded_modules_are_deduplicated() {
+    let first = Assembler::new()
+        .assemble_module(".const TEN 10\n    PUSH TEN\n    HALT")
+        .unwrap();
+    let second = Assembler::new()
+        .assemble_module(".const ALSO_TEN 10\n.func ten returns=1\n    PUSH ALSO_TEN\n    RET\n.endfunc")
+        .unwrap();
+    let linked = Linker::new().add(first).add(second).link().unwrap();
+    assert_eq!(linked.constants, vec![Value::Integer(10)]);
+    assert_eq!(linked.symbols.constants["ALSO_TEN"], 0);
+
+    // Containers written by other tools may repeat constants
+    let module = BytecodeModule::new(
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![Value::String("x".into()), Value::String("x".into())],
+    );
+    let loaded = BytecodeModule::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(loaded.constants.len(), 1);
+    assert_eq!(loaded.instructions[0].operand(), Some(&Value::Integer(0)));
+}
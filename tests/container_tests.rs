@@ -0,0 +1,162 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use sha2::{Digest, Sha256};
+use stack_vm_jit::vm::container::{CONTAINER_FORMAT_VERSION, CONTAINER_MAGIC, DIGEST_LEN};
+use stack_vm_jit::vm::module::{BytecodeModule, FunctionInfo, ModuleError};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+const SOURCE: &str = r#"
+.string GREETING "hello there"
+.data PRIMES [2, 3, 5]
+    PUSH 2.5
+    PUSH 4.0
+    CALL scale
+    LDC GREETING
+    POP
+    LDC PRIMES
+    POP
+    HALT
+.func scale arity=2 returns=1
+    LOAD 0
+    LOAD 1
+    MUL
+    RET
+.endfunc
+"#;
+
+fn run(module: BytecodeModule) -> Value {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    vm.stack_top().unwrap().clone()
+}
+
+#[test]
+fn test_container_roundtrip() {
+    let module = Assembler::new().assemble_module(SOURCE).unwrap();
+    let bytes = module.to_bytes().unwrap();
+    assert_eq!(&bytes[..4], &CONTAINER_MAGIC);
+    assert_eq!(u16::from_le_bytes([bytes[4], bytes[5]]), CONTAINER_FORMAT_VERSION);
+
+    let restored = BytecodeModule::from_bytes(&bytes).unwrap();
+    assert_eq!(restored.functions, module.functions);
+    assert_eq!(restored.symbols, module.symbols);
+    assert_eq!(restored.debug_info, module.debug_info);
+    assert_eq!(restored.constants[0], Value::String("hello there".into()));
+    // Float literals were moved into the pool
+    assert_eq!(restored.constants.len(), 4);
+    assert_eq!(restored.instructions.len(), module.instructions.len());
+
+    assert_eq!(run(restored), Value::Float(10.0));
+}
+
+#[test]
+fn test_literal_only_programs_stay_without_a_pool() {
+    let module = Assembler::new()
+        .assemble_module("PUSH 6\nPUSH 7\nMUL\nHALT")
+        .unwrap();
+    let restored = BytecodeModule::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert!(restored.constants.is_empty());
+    assert_eq!(run(restored), Value::Integer(42));
+
+    // A negative literal cannot be an index, so the pool takes all literals
+    let module = Assembler::new()
+        .assemble_module("PUSH -6\nPUSH 7\nMUL\nHALT")
+        .unwrap();
+    let restored = BytecodeModule::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(restored.constants, vec![Value::Integer(-6), Value::Integer(7)]);
+    assert_eq!(run(restored), Value::Integer(-42));
+}
+
+#[test]
+fn test_unknown_sections_are_skipped() {
+    let module = Assembler::new().assemble_module("PUSH 1\nHALT").unwrap();
+    let mut bytes = module.to_bytes().unwrap();
+    // Before the end section and, as a newer writer would, in the digest
+    let end = bytes.split_off(bytes.len() - 5);
+    bytes.extend_from_slice(&[0xEE, 3, 0, 0, 0, 1, 2, 3]);
+    let digest = Sha256::digest(&bytes[6 + DIGEST_LEN..]);
+    bytes[6..6 + DIGEST_LEN].copy_from_slice(&digest);
+    bytes.extend_from_slice(&end);
+    let restored = BytecodeModule::from_bytes(&bytes).unwrap();
+    assert_eq!(run(restored), Value::Integer(1));
+}
+
+#[test]
+fn test_malformed_containers() {
+    let module = Assembler::new().assemble_module(SOURCE).unwrap();
+    let bytes = module.to_bytes().unwrap();
+
+    assert!(matches!(
+        BytecodeModule::from_bytes(b"NOPE\x01\x00"),
+        Err(ModuleError::InvalidContainer(_))
+    ));
+
+    let mut future = bytes.clone();
+    future[4] = 99;
+    assert!(matches!(
+        BytecodeModule::from_bytes(&future),
+        Err(ModuleError::UnsupportedContainerVersion(99))
+    ));
+
+    for len in [5, 10, bytes.len() / 2, bytes.len() - 1] {
+        assert!(
+            matches!(
+                BytecodeModule::from_bytes(&bytes[..len]),
+                Err(ModuleError::InvalidContainer(_))
+            ),
+            "truncated to {} bytes",
+            len
+        );
+    }
+
+    // Nothing may follow the end section, and it must be there
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(matches!(
+        BytecodeModule::from_bytes(&trailing),
+        Err(ModuleError::InvalidContainer(ref message)) if message == "data after the end section"
+    ));
+    assert!(matches!(
+        BytecodeModule::from_bytes(&bytes[..bytes.len() - 5]),
+        Err(ModuleError::InvalidContainer(ref message)) if message == "missing end section"
+    ));
+
+    // Magic and version, but no sections
+    let mut empty = CONTAINER_MAGIC.to_vec();
+    empty.extend_from_slice(&CONTAINER_FORMAT_VERSION.to_le_bytes());
+    let error = BytecodeModule::from_bytes(&empty).unwrap_err();
+    assert!(error.to_string().contains("missing"));
+}
+
+#[test]
+fn test_assembled_bytes_load_and_run() {
+    let source = r#"
+        PUSH 6
+        PUSH 7
+        CALL mul
+        HALT
+    .func mul arity=2
+        LOAD 0
+        LOAD 1
+        MUL
+        RET
+    .endfunc
+    "#;
+    let bytes = Assembler::new().assemble_to_bytes(source).unwrap();
+    assert!(bytes.starts_with(&CONTAINER_MAGIC));
+
+    let module = BytecodeModule::from_bytes(&bytes).unwrap();
+    assert_eq!(module.instructions.len(), 8);
+    assert_eq!(module.functions, vec![FunctionInfo::new("mul", 4, 2, 2)]);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(42));
+
+    assert!(matches!(
+        Assembler::new().assemble_to_bytes("BOGUS"),
+        Err(ModuleError::Assembly(_))
+    ));
+}
@@ -0,0 +1,94 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::coverage::CoverageCollector;
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine};
+use stack_vm_jit::vm::types::Value;
+
+/// Halves its argument, or fails on odd ones through a path the even runs
+/// never take
+const PROGRAM: &str = "    CALL main\n    HALT\n.func half arity=1 returns=1\n    LOAD 0\n    PUSH 2\n    MOD\n    JT .odd\n    LOAD 0\n    PUSH 2\n    DIV\n    RET\n.odd:\n    PUSH 0\n    RET\n.endfunc\n.func main returns=1\n    PUSH 4\n    CALL half\n    RET\n.endfunc\n.func unused\n    RET\n.endfunc";
+
+fn load(source: &str) -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    vm
+}
+
+#[test]
+fn test_coverage_reports_unexecuted_regions() {
+    let mut vm = load(PROGRAM);
+    let coverage = CoverageCollector::new();
+    coverage.attach(&mut vm);
+    assert_eq!(vm.run().unwrap(), RunOutcome::Halted);
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(2));
+
+    assert_eq!(coverage.count(0), 1);
+    assert_eq!(coverage.count(10), 0);
+    let report = coverage.report(&vm);
+    assert_eq!((report.instructions, report.covered), (16, 13));
+    assert_eq!(report.counts.len(), 16);
+    let names: Vec<&str> = report.functions.iter().map(|function| function.name.as_str()).collect();
+    assert_eq!(names, ["(top level)", "half", "main", "unused"]);
+    assert_eq!((report.functions[1].covered, report.functions[1].instructions), (8, 10));
+
+    assert_eq!(report.uncovered.len(), 2);
+    let odd = &report.uncovered[0];
+    assert_eq!((odd.start, odd.end), (10, 11));
+    assert_eq!(odd.function.as_deref(), Some("half"));
+    assert_eq!((odd.first_line, odd.last_line), (Some(13), Some(14)));
+    assert_eq!(odd.code, ["PUSH 0", "RET"]);
+    assert_eq!(report.uncovered[1].function.as_deref(), Some("unused"));
+
+    let text = report.to_string();
+    assert!(text.starts_with("Coverage: 13 of 16 instructions (81.2%)"));
+    assert!(text.contains("pc 10-11 (lines 13-14) in half: PUSH 0; RET"));
+    assert!(text.contains("pc 15 (line 22) in unused: RET"));
+}
+
+#[test]
+fn test_coverage_accumulates_across_runs() {
+    let coverage = CoverageCollector::new();
+    let mut first = load(PROGRAM);
+    coverage.attach(&mut first);
+    first.run().unwrap();
+    let mut second = load(PROGRAM);
+    coverage.clone().attach(&mut second);
+    assert_eq!(second.call_function("half", &[Value::Integer(3)]).unwrap(), Value::Integer(0));
+
+    // Both runs entered half, only the second took the odd path
+    assert_eq!(coverage.count(2), 2);
+    assert_eq!(coverage.count(10), 1);
+    let report = coverage.report(&second);
+    assert_eq!(report.covered, 15);
+    assert_eq!(report.uncovered.len(), 1);
+
+    coverage.clear();
+    assert_eq!(coverage.count(2), 0);
+    assert_eq!(coverage.report(&second).covered, 0);
+}
+
+#[test]
+fn test_coverage_of_program_without_functions() {
+    let mut vm = load("    PUSH 1\n    JT .end\n    PUSH 2\n    POP\n.end:\n    HALT\n");
+    let coverage = CoverageCollector::new();
+    coverage.attach(&mut vm);
+    vm.run().unwrap();
+    let report = coverage.report(&vm);
+    assert_eq!(report.functions.len(), 1);
+    assert_eq!(report.uncovered[0].function.as_deref(), Some("(top level)"));
+    // Code outside any function is not said to be in one
+    assert!(report.to_string().contains("pc 2-3 (lines 3-4): PUSH 2; POP\n"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_coverage_report_as_json() {
+    let mut vm = load(PROGRAM);
+    let coverage = CoverageCollector::new();
+    coverage.attach(&mut vm);
+    vm.run().unwrap();
+    let json: serde_json::Value = serde_json::from_str(&coverage.report(&vm).to_json()).unwrap();
+    assert_eq!(json["covered"], 13);
+    assert_eq!(json["functions"][3]["name"], "unused");
+    assert_eq!(json["uncovered"][0]["start"], 10);
+    assert_eq!(json["uncovered"][0]["code"][1], "RET");
+}
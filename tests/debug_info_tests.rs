@@ -0,0 +1,118 @@
+use stack_vm_jit::vm::assembler::{Assembler, Disassembler, SimpleCompiler};
+use stack_vm_jit::vm::linker::Linker;
+use stack_vm_jit::vm::instruction::ExecutionError;
+use stack_vm_jit::vm::module::{BytecodeModule, FileEntry, StripStats};
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+
+const LIB: &str = ".export half\n.func half arity=1 returns=1\n    LOAD 0\n    PUSH 0\n    DIV\n    RET\n.endfunc";
+
+#[test]
+fn test_compiler_records_source_file() {
+    let module = SimpleCompiler::new()
+        .with_source_name("calc.src")
+        .compile_program("let x = 1;\nlet y = 0;\nx / y")
+        .unwrap();
+    assert_eq!(module.debug_info.file_at(0), Some("calc.src"));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(matches!(
+        error,
+        VmError::ExecutionErrorAt { line: Some(3), file: Some(ref file), .. } if file == "calc.src"
+    ));
+    assert!(error.to_string().starts_with("Execution error at calc.src:3 (pc "));
+}
+
+#[test]
+fn test_unnamed_sources_keep_line_only_messages() {
+    let module = Assembler::new().assemble_module("    PUSH 1\n    PUSH 0\n    DIV\n    HALT").unwrap();
+    assert!(module.debug_info.files.is_empty());
+    assert_eq!(module.debug_info.location(2), Some("line 3".to_string()));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    assert!(vm.run().unwrap_err().to_string().starts_with("Execution error at line 3 (pc 2)"));
+}
+
+#[test]
+fn test_linked_modules_keep_their_files() {
+    let main = Assembler::new()
+        .with_source_name("main.svm")
+        .assemble_module(".extern half\n    PUSH 8\n    CALL half\n    HALT")
+        .unwrap();
+    let lib = Assembler::new().with_source_name("lib.svm").assemble_module(LIB).unwrap();
+    let unnamed = Assembler::new()
+        .assemble_module(".export nop\n.func nop\n    RET\n.endfunc")
+        .unwrap();
+
+    let linked = Linker::new().add(main).add(lib).add(unnamed).link().unwrap();
+    assert_eq!(
+        linked.debug_info.files,
+        [
+            FileEntry { address: 0, name: Some("main.svm".to_string()) },
+            FileEntry { address: 3, name: Some("lib.svm".to_string()) },
+            FileEntry { address: 7, name: None },
+        ]
+    );
+    assert_eq!(linked.debug_info.location(5), Some("lib.svm:5".to_string()));
+    assert_eq!(linked.debug_info.location(7), Some("line 3".to_string()));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(linked.clone()).unwrap();
+    assert!(vm.run().is_err());
+    let trace = vm.stack_trace();
+    assert_eq!(trace[0].file.as_deref(), Some("lib.svm"));
+    assert_eq!(trace[0].to_string(), "at half (lib.svm:5, pc 5)");
+    assert_eq!(trace[1].file.as_deref(), Some("main.svm"));
+
+    // Files survive a container roundtrip
+    let restored = BytecodeModule::from_bytes(&linked.to_bytes().unwrap()).unwrap();
+    assert_eq!(restored.debug_info, linked.debug_info);
+}
+
+#[test]
+fn test_disassembler_line_comments() {
+    let module = Assembler::new().with_source_name("lib.svm").assemble_module(LIB).unwrap();
+
+    let plain = Disassembler::new().disassemble_module(&module).unwrap();
+    assert!(!plain.contains("; lib.svm"));
+
+    let text = Disassembler::new()
+        .with_line_comments(true)
+        .disassemble_module(&module)
+        .unwrap();
+    assert!(text.contains("    ; lib.svm:3\n    LOAD 0\n    ; lib.svm:4\n    PUSH 0\n"));
+    // The comments don't get in the way of reassembling
+    let reassembled = Assembler::new().assemble_module(&text).unwrap();
+    assert_eq!(reassembled.instructions.len(), module.instructions.len());
+    for (a, b) in reassembled.instructions.iter().zip(&module.instructions) {
+        assert_eq!((a.opcode(), a.operand()), (b.opcode(), b.operand()));
+    }
+}
+
+#[test]
+fn test_strip_debug() {
+    let source = format!("{}\n.func main\n    PUSH 2\n    CALL half\nagain:\n    RET\n.endfunc", LIB);
+    let mut module = Assembler::new().with_source_name("lib.svm").assemble_module(&source).unwrap();
+    let size = module.to_bytes().unwrap().len();
+
+    let stats = module.strip_debug();
+    assert_eq!(stats.files, 1);
+    assert_eq!(stats.symbols, 3);
+    assert!(stats.line_entries > 0);
+    assert_eq!(module.to_bytes().unwrap().len(), size - stats.bytes);
+    assert!(stats.to_string().starts_with("Stripped "));
+    assert!(module.symbols.is_empty());
+    assert_eq!(module.debug_info, Default::default());
+    assert_eq!(module.strip_debug(), StripStats::default());
+
+    // Exports still work, and errors lose only their location
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    assert!(matches!(
+        vm.call_function("half", &[Value::Integer(4)]),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::DivisionByZero, line: None, file: None, .. })
+    ));
+}
@@ -0,0 +1,137 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::debugger::{Debugger, DebuggerOutput};
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+
+const PROGRAM: &str = r#"
+    PUSH 3
+    CALL square_plus_one
+    PUSH 1
+    ADD
+    HALT
+.func square_plus_one arity=1 returns=1
+    LOAD 0
+    CALL square
+    PUSH 1
+    ADD
+    RET
+.endfunc
+.func square arity=1 returns=1
+    LOAD 0
+    LOAD 0
+    MUL
+    RET
+.endfunc
+"#;
+
+fn debugger(source: &str) -> Debugger {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().with_source_name("square.asm").assemble_module(source).unwrap()).unwrap();
+    Debugger::new(vm)
+}
+
+fn message(debugger: &mut Debugger, command: &str) -> String {
+    match debugger.handle(command).unwrap() {
+        DebuggerOutput::Message(text) => text,
+        DebuggerOutput::Quit => panic!("{} should not quit", command),
+    }
+}
+
+#[test]
+fn test_breakpoints_and_continue() {
+    let mut debugger = debugger(PROGRAM);
+    assert_eq!(message(&mut debugger, "break square"), "Breakpoint at pc 10 (square.asm:15): LOAD 0");
+    assert_eq!(message(&mut debugger, "b :4"), "Breakpoint at pc 2 (square.asm:4): PUSH 1");
+    assert_eq!(message(&mut debugger, "b 99"), "No address, label, function or line 99");
+    assert_eq!(message(&mut debugger, "breakpoints").lines().count(), 2);
+
+    assert_eq!(
+        message(&mut debugger, "continue"),
+        "Stopped at a breakpoint (hit 1)\npc 10 (square.asm:15): LOAD 0"
+    );
+    assert_eq!(debugger.vm().call_depth(), 2);
+    assert_eq!(message(&mut debugger, "locals"), "   0: 3");
+    let trace = message(&mut debugger, "where");
+    assert!(trace.starts_with("at square (square.asm:15, pc 10)\nat square_plus_one"));
+
+    assert!(message(&mut debugger, "c").ends_with("pc 2 (square.asm:4): PUSH 1"));
+    assert_eq!(message(&mut debugger, "d :4"), "Removed the breakpoint at pc 2");
+    assert_eq!(message(&mut debugger, "c"), "Program halted with 11 on the stack");
+    // Finished programs stay finished until restarted
+    assert_eq!(message(&mut debugger, "step"), "Program halted with 11 on the stack");
+    assert_eq!(message(&mut debugger, "restart"), "pc 0 (square.asm:2): PUSH 3");
+    assert_eq!(debugger.vm().breakpoints().collect::<Vec<_>>(), [10]);
+}
+
+#[test]
+fn test_stepping_commands() {
+    let mut debugger = debugger(PROGRAM);
+    assert_eq!(message(&mut debugger, "step"), "pc 1 (square.asm:3): CALL 5");
+    assert_eq!(message(&mut debugger, "s"), "pc 5 (square.asm:8): LOAD 0");
+    assert_eq!(message(&mut debugger, "next"), "pc 6 (square.asm:9): CALL 10");
+    assert_eq!(message(&mut debugger, "next"), "pc 7 (square.asm:10): PUSH 1");
+    assert_eq!(message(&mut debugger, "stack"), "   0: 9");
+    assert_eq!(message(&mut debugger, "finish"), "pc 2 (square.asm:4): PUSH 1");
+    assert_eq!(message(&mut debugger, "locals"), "No locals");
+
+    // Continuing from a breakpoint stepped onto leaves it
+    message(&mut debugger, "break 3");
+    message(&mut debugger, "step");
+    assert_eq!(message(&mut debugger, "c"), "Program halted with 11 on the stack");
+}
+
+#[test]
+fn test_errors_and_transcript() {
+    let mut debugger = debugger("    PUSH 1\n    PUSH 0\n    DIV\n    HALT");
+    message(&mut debugger, "next");
+    assert!(matches!(debugger.handle("c"), Err(VmError::ExecutionErrorAt { pc: 2, .. })));
+    assert_eq!(message(&mut debugger, ""), "pc 2 (square.asm:3): DIV");
+    assert!(message(&mut debugger, "frobnicate").contains("try help"));
+    assert!(matches!(debugger.handle("quit").unwrap(), DebuggerOutput::Quit));
+
+    let mut output = Vec::new();
+    let mut debugger = debugger_without_source();
+    debugger.run("b 1\nc\nstack\nq\nstep\n".as_bytes(), &mut output).unwrap();
+    assert_eq!(
+        String::from_utf8(output).unwrap(),
+        "pc 0: PUSH 2\n(debug) Breakpoint at pc 1: PUSH 5\n(debug) Stopped at a breakpoint (hit 1)\npc 1: PUSH 5\n(debug)    0: 2\n(debug) "
+    );
+}
+
+fn debugger_without_source() -> Debugger {
+    let mut vm = VirtualMachine::new();
+    let mut module = Assembler::new().assemble_module("    PUSH 2\n    PUSH 5\n    ADD\n    HALT").unwrap();
+    module.strip_debug();
+    vm.load_module(module).unwrap();
+    Debugger::new(vm)
+}
+
+#[test]
+fn test_going_back() {
+    let mut debugger = debugger(PROGRAM);
+    message(&mut debugger, "break square");
+    message(&mut debugger, "continue");
+    for _ in 0..3 {
+        message(&mut debugger, "step");
+    }
+    assert_eq!(message(&mut debugger, "stack"), "   0: 9");
+
+    // How did 9 get there? The MUL, whose operands are then on the stack
+    assert_eq!(message(&mut debugger, "origin"), "9 was put there 1 instruction back, by\npc 12 (square.asm:17): MUL");
+    assert_eq!(message(&mut debugger, "stack"), "   0: 3\n   1: 3");
+    assert_eq!(message(&mut debugger, "origin 0"), "3 was put there 2 instructions back, by\npc 10 (square.asm:15): LOAD 0");
+
+    // Back out of the call, one instruction at a time
+    assert_eq!(message(&mut debugger, "back"), "pc 6 (square.asm:9): CALL 10");
+    assert_eq!(message(&mut debugger, "back"), "pc 5 (square.asm:8): LOAD 0");
+    assert_eq!(message(&mut debugger, "locals"), "   0: 3");
+    assert_eq!(message(&mut debugger, "rc"), "No breakpoint passed; back at the start\npc 0 (square.asm:2): PUSH 3");
+    assert_eq!(message(&mut debugger, "back"), "Already at the start\npc 0 (square.asm:2): PUSH 3");
+
+    // Forward again to the same end, and back to the breakpoint passed
+    message(&mut debugger, "continue");
+    assert_eq!(message(&mut debugger, "continue"), "Program halted with 11 on the stack");
+    assert_eq!(message(&mut debugger, "rc"), "Back at a breakpoint\npc 10 (square.asm:15): LOAD 0");
+    assert_eq!(message(&mut debugger, "where").lines().next(), Some("at square (square.asm:15, pc 10)"));
+    assert_eq!(message(&mut debugger, "origin 5"), "No stack slot 5");
+    assert_eq!(message(&mut debugger, "origin"), "(empty stack)");
+}
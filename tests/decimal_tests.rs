@@ -0,0 +1,91 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::{Decimal, Value};
+
+fn dec(text: &str) -> Value {
+    Value::Decimal(text.parse().unwrap())
+}
+
+fn run_binary(a: Value, b: Value, opcode: Opcode) -> Result<Value, String> {
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(
+        vec![
+            Instruction::new(Opcode::Push, Some(a)),
+            Instruction::new(Opcode::Push, Some(b)),
+            Instruction::new(opcode, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![],
+    )
+    .unwrap();
+    vm.run().map_err(|e| e.to_string())?;
+    Ok(vm.stack_top().unwrap().clone())
+}
+
+#[test]
+fn test_decimal_parse_and_display() {
+    assert_eq!("0.1".parse::<Decimal>().unwrap().to_string(), "0.1");
+    assert_eq!("-1.250".parse::<Decimal>().unwrap().to_string(), "-1.25");
+    assert_eq!("2".parse::<Decimal>().unwrap().to_string(), "2.0");
+    assert_eq!(dec("-0.5").to_string(), "-0.5");
+
+    assert!("".parse::<Decimal>().is_err());
+    assert!(".5".parse::<Decimal>().is_err());
+    assert!("1.2.3".parse::<Decimal>().is_err());
+    assert!("0.1234567".parse::<Decimal>().is_err()); // beyond SCALE digits
+}
+
+#[test]
+fn test_decimal_arithmetic_is_exact() {
+    assert_eq!(run_binary(dec("0.1"), dec("0.2"), Opcode::Add).unwrap(), dec("0.3"));
+    assert_eq!(run_binary(dec("1.5"), Value::Integer(2), Opcode::Mul).unwrap(), dec("3"));
+    assert_eq!(run_binary(Value::Integer(1), dec("0.75"), Opcode::Sub).unwrap(), dec("0.25"));
+    assert_eq!(run_binary(dec("7.5"), dec("2"), Opcode::Mod).unwrap(), dec("1.5"));
+
+    // Division rounds half away from zero at the last digit
+    assert_eq!(run_binary(dec("2"), dec("3"), Opcode::Div).unwrap(), dec("0.666667"));
+    assert_eq!(run_binary(dec("-2"), dec("3"), Opcode::Div).unwrap(), dec("-0.666667"));
+}
+
+#[test]
+fn test_decimal_comparison() {
+    assert_eq!(
+        run_binary(dec("0.1"), dec("0.2"), Opcode::LessThan).unwrap(),
+        Value::Boolean(true)
+    );
+    assert_eq!(
+        run_binary(dec("2.0"), Value::Integer(2), Opcode::GreaterEqual).unwrap(),
+        Value::Boolean(true)
+    );
+
+    // Total order places decimals between integers and floats of equal value
+    let mut values = vec![Value::Float(1.0), dec("1"), Value::Integer(1), dec("0.5")];
+    values.sort_by(|a, b| a.total_cmp(b));
+    assert_eq!(values, vec![dec("0.5"), Value::Integer(1), dec("1"), Value::Float(1.0)]);
+}
+
+#[test]
+fn test_decimal_errors() {
+    assert!(run_binary(dec("1"), dec("0"), Opcode::Div)
+        .unwrap_err()
+        .contains("Division by zero"));
+    assert!(run_binary(dec("1"), Value::Float(0.5), Opcode::Add)
+        .unwrap_err()
+        .contains("Cannot mix decimal with float"));
+    assert!(run_binary(dec("9000000000000"), dec("9000000000000"), Opcode::Add)
+        .unwrap_err()
+        .contains("overflow"));
+}
+
+#[test]
+fn test_decimal_assembler_literal() {
+    let mut assembler = Assembler::new();
+    let (program, _) = assembler.assemble("PUSH 0.1d\nPUSH 0.2d\nADD\nHALT").unwrap();
+    assert_eq!(program[0].operand(), Some(&dec("0.1")));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(program);
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &dec("0.3"));
+}
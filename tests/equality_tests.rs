@@ -0,0 +1,65 @@
+use stack_vm_jit::vm::heap::Heap;
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+fn compare_arrays(opcode: Opcode, same_array: bool) -> Value {
+    let mut instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::NewArray, Some(Value::Integer(2))),
+    ];
+    if same_array {
+        instructions.push(Instruction::new(Opcode::Dup, None));
+    } else {
+        instructions.extend(vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+            Instruction::new(Opcode::NewArray, Some(Value::Integer(2))),
+        ]);
+    }
+    instructions.push(Instruction::new(opcode, None));
+    instructions.push(Instruction::new(Opcode::Halt, None));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+    vm.stack_top().unwrap().clone()
+}
+
+#[test]
+fn test_equal_uses_identity_for_heap_values() {
+    assert_eq!(compare_arrays(Opcode::Equal, true), Value::Boolean(true));
+    assert_eq!(compare_arrays(Opcode::Equal, false), Value::Boolean(false));
+    assert_eq!(compare_arrays(Opcode::NotEqual, false), Value::Boolean(true));
+    assert_eq!(compare_arrays(Opcode::RefEqual, false), Value::Boolean(false));
+}
+
+#[test]
+fn test_deep_equal_compares_contents() {
+    assert_eq!(compare_arrays(Opcode::DeepEqual, false), Value::Boolean(true));
+    assert_eq!(compare_arrays(Opcode::DeepEqual, true), Value::Boolean(true));
+}
+
+#[test]
+fn test_strings_compare_by_content() {
+    let mut heap = Heap::new();
+    let gc = Value::GcString(heap.allocate_string("text".to_string()).unwrap());
+    let plain = Value::String("text".into());
+
+    assert!(gc.ref_equals(&plain));
+    assert!(plain.ref_equals(&gc));
+    assert!(gc.deep_equals(&plain));
+}
+
+#[test]
+fn test_deep_equal_handles_cycles() {
+    let mut heap = Heap::new();
+    let a = heap.allocate_array(vec![Value::Integer(1)]).unwrap();
+    let b = heap.allocate_array(vec![Value::Integer(1)]).unwrap();
+    a.push(Value::Array(a.clone()));
+    b.push(Value::Array(b.clone()));
+
+    assert!(Value::Array(a.clone()).deep_equals(&Value::Array(b.clone())));
+    assert!(!Value::Array(a).ref_equals(&Value::Array(b)));
+}
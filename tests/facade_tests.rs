@@ -0,0 +1,49 @@
+use stack_vm_jit::prelude::*;
+
+#[test]
+fn test_prelude_runs_assembly() {
+    let module = Assembler::new()
+        .assemble_module(".export double\n    PUSH 21\n    CALL double\n    HALT\n.func double arity=1 returns=1\n    LOAD 0\n    PUSH 2\n    MUL\n    RET\n.endfunc")
+        .unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    assert_eq!(vm.run().unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+    assert_eq!(vm.call_function("double", &[Value::Integer(4)]).unwrap(), Value::Integer(8));
+}
+
+#[test]
+fn test_prelude_builds_and_compiles() {
+    let mut builder = ProgramBuilder::new();
+    builder.emit_with(Opcode::Push, Value::Integer(2)).emit_with(Opcode::Push, Value::Integer(3));
+    builder.emit(Opcode::Add).emit(Opcode::Halt);
+    let mut vm = VirtualMachine::new();
+    vm.load_module(builder.build().unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(5));
+
+    let module: BytecodeModule = SimpleCompiler::new().compile_program("let x = 6; x * 7").unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+}
+
+#[test]
+fn test_root_reexports_match_module_paths() {
+    // The short paths name the same types as the long ones
+    let io = stack_vm_jit::MemoryIo::new();
+    let mut vm: stack_vm_jit::vm::runtime::VirtualMachine =
+        stack_vm_jit::VirtualMachine::builder().with_io(io.clone()).build();
+    let instruction: stack_vm_jit::vm::instruction::Instruction = Instruction::new(Opcode::Halt, None);
+    assert_eq!(instruction.opcode(), stack_vm_jit::Opcode::Halt);
+
+    vm.register_native("fail", 0, |_| Err(stack_vm_jit::NativeError::new("no")));
+    let module = stack_vm_jit::Assembler::new()
+        .assemble_module(".extern fail\n    PUSH \"hi\"\n    PRINT\n    CALL fail\n    HALT")
+        .unwrap();
+    vm.load_module(module).unwrap();
+    let error: stack_vm_jit::VmError = vm.run().unwrap_err();
+    assert!(error.to_string().contains("no"));
+    assert_eq!(io.stdout(), "hi\n");
+}
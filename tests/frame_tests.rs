@@ -0,0 +1,112 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::instruction::ExecutionError;
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use stack_vm_jit::vm::stack::StackError;
+use stack_vm_jit::vm::types::Value;
+
+fn run(source: &str) -> (VirtualMachine, Result<RunOutcome, VmError>) {
+    let mut vm = VirtualMachine::new();
+    // These programs break the rules on purpose, to show the frames hold
+    vm.load_module_unverified(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    let result = vm.run();
+    (vm, result)
+}
+
+#[test]
+fn test_functions_cannot_pop_their_callers_values() {
+    let source = r#"
+    PUSH 7
+    PUSH 1
+    CALL greedy
+    HALT
+.func greedy arity=1 returns=1
+    LOAD 0
+    POP
+    POP
+    RET
+.endfunc
+"#;
+    let (vm, result) = run(source);
+    assert!(matches!(
+        result,
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::StackError(StackError::FrameUnderflow), line: Some(9), .. })
+    ));
+    // The caller's value is still there
+    assert_eq!(vm.stack_size(), 1);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(7));
+}
+
+#[test]
+fn test_integer_arithmetic_stays_in_its_frame() {
+    // Both integers are there, but one belongs to the caller
+    let source = r#"
+    PUSH 7
+    PUSH 1
+    CALL greedy
+    HALT
+.func greedy arity=1 returns=1
+    LOAD 0
+    ADD
+    RET
+.endfunc
+"#;
+    let (vm, result) = run(source);
+    assert!(matches!(
+        result,
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::StackError(StackError::FrameUnderflow), line: Some(8), .. })
+    ));
+    assert_eq!(vm.stack_contents()[0], Value::Integer(7));
+}
+
+#[test]
+fn test_returning_restores_the_callers_frame() {
+    let source = r#"
+    PUSH 10
+    CALL outer
+    ADD
+    HALT
+.func outer arity=0 returns=1
+    PUSH 2
+    CALL inner
+    PUSH 3
+    ADD
+    RET
+.endfunc
+.func inner arity=1 returns=1
+    LOAD 0
+    LOAD 0
+    MUL
+    RET
+.endfunc
+"#;
+    let (vm, result) = run(source);
+    result.unwrap();
+    // 10 + (2 * 2 + 3)
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(17));
+}
+
+#[test]
+fn test_arguments_must_come_from_the_callers_frame() {
+    // outer's caller pushed a value, but outer itself has none to pass on
+    let source = r#"
+    PUSH 5
+    CALL outer
+    HALT
+.func outer arity=0 returns=1
+    CALL inner
+    RET
+.endfunc
+.func inner arity=1 returns=1
+    LOAD 0
+    RET
+.endfunc
+"#;
+    let (_, result) = run(source);
+    assert!(matches!(
+        result,
+        Err(VmError::ExecutionErrorAt {
+            error: ExecutionError::ArityMismatch { expected: 1, available: 0, .. },
+            ..
+        })
+    ));
+}
@@ -0,0 +1,65 @@
+use stack_vm_jit::vm::assembler::SimpleCompiler;
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+
+const SUM: &str = "let total = 0; let i = 1; while i <= 10 { total = total + i; i = i + 1; } total";
+
+fn sum_vm() -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(SimpleCompiler::new().compile_program(SUM).unwrap()).unwrap();
+    vm
+}
+
+#[test]
+fn test_run_with_enough_fuel_halts() {
+    let mut vm = sum_vm();
+    assert_eq!(vm.run_with_fuel(1_000).unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(55));
+    assert!(vm.remaining_fuel() > 0);
+}
+
+#[test]
+fn test_exhausted_runs_resume_where_they_stopped() {
+    let mut vm = sum_vm();
+    assert_eq!(vm.run_with_fuel(7).unwrap(), RunOutcome::Exhausted);
+    assert_eq!(vm.remaining_fuel(), 0);
+    assert_eq!(vm.instruction_count(), 7);
+    assert!(!vm.is_halted());
+
+    // Without more fuel nothing happens
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Exhausted);
+    assert_eq!(vm.instruction_count(), 7);
+
+    // Drip-feeding fuel gets the same answer as one long run
+    let mut pauses = 0;
+    loop {
+        vm.add_fuel(5);
+        match vm.resume().unwrap() {
+            RunOutcome::Halted => break,
+            RunOutcome::Exhausted => pauses += 1,
+            RunOutcome::Paused | RunOutcome::Stopped(_) => unreachable!("nothing stops this VM"),
+        }
+    }
+    assert!(pauses > 5);
+    assert_eq!(vm.stack_size(), 1);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(55));
+
+    // A halted program stays halted
+    vm.add_fuel(5);
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.remaining_fuel(), 5);
+}
+
+#[test]
+fn test_run_fails_when_out_of_fuel_but_can_resume() {
+    let mut vm = VirtualMachine::builder().with_max_instructions(10).build();
+    vm.load_module(SimpleCompiler::new().compile_program(SUM).unwrap()).unwrap();
+    assert!(matches!(vm.run(), Err(VmError::InvalidProgramState(_))));
+
+    vm.add_fuel(1_000);
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(55));
+
+    let mut vm = VirtualMachine::new();
+    assert!(matches!(vm.resume(), Err(VmError::NoProgram)));
+}
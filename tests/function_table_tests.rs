@@ -0,0 +1,259 @@
+use stack_vm_jit::vm::assembler::{Assembler, AssemblerError, Disassembler};
+use stack_vm_jit::vm::instruction::ExecutionError;
+use stack_vm_jit::vm::linker::{LinkError, Linker};
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+
+const MATH: &str = r#"
+.export fib
+.export divide
+    HALT
+.func fib arity=1 returns=1
+    LOAD 0
+    PUSH 2
+    LT
+    JF .recurse
+    LOAD 0
+    RET
+.recurse:
+    LOAD 0
+    PUSH 1
+    SUB
+    CALL fib
+    LOAD 0
+    PUSH 2
+    SUB
+    CALL fib
+    ADD
+    RET
+.endfunc
+.func divide arity=2 returns=1
+    LOAD 0
+    LOAD 1
+    CALL checked_div
+    RET
+.endfunc
+.func checked_div arity=2 returns=1
+    LOAD 0
+    LOAD 1
+    DIV
+    RET
+.endfunc
+"#;
+
+fn math_vm() -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(MATH).unwrap())
+        .unwrap();
+    vm
+}
+
+#[test]
+fn test_exports_are_marked() {
+    let module = Assembler::new().assemble_module(MATH).unwrap();
+    let exported: Vec<&str> = module.exports().map(|f| f.name.as_str()).collect();
+    assert_eq!(exported, ["fib", "divide"]);
+    assert!(!module.function("checked_div").unwrap().exported);
+
+    // Without .export, every function is exported
+    let module = Assembler::new()
+        .assemble_module(".func f\n    RET\n.endfunc\n.func g\n    RET\n.endfunc")
+        .unwrap();
+    assert_eq!(module.exports().count(), 2);
+
+    let error = Assembler::new()
+        .assemble_module(".export missing\n    HALT")
+        .unwrap_err();
+    assert!(matches!(error, AssemblerError::UnknownLabel { ref label, .. } if label == "missing"));
+}
+
+#[test]
+fn test_exports_survive_disassembly_and_containers() {
+    let module = Assembler::new().assemble_module(MATH).unwrap();
+
+    let text = Disassembler::new().disassemble_module(&module).unwrap();
+    assert!(text.contains(".export fib\n"));
+    let reassembled = Assembler::new().assemble_module(&text).unwrap();
+    assert_eq!(reassembled.functions, module.functions);
+
+    let restored = BytecodeModule::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(restored.functions, module.functions);
+
+    #[cfg(feature = "serde")]
+    {
+        let restored = BytecodeModule::from_json_bytes(&module.to_json_bytes().unwrap()).unwrap();
+        assert_eq!(restored.functions, module.functions);
+    }
+}
+
+#[test]
+fn test_call_function_by_name() {
+    let mut vm = math_vm();
+    assert_eq!(vm.call_function("fib", &[Value::Integer(10)]).unwrap(), Value::Integer(55));
+    // The VM can be called into again
+    assert_eq!(
+        vm.call_function("divide", &[Value::Integer(42), Value::Integer(6)]).unwrap(),
+        Value::Integer(7)
+    );
+    assert_eq!(vm.call_depth(), 0);
+}
+
+#[test]
+fn test_call_function_by_address() {
+    let mut vm = math_vm();
+    // Unexported functions can still be called by address
+    let address = vm.functions().iter().find(|f| f.name == "checked_div").unwrap().address;
+    assert_eq!(
+        vm.call_function(address, &[Value::Integer(9), Value::Integer(3)]).unwrap(),
+        Value::Integer(3)
+    );
+    assert!(matches!(vm.call_function(2, &[]), Err(VmError::NoFunctionAt(2))));
+    assert_eq!(
+        vm.call_function_values("fib", &[Value::Integer(7)]).unwrap(),
+        [Value::Integer(13)]
+    );
+}
+
+#[test]
+fn test_call_function_restores_the_running_program() {
+    let source = r#"
+    PUSH 40
+    PUSH 2
+    ADD
+    HALT
+.func noop arity=0 returns=0
+    RET
+.endfunc
+.func square arity=1 returns=1
+    LOAD 0
+    LOAD 0
+    MUL
+    RET
+.endfunc
+"#;
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    assert_eq!(vm.run_with_fuel(2).unwrap(), RunOutcome::Exhausted);
+
+    assert_eq!(vm.call_function("square", &[Value::Integer(12)]).unwrap(), Value::Integer(144));
+    assert_eq!(vm.call_function("noop", &[]).unwrap(), Value::Null);
+    assert_eq!(vm.program_counter(), 2);
+    assert_eq!(vm.stack_size(), 2);
+    assert_eq!(vm.remaining_fuel(), 0);
+
+    vm.add_fuel(10);
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+    assert_eq!(vm.call_function("square", &[Value::Integer(3)]).unwrap(), Value::Integer(9));
+    assert!(vm.is_halted());
+}
+
+#[test]
+fn test_failed_call_leaves_the_program_resumable() {
+    let source = r#"
+    PUSH 40
+    PUSH 2
+    ADD
+    HALT
+.func divide arity=2 returns=1
+    LOAD 0
+    LOAD 1
+    DIV
+    RET
+.endfunc
+"#;
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    assert_eq!(vm.run_with_fuel(2).unwrap(), RunOutcome::Exhausted);
+
+    assert!(vm.call_function("divide", &[Value::Integer(1), Value::Integer(0)]).is_err());
+    assert!(vm.call_function("divide", &[Value::Integer(1)]).is_err());
+    assert_eq!(vm.program_counter(), 2);
+    assert_eq!(vm.stack_size(), 2);
+    assert_eq!(vm.call_depth(), 0);
+
+    vm.add_fuel(10);
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+}
+
+#[test]
+fn test_call_function_errors() {
+    let mut vm = math_vm();
+    assert!(matches!(
+        vm.call_function("checked_div", &[Value::Integer(1), Value::Integer(1)]),
+        Err(VmError::UnknownFunction(ref name)) if name == "checked_div"
+    ));
+    assert!(matches!(
+        vm.call_function("nope", &[]),
+        Err(VmError::UnknownFunction(_))
+    ));
+    assert!(matches!(
+        vm.call_function("fib", &[]),
+        Err(VmError::ExecutionError(ExecutionError::ArityMismatch { expected: 1, available: 0, .. }))
+    ));
+}
+
+#[test]
+fn test_stack_trace_names_functions() {
+    let mut vm = math_vm();
+    let error = vm.call_function("divide", &[Value::Integer(1), Value::Integer(0)]).unwrap_err();
+
+    let trace = error.backtrace();
+    let functions: Vec<Option<&str>> = trace.iter().map(|entry| entry.function.as_deref()).collect();
+    assert_eq!(functions, [Some("checked_div"), Some("divide"), None]);
+    // The failing DIV, then the CALL waiting on it
+    assert_eq!(trace[0].line, Some(33));
+    assert_eq!(trace[1].line, Some(27));
+    assert_eq!(trace[0].to_string(), format!("at checked_div (line 33, pc {})", trace[0].pc));
+    // The VM itself is back where it was before the call
+    assert_eq!(vm.stack_trace().len(), 1);
+}
+
+#[test]
+fn test_errors_format_a_traceback() {
+    let mut vm = math_vm();
+    let error = vm.call_function("divide", &[Value::Integer(1), Value::Integer(0)]).unwrap_err();
+    let traceback = format!("{:#}", error);
+    let lines: Vec<&str> = traceback.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], error.to_string());
+    assert!(lines[1].starts_with("    at checked_div (line 33, pc "));
+    assert!(lines[2].starts_with("    at divide (line 27, pc "));
+    assert!(lines[3].starts_with("    at <top level>"));
+    assert!(VmError::NoProgram.backtrace().is_empty());
+}
+
+#[test]
+fn test_frames_are_named_from_symbols() {
+    // Without a function table entry, the name comes from the symbol table
+    let source = "    CALL boom\n    HALT\n.func boom\n    PUSH 1\n    PUSH 0\n    DIV\n    RET\n.endfunc";
+    let mut module = Assembler::new().assemble_module(source).unwrap();
+    module.functions.clear();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    let error = vm.run().unwrap_err();
+    let functions: Vec<Option<&str>> =
+        error.backtrace().iter().map(|entry| entry.function.as_deref()).collect();
+    assert_eq!(functions, [Some("boom"), None]);
+}
+
+#[test]
+fn test_linker_only_sees_exports() {
+    let main = Assembler::new()
+        .assemble_module(".extern checked_div\n    PUSH 8\n    PUSH 2\n    CALL checked_div\n    HALT")
+        .unwrap();
+    let math = Assembler::new().assemble_module(MATH).unwrap();
+    assert!(matches!(
+        Linker::new().add(main).add(math.clone()).link(),
+        Err(LinkError::UnresolvedExterns(_))
+    ));
+
+    // Private functions may share names across modules
+    let other = Assembler::new()
+        .assemble_module(".export twice\n.func twice arity=1 returns=1\n    LOAD 0\n    CALL checked_div\n    RET\n.endfunc\n.func checked_div arity=1 returns=1\n    LOAD 0\n    RET\n.endfunc")
+        .unwrap();
+    let linked = Linker::new().add(math).add(other).link().unwrap();
+    assert_eq!(linked.exports().count(), 3);
+}
@@ -0,0 +1,66 @@
+#![cfg(feature = "fuzz")]
+
+use arbitrary::{Arbitrary, Unstructured};
+use stack_vm_jit::vm::fuzz::{self, FuzzModule};
+use stack_vm_jit::vm::instruction::Instruction;
+use stack_vm_jit::vm::types::Value;
+
+/// Deterministic pseudo-random bytes standing in for fuzzer input
+fn inputs(count: usize) -> impl Iterator<Item = Vec<u8>> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..count).map(move |index| {
+        let length = 16 + index % 512;
+        (0..length)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    })
+}
+
+#[test]
+fn test_generated_modules_mostly_verify() {
+    let mut verified = 0;
+    for data in inputs(500) {
+        let module = FuzzModule::arbitrary(&mut Unstructured::new(&data))
+            .unwrap()
+            .into_module();
+        assert!(!module.instructions.is_empty());
+        if fuzz::verifies(&module) {
+            verified += 1;
+        }
+    }
+    assert!(
+        verified > 250,
+        "only {} of 500 generated modules verified",
+        verified
+    );
+}
+
+#[test]
+fn test_interpreter_survives_generated_modules() {
+    for data in inputs(2000) {
+        let module = FuzzModule::arbitrary(&mut Unstructured::new(&data)).unwrap();
+        let _ = fuzz::run_module(module.into_module());
+    }
+}
+
+#[test]
+fn test_verifier_survives_arbitrary_instructions() {
+    for data in inputs(2000) {
+        let mut u = Unstructured::new(&data);
+        let instructions = Vec::<Instruction>::arbitrary(&mut u).unwrap();
+        let constants = Vec::<Value>::arbitrary(&mut u).unwrap();
+        let _ = fuzz::run_verified(instructions, constants);
+    }
+}
+
+#[test]
+fn test_loader_survives_arbitrary_bytes() {
+    for data in inputs(500) {
+        assert!(fuzz::run_bytes(&data).is_none());
+    }
+}
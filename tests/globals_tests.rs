@@ -0,0 +1,106 @@
+use stack_vm_jit::vm::assembler::{Assembler, Disassembler};
+use stack_vm_jit::vm::instruction::{ExecutionError, Instruction, Opcode};
+use stack_vm_jit::vm::linker::Linker;
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+
+const SCALE: &str = r#"
+    LDG config
+    PUSH 3
+    MUL
+    DUP
+    STG "last result"
+    HALT
+"#;
+
+fn assemble(source: &str) -> BytecodeModule {
+    Assembler::new().assemble_module(source).unwrap()
+}
+
+#[test]
+fn test_programs_read_and_write_host_globals() {
+    let mut vm = VirtualMachine::new();
+    vm.set_global("config", Value::Integer(7));
+    vm.load_module(assemble(SCALE)).unwrap();
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(21)));
+    assert_eq!(vm.get_global("last result"), Some(&Value::Integer(21)));
+
+    // Globals outlive the program, and changes show on the next run
+    vm.set_global("config", Value::Integer(-1));
+    vm.reset();
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(-3)));
+    assert_eq!(vm.get_global("last result"), Some(&Value::Integer(-3)));
+
+    assert_eq!(vm.remove_global("config"), Some(Value::Integer(-1)));
+    assert_eq!(vm.get_global("config"), None);
+}
+
+#[test]
+fn test_reading_an_unset_global_fails() {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(assemble(SCALE)).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(matches!(
+        error,
+        VmError::ExecutionErrorAt { error: ExecutionError::UndefinedGlobal(ref name), line: Some(2), .. }
+            if name == "config"
+    ));
+    assert!(error.to_string().contains("Undefined global: config"));
+}
+
+#[test]
+fn test_global_names_live_in_the_constants_pool() {
+    let module = assemble(SCALE);
+    // Pooling the names pools the literal pushed alongside them
+    assert_eq!(
+        module.constants,
+        [Value::Integer(3), Value::String("config".into()), Value::String("last result".into())]
+    );
+    assert_eq!(module.instructions[0].operand(), Some(&Value::Integer(1)));
+    assert_eq!(module.instructions[4].operand(), Some(&Value::Integer(2)));
+
+    let text = Disassembler::new().disassemble_module(&module).unwrap();
+    assert!(text.contains("    LDG config\n"));
+    assert!(text.contains("    STG \"last result\"\n"));
+
+    for module in [assemble(&text), BytecodeModule::from_bytes(&module.to_bytes().unwrap()).unwrap()] {
+        let mut vm = VirtualMachine::new();
+        vm.set_global("config", Value::Integer(2));
+        vm.load_module(module).unwrap();
+        assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(6)));
+    }
+}
+
+#[test]
+fn test_linked_modules_share_globals() {
+    let setup = assemble(".export setup\n    HALT\n.func setup arity=0 returns=0\n    PUSH 5\n    STG counter\n    RET\n.endfunc");
+    let main = assemble(".extern setup\n    CALL setup\n    LDG counter\n    LDG counter\n    ADD\n    HALT");
+    let linked = Linker::new().add(main).add(setup).link().unwrap();
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(linked).unwrap();
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(10)));
+    assert_eq!(vm.get_global("counter"), Some(&Value::Integer(5)));
+}
+
+#[test]
+fn test_global_operands_must_name_a_global() {
+    assert!(Assembler::new().assemble_module("    LDG 3\n    HALT").is_err());
+
+    let mut vm = VirtualMachine::new();
+    let (program, constants) = Assembler::new().assemble("    STG\n    HALT").unwrap();
+    assert!(vm.load_bytecode_module(program, constants).is_err());
+
+    let program = vec![
+        Instruction::new(Opcode::LoadGlobal, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    assert!(matches!(
+        vm.load_bytecode_module(program.clone(), vec![Value::Integer(1)]),
+        Err(VmError::VerificationFailed(_))
+    ));
+    vm.load_bytecode_module(program, vec![Value::String("answer".into())]).unwrap();
+    vm.set_global("answer", Value::Integer(42));
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(42)));
+}
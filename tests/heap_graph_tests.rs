@@ -0,0 +1,76 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::heap::{Array, GcPtr, Object};
+use stack_vm_jit::vm::heap_graph::{HeapGraph, HeapNodeKind};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::{Value, VmTuple};
+
+#[test]
+fn test_heap_graph_follows_fields_elements_and_tuples() {
+    let head = GcPtr::detached(Object::new());
+    let tail = GcPtr::detached(Object::new());
+    let items = GcPtr::detached(Array::new(vec![Value::GcObject(head.clone()), Value::Integer(3)]));
+    head.set_field("next", Value::GcObject(tail.clone()));
+    tail.set_field("next", Value::GcObject(head.clone()));
+    tail.set_field(
+        "pair",
+        Value::Tuple(VmTuple::new(vec![Value::Null, Value::Array(items.clone())]).unwrap()),
+    );
+    tail.freeze();
+
+    let root = Value::GcObject(head.clone());
+    let graph = HeapGraph::from_roots([("list".to_string(), &root), ("count".to_string(), &Value::Integer(2))]);
+    let ids: Vec<usize> = graph.nodes.iter().map(|node| node.id).collect();
+    assert_eq!(ids, [head.object_id(), tail.object_id(), items.object_id()]);
+    assert_eq!(graph.roots.len(), 1);
+    assert!(graph.is_root(head.object_id()));
+    assert!(!graph.is_root(tail.object_id()));
+
+    let edges: Vec<(usize, usize, &str)> =
+        graph.edges.iter().map(|edge| (edge.from, edge.to, edge.label.as_str())).collect();
+    assert_eq!(
+        edges,
+        [
+            (head.object_id(), tail.object_id(), "next"),
+            (tail.object_id(), head.object_id(), "next"),
+            (tail.object_id(), items.object_id(), "pair.1"),
+            (items.object_id(), head.object_id(), "[0]"),
+        ]
+    );
+    assert_eq!(graph.retainers(head.object_id()).count(), 2);
+    let tail_node = graph.node(tail.object_id()).unwrap();
+    assert_eq!((tail_node.kind, tail_node.len, tail_node.frozen), (HeapNodeKind::Object, 2, true));
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph heap {\n"));
+    assert!(dot.contains(&format!(
+        "    n{0} [label=\"#{0} object, 1 fields\", style=\"filled,bold\", fillcolor=lightblue];\n",
+        head.object_id()
+    )));
+    assert!(dot.contains(&format!("    n{0} [label=\"#{0} object, 2 fields, frozen\"];\n", tail.object_id())));
+    assert!(dot.contains(&format!("    root0 -> n{} [style=bold];\n", head.object_id())));
+    assert!(dot.contains(&format!("    n{} -> n{} [label=\"pair.1\"];\n", tail.object_id(), items.object_id())));
+    assert!(dot.ends_with("}\n"));
+}
+
+#[test]
+fn test_vm_heap_graph_names_roots() {
+    let source = "    NEW\n    DUP\n    PUSH \"say \\\"hi\\\"\"\n    SET_FIELD \"greeting\"\n    STG \"config\"\n    PUSH 4\n    NEW_BYTES\n    HALT\n";
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    vm.run().unwrap();
+
+    let graph = vm.heap_graph();
+    let roots: Vec<&str> = graph.roots.iter().map(|root| root.name.as_str()).collect();
+    assert_eq!(roots, ["stack 0", "global config"]);
+    let kinds: Vec<HeapNodeKind> = graph.nodes.iter().map(|node| node.kind).collect();
+    assert_eq!(kinds, [HeapNodeKind::Bytes, HeapNodeKind::Object]);
+    assert!(graph.to_dot().contains(" bytes\", style=\"filled,bold\""));
+
+    #[cfg(feature = "serde")]
+    {
+        let json: serde_json::Value = serde_json::from_str(&graph.to_json()).unwrap();
+        assert_eq!(json["roots"][1]["name"], "global config");
+        assert_eq!(json["nodes"][0]["kind"], "Bytes");
+        assert_eq!(json["nodes"][1]["len"], 1);
+    }
+}
@@ -25,15 +25,15 @@ fn test_object_allocation() {
     let mut heap = Heap::new();
     
     // Create an object with some fields
-    let mut obj = Object::new();
-    obj.set_field("name".to_string(), Value::String("test".to_string()));
+    let obj = Object::new();
+    obj.set_field("name".to_string(), Value::String("test".into()));
     obj.set_field("value".to_string(), Value::Integer(42));
     
     let gc_object = heap.allocate_object(obj).unwrap();
     
     assert_eq!(heap.allocated_objects(), 1);
-    assert_eq!(gc_object.get_field("name").unwrap(), &Value::String("test".to_string()));
-    assert_eq!(gc_object.get_field("value").unwrap(), &Value::Integer(42));
+    assert_eq!(gc_object.get_field("name").unwrap(), Value::String("test".into()));
+    assert_eq!(gc_object.get_field("value").unwrap(), Value::Integer(42));
 }
 
 #[test]
@@ -60,9 +60,7 @@ fn test_basic_mark_and_sweep() {
     assert_eq!(heap.allocated_objects(), 3);
     
     // Create a root set (objects that should be kept)
-    let mut roots = Vec::new();
-    roots.push(&_string1);
-    roots.push(&_string2);
+    let roots = vec![&_string1, &_string2];
     // Note: unreferenced is not in roots, so should be collected
     
     // Run garbage collection
@@ -80,6 +78,24 @@ fn test_heap_size_limits() {
     assert_eq!(heap.current_heap_size(), 0);
 }
 
+#[test]
+fn test_heap_size_counts_live_objects() {
+    let mut heap = Heap::with_initial_size(1024);
+    let kept = heap.allocate_string("kept".to_string()).unwrap();
+    let size = heap.current_heap_size();
+    assert!(size > 0);
+
+    // Dropping the last pointer to an object gives its bytes back
+    for _ in 0..100 {
+        let dropped = heap.allocate_bytes(vec![0; 256]).unwrap();
+        assert!(heap.current_heap_size() > size);
+        drop(dropped);
+        assert_eq!(heap.current_heap_size(), size);
+    }
+    drop(kept);
+    assert_eq!(heap.current_heap_size(), 0);
+}
+
 #[test]
 fn test_allocation_failure_on_oom() {
     let mut heap = Heap::with_initial_size(100); // Very small heap
@@ -0,0 +1,107 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::history::ExecutionHistory;
+use stack_vm_jit::vm::io::MemoryIo;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+/// Fills an array with 1, 2, 3 one element at a time, keeping the last
+/// element stored in a global
+const FILL: &str = "    PUSH 0\n    PUSH 0\n    PUSH 0\n    NEW_ARRAY 3\n    DUP\n    PUSH 0\n    PUSH 1\n    SET_ARRAY\n    DUP\n    PUSH 1\n    PUSH 2\n    SET_ARRAY\n    PUSH 2\n    STG last\n    DUP\n    PUSH 2\n    PUSH 3\n    SET_ARRAY\n    PUSH 3\n    STG last\n    HALT\n";
+
+fn load(source: &str) -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    vm
+}
+
+fn elements(vm: &VirtualMachine) -> Vec<Value> {
+    match vm.stack_top().unwrap() {
+        Value::Array(array) => array.to_vec(),
+        other => panic!("expected an array, found {}", other),
+    }
+}
+
+#[test]
+fn test_restore_undoes_heap_writes() {
+    let mut vm = load(FILL);
+    for _ in 0..12 {
+        vm.step().unwrap();
+    }
+    let snapshot = vm.snapshot();
+    assert_eq!(ExecutionHistory::position(&vm), 12);
+    assert_eq!(elements(&vm), vec![Value::Integer(1), Value::Integer(2), Value::Integer(0)]);
+
+    vm.run().unwrap();
+    assert_eq!(elements(&vm), vec![Value::Integer(1), Value::Integer(2), Value::Integer(3)]);
+    assert_eq!(vm.get_global("last"), Some(&Value::Integer(3)));
+
+    // The same array, as it was
+    vm.restore(&snapshot);
+    assert!(!vm.is_halted());
+    assert_eq!(ExecutionHistory::position(&vm), 12);
+    assert_eq!(vm.program_counter(), 12);
+    assert_eq!(elements(&vm), vec![Value::Integer(1), Value::Integer(2), Value::Integer(0)]);
+    assert_eq!(vm.get_global("last"), None);
+
+    // A snapshot can be restored again
+    vm.run().unwrap();
+    vm.restore(&snapshot);
+    vm.step().unwrap();
+    assert_eq!(vm.stack_size(), 2);
+}
+
+#[test]
+fn test_history_goes_back_and_forward() {
+    let mut vm = load(FILL);
+    let mut history = ExecutionHistory::new(&mut vm, 4);
+    assert_eq!((history.start(), history.snapshot_count()), (0, 1));
+    history.go_to(&mut vm, 21).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(history.snapshot_count(), 6);
+
+    history.go_to(&mut vm, 13).unwrap();
+    assert_eq!(vm.program_counter(), 13);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(2));
+    assert_eq!(elements_below_top(&vm), vec![Value::Integer(1), Value::Integer(2), Value::Integer(0)]);
+    history.go_to(&mut vm, 100).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.get_global("last"), Some(&Value::Integer(3)));
+
+    // The latest point the array's last element was still zero
+    let found = history
+        .find_back(&mut vm, |vm| match vm.stack_contents().first() {
+            Some(Value::Array(array)) => array.get(2) == Some(Value::Integer(0)),
+            _ => false,
+        })
+        .unwrap();
+    assert_eq!(found, Some(17));
+    assert_eq!(ExecutionHistory::position(&vm), 17);
+    assert_eq!(history.find_back(&mut vm, |vm| vm.stack_size() > 10).unwrap(), None);
+    assert_eq!(ExecutionHistory::position(&vm), 17);
+}
+
+fn elements_below_top(vm: &VirtualMachine) -> Vec<Value> {
+    match vm.stack_contents().first() {
+        Some(Value::Array(array)) => array.to_vec(),
+        other => panic!("expected an array, found {:?}", other),
+    }
+}
+
+#[test]
+fn test_history_replays_streams() {
+    let mut vm = load("    READ_LINE\n    PRINT\n    READ_LINE\n    PRINT\n    HALT\n");
+    let io = MemoryIo::with_input("first\nsecond\n");
+    vm.set_io(io.clone());
+    let mut history = ExecutionHistory::new(&mut vm, 100);
+    history.go_to(&mut vm, 4).unwrap();
+    assert_eq!(io.stdout(), "first\nsecond\n");
+
+    // Going back and forward again reads the same lines from the history
+    // and writes nothing twice
+    history.go_to(&mut vm, 1).unwrap();
+    assert_eq!(vm.stack_top().unwrap().to_string(), "first");
+    io.push_input("third");
+    history.go_to(&mut vm, 5).unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(io.stdout(), "first\nsecond\n");
+}
@@ -0,0 +1,87 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::instruction::Opcode;
+use stack_vm_jit::vm::runtime::{HookEvent, VirtualMachine};
+use std::sync::{Arc, Mutex};
+
+const PROGRAM: &str = r#"
+    PUSH 4
+    CALL double
+    HALT
+.func double arity=1 returns=1
+    LOAD 0
+    PUSH 2
+    MUL
+    RET
+.endfunc
+"#;
+
+/// Runs PROGRAM with a hook describing every event in one line
+fn trace() -> Vec<String> {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let mut vm = VirtualMachine::new();
+    let sink = Arc::clone(&events);
+    vm.set_hook(Box::new(move |event: &HookEvent| {
+        let line = match event {
+            HookEvent::BeforeInstruction { pc, instruction, stack_depth } => {
+                format!("before {} {:?} {}", pc, instruction.opcode(), stack_depth)
+            }
+            HookEvent::AfterInstruction { pc, stack_depth, .. } => format!("after {} {}", pc, stack_depth),
+            HookEvent::Call { pc, target, depth } => format!("call {} -> {} {}", pc, target, depth),
+            HookEvent::Return { pc, target, depth } => format!("return {} -> {} {}", pc, target, depth),
+            HookEvent::GarbageCollection { collected } => format!("gc {}", collected),
+        };
+        sink.lock().unwrap().push(line);
+    }));
+    vm.load_module(Assembler::new().assemble_module(PROGRAM).unwrap()).unwrap();
+    vm.run().unwrap();
+    vm.trigger_gc();
+    events.lock().unwrap().clone()
+}
+
+#[test]
+fn test_hooks_see_instructions_calls_and_returns() {
+    let events = trace();
+    assert_eq!(
+        events,
+        [
+            "before 0 Push 0",
+            "after 0 1",
+            "before 1 Call 1",
+            "call 1 -> 3 1",
+            "after 1 0",
+            "before 3 Load 0",
+            "after 3 1",
+            "before 4 Push 1",
+            "after 4 2",
+            "before 5 Mul 2",
+            "after 5 1",
+            "before 6 Return 1",
+            "return 6 -> 2 0",
+            "after 6 1",
+            "before 2 Halt 1",
+            "after 2 1",
+            "gc 0",
+        ]
+    );
+}
+
+#[test]
+fn test_clearing_the_hook() {
+    let count = Arc::new(Mutex::new(0));
+    let mut vm = VirtualMachine::new();
+    let counter = Arc::clone(&count);
+    vm.set_hook(Box::new(move |event: &HookEvent| {
+        if let HookEvent::BeforeInstruction { instruction, .. } = event
+            && instruction.opcode() != Opcode::Halt {
+                *counter.lock().unwrap() += 1;
+            }
+    }));
+    vm.load_module(Assembler::new().assemble_module(PROGRAM).unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*count.lock().unwrap(), 6);
+
+    vm.clear_hook();
+    vm.reset();
+    vm.run().unwrap();
+    assert_eq!(*count.lock().unwrap(), 6);
+}
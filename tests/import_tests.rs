@@ -0,0 +1,212 @@
+use stack_vm_jit::vm::assembler::{Assembler, Disassembler};
+use stack_vm_jit::vm::instruction::{ExecutionError, Opcode};
+use stack_vm_jit::vm::linker::{LinkError, Linker};
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+
+const LIB: &str = r#"
+.const SCALE 10
+.export SCALE
+.export scale
+.func scale arity=1 returns=1
+    LOAD 0
+    LDC SCALE
+    MUL
+    RET
+.endfunc
+"#;
+
+const MAIN: &str = r#"
+.extern scale
+.extern SCALE
+.extern OFFSET
+.extern clamp
+    PUSH 4
+    CALL scale
+    LDC SCALE
+    ADD
+    LDC OFFSET
+    ADD
+    CALL clamp
+    HALT
+"#;
+
+fn assemble(source: &str) -> BytecodeModule {
+    Assembler::new().assemble_module(source).unwrap()
+}
+
+fn host_vm() -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.define_global("OFFSET", Value::Integer(100));
+    vm.register_host_function("clamp", 1, 1, |args| match &args[0] {
+        Value::Integer(n) => Ok(vec![Value::Integer((*n).min(120))]),
+        other => Err(format!("cannot clamp {:?}", other).into()),
+    });
+    vm
+}
+
+#[test]
+fn test_imports_resolve_against_modules_and_host() {
+    let mut vm = host_vm();
+    vm.load_modules([assemble(MAIN), assemble(LIB)]).unwrap();
+    vm.run().unwrap();
+    // 4 * 10 + 10 + 100, clamped
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(120));
+    assert_eq!(vm.stack_size(), 1);
+}
+
+#[test]
+fn test_exported_constants_are_globals() {
+    let lib = assemble(LIB);
+    assert_eq!(lib.globals.get("SCALE"), Some(&0));
+
+    let text = Disassembler::new().disassemble_module(&lib).unwrap();
+    assert!(text.contains(".export scale\n.export SCALE\n"));
+    assert_eq!(assemble(&text).globals, lib.globals);
+
+    let restored = BytecodeModule::from_bytes(&lib.to_bytes().unwrap()).unwrap();
+    assert_eq!(restored.globals, lib.globals);
+    #[cfg(feature = "serde")]
+    {
+        let restored = BytecodeModule::from_json_bytes(&lib.to_json_bytes().unwrap()).unwrap();
+        assert_eq!(restored.globals, lib.globals);
+    }
+
+    // Two modules may not export the same global
+    let other = assemble(".const SCALE 2\n.export SCALE\n    HALT");
+    assert!(matches!(
+        Linker::new().add(lib).add(other).link(),
+        Err(LinkError::DuplicateSymbol { ref name, first: 0, second: 1 }) if name == "SCALE"
+    ));
+}
+
+#[test]
+fn test_linker_can_leave_imports_for_the_host() {
+    let main = assemble(MAIN);
+    assert!(matches!(
+        Linker::new().add(main.clone()).add(assemble(LIB)).link(),
+        Err(LinkError::UnresolvedExterns(ref unresolved)) if unresolved.len() == 2
+    ));
+
+    let linked = Linker::new()
+        .add(main)
+        .add(assemble(LIB))
+        .allow_unresolved(true)
+        .link()
+        .unwrap();
+    let names: Vec<&str> = linked.externs.iter().map(|reference| reference.name.as_str()).collect();
+    assert_eq!(names, ["OFFSET", "clamp"]);
+
+    let mut vm = host_vm();
+    vm.load_module(linked).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(120));
+}
+
+#[test]
+fn test_host_globals_in_modules_with_literal_pushes() {
+    // The module has no pool, so PUSH 7 is a literal until OFFSET is added
+    let mut vm = host_vm();
+    vm.load_module(assemble(".extern OFFSET\n    PUSH 7\n    LDC OFFSET\n    ADD\n    LDC OFFSET\n    ADD\n    HALT"))
+        .unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(207));
+    assert_eq!(vm.constants_pool_size(), 2);
+}
+
+#[test]
+fn test_unresolved_imports_are_refused() {
+    let mut vm = VirtualMachine::new();
+    let error = vm.load_module(assemble(MAIN)).unwrap_err();
+    assert!(error.to_string().contains("Unresolved external symbol scale at 1"));
+
+    // A host function cannot stand in for a global, nor the other way round
+    let mut vm = host_vm();
+    assert!(vm.load_module(assemble(".extern clamp\n    LDC clamp\n    HALT")).is_err());
+    assert!(vm.load_module(assemble(".extern OFFSET\n    CALL OFFSET\n    HALT")).is_err());
+
+    assert!(matches!(vm.load_modules([]), Err(VmError::LinkFailed(LinkError::NoModules))));
+}
+
+#[test]
+fn test_host_function_errors() {
+    let mut vm = host_vm();
+    vm.load_module(assemble(".extern clamp\n    PUSH 1.5\n    CALL clamp\n    HALT")).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(matches!(
+        error,
+        VmError::ExecutionErrorAt { error: ExecutionError::HostFunction { ref function, .. }, line: Some(3), .. }
+            if function == "clamp"
+    ));
+    assert!(error.to_string().contains("Host function clamp failed: cannot clamp Float(1.5)"));
+
+    // Host functions must leave the results they declare
+    vm.register_host_function("pair", 0, 2, |_| Ok(vec![Value::Integer(1)]));
+    vm.load_module(assemble(".extern pair\n    CALL pair\n    HALT")).unwrap();
+    assert_eq!(vm.current_instruction().map(|i| i.opcode()), Some(Opcode::CallNative));
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt {
+            error: ExecutionError::StackEffectMismatch { expected: 2, actual: 1, .. },
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_modules_added_later_link_against_those_loaded() {
+    let mut vm = host_vm();
+    vm.load_module(assemble(".export double\n    PUSH 21\n    CALL double\n    HALT\n.func double arity=1 returns=1\n    LOAD 0\n    PUSH 2\n    MUL\n    RET\n.endfunc")).unwrap();
+    vm.add_module(assemble(LIB)).unwrap();
+    assert_eq!(vm.call_function("scale", &[Value::Integer(3)]).unwrap(), Value::Integer(30));
+
+    // Imports resolve against both loaded modules and the host
+    let client = r#"
+.extern double
+.extern scale
+.extern SCALE
+.extern clamp
+.export both
+    HALT
+.func both arity=1 returns=1
+    LOAD 0
+    CALL double
+    CALL scale
+    LDC SCALE
+    ADD
+    CALL clamp
+    RET
+.endfunc
+"#;
+    vm.add_module(assemble(client)).unwrap();
+    assert_eq!(vm.call_function("both", &[Value::Integer(2)]).unwrap(), Value::Integer(50));
+    assert_eq!(vm.call_function("both", &[Value::Integer(9)]).unwrap(), Value::Integer(120));
+
+    // The first module is still the program
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(42)));
+    assert_eq!(vm.functions().len(), 3);
+}
+
+#[test]
+fn test_adding_modules_keeps_the_running_program() {
+    let mut vm = VirtualMachine::new();
+    // Without a program loaded this is load_module, which needs scale
+    assert!(vm.add_module(assemble(".extern scale\n    PUSH 4\n    CALL scale\n    HALT")).is_err());
+
+    vm.load_module(assemble(".const ONE 1\n    PUSH ONE\n    PUSH ONE\n    ADD\n    HALT")).unwrap();
+    vm.step().unwrap();
+    vm.add_module(assemble(LIB)).unwrap();
+    let entry = ".extern scale\n.export entry\n    HALT\n.func entry arity=0 returns=1\n    PUSH 4\n    CALL scale\n    RET\n.endfunc";
+    vm.add_module(assemble(entry)).unwrap();
+    assert_eq!(vm.program_counter(), 1);
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(2)));
+    assert_eq!(vm.call_function("entry", &[]).unwrap(), Value::Integer(40));
+
+    // Exports may not clash
+    assert!(matches!(
+        vm.add_module(assemble(LIB)),
+        Err(VmError::LinkFailed(LinkError::DuplicateSymbol { ref name, .. })) if name == "scale" || name == "SCALE"
+    ));
+    assert_eq!(vm.call_function("scale", &[Value::Integer(1)]).unwrap(), Value::Integer(10));
+}
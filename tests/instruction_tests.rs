@@ -1,5 +1,7 @@
 use stack_vm_jit::vm::call_frame::CallStack;
-use stack_vm_jit::vm::instruction::{Instruction, InstructionDispatcher, Opcode};
+use stack_vm_jit::vm::instruction::{
+    decode_program, encode_program, EncodingError, Instruction, InstructionDispatcher, Opcode,
+};
 use stack_vm_jit::vm::stack::OperandStack;
 use stack_vm_jit::vm::types::Value;
 
@@ -70,6 +72,31 @@ fn test_arithmetic_instructions() {
     assert_eq!(result, Value::Integer(8));
 }
 
+#[test]
+fn test_negate_instruction() {
+    let mut dispatcher = InstructionDispatcher::new();
+    let mut stack = OperandStack::new();
+    let mut call_stack = CallStack::new();
+    let neg_instr = Instruction::new(Opcode::Negate, None);
+
+    for (value, expected) in [
+        (Value::Integer(7), Value::Integer(-7)),
+        (Value::Float(-2.5), Value::Float(2.5)),
+    ] {
+        stack.push(value);
+        dispatcher
+            .execute(&neg_instr, &mut stack, &mut call_stack)
+            .unwrap();
+        assert_eq!(stack.pop().unwrap(), expected);
+    }
+
+    stack.push(Value::Integer(i64::MIN));
+    assert!(dispatcher.execute(&neg_instr, &mut stack, &mut call_stack).is_err());
+
+    stack.push(Value::Boolean(true));
+    assert!(dispatcher.execute(&neg_instr, &mut stack, &mut call_stack).is_err());
+}
+
 #[test]
 fn test_comparison_instructions() {
     let mut dispatcher = InstructionDispatcher::new();
@@ -171,6 +198,8 @@ fn test_instruction_execution_count() {
         .unwrap();
 
     assert_eq!(dispatcher.instruction_count(), 2);
+    // Instructions other than control flow move on to the next one
+    assert_eq!(dispatcher.current_pc(), 2);
 }
 
 #[test]
@@ -184,3 +213,60 @@ fn test_error_handling() {
     let result = dispatcher.execute(&add_instr, &mut stack, &mut call_stack);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_narrow_index_encoding() {
+    let instr = Instruction::new(Opcode::Push, Some(Value::Integer(200)));
+    let mut bytes = Vec::new();
+    instr.encode(&mut bytes).unwrap();
+    assert_eq!(bytes, vec![Opcode::Push as u8, 200]);
+
+    let (decoded, consumed) = Instruction::decode(&bytes).unwrap();
+    assert_eq!(consumed, 2);
+    assert_eq!(decoded.opcode(), Opcode::Push);
+    assert_eq!(decoded.operand(), Some(&Value::Integer(200)));
+}
+
+#[test]
+fn test_wide_index_encoding() {
+    let program = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(70_000))),
+        Instruction::new(Opcode::Store, Some(Value::Integer(300))),
+        Instruction::new(Opcode::Load, Some(Value::Integer(300))),
+        Instruction::new(Opcode::Add, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    let bytes = encode_program(&program).unwrap();
+    assert_eq!(&bytes[..2], &[Opcode::Wide as u8, Opcode::Push as u8]);
+    assert_eq!(bytes.len(), 6 + 6 + 6 + 1 + 1);
+
+    let decoded = decode_program(&bytes).unwrap();
+    assert_eq!(decoded.len(), program.len());
+    for (original, roundtrip) in program.iter().zip(&decoded) {
+        assert_eq!(original.opcode(), roundtrip.opcode());
+        assert_eq!(original.operand(), roundtrip.operand());
+    }
+}
+
+#[test]
+fn test_wide_prefix_errors() {
+    // Wide must precede an opcode that takes an index
+    let bytes = [Opcode::Wide as u8, Opcode::Add as u8];
+    assert_eq!(
+        Instruction::decode(&bytes).unwrap_err(),
+        EncodingError::MisplacedWidePrefix(0)
+    );
+
+    // Truncated wide operand
+    let bytes = [Opcode::Wide as u8, Opcode::Load as u8, 0x01];
+    assert!(matches!(
+        Instruction::decode(&bytes),
+        Err(EncodingError::UnexpectedEnd(_))
+    ));
+
+    // Negative indices cannot be encoded
+    let mut out = Vec::new();
+    let instr = Instruction::new(Opcode::Load, Some(Value::Integer(-1)));
+    assert!(instr.encode(&mut out).is_err());
+}
@@ -1,5 +1,5 @@
 use stack_vm_jit::vm::instruction::{Instruction, Opcode};
-use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
 use stack_vm_jit::vm::types::Value;
 
 #[test] 
@@ -46,15 +46,14 @@ fn test_vm_error_handling() {
         Instruction::new(Opcode::Halt, None),
     ];
     
-    vm.load_bytecode_module(instructions, constants).unwrap();
-    let result = vm.run();
-    assert!(result.is_err());
+    let result = vm.load_bytecode_module(instructions, constants);
+    assert!(matches!(result, Err(VmError::VerificationFailed(_))));
 }
 
 #[test] 
 fn test_vm_performance_limits() {
     // Test that VM respects instruction count limits
-    let mut vm = VirtualMachine::with_max_instructions(5);
+    let mut vm = VirtualMachine::builder().with_max_instructions(5).build();
     
     let constants = vec![Value::Integer(0)];
     let instructions = vec![
@@ -72,14 +71,14 @@ fn test_vm_performance_limits() {
 #[test]
 fn test_mixed_push_modes() {
     // Test that Push works both with constants pool and literal values
-    let constants = vec![Value::String("from_pool".to_string())];
+    let constants = vec![Value::String("from_pool".into())];
     
     let instructions = vec![
         // Push from constants pool (integer operand when pool is not empty)
         Instruction::new(Opcode::Push, Some(Value::Integer(0))),
         
         // Push literal string value 
-        Instruction::new(Opcode::Push, Some(Value::String("literal".to_string()))),
+        Instruction::new(Opcode::Push, Some(Value::String("literal".into()))),
         
         // Push literal boolean (non-integer, so definitely literal)
         Instruction::new(Opcode::Push, Some(Value::Boolean(true))),
@@ -96,4 +95,26 @@ fn test_mixed_push_modes() {
     
     // Note: For this test to work exactly as expected, we'd need stack inspection methods
     // For now, we're just testing that it runs without error
-}
\ No newline at end of file
+}
+#[test]
+fn test_run_to_value() {
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(6))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(7))),
+        Instruction::new(Opcode::Mul, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    let mut vm = VirtualMachine::new();
+    vm.load_program(instructions);
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(42)));
+    // The result stays on the stack
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+
+    // A program leaving nothing behind has no result
+    vm.load_program(vec![Instruction::new(Opcode::Halt, None)]);
+    assert_eq!(vm.run_to_value().unwrap(), None);
+
+    // Errors are reported as with run
+    let mut vm = VirtualMachine::new();
+    assert!(matches!(vm.run_to_value(), Err(VmError::NoProgram)));
+}
@@ -0,0 +1,91 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::container::{sign_container, DIGEST_LEN};
+use stack_vm_jit::vm::loader::ModuleLoader;
+use stack_vm_jit::vm::module::{BytecodeModule, ModuleError};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+const SOURCE: &str = ".string GREETING \"hello\"\n    LDC GREETING\n    HALT";
+
+fn container() -> Vec<u8> {
+    Assembler::new().assemble_module(SOURCE).unwrap().to_bytes().unwrap()
+}
+
+/// Stand-in for a real signature scheme: the digest mixed with a key
+fn sign(key: u8) -> impl Fn(&[u8; DIGEST_LEN]) -> Vec<u8> {
+    move |digest| digest.iter().map(|byte| byte ^ key).collect()
+}
+
+fn trusting(key: u8) -> ModuleLoader {
+    ModuleLoader::new().with_signature_verifier(move |digest: &[u8; DIGEST_LEN], signature: Option<&[u8]>| {
+        match signature {
+            Some(signature) if signature == sign(key)(digest) => Ok(()),
+            Some(_) => Err("not signed by a trusted key".to_string()),
+            None => Err("unsigned".to_string()),
+        }
+    })
+}
+
+#[test]
+fn test_tampered_containers_are_refused() {
+    let mut bytes = container();
+    let at = bytes.windows(5).position(|window| window == b"hello").unwrap();
+    bytes[at] = b'j';
+
+    assert!(matches!(BytecodeModule::from_bytes(&bytes), Err(ModuleError::DigestMismatch)));
+    assert!(matches!(
+        BytecodeModule::read_from(bytes.as_slice()),
+        Err(ModuleError::DigestMismatch)
+    ));
+
+    // So is a corrupt digest
+    let mut bytes = container();
+    bytes[6] ^= 1;
+    assert!(matches!(BytecodeModule::from_bytes(&bytes), Err(ModuleError::DigestMismatch)));
+}
+
+#[test]
+fn test_version_one_containers_still_load() {
+    let bytes = container();
+    let mut old = bytes[..6].to_vec();
+    old[4] = 1;
+    // Without the digest or the end section, which version 1 did not have
+    old.extend_from_slice(&bytes[6 + DIGEST_LEN..bytes.len() - 5]);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(BytecodeModule::from_bytes(&old).unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::String("hello".into()));
+}
+
+#[test]
+fn test_signed_containers() {
+    let mut bytes = container();
+    sign_container(&mut bytes, sign(0x5A)).unwrap();
+
+    trusting(0x5A).load(bytes.as_slice()).unwrap();
+    // Loading without a verifier ignores the signature
+    BytecodeModule::from_bytes(&bytes).unwrap();
+
+    assert!(matches!(
+        trusting(0x33).load(bytes.as_slice()),
+        Err(ModuleError::SignatureRejected(ref reason)) if reason == "not signed by a trusted key"
+    ));
+    assert!(matches!(
+        trusting(0x5A).load(container().as_slice()),
+        Err(ModuleError::SignatureRejected(ref reason)) if reason == "unsigned"
+    ));
+}
+
+#[test]
+fn test_signature_must_come_last() {
+    let mut bytes = container();
+    sign_container(&mut bytes, sign(1)).unwrap();
+    sign_container(&mut bytes, sign(1)).unwrap();
+    assert!(matches!(
+        BytecodeModule::from_bytes(&bytes),
+        Err(ModuleError::InvalidContainer(_))
+    ));
+
+    assert!(sign_container(&mut b"SVMB\x01\x00".to_vec(), sign(1)).is_err());
+}
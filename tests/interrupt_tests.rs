@@ -0,0 +1,59 @@
+use stack_vm_jit::vm::assembler::{Assembler, SimpleCompiler};
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+use std::thread;
+use std::time::Duration;
+
+const ENDLESS: &str = "let n = 0; while 1 < 2 { n = n + 1; } n";
+
+#[test]
+fn test_interrupt_from_another_thread() {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(SimpleCompiler::new().compile_program(ENDLESS).unwrap()).unwrap();
+
+    let handle = vm.interrupt_handle();
+    let interrupter = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        handle.interrupt();
+    });
+    let error = vm.run_with_fuel(u64::MAX).unwrap_err();
+    interrupter.join().unwrap();
+    assert!(matches!(error, VmError::Interrupted));
+    assert_eq!(error.to_string(), "Execution interrupted");
+
+    // The interrupt is used up, and the loop picks up where it stopped
+    assert!(!vm.interrupt_handle().is_interrupted());
+    let count = vm.instruction_count();
+    assert!(count > 0);
+    assert_eq!(vm.run_with_fuel(100).unwrap(), RunOutcome::Exhausted);
+    assert_eq!(vm.instruction_count(), count + 100);
+}
+
+#[test]
+fn test_interrupt_before_running() {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module("    PUSH 1\n    HALT").unwrap()).unwrap();
+    let handle = vm.interrupt_handle();
+    handle.clone().interrupt();
+    assert!(handle.is_interrupted());
+
+    assert!(matches!(vm.run(), Err(VmError::Interrupted)));
+    assert_eq!(vm.instruction_count(), 0);
+    assert_eq!(vm.run().unwrap(), RunOutcome::Halted);
+}
+
+#[test]
+fn test_natives_can_interrupt_calls() {
+    let mut vm = VirtualMachine::new();
+    let handle = vm.interrupt_handle();
+    vm.register_native("cancel", 0, move |_| {
+        handle.interrupt();
+        Ok(Value::Null)
+    });
+    let source = ".extern cancel\n.export work\n    HALT\n.func work arity=0 returns=1\n    CALL cancel\n    POP\n    PUSH 1\n    RET\n.endfunc";
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+
+    assert!(matches!(vm.call_function("work", &[]), Err(VmError::Interrupted)));
+    vm.reset();
+    assert_eq!(vm.run().unwrap(), RunOutcome::Halted);
+}
@@ -0,0 +1,91 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::instruction::ExecutionError;
+use stack_vm_jit::vm::io::{MemoryIo, VmIo};
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+use std::io;
+
+fn assemble(source: &str) -> BytecodeModule {
+    Assembler::new().assemble_module(source).unwrap()
+}
+
+/// Echoes input lines back until the input runs out
+const ECHO: &str = r#"
+.loop:
+    READ_LINE
+    DUP
+    PUSH null
+    EQ
+    JT .done
+    PRINT
+    JMP .loop
+.done:
+    HALT
+"#;
+
+#[test]
+fn test_print_and_read_line_use_the_vm_io() {
+    let io = MemoryIo::with_input("first\nsecond line\r\n");
+    let mut vm = VirtualMachine::new();
+    vm.set_io(io.clone());
+    vm.load_module(assemble(ECHO)).unwrap();
+    vm.run().unwrap();
+    assert_eq!(io.stdout(), "first\nsecond line\n");
+    assert_eq!(vm.stack_top().unwrap(), &Value::Null);
+
+    // More input, more output
+    io.push_input("third");
+    assert_eq!(io.take_stdout(), "first\nsecond line\n");
+    vm.reset();
+    vm.run().unwrap();
+    assert_eq!(io.stdout(), "third\n");
+}
+
+#[test]
+fn test_host_functions_share_the_streams() {
+    let io = MemoryIo::with_input("42");
+    let mut vm = VirtualMachine::builder().with_io(io.clone()).build();
+    vm.register_native_with_context("shout", 1, |context, args| {
+        context.io().write_stdout(&format!("{}!\n", args[0]))?;
+        Ok(Value::Null)
+    });
+    let source = ".extern read_line\n.extern println\n.extern eprintln\n.extern print\n.extern shout\n    CALL read_line\n    DUP\n    CALL println\n    DUP\n    CALL eprintln\n    DUP\n    CALL print\n    CALL shout\n    HALT";
+    vm.load_module(assemble(source)).unwrap();
+    vm.run().unwrap();
+    assert_eq!(io.stdout(), "42\n4242!\n");
+    assert_eq!(io.stderr(), "42\n");
+}
+
+/// Streams that fail every operation
+struct Broken;
+
+impl VmIo for Broken {
+    fn write_stdout(&mut self, _: &str) -> io::Result<()> {
+        Err(io::Error::other("stdout closed"))
+    }
+
+    fn write_stderr(&mut self, _: &str) -> io::Result<()> {
+        Err(io::Error::other("stderr closed"))
+    }
+
+    fn read_line(&mut self) -> io::Result<Option<String>> {
+        Err(io::Error::other("stdin closed"))
+    }
+}
+
+#[test]
+fn test_io_failures_stop_the_program() {
+    let mut vm = VirtualMachine::new();
+    vm.set_io(Broken);
+    vm.load_module(assemble("    PUSH 1\n    PRINT\n    HALT")).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(matches!(
+        error,
+        VmError::ExecutionErrorAt { error: ExecutionError::Io(ref message), line: Some(2), .. }
+            if message == "stdout closed"
+    ));
+
+    vm.load_module(assemble(ECHO)).unwrap();
+    assert!(vm.run().unwrap_err().to_string().contains("I/O error: stdin closed"));
+}
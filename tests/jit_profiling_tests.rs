@@ -227,6 +227,33 @@ fn test_vm_profiling_integration() {
 }
 
 #[test]
+fn test_type_profiles_find_integer_sites() {
+    let mut vm = VirtualMachine::new();
+    vm.enable_profiling();
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(3))),
+        Instruction::new(Opcode::Mul, None),                        // 2: integers
+        Instruction::new(Opcode::Push, Some(Value::Float(0.5))),
+        Instruction::new(Opcode::Add, None),                        // 4: integer and float
+        Instruction::new(Opcode::Push, Some(Value::Float(6.0))),
+        Instruction::new(Opcode::LessThan, None),                   // 6: floats
+        Instruction::new(Opcode::Halt, None),
+    ];
+    vm.load_program(instructions);
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Boolean(false));
+
+    let profiler = vm.get_profiler().unwrap();
+    assert_eq!(profiler.get_type_profile(2).unwrap().get_type_frequency("integer"), 2);
+    assert_eq!(profiler.get_type_profile(4).unwrap().get_type_frequency("float"), 1);
+    assert!(profiler.get_type_profile(3).is_none());
+    assert_eq!(profiler.integer_sites(1.0), [2]);
+    assert_eq!(profiler.integer_sites(0.5), [2, 4]);
+}
+
+#[test]
+#[cfg(feature = "serde")]
 fn test_profiling_data_export() {
     let mut profiler = HotSpotProfiler::new();
     
@@ -253,6 +280,30 @@ fn test_profiling_data_export() {
     assert_eq!(new_profiler.get_loop_count(5), 100);
 }
 
+#[test]
+#[cfg(feature = "serde")]
+fn test_profiling_data_export_keeps_instructions() {
+    let mut profiler = HotSpotProfiler::new();
+    for _ in 0..3 {
+        profiler.record_instruction_execution(4, Opcode::Add);
+    }
+    profiler.record_instruction_execution(9, Opcode::Halt);
+
+    let mut new_profiler = HotSpotProfiler::new();
+    new_profiler.import_profile_data(&profiler.export_profile_data()).unwrap();
+    let add = new_profiler.get_instruction_profile(4).unwrap();
+    assert_eq!((add.opcode, add.execution_count), (Opcode::Add, 3));
+    assert_eq!(new_profiler.get_hot_instructions(1).len(), 2);
+
+    // Profiles from before instructions were exported still import
+    let mut old_profiler = HotSpotProfiler::new();
+    old_profiler
+        .import_profile_data(r#"{"function_counts":{"1":2},"loop_counts":{},"type_profiles":{},"branch_profiles":{}}"#)
+        .unwrap();
+    assert_eq!(old_profiler.get_function_count(1), 2);
+    assert!(old_profiler.get_hot_instructions(1).is_empty());
+}
+
 #[test]
 fn test_profiling_reset() {
     let mut profiler = HotSpotProfiler::new();
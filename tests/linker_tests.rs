@@ -0,0 +1,100 @@
+use stack_vm_jit::vm::assembler::{Assembler, AssemblerError, Disassembler};
+use stack_vm_jit::vm::linker::{LinkError, Linker};
+use stack_vm_jit::vm::module::ExternRef;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+const MAIN: &str = r#"
+.extern square_plus_bias
+    PUSH 7
+    CALL square_plus_bias
+    JMP done
+done:
+    HALT
+"#;
+
+const LIBRARY: &str = r#"
+.const BIAS 100
+.func square_plus_bias arity=1 returns=1
+    LOAD 0
+    DUP
+    MUL
+    PUSH BIAS
+    ADD
+    RET
+.endfunc
+"#;
+
+#[test]
+fn test_link_and_run() {
+    let main = Assembler::new().assemble_module(MAIN).unwrap();
+    assert_eq!(
+        main.externs,
+        vec![ExternRef { name: "square_plus_bias".to_string(), address: 1 }]
+    );
+    let library = Assembler::new().assemble_module(LIBRARY).unwrap();
+
+    let linked = Linker::new().add(main).add(library).link().unwrap();
+    assert!(linked.externs.is_empty());
+    assert_eq!(linked.instructions.len(), 10);
+    assert_eq!(linked.function("square_plus_bias").map(|f| f.address), Some(4));
+    assert_eq!(linked.symbols.labels.get("done"), Some(&3));
+    assert_eq!(linked.symbols.constants.get("BIAS"), Some(&0));
+    assert_eq!(linked.debug_info.lines.line_at(0), Some(3));
+    assert_eq!(linked.debug_info.lines.line_at(4), Some(4));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(linked).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(149));
+}
+
+#[test]
+fn test_unresolved_and_duplicate_symbols() {
+    let main = Assembler::new()
+        .assemble_module(".extern missing\n.extern also_missing\nCALL missing\nJMP also_missing")
+        .unwrap();
+    match Linker::new().add(main.clone()).link() {
+        Err(LinkError::UnresolvedExterns(unresolved)) => {
+            let names: Vec<&str> = unresolved.iter().map(|r| r.name.as_str()).collect();
+            assert_eq!(names, vec!["missing", "also_missing"]);
+            assert_eq!((unresolved[1].module, unresolved[1].address), (0, 1));
+        }
+        other => panic!("expected unresolved externs, got {:?}", other),
+    }
+
+    // An unlinked module cannot be run
+    let mut vm = VirtualMachine::new();
+    assert!(vm.load_module(main).is_err());
+
+    let library = Assembler::new().assemble_module(LIBRARY).unwrap();
+    let error = Linker::new().add(library.clone()).add(library).link().unwrap_err();
+    assert_eq!(
+        error,
+        LinkError::DuplicateSymbol { name: "square_plus_bias".to_string(), first: 0, second: 1 }
+    );
+    assert_eq!(Linker::new().link().unwrap_err(), LinkError::NoModules);
+}
+
+#[test]
+fn test_extern_declarations_in_assembly() {
+    let error = Assembler::new()
+        .assemble(".extern f\nPUSH f")
+        .unwrap_err();
+    assert!(error.to_string().contains("can only be a jump or call target"));
+
+    let error = Assembler::new()
+        .assemble(".extern f\n.func f\nRET\n.endfunc")
+        .unwrap_err();
+    assert!(matches!(error, AssemblerError::DuplicateLabel { .. }));
+
+    // Unlinked references disassemble back to their names
+    let main = Assembler::new().assemble_module(MAIN).unwrap();
+    let text = Disassembler::new().disassemble_module(&main).unwrap();
+    assert!(text.contains(".extern square_plus_bias"));
+    assert!(text.contains("CALL square_plus_bias"));
+    let again = Assembler::new().assemble_module(&text).unwrap();
+    assert_eq!(again.externs, main.externs);
+    assert_eq!(again.instructions.len(), main.instructions.len());
+    assert_eq!(again.instructions[1].operand(), Some(&Value::Integer(0)));
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "serde")]
+
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::module::{BytecodeModule, ModuleError};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+const SOURCE: &str = r#"
+.string GREETING "hello"
+    LDC GREETING
+    POP
+    LDC GREETING
+    CALL shout
+    HALT
+.func shout arity=1 returns=1
+    LOAD 0
+    LOAD 0
+    EQ
+    JF .empty
+    LOAD 0
+    RET
+.empty:
+    LDC GREETING
+    RET
+.endfunc
+"#;
+
+fn listing(module: &BytecodeModule) -> serde_json::Value {
+    serde_json::from_str(&module.to_json().unwrap()).unwrap()
+}
+
+#[test]
+fn test_listing_resolves_names() {
+    let module = Assembler::new().with_source_name("shout.svm").assemble_module(SOURCE).unwrap();
+    let json = listing(&module);
+
+    assert_eq!(json["constants"][0]["names"], serde_json::json!(["GREETING"]));
+    assert_eq!(json["constants"][0]["value"], serde_json::json!({ "String": "hello" }));
+    assert_eq!(json["functions"][0]["name"], "shout");
+    assert_eq!(json["functions"][0]["end"], 13);
+    assert_eq!(json["files"][0]["name"], "shout.svm");
+
+    let instructions = json["instructions"].as_array().unwrap();
+    assert_eq!(instructions.len(), module.instructions.len());
+    assert_eq!(instructions[0]["mnemonic"], "LDC");
+    assert_eq!(instructions[0]["operand"], serde_json::json!({ "Integer": 0 }));
+    assert_eq!(instructions[0]["constant"], serde_json::json!({ "String": "hello" }));
+    assert_eq!(instructions[0]["line"], 3);
+    assert_eq!(instructions[3]["target"], "shout");
+    assert_eq!(instructions[5]["function"], "shout");
+    assert_eq!(instructions[8]["target"], "shout.empty");
+    assert_eq!(instructions[11]["labels"], serde_json::json!(["shout.empty"]));
+    // Fields with nothing to say are left out
+    assert!(instructions[1].get("operand").is_none());
+    assert!(instructions[1].get("labels").is_none());
+}
+
+#[test]
+fn test_listing_roundtrip() {
+    let module = Assembler::new().with_source_name("shout.svm").assemble_module(SOURCE).unwrap();
+    let restored = BytecodeModule::from_json(&module.to_json().unwrap()).unwrap();
+
+    assert_eq!(restored.constants, module.constants);
+    assert_eq!(restored.functions, module.functions);
+    assert_eq!(restored.symbols, module.symbols);
+    assert_eq!(restored.debug_info, module.debug_info);
+    assert_eq!(restored.to_json().unwrap(), module.to_json().unwrap());
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(restored).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::String("hello".into()));
+}
+
+#[test]
+fn test_listing_importer_accepts_hand_written_json() {
+    // Long mnemonics in any case, and no debug fields
+    let json = r#"{
+        "version": 1,
+        "constants": [],
+        "functions": [],
+        "instructions": [
+            { "address": 0, "mnemonic": "push", "operand": { "Integer": 6 } },
+            { "address": 1, "mnemonic": "PUSH", "operand": { "Integer": 7 } },
+            { "address": 2, "mnemonic": "MULTIPLY" },
+            { "address": 3, "mnemonic": "HALT" }
+        ]
+    }"#;
+    let mut vm = VirtualMachine::new();
+    vm.load_module(BytecodeModule::from_json(json).unwrap()).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+}
+
+#[test]
+fn test_listing_importer_errors() {
+    let error = |json: &str| match BytecodeModule::from_json(json) {
+        Err(ModuleError::Serialization(message)) => message,
+        other => panic!("expected a serialization error, got {:?}", other.map(|_| ())),
+    };
+
+    assert!(error("[]").contains("invalid length"));
+    assert!(error(r#"{"version":2,"constants":[],"functions":[],"instructions":[]}"#)
+        .contains("unsupported listing version 2"));
+    assert!(error(r#"{"version":1,"constants":[],"functions":[],"instructions":[{"address":0,"mnemonic":"FLY"}]}"#)
+        .contains("unknown mnemonic FLY"));
+    assert!(error(r#"{"version":1,"constants":[],"functions":[],"instructions":[{"address":1,"mnemonic":"HALT"}]}"#)
+        .contains("listed at address 1"));
+}
@@ -0,0 +1,151 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::container::DIGEST_LEN;
+use stack_vm_jit::vm::loader::ModuleLoader;
+use stack_vm_jit::vm::module::{BytecodeModule, ModuleError};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+use std::io::{self, Cursor, Read};
+
+const SOURCE: &str = r#"
+    PUSH 6
+    PUSH 7
+    CALL mul
+    HALT
+.func mul arity=2 returns=1
+    LOAD 0
+    LOAD 1
+    MUL
+    RET
+.endfunc
+"#;
+
+fn container() -> Vec<u8> {
+    Assembler::new().assemble_module(SOURCE).unwrap().to_bytes().unwrap()
+}
+
+/// Hands out at most `chunk` bytes per read, like a slow socket
+struct Trickle<'a> {
+    data: &'a [u8],
+    chunk: usize,
+    reads: usize,
+}
+
+impl Read for Trickle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.chunk.min(buf.len()).min(self.data.len());
+        buf[..len].copy_from_slice(&self.data[..len]);
+        self.data = &self.data[len..];
+        self.reads += 1;
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_load_from_reader() {
+    let bytes = container();
+    let module = BytecodeModule::read_from(Cursor::new(&bytes)).unwrap();
+    assert_eq!(module.functions.len(), 1);
+
+    let mut reader = Trickle { data: &bytes, chunk: 3, reads: 0 };
+    let module = ModuleLoader::new().load(&mut reader).unwrap();
+    assert!(reader.reads > bytes.len() / 3);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+}
+
+#[test]
+fn test_load_stops_at_the_end_of_a_container() {
+    let first = container();
+    let second = Assembler::new().assemble_module("PUSH 9\n    HALT").unwrap().to_bytes().unwrap();
+    let mut stream = first.clone();
+    stream.extend_from_slice(&second);
+    stream.extend_from_slice(b"rest");
+
+    // Containers back to back in one stream load one at a time
+    let mut reader = Trickle { data: &stream, chunk: 7, reads: 0 };
+    let loader = ModuleLoader::new();
+    assert_eq!(loader.load(&mut reader).unwrap().functions.len(), 1);
+    assert_eq!(reader.data.len(), second.len() + 4);
+    assert_eq!(loader.load(&mut reader).unwrap().instructions.len(), 2);
+    assert_eq!(reader.data, b"rest");
+
+    // A stream that runs out first is missing the end section
+    let truncated = &first[..first.len() - 5];
+    assert!(matches!(
+        BytecodeModule::read_from(truncated),
+        Err(ModuleError::InvalidContainer(ref message)) if message == "missing end section"
+    ));
+}
+
+#[test]
+fn test_load_stops_at_a_bad_header() {
+    let mut bytes = container();
+    bytes[4] = 99;
+    let mut reader = Trickle { data: &bytes, chunk: 6, reads: 0 };
+    assert!(matches!(
+        ModuleLoader::new().load(&mut reader),
+        Err(ModuleError::UnsupportedContainerVersion(99))
+    ));
+    // Nothing past the header was read
+    assert_eq!(reader.data.len(), bytes.len() - 6);
+}
+
+#[test]
+fn test_load_enforces_limits() {
+    let bytes = container();
+    assert!(matches!(
+        ModuleLoader::new().with_max_section_len(4).load(Cursor::new(&bytes)),
+        Err(ModuleError::TooLarge { limit: 4, .. })
+    ));
+    assert!(matches!(
+        ModuleLoader::new()
+            .with_max_container_len(bytes.len() as u64 - 1)
+            .load(Cursor::new(&bytes)),
+        Err(ModuleError::TooLarge { .. })
+    ));
+    ModuleLoader::new()
+        .with_max_container_len(bytes.len() as u64)
+        .load(Cursor::new(&bytes))
+        .unwrap();
+
+    // A huge declared length fails on the limit, not on allocation
+    let mut huge = bytes[..6 + DIGEST_LEN].to_vec();
+    huge.push(1);
+    huge.extend_from_slice(&u32::MAX.to_le_bytes());
+    assert!(matches!(
+        BytecodeModule::read_from(Cursor::new(&huge)),
+        Err(ModuleError::TooLarge { .. })
+    ));
+}
+
+#[test]
+fn test_load_rejects_truncated_data() {
+    let bytes = container();
+    for len in [0, 3, 8, bytes.len() / 2, bytes.len() - 1] {
+        assert!(
+            matches!(
+                BytecodeModule::read_from(Cursor::new(&bytes[..len])),
+                Err(ModuleError::InvalidContainer(_))
+            ),
+            "truncated to {} bytes",
+            len
+        );
+    }
+}
+
+#[test]
+fn test_load_reports_io_errors() {
+    struct Failing;
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::ConnectionReset, "gone"))
+        }
+    }
+    assert!(matches!(
+        BytecodeModule::read_from(Failing),
+        Err(ModuleError::Io(ref e)) if e.kind() == io::ErrorKind::ConnectionReset
+    ));
+}
@@ -130,8 +130,8 @@ fn test_logical_operations_with_truthy_values() {
     assert_eq!(stack.pop().unwrap(), Value::Boolean(false)); // 42 AND 0 = false
     
     // Test string values
-    stack.push(Value::String("hello".to_string()));
-    stack.push(Value::String("".to_string()));
+    stack.push(Value::String("hello".into()));
+    stack.push(Value::String("".into()));
     
     dispatcher.execute(&and_instruction, &mut stack, &mut call_stack).unwrap();
     
@@ -160,4 +160,25 @@ fn test_logical_operations_error_handling() {
     let result = dispatcher.execute(&not_instruction, &mut stack, &mut call_stack);
     
     assert!(result.is_err());
-}
\ No newline at end of file
+}
+#[test]
+fn test_null_coalesce_operation() {
+    let mut dispatcher = InstructionDispatcher::new();
+    let mut stack = OperandStack::new();
+    let mut call_stack = CallStack::new();
+    
+    let coalesce_instruction = Instruction::new(Opcode::NullCoalesce, None);
+    
+    // Null falls back to the default
+    stack.push(Value::Null);
+    stack.push(Value::Integer(7));
+    dispatcher.execute(&coalesce_instruction, &mut stack, &mut call_stack).unwrap();
+    assert_eq!(stack.pop().unwrap(), Value::Integer(7));
+    
+    // Falsy but non-null values are kept
+    stack.push(Value::Boolean(false));
+    stack.push(Value::Integer(7));
+    dispatcher.execute(&coalesce_instruction, &mut stack, &mut call_stack).unwrap();
+    assert_eq!(stack.pop().unwrap(), Value::Boolean(false));
+    assert_eq!(stack.size(), 0);
+}
@@ -0,0 +1,154 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::heap::{Heap, Object};
+use stack_vm_jit::vm::instruction::ExecutionError;
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::{MemoryUsage, VirtualMachine, VmError};
+use stack_vm_jit::vm::stack::StackError;
+use stack_vm_jit::vm::types::Value;
+
+// Pushes forever without popping
+const FLOOD: &str = ".loop:\n    PUSH 1\n    JMP .loop";
+
+fn assemble(source: &str) -> BytecodeModule {
+    Assembler::new().assemble_module(source).unwrap()
+}
+
+#[test]
+fn test_memory_limit_stops_growing_stacks() {
+    let value_size = std::mem::size_of::<Value>();
+    let mut vm = VirtualMachine::builder().with_memory_limit(100 * value_size).build();
+    assert_eq!(vm.memory_limit(), Some(100 * value_size));
+    vm.load_module_unverified(assemble(FLOOD)).unwrap();
+
+    let error = vm.run().unwrap_err();
+    let expected = MemoryUsage { stack_bytes: 101 * value_size, heap_bytes: 0 };
+    assert!(matches!(
+        error,
+        VmError::MemoryLimitExceeded { limit, usage } if limit == 100 * value_size && usage == expected
+    ));
+    assert!(error.to_string().contains(&format!("({} bytes of stack, 0 bytes of heap)", 101 * value_size)));
+    assert_eq!(vm.memory_usage(), expected);
+
+    // Raising the limit lets the program carry on to the new one
+    vm.set_memory_limit(200 * value_size);
+    assert!(matches!(vm.resume(), Err(VmError::MemoryLimitExceeded { .. })));
+    assert_eq!(vm.stack_size(), 201);
+
+    vm.clear_memory_limit();
+    assert!(vm.step().is_ok());
+}
+
+#[test]
+fn test_memory_limit_counts_the_heap() {
+    let source = ".loop:\n    PUSH 1\n    PUSH 2\n    PUSH 3\n    NEW_ARRAY 3\n    JMP .loop";
+    let mut vm = VirtualMachine::new();
+    vm.set_memory_limit(4096);
+    vm.load_module_unverified(assemble(source)).unwrap();
+
+    let Err(VmError::MemoryLimitExceeded { usage, .. }) = vm.run() else {
+        panic!("the arrays should have run out of memory");
+    };
+    assert!(usage.heap_bytes > 0);
+    assert!(usage.total() > 4096);
+    assert!(usage.total() - 4096 < 4096);
+}
+
+#[test]
+fn test_memory_limit_counts_only_live_objects() {
+    // Builds and drops a small array 10,000 times, far more bytes in total
+    // than the limit but never more than one array at a time
+    let source = r#"
+    PUSH 0
+.loop:
+    DUP
+    PUSH 10000
+    LT
+    JF .done
+    PUSH 1
+    PUSH 2
+    NEW_ARRAY 2
+    POP
+    PUSH 1
+    ADD
+    JMP .loop
+.done:
+    HALT
+"#;
+    let mut vm = VirtualMachine::builder()
+        .with_memory_limit(64 * 1024)
+        .with_max_instructions(1_000_000)
+        .build();
+    vm.load_module(assemble(source)).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(10_000));
+    assert_eq!(vm.memory_usage().heap_bytes, 0);
+}
+
+#[test]
+fn test_unbounded_stacks_overflow_without_panicking() {
+    let mut vm = VirtualMachine::builder().with_max_instructions(u64::MAX).build();
+    vm.load_module_unverified(assemble(FLOOD)).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::StackError(StackError::Overflow), .. })
+    ));
+    assert_eq!(vm.stack_size(), 1_000_000);
+}
+
+#[test]
+fn test_memory_limit_counts_strings() {
+    // Doubles a string forever
+    let source = "    PUSH \"abcdefghabcdefgh\"\n.loop:\n    DUP\n    STRCAT\n    JMP .loop";
+    let mut vm = VirtualMachine::builder().with_memory_limit(64 * 1024).build();
+    vm.load_module_unverified(assemble(source)).unwrap();
+
+    let Err(VmError::MemoryLimitExceeded { usage, .. }) = vm.run() else {
+        panic!("the strings should have run out of memory");
+    };
+    assert!(usage.heap_bytes > 32 * 1024);
+    assert!(usage.total() < 2 * 64 * 1024);
+
+    // The heap's own limit refuses the string before it is built
+    let mut vm = VirtualMachine::builder().with_heap_limit(64 * 1024).build();
+    vm.load_module_unverified(assemble(source)).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::InvalidOperand(ref message), .. })
+            if message.starts_with("Failed to allocate string")
+    ));
+}
+
+#[test]
+fn test_memory_limit_counts_host_strings() {
+    let mut vm = VirtualMachine::builder().with_memory_limit(64 * 1024).build();
+    vm.register_native("page", 0, |_| Ok(Value::String("x".repeat(4096).into())));
+    vm.load_module_unverified(assemble(".extern page\n.loop:\n    CALL page\n    JMP .loop")).unwrap();
+
+    let Err(VmError::MemoryLimitExceeded { usage, .. }) = vm.run() else {
+        panic!("the host's strings should have run out of memory");
+    };
+    assert!(usage.heap_bytes > 60 * 1024);
+    assert!(vm.stack_size() < 20);
+}
+
+#[test]
+fn test_heap_counts_objects_growing() {
+    let mut heap = Heap::new();
+    let object = heap.allocate_object(Object::new()).unwrap();
+    let array = heap.allocate_array(Vec::new()).unwrap();
+    let size = heap.current_heap_size();
+
+    for i in 0..100 {
+        object.set_field(format!("field_{}", i), Value::Integer(i));
+        array.push(Value::Integer(i));
+    }
+    let grown = heap.current_heap_size() - size;
+    assert!(grown >= 100 * (std::mem::size_of::<(String, Value)>() + std::mem::size_of::<Value>()));
+
+    // Overwriting a field takes no more room
+    object.set_field("field_0", Value::Null);
+    assert_eq!(heap.current_heap_size() - size, grown);
+
+    drop((object, array));
+    assert_eq!(heap.current_heap_size(), 0);
+}
@@ -12,7 +12,7 @@ fn test_load_local_variable() {
     // Create a call frame with some local variables
     let mut frame = CallFrame::new(0, 0, 3);
     frame.set_local(0, Value::Integer(42)).unwrap();
-    frame.set_local(1, Value::String("hello".to_string())).unwrap();
+    frame.set_local(1, Value::String("hello".into())).unwrap();
     frame.set_local(2, Value::Boolean(true)).unwrap();
     call_stack.push_unchecked(frame);
     
@@ -26,7 +26,7 @@ fn test_load_local_variable() {
     let load_instruction = Instruction::new(Opcode::Load, Some(Value::Integer(1)));
     dispatcher.execute(&load_instruction, &mut stack, &mut call_stack).unwrap();
     
-    assert_eq!(stack.pop().unwrap(), Value::String("hello".to_string()));
+    assert_eq!(stack.pop().unwrap(), Value::String("hello".into()));
     
     // Test loading local variable at index 2
     let load_instruction = Instruction::new(Opcode::Load, Some(Value::Integer(2)));
@@ -47,7 +47,7 @@ fn test_store_local_variable() {
     
     // Push values to store
     stack.push(Value::Integer(100));
-    stack.push(Value::String("world".to_string()));
+    stack.push(Value::String("world".into()));
     stack.push(Value::Boolean(false));
     
     // Test storing to local variable at index 2
@@ -65,7 +65,7 @@ fn test_store_local_variable() {
     // Verify the values were stored correctly
     let current_frame = call_stack.current().unwrap();
     assert_eq!(current_frame.get_local(0).unwrap(), &Value::Integer(100));
-    assert_eq!(current_frame.get_local(1).unwrap(), &Value::String("world".to_string()));
+    assert_eq!(current_frame.get_local(1).unwrap(), &Value::String("world".into()));
     assert_eq!(current_frame.get_local(2).unwrap(), &Value::Boolean(false));
 }
 
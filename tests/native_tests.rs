@@ -0,0 +1,240 @@
+use stack_vm_jit::vm::assembler::{Assembler, Disassembler};
+use stack_vm_jit::vm::instruction::{ExecutionError, Opcode};
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::{NativeError, VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+use std::sync::{Arc, Mutex};
+
+fn assemble(source: &str) -> BytecodeModule {
+    Assembler::new().assemble_module(source).unwrap()
+}
+
+fn hypot(args: &[Value]) -> Result<Value, NativeError> {
+    match args {
+        [Value::Float(a), Value::Float(b)] => Ok(Value::Float(a.hypot(*b))),
+        _ => Err(NativeError::new("hypot takes two floats")),
+    }
+}
+
+#[test]
+fn test_natives_are_called_with_their_arguments() {
+    let printed = Arc::new(Mutex::new(Vec::new()));
+    let mut vm = VirtualMachine::new();
+    vm.register_native("hypot", 2, hypot);
+    let sink = Arc::clone(&printed);
+    vm.register_native("print", 1, move |args| {
+        sink.lock().unwrap().push(args[0].to_string());
+        Ok(Value::Null)
+    });
+
+    let source = ".extern hypot\n.extern print\n    PUSH 3.0\n    PUSH 4.0\n    CALL hypot\n    DUP\n    CALL print\n    POP\n    HALT";
+    vm.load_module(assemble(source)).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_top().unwrap(), &Value::Float(5.0));
+    assert_eq!(vm.stack_size(), 1);
+    assert_eq!(*printed.lock().unwrap(), [Value::Float(5.0).to_string()]);
+}
+
+#[test]
+fn test_native_errors_stop_the_program() {
+    let mut vm = VirtualMachine::new();
+    vm.register_native("hypot", 2, hypot);
+    vm.load_module(assemble(".extern hypot\n    PUSH 1.0\n    PUSH 2\n    CALL hypot\n    HALT")).unwrap();
+
+    let error = vm.run().unwrap_err();
+    assert!(matches!(
+        error,
+        VmError::ExecutionErrorAt { error: ExecutionError::HostFunction { ref message, .. }, line: Some(4), .. }
+            if message == "hypot takes two floats"
+    ));
+
+    // Too few arguments on the stack
+    vm.load_module(assemble(".extern hypot\n    PUSH 1.0\n    CALL hypot\n    HALT")).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt {
+            error: ExecutionError::ArityMismatch { expected: 2, available: 1, .. },
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_reregistering_replaces_the_native() {
+    let mut vm = VirtualMachine::new();
+    vm.register_native("answer", 0, |_| Ok(Value::Integer(41)));
+    vm.register_native("answer", 0, |_| Ok(Value::Integer(42)));
+    vm.load_module(assemble(".extern answer\n    CALL answer\n    HALT")).unwrap();
+    assert_eq!(
+        vm.current_instruction().map(|instruction| (instruction.opcode(), instruction.operand().cloned())),
+        Some((Opcode::CallNative, Some(Value::Integer(0))))
+    );
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+}
+
+#[test]
+fn test_call_native_by_index() {
+    let module = assemble("    CALL_NATIVE 0\n    HALT");
+    let text = Disassembler::new().disassemble_module(&module).unwrap();
+    assert!(text.contains("    CALL_NATIVE 0\n"));
+
+    let restored = BytecodeModule::from_bytes(&module.to_bytes().unwrap()).unwrap();
+    assert_eq!(restored.instructions[0].opcode(), Opcode::CallNative);
+
+    // Without a native at that index the call fails when it runs
+    let mut vm = VirtualMachine::new();
+    vm.load_module(restored.clone()).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::InvalidOperand(_), .. })
+    ));
+
+    vm.register_native("zero", 0, |_| Ok(Value::Integer(0)));
+    vm.load_module(restored).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(0));
+}
+
+const CALLBACKS: &str = r#"
+.extern apply
+.export double
+.export sum_to
+    PUSH 5
+    CALL sum_to
+    HALT
+.func double arity=1 returns=1
+    LOAD 0
+    PUSH 2
+    MUL
+    RET
+.endfunc
+.func sum_to arity=1 returns=1
+    LOAD 0
+    JF .done
+    LOAD 0
+    LOAD 0
+    PUSH 1
+    SUB
+    CALL apply
+    ADD
+    RET
+.done:
+    PUSH 0
+    RET
+.endfunc
+"#;
+
+#[test]
+fn test_natives_can_call_back_into_the_guest() {
+    let mut vm = VirtualMachine::new();
+    // apply(n) calls sum_to(n) in the guest, which calls apply again
+    vm.register_native_with_context("apply", 1, |context, args| {
+        Ok(context.call("sum_to", args)?)
+    });
+    vm.load_module(assemble(CALLBACKS)).unwrap();
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(15)));
+    assert_eq!(vm.stack_size(), 1);
+
+    // From outside the VM too
+    assert_eq!(vm.call_function("sum_to", &[Value::Integer(4)]).unwrap(), Value::Integer(10));
+}
+
+#[test]
+fn test_nested_failures_leave_the_caller_intact() {
+    let mut vm = VirtualMachine::new();
+    vm.register_native_with_context("apply", 1, |context, args| {
+        // Wrong arity, then a call that succeeds
+        assert!(context.call("double", &[]).is_err());
+        assert!(matches!(context.call("missing", args), Err(VmError::UnknownFunction(_))));
+        Ok(context.call("double", args)?)
+    });
+    vm.load_module(assemble(CALLBACKS)).unwrap();
+    // sum_to(5) = 5 + double(4)
+    assert_eq!(vm.run_to_value().unwrap(), Some(Value::Integer(13)));
+
+    // An error inside the callback fails the native that made it
+    vm.register_native_with_context("apply", 1, |context, _| {
+        Ok(context.call("double", &[Value::Null])?)
+    });
+    vm.load_module(assemble(CALLBACKS)).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(matches!(
+        error,
+        VmError::ExecutionErrorAt { error: ExecutionError::HostFunction { ref function, .. }, .. }
+            if function == "apply"
+    ));
+    // ...at the call to the native, not inside the callback
+    assert_eq!(vm.current_instruction().map(|i| i.opcode()), Some(Opcode::CallNative));
+}
+
+#[test]
+fn test_nesting_counts_towards_the_call_depth() {
+    let mut vm = VirtualMachine::builder().with_max_call_depth(8).build();
+    // Never reaches the base case
+    vm.register_native_with_context("apply", 1, |context, args| {
+        Ok(context.call("sum_to", &[Value::Integer(1)]).map(|_| args[0].clone())?)
+    });
+    vm.load_module(assemble(CALLBACKS)).unwrap();
+    let error = vm.run().unwrap_err();
+    assert!(error.to_string().contains("Call depth exceeded the limit of"));
+}
+
+#[test]
+fn test_natives_build_values_on_the_heap() {
+    let mut vm = VirtualMachine::new();
+    vm.register_native_with_context("person", 1, |context, args| {
+        let name = context.new_string(args[0].to_string())?;
+        let tags = context.new_array(vec![Value::Integer(1), Value::Integer(2)])?;
+        context.new_object([("name", name), ("tags", tags)])
+    });
+    let source = ".extern person\n    PUSH \"ada\"\n    CALL person\n    DUP\n    GET_FIELD \"tags\"\n    LEN\n    SWAP\n    GET_FIELD \"name\"\n    HALT";
+    vm.load_module(assemble(source)).unwrap();
+    let before = vm.memory_usage().heap_bytes;
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_top().unwrap().to_string(), "ada");
+    assert_eq!(vm.stack_contents()[0], Value::Integer(2));
+    assert_eq!(vm.heap_allocated_objects(), 3);
+    assert!(vm.memory_usage().heap_bytes > before);
+
+    // Allocations count towards the heap limit
+    let mut vm = VirtualMachine::builder().with_heap_limit(64).build();
+    vm.register_native_with_context("bytes", 0, |context, _| context.new_bytes(vec![0; 1024]));
+    vm.load_module(assemble(".extern bytes\n    CALL bytes\n    HALT")).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::HostFunction { ref message, .. }, .. })
+            if message == "Out of memory"
+    ));
+}
+
+#[test]
+fn test_natives_see_globals_and_the_profiler() {
+    let mut vm = VirtualMachine::new();
+    vm.enable_profiling();
+    vm.set_global("calls", Value::Integer(0));
+    vm.register_native_with_context("count", 0, |context, _| {
+        let calls = match context.global("calls") {
+            Some(Value::Integer(calls)) => calls + 1,
+            _ => return Err(NativeError::new("calls is not set")),
+        };
+        context.set_global("calls", Value::Integer(calls));
+        let profiler = context.profiler().ok_or("profiling is off")?;
+        profiler.record_deoptimization(0, "counted");
+        Ok(Value::Integer(profiler.get_deoptimization_count(0).into()))
+    });
+    let source = ".extern count\n    CALL count\n    POP\n    CALL count\n    LDG calls\n    HALT";
+    vm.load_module(assemble(source)).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_contents(), [Value::Integer(2), Value::Integer(2)]);
+    assert_eq!(vm.get_global("calls"), Some(&Value::Integer(2)));
+
+    // Without a profiler the native fails
+    vm.disable_profiling();
+    vm.reset();
+    let error = vm.run().unwrap_err();
+    assert!(error.to_string().contains("Host function count failed: profiling is off"));
+}
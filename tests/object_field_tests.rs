@@ -9,7 +9,7 @@ fn test_get_field_nonexistent() {
     let constants = vec![];
     let instructions = vec![
         Instruction::new(Opcode::NewObject, None),                        // Create object
-        Instruction::new(Opcode::GetField, Some(Value::String("name".to_string()))), // Get field "name"
+        Instruction::new(Opcode::GetField, Some(Value::String("name".into()))), // Get field "name"
         Instruction::new(Opcode::Halt, None),
     ];
     
@@ -51,7 +51,10 @@ fn test_get_field_invalid_operand() {
         Instruction::new(Opcode::Halt, None),
     ];
     
-    vm.load_bytecode_module(instructions, constants).unwrap();
+    // The verifier rejects this at load time; load it unchecked to test
+    // the runtime check as well
+    assert!(vm.load_bytecode_module(instructions.clone(), constants).is_err());
+    vm.load_program(instructions);
     let result = vm.run();
     
     // Should fail due to invalid operand
@@ -69,7 +72,10 @@ fn test_get_field_no_operand() {
         Instruction::new(Opcode::Halt, None),
     ];
     
-    vm.load_bytecode_module(instructions, constants).unwrap();
+    // The verifier rejects this at load time; load it unchecked to test
+    // the runtime check as well
+    assert!(vm.load_bytecode_module(instructions.clone(), constants).is_err());
+    vm.load_program(instructions);
     let result = vm.run();
     
     // Should fail due to missing operand
@@ -83,7 +89,7 @@ fn test_get_field_non_object() {
     let constants = vec![Value::Integer(42)];
     let instructions = vec![
         Instruction::new(Opcode::Push, Some(Value::Integer(0))),         // Push integer
-        Instruction::new(Opcode::GetField, Some(Value::String("field".to_string()))), // Try to get field
+        Instruction::new(Opcode::GetField, Some(Value::String("field".into()))), // Try to get field
         Instruction::new(Opcode::Halt, None),
     ];
     
@@ -95,25 +101,76 @@ fn test_get_field_non_object() {
 }
 
 #[test]
-fn test_set_field_current_limitation() {
+fn test_set_field_updates_object() {
     let mut vm = VirtualMachine::new();
     
-    let constants = vec![Value::Integer(123)];
     let instructions = vec![
         Instruction::new(Opcode::NewObject, None),                       // Create object
-        Instruction::new(Opcode::Push, Some(Value::Integer(0))),        // Push value 123
-        Instruction::new(Opcode::SetField, Some(Value::String("value".to_string()))), // Set field
+        Instruction::new(Opcode::Dup, None),                             // Keep a reference
+        Instruction::new(Opcode::Push, Some(Value::Integer(123))),      // Push value 123
+        Instruction::new(Opcode::SetField, Some(Value::String("value".into()))), // Set field
+        Instruction::new(Opcode::GetField, Some(Value::String("value".into()))), // Read it back
         Instruction::new(Opcode::Halt, None),
     ];
     
-    vm.load_bytecode_module(instructions, constants).unwrap();
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+    
+    // SetField consumes the object and value
+    assert_eq!(vm.stack_size(), 1);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(123));
+}
+
+#[test]
+fn test_set_field_on_frozen_object() {
+    let mut vm = VirtualMachine::new();
+    
+    let instructions = vec![
+        Instruction::new(Opcode::NewObject, None),
+        Instruction::new(Opcode::Freeze, None),                          // Object stays on the stack
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::SetField, Some(Value::String("value".into()))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
     let result = vm.run();
     
-    // Should fail due to current limitation (no interior mutability)
-    assert!(result.is_err());
+    assert!(result.unwrap_err().to_string().contains("frozen"));
+    
+    // Values should be pushed back onto stack, with the object unchanged
+    assert_eq!(vm.stack_size(), 2);
+}
+
+#[test]
+fn test_freeze_object_api() {
+    use stack_vm_jit::vm::heap::Object;
     
-    // But the values should be pushed back onto the stack
-    assert_eq!(vm.stack_size(), 2); // object and value should be back on stack
+    let object = Object::new();
+    assert!(object.set_field("a".to_string(), Value::Integer(1)));
+    assert!(!object.is_frozen());
+    
+    object.freeze();
+    assert!(object.is_frozen());
+    assert!(!object.set_field("a".to_string(), Value::Integer(2)));
+    assert_eq!(object.get_field("a"), Some(Value::Integer(1)));
+    
+    // Copies are independent and writable
+    let copy = object.clone();
+    assert!(copy.set_field("b".to_string(), Value::Null));
+    assert_eq!(object.field_count(), 1);
+}
+
+#[test]
+fn test_freeze_non_object() {
+    let mut vm = VirtualMachine::new();
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Freeze, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    assert!(vm.run().is_err());
 }
 
 #[test]
@@ -124,7 +181,7 @@ fn test_set_field_non_object() {
     let instructions = vec![
         Instruction::new(Opcode::Push, Some(Value::Integer(0))),         // Push integer 42
         Instruction::new(Opcode::Push, Some(Value::Integer(1))),         // Push value 123
-        Instruction::new(Opcode::SetField, Some(Value::String("field".to_string()))), // Try to set field
+        Instruction::new(Opcode::SetField, Some(Value::String("field".into()))), // Try to set field
         Instruction::new(Opcode::Halt, None),
     ];
     
@@ -151,10 +208,10 @@ fn test_object_creation_and_field_access_workflow() {
         
         // Try to get fields from both (should be null)
         Instruction::new(Opcode::Dup, None), // Duplicate second object
-        Instruction::new(Opcode::GetField, Some(Value::String("name".to_string()))),
+        Instruction::new(Opcode::GetField, Some(Value::String("name".into()))),
         
         Instruction::new(Opcode::Swap, None), // Swap to get first object on top
-        Instruction::new(Opcode::GetField, Some(Value::String("id".to_string()))),
+        Instruction::new(Opcode::GetField, Some(Value::String("id".into()))),
         
         Instruction::new(Opcode::Halt, None),
     ];
@@ -184,4 +241,47 @@ fn test_field_access_opcodes_exist() {
     // Test opcode parsing
     assert_eq!(Opcode::from_u8(0x53), Some(Opcode::GetField));
     assert_eq!(Opcode::from_u8(0x54), Some(Opcode::SetField));
-}
\ No newline at end of file
+}
+#[test]
+fn test_safe_get_field_propagates_null() {
+    let mut vm = VirtualMachine::new();
+    
+    // obj?.address?.city ?? "unknown", where obj is null
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Null)),
+        Instruction::new(Opcode::SafeGetField, Some(Value::String("address".into()))),
+        Instruction::new(Opcode::SafeGetField, Some(Value::String("city".into()))),
+        Instruction::new(Opcode::Push, Some(Value::String("unknown".into()))),
+        Instruction::new(Opcode::NullCoalesce, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+    
+    assert_eq!(vm.stack_size(), 1);
+    assert_eq!(vm.stack_top().unwrap(), &Value::String("unknown".into()));
+}
+
+#[test]
+fn test_safe_get_field_on_object_and_non_object() {
+    let mut vm = VirtualMachine::new();
+    let instructions = vec![
+        Instruction::new(Opcode::NewObject, None),
+        Instruction::new(Opcode::SafeGetField, Some(Value::String("missing".into()))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Null);
+    
+    // Only null is propagated; other non-objects are still type errors
+    let mut vm = VirtualMachine::new();
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::SafeGetField, Some(Value::String("x".into()))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    assert!(vm.run().is_err());
+}
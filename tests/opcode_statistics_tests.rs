@@ -0,0 +1,47 @@
+use stack_vm_jit::vm::assembler::SimpleCompiler;
+use stack_vm_jit::vm::instruction::Opcode;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+
+const SUM: &str = "let total = 0; let i = 1; while i <= 10 { total = total + i; i = i + 1; } total";
+
+#[test]
+fn test_opcode_statistics_count_the_instruction_mix() {
+    let mut vm = VirtualMachine::new();
+    assert!(!vm.is_profiling_enabled());
+    vm.load_module(SimpleCompiler::new().compile_program(SUM).unwrap()).unwrap();
+    vm.run().unwrap();
+
+    let statistics = vm.opcode_statistics();
+    // Two additions per iteration, and one more test of the condition
+    assert_eq!(statistics.count(Opcode::Add), 20);
+    assert_eq!(statistics.count(Opcode::LessEqual), 11);
+    assert_eq!(statistics.count(Opcode::JumpIfFalse), 11);
+    assert_eq!(statistics.count(Opcode::Jump), 10);
+    assert_eq!(statistics.count(Opcode::Halt), 1);
+    assert_eq!(statistics.count(Opcode::Mul), 0);
+    assert_eq!(statistics.total(), vm.instruction_count() + 1);
+
+    let most_executed = statistics.most_executed();
+    assert_eq!(most_executed[0], (Opcode::Load, 42));
+    assert_eq!(most_executed.iter().map(|&(_, count)| count).sum::<u64>(), statistics.total());
+    assert!(most_executed.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    assert!(format!("{:?}", statistics).contains("Load: 42, "));
+}
+
+#[test]
+fn test_opcode_statistics_reset() {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(SimpleCompiler::new().compile_program(SUM).unwrap()).unwrap();
+    vm.run().unwrap();
+    let counted = vm.opcode_statistics().clone();
+
+    vm.reset_opcode_statistics();
+    assert_eq!(vm.opcode_statistics().total(), 0);
+
+    // Running again from the start counts the same again
+    vm.reset();
+    vm.run().unwrap();
+    assert_eq!(vm.opcode_statistics(), &counted);
+    vm.reset();
+    assert!(vm.opcode_statistics().most_executed().is_empty());
+}
@@ -0,0 +1,154 @@
+use stack_vm_jit::vm::assembler::{Assembler, SimpleCompiler};
+use stack_vm_jit::vm::instruction::Opcode;
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::optimizer::{optimize_bytes, optimize_module, OptLevel, OptimizationStats};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+fn assemble(source: &str) -> BytecodeModule {
+    Assembler::new().assemble_module(source).unwrap()
+}
+
+/// Stack height and top value left by running `module`, which need not
+/// pass the verifier
+fn run(module: BytecodeModule) -> (usize, Value) {
+    let mut vm = VirtualMachine::new();
+    vm.load_module_unverified(module).unwrap();
+    vm.run().unwrap();
+    (vm.stack_size(), vm.stack_top().unwrap().clone())
+}
+
+fn opcodes(module: &BytecodeModule) -> Vec<Opcode> {
+    module.instructions.iter().map(|instruction| instruction.opcode()).collect()
+}
+
+/// Optimizes a copy of `module` at `level`, checking it still computes
+/// the same
+fn optimized(module: &BytecodeModule, level: OptLevel) -> (BytecodeModule, OptimizationStats) {
+    let mut optimized = module.clone();
+    let stats = optimize_module(&mut optimized, level);
+    assert_eq!(run(optimized.clone()), run(module.clone()));
+    (optimized, stats)
+}
+
+#[test]
+fn test_constant_folding() {
+    let module = assemble("    PUSH 2\n    PUSH 3\n    ADD\n    PUSH 4\n    MUL\n    NEG\n    HALT");
+    let (module, stats) = optimized(&module, OptLevel::Basic);
+    assert_eq!(opcodes(&module), [Opcode::Push, Opcode::Halt]);
+    assert_eq!(module.instructions[0].operand(), Some(&Value::Integer(-20)));
+    assert_eq!(stats.folded, 3);
+
+    // With a pool, integer results are pooled too
+    let module = assemble(".const BIG 1000\n.const SIX 6\n    LDC BIG\n    PUSH 1.5\n    MUL\n    LDC SIX\n    LDC SIX\n    MUL\n    HALT");
+    let (module, _) = optimized(&module, OptLevel::Basic);
+    assert_eq!(opcodes(&module), [Opcode::Push, Opcode::Push, Opcode::Halt]);
+    assert_eq!(module.instructions[0].operand(), Some(&Value::Float(1500.0)));
+    assert_eq!(module.constants[2], Value::Integer(36));
+    assert_eq!(run(module), (2, Value::Integer(36)));
+}
+
+#[test]
+fn test_failing_operations_are_left_for_run_time() {
+    let module = assemble("    PUSH 1\n    PUSH 0\n    DIV\n    HALT");
+    let mut optimized = module.clone();
+    assert!(optimize_module(&mut optimized, OptLevel::Full).is_empty());
+    assert_eq!(opcodes(&optimized), opcodes(&module));
+}
+
+#[test]
+fn test_branches_on_constants() {
+    let source = "    PUSH 1\n    PUSH 1\n    EQ\n    JF else\n    PUSH 10\n    HALT\nelse:\n    PUSH 20\n    HALT";
+    let module = assemble(source);
+
+    // Basic drops the untaken jump but keeps the dead branch
+    let (basic, _) = optimized(&module, OptLevel::Basic);
+    assert_eq!(opcodes(&basic), [Opcode::Push, Opcode::Halt, Opcode::Push, Opcode::Halt]);
+
+    let (full, stats) = optimized(&module, OptLevel::Full);
+    assert_eq!(opcodes(&full), [Opcode::Push, Opcode::Halt]);
+    assert_eq!(stats.unreachable, 2);
+    // Labels on removed code move to the end of the program
+    assert_eq!(full.symbols.labels["else"], 2);
+}
+
+#[test]
+fn test_peephole() {
+    let source = "    PUSH 1\n    DUP\n    PUSH 9\n    POP\n    SWAP\n    SWAP\n    JMP next\nnext:\n    JT hop\n    HALT\nhop:\n    JMP done\ndone:\n    PUSH 5\n    HALT";
+    let module = assemble(source);
+    let (module, stats) = optimized(&module, OptLevel::Basic);
+    assert!(stats.peephole >= 4);
+    assert_eq!(
+        opcodes(&module),
+        [Opcode::Push, Opcode::Dup, Opcode::JumpIfTrue, Opcode::Halt, Opcode::Push, Opcode::Halt]
+    );
+    // The conditional jump skips the jump it used to land on
+    assert_eq!(module.instructions[2].operand(), Some(&Value::Integer(4)));
+}
+
+#[test]
+fn test_jump_targets_are_not_folded_across() {
+    let source = "    PUSH 1\n    DUP\n    JT mid\n    PUSH 2\nmid:\n    PUSH 3\n    ADD\n    HALT";
+    let module = assemble(source);
+    let (optimized, _) = optimized(&module, OptLevel::Basic);
+    // Taking the jump adds 3 to the 1, so PUSH 2 and PUSH 3 stay apart
+    assert!(opcodes(&optimized).contains(&Opcode::Add));
+}
+
+#[test]
+fn test_tables_follow_the_code() {
+    let source = r#"
+    PUSH 2
+    PUSH 3
+    MUL
+    CALL twice
+    HALT
+.func twice arity=1 returns=1
+    PUSH 1
+    POP
+    LOAD 0
+    PUSH 2
+    MUL
+    RET
+.endfunc
+"#;
+    let module = Assembler::new().with_source_name("twice.svm").assemble_module(source).unwrap();
+    let (optimized, _) = optimized(&module, OptLevel::Full);
+
+    let twice = optimized.function("twice").unwrap();
+    assert_eq!(twice.address, 3);
+    assert_eq!(optimized.symbols.functions["twice"], 3..7);
+    assert_eq!(optimized.debug_info.location(3), Some("twice.svm:10".to_string()));
+    assert_eq!(optimized.debug_info.location(0), Some("twice.svm:4".to_string()));
+    assert_eq!(run(optimized), (1, Value::Integer(12)));
+}
+
+#[test]
+fn test_compiled_programs_keep_their_results() {
+    let programs = [
+        "let x = 2 * 3; let y = x + 4; y * 2",
+        "let total = 0; let i = 0; while i < 10 { total = total + i; i = i + 1; } total",
+        "let n = 5; while 1 > 2 { n = n + 1; } n * (3 - 1)",
+    ];
+    for program in programs {
+        let module = SimpleCompiler::new()
+            .with_constant_folding(false)
+            .compile_program(program)
+            .unwrap();
+        let (optimized, _) = optimized(&module, OptLevel::Full);
+        assert!(optimized.instructions.len() <= module.instructions.len());
+    }
+}
+
+#[test]
+fn test_optimize_containers() {
+    let module = assemble("    PUSH 2\n    PUSH 3\n    ADD\n    HALT");
+    let bytes = optimize_bytes(&module.to_bytes().unwrap(), OptLevel::Full).unwrap();
+    let restored = BytecodeModule::from_bytes(&bytes).unwrap();
+    assert_eq!(opcodes(&restored), [Opcode::Push, Opcode::Halt]);
+    assert_eq!(run(restored), (1, Value::Integer(5)));
+
+    let mut untouched = module.clone();
+    assert!(optimize_module(&mut untouched, OptLevel::None).is_empty());
+    assert_eq!(opcodes(&untouched), opcodes(&module));
+}
@@ -0,0 +1,64 @@
+use stack_vm_jit::vm::assembler::{Assembler, SimpleCompiler};
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_host_functions_can_pause_the_program() {
+    let mut vm = VirtualMachine::new();
+    let handle = vm.execution_handle();
+    vm.register_native("checkpoint", 1, move |args| {
+        handle.pause();
+        Ok(args[0].clone())
+    });
+    let source = ".extern checkpoint\n    PUSH 1\n    CALL checkpoint\n    PUSH 2\n    ADD\n    CALL checkpoint\n    PUSH 3\n    ADD\n    HALT";
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+
+    assert!(matches!(vm.run(), Err(VmError::Paused)));
+    // Stopped just after the call, with its result on the stack
+    assert_eq!(vm.program_counter(), 2);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(1));
+    assert!(!vm.is_halted());
+
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Paused);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(3));
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(6));
+}
+
+#[test]
+fn test_pause_from_another_thread() {
+    let mut vm = VirtualMachine::new();
+    let program = SimpleCompiler::new()
+        .compile_program("let n = 0; while 1 < 2 { n = n + 1; } n")
+        .unwrap();
+    vm.load_module(program).unwrap();
+
+    let handle = vm.execution_handle();
+    let pauser = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(20));
+        handle.pause();
+    });
+    assert_eq!(vm.run_with_fuel(u64::MAX).unwrap(), RunOutcome::Paused);
+    pauser.join().unwrap();
+
+    assert!(!vm.execution_handle().is_pause_requested());
+
+    // The loop picks up where it stopped
+    let count = vm.instruction_count();
+    assert!(count > 0);
+    assert_eq!(vm.run_with_fuel(100).unwrap(), RunOutcome::Exhausted);
+    assert_eq!(vm.instruction_count(), count + 100);
+}
+
+#[test]
+fn test_pause_before_running() {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module("    PUSH 1\n    HALT").unwrap()).unwrap();
+    vm.pause();
+    assert_eq!(vm.run_with_fuel(10).unwrap(), RunOutcome::Paused);
+    assert_eq!(vm.instruction_count(), 0);
+    assert_eq!(vm.remaining_fuel(), 10);
+    assert_eq!(vm.resume().unwrap(), RunOutcome::Halted);
+}
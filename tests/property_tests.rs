@@ -0,0 +1,62 @@
+#![cfg(feature = "proptest")]
+
+use proptest::prelude::*;
+use stack_vm_jit::vm::instruction::Instruction;
+use stack_vm_jit::vm::runtime::{HookEvent, RunOutcome, VirtualMachine};
+use stack_vm_jit::vm::strategy::Program;
+use stack_vm_jit::vm::types::Value;
+use stack_vm_jit::vm::verifier::Verifier;
+use std::cmp::Ordering;
+use std::sync::{Arc, Mutex};
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(2000))]
+
+    #[test]
+    fn test_stack_depth_matches_verifier_prediction(program in any::<Program>()) {
+        let depths = Verifier::new(&program.instructions, &program.constants).stack_depths().unwrap();
+        let mismatches = Arc::new(Mutex::new(Vec::new()));
+        let mut vm = VirtualMachine::new();
+        vm.load_module(program.into_module()).unwrap();
+        let seen = Arc::clone(&mismatches);
+        vm.set_hook(Box::new(move |event: &HookEvent| {
+            if let HookEvent::BeforeInstruction { pc, stack_depth, .. } = *event
+                && depths[pc] != Some(stack_depth)
+            {
+                seen.lock().unwrap().push((pc, depths[pc], stack_depth));
+            }
+        }));
+
+        // Runs end at Halt or with an error, never for want of fuel
+        let outcome = vm.run();
+        prop_assert!(!matches!(outcome, Ok(RunOutcome::Exhausted)));
+        prop_assert_eq!(&*mismatches.lock().unwrap(), &[]);
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_values_equal_themselves(value in any::<Value>()) {
+        prop_assert_eq!(value.total_cmp(&value.clone()), Ordering::Equal);
+        let _ = value.to_string();
+    }
+
+    #[test]
+    fn test_instruction_encoding_round_trips(instruction in any::<Instruction>()) {
+        let mut bytes = Vec::new();
+        if instruction.encode(&mut bytes).is_ok() {
+            let (decoded, length) = Instruction::decode(&bytes).unwrap();
+            prop_assert_eq!(length, bytes.len());
+            prop_assert_eq!(decoded.opcode(), instruction.opcode());
+            prop_assert_eq!(decoded.operand(), instruction.operand());
+        }
+    }
+
+    #[test]
+    fn test_verifier_never_panics(
+        instructions in proptest::collection::vec(any::<Instruction>(), 0..32),
+        constants in proptest::collection::vec(any::<Value>(), 0..4),
+    ) {
+        let _ = Verifier::new(&instructions, &constants).stack_depths();
+    }
+}
@@ -0,0 +1,140 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::instruction::{EncodingError, Opcode};
+use stack_vm_jit::vm::io::MemoryIo;
+use stack_vm_jit::vm::recorder::{ExecutionTrace, ReplayError, SideEffect, TraceRecorder, TraceReplayer};
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine};
+
+/// Reads two lines, prints them and keeps how many it read
+const ECHO: &str = "    PUSH 0\n.loop:\n    READ_LINE\n    DUP\n    PUSH null\n    EQ\n    JT .done\n    PRINT\n    PUSH 1\n    ADD\n    DUP\n    STG count\n    JMP .loop\n.done:\n    POP\n    HALT\n";
+
+fn load(source: &str) -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    vm
+}
+
+fn record(source: &str, input: &str) -> (ExecutionTrace, MemoryIo) {
+    let mut vm = load(source);
+    let io = MemoryIo::with_input(input);
+    let mut recorder = TraceRecorder::new(&mut vm, io.clone());
+    assert_eq!(recorder.run(&mut vm, 1000).unwrap(), RunOutcome::Halted);
+    (recorder.into_trace(), io)
+}
+
+#[test]
+fn test_recorder_captures_side_effects() {
+    let (trace, io) = record(ECHO, "a\nb\n");
+    // The program's output still goes where it was sent
+    assert_eq!(io.stdout(), "a\nb\n");
+    assert!(trace.halted());
+    assert_eq!(trace.len(), 1 + 2 * 11 + 5 + 2);
+
+    let first = &trace.records()[0];
+    assert_eq!((first.pc, first.opcode, first.stack_delta), (0, Opcode::Push, 1));
+    assert!(first.effects.is_empty());
+    let read = &trace.records()[1];
+    assert_eq!(read.effects, vec![SideEffect::Input(Some("a".to_string()))]);
+    let print = &trace.records()[6];
+    assert_eq!((print.opcode, print.stack_delta), (Opcode::Print, -1));
+    assert_eq!(print.effects, vec![SideEffect::Output("a\n".to_string())]);
+    let store = &trace.records()[10];
+    assert_eq!(store.effects, vec![SideEffect::Global("1".to_string())]);
+    assert_eq!(store.to_string(), "pc 10: STG, stack -1, global set to 1");
+    assert_eq!(trace.records()[23].effects, vec![SideEffect::Input(None)]);
+}
+
+#[test]
+fn test_recorder_records_failures() {
+    let mut vm = load("    PUSH 1\n    PUSH 0\n    DIV\n    HALT\n");
+    let mut recorder = TraceRecorder::new(&mut vm, MemoryIo::new());
+    let error = recorder.run(&mut vm, 10).unwrap_err();
+    assert!(error.to_string().contains("Division by zero"));
+    let trace = recorder.into_trace();
+    assert_eq!(trace.len(), 3);
+    assert!(!trace.halted());
+    assert!(matches!(&trace.records()[2].effects[..], [SideEffect::Failed(error)] if error.contains("Division by zero")));
+}
+
+#[test]
+fn test_trace_bytes_round_trip() {
+    let (trace, _) = record(ECHO, "héllo\nworld\n");
+    let bytes = trace.to_bytes();
+    assert!(bytes.starts_with(b"SVMT\x01"));
+    // Four bytes for most records, and more only for the text they carry
+    assert!(bytes.len() < 4 * trace.len() + 64);
+    assert_eq!(ExecutionTrace::from_bytes(&bytes).unwrap(), trace);
+
+    assert!(matches!(ExecutionTrace::from_bytes(b"SVMB"), Err(EncodingError::InvalidOperand(_))));
+    assert!(matches!(
+        ExecutionTrace::from_bytes(&bytes[..bytes.len() - 1]),
+        Err(EncodingError::UnexpectedEnd(_))
+    ));
+    let mut bad_opcode = b"SVMT\x00\x01\x00".to_vec();
+    bad_opcode.extend([0xEE, 0x00, 0x00]);
+    assert_eq!(ExecutionTrace::from_bytes(&bad_opcode), Err(EncodingError::UnknownOpcode(0xEE)));
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert!(ExecutionTrace::from_bytes(&trailing).is_err());
+}
+
+#[test]
+fn test_replay_matches_the_recording() {
+    let (trace, _) = record(ECHO, "a\nb\n");
+    // The replay reads the recorded input, not the live VM's
+    let mut vm = load(ECHO);
+    let mut replayer = TraceReplayer::new(&trace, &mut vm);
+    assert!(replayer.step(&mut vm).unwrap());
+    assert_eq!(replayer.steps(), 1);
+    replayer.run(&mut vm).unwrap();
+    assert_eq!(replayer.steps(), trace.len());
+    assert!(vm.is_halted());
+    assert_eq!(vm.get_global("count").unwrap().to_string(), "2");
+}
+
+#[test]
+fn test_replay_finds_the_first_divergence() {
+    let (trace, _) = record(ECHO, "a\nb\n");
+    let changed = ECHO.replace("PUSH 1\n    ADD", "PUSH 2\n    ADD");
+    let mut vm = load(&changed);
+    let error = TraceReplayer::new(&trace, &mut vm).run(&mut vm).unwrap_err();
+    let ReplayError::Diverged { step, expected, actual } = &error else {
+        panic!("expected a divergence, got {:?}", error);
+    };
+    assert_eq!(*step, 10);
+    assert_eq!(expected.effects, vec![SideEffect::Global("1".to_string())]);
+    assert_eq!(actual.effects, vec![SideEffect::Global("2".to_string())]);
+    assert_eq!(
+        error.to_string(),
+        "Step 10 diverged: expected pc 10: STG, stack -1, global set to 1; found pc 10: STG, stack -1, global set to 2"
+    );
+
+    let (other, _) = record(&changed, "a\nb\n");
+    assert_eq!(trace.first_divergence(&other), Some(10));
+    assert_eq!(trace.first_divergence(&trace.clone()), None);
+
+    // A program that stops short of its recording
+    let mut vm = load("    PUSH 0\n    HALT\n");
+    let error = TraceReplayer::new(&trace, &mut vm).run(&mut vm).unwrap_err();
+    assert!(matches!(error, ReplayError::Diverged { step: 1, .. }));
+    let mut vm = load("    PUSH 0\n    HALT\n");
+    let mut recorder = TraceRecorder::new(&mut vm, MemoryIo::new());
+    recorder.step(&mut vm).unwrap();
+    let short = recorder.into_trace();
+    let mut vm = load("    PUSH 0\n    PUSH 1\n    HALT\n");
+    let mut replayer = TraceReplayer::new(&short, &mut vm);
+    replayer.run(&mut vm).unwrap();
+    assert!(!short.halted());
+
+    // A recording of a program that had already halted, and a program
+    // that has halted before its recording starts
+    let mut vm = load("    PUSH 0\n    HALT\n");
+    vm.run().unwrap();
+    let mut recorder = TraceRecorder::new(&mut vm, MemoryIo::new());
+    assert_eq!(recorder.run(&mut vm, 10).unwrap(), RunOutcome::Halted);
+    let finished = recorder.into_trace();
+    assert!(finished.is_empty() && finished.halted());
+    let mut vm = load("    PUSH 0\n    HALT\n");
+    assert_eq!(TraceReplayer::new(&finished, &mut vm).run(&mut vm), Err(ReplayError::NotHalted));
+    vm.run().unwrap();
+    assert_eq!(TraceReplayer::new(&trace, &mut vm).run(&mut vm), Err(ReplayError::HaltedEarly { step: 0 }));
+}
@@ -0,0 +1,75 @@
+use stack_vm_jit::vm::repl::{Repl, ReplError, ReplMode, ReplOutput};
+use stack_vm_jit::vm::types::Value;
+
+#[test]
+fn test_stack_persists_between_lines() {
+    let mut repl = Repl::new();
+    assert_eq!(repl.eval_line("PUSH 5").unwrap(), Some(Value::Integer(5)));
+    assert_eq!(repl.eval_line("PUSH 3").unwrap(), Some(Value::Integer(3)));
+    assert_eq!(repl.eval_line("ADD").unwrap(), Some(Value::Integer(8)));
+    assert_eq!(repl.eval_line("; just a comment").unwrap(), Some(Value::Integer(8)));
+    assert_eq!(repl.vm().stack_size(), 1);
+    assert_eq!(repl.eval_line("POP").unwrap(), None);
+}
+
+#[test]
+fn test_expression_mode() {
+    let mut repl = Repl::with_mode(ReplMode::Expression);
+    assert_eq!(repl.eval_line("(1 + 2) * 3").unwrap(), Some(Value::Integer(9)));
+    assert_eq!(
+        repl.eval_line("\"a\" + \"b\"").unwrap().as_ref().and_then(Value::as_str),
+        Some("ab")
+    );
+
+    // Switching to assembly keeps the stack
+    assert!(matches!(repl.handle(":asm").unwrap(), ReplOutput::Message(_)));
+    assert_eq!(repl.mode(), ReplMode::Assembly);
+    assert_eq!(repl.eval_line("POP").unwrap(), Some(Value::Integer(9)));
+}
+
+#[test]
+fn test_errors_leave_the_repl_usable() {
+    let mut repl = Repl::new();
+    assert!(matches!(repl.eval_line("BOGUS"), Err(ReplError::Assembly(_))));
+    assert!(matches!(repl.eval_line("ADD"), Err(ReplError::Execution(_))));
+    assert_eq!(repl.eval_line("PUSH 1").unwrap(), Some(Value::Integer(1)));
+
+    assert!(matches!(repl.handle(":reset").unwrap(), ReplOutput::Message(_)));
+    assert_eq!(repl.vm().stack_size(), 0);
+    assert!(matches!(repl.handle(":nope").unwrap(), ReplOutput::Message(m) if m.contains(":help")));
+}
+
+#[test]
+fn test_run_loop_transcript() {
+    let input = "PUSH 2\nPUSH 21\nMUL\nPOP\n:expr\n10 / 0\n:quit\nPUSH 1\n";
+    let mut output = Vec::new();
+    Repl::new().run(input.as_bytes(), &mut output).unwrap();
+
+    let text = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = text.split("> ").filter(|line| !line.is_empty()).collect();
+    assert_eq!(lines[0], "2\n");
+    assert_eq!(lines[2], "42\n");
+    assert_eq!(lines[3], "(empty stack)\n");
+    assert!(lines[5].starts_with("error: "));
+    // Nothing after :quit is read
+    assert_eq!(lines.len(), 6);
+    assert!(text.ends_with("> "));
+}
+
+#[test]
+fn test_stack_and_heap_commands() {
+    let mut repl = Repl::new();
+    assert_eq!(repl.handle(":stack").unwrap(), ReplOutput::Message("(empty stack)".to_string()));
+    repl.eval_line("PUSH 1").unwrap();
+    repl.eval_line("PUSH 2").unwrap();
+    assert_eq!(repl.handle(":stack").unwrap(), ReplOutput::Message("   0: 1\n   1: 2".to_string()));
+
+    repl.eval_line("NEW_ARRAY 2").unwrap();
+    let ReplOutput::Message(heap) = repl.handle(":heap").unwrap() else {
+        panic!(":heap should describe the heap");
+    };
+    assert!(heap.starts_with("1 objects, "));
+
+    repl.handle(":reset").unwrap();
+    assert_eq!(repl.handle(":heap").unwrap(), ReplOutput::Message("0 objects, 0 bytes in use, 0 bytes allocated in all".to_string()));
+}
@@ -0,0 +1,174 @@
+#![cfg(feature = "serde")]
+
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::heap::{Heap, Object};
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::module::{BytecodeModule, ModuleError};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::{Value, VmTuple};
+
+#[test]
+fn test_scalar_value_roundtrip() {
+    let values = vec![
+        Value::Integer(-7),
+        Value::Float(2.5),
+        Value::Decimal("0.1".parse().unwrap()),
+        Value::Boolean(true),
+        Value::Char('λ'),
+        Value::String("hello".into()),
+        Value::Function { address: 12, arity: 2 },
+        Value::Tuple(VmTuple::new(vec![Value::Integer(1), Value::Null]).unwrap()),
+        Value::Null,
+    ];
+
+    let json = serde_json::to_string(&values).unwrap();
+    let restored: Vec<Value> = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, values);
+}
+
+#[test]
+fn test_gc_values_serialize_by_deep_copy() {
+    let mut heap = Heap::new();
+
+    let inner = heap.allocate_array(vec![Value::Integer(1), Value::Integer(2)]).unwrap();
+    let object = Object::new();
+    object.set_field("items".to_string(), Value::Array(inner.clone()));
+    object.set_field("raw".to_string(), Value::Bytes(heap.allocate_bytes(vec![1, 2, 3]).unwrap()));
+    object.set_field("ints".to_string(), Value::IntArray(heap.allocate_typed_array(vec![4i64, 5]).unwrap()));
+    object.set_field("floats".to_string(), Value::FloatArray(heap.allocate_typed_array(vec![0.5f64]).unwrap()));
+    let object = Value::GcObject(heap.allocate_object(object).unwrap());
+
+    let json = serde_json::to_string(&object).unwrap();
+    let restored: Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored, object);
+
+    // The copy is independent of the original allocation
+    if let Value::GcObject(copy) = &restored {
+        match copy.get_field("items") {
+            Some(Value::Array(items)) => assert_ne!(items.object_id(), inner.object_id()),
+            other => panic!("Expected array field, got {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_cyclic_value_is_rejected() {
+    let mut heap = Heap::new();
+    let array = heap.allocate_array(vec![]).unwrap();
+    array.push(Value::Array(array.clone()));
+
+    assert!(serde_json::to_string(&Value::Array(array)).is_err());
+}
+
+#[test]
+fn test_program_roundtrip_runs() {
+    let program = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(6))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(7))),
+        Instruction::new(Opcode::Mul, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    let json = serde_json::to_string(&program).unwrap();
+    let restored: Vec<Instruction> = serde_json::from_str(&json).unwrap();
+
+    let mut vm = VirtualMachine::new();
+    vm.load_program(restored);
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(42));
+}
+
+#[test]
+fn test_module_bytes_roundtrip_runs() {
+    let module = Assembler::new()
+        .assemble_module(".string NAME \"two words\"\nPUSH 2.5\nPUSH 4.0\nMUL\nHALT")
+        .unwrap();
+    let restored = BytecodeModule::from_json_bytes(&module.to_json_bytes().unwrap()).unwrap();
+    assert_eq!(restored.constants, vec![Value::String("two words".into())]);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(restored).unwrap();
+    vm.run().unwrap();
+    assert_eq!(*vm.stack_top().unwrap(), Value::Float(10.0));
+}
+
+#[test]
+fn test_module_bytes_errors() {
+    assert!(matches!(
+        BytecodeModule::from_json_bytes(b"not a module"),
+        Err(ModuleError::Serialization(_))
+    ));
+
+    let bytes = br#"{"version":99,"instructions":[],"constants":[],"functions":[]}"#;
+    assert!(matches!(
+        BytecodeModule::from_json_bytes(bytes),
+        Err(ModuleError::UnsupportedVersion(99))
+    ));
+}
+
+#[test]
+fn test_module_bytes_keep_debug_info() {
+    let module = Assembler::new()
+        .assemble_module("PUSH 1\nPUSH 0\n\nDIV\nHALT")
+        .unwrap();
+    let restored = BytecodeModule::from_json_bytes(&module.to_json_bytes().unwrap()).unwrap();
+    assert_eq!(restored.debug_info, module.debug_info);
+    assert_eq!(restored.debug_info.lines.line_at(2), Some(4));
+}
+
+const PIPELINE_SOURCE: &str = r#"
+.string GREETING "hi"
+.export twice
+    LDC GREETING
+    POP
+    HALT
+.func twice arity=1 returns=1
+    LOAD 0
+    DUP
+    ADD
+    RET
+.endfunc
+"#;
+
+#[test]
+fn test_module_in_other_serde_formats() {
+    let module = Assembler::new().assemble_module(PIPELINE_SOURCE).unwrap();
+
+    let mut cbor = Vec::new();
+    ciborium::into_writer(&module, &mut cbor).unwrap();
+    let restored: BytecodeModule = ciborium::from_reader(cbor.as_slice()).unwrap();
+    assert_eq!(restored.constants, module.constants);
+    assert_eq!(restored.functions, module.functions);
+    assert_eq!(restored.symbols, module.symbols);
+    assert_eq!(restored.debug_info, module.debug_info);
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(restored).unwrap();
+    assert_eq!(vm.call_function("twice", &[Value::Integer(21)]).unwrap(), Value::Integer(42));
+
+    // The same layout BytecodeModule::to_json_bytes writes
+    let value = serde_json::to_value(&module).unwrap();
+    assert_eq!(value, serde_json::from_slice::<serde_json::Value>(&module.to_json_bytes().unwrap()).unwrap());
+}
+
+#[test]
+fn test_module_embedded_in_a_document() {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Build {
+        name: String,
+        modules: Vec<BytecodeModule>,
+    }
+
+    let build = Build {
+        name: "app".to_string(),
+        modules: vec![Assembler::new().assemble_module(PIPELINE_SOURCE).unwrap()],
+    };
+    let json = serde_json::to_string(&build).unwrap();
+    let restored: Build = serde_json::from_str(&json).unwrap();
+    assert_eq!(restored.name, "app");
+    assert_eq!(restored.modules[0].functions, build.modules[0].functions);
+
+    let future = json.replace("\"version\":1", "\"version\":99");
+    let error = serde_json::from_str::<Build>(&future).err().unwrap();
+    assert!(error.to_string().contains("Unsupported module format version 99"));
+}
@@ -0,0 +1,69 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use std::time::Duration;
+
+const LOOP: &str = "    PUSH 0\n.loop:\n    PUSH 1\n    ADD\n    DUP\n    PUSH 50\n    LT\n    JT .loop\n    NEW_ARRAY 1\n    HALT";
+
+fn vm(profiling: bool) -> VirtualMachine {
+    let mut vm = VirtualMachine::builder()
+        .with_profiling(profiling)
+        .with_jit_thresholds(1, 10)
+        .build();
+    vm.load_module(Assembler::new().assemble_module(LOOP).unwrap()).unwrap();
+    vm
+}
+
+#[test]
+fn test_statistics_of_a_run() {
+    let mut vm = vm(false);
+    assert_eq!(vm.run_time(), Duration::ZERO);
+    vm.run().unwrap();
+    vm.trigger_gc();
+
+    let statistics = vm.statistics();
+    assert_eq!(statistics.instructions, vm.instruction_count());
+    assert_eq!(statistics.run_time_seconds, vm.run_time().as_secs_f64());
+    assert!(statistics.run_time_seconds > 0.0);
+    assert!(statistics.instructions_per_second > 0.0);
+    assert_eq!(statistics.opcodes["PUSH"], 101);
+    assert_eq!(statistics.opcodes["JT"], 50);
+    assert!(!statistics.opcodes.contains_key("MUL"));
+    assert_eq!(statistics.gc.collections, 1);
+    assert_eq!(statistics.gc.collected_objects, 1);
+    assert!(statistics.gc.bytes_allocated > 0);
+    assert!(statistics.jit.is_none());
+
+    vm.reset();
+    let statistics = vm.statistics();
+    assert_eq!(statistics.run_time_seconds, 0.0);
+    assert_eq!(statistics.instructions_per_second, 0.0);
+    assert!(statistics.opcodes.is_empty());
+    // The heap outlives a reset
+    assert_eq!(statistics.gc.collections, 1);
+}
+
+#[test]
+fn test_statistics_with_the_profiler() {
+    let mut vm = vm(true);
+    vm.run().unwrap();
+    let jit = vm.statistics().jit.unwrap();
+    assert_eq!(jit.hot_loops, [1]);
+    assert!(jit.hot_functions.is_empty());
+    assert!(jit.hot_spot_executions >= 49);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_statistics_as_json() {
+    let mut vm = vm(true);
+    vm.run().unwrap();
+    let json: serde_json::Value = serde_json::from_str(&vm.statistics().to_json()).unwrap();
+    assert_eq!(json["instructions"], vm.instruction_count());
+    assert_eq!(json["opcodes"]["ADD"], 50);
+    assert_eq!(json["gc"]["live_objects"], 1);
+    assert_eq!(json["jit"]["hot_loops"], serde_json::json!([1]));
+
+    vm.disable_profiling();
+    let json: serde_json::Value = serde_json::from_str(&vm.statistics().to_json()).unwrap();
+    assert!(json["jit"].is_null());
+}
@@ -0,0 +1,71 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::linker::LinkError;
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::stdlib;
+use stack_vm_jit::vm::types::Value;
+
+fn run_linked(source: &str) -> VirtualMachine {
+    let program = Assembler::new().assemble_module(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(stdlib::link(program).unwrap()).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn test_stdlib_exports() {
+    let module = stdlib::module();
+    for name in ["abs", "min", "max", "array_fill", "array_sum", "to_string", "str_repeat"] {
+        let function = module.function(name).unwrap();
+        assert_eq!(function.returns, Some(1), "{} declares its result", name);
+    }
+}
+
+#[test]
+fn test_numeric_routines() {
+    let cases = [
+        ("PUSH -5\nCALL abs", Value::Integer(5)),
+        ("PUSH 2.5\nCALL abs", Value::Float(2.5)),
+        ("PUSH 3\nPUSH 9\nCALL min", Value::Integer(3)),
+        ("PUSH 3\nPUSH 9\nCALL max", Value::Integer(9)),
+        ("PUSH -1\nPUSH -4\nCALL max", Value::Integer(-1)),
+    ];
+    for (body, expected) in cases {
+        let source = format!(".extern abs\n.extern min\n.extern max\n{}\nHALT", body);
+        let vm = run_linked(&source);
+        assert_eq!(*vm.stack_top().unwrap(), expected, "{}", body);
+        assert_eq!(vm.stack_size(), 1);
+    }
+}
+
+#[test]
+fn test_array_routines() {
+    let vm = run_linked(
+        ".extern array_fill\n.extern array_sum\n\
+         PUSH 0\nPUSH 0\nPUSH 0\nNEW_ARRAY 3\n\
+         PUSH 7\nCALL array_fill\n\
+         CALL array_sum\n\
+         HALT",
+    );
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(21));
+    assert_eq!(vm.stack_size(), 1);
+}
+
+#[test]
+fn test_string_routines() {
+    let vm = run_linked(
+        ".extern to_string\n.extern str_repeat\n\
+         PUSH 42\nCALL to_string\n\
+         PUSH 3\nCALL str_repeat\n\
+         HALT",
+    );
+    assert_eq!(vm.stack_top().unwrap().as_str(), Some("424242"));
+}
+
+#[test]
+fn test_unknown_routine_is_unresolved() {
+    let program = Assembler::new()
+        .assemble_module(".extern sqrt\nPUSH 4\nCALL sqrt\nHALT")
+        .unwrap();
+    assert!(matches!(stdlib::link(program), Err(LinkError::UnresolvedExterns(_))));
+}
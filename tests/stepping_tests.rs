@@ -0,0 +1,75 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+
+const PROGRAM: &str = r#"
+    PUSH 3
+    CALL square_plus_one
+    PUSH 1
+    ADD
+    HALT
+.func square_plus_one arity=1 returns=1
+    LOAD 0
+    CALL square
+    PUSH 1
+    ADD
+    RET
+.endfunc
+.func square arity=1 returns=1
+    LOAD 0
+    LOAD 0
+    MUL
+    RET
+.endfunc
+"#;
+
+fn vm() -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(PROGRAM).unwrap()).unwrap();
+    vm
+}
+
+#[test]
+fn test_step_over_treats_calls_as_one_step() {
+    let mut vm = vm();
+    vm.step_over().unwrap();
+    assert_eq!(vm.program_counter(), 1);
+    vm.step_over().unwrap();
+    // The whole call ran
+    assert_eq!(vm.program_counter(), 2);
+    assert_eq!(vm.call_depth(), 0);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(10));
+}
+
+#[test]
+fn test_step_into_descends_and_step_out_returns() {
+    let mut vm = vm();
+    vm.step_into().unwrap();
+    vm.step_into().unwrap();
+    assert_eq!(vm.call_depth(), 1);
+    assert_eq!(vm.program_counter(), 5);
+
+    // Over the inner call, then out of the outer function
+    vm.step_over().unwrap();
+    vm.step_over().unwrap();
+    assert_eq!(vm.call_depth(), 1);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(9));
+    vm.step_out().unwrap();
+    assert_eq!(vm.call_depth(), 0);
+    assert_eq!(vm.program_counter(), 2);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(10));
+
+    // At the top level, stepping out runs to the end
+    vm.step_out().unwrap();
+    assert!(vm.is_halted());
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(11));
+}
+
+#[test]
+fn test_stepping_over_an_endless_call_stops() {
+    let source = "    CALL spin\n    HALT\n.func spin\nloop:\n    JMP loop\n.endfunc";
+    let mut vm = VirtualMachine::builder().with_max_instructions(50).build();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    assert!(matches!(vm.step_over(), Err(VmError::InvalidProgramState(_))));
+    assert_eq!(vm.call_depth(), 1);
+}
@@ -0,0 +1,207 @@
+use stack_vm_jit::vm::call_frame::CallStack;
+use stack_vm_jit::vm::heap::GcStr;
+use stack_vm_jit::vm::instruction::{Instruction, InstructionDispatcher, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::stack::OperandStack;
+use stack_vm_jit::vm::types::Value;
+
+fn run_program(instructions: Vec<Instruction>) -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+    vm
+}
+
+#[test]
+fn test_char_value_basics() {
+    assert_eq!(Value::Char('a').type_name(), "char");
+    assert!(Value::Char('a').is_truthy());
+    assert!(!Value::Char('\0').is_truthy());
+}
+
+#[test]
+fn test_char_at() {
+    let vm = run_program(vec![
+        Instruction::new(Opcode::Push, Some(Value::String("héllo".into()))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::CharAt, None),
+        Instruction::new(Opcode::Halt, None),
+    ]);
+
+    assert_eq!(vm.stack_top().unwrap(), &Value::Char('é'));
+}
+
+#[test]
+fn test_char_code_roundtrip() {
+    let vm = run_program(vec![
+        Instruction::new(Opcode::Push, Some(Value::Char('A'))),
+        Instruction::new(Opcode::CharCode, None),                 // 65
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Add, None),                      // 66
+        Instruction::new(Opcode::CodeToChar, None),               // 'B'
+        Instruction::new(Opcode::Halt, None),
+    ]);
+
+    assert_eq!(vm.stack_top().unwrap(), &Value::Char('B'));
+}
+
+#[test]
+fn test_char_comparison() {
+    let vm = run_program(vec![
+        Instruction::new(Opcode::Push, Some(Value::Char('a'))),
+        Instruction::new(Opcode::Push, Some(Value::Char('z'))),
+        Instruction::new(Opcode::LessThan, None),
+        Instruction::new(Opcode::Halt, None),
+    ]);
+
+    assert_eq!(vm.stack_top().unwrap(), &Value::Boolean(true));
+}
+
+#[test]
+fn test_char_operation_errors() {
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::String("ab".into()))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+            Instruction::new(Opcode::CharAt, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![],
+    )
+    .unwrap();
+    assert!(vm.run().is_err());
+
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(0xD800))), // surrogate
+            Instruction::new(Opcode::CodeToChar, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![],
+    )
+    .unwrap();
+    assert!(vm.run().is_err());
+}
+
+#[test]
+fn test_substring_is_zero_copy() {
+    let mut dispatcher = InstructionDispatcher::new();
+    let mut stack = OperandStack::new();
+    let mut call_stack = CallStack::new();
+    let substring = Instruction::new(Opcode::Substring, None);
+
+    stack.push(Value::String("héllo world".into()));
+    stack.push(Value::Integer(1));
+    stack.push(Value::Integer(5));
+    dispatcher.execute(&substring, &mut stack, &mut call_stack).unwrap();
+    let outer = stack.pop().unwrap();
+
+    stack.push(outer.clone());
+    stack.push(Value::Integer(1));
+    stack.push(Value::Integer(3));
+    dispatcher.execute(&substring, &mut stack, &mut call_stack).unwrap();
+    let inner = stack.pop().unwrap();
+
+    let (outer_view, inner_view) = match (&outer, &inner) {
+        (Value::StrSlice(outer), Value::StrSlice(inner)) => (outer, inner),
+        other => panic!("expected string slices, got {:?}", other),
+    };
+    assert_eq!(outer_view.as_str(), "éllo");
+    assert_eq!(inner_view.as_str(), "ll");
+    assert!(GcStr::shares_source(outer_view, inner_view));
+
+    // Slices compare by content with ordinary strings
+    assert_eq!(inner.type_name(), "string_slice");
+    assert!(inner.ref_equals(&Value::String("ll".into())));
+}
+
+#[test]
+fn test_split_produces_slices() {
+    let vm = run_program(vec![
+        Instruction::new(Opcode::Push, Some(Value::String("a,bc,,d".into()))),
+        Instruction::new(Opcode::Push, Some(Value::String(",".into()))),
+        Instruction::new(Opcode::Split, None),
+        Instruction::new(Opcode::Halt, None),
+    ]);
+
+    let array = match vm.stack_top().unwrap() {
+        Value::Array(array) => array.to_vec(),
+        other => panic!("expected array, got {:?}", other),
+    };
+    let pieces: Vec<&str> = array.iter().map(|v| v.as_str().unwrap()).collect();
+    assert_eq!(pieces, vec!["a", "bc", "", "d"]);
+    assert!(array.iter().all(|v| matches!(v, Value::StrSlice(_))));
+
+    // Character operations accept slices
+    let vm = run_program(vec![
+        Instruction::new(Opcode::Push, Some(Value::String("x-yz".into()))),
+        Instruction::new(Opcode::Push, Some(Value::String("-".into()))),
+        Instruction::new(Opcode::Split, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::ArrayGet, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::CharAt, None),
+        Instruction::new(Opcode::Halt, None),
+    ]);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Char('z'));
+}
+
+#[test]
+fn test_slice_operation_errors() {
+    let failing = [
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::String("abc".into()))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::Substring, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::String("abc".into()))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(4))),
+            Instruction::new(Opcode::Substring, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::String("abc".into()))),
+            Instruction::new(Opcode::Push, Some(Value::String("".into()))),
+            Instruction::new(Opcode::Split, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+    ];
+
+    for program in failing {
+        let mut vm = VirtualMachine::new();
+        vm.load_bytecode_module(program, vec![]).unwrap();
+        assert!(vm.run().is_err());
+    }
+}
+
+#[test]
+fn test_str_concat() {
+    let vm = run_program(vec![
+        Instruction::new(Opcode::Push, Some(Value::String("n = ".into()))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(4))),
+        Instruction::new(Opcode::StrConcat, None),
+        Instruction::new(Opcode::Push, Some(Value::Char('!'))),
+        Instruction::new(Opcode::StrConcat, None),
+        Instruction::new(Opcode::Halt, None),
+    ]);
+    assert_eq!(vm.stack_top().unwrap().as_str(), Some("n = 4!"));
+
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+            Instruction::new(Opcode::StrConcat, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![],
+    )
+    .unwrap();
+    assert!(vm.run().is_err());
+}
@@ -0,0 +1,122 @@
+use stack_vm_jit::vm::assembler::{Assembler, SimpleCompiler};
+use stack_vm_jit::vm::io::MemoryIo;
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine};
+use stack_vm_jit::vm::types::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const SUM: &str = "let total = 0; let i = 1; while i <= 100 { total = total + i; i = i + 1; } total";
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn test_virtual_machines_are_send() {
+    assert_send::<VirtualMachine>();
+}
+
+#[test]
+fn test_runs_continue_on_another_thread() {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(SimpleCompiler::new().compile_program(SUM).unwrap()).unwrap();
+    assert_eq!(vm.run_with_fuel(100).unwrap(), RunOutcome::Exhausted);
+
+    // Each leg of the run happens on a thread of its own
+    for _ in 0..3 {
+        vm.add_fuel(100);
+        let (returned, outcome) = vm.resume_on_thread().join().unwrap();
+        vm = returned;
+        if outcome.unwrap() == RunOutcome::Halted {
+            break;
+        }
+    }
+    vm.add_fuel(10_000);
+    let (vm, outcome) = thread::spawn(move || {
+        let outcome = vm.resume();
+        (vm, outcome)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(outcome.unwrap(), RunOutcome::Halted);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(5050));
+}
+
+#[test]
+fn test_paused_runs_resume_elsewhere() {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(SimpleCompiler::new().compile_program("let n = 0; while 1 < 2 { n = n + 1; } n").unwrap())
+        .unwrap();
+    vm.add_fuel(u64::MAX);
+    let handle = vm.execution_handle();
+    let worker = vm.resume_on_thread();
+    thread::sleep(Duration::from_millis(10));
+    handle.pause();
+
+    let (mut vm, outcome) = worker.join().unwrap();
+    assert_eq!(outcome.unwrap(), RunOutcome::Paused);
+    let count = vm.instruction_count();
+    assert!(count > 0);
+
+    let (vm, outcome) = thread::spawn(move || {
+        let outcome = vm.run_with_fuel(50);
+        (vm, outcome)
+    })
+    .join()
+    .unwrap();
+    assert_eq!(outcome.unwrap(), RunOutcome::Exhausted);
+    assert_eq!(vm.instruction_count(), count + 50);
+}
+
+#[test]
+fn test_worker_pool_runs_virtual_machines_with_natives() {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let (jobs, queue) = mpsc::channel::<VirtualMachine>();
+    let (results, finished) = mpsc::channel();
+    let queue = Arc::new(Mutex::new(queue));
+    let workers: Vec<_> = (0..2)
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let results = results.clone();
+            thread::spawn(move || {
+                loop {
+                    // Not holding the queue while running
+                    let job = queue.lock().unwrap().recv();
+                    let Ok(mut vm) = job else { break };
+                    let value = vm.run_to_value().unwrap();
+                    results.send((vm, value)).unwrap();
+                }
+            })
+        })
+        .collect();
+
+    let mut outputs = Vec::new();
+    for n in 0..4 {
+        let io = MemoryIo::new();
+        outputs.push(io.clone());
+        let mut vm = VirtualMachine::builder().with_io(io.clone()).build();
+        let counter = Arc::clone(&calls);
+        vm.register_native("square", 1, move |args| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match args[0] {
+                Value::Integer(n) => Ok(Value::Integer(n * n)),
+                _ => Ok(Value::Null),
+            }
+        });
+        let source = format!(".extern square\n.extern println\n    PUSH {}\n    CALL square\n    DUP\n    CALL println\n    HALT", n);
+        vm.load_module(Assembler::new().assemble_module(&source).unwrap()).unwrap();
+        jobs.send(vm).unwrap();
+    }
+    drop(jobs);
+    drop(results);
+
+    let mut squares: Vec<Value> = finished.iter().map(|(_, value)| value.unwrap()).collect();
+    squares.sort_by_key(|value| value.to_string());
+    assert_eq!(squares, [0, 1, 4, 9].map(Value::Integer));
+    assert_eq!(calls.load(Ordering::SeqCst), 4);
+    let printed: Vec<String> = outputs.iter().map(MemoryIo::stdout).collect();
+    assert_eq!(printed, ["0\n", "1\n", "4\n", "9\n"]);
+    for worker in workers {
+        worker.join().unwrap();
+    }
+}
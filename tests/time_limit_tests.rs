@@ -0,0 +1,49 @@
+use stack_vm_jit::vm::assembler::SimpleCompiler;
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use stack_vm_jit::vm::types::Value;
+use std::time::{Duration, Instant};
+
+const FOREVER: &str = "let n = 0; while 1 < 2 { n = n + 1; } n";
+
+fn compile(source: &str) -> BytecodeModule {
+    SimpleCompiler::new().compile_program(source).unwrap()
+}
+
+#[test]
+fn test_time_limit_stops_long_runs() {
+    let mut vm = VirtualMachine::new();
+    vm.set_time_limit(Duration::from_millis(30));
+    assert_eq!(vm.time_limit(), Some(Duration::from_millis(30)));
+    vm.load_module(compile(FOREVER)).unwrap();
+
+    let started = Instant::now();
+    assert!(matches!(
+        vm.run_with_fuel(u64::MAX),
+        Err(VmError::TimeLimitExceeded(limit)) if limit == Duration::from_millis(30)
+    ));
+    assert!(started.elapsed() >= Duration::from_millis(30));
+    assert!(!vm.is_halted());
+
+    // The program stopped at a safepoint and can carry on
+    let count = vm.instruction_count();
+    vm.clear_time_limit();
+    assert_eq!(vm.run_with_fuel(100).unwrap(), RunOutcome::Exhausted);
+    assert_eq!(vm.instruction_count(), count + 100);
+}
+
+#[test]
+fn test_time_limit_from_builder() {
+    let mut vm = VirtualMachine::builder()
+        .with_time_limit(Duration::from_millis(10))
+        .with_max_instructions(u64::MAX)
+        .build();
+    vm.load_module(compile(FOREVER)).unwrap();
+    assert!(matches!(vm.run(), Err(VmError::TimeLimitExceeded(_))));
+    assert!(vm.step_over().is_ok());
+
+    // Programs that finish in time are unaffected
+    vm.load_module(compile("let total = 0; let i = 0; while i < 10 { total = total + i; i = i + 1; } total")).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(45));
+}
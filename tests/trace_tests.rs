@@ -0,0 +1,45 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::runtime::{RunOutcome, VirtualMachine, VmError};
+use stack_vm_jit::vm::trace::{TraceError, TraceWriter};
+
+fn load(source: &str) -> VirtualMachine {
+    let mut vm = VirtualMachine::new();
+    vm.load_module(Assembler::new().assemble_module(source).unwrap()).unwrap();
+    vm
+}
+
+#[test]
+fn test_trace_rows() {
+    let mut vm = load("    PUSH 5\n    PUSH 3\n    ADD\n    POP\n    HALT");
+    let mut tracer = TraceWriter::new(Vec::new());
+    tracer.write_header().unwrap();
+    assert_eq!(tracer.run(&mut vm, 100).unwrap(), RunOutcome::Halted);
+    assert_eq!(tracer.steps(), 5);
+
+    let text = String::from_utf8(tracer.into_inner()).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], " Step |    PC | Opcode       | Operand      | Stack top");
+    assert_eq!(lines[2], "    0 |     0 | PUSH         | 5            | 5");
+    assert_eq!(lines[4], "    2 |     2 | ADD          |              | 8");
+    assert_eq!(lines[5], "    3 |     3 | POP          |              | [empty]");
+    assert_eq!(lines.len(), 7);
+}
+
+#[test]
+fn test_trace_stops_at_the_step_limit_and_errors() {
+    let mut vm = load(".loop:\n    PUSH 1\n    POP\n    JMP .loop");
+    let mut tracer = TraceWriter::new(Vec::new());
+    assert_eq!(tracer.run(&mut vm, 4).unwrap(), RunOutcome::Exhausted);
+    // Tracing again carries on the step count
+    tracer.step(&mut vm).unwrap();
+    assert!(String::from_utf8(tracer.into_inner()).unwrap().ends_with("    4 |     1 | POP          |              | [empty]\n"));
+
+    let mut vm = load("    PUSH 1\n    PUSH 0\n    DIV\n    HALT");
+    let mut tracer = TraceWriter::new(Vec::new());
+    assert!(matches!(
+        tracer.run(&mut vm, 10),
+        Err(TraceError::Execution(VmError::ExecutionErrorAt { pc: 2, .. }))
+    ));
+    let text = String::from_utf8(tracer.into_inner()).unwrap();
+    assert!(text.lines().last().unwrap().starts_with("    2 |     2 | DIV          |              | error: "));
+}
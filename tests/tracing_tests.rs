@@ -0,0 +1,99 @@
+#![cfg(feature = "tracing")]
+
+use stack_vm_jit::vm::assembler::SimpleCompiler;
+use stack_vm_jit::vm::optimizer::{optimize_module, OptLevel};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Metadata, Subscriber};
+
+/// Keeps the level and message of every event, and the names of spans
+#[derive(Clone, Default)]
+struct Recorder {
+    events: Arc<Mutex<Vec<(Level, String)>>>,
+    spans: Arc<Mutex<Vec<String>>>,
+}
+
+struct Message(String);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
+
+impl Subscriber for Recorder {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        let mut spans = self.spans.lock().unwrap();
+        spans.push(span.metadata().name().to_string());
+        Id::from_u64(spans.len() as u64)
+    }
+
+    fn record(&self, _: &Id, _: &Record<'_>) {}
+
+    fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        self.events.lock().unwrap().push((*event.metadata().level(), message.0));
+    }
+
+    fn enter(&self, _: &Id) {}
+
+    fn exit(&self, _: &Id) {}
+}
+
+impl Recorder {
+    fn count(&self, level: Level, message: &str) -> usize {
+        let events = self.events.lock().unwrap();
+        events.iter().filter(|(l, m)| *l == level && m == message).count()
+    }
+}
+
+#[test]
+fn test_execution_is_traced() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut vm = VirtualMachine::builder()
+            .with_profiling(true)
+            .with_jit_thresholds(10, 3)
+            .build();
+        let program = SimpleCompiler::new()
+            .compile_program("let i = 0; while i < 5 { i = i + 1; } i")
+            .unwrap();
+        vm.load_module(program).unwrap();
+        vm.run().unwrap();
+        vm.trigger_gc();
+
+        assert_eq!(recorder.count(Level::INFO, "program loaded"), 1);
+        assert_eq!(
+            recorder.count(Level::TRACE, "instruction"),
+            vm.instruction_count() as usize + 1
+        );
+        assert_eq!(recorder.count(Level::DEBUG, "hot spot: loop"), 1);
+        assert_eq!(recorder.count(Level::DEBUG, "garbage collected"), 1);
+    });
+    assert_eq!(*recorder.spans.lock().unwrap(), ["run"]);
+}
+
+#[test]
+fn test_optimization_is_traced() {
+    let recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || {
+        let mut module = SimpleCompiler::new().compile_program("1 + 2").unwrap();
+        optimize_module(&mut module, OptLevel::None);
+        assert_eq!(recorder.count(Level::DEBUG, "module optimized"), 0);
+        optimize_module(&mut module, OptLevel::Full);
+        assert_eq!(recorder.count(Level::DEBUG, "module optimized"), 1);
+    });
+    assert_eq!(*recorder.spans.lock().unwrap(), ["optimize"]);
+}
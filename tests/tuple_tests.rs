@@ -0,0 +1,104 @@
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::{Value, VmTuple, MAX_TUPLE_LEN};
+
+fn tuple(elements: Vec<Value>) -> Value {
+    Value::Tuple(VmTuple::new(elements).unwrap())
+}
+
+#[test]
+fn test_tuple_value_basics() {
+    let pair = tuple(vec![Value::Integer(1), Value::String("a".into())]);
+
+    assert_eq!(pair.type_name(), "tuple");
+    assert!(pair.is_truthy());
+    assert!(!tuple(vec![]).is_truthy());
+    assert_eq!(pair.to_string(), "(1, \"a\")");
+    assert_eq!(tuple(vec![Value::Null]).to_string(), "(null,)");
+
+    assert!(VmTuple::new(vec![Value::Null; MAX_TUPLE_LEN + 1]).is_none());
+    let full = VmTuple::new((0..MAX_TUPLE_LEN as i64).map(Value::Integer)).unwrap();
+    assert_eq!(full.len(), MAX_TUPLE_LEN);
+    assert_eq!(full.get(MAX_TUPLE_LEN - 1), Some(&Value::Integer(MAX_TUPLE_LEN as i64 - 1)));
+    assert_eq!(full.get(MAX_TUPLE_LEN), None);
+}
+
+#[test]
+fn test_deeply_nested_tuples_print_and_drop() {
+    let mut value = Value::Null;
+    for _ in 0..200_000 {
+        value = tuple(vec![value]);
+    }
+    let text = value.to_string();
+    assert!(text.starts_with("(((("));
+    assert!(text.contains("..."));
+    // Dropping it must not recurse once per level
+    drop(value);
+}
+
+#[test]
+fn test_tuple_equality_is_by_value() {
+    let a = tuple(vec![Value::Integer(1), Value::String("x".into())]);
+    let b = tuple(vec![Value::Integer(1), Value::String("x".into())]);
+    let c = tuple(vec![Value::Integer(1)]);
+
+    assert!(a.ref_equals(&b));
+    assert!(a.deep_equals(&b));
+    assert!(!a.ref_equals(&c));
+    assert!(c.total_cmp(&a).is_lt());
+}
+
+#[test]
+fn test_multiple_return_values() {
+    let mut vm = VirtualMachine::new();
+    vm.load_bytecode_module(
+        vec![
+            Instruction::new(Opcode::Call, Some(Value::Integer(6))),      // 0
+            Instruction::new(Opcode::Dup, None),                          // 1
+            Instruction::new(Opcode::TupleGet, Some(Value::Integer(0))),  // 2 - quotient
+            Instruction::new(Opcode::Swap, None),                         // 3
+            Instruction::new(Opcode::TupleGet, Some(Value::Integer(1))),  // 4 - remainder
+            Instruction::new(Opcode::Halt, None),                         // 5
+            // divmod(17, 5) returns (3, 2)
+            Instruction::new(Opcode::Push, Some(Value::Integer(3))),      // 6
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),      // 7
+            Instruction::new(Opcode::MakeTuple, Some(Value::Integer(2))), // 8
+            Instruction::new(Opcode::Return, None),                       // 9
+        ],
+        vec![],
+    )
+    .unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_size(), 2);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(2));
+    // No heap objects were needed to return both values
+    assert_eq!(vm.heap_allocated_objects(), 0);
+}
+
+#[test]
+fn test_tuple_operation_errors() {
+    let failing = [
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::MakeTuple, Some(Value::Integer(1))),
+            Instruction::new(Opcode::TupleGet, Some(Value::Integer(1))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::MakeTuple, Some(Value::Integer(MAX_TUPLE_LEN as i64 + 1))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::TupleGet, Some(Value::Integer(0))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+    ];
+
+    for program in failing {
+        let mut vm = VirtualMachine::new();
+        vm.load_program(program);
+        assert!(vm.run().is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use stack_vm_jit::vm::heap::Heap;
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::types::Value;
+
+#[test]
+fn test_typed_array_value_basics() {
+    let mut heap = Heap::new();
+
+    let ints = Value::IntArray(heap.allocate_typed_array(vec![1i64, 2, 3]).unwrap());
+    let floats = Value::FloatArray(heap.allocate_typed_array(vec![0.5f64, 2.0]).unwrap());
+    let empty = Value::IntArray(heap.allocate_typed_array(Vec::<i64>::new()).unwrap());
+
+    assert_eq!(ints.type_name(), "int_array");
+    assert_eq!(floats.type_name(), "float_array");
+    assert!(ints.is_truthy());
+    assert!(!empty.is_truthy());
+    assert_eq!(ints.to_string(), "i64[1, 2, 3]");
+    assert_eq!(floats.to_string(), "f64[0.5, 2.0]");
+    assert_eq!(heap.allocated_objects(), 3);
+}
+
+#[test]
+fn test_typed_array_in_place_access() {
+    let mut heap = Heap::new();
+    let array = heap.allocate_typed_array(vec![1.0f64, 2.0, 3.0]).unwrap();
+
+    array.with_slice_mut(|elements| elements.iter_mut().for_each(|x| *x *= 2.0));
+    let sum: f64 = array.with_slice(|elements| elements.iter().sum());
+
+    assert_eq!(sum, 12.0);
+}
+
+#[test]
+fn test_int_array_write_and_read() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(4))),
+        Instruction::new(Opcode::NewIntArray, None),               // [0, 0, 0, 0]
+        Instruction::new(Opcode::Dup, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(-7))),
+        Instruction::new(Opcode::IntArraySet, None),               // a[1] = -7
+        Instruction::new(Opcode::Dup, None),
+        Instruction::new(Opcode::TypedArrayLength, None),
+        Instruction::new(Opcode::Swap, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::IntArrayGet, None),               // a[1]
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_size(), 2);
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(-7));
+}
+
+#[test]
+fn test_float_array_widens_integers() {
+    let mut vm = VirtualMachine::new();
+
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+        Instruction::new(Opcode::NewFloatArray, None),
+        Instruction::new(Opcode::Dup, None),
+        Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+        Instruction::new(Opcode::Push, Some(Value::Integer(3))),
+        Instruction::new(Opcode::FloatArraySet, None),             // a[0] = 3.0
+        Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+        Instruction::new(Opcode::FloatArrayGet, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+
+    vm.load_bytecode_module(instructions, vec![]).unwrap();
+    vm.run().unwrap();
+
+    assert_eq!(vm.stack_top().unwrap(), &Value::Float(3.0));
+}
+
+#[test]
+fn test_typed_array_errors() {
+    let failing = [
+        // Out of bounds
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+            Instruction::new(Opcode::NewIntArray, None),
+            Instruction::new(Opcode::Push, Some(Value::Integer(2))),
+            Instruction::new(Opcode::IntArrayGet, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        // Floats cannot be stored in an int array
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::NewIntArray, None),
+            Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+            Instruction::new(Opcode::Push, Some(Value::Float(1.5))),
+            Instruction::new(Opcode::IntArraySet, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        // Element type is fixed per opcode
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+            Instruction::new(Opcode::NewIntArray, None),
+            Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+            Instruction::new(Opcode::FloatArrayGet, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(-1))),
+            Instruction::new(Opcode::NewFloatArray, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+    ];
+
+    for program in failing {
+        let mut vm = VirtualMachine::new();
+        vm.load_bytecode_module(program, vec![]).unwrap();
+        assert!(vm.run().is_err());
+    }
+}
@@ -0,0 +1,163 @@
+use stack_vm_jit::vm::heap::Heap;
+use stack_vm_jit::vm::types::{Value, ValueKey, VmString};
+use std::collections::HashMap;
+
+#[test]
+fn test_value_is_two_words() {
+    assert_eq!(
+        std::mem::size_of::<Value>(),
+        2 * std::mem::size_of::<usize>()
+    );
+}
+
+#[test]
+fn test_string_clone_shares_buffer() {
+    let original = VmString::from("shared text");
+    let value = Value::String(original.clone());
+    let duplicate = value.clone();
+
+    match duplicate {
+        Value::String(s) => {
+            assert!(VmString::ptr_eq(&s, &original));
+            assert_eq!(s, "shared text");
+        }
+        _ => panic!("Expected string value"),
+    }
+}
+
+#[test]
+fn test_string_equality_is_by_content() {
+    assert_eq!(Value::String("abc".into()), Value::String("abc".into()));
+    assert_ne!(Value::String("abc".into()), Value::String("abd".into()));
+    assert_eq!(format!("{:?}", Value::String("abc".into())), "String(\"abc\")");
+}
+
+#[test]
+fn test_gc_handles_keep_object_ids() {
+    let mut heap = Heap::new();
+    let first = heap.allocate_string("a".to_string()).unwrap();
+    let second = heap.allocate_string("b".to_string()).unwrap();
+
+    assert_ne!(first.object_id(), second.object_id());
+    assert_eq!(first.clone().object_id(), first.object_id());
+}
+
+#[test]
+fn test_total_ordering_across_types() {
+    use std::cmp::Ordering;
+
+    let mut heap = Heap::new();
+    let array = Value::Array(heap.allocate_array(vec![Value::Integer(1)]).unwrap());
+
+    // null < boolean < number < char < string < array < function
+    let ordered = [
+        Value::Null,
+        Value::Boolean(false),
+        Value::Boolean(true),
+        Value::Integer(-5),
+        Value::Float(1.5),
+        Value::Integer(2),
+        Value::Float(f64::NAN),
+        Value::Char('a'),
+        Value::String("apple".into()),
+        Value::String("banana".into()),
+        array,
+        Value::Function { address: 0, arity: 0 },
+    ];
+
+    for window in ordered.windows(2) {
+        assert_eq!(
+            window[0].total_cmp(&window[1]),
+            Ordering::Less,
+            "{:?} should sort before {:?}",
+            window[0],
+            window[1]
+        );
+        assert_eq!(window[1].total_cmp(&window[0]), Ordering::Greater);
+    }
+}
+
+#[test]
+fn test_total_ordering_numeric_ties() {
+    use std::cmp::Ordering;
+
+    assert_eq!(Value::Integer(1).total_cmp(&Value::Float(1.0)), Ordering::Less);
+    assert_eq!(Value::Float(1.0).total_cmp(&Value::Integer(1)), Ordering::Greater);
+    assert_eq!(Value::Float(f64::NAN).total_cmp(&Value::Float(f64::NAN)), Ordering::Equal);
+
+    let mut heap = Heap::new();
+    let gc = Value::GcString(heap.allocate_string("same".to_string()).unwrap());
+    assert_eq!(gc.total_cmp(&Value::String("same".into())), Ordering::Equal);
+}
+
+#[test]
+#[allow(clippy::mutable_key_type)] // ValueKey hashes heap values by id, not by their locked contents
+fn test_value_keys_in_hash_map() {
+    let mut heap = Heap::new();
+    let mut map: HashMap<ValueKey, &str> = HashMap::new();
+
+    map.insert(Value::Integer(1).into(), "int");
+    map.insert(Value::Float(1.0).into(), "float");
+    map.insert(Value::Float(f64::NAN).into(), "nan");
+    map.insert(Value::String("key".into()).into(), "string");
+
+    assert_eq!(map.len(), 4);
+    assert_eq!(map[&Value::Integer(1).into()], "int");
+    assert_eq!(map[&Value::Float(1.0).into()], "float");
+    assert_eq!(map[&Value::Float(-f64::NAN).into()], "nan");
+
+    // Interned GC strings find entries keyed by plain strings
+    let gc_key = Value::GcString(heap.allocate_string("key".to_string()).unwrap());
+    assert_eq!(map[&gc_key.into()], "string");
+
+    // Positive and negative zero are the same key
+    map.insert(Value::Float(0.0).into(), "zero");
+    assert_eq!(map[&Value::Float(-0.0).into()], "zero");
+}
+
+#[test]
+fn test_heap_value_keys_use_identity() {
+    let mut heap = Heap::new();
+    let a = Value::Array(heap.allocate_array(vec![Value::Integer(1)]).unwrap());
+    let b = Value::Array(heap.allocate_array(vec![Value::Integer(1)]).unwrap());
+
+    // Structurally equal values, but different map keys
+    assert_eq!(a, b);
+    assert_ne!(ValueKey::new(a.clone()), ValueKey::new(b));
+    assert_eq!(ValueKey::new(a.clone()), ValueKey::new(a));
+}
+
+#[test]
+fn test_display_scalars() {
+    assert_eq!(Value::Integer(42).to_string(), "42");
+    assert_eq!(Value::Float(2.5).to_string(), "2.5");
+    assert_eq!(Value::Float(2.0).to_string(), "2.0");
+    assert_eq!(Value::Boolean(true).to_string(), "true");
+    assert_eq!(Value::Null.to_string(), "null");
+    assert_eq!(Value::Char('x').to_string(), "x");
+    assert_eq!(Value::String("hello world".into()).to_string(), "hello world");
+}
+
+#[test]
+fn test_display_collections() {
+    let mut heap = Heap::new();
+
+    let array = heap
+        .allocate_array(vec![
+            Value::Integer(1),
+            Value::String("a".into()),
+            Value::Char('c'),
+        ])
+        .unwrap();
+    assert_eq!(Value::Array(array.clone()).to_string(), "[1, \"a\", 'c']");
+
+    let object = stack_vm_jit::vm::heap::Object::new();
+    object.set_field("name".to_string(), Value::String("x".into()));
+    object.set_field("id".to_string(), Value::Integer(7));
+    let object = heap.allocate_object(object).unwrap();
+    assert_eq!(Value::GcObject(object).to_string(), "{id: 7, name: \"x\"}");
+
+    // Self-referential arrays do not recurse forever
+    array.push(Value::Array(array.clone()));
+    assert_eq!(Value::Array(array).to_string(), "[1, \"a\", 'c', [...]]");
+}
@@ -0,0 +1,246 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::compiler::SimpleCompiler;
+use stack_vm_jit::vm::instruction::{Instruction, Opcode};
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::stdlib;
+use stack_vm_jit::vm::types::Value;
+use stack_vm_jit::vm::verifier::{verify_module, VerificationError, Verifier};
+
+fn verify(source: &str) -> Result<(), VerificationError> {
+    let (instructions, constants) = Assembler::new().assemble(source).unwrap();
+    Verifier::new(&instructions, &constants).verify()
+}
+
+fn rejected_at(source: &str) -> usize {
+    match verify(source) {
+        Err(error) => error.pc,
+        Ok(()) => panic!("expected verification to fail for:\n{}", source),
+    }
+}
+
+#[test]
+fn test_accepts_valid_programs() {
+    verify(
+        r#"
+        PUSH 0
+    loop:
+        DUP
+        PUSH 10
+        LT
+        JF done
+        PUSH 1
+        ADD
+        JMP loop
+    done:
+        HALT
+    "#,
+    )
+    .unwrap();
+
+    let (instructions, constants) = SimpleCompiler::new()
+        .compile_expression("(1 + 2) * 3 - 4 / 2")
+        .unwrap();
+    Verifier::new(&instructions, &constants).verify().unwrap();
+
+    verify_module(&stdlib::module()).unwrap();
+}
+
+#[test]
+fn test_rejects_out_of_bounds_targets() {
+    let instructions = vec![
+        Instruction::new(Opcode::Jump, Some(Value::Integer(7))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    let error = Verifier::new(&instructions, &[]).verify().unwrap_err();
+    assert_eq!(error.pc, 0);
+
+    let instructions = vec![
+        Instruction::new(Opcode::Call, Some(Value::Integer(-1))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    assert!(Verifier::new(&instructions, &[]).verify().is_err());
+}
+
+#[test]
+fn test_rejects_mistyped_operands() {
+    let programs = [
+        vec![
+            Instruction::new(Opcode::Jump, Some(Value::String("start".into()))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::Load, Some(Value::Integer(-1))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::NewObject, None),
+            Instruction::new(Opcode::GetField, Some(Value::Boolean(true))),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::Push, None),
+            Instruction::new(Opcode::Halt, None),
+        ],
+        vec![
+            Instruction::new(Opcode::Halt, Some(Value::Integer(1))),
+        ],
+    ];
+    for instructions in programs {
+        assert!(
+            Verifier::new(&instructions, &[]).verify().is_err(),
+            "{:?} should be rejected",
+            instructions
+        );
+    }
+}
+
+#[test]
+fn test_rejects_invalid_constant_indices() {
+    let constants = vec![Value::Integer(1)];
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(0))),
+        Instruction::new(Opcode::LoadConstant, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    let error = Verifier::new(&instructions, &constants).verify().unwrap_err();
+    assert_eq!(error.pc, 1);
+
+    // Without a pool, integer pushes are literals
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(99))),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    Verifier::new(&instructions, &[]).verify().unwrap();
+}
+
+#[test]
+fn test_rejects_stack_underflow() {
+    assert_eq!(rejected_at("PUSH 1\nADD\nHALT"), 1);
+    assert_eq!(rejected_at("POP\nHALT"), 0);
+}
+
+#[test]
+fn test_rejects_inconsistent_stack_depths() {
+    // The taken branch reaches `done` with one more value than the other
+    let pc = rejected_at(
+        r#"
+        PUSH 1
+        PUSH true
+        JT done
+        POP
+    done:
+        HALT
+    "#,
+    );
+    assert_eq!(pc, 4);
+}
+
+#[test]
+fn test_reports_stack_depths() {
+    let (instructions, constants) = Assembler::new()
+        .assemble("PUSH 1\nPUSH 2\nADD\nJMP done\nPOP\ndone:\nCALL 6\nHALT")
+        .unwrap();
+    let depths = Verifier::new(&instructions, &constants).stack_depths().unwrap();
+    // The POP is unreachable, and a call to an undeclared function leaves
+    // the depth unknown
+    assert_eq!(depths, [Some(0), Some(1), Some(2), Some(1), None, Some(1), None]);
+}
+
+#[test]
+fn test_known_depths_survive_undeclared_calls() {
+    // `skip` is reached after an undeclared call and by a jump; the jump's
+    // depth holds from there, so the POP is checked and found to underflow
+    let source = r#"
+        PUSH true
+        JT skip
+        CALL helper
+    skip:
+        POP
+        HALT
+    helper:
+        RET
+    "#;
+    assert_eq!(rejected_at(source), 3);
+
+    let (instructions, constants) = Assembler::new()
+        .assemble(&source.replace("POP", "PUSH 1\n        POP"))
+        .unwrap();
+    let depths = Verifier::new(&instructions, &constants).stack_depths().unwrap();
+    assert_eq!(depths[..6], [Some(0), Some(1), Some(0), Some(0), Some(1), Some(0)]);
+}
+
+#[test]
+fn test_rejects_running_off_the_end() {
+    assert_eq!(rejected_at("PUSH 1\nPOP"), 1);
+}
+
+#[test]
+fn test_checks_declared_function_effects() {
+    let source = r#"
+        PUSH 3
+        CALL pair
+        ADD
+        HALT
+    .func pair arity=1 returns=2
+        LOAD 0
+        DUP
+        RET
+    .endfunc
+    "#;
+    let module = Assembler::new().assemble_module(source).unwrap();
+    verify_module(&module).unwrap();
+
+    let module = Assembler::new()
+        .assemble_module(&source.replace("DUP", "PUSH 1\n        POP"))
+        .unwrap();
+    let error = verify_module(&module).unwrap_err();
+    assert_eq!(error.pc, 7);
+
+    let module = Assembler::new()
+        .assemble_module(&source.replace("PUSH 3\n", ""))
+        .unwrap();
+    let error = verify_module(&module).unwrap_err();
+    assert_eq!(error.pc, 0);
+}
+
+#[test]
+fn test_load_bytecode_module_verifies() {
+    let mut vm = VirtualMachine::new();
+    let instructions = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(1))),
+        Instruction::new(Opcode::Add, None),
+        Instruction::new(Opcode::Halt, None),
+    ];
+    match vm.load_bytecode_module(instructions, vec![]) {
+        Err(VmError::VerificationFailed(error)) => {
+            assert_eq!(error.pc, 1);
+            assert!(error.message.contains("Add"), "{}", error.message);
+        }
+        other => panic!("expected a verification failure, got {:?}", other),
+    }
+    assert!(vm.run().is_err());
+}
+
+#[test]
+fn test_load_module_verifies() {
+    // Each trip round the loop leaves one more value on the stack
+    let source = "    PUSH 0\n.loop:\n    PUSH 1\n    DUP\n    JT .loop\n    HALT";
+    let module = Assembler::new().assemble_module(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    match vm.load_module(module.clone()) {
+        Err(VmError::VerificationFailed(error)) => {
+            assert_eq!(error.pc, 1);
+            assert!(error.message.contains("stack depth"), "{}", error.message);
+        }
+        other => panic!("expected a verification failure, got {:?}", other),
+    }
+    assert!(vm.run().is_err());
+    assert!(matches!(vm.load_modules([module.clone()]), Err(VmError::VerificationFailed(_))));
+
+    let main = Assembler::new().assemble_module("    HALT").unwrap();
+    vm.load_module(main).unwrap();
+    assert!(matches!(vm.add_module(module.clone()), Err(VmError::VerificationFailed(_))));
+
+    // The interpreter's own checks still stand behind an unverified load
+    vm.load_module_unverified(module).unwrap();
+}
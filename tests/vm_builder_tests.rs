@@ -0,0 +1,151 @@
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::module::BytecodeModule;
+use stack_vm_jit::vm::instruction::ExecutionError;
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
+use stack_vm_jit::vm::stack::StackError;
+use stack_vm_jit::vm::types::Value;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+fn assemble(source: &str) -> BytecodeModule {
+    Assembler::new().assemble_module(source).unwrap()
+}
+
+/// Output sink the test can read back after handing it to the VM
+#[derive(Clone, Default)]
+struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+const COUNTDOWN: &str = r#"
+    PUSH 50
+    CALL countdown
+    HALT
+.func countdown arity=1 returns=1
+    LOAD 0
+    JF done
+    LOAD 0
+    PUSH 1
+    SUB
+    CALL countdown
+    RET
+done:
+    LOAD 0
+    RET
+.endfunc
+"#;
+
+#[test]
+fn test_default_builder_matches_new() {
+    let mut vm = VirtualMachine::builder().build();
+    assert!(!vm.is_profiling_enabled());
+    vm.load_module(assemble(COUNTDOWN)).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_top().unwrap(), &Value::Integer(0));
+}
+
+#[test]
+fn test_limits() {
+    let mut vm = VirtualMachine::builder().with_max_call_depth(10).build();
+    vm.load_module(assemble(COUNTDOWN)).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::CallDepthExceeded(10), .. })
+    ));
+
+    let mut vm = VirtualMachine::builder().with_stack_capacity(2).build();
+    vm.load_module(assemble("    PUSH 1\n    PUSH 2\n    ADD\n    DUP\n    DUP\n    HALT")).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::StackError(StackError::Overflow), line: Some(5), .. })
+    ));
+
+    let mut vm = VirtualMachine::builder().with_heap_limit(64).build();
+    vm.load_module(assemble("    PUSH 1000\n    NEW_INT_ARRAY\n    HALT")).unwrap();
+    assert!(vm.run().is_err());
+    let mut vm = VirtualMachine::new();
+    vm.load_module(assemble("    PUSH 1000\n    NEW_INT_ARRAY\n    HALT")).unwrap();
+    vm.run().unwrap();
+
+    let mut vm = VirtualMachine::builder().with_max_instructions(20).build();
+    vm.load_module(assemble(COUNTDOWN)).unwrap();
+    assert!(matches!(vm.run(), Err(VmError::InvalidProgramState(_))));
+
+    // Limits hold across programs
+    let mut vm = VirtualMachine::builder().with_max_call_depth(100).build();
+    vm.load_module(assemble(COUNTDOWN)).unwrap();
+    vm.run().unwrap();
+    vm.load_module(assemble(&COUNTDOWN.replace("PUSH 50", "PUSH 200"))).unwrap();
+    assert!(matches!(
+        vm.run(),
+        Err(VmError::ExecutionErrorAt { error: ExecutionError::CallDepthExceeded(100), .. })
+    ));
+}
+
+#[test]
+fn test_profiling_thresholds() {
+    let mut vm = VirtualMachine::builder()
+        .with_profiling(true)
+        .with_jit_thresholds(2, 3)
+        .build();
+    assert!(vm.is_profiling_enabled());
+
+    let profiler = vm.get_profiler_mut().unwrap();
+    profiler.record_function_entry(7);
+    profiler.record_function_entry(7);
+    profiler.record_loop_iteration(4);
+    profiler.record_loop_iteration(4);
+    assert_eq!(profiler.hot_functions(), [7]);
+    assert!(profiler.hot_loops().is_empty());
+
+    // Profilers enabled later use the same thresholds
+    vm.disable_profiling();
+    vm.enable_profiling();
+    let profiler = vm.get_profiler_mut().unwrap();
+    profiler.record_function_entry(1);
+    assert!(profiler.hot_functions().is_empty());
+    profiler.record_function_entry(1);
+    assert_eq!(profiler.hot_functions(), [1]);
+}
+
+#[test]
+fn test_running_records_calls_and_loops() {
+    let mut vm = VirtualMachine::builder()
+        .with_profiling(true)
+        .with_jit_thresholds(50, 1)
+        .build();
+    vm.load_module(assemble(COUNTDOWN)).unwrap();
+    vm.run().unwrap();
+    let countdown = vm.functions()[0].address;
+    let profiler = vm.get_profiler().unwrap();
+    assert_eq!(profiler.get_function_count(countdown), 51);
+    assert_eq!(profiler.hot_functions(), [countdown]);
+    // Recursion, not loops
+    assert!(profiler.hot_loops().is_empty());
+}
+
+#[test]
+fn test_output_sink() {
+    let output = SharedOutput::default();
+    let mut vm = VirtualMachine::builder().with_output(output.clone()).build();
+
+    let source = ".extern print\n.extern println\n    PUSH 4\n    CALL print\n    PUSH 2\n    CALL println\n    PUSH 1.5\n    CALL println\n    HALT";
+    vm.load_module(assemble(source)).unwrap();
+    vm.run().unwrap();
+
+    let expected = format!("{}{}\n{}\n", Value::Integer(4), Value::Integer(2), Value::Float(1.5));
+    assert_eq!(String::from_utf8(output.0.lock().unwrap().clone()).unwrap(), expected);
+    assert_eq!(vm.stack_size(), 0);
+
+    // Without a sink there is nothing to print to
+    let mut vm = VirtualMachine::new();
+    assert!(vm.load_module(assemble(source)).is_err());
+}
@@ -85,7 +85,7 @@ fn test_mixed_value_types_with_heap() {
     
     let constants = vec![
         Value::Integer(42),
-        Value::String("hello".to_string()),
+        Value::String("hello".into()),
     ];
     
     let instructions = vec![
@@ -1,5 +1,7 @@
-use stack_vm_jit::vm::instruction::{Instruction, Opcode};
-use stack_vm_jit::vm::runtime::VirtualMachine;
+use stack_vm_jit::vm::assembler::Assembler;
+use stack_vm_jit::vm::instruction::{ExecutionError, Instruction, Opcode};
+use stack_vm_jit::vm::module::{BytecodeModule, FunctionInfo};
+use stack_vm_jit::vm::runtime::{VirtualMachine, VmError};
 use stack_vm_jit::vm::types::Value;
 
 #[test]
@@ -54,6 +56,28 @@ fn test_comparison_and_branching() {
     assert_eq!(*result, Value::Integer(200));
 }
 
+#[test]
+fn test_conditional_jump_not_taken_falls_through() {
+    let mut vm = VirtualMachine::new();
+
+    let program = vec![
+        Instruction::new(Opcode::Push, Some(Value::Boolean(false))), // 0
+        Instruction::new(Opcode::JumpIfTrue, Some(Value::Integer(6))), // 1 - not taken
+        Instruction::new(Opcode::Push, Some(Value::Boolean(true))),  // 2
+        Instruction::new(Opcode::JumpIfFalse, Some(Value::Integer(6))), // 3 - not taken
+        Instruction::new(Opcode::Push, Some(Value::Integer(100))),   // 4
+        Instruction::new(Opcode::Halt, None),                        // 5
+        Instruction::new(Opcode::Push, Some(Value::Integer(200))),   // 6
+        Instruction::new(Opcode::Halt, None),                        // 7
+    ];
+
+    vm.load_program(program);
+    vm.run().unwrap();
+
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(100));
+    assert_eq!(vm.stack_size(), 1);
+}
+
 #[test]
 fn test_function_call_and_return() {
     let mut vm = VirtualMachine::new();
@@ -166,6 +190,28 @@ fn test_error_handling() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_integer_overflow_is_an_error() {
+    let cases = [
+        (i64::MAX, 1, Opcode::Add),
+        (i64::MIN, 1, Opcode::Sub),
+        (i64::MAX, 2, Opcode::Mul),
+        (i64::MIN, -1, Opcode::Div),
+        (i64::MIN, -1, Opcode::Mod),
+    ];
+    for (a, b, opcode) in cases {
+        let mut vm = VirtualMachine::new();
+        vm.load_program(vec![
+            Instruction::new(Opcode::Push, Some(Value::Integer(a))),
+            Instruction::new(Opcode::Push, Some(Value::Integer(b))),
+            Instruction::new(opcode, None),
+            Instruction::new(Opcode::Halt, None),
+        ]);
+        let error = vm.run().unwrap_err();
+        assert!(error.to_string().contains("Arithmetic overflow"), "{:?}: {}", opcode, error);
+    }
+}
+
 #[test]
 fn test_vm_reset() {
     let mut vm = VirtualMachine::new();
@@ -186,3 +232,232 @@ fn test_vm_reset() {
     assert_eq!(vm.program_counter(), 0);
     assert!(!vm.is_halted());
 }
+
+#[test]
+fn test_call_indirect_through_function_value() {
+    let mut vm = VirtualMachine::new();
+
+    // Push an argument and a function value, then call it indirectly.
+    // The callee doubles its argument.
+    let program = vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(21))), // 0 - argument
+        Instruction::new(
+            Opcode::Push,
+            Some(Value::Function { address: 4, arity: 1 }),
+        ), // 1 - callee
+        Instruction::new(Opcode::CallIndirect, None),             // 2
+        Instruction::new(Opcode::Halt, None),                     // 3
+        Instruction::new(Opcode::Push, Some(Value::Integer(2))),  // 4 - function body
+        Instruction::new(Opcode::Mul, None),                      // 5
+        Instruction::new(Opcode::Return, None),                   // 6
+    ];
+
+    vm.load_program(program);
+    vm.run().unwrap();
+
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(42));
+    assert_eq!(vm.call_depth(), 0);
+}
+
+#[test]
+fn test_call_indirect_errors() {
+    // Calling a non-function value is a type error
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::new(Opcode::Push, Some(Value::Integer(7))),
+        Instruction::new(Opcode::CallIndirect, None),
+        Instruction::new(Opcode::Halt, None),
+    ]);
+    assert!(vm.run().is_err());
+
+    // Missing arguments for the declared arity
+    let mut vm = VirtualMachine::new();
+    vm.load_program(vec![
+        Instruction::new(
+            Opcode::Push,
+            Some(Value::Function { address: 3, arity: 2 }),
+        ),
+        Instruction::new(Opcode::CallIndirect, None),
+        Instruction::new(Opcode::Halt, None),
+        Instruction::new(Opcode::Return, None),
+    ]);
+    assert!(vm.run().is_err());
+
+    let function = Value::Function { address: 0, arity: 0 };
+    assert_eq!(function.type_name(), "function");
+    assert!(function.is_truthy());
+}
+
+#[test]
+fn test_declared_function_gets_arguments_and_locals() {
+    // sub_then_square(a, b) = (a - b) * (a - b), using a scratch local
+    let source = r#"
+        PUSH 10
+        PUSH 4
+        CALL sub_then_square
+        HALT
+    .func sub_then_square arity=2 locals=3
+        LOAD 0
+        LOAD 1
+        SUB
+        STORE 2
+        LOAD 2
+        LOAD 2
+        MUL
+        RET
+    .endfunc
+    "#;
+    let module = Assembler::new().assemble_module(source).unwrap();
+    assert_eq!(
+        module.functions,
+        vec![FunctionInfo::new("sub_then_square", 4, 2, 3)]
+    );
+
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+
+    // The arguments were moved into the frame, leaving only the result
+    assert_eq!(*vm.stack_top().unwrap(), Value::Integer(36));
+    assert_eq!(vm.stack_size(), 1);
+    assert_eq!(vm.call_depth(), 0);
+}
+
+#[test]
+fn test_declared_function_frame_metadata() {
+    let source = r#"
+        PUSH 1
+        CALL f
+        HALT
+    .func f arity=1 locals=4
+        LOAD 0
+        STORE 3
+        HALT
+    .endfunc
+    "#;
+    let module = Assembler::new().assemble_module(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+
+    // Halted inside the function with its frame still live
+    assert_eq!(vm.call_depth(), 1);
+    assert_eq!(vm.stack_size(), 0);
+}
+
+#[test]
+fn test_declared_function_requires_its_arguments() {
+    let source = r#"
+        CALL f
+        HALT
+    .func f arity=2
+        RET
+    .endfunc
+    "#;
+    let module = Assembler::new().assemble_module(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module_unverified(module).unwrap();
+    match vm.run() {
+        Err(VmError::ExecutionErrorAt {
+            error: ExecutionError::ArityMismatch { function, expected, available },
+            ..
+        }) => {
+            assert_eq!(function, "f");
+            assert_eq!((expected, available), (2, 0));
+        }
+        other => panic!("expected an arity mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_declared_stack_effect_is_checked_on_return() {
+    let source = r#"
+        PUSH 3
+        CALL pair
+        HALT
+    .func pair arity=1 returns=2
+        LOAD 0
+        DUP
+        RET
+    .endfunc
+    "#;
+    let module = Assembler::new().assemble_module(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module(module).unwrap();
+    vm.run().unwrap();
+    assert_eq!(vm.stack_size(), 2);
+
+    // Leaving one value where two were declared is an error
+    let module = Assembler::new()
+        .assemble_module(&source.replace("DUP", "PUSH 1\n        POP"))
+        .unwrap();
+    assert!(matches!(vm.load_module(module.clone()), Err(VmError::VerificationFailed(_))));
+    vm.load_module_unverified(module).unwrap();
+    match vm.run() {
+        Err(VmError::ExecutionErrorAt {
+            error: ExecutionError::StackEffectMismatch { expected, actual, .. },
+            ..
+        }) => assert_eq!((expected, actual), (2, 1)),
+        other => panic!("expected a stack effect mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_execution_errors_carry_context() {
+    let source = r#"
+    PUSH 9
+    PUSH 0
+    CALL divide
+    HALT
+.func divide arity=2 returns=1
+    PUSH 1
+    LOAD 0
+    LOAD 1
+    DIV
+    RET
+.endfunc
+"#;
+    let module = Assembler::new().with_source_name("div.svm").assemble_module(source).unwrap();
+    let mut vm = VirtualMachine::new();
+    vm.load_module_unverified(module).unwrap();
+    let error = vm.run().unwrap_err();
+    match &error {
+        VmError::ExecutionErrorAt { error, pc, line, context, .. } => {
+            assert!(matches!(error, ExecutionError::DivisionByZero));
+            assert_eq!((*pc, *line), (7, Some(10)));
+            assert_eq!((context.opcode, context.stack_depth), (Opcode::Div, 3));
+            let backtrace = &context.backtrace;
+            let functions: Vec<Option<&str>> =
+                backtrace.iter().map(|entry| entry.function.as_deref()).collect();
+            assert_eq!(functions, [Some("divide"), None]);
+            assert_eq!(backtrace[1].line, Some(4));
+        }
+        other => panic!("expected a located error, got {:?}", other),
+    }
+    assert_eq!(
+        error.to_string(),
+        "Execution error at div.svm:10 (pc 7): Division by zero (in DIV, stack depth 3)"
+    );
+
+    // Wrapping leaves other errors alone
+    assert!(matches!(
+        VmError::NoProgram.at(0, Opcode::Add, 0, Vec::new()),
+        VmError::NoProgram
+    ));
+}
+
+#[test]
+fn test_load_module_validates_function_table() {
+    let module = BytecodeModule::new(vec![Instruction::new(Opcode::Halt, None)], vec![])
+        .with_functions(vec![FunctionInfo::new("f", 5, 0, 0)]);
+    let mut vm = VirtualMachine::new();
+    assert!(vm.load_module(module).is_err());
+
+    // Loading a plain program drops a previous function table
+    let module = BytecodeModule::new(vec![Instruction::new(Opcode::Halt, None)], vec![])
+        .with_functions(vec![FunctionInfo::new("f", 0, 0, 0)]);
+    vm.load_module(module).unwrap();
+    assert_eq!(vm.functions().len(), 1);
+    vm.load_program(vec![Instruction::new(Opcode::Halt, None)]);
+    assert!(vm.functions().is_empty());
+}